@@ -40,6 +40,28 @@ fn voxel_data_extract_material_index(voxel_data: u32) -> u32 {
     return voxel_data & 255u;
 }
 
+// 方块光照 [12-15] 位 转成 0-1
+fn voxel_data_extract_block_light(voxel_data: u32) -> f32 {
+    return f32(voxel_data >> 12u & 15u) / 15.0;
+}
+
+// 天空光照 [16-19] 位 转成 0-1
+fn voxel_data_extract_sky_light(voxel_data: u32) -> f32 {
+    return f32(voxel_data >> 16u & 15u) / 15.0;
+}
+
+// 方块光源的颜色 偏暖
+const BLOCK_LIGHT_COLOR: vec3<f32> = vec3<f32>(1.0, 0.85, 0.6);
+// 完全没有光照的地方保留的亮度
+const MIN_LIGHT_FACTOR: f32 = 0.03;
+
+// 纯色显示时每种体素的颜色 由材质下标散列得到 偏灰一点方便看清光照
+fn voxel_flat_color(layer: u32) -> vec4<f32> {
+    let h = layer * 2654435761u;
+    let rgb = vec3<f32>(f32(h >> 24u & 255u), f32(h >> 16u & 255u), f32(h >> 8u & 255u)) / 255.0;
+    return vec4<f32>(mix(vec3<f32>(0.6), rgb, 0.5), 1.0);
+}
+
 
 
 
@@ -113,7 +135,14 @@ fn fragment(in: FragmentInput) -> @location(0) vec4<f32> {
     // pbr_input.material.reflectance = 0.7;
 
     pbr_input.flags |= MESH_FLAGS_SHADOW_RECEIVER_BIT;
+#ifdef VOXEL_FLAT_COLOR
+    pbr_input.material.base_color = voxel_flat_color(u32(layer));
+#else
     pbr_input.material.base_color = textureSample(textures[layer], nearest_sampler, in.uv);
+#endif
+    // 方块光照作为自发光 平方让衰减更明显
+    let block_light = voxel_data_extract_block_light(in.voxel_data);
+    pbr_input.material.emissive = vec4<f32>(pbr_input.material.base_color.rgb * BLOCK_LIGHT_COLOR * block_light * block_light, 1.0);
 
     pbr_input.frag_coord = in.frag_coord;
     pbr_input.world_position =  vec4<f32>(in.world_position, 1.0);
@@ -124,7 +153,11 @@ fn fragment(in: FragmentInput) -> @location(0) vec4<f32> {
     pbr_input.N = normalize(mfn::mesh_normal_local_to_world(in.voxel_normal));
     pbr_input.V = fns::calculate_view(vec4<f32>(in.world_position, 1.0), pbr_input.is_orthographic);
     
-    return tone_mapping(fns::pbr(pbr_input),view.color_grading);
+    // 天空光和方块光取最大值 洞穴里没有光照的地方接近全黑
+    let light = max(block_light, voxel_data_extract_sky_light(in.voxel_data));
+    let light_factor = mix(MIN_LIGHT_FACTOR, 1.0, light * light);
+    let color = fns::pbr(pbr_input);
+    return tone_mapping(vec4<f32>(color.rgb * light_factor, color.a), view.color_grading);
 }
 
 
@@ -0,0 +1,232 @@
+// 地形生成的性能测试
+// 每次迭代处理一个区块 报告的时间就是每个区块的时间 目标是每个区块 2ms 以内
+// 运行: cargo bench --bench generation
+
+use bevy::prelude::IVec3;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use just_join::{
+    client::voxels::{mesh::gen_mesh, voxel_materail_config::MaterailConfiguration},
+    server::gen_pool::GenPool,
+    voxel_world::{
+        biomes::{
+            biomes_generate, biomes_noise, build_biomes_noise, tree_noise, PanelShape, SampleShape,
+        },
+        chunk::ChunkKey,
+        chunk_map::ChunkMap,
+        gen_config::{gen_config, COLUMN_MAX_CHUNK_Y, COLUMN_MIN_CHUNK_Y},
+        map_generator::{gen_chunk_data_by_seed, terrain_tops},
+        structure::make_structures_for_chunk,
+        voxel::Voxel,
+    },
+    CHUNK_SIZE, CHUNK_SIZE_U32, DEFAULT_SEED, MATERIAL_RON,
+};
+use ndshape::ConstShape;
+
+const SEED: i32 = DEFAULT_SEED;
+// 查找代表性区块的范围(区块)
+const SEARCH_RADIUS: i32 = 16;
+
+// 范围内地形最高的区块列 取地表所在的区块 作为慢的情况
+fn mountain_chunk() -> ChunkKey {
+    let config = gen_config();
+    let mut best = (f32::MIN, IVec3::ZERO);
+    for x in -SEARCH_RADIUS..=SEARCH_RADIUS {
+        for z in -SEARCH_RADIUS..=SEARCH_RADIUS {
+            let tops = terrain_tops(ChunkKey(IVec3::new(x, 0, z)), SEED, &config);
+            let mean = tops.iter().sum::<f32>() / tops.len() as f32;
+            if mean > best.0 {
+                best = (mean, IVec3::new(x, 0, z));
+            }
+        }
+    }
+    let (top, mut key) = best;
+    key.y = (top / CHUNK_SIZE as f32).floor() as i32;
+    ChunkKey(key)
+}
+
+// 原点上方第一个全是空气的区块 作为快的情况
+fn air_chunk() -> ChunkKey {
+    for y in (COLUMN_MIN_CHUNK_Y..=COLUMN_MAX_CHUNK_Y).rev() {
+        let key = ChunkKey(IVec3::new(0, y, 0));
+        let (voxels, _) = gen_chunk_data_by_seed(SEED, key);
+        if voxels.iter().all(|v| v.id == Voxel::EMPTY.id) {
+            return key;
+        }
+    }
+    panic!("原点上方没有全是空气的区块");
+}
+
+fn sample_chunks() -> Vec<(&'static str, ChunkKey)> {
+    vec![
+        ("air", air_chunk()),
+        ("origin_surface", {
+            let tops = terrain_tops(ChunkKey(IVec3::ZERO), SEED, &gen_config());
+            ChunkKey(IVec3::new(
+                0,
+                (tops[0] / CHUNK_SIZE as f32).floor() as i32,
+                0,
+            ))
+        }),
+        ("mountain", mountain_chunk()),
+    ]
+}
+
+fn bench_stages(c: &mut Criterion) {
+    let chunks = sample_chunks();
+    let mut group = c.benchmark_group("generation");
+    group.throughput(Throughput::Elements(1));
+
+    let config = gen_config();
+    let frequency = config.biome_frequency();
+    for (name, key) in chunks.iter() {
+        // 不使用缓存 重复同一个区块时 biomes_noise 只会测到缓存的读取
+        group.bench_with_input(BenchmarkId::new("biomes_noise", name), key, |b, key| {
+            b.iter(|| build_biomes_noise(black_box(*key), SEED, frequency))
+        });
+        group.bench_with_input(BenchmarkId::new("tree_noise", name), key, |b, key| {
+            b.iter(|| tree_noise(black_box(*key), SEED))
+        });
+        group.bench_with_input(BenchmarkId::new("terrain_tops", name), key, |b, key| {
+            b.iter(|| terrain_tops(black_box(*key), SEED, &config))
+        });
+        // 每一列都作为地表 群落生成最多的情况
+        let surface: Vec<u32> = (0..PanelShape::SIZE)
+            .map(|index| {
+                let [x, z] = PanelShape::delinearize(index);
+                SampleShape::linearize([x, CHUNK_SIZE_U32 / 2, z])
+            })
+            .collect();
+        let (voxels, _) = gen_chunk_data_by_seed(SEED, *key);
+        group.bench_with_input(BenchmarkId::new("biomes_generate", name), key, |b, key| {
+            b.iter_batched(
+                || (voxels.clone(), surface.clone()),
+                |(mut voxels, surface)| {
+                    biomes_generate(black_box(*key), SEED, surface, &mut voxels, &config)
+                },
+                criterion::BatchSize::SmallInput,
+            )
+        });
+        group.bench_with_input(BenchmarkId::new("structures", name), key, |b, key| {
+            b.iter_batched(
+                || voxels.clone(),
+                |mut voxels| make_structures_for_chunk(black_box(*key), SEED, &mut voxels),
+                criterion::BatchSize::SmallInput,
+            )
+        });
+        // 洞穴还没有启用 完整的流程包括上面所有的阶段
+        group.bench_with_input(BenchmarkId::new("full_chunk", name), key, |b, key| {
+            b.iter(|| gen_chunk_data_by_seed(SEED, black_box(*key)))
+        });
+    }
+    group.finish();
+}
+
+/**
+ * 一整列区块(从 COLUMN_MIN_CHUNK_Y 到 COLUMN_MAX_CHUNK_Y)的群落噪声 每次迭代是一列
+ * uncached 每个区块都重新采样 cached 每次换一列 只有第一个区块需要采样
+ * 一列 16 个区块: uncached 采样 16 个平面(16 * 256 个 Worley 值)
+ * cached 只采样 1 个平面 其余 15 次是加锁后复制 256 个 f32
+ * 所以 cached 的时间应该接近 uncached 的 1/16 复制占的比例越大差距越小
+ * 具体的时间和机器有关 运行 cargo bench --bench generation -- biomes_noise_column 查看
+ */
+fn bench_noise_column(c: &mut Criterion) {
+    let frequency = gen_config().biome_frequency();
+    let mut group = c.benchmark_group("biomes_noise_column");
+    group.throughput(Throughput::Elements(
+        (COLUMN_MAX_CHUNK_Y - COLUMN_MIN_CHUNK_Y + 1) as u64,
+    ));
+    group.bench_function("uncached", |b| {
+        let mut x = 0;
+        b.iter(|| {
+            x += 1;
+            for y in COLUMN_MIN_CHUNK_Y..=COLUMN_MAX_CHUNK_Y {
+                black_box(build_biomes_noise(
+                    ChunkKey(IVec3::new(x, y, 0)),
+                    SEED,
+                    frequency,
+                ));
+            }
+        })
+    });
+    group.bench_function("cached", |b| {
+        let mut x = 0;
+        b.iter(|| {
+            x += 1;
+            for y in COLUMN_MIN_CHUNK_Y..=COLUMN_MAX_CHUNK_Y {
+                black_box(biomes_noise(ChunkKey(IVec3::new(x, y, 0)), SEED, frequency));
+            }
+        })
+    });
+    group.finish();
+}
+
+// 网格按照整列生成 需要周围的区块列 这里每次迭代是一整列(16 个区块)
+fn bench_mesh(c: &mut Criterion) {
+    let material_config = MaterailConfiguration::new()
+        .read_file(String::from(MATERIAL_RON))
+        .unwrap();
+    let mut group = c.benchmark_group("mesh");
+    group.throughput(Throughput::Elements(1));
+    for (name, key) in [
+        ("origin", ChunkKey(IVec3::ZERO)),
+        ("mountain", {
+            let mut key = mountain_chunk();
+            key.0.y = 0;
+            key
+        }),
+    ] {
+        let mut chunk_map = ChunkMap::new();
+        for dx in -1..=1 {
+            for dz in -1..=1 {
+                for y in COLUMN_MIN_CHUNK_Y..=COLUMN_MAX_CHUNK_Y {
+                    let neighbor = ChunkKey(IVec3::new(key.0.x + dx, y, key.0.z + dz));
+                    chunk_map.write_chunk(neighbor, gen_chunk_data_by_seed(SEED, neighbor).0);
+                }
+            }
+        }
+        let voxels = chunk_map.get_with_neighbor_full_y(key);
+        let seeds = chunk_map.light_seeds_near(key);
+        group.bench_function(BenchmarkId::new("gen_mesh", name), |b| {
+            b.iter(|| gen_mesh(voxels.clone(), &seeds, material_config.clone()))
+        });
+    }
+    group.finish();
+}
+
+// 线程池的吞吐量 每次迭代生成 GEN_POOL_CHUNKS 个区块 比较不同的线程数
+const GEN_POOL_CHUNKS: i32 = 64;
+
+fn bench_gen_pool(c: &mut Criterion) {
+    let mut group = c.benchmark_group("gen_pool");
+    group.throughput(Throughput::Elements(GEN_POOL_CHUNKS as u64));
+    group.sample_size(10);
+    let keys: Vec<ChunkKey> = (0..GEN_POOL_CHUNKS)
+        .map(|i| ChunkKey(IVec3::new(i % 8, 0, i / 8)))
+        .collect();
+    let max_threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    for threads in [1, 2, 4, 8].into_iter().filter(|t| *t <= max_threads) {
+        let mut pool = GenPool::new(threads, keys.len());
+        group.bench_with_input(BenchmarkId::new("threads", threads), &keys, |b, keys| {
+            b.iter(|| {
+                for key in keys.iter() {
+                    assert!(pool.try_request(SEED, *key));
+                }
+                let mut done = 0;
+                while done < keys.len() {
+                    done += pool.take_results().len();
+                    std::thread::yield_now();
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_stages,
+    bench_noise_column,
+    bench_mesh,
+    bench_gen_pool
+);
+criterion_main!(benches);
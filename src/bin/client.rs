@@ -4,6 +4,10 @@ use bevy::{
     asset::ChangeWatcher,
     diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin},
     prelude::{App, AssetPlugin, PluginGroup, Update},
+    render::{
+        settings::{WgpuFeatures, WgpuSettings},
+        RenderPlugin,
+    },
     window::{ExitCondition, WindowPlugin, WindowMode, Window},
     DefaultPlugins,
 };
@@ -13,21 +17,71 @@ use bevy_egui::EguiPlugin;
 use bevy_mod_billboard::prelude::BillboardPlugin;
 use bevy_renet::{transport::NetcodeClientPlugin, RenetClientPlugin};
 use bevy_sprite3d::Sprite3dPlugin;
+use clap::{error::ErrorKind, CommandFactory, Parser};
 use just_join::{
     client::{
         debug::ClientDebugPlugin,
+        render_scale::RenderScalePlugin,
+        settings::ClientSettingsPlugin,
         state_manager::{
             game::GamePlugin, menu::MenuPlugin, notification::NotificationPlugin,
-            splash::SplashPlugin, ConnectionAddr, GameState,
+            splash::SplashPlugin, ConnectionAddr, GameState, QuickJoin,
         },
         ui::UiResourcePlugin,
     },
     staff::StaffInfoPlugin,
     tools::inspector_egui::inspector_ui,
-    voxel_world::voxel_mesh::VoxelMeshPlugin,
+    voxel_world::{voxel_mesh::VoxelMeshPlugin, voxel_registry::VoxelRegistryPlugin},
     CLIENT_DEBUG, CLIENT_FPS,
 };
+
+#[derive(Parser)]
+#[command(name = "client", about = "just join client")]
+struct ClientArgs {
+    /// server address (ip:port), skips the menu and joins directly
+    #[arg(long)]
+    connect: Option<String>,
+    /// nickname used to join the server
+    #[arg(long)]
+    name: Option<String>,
+}
+
+// 读取启动参数 参数不正确时打印用法 回到菜单 --help 和 --version 打印后直接退出
+fn connection_from_args() -> (ConnectionAddr, bool) {
+    let args = match ClientArgs::try_parse() {
+        Ok(args) => args,
+        Err(err)
+            if matches!(
+                err.kind(),
+                ErrorKind::DisplayHelp | ErrorKind::DisplayVersion
+            ) =>
+        {
+            err.exit()
+        }
+        Err(err) => {
+            let _ = err.print();
+            return (ConnectionAddr::default(), false);
+        }
+    };
+    let Some(connect) = args.connect else {
+        let addr = match args.name {
+            Some(name) => ConnectionAddr::default().with_nickname(name),
+            None => ConnectionAddr::default(),
+        };
+        return (addr, false);
+    };
+    match ConnectionAddr::from_args(&connect, args.name) {
+        Ok(addr) => (addr, true),
+        Err(err) => {
+            eprintln!("启动参数错误 {}", err);
+            eprintln!("{}", ClientArgs::command().render_usage());
+            (ConnectionAddr::default(), false)
+        }
+    }
+}
+
 fn main() {
+    let (connection_addr, quick_join) = connection_from_args();
     let mut app = App::new();
     app.add_plugins(WindowPlugin {
         exit_condition: ExitCondition::OnAllClosed,
@@ -44,11 +98,21 @@ fn main() {
                 watch_for_changes: ChangeWatcher::with_delay(Duration::from_millis(200)),
                 ..Default::default()
             })
+            // 线框模式需要
+            .set(RenderPlugin {
+                wgpu_settings: WgpuSettings {
+                    features: WgpuFeatures::POLYGON_MODE_LINE,
+                    ..Default::default()
+                },
+            })
             .build()
             .disable::<WindowPlugin>(),
     );
     app.add_state::<GameState>();
-    app.insert_resource(ConnectionAddr::default());
+    app.insert_resource(connection_addr);
+    if quick_join {
+        app.insert_resource(QuickJoin);
+    }
     app.add_plugins(LocalizePlugin);
     app.insert_resource(Localize::from_asset_path("translation.csv"));
     app.add_plugins(BillboardPlugin);
@@ -59,6 +123,9 @@ fn main() {
     app.add_plugins(UiResourcePlugin);
     app.add_plugins(Sprite3dPlugin);
     app.add_plugins(VoxelMeshPlugin);
+    app.add_plugins(VoxelRegistryPlugin);
+    app.add_plugins(ClientSettingsPlugin);
+    app.add_plugins(RenderScalePlugin);
 
     app.add_plugins((SplashPlugin, MenuPlugin, NotificationPlugin, GamePlugin));
     // 调试工具
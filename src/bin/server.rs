@@ -1,31 +1,55 @@
 use std::{net::UdpSocket, time::SystemTime};
 
 use bevy::prelude::{
-    App, Camera3dBundle, Commands, PointLightBundle, Res, ResMut, Startup, Transform, Update, Vec3,
+    apply_deferred, App, Camera3dBundle, Commands, IntoSystemConfigs, PointLightBundle, Res,
+    ResMut, Startup, Transform, Update, Vec3,
 };
 use bevy_rapier3d::prelude::{NoUserData, RapierPhysicsPlugin};
 use bevy_renet::{
     renet::{
-        transport::{NetcodeServerTransport, ServerAuthentication, ServerConfig},
+        transport::{NetcodeServerTransport, ServerAuthentication, ServerConfig as NetcodeConfig},
         RenetServer,
     },
     transport::NetcodeServerPlugin,
     RenetServerPlugin,
 };
+use clap::Parser;
 use just_join::{
     common::ServerClipSpheresPlugin,
     connection_config,
     server::{
-        async_chunk::ChunkDataPlugin, chunk::ServerChunkPlugin,
-        cross_through_check::CrossTroughCheckPlugin, deal_message_system,
-        object_filing::ObjectFilingPlugin, player::ServerLobby, server_connect_system,
-        sp_physics::SpPhysicsPlugin, staff_rule_sync::ServerStaffRulePlugin, sync_body_and_head,
+        async_chunk::ChunkDataPlugin,
+        autosave::AutosavePlugin,
+        chunk::ServerChunkPlugin,
+        chunk_budget::ChunkBudgetPlugin,
+        config::{ServerConfig, ServerConfigPlugin},
+        cross_through_check::CrossTroughCheckPlugin,
+        deal_message_system,
+        death::PlayerDeathPlugin,
+        disconnect::ServerDisconnectPlugin,
+        falling_block::FallingBlockPlugin,
+        gen_reload::GenConfigPlugin,
+        hold_move_targets,
+        light_query::LightQueryPlugin,
+        mob::ServerMobPlugin,
+        object_filing::ObjectFilingPlugin,
+        physics_config::PhysicsConfigPlugin,
+        player::{MaxPlayers, ServerAdmins, ServerLobby},
+        scripting::ScriptingPlugin,
+        server_command::ServerCommandPlugin,
+        server_connect_system,
+        sp_physics::SpPhysicsPlugin,
+        staff_rule_sync::ServerStaffRulePlugin,
+        sync_body_and_head,
         terrain_physics::TerrainPhysicsPlugin,
+        tick_rate::{ServerTickRate, ServerTickRatePlugin},
     },
     sky::ServerSkyPlugins,
     staff::ServerStaffInfoPlugin,
-    voxel_world::{biomes::OtherTreePlugin, voxel_mesh::VoxelMeshPlugin},
-    PROTOCOL_ID,
+    voxel_world::{
+        biomes::OtherTreePlugin, voxel_mesh::VoxelMeshPlugin, voxel_registry::VoxelRegistryPlugin,
+    },
+    PROTOCOL_ID, SERVER_CONFIG_PATH,
 };
 use renet_visualizer::RenetServerVisualizer;
 use seldom_state::StateMachinePlugin;
@@ -49,7 +73,56 @@ use bevy::{
     MinimalPlugins,
 };
 
-fn new_renet_server() -> (RenetServer, NetcodeServerTransport) {
+#[derive(Parser)]
+#[command(name = "server", about = "just join server")]
+struct ServerArgs {
+    /// path of the server config file
+    #[arg(long, default_value = SERVER_CONFIG_PATH)]
+    config: String,
+    /// server ticks per second, overrides the config file
+    #[arg(long)]
+    tick_rate: Option<u32>,
+    /// max players on the server, overrides the config file
+    #[arg(long)]
+    max_players: Option<usize>,
+    /// chunk data bytes sent to each client per tick, overrides the config file
+    #[arg(long)]
+    chunk_budget: Option<usize>,
+    /// chunk generation threads, 0 uses the cpu cores minus 2, overrides the config file
+    #[arg(long)]
+    gen_threads: Option<usize>,
+    /// usernames of the server admins, added to the config file
+    #[arg(long = "admin")]
+    admins: Vec<String>,
+}
+
+// 读取配置文件 命令行的参数优先 配置错误时直接退出
+fn load_config(args: ServerArgs) -> ServerConfig {
+    let mut config = ServerConfig::load_or_create(&args.config).unwrap_or_else(|err| {
+        eprintln!("服务端配置错误 {}", err);
+        std::process::exit(1);
+    });
+    if let Some(tick_rate) = args.tick_rate {
+        config.tick_rate = tick_rate;
+    }
+    if let Some(max_players) = args.max_players {
+        config.max_players = max_players;
+    }
+    if let Some(chunk_budget) = args.chunk_budget {
+        config.chunk_budget = chunk_budget;
+    }
+    if let Some(gen_threads) = args.gen_threads {
+        config.gen_threads = gen_threads;
+    }
+    config.admins.extend(args.admins);
+    if let Err(err) = config.validate() {
+        eprintln!("服务端参数错误 {}", err);
+        std::process::exit(1);
+    }
+    config
+}
+
+fn new_renet_server(max_players: usize) -> (RenetServer, NetcodeServerTransport) {
     let server = RenetServer::new(connection_config());
 
     let public_addr = "127.0.0.1:5000".parse().unwrap();
@@ -58,8 +131,9 @@ fn new_renet_server() -> (RenetServer, NetcodeServerTransport) {
         .duration_since(SystemTime::UNIX_EPOCH)
         .unwrap();
     // FIXME: 这里的写法 和master分支有出入 没有多态主机
-    let server_config = ServerConfig {
-        max_clients: 64,
+    let server_config = NetcodeConfig {
+        // 多留一个位置 用来通知连接的客户端服务器已满
+        max_clients: max_players + 1,
         protocol_id: PROTOCOL_ID,
         authentication: ServerAuthentication::Unsecure,
         public_addr,
@@ -92,11 +166,16 @@ fn setup(mut commands: Commands) {
 }
 
 fn main() {
+    let config = load_config(ServerArgs::parse());
+    let tick_rate = ServerTickRate {
+        rate: config.tick_rate,
+    };
     let mut app = App::new();
 
     #[cfg(feature = "server_ui")]
     {
         app.add_plugins(DefaultPlugins);
+        app.insert_resource(tick_rate.winit_settings());
         app.add_plugins(RapierDebugRenderPlugin::default());
         app.add_plugins(EguiPlugin);
         app.add_plugins(FpsCameraPlugin::default());
@@ -104,7 +183,7 @@ fn main() {
 
     #[cfg(feature = "headless")]
     {
-        app.add_plugins(MinimalPlugins);
+        app.add_plugins(MinimalPlugins.set(tick_rate.runner()));
         app.add_plugins(AssetPlugin::default());
         app.add_asset::<Mesh>();
     }
@@ -114,9 +193,18 @@ fn main() {
     app.add_plugins(RapierPhysicsPlugin::<NoUserData>::default());
     app.add_plugins(LookTransformPlugin);
 
+    // 其他插件会读取配置 需要最先添加
+    app.add_plugins((
+        ServerConfigPlugin {
+            config: config.clone(),
+        },
+        GenConfigPlugin,
+    ));
+
     // 这里添加必要的系统
     app.add_plugins((
         StateMachinePlugin,
+        VoxelRegistryPlugin,
         ServerStaffInfoPlugin,
         ServerClipSpheresPlugin,
         ServerChunkPlugin,
@@ -129,13 +217,32 @@ fn main() {
         OtherTreePlugin,
         VoxelMeshPlugin,
         SpPhysicsPlugin,
+        ServerCommandPlugin,
+    ));
+    app.add_plugins((
+        PhysicsConfigPlugin,
+        ServerDisconnectPlugin,
+        ServerMobPlugin,
+        ServerTickRatePlugin {
+            rate: config.tick_rate,
+        },
+        ChunkBudgetPlugin {
+            bytes_per_tick: config.chunk_budget,
+        },
+        AutosavePlugin,
+        FallingBlockPlugin,
+        PlayerDeathPlugin,
+        ScriptingPlugin,
+        LightQueryPlugin,
     ));
 
-    let (server, transport) = new_renet_server();
+    let (server, transport) = new_renet_server(config.max_players);
     app.insert_resource(server);
     app.insert_resource(transport);
     app.insert_resource(RenetServerVisualizer::<200>::default());
     app.insert_resource(ServerLobby::default());
+    app.insert_resource(MaxPlayers(config.max_players));
+    app.insert_resource(ServerAdmins(config.admins.into_iter().collect()));
 
     app.add_systems(Startup, setup);
     app.add_systems(Update, update_visulizer_system);
@@ -145,7 +252,7 @@ fn main() {
         Update,
         (
             server_connect_system,
-            deal_message_system,
+            (deal_message_system, apply_deferred, hold_move_targets).chain(),
             sync_body_and_head,
         ),
     );
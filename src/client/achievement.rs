@@ -0,0 +1,229 @@
+// 成就
+// 其他系统发送 AchievementEvent 这里按照 ACHIEVEMENTS 中的条件检查是否解锁
+// 添加新的成就只需要在 ACHIEVEMENTS 中添加一条 解锁记录保存在文件中
+
+use std::io::Write;
+
+use bevy::{
+    prelude::{
+        in_state, Event, EventReader, EventWriter, IntoSystemConfigs, Local, Plugin, Query, Res,
+        ResMut, Resource, Transform, Update, With,
+    },
+    utils::HashSet,
+};
+use bevy_easy_localize::Localize;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    voxel_world::{
+        biomes::{refresh_sampler, BiomeHeightSampler, BiomeKind},
+        map_database::WorldSeed,
+    },
+    ACHIEVEMENTS_PATH,
+};
+
+use super::{
+    player::controller::CharacterController,
+    state_manager::{notification::Notification, GameState},
+};
+
+// 游戏中发生的事情
+#[derive(Debug, Clone, Copy, PartialEq, Event)]
+pub enum AchievementEvent {
+    // 放置了一个方块
+    BlockPlaced,
+    // 破坏了一个方块
+    BlockBroken,
+    // 进入了新的群落
+    BiomeEntered(BiomeKind),
+    // 一次下落的高度
+    Fell(f32),
+}
+
+pub struct AchievementDef {
+    // 保存在文件中的 id 不要修改
+    pub id: &'static str,
+    // 翻译的关键字
+    pub name: &'static str,
+    pub check: fn(&AchievementEvent) -> bool,
+}
+
+pub const ACHIEVEMENTS: &[AchievementDef] = &[
+    AchievementDef {
+        id: "first_block_placed",
+        name: "第一块方块",
+        check: |event| matches!(event, AchievementEvent::BlockPlaced),
+    },
+    AchievementDef {
+        id: "first_block_broken",
+        name: "开始挖掘",
+        check: |event| matches!(event, AchievementEvent::BlockBroken),
+    },
+    AchievementDef {
+        id: "reached_snow_biome",
+        name: "雪地探险",
+        check: |event| matches!(event, AchievementEvent::BiomeEntered(BiomeKind::Snow)),
+    },
+    AchievementDef {
+        id: "reached_sand_biome",
+        name: "沙漠旅人",
+        check: |event| matches!(event, AchievementEvent::BiomeEntered(BiomeKind::Sand)),
+    },
+    AchievementDef {
+        id: "fell_20_blocks",
+        name: "高处坠落",
+        check: |event| matches!(event, AchievementEvent::Fell(height) if *height >= 20.0),
+    },
+    AchievementDef {
+        id: "dove_100_blocks",
+        name: "深渊跳水",
+        check: |event| matches!(event, AchievementEvent::Fell(height) if *height >= 100.0),
+    },
+];
+
+/**
+ * 已经解锁的成就
+ */
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Resource)]
+pub struct Achievements {
+    pub unlocked: HashSet<String>,
+}
+
+impl Achievements {
+    // 没有文件时没有解锁任何成就
+    pub fn load() -> Self {
+        match std::fs::File::open(ACHIEVEMENTS_PATH) {
+            Ok(file) => ron::de::from_reader(file).unwrap_or_else(|err| {
+                println!("成就读取失败: {}", err);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn save(&self) {
+        let res = ron::to_string(self).unwrap();
+        match std::fs::File::create(ACHIEVEMENTS_PATH) {
+            Ok(mut file) => {
+                if let Err(err) = file.write_all(res.as_bytes()) {
+                    println!("成就保存失败: {}", err);
+                }
+            }
+            Err(err) => println!("成就保存失败: {}", err),
+        }
+    }
+
+    pub fn is_unlocked(&self, id: &str) -> bool {
+        self.unlocked.contains(id)
+    }
+
+    /**
+     * 返回这个事件新解锁的成就
+     */
+    pub fn unlock(&mut self, event: &AchievementEvent) -> Vec<&'static AchievementDef> {
+        let mut unlocked = Vec::new();
+        for def in ACHIEVEMENTS {
+            if !self.is_unlocked(def.id) && (def.check)(event) {
+                self.unlocked.insert(String::from(def.id));
+                unlocked.push(def);
+            }
+        }
+        unlocked
+    }
+}
+
+pub struct AchievementPlugin;
+
+impl Plugin for AchievementPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.add_event::<AchievementEvent>();
+        app.insert_resource(Achievements::load());
+        app.add_systems(
+            Update,
+            (track_biome, track_fall, unlock_achievements).run_if(in_state(GameState::Game)),
+        );
+    }
+}
+
+fn unlock_achievements(
+    mut events: EventReader<AchievementEvent>,
+    mut achievements: ResMut<Achievements>,
+    mut notification: ResMut<Notification>,
+    localize: Res<Localize>,
+) {
+    let mut changed = false;
+    for event in events.iter() {
+        for def in achievements.unlock(event) {
+            changed = true;
+            notification.success(format!(
+                "{}: {}",
+                localize.get("成就解锁"),
+                localize.get(def.name)
+            ));
+        }
+    }
+    if changed {
+        achievements.save();
+    }
+}
+
+// 玩家进入不同的群落
+fn track_biome(
+    world_seed: Option<Res<WorldSeed>>,
+    player_query: Query<&Transform, With<CharacterController>>,
+    mut events: EventWriter<AchievementEvent>,
+    mut sampler: Local<Option<(i32, BiomeHeightSampler)>>,
+    mut last_biome: Local<Option<BiomeKind>>,
+) {
+    let (Some(world_seed), Ok(transform)) = (world_seed, player_query.get_single()) else {
+        return;
+    };
+    let sampler = refresh_sampler(&mut sampler, world_seed.0);
+    let biome = sampler.biome_at(transform.translation.x, transform.translation.z);
+    if *last_biome != Some(biome) {
+        *last_biome = Some(biome);
+        events.send(AchievementEvent::BiomeEntered(biome));
+    }
+}
+
+/**
+ * 记录一次下落的高度 从开始下降到停止下降
+ * 保存 (开始下落的高度, 上一帧的高度)
+ */
+fn track_fall(
+    player_query: Query<&Transform, With<CharacterController>>,
+    mut events: EventWriter<AchievementEvent>,
+    mut fall: Local<Option<(f32, f32)>>,
+    mut last_y: Local<Option<f32>>,
+) {
+    let Ok(transform) = player_query.get_single() else {
+        *fall = None;
+        *last_y = None;
+        return;
+    };
+    let y = transform.translation.y;
+    let Some(previous) = last_y.replace(y) else {
+        return;
+    };
+    if y < previous {
+        let start = fall.map_or(previous, |(start, _)| start);
+        *fall = Some((start, y));
+    } else if let Some((start, end)) = fall.take() {
+        events.send(AchievementEvent::Fell(start - end));
+    }
+}
+
+#[test]
+fn test_unlock_achievements() {
+    let mut achievements = Achievements::default();
+    let unlocked = achievements.unlock(&AchievementEvent::Fell(120.0));
+    let ids: Vec<&str> = unlocked.iter().map(|def| def.id).collect();
+    assert_eq!(ids, vec!["fell_20_blocks", "dove_100_blocks"]);
+    // 已经解锁的不会再次解锁
+    assert!(achievements
+        .unlock(&AchievementEvent::Fell(120.0))
+        .is_empty());
+    assert!(achievements
+        .unlock(&AchievementEvent::BiomeEntered(BiomeKind::Basic))
+        .is_empty());
+}
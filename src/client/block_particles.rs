@@ -0,0 +1,191 @@
+// 破坏方块时的碎片粒子
+// 服务端广播方块的修改 实心方块变成空气时在方块的位置生成一组小方块
+// 碎片使用被破坏的体素的网格和材质 所以和方块的贴图一致 所有看到修改的玩家都会显示
+// 数量和存在的时间在设置中修改 关闭后不再生成
+
+use bevy::{
+    prelude::{
+        in_state, Assets, Commands, Component, Entity, Event, EventReader, Handle,
+        IntoSystemConfigs, MaterialMeshBundle, Mesh, OnExit, Plugin, Query, Res, ResMut, Resource,
+        Time, Transform, Update, Vec3, With,
+    },
+    utils::HashMap,
+};
+use rand::Rng;
+
+use crate::voxel_world::voxel::Voxel;
+
+use super::{
+    settings::{GraphicsSettings, BREAK_PARTICLES_RANGE, BREAK_PARTICLE_LIFETIME_RANGE},
+    state_manager::GameState,
+    voxels::{
+        mesh::gen_one_volex_mesh, mesh_material::MaterialStorge,
+        voxel_materail_config::MaterailConfiguration,
+    },
+};
+
+// 碎片的大小(格) 重力和初始速度
+const PARTICLE_SIZE: f32 = 0.15;
+const PARTICLE_GRAVITY: f32 = 18.0;
+const PARTICLE_SPEED: f32 = 4.0;
+// 同时存在的碎片上限 连续破坏时不再生成
+const MAX_BREAK_PARTICLES: usize = 512;
+
+// 方块被破坏 center 是方块的中心
+#[derive(Debug, Clone, Event)]
+pub struct BlockBrokenEvent {
+    pub center: Vec3,
+    pub voxel: Voxel,
+}
+
+#[derive(Debug, Component)]
+pub struct BreakParticle {
+    pub velocity: Vec3,
+    pub age: f32,
+    pub lifetime: f32,
+}
+
+// 每种体素的碎片网格
+#[derive(Debug, Default, Resource)]
+pub struct BreakParticleMeshes(HashMap<u8, Handle<Mesh>>);
+
+pub struct BlockParticlePlugin;
+
+impl Plugin for BlockParticlePlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.add_event::<BlockBrokenEvent>();
+        app.init_resource::<BreakParticleMeshes>();
+        app.add_systems(
+            Update,
+            (spawn_break_particles, update_break_particles)
+                .chain()
+                .run_if(in_state(GameState::Game)),
+        );
+        app.add_systems(OnExit(GameState::Game), setdown_break_particles);
+    }
+}
+
+/**
+ * 碎片一帧的运动 返回新的位置和速度
+ * 碰到方块不处理 碎片存在的时间很短
+ */
+pub fn particle_step(translation: Vec3, velocity: Vec3, delta: f32) -> (Vec3, Vec3) {
+    let velocity = velocity - Vec3::Y * PARTICLE_GRAVITY * delta;
+    (translation + velocity * delta, velocity)
+}
+
+// 最后三分之一的时间里缩小消失
+pub fn particle_scale(age: f32, lifetime: f32) -> f32 {
+    let left = 1.0 - age / lifetime;
+    (left * 3.0).clamp(0.0, 1.0) * PARTICLE_SIZE
+}
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_break_particles(
+    mut commands: Commands,
+    mut events: EventReader<BlockBrokenEvent>,
+    settings: Res<GraphicsSettings>,
+    mut particle_meshes: ResMut<BreakParticleMeshes>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    materials: Res<MaterialStorge>,
+    material_config: Res<MaterailConfiguration>,
+    particles: Query<(), With<BreakParticle>>,
+) {
+    if !settings.break_particles {
+        events.clear();
+        return;
+    }
+    let count = settings
+        .break_particle_count
+        .clamp(*BREAK_PARTICLES_RANGE.start(), *BREAK_PARTICLES_RANGE.end());
+    let lifetime = settings.break_particle_lifetime.clamp(
+        *BREAK_PARTICLE_LIFETIME_RANGE.start(),
+        *BREAK_PARTICLE_LIFETIME_RANGE.end(),
+    );
+    let mut total = particles.iter().count();
+    let mut rng = rand::thread_rng();
+    for BlockBrokenEvent { center, voxel } in events.iter() {
+        if total + count > MAX_BREAK_PARTICLES {
+            continue;
+        }
+        let mesh = match particle_meshes.0.get(&voxel.id) {
+            Some(mesh) => mesh.clone(),
+            None => {
+                let Some(mesh) = gen_one_volex_mesh(*voxel, material_config.clone()) else {
+                    continue;
+                };
+                let mesh = meshes.add(mesh);
+                particle_meshes.0.insert(voxel.id, mesh.clone());
+                mesh
+            }
+        };
+        for _ in 0..count {
+            let offset = Vec3::new(
+                rng.gen_range(-0.4..0.4),
+                rng.gen_range(-0.4..0.4),
+                rng.gen_range(-0.4..0.4),
+            );
+            // 向外和向上飞出
+            let velocity = (offset + Vec3::Y * 0.5).normalize_or_zero()
+                * PARTICLE_SPEED
+                * rng.gen_range(0.5..1.0);
+            commands.spawn((
+                MaterialMeshBundle {
+                    mesh: mesh.clone(),
+                    material: materials.0.clone(),
+                    transform: Transform::from_translation(*center + offset)
+                        .with_scale(Vec3::splat(PARTICLE_SIZE)),
+                    ..Default::default()
+                },
+                BreakParticle {
+                    velocity,
+                    age: 0.0,
+                    lifetime: lifetime * rng.gen_range(0.7..1.0),
+                },
+            ));
+        }
+        total += count;
+    }
+}
+
+fn update_break_particles(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut BreakParticle, &mut Transform)>,
+) {
+    let delta = time.delta_seconds();
+    for (entity, mut particle, mut transform) in query.iter_mut() {
+        particle.age += delta;
+        if particle.age >= particle.lifetime {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        let (translation, velocity) =
+            particle_step(transform.translation, particle.velocity, delta);
+        transform.translation = translation;
+        transform.scale = Vec3::splat(particle_scale(particle.age, particle.lifetime));
+        particle.velocity = velocity;
+    }
+}
+
+fn setdown_break_particles(mut commands: Commands, query: Query<Entity, With<BreakParticle>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+#[test]
+fn test_break_particles() {
+    // 碎片先上升 然后落下
+    let (mut translation, mut velocity) = (Vec3::ZERO, Vec3::new(1.0, 4.0, 0.0));
+    (translation, velocity) = particle_step(translation, velocity, 0.1);
+    assert!(translation.y > 0.0 && translation.x > 0.0);
+    for _ in 0..10 {
+        (translation, velocity) = particle_step(translation, velocity, 0.1);
+    }
+    assert!(translation.y < 0.0 && velocity.y < 0.0);
+
+    assert_eq!(particle_scale(0.0, 1.0), PARTICLE_SIZE);
+    assert!(particle_scale(0.9, 1.0) < PARTICLE_SIZE);
+    assert_eq!(particle_scale(1.0, 1.0), 0.0);
+}
@@ -0,0 +1,299 @@
+// 聊天窗口和指令输入
+// 按 Enter 打开输入框聊天 按 / 打开输入框输入指令
+// 以 / 开头的内容交给 bevy_console 处理 结果显示在聊天记录中 其他的内容作为聊天消息发送
+// 需要发送 / 开头的聊天消息时 使用 // 转义
+
+use bevy::{
+    prelude::{
+        in_state, EventReader, EventWriter, Input, IntoSystemConfigs, KeyCode, Plugin, Query, Res,
+        ResMut, Resource, Update, With,
+    },
+    window::{PrimaryWindow, Window},
+};
+use bevy_console::{ConsoleCommandEntered, ConsoleConfiguration, PrintConsoleLine};
+use bevy_egui::{egui, EguiContexts, EguiSet};
+use bevy_renet::renet::RenetClient;
+
+use crate::server::permission::{command_level, PermissionLevel};
+
+use super::{
+    message_def::{server_command::ServerCommandMessage, ClientChannel},
+    player::controller::{set_cursor_grabbed, ControllerFlag},
+    state_manager::{game::PlayState, GameState},
+};
+
+// 最多保留的聊天记录
+pub const CHAT_LOG_LIMIT: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChatLineKind {
+    // 玩家的聊天消息
+    Chat,
+    // 私聊消息
+    Whisper,
+    // 玩家输入的指令
+    Command,
+    // 指令的输出
+    CommandOutput,
+}
+
+#[derive(Debug, Clone)]
+pub struct ChatLine {
+    pub kind: ChatLineKind,
+    pub text: String,
+}
+
+#[derive(Debug, Default, Resource)]
+pub struct ChatLog {
+    pub lines: Vec<ChatLine>,
+}
+
+impl ChatLog {
+    pub fn push(&mut self, kind: ChatLineKind, text: String) {
+        self.lines.push(ChatLine { kind, text });
+        if self.lines.len() > CHAT_LOG_LIMIT {
+            let overflow = self.lines.len() - CHAT_LOG_LIMIT;
+            self.lines.drain(..overflow);
+        }
+    }
+}
+
+#[derive(Debug, Default, Resource)]
+pub struct ChatInput {
+    pub open: bool,
+    pub text: String,
+}
+
+// 输入框打开时 其他的按键操作不生效
+pub fn chat_input_closed(chat_input: Res<ChatInput>) -> bool {
+    !chat_input.open
+}
+
+pub struct ChatPlugin;
+
+impl Plugin for ChatPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.init_resource::<ChatLog>();
+        app.init_resource::<ChatInput>();
+        app.add_systems(
+            Update,
+            (toggle_chat_input, collect_console_output).run_if(in_state(GameState::Game)),
+        );
+        app.add_systems(
+            Update,
+            chat_window
+                .run_if(in_state(PlayState::Main))
+                .after(EguiSet::InitContexts)
+                // 提交时按下的 Enter 不能再次打开输入框
+                .after(toggle_chat_input),
+        );
+    }
+}
+
+// 打开时释放光标 关闭时重新锁定光标
+fn toggle_chat_input(
+    keys: Res<Input<KeyCode>>,
+    mut chat_input: ResMut<ChatInput>,
+    mut flags: ResMut<ControllerFlag>,
+    mut primary_window: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    let Ok(mut window) = primary_window.get_single_mut() else {
+        return;
+    };
+    if !chat_input.open && (keys.just_pressed(KeyCode::Slash) || keys.just_pressed(KeyCode::Return))
+    {
+        chat_input.open = true;
+        chat_input.text = if keys.just_pressed(KeyCode::Slash) {
+            String::from("/")
+        } else {
+            String::new()
+        };
+        flags.flag = false;
+        set_cursor_grabbed(&mut window, false);
+    } else if chat_input.open && keys.just_pressed(KeyCode::Escape) {
+        close_chat_input(&mut chat_input, &mut flags, &mut window);
+    }
+}
+
+fn close_chat_input(chat_input: &mut ChatInput, flags: &mut ControllerFlag, window: &mut Window) {
+    chat_input.open = false;
+    chat_input.text.clear();
+    flags.flag = true;
+    set_cursor_grabbed(window, true);
+}
+
+// 把控制台的输出放到聊天记录中
+fn collect_console_output(
+    mut console_lines: EventReader<PrintConsoleLine>,
+    mut chat_log: ResMut<ChatLog>,
+) {
+    for PrintConsoleLine { line } in console_lines.iter() {
+        chat_log.push(ChatLineKind::CommandOutput, line.to_string());
+    }
+}
+
+// 输入的内容
+#[derive(Debug, PartialEq, Eq)]
+pub enum ChatSubmit {
+    Chat(String),
+    Command(String),
+}
+
+/**
+ * 解析输入的内容
+ * / 开头的是指令 // 开头的是以 / 开头的聊天消息
+ */
+pub fn parse_chat_input(text: &str) -> Option<ChatSubmit> {
+    let text = text.trim();
+    if text.is_empty() {
+        return None;
+    }
+    if let Some(escaped) = text.strip_prefix("//") {
+        return Some(ChatSubmit::Chat(format!("/{}", escaped)));
+    }
+    match text.strip_prefix('/') {
+        Some(command) => Some(ChatSubmit::Command(String::from(command.trim()))),
+        None => Some(ChatSubmit::Chat(String::from(text))),
+    }
+}
+
+// 列出所有可以使用的指令 权限不够的指令不显示
+fn help_lines(config: &ConsoleConfiguration, level: PermissionLevel) -> Vec<String> {
+    let mut lines = vec![String::from("/help - list available commands")];
+    for (name, command) in config.commands.iter() {
+        if command_level(name) > level {
+            continue;
+        }
+        match command.get_about() {
+            Some(about) => lines.push(format!("/{} - {}", name, about)),
+            None => lines.push(format!("/{}", name)),
+        }
+    }
+    lines
+}
+
+/**
+ * 执行输入的指令
+ * 未知的指令和权限不够的指令直接提示 不发给 bevy_console
+ */
+fn submit_command(
+    command: &str,
+    config: &ConsoleConfiguration,
+    level: PermissionLevel,
+    chat_log: &mut ChatLog,
+    command_entered: &mut EventWriter<ConsoleCommandEntered>,
+) {
+    let mut words = command.split_whitespace();
+    let Some(command_name) = words.next() else {
+        return;
+    };
+    chat_log.push(ChatLineKind::Command, format!("/{}", command));
+    if command_name == "help" {
+        for line in help_lines(config, level) {
+            chat_log.push(ChatLineKind::CommandOutput, line);
+        }
+        return;
+    }
+    if !config.commands.contains_key(command_name) {
+        chat_log.push(
+            ChatLineKind::CommandOutput,
+            format!("Unknown command: {}, use /help", command_name),
+        );
+        return;
+    }
+    if command_level(command_name) > level {
+        chat_log.push(
+            ChatLineKind::CommandOutput,
+            format!(
+                "Insufficient permission: requires {:?}",
+                command_level(command_name)
+            ),
+        );
+        return;
+    }
+    command_entered.send(ConsoleCommandEntered {
+        command_name: String::from(command_name),
+        args: words.map(String::from).collect(),
+    });
+}
+
+#[allow(clippy::too_many_arguments)]
+fn chat_window(
+    mut contexts: EguiContexts,
+    mut chat_input: ResMut<ChatInput>,
+    mut chat_log: ResMut<ChatLog>,
+    mut flags: ResMut<ControllerFlag>,
+    config: Res<ConsoleConfiguration>,
+    level: Option<Res<PermissionLevel>>,
+    mut command_entered: EventWriter<ConsoleCommandEntered>,
+    mut primary_window: Query<&mut Window, With<PrimaryWindow>>,
+    client: Option<ResMut<RenetClient>>,
+) {
+    let ctx = contexts.ctx_mut();
+    let mut submitted = false;
+    egui::Window::new("Chat")
+        .title_bar(false)
+        .resizable(false)
+        .frame(egui::Frame::none().fill(egui::Color32::BLACK.gamma_multiply(0.8)))
+        .default_height(200.0)
+        .default_width(360.0)
+        .anchor(egui::Align2::LEFT_BOTTOM, [0.0, 0.0])
+        .collapsible(false)
+        .show(ctx, |ui| {
+            egui::CentralPanel::default().show_inside(ui, |ui| {
+                egui::ScrollArea::vertical()
+                    .stick_to_bottom(true)
+                    .show(ui, |ui| {
+                        for line in chat_log.lines.iter() {
+                            let color = match line.kind {
+                                ChatLineKind::Chat => egui::Color32::WHITE,
+                                ChatLineKind::Whisper => egui::Color32::LIGHT_BLUE,
+                                ChatLineKind::Command => egui::Color32::LIGHT_GRAY,
+                                ChatLineKind::CommandOutput => egui::Color32::LIGHT_YELLOW,
+                            };
+                            ui.colored_label(color, &line.text);
+                        }
+                    });
+            });
+
+            if chat_input.open {
+                egui::TopBottomPanel::bottom("bottom").show_inside(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        let response = ui.text_edit_singleline(&mut chat_input.text);
+                        let enter = ui.input(|i| i.key_pressed(egui::Key::Enter));
+                        if (response.lost_focus() && enter) || ui.button("Send").clicked() {
+                            submitted = true;
+                        } else {
+                            response.request_focus();
+                        }
+                    });
+                });
+            }
+        });
+    if submitted {
+        match parse_chat_input(&chat_input.text) {
+            Some(ChatSubmit::Command(command)) => {
+                // 还没有收到服务端的等级时 按默认等级处理
+                let level = level.map(|level| *level).unwrap_or_default();
+                submit_command(
+                    &command,
+                    &config,
+                    level,
+                    &mut chat_log,
+                    &mut command_entered,
+                );
+            }
+            Some(ChatSubmit::Chat(text)) => {
+                // 服务端广播后再显示
+                if let Some(mut client) = client {
+                    let message = bincode::serialize(&ServerCommandMessage::Chat { text }).unwrap();
+                    client.send_message(ClientChannel::ServerCommand, message);
+                }
+            }
+            None => {}
+        }
+        if let Ok(mut window) = primary_window.get_single_mut() {
+            close_chat_input(&mut chat_input, &mut flags, &mut window);
+        }
+    }
+}
@@ -0,0 +1,189 @@
+// 连接质量
+// 右上角一直显示一个彩色的圆点和延迟 绿色正常 黄色较差 红色很差
+// 延迟或者丢包持续超过设置的阈值时弹出一次警告 恢复后可以再次提醒
+
+use bevy::prelude::{
+    in_state, IntoSystemConfigs, OnExit, Plugin, Res, ResMut, Resource, Time, Update,
+};
+use bevy_easy_localize::Localize;
+use bevy_egui::{egui, EguiContexts};
+use bevy_renet::renet::RenetClient;
+
+use super::{
+    settings::GraphicsSettings,
+    state_manager::{notification::Notification, GameState},
+};
+
+// 超过阈值持续这么久(秒)后警告
+pub const UNSTABLE_WARN_DELAY: f32 = 5.0;
+// 指示器的圆点半径
+const INDICATOR_RADIUS: f32 = 5.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionQuality {
+    Good,
+    Fair,
+    Poor,
+}
+
+impl ConnectionQuality {
+    /**
+     * 延迟(毫秒)和丢包率 任意一个超过阈值就是 Poor
+     * 超过阈值的一半是 Fair
+     */
+    pub fn classify(rtt_ms: f32, packet_loss: f32, warn_rtt: f32, warn_loss: f32) -> Self {
+        if rtt_ms >= warn_rtt || packet_loss >= warn_loss {
+            ConnectionQuality::Poor
+        } else if rtt_ms >= warn_rtt / 2.0 || packet_loss >= warn_loss / 2.0 {
+            ConnectionQuality::Fair
+        } else {
+            ConnectionQuality::Good
+        }
+    }
+
+    pub fn color(&self) -> egui::Color32 {
+        match self {
+            ConnectionQuality::Good => egui::Color32::GREEN,
+            ConnectionQuality::Fair => egui::Color32::YELLOW,
+            ConnectionQuality::Poor => egui::Color32::RED,
+        }
+    }
+}
+
+#[derive(Debug, Resource)]
+pub struct ConnectionMonitor {
+    pub quality: ConnectionQuality,
+    pub rtt_ms: f32,
+    pub packet_loss: f32,
+    // 连续处于 Poor 的时间
+    pub poor_for: f32,
+    pub warned: bool,
+}
+
+impl Default for ConnectionMonitor {
+    fn default() -> Self {
+        Self {
+            quality: ConnectionQuality::Good,
+            rtt_ms: 0.0,
+            packet_loss: 0.0,
+            poor_for: 0.0,
+            warned: false,
+        }
+    }
+}
+
+impl ConnectionMonitor {
+    /**
+     * 更新一帧 需要警告时返回 true
+     * 每次变差只警告一次 恢复正常后重新计时
+     */
+    pub fn update(&mut self, quality: ConnectionQuality, delta: f32) -> bool {
+        self.quality = quality;
+        if quality != ConnectionQuality::Poor {
+            self.poor_for = 0.0;
+            self.warned = false;
+            return false;
+        }
+        self.poor_for += delta;
+        if !self.warned && self.poor_for >= UNSTABLE_WARN_DELAY {
+            self.warned = true;
+            return true;
+        }
+        false
+    }
+}
+
+pub struct ConnectionQualityPlugin;
+
+impl Plugin for ConnectionQualityPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.init_resource::<ConnectionMonitor>();
+        app.add_systems(
+            Update,
+            (monitor_connection, draw_connection_indicator)
+                .chain()
+                .run_if(in_state(GameState::Game))
+                .run_if(bevy_renet::transport::client_connected()),
+        );
+        app.add_systems(OnExit(GameState::Game), setdown_connection_monitor);
+    }
+}
+
+fn monitor_connection(
+    time: Res<Time>,
+    client: Res<RenetClient>,
+    settings: Res<GraphicsSettings>,
+    localize: Res<Localize>,
+    mut monitor: ResMut<ConnectionMonitor>,
+    mut notification: ResMut<Notification>,
+) {
+    let info = client.network_info();
+    // rtt 的单位是秒
+    monitor.rtt_ms = (info.rtt * 1000.0) as f32;
+    monitor.packet_loss = info.packet_loss as f32;
+    let quality = ConnectionQuality::classify(
+        monitor.rtt_ms,
+        monitor.packet_loss,
+        settings.connection_warn_rtt,
+        settings.connection_warn_loss,
+    );
+    if monitor.update(quality, time.delta_seconds()) {
+        notification.warning(format!(
+            "{} ({:.0} ms, {:.0}%)",
+            localize.get("连接不稳定"),
+            monitor.rtt_ms,
+            monitor.packet_loss * 100.0
+        ));
+    }
+}
+
+fn draw_connection_indicator(mut contexts: EguiContexts, monitor: Res<ConnectionMonitor>) {
+    egui::Area::new("connection_quality")
+        .anchor(egui::Align2::RIGHT_TOP, [-10.0, 10.0])
+        .interactable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                let (rect, _) = ui.allocate_exact_size(
+                    egui::Vec2::splat(INDICATOR_RADIUS * 2.0),
+                    egui::Sense::hover(),
+                );
+                ui.painter().circle_filled(
+                    rect.center(),
+                    INDICATOR_RADIUS,
+                    monitor.quality.color(),
+                );
+                ui.colored_label(egui::Color32::WHITE, format!("{:.0} ms", monitor.rtt_ms));
+            });
+        });
+}
+
+fn setdown_connection_monitor(mut monitor: ResMut<ConnectionMonitor>) {
+    *monitor = ConnectionMonitor::default();
+}
+
+#[test]
+fn test_connection_monitor() {
+    assert_eq!(
+        ConnectionQuality::classify(40.0, 0.0, 250.0, 0.05),
+        ConnectionQuality::Good
+    );
+    assert_eq!(
+        ConnectionQuality::classify(150.0, 0.0, 250.0, 0.05),
+        ConnectionQuality::Fair
+    );
+    assert_eq!(
+        ConnectionQuality::classify(40.0, 0.1, 250.0, 0.05),
+        ConnectionQuality::Poor
+    );
+
+    // 持续变差才警告 只警告一次
+    let mut monitor = ConnectionMonitor::default();
+    assert!(!monitor.update(ConnectionQuality::Poor, 3.0));
+    assert!(!monitor.update(ConnectionQuality::Good, 0.1));
+    assert!(!monitor.update(ConnectionQuality::Poor, 3.0));
+    assert!(monitor.update(ConnectionQuality::Poor, 3.0));
+    assert!(!monitor.update(ConnectionQuality::Poor, 3.0));
+    // 恢复后再次变差可以重新警告
+    monitor.update(ConnectionQuality::Fair, 0.1);
+    assert!(monitor.update(ConnectionQuality::Poor, UNSTABLE_WARN_DELAY));
+}
@@ -0,0 +1,25 @@
+use bevy::prelude::ResMut;
+use bevy_console::ConsoleCommand;
+use bevy_renet::renet::RenetClient;
+use clap::Parser;
+
+use crate::client::message_def::{server_command::ServerCommandMessage, ClientChannel};
+
+#[derive(Parser, ConsoleCommand)]
+#[command(name = "bandwidth", about = "show chunk bandwidth per client (admin)")]
+pub struct BandwidthCommand;
+
+// 结果由服务端返回后打印
+pub fn show_bandwidth(
+    mut bandwidth_command: ConsoleCommand<BandwidthCommand>,
+    client: Option<ResMut<RenetClient>>,
+) {
+    if let Some(Ok(_)) = bandwidth_command.take() {
+        let Some(mut client) = client else {
+            bandwidth_command.reply_failed("Not connected to server");
+            return;
+        };
+        let message = bincode::serialize(&ServerCommandMessage::Bandwidth).unwrap();
+        client.send_message(ClientChannel::ServerCommand, message);
+    }
+}
@@ -0,0 +1,83 @@
+use bevy::prelude::ResMut;
+use bevy_console::ConsoleCommand;
+use clap::{Parser, ValueEnum};
+
+use crate::{
+    client::player::mouse_control::ActiveBrush,
+    server::brush::{BrushMode, BrushShape, MAX_BRUSH_RADIUS},
+};
+
+use super::fill::parse_voxel;
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum BrushAction {
+    // 放置到空气中
+    Normal,
+    // 只替换不是空气的方块
+    Replace,
+    // 只替换指定的体素
+    ReplaceType,
+}
+
+#[derive(Parser, ConsoleCommand)]
+#[command(
+    name = "brush",
+    about = "change how right click places blocks (admin or creative world)"
+)]
+pub struct BrushCommand {
+    #[arg(value_enum)]
+    mode: BrushAction,
+    /// voxel to replace, name or id, required by replace-type
+    target: Option<String>,
+    /// blocks around the target, 0 places a single block
+    #[arg(long, default_value_t = 0)]
+    radius: u32,
+    /// use a cube instead of a sphere
+    #[arg(long)]
+    cube: bool,
+}
+
+// 只修改客户端的笔刷 权限在服务端检查
+pub fn set_brush(mut brush_command: ConsoleCommand<BrushCommand>, mut brush: ResMut<ActiveBrush>) {
+    if let Some(Ok(BrushCommand {
+        mode,
+        target,
+        radius,
+        cube,
+    })) = brush_command.take()
+    {
+        if radius > MAX_BRUSH_RADIUS {
+            brush_command
+                .reply_failed(format!("Brush radius must be at most {}", MAX_BRUSH_RADIUS));
+            return;
+        }
+        let mode = match (mode, target) {
+            (BrushAction::Normal, _) => BrushMode::Normal,
+            (BrushAction::Replace, _) => BrushMode::ReplaceOnly,
+            (BrushAction::ReplaceType, Some(target)) => match parse_voxel(&target) {
+                Some(voxel) => BrushMode::ReplaceType(voxel),
+                None => {
+                    brush_command.reply_failed(format!("Unknown voxel: {}", target));
+                    return;
+                }
+            },
+            (BrushAction::ReplaceType, None) => {
+                brush_command.reply_failed("replace-type needs a target voxel");
+                return;
+            }
+        };
+        *brush = ActiveBrush {
+            mode,
+            shape: if cube {
+                BrushShape::Cube
+            } else {
+                BrushShape::Sphere
+            },
+            radius,
+        };
+        brush_command.reply_ok(format!(
+            "Brush: {:?} {:?} radius {}",
+            mode, brush.shape, radius
+        ));
+    }
+}
@@ -0,0 +1,243 @@
+// 区块调试信息
+// /chunks 统计客户端已加载的区块 /chunk 查看单个区块的群落 地表高度和体素的数量
+// 网格按照 y = 0 的区块保存 一个网格包括整列区块
+
+use bevy::prelude::{IVec3, Res, ResMut};
+use bevy_console::ConsoleCommand;
+use bevy_renet::renet::RenetClient;
+use clap::Parser;
+use ndshape::ConstShape;
+
+use crate::{
+    client::{
+        mesh_display::{DirtyChunks, MeshManager, MeshUploadQueue},
+        message_def::{server_command::ServerCommandMessage, ClientChannel},
+    },
+    server::fill::chunk_to_block,
+    voxel_world::{
+        biomes::biome_at, chunk::ChunkKey, chunk_map::ChunkMap, gen_config::gen_config,
+        map_database::WorldSeed, voxel::Voxel, voxel_registry::VOXEL_REGISTRY,
+    },
+    ChunkShape, CHUNK_SIZE_U32,
+};
+
+#[derive(Parser, ConsoleCommand)]
+#[command(name = "chunks", about = "summary of the chunks loaded on the client")]
+pub struct ChunksCommand;
+
+#[derive(Parser, ConsoleCommand)]
+#[command(
+    name = "chunk",
+    about = "details of one chunk, optionally teleport into it"
+)]
+pub struct ChunkCommand {
+    x: i32,
+    y: i32,
+    z: i32,
+    /// teleport to the surface of the chunk (admin)
+    #[arg(long)]
+    tp: bool,
+}
+
+/**
+ * 每种体素的数量 数量多的在前面
+ */
+pub fn voxel_histogram<'a>(voxels: impl IntoIterator<Item = &'a Voxel>) -> Vec<(u8, usize)> {
+    let mut counts = [0_usize; u8::MAX as usize + 1];
+    for voxel in voxels {
+        counts[voxel.id as usize] += 1;
+    }
+    let mut histogram: Vec<(u8, usize)> = counts
+        .iter()
+        .enumerate()
+        .filter(|(_, count)| **count > 0)
+        .map(|(id, count)| (id as u8, *count))
+        .collect();
+    histogram.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    histogram
+}
+
+/**
+ * 区块所在的这一列中 每个位置最高的实心方块 返回最低和最高的地表高度
+ * 只统计已经加载的区块 整列都没有实心方块时返回 None
+ */
+pub fn surface_range(chunk_map: &ChunkMap, chunk_key: ChunkKey) -> Option<(i32, i32)> {
+    let mut range: Option<(i32, i32)> = None;
+    let height = gen_config().world_height;
+    for x in 0..CHUNK_SIZE_U32 {
+        for z in 0..CHUNK_SIZE_U32 {
+            let top = height.chunk_ys().rev().find_map(|y| {
+                let key = ChunkKey(IVec3::new(chunk_key.0.x, y, chunk_key.0.z));
+                let voxels = chunk_map.get(key)?;
+                (0..CHUNK_SIZE_U32).rev().find_map(|cy| {
+                    let index = ChunkShape::linearize([x, cy, z]) as usize;
+                    voxels[index]
+                        .is_solid()
+                        .then(|| chunk_to_block(key, [x, cy, z]).y)
+                })
+            });
+            if let Some(top) = top {
+                range = Some(match range {
+                    Some((min, max)) => (min.min(top), max.max(top)),
+                    None => (top, top),
+                });
+            }
+        }
+    }
+    range
+}
+
+pub fn voxel_name(id: u8) -> String {
+    match VOXEL_REGISTRY.get(id) {
+        Some(def) => def.name.to_string(),
+        None => format!("#{}", id),
+    }
+}
+
+pub fn chunks_summary(
+    mut chunks_command: ConsoleCommand<ChunksCommand>,
+    chunk_map: Res<ChunkMap>,
+    mesh_manager: Res<MeshManager>,
+    upload_queue: Res<MeshUploadQueue>,
+    dirty_chunks: Res<DirtyChunks>,
+) {
+    if let Some(Ok(_)) = chunks_command.take() {
+        let empty = chunk_map
+            .map_data
+            .values()
+            .filter(|voxels| voxels.iter().all(|voxel| *voxel == Voxel::EMPTY))
+            .count();
+        let meshed = mesh_manager.entities.len();
+        // 还在等待服务端数据的列
+        let pending = mesh_manager
+            .data_status
+            .iter()
+            .filter(|(key, (ready, _))| !ready && !mesh_manager.entities.contains_key(key))
+            .count();
+        // 已经开始生成网格 还没有显示的列
+        let meshing = mesh_manager
+            .fast_key
+            .iter()
+            .filter(|key| !mesh_manager.entities.contains_key(key))
+            .count();
+        chunks_command.reply_ok(format!(
+            "Loaded: {} chunks ({} empty), meshed: {} columns ({} coarse), pending data: {}, meshing: {}, upload queue: {}, dirty: {}, remeshed: {}",
+            chunk_map.map_data.len(),
+            empty,
+            meshed,
+            mesh_manager.lod_coarse.len(),
+            pending,
+            meshing,
+            upload_queue.ready.len(),
+            dirty_chunks.dirty.len(),
+            dirty_chunks.remeshes
+        ));
+    }
+}
+
+pub fn chunk_details(
+    mut chunk_command: ConsoleCommand<ChunkCommand>,
+    chunk_map: Res<ChunkMap>,
+    mesh_manager: Res<MeshManager>,
+    world_seed: Option<Res<WorldSeed>>,
+    client: Option<ResMut<RenetClient>>,
+) {
+    if let Some(Ok(ChunkCommand { x, y, z, tp })) = chunk_command.take() {
+        let chunk_key = ChunkKey(IVec3::new(x, y, z));
+        let column_key = ChunkKey(IVec3::new(x, 0, z));
+        // 区块中心的方块坐标
+        let center = chunk_to_block(chunk_key, [CHUNK_SIZE_U32 / 2; 3]);
+        let surface = surface_range(&chunk_map, chunk_key);
+
+        chunk_command.reply(format!("Chunk {:?}", chunk_key));
+        match world_seed {
+            Some(seed) => chunk_command.reply(format!(
+                "Biome: {:?}",
+                biome_at(center.x as f32, center.z as f32, seed.0)
+            )),
+            None => chunk_command.reply("Biome: unknown, seed has not been synced"),
+        }
+        match surface {
+            Some((min, max)) => chunk_command.reply(format!("Surface: y {} to {}", min, max)),
+            None => chunk_command.reply("Surface: none in loaded chunks"),
+        }
+        chunk_command.reply(format!(
+            "Mesh: {}, data: {:?}",
+            if mesh_manager.entities.contains_key(&column_key) {
+                "shown"
+            } else if mesh_manager.fast_key.contains(&column_key) {
+                "meshing"
+            } else {
+                "none"
+            },
+            mesh_manager
+                .data_status
+                .get(&column_key)
+                .map(|(ready, _)| ready)
+        ));
+        match chunk_map.get(chunk_key) {
+            Some(voxels) => {
+                let histogram = voxel_histogram(voxels)
+                    .iter()
+                    .map(|(id, count)| format!("{} {}", voxel_name(*id), count))
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                chunk_command.reply(format!("Voxels: {}", histogram));
+            }
+            None => chunk_command.reply("Voxels: not loaded"),
+        }
+
+        if tp {
+            let Some(mut client) = client else {
+                chunk_command.reply_failed("Not connected to server");
+                return;
+            };
+            // 站在地表上 没有地表时传送到区块中心
+            let feet = surface.map_or(center.y, |(_, max)| max + 1);
+            let position = [
+                center.x as f32 + 0.5,
+                feet as f32 + 1.0,
+                center.z as f32 + 0.5,
+            ];
+            let message = bincode::serialize(&ServerCommandMessage::Teleport { position }).unwrap();
+            client.send_message(ClientChannel::ServerCommand, message);
+            return;
+        }
+        chunk_command.ok();
+    }
+}
+
+#[test]
+fn test_chunk_details() {
+    use crate::{
+        voxel_world::voxel::{Stone, VoxelMaterial},
+        CHUNK_VOLUME,
+    };
+
+    let mut voxels = vec![Voxel::EMPTY; CHUNK_VOLUME as usize];
+    voxels[..10].fill(Stone::into_voxel());
+    assert_eq!(
+        voxel_histogram(&voxels),
+        vec![
+            (Voxel::EMPTY.id, CHUNK_VOLUME as usize - 10),
+            (Stone::ID, 10)
+        ]
+    );
+
+    // 上面的区块有一个更高的方块
+    let chunk_key = ChunkKey(IVec3::ZERO);
+    let above = ChunkKey(IVec3::new(0, 1, 0));
+    let mut chunk_map = ChunkMap::new();
+    chunk_map.write_chunk(chunk_key, vec![Stone::into_voxel(); CHUNK_VOLUME as usize]);
+    let mut top = vec![Voxel::EMPTY; CHUNK_VOLUME as usize];
+    top[ChunkShape::linearize([3, 2, 3]) as usize] = Stone::into_voxel();
+    chunk_map.write_chunk(above, top);
+    assert_eq!(
+        surface_range(&chunk_map, chunk_key),
+        Some((
+            chunk_to_block(chunk_key, [0, CHUNK_SIZE_U32 - 1, 0]).y,
+            chunk_to_block(above, [3, 2, 3]).y
+        ))
+    );
+    assert_eq!(surface_range(&ChunkMap::new(), chunk_key), None);
+}
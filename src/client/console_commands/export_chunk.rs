@@ -0,0 +1,52 @@
+use bevy::prelude::{Query, Res, Transform, With};
+use bevy_console::ConsoleCommand;
+use clap::Parser;
+
+use crate::{
+    client::player::controller::CharacterController,
+    tools::chunk_dump::ChunkDump,
+    voxel_world::{
+        chunk::{get_chunk_key_i3_by_vec3, ChunkKey},
+        chunk_map::ChunkMap,
+        map_database::WorldSeed,
+    },
+};
+
+#[derive(Parser, ConsoleCommand)]
+#[command(
+    name = "exportchunk",
+    about = "export the voxels of the chunk you are standing in"
+)]
+pub struct ExportChunkCommand {
+    // 默认是 chunk_x_y_z.bin
+    path: Option<String>,
+}
+
+pub fn export_chunk(
+    mut export_command: ConsoleCommand<ExportChunkCommand>,
+    chunk_map: Res<ChunkMap>,
+    world_seed: Option<Res<WorldSeed>>,
+    player_query: Query<&Transform, With<CharacterController>>,
+) {
+    if let Some(Ok(ExportChunkCommand { path })) = export_command.take() {
+        let Ok(transform) = player_query.get_single() else {
+            export_command.reply_failed("Player not found");
+            return;
+        };
+        let chunk_key = ChunkKey(get_chunk_key_i3_by_vec3(transform.translation));
+        let Some(voxels) = chunk_map.get(chunk_key) else {
+            export_command.reply_failed(format!("Chunk {:?} is not loaded", chunk_key.0));
+            return;
+        };
+        let dump = ChunkDump {
+            chunk_key,
+            seed: world_seed.map(|seed| seed.0),
+            voxels: voxels.clone(),
+        };
+        let path = path.unwrap_or_else(|| dump.file_name());
+        match dump.save(&path) {
+            Ok(_) => export_command.reply_ok(format!("Chunk {:?} saved to {}", chunk_key.0, path)),
+            Err(err) => export_command.reply_failed(format!("Export failed: {}", err)),
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use bevy::prelude::ResMut;
+use bevy_console::ConsoleCommand;
+use bevy_renet::renet::RenetClient;
+use clap::Parser;
+
+use crate::{
+    client::message_def::{chunk_query::ChunkQuery, ClientChannel},
+    voxel_world::{voxel::Voxel, voxel_registry::VOXEL_REGISTRY},
+};
+
+#[derive(Parser, ConsoleCommand)]
+#[command(
+    name = "fill",
+    about = "set every block between two corners to a voxel (admin)",
+    allow_negative_numbers = true
+)]
+pub struct FillCommand {
+    x1: i32,
+    y1: i32,
+    z1: i32,
+    x2: i32,
+    y2: i32,
+    z2: i32,
+    /// voxel name or id
+    voxel: String,
+}
+
+#[derive(Parser, ConsoleCommand)]
+#[command(
+    name = "clear",
+    about = "clear every block between two corners (admin)",
+    allow_negative_numbers = true
+)]
+pub struct ClearCommand {
+    x1: i32,
+    y1: i32,
+    z1: i32,
+    x2: i32,
+    y2: i32,
+    z2: i32,
+}
+
+// 体素的名字或者 id
+pub fn parse_voxel(name: &str) -> Option<Voxel> {
+    let def = match name.parse::<u8>() {
+        Ok(id) => VOXEL_REGISTRY.get(id),
+        Err(_) => VOXEL_REGISTRY.find_by_name(name),
+    }?;
+    Some(Voxel {
+        id: def.id,
+        direction: Default::default(),
+    })
+}
+
+fn send_fill(client: &mut RenetClient, from: [i32; 3], to: [i32; 3], voxel: Voxel) {
+    let message = bincode::serialize(&ChunkQuery::Fill { from, to, voxel }).unwrap();
+    client.send_message(ClientChannel::ChunkQuery, message);
+}
+
+// 填充的结果由服务端返回
+pub fn fill_blocks(
+    mut fill_command: ConsoleCommand<FillCommand>,
+    client: Option<ResMut<RenetClient>>,
+) {
+    if let Some(Ok(FillCommand {
+        x1,
+        y1,
+        z1,
+        x2,
+        y2,
+        z2,
+        voxel,
+    })) = fill_command.take()
+    {
+        let Some(mut client) = client else {
+            fill_command.reply_failed("Not connected to server");
+            return;
+        };
+        let Some(voxel) = parse_voxel(&voxel) else {
+            fill_command.reply_failed(format!("Unknown voxel: {}", voxel));
+            return;
+        };
+        send_fill(&mut client, [x1, y1, z1], [x2, y2, z2], voxel);
+    }
+}
+
+pub fn clear_blocks(
+    mut clear_command: ConsoleCommand<ClearCommand>,
+    client: Option<ResMut<RenetClient>>,
+) {
+    if let Some(Ok(ClearCommand {
+        x1,
+        y1,
+        z1,
+        x2,
+        y2,
+        z2,
+    })) = clear_command.take()
+    {
+        let Some(mut client) = client else {
+            clear_command.reply_failed("Not connected to server");
+            return;
+        };
+        send_fill(&mut client, [x1, y1, z1], [x2, y2, z2], Voxel::EMPTY);
+    }
+}
@@ -0,0 +1,25 @@
+use bevy::prelude::ResMut;
+use bevy_console::ConsoleCommand;
+use bevy_renet::renet::RenetClient;
+use clap::Parser;
+
+use crate::client::message_def::{server_command::ServerCommandMessage, ClientChannel};
+
+#[derive(Parser, ConsoleCommand)]
+#[command(name = "kill", about = "die and respawn at the spawn point")]
+pub struct KillCommand;
+
+// 服务端处理死亡后返回结果
+pub fn kill_self(
+    mut kill_command: ConsoleCommand<KillCommand>,
+    client: Option<ResMut<RenetClient>>,
+) {
+    if let Some(Ok(_)) = kill_command.take() {
+        let Some(mut client) = client else {
+            kill_command.reply_failed("Not connected to server");
+            return;
+        };
+        let message = bincode::serialize(&ServerCommandMessage::Kill).unwrap();
+        client.send_message(ClientChannel::ServerCommand, message);
+    }
+}
@@ -0,0 +1,48 @@
+use bevy::prelude::ResMut;
+use bevy_console::ConsoleCommand;
+use bevy_renet::renet::RenetClient;
+use clap::Parser;
+
+use crate::{
+    client::message_def::{server_command::ServerCommandMessage, ClientChannel},
+    voxel_world::{biomes::BiomeKind, spawn::LOCATE_BIOME_MAX_RADIUS},
+};
+
+#[derive(Parser, ConsoleCommand)]
+#[command(name = "locatebiome", about = "find the nearest biome of a kind")]
+pub struct LocateBiomeCommand {
+    #[arg(value_enum)]
+    biome: BiomeKind,
+    /// how far to search in blocks
+    #[arg(long, default_value_t = LOCATE_BIOME_MAX_RADIUS)]
+    radius: i32,
+    /// teleport to the found position (admin)
+    #[arg(long)]
+    tp: bool,
+}
+
+pub fn locate_biome(
+    mut locate_command: ConsoleCommand<LocateBiomeCommand>,
+    client: Option<ResMut<RenetClient>>,
+) {
+    if let Some(Ok(LocateBiomeCommand { biome, radius, tp })) = locate_command.take() {
+        let Some(mut client) = client else {
+            locate_command.reply_failed("Not connected to server");
+            return;
+        };
+        if radius <= 0 || radius > LOCATE_BIOME_MAX_RADIUS {
+            locate_command.reply_failed(format!(
+                "Radius must be between 1 and {}",
+                LOCATE_BIOME_MAX_RADIUS
+            ));
+            return;
+        }
+        let message = bincode::serialize(&ServerCommandMessage::LocateBiome {
+            biome,
+            radius,
+            teleport: tp,
+        })
+        .unwrap();
+        client.send_message(ClientChannel::ServerCommand, message);
+    }
+}
@@ -0,0 +1,27 @@
+use bevy::prelude::ResMut;
+use bevy_console::ConsoleCommand;
+use clap::Parser;
+
+use crate::client::debug::MeasureTool;
+
+#[derive(Parser, ConsoleCommand)]
+#[command(
+    name = "measure",
+    about = "toggle the measure tool, middle click two blocks to measure"
+)]
+pub struct MeasureCommand;
+
+pub fn toggle_measure(
+    mut measure_command: ConsoleCommand<MeasureCommand>,
+    mut measure: ResMut<MeasureTool>,
+) {
+    if let Some(Ok(_)) = measure_command.take() {
+        measure.enabled = !measure.enabled;
+        measure.points.clear();
+        if measure.enabled {
+            measure_command.reply_ok("Measure on, middle click two blocks");
+        } else {
+            measure_command.reply_ok("Measure off");
+        }
+    }
+}
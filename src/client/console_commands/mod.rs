@@ -3,11 +3,60 @@ use bevy_console::{
     AddConsoleCommand, ConsoleCommandEntered, ConsoleOpen, ConsolePlugin, ConsoleSet,
 };
 
-use self::mesh_state::{check_mesh_state, MeshStateCommand};
+use self::{
+    bandwidth::{show_bandwidth, BandwidthCommand},
+    brush::{set_brush, BrushCommand},
+    chunks::{chunk_details, chunks_summary, ChunkCommand, ChunksCommand},
+    export_chunk::{export_chunk, ExportChunkCommand},
+    fill::{clear_blocks, fill_blocks, ClearCommand, FillCommand},
+    kill::{kill_self, KillCommand},
+    locate_biome::{locate_biome, LocateBiomeCommand},
+    measure::{toggle_measure, MeasureCommand},
+    mesh_state::{check_mesh_state, MeshStateCommand},
+    moderation::{
+        ban_player, kick_player, list_bans, op_player, unban_player, BanCommand, BanListCommand,
+        KickCommand, OpCommand, UnbanCommand,
+    },
+    physics::{set_gravity, set_jump, GravityCommand, JumpCommand},
+    players::{list_players, PlayersCommand},
+    record::{record_input, replay_input, RecordCommand, ReplayCommand},
+    regen::{regen_chunks, RegenCommand},
+    remesh::{remesh_chunks, RemeshCommand},
+    save::{save_world, SaveCommand},
+    seed::{print_seed, SeedCommand},
+    stats::{voxel_stats, StatsCommand},
+    tp::{teleport, teleport_here, TpCommand, TpHereCommand},
+    undo::{undo_edits, UndoCommand},
+    weather::{set_weather, WeatherCommand},
+    whisper::{reply_whisper, whisper, ReplyCommand, WhisperCommand},
+    world::{change_world, WorldCommand},
+};
 
 use super::player::controller::ControllerFlag;
 
+pub mod bandwidth;
+pub mod brush;
+pub mod chunks;
+pub mod export_chunk;
+pub mod fill;
+pub mod kill;
+pub mod locate_biome;
+pub mod measure;
 pub mod mesh_state;
+pub mod moderation;
+pub mod physics;
+pub mod players;
+pub mod record;
+pub mod regen;
+pub mod remesh;
+pub mod save;
+pub mod seed;
+pub mod stats;
+pub mod tp;
+pub mod undo;
+pub mod weather;
+pub mod whisper;
+pub mod world;
 
 pub struct ConsoleCommandPlugins;
 
@@ -16,7 +65,39 @@ impl Plugin for ConsoleCommandPlugins {
         app.add_plugins(ConsolePlugin)
             .add_systems(PreUpdate, sync_flags)
             .add_systems(Update, raw_commands.in_set(ConsoleSet::Commands))
-            .add_console_command::<MeshStateCommand, _>(check_mesh_state);
+            .add_console_command::<MeshStateCommand, _>(check_mesh_state)
+            .add_console_command::<SeedCommand, _>(print_seed)
+            .add_console_command::<RegenCommand, _>(regen_chunks)
+            .add_console_command::<RemeshCommand, _>(remesh_chunks)
+            .add_console_command::<GravityCommand, _>(set_gravity)
+            .add_console_command::<JumpCommand, _>(set_jump)
+            .add_console_command::<PlayersCommand, _>(list_players)
+            .add_console_command::<KickCommand, _>(kick_player)
+            .add_console_command::<BanCommand, _>(ban_player)
+            .add_console_command::<UnbanCommand, _>(unban_player)
+            .add_console_command::<BanListCommand, _>(list_bans)
+            .add_console_command::<OpCommand, _>(op_player)
+            .add_console_command::<WhisperCommand, _>(whisper)
+            .add_console_command::<ReplyCommand, _>(reply_whisper)
+            .add_console_command::<ExportChunkCommand, _>(export_chunk)
+            .add_console_command::<BandwidthCommand, _>(show_bandwidth)
+            .add_console_command::<WeatherCommand, _>(set_weather)
+            .add_console_command::<LocateBiomeCommand, _>(locate_biome)
+            .add_console_command::<RecordCommand, _>(record_input)
+            .add_console_command::<ReplayCommand, _>(replay_input)
+            .add_console_command::<SaveCommand, _>(save_world)
+            .add_console_command::<FillCommand, _>(fill_blocks)
+            .add_console_command::<ClearCommand, _>(clear_blocks)
+            .add_console_command::<BrushCommand, _>(set_brush)
+            .add_console_command::<UndoCommand, _>(undo_edits)
+            .add_console_command::<MeasureCommand, _>(toggle_measure)
+            .add_console_command::<KillCommand, _>(kill_self)
+            .add_console_command::<ChunksCommand, _>(chunks_summary)
+            .add_console_command::<ChunkCommand, _>(chunk_details)
+            .add_console_command::<StatsCommand, _>(voxel_stats)
+            .add_console_command::<WorldCommand, _>(change_world)
+            .add_console_command::<TpCommand, _>(teleport)
+            .add_console_command::<TpHereCommand, _>(teleport_here);
     }
 }
 
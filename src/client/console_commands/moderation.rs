@@ -0,0 +1,137 @@
+use bevy::prelude::ResMut;
+use bevy_console::ConsoleCommand;
+use bevy_renet::renet::RenetClient;
+use clap::Parser;
+
+use crate::{
+    client::message_def::{server_command::ServerCommandMessage, ClientChannel},
+    server::permission::PermissionLevel,
+};
+
+#[derive(Parser, ConsoleCommand)]
+#[command(name = "kick", about = "kick a player from the server (moderator)")]
+pub struct KickCommand {
+    username: String,
+    reason: Vec<String>,
+}
+
+#[derive(Parser, ConsoleCommand)]
+#[command(
+    name = "ban",
+    about = "ban a player and their address, or an IP address (moderator)"
+)]
+pub struct BanCommand {
+    /// player name or IP address
+    username: String,
+    reason: Vec<String>,
+}
+
+#[derive(Parser, ConsoleCommand)]
+#[command(
+    name = "unban",
+    about = "remove a player or IP address from the ban list (admin)"
+)]
+pub struct UnbanCommand {
+    /// player name or IP address
+    username: String,
+}
+
+#[derive(Parser, ConsoleCommand)]
+#[command(name = "banlist", about = "list banned players (moderator)")]
+pub struct BanListCommand;
+
+#[derive(Parser, ConsoleCommand)]
+#[command(name = "op", about = "set the permission level of a player (admin)")]
+pub struct OpCommand {
+    username: String,
+    #[arg(value_enum, default_value_t = PermissionLevel::Moderator)]
+    level: PermissionLevel,
+}
+
+// 原因可以包含空格
+fn join_reason(reason: Vec<String>) -> Option<String> {
+    if reason.is_empty() {
+        None
+    } else {
+        Some(reason.join(" "))
+    }
+}
+
+fn send_command(client: &mut RenetClient, command: ServerCommandMessage) {
+    let message = bincode::serialize(&command).unwrap();
+    client.send_message(ClientChannel::ServerCommand, message);
+}
+
+pub fn kick_player(
+    mut kick_command: ConsoleCommand<KickCommand>,
+    client: Option<ResMut<RenetClient>>,
+) {
+    if let Some(Ok(KickCommand { username, reason })) = kick_command.take() {
+        let Some(mut client) = client else {
+            kick_command.reply_failed("Not connected to server");
+            return;
+        };
+        send_command(
+            &mut client,
+            ServerCommandMessage::Kick {
+                username,
+                reason: join_reason(reason),
+            },
+        );
+    }
+}
+
+pub fn ban_player(
+    mut ban_command: ConsoleCommand<BanCommand>,
+    client: Option<ResMut<RenetClient>>,
+) {
+    if let Some(Ok(BanCommand { username, reason })) = ban_command.take() {
+        let Some(mut client) = client else {
+            ban_command.reply_failed("Not connected to server");
+            return;
+        };
+        send_command(
+            &mut client,
+            ServerCommandMessage::Ban {
+                username,
+                reason: join_reason(reason),
+            },
+        );
+    }
+}
+
+pub fn unban_player(
+    mut unban_command: ConsoleCommand<UnbanCommand>,
+    client: Option<ResMut<RenetClient>>,
+) {
+    if let Some(Ok(UnbanCommand { username })) = unban_command.take() {
+        let Some(mut client) = client else {
+            unban_command.reply_failed("Not connected to server");
+            return;
+        };
+        send_command(&mut client, ServerCommandMessage::Unban { username });
+    }
+}
+
+pub fn list_bans(
+    mut ban_list_command: ConsoleCommand<BanListCommand>,
+    client: Option<ResMut<RenetClient>>,
+) {
+    if let Some(Ok(_)) = ban_list_command.take() {
+        let Some(mut client) = client else {
+            ban_list_command.reply_failed("Not connected to server");
+            return;
+        };
+        send_command(&mut client, ServerCommandMessage::BanList);
+    }
+}
+
+pub fn op_player(mut op_command: ConsoleCommand<OpCommand>, client: Option<ResMut<RenetClient>>) {
+    if let Some(Ok(OpCommand { username, level })) = op_command.take() {
+        let Some(mut client) = client else {
+            op_command.reply_failed("Not connected to server");
+            return;
+        };
+        send_command(&mut client, ServerCommandMessage::Op { username, level });
+    }
+}
@@ -0,0 +1,70 @@
+use bevy::prelude::{Res, ResMut};
+use bevy_console::ConsoleCommand;
+use bevy_renet::renet::RenetClient;
+use clap::Parser;
+
+use crate::{
+    client::message_def::{server_command::ServerCommandMessage, ClientChannel},
+    server::physics_config::PhysicsConfig,
+};
+
+#[derive(Parser, ConsoleCommand)]
+#[command(name = "gravity", about = "print or set the gravity of the world")]
+pub struct GravityCommand {
+    /// new gravity, default is 9.81
+    value: Option<f32>,
+}
+
+#[derive(Parser, ConsoleCommand)]
+#[command(name = "jump", about = "print or set the jump speed of players")]
+pub struct JumpCommand {
+    /// new jump speed, default is 5.0
+    value: Option<f32>,
+}
+
+fn send_physics(client: &mut RenetClient, gravity: Option<f32>, jump_speed: Option<f32>) {
+    let message = bincode::serialize(&ServerCommandMessage::SetPhysics {
+        gravity,
+        jump_speed,
+    })
+    .unwrap();
+    client.send_message(ClientChannel::ServerCommand, message);
+}
+
+pub fn set_gravity(
+    mut gravity_command: ConsoleCommand<GravityCommand>,
+    physics_config: Res<PhysicsConfig>,
+    client: Option<ResMut<RenetClient>>,
+) {
+    if let Some(Ok(GravityCommand { value })) = gravity_command.take() {
+        let Some(value) = value else {
+            gravity_command.reply_ok(format!("Gravity: {}", physics_config.gravity));
+            return;
+        };
+        let Some(mut client) = client else {
+            gravity_command.reply_failed("Not connected to server");
+            return;
+        };
+        send_physics(&mut client, Some(value), None);
+        gravity_command.reply_ok(format!("Set gravity to {}", value));
+    }
+}
+
+pub fn set_jump(
+    mut jump_command: ConsoleCommand<JumpCommand>,
+    physics_config: Res<PhysicsConfig>,
+    client: Option<ResMut<RenetClient>>,
+) {
+    if let Some(Ok(JumpCommand { value })) = jump_command.take() {
+        let Some(value) = value else {
+            jump_command.reply_ok(format!("Jump speed: {}", physics_config.jump_speed));
+            return;
+        };
+        let Some(mut client) = client else {
+            jump_command.reply_failed("Not connected to server");
+            return;
+        };
+        send_physics(&mut client, None, Some(value));
+        jump_command.reply_ok(format!("Set jump speed to {}", value));
+    }
+}
@@ -0,0 +1,25 @@
+use bevy::prelude::ResMut;
+use bevy_console::ConsoleCommand;
+use bevy_renet::renet::RenetClient;
+use clap::Parser;
+
+use crate::client::message_def::{server_command::ServerCommandMessage, ClientChannel};
+
+#[derive(Parser, ConsoleCommand)]
+#[command(name = "players", about = "list online players")]
+pub struct PlayersCommand;
+
+// 结果由服务端返回后打印
+pub fn list_players(
+    mut players_command: ConsoleCommand<PlayersCommand>,
+    client: Option<ResMut<RenetClient>>,
+) {
+    if let Some(Ok(_)) = players_command.take() {
+        let Some(mut client) = client else {
+            players_command.reply_failed("Not connected to server");
+            return;
+        };
+        let message = bincode::serialize(&ServerCommandMessage::ListPlayers).unwrap();
+        client.send_message(ClientChannel::ServerCommand, message);
+    }
+}
@@ -0,0 +1,127 @@
+use bevy::prelude::{Query, Res, ResMut, Transform, With};
+use bevy_console::ConsoleCommand;
+use bevy_renet::renet::RenetClient;
+use clap::{Parser, ValueEnum};
+
+use crate::{
+    client::{
+        input_record::{InputRecorder, InputRecording},
+        message_def::{server_command::ServerCommandMessage, ClientChannel},
+        player::{controller::CharacterController, look::MouseSettings},
+    },
+    voxel_world::map_database::WorldSeed,
+};
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum RecordAction {
+    Start,
+    Stop,
+}
+
+#[derive(Parser, ConsoleCommand)]
+#[command(name = "record", about = "record keyboard and mouse input to a file")]
+pub struct RecordCommand {
+    #[arg(value_enum)]
+    action: RecordAction,
+    // 停止时保存的位置 默认是 recording_seed_x_z.bin
+    path: Option<String>,
+}
+
+#[derive(Parser, ConsoleCommand)]
+#[command(
+    name = "replay",
+    about = "replay recorded input from the recorded position, press Esc to stop"
+)]
+pub struct ReplayCommand {
+    path: String,
+}
+
+pub fn record_input(
+    mut record_command: ConsoleCommand<RecordCommand>,
+    mut recorder: ResMut<InputRecorder>,
+    world_seed: Option<Res<WorldSeed>>,
+    mouse_settings: Res<MouseSettings>,
+    player_query: Query<&Transform, With<CharacterController>>,
+) {
+    if let Some(Ok(RecordCommand { action, path })) = record_command.take() {
+        match (action, recorder.as_mut()) {
+            (RecordAction::Start, InputRecorder::Idle) => {
+                let Ok(transform) = player_query.get_single() else {
+                    record_command.reply_failed("Player not found");
+                    return;
+                };
+                *recorder = InputRecorder::Recording {
+                    recording: InputRecording::new(
+                        world_seed.map(|seed| seed.0),
+                        transform.translation,
+                        mouse_settings.yaw_pitch_roll,
+                    ),
+                    elapsed: 0.0,
+                };
+                record_command.reply_ok("Recording started");
+            }
+            (RecordAction::Stop, InputRecorder::Recording { recording, .. }) => {
+                let path = path.unwrap_or_else(|| recording.file_name());
+                match recording.save(&path) {
+                    Ok(_) => record_command.reply_ok(format!(
+                        "Saved {:.1}s of input to {}",
+                        recording.duration(),
+                        path
+                    )),
+                    Err(err) => record_command.reply_failed(format!("Save failed: {}", err)),
+                }
+                *recorder = InputRecorder::Idle;
+            }
+            (RecordAction::Start, _) => record_command.reply_failed("Already recording"),
+            (RecordAction::Stop, _) => record_command.reply_failed("Not recording"),
+        }
+    }
+}
+
+pub fn replay_input(
+    mut replay_command: ConsoleCommand<ReplayCommand>,
+    mut recorder: ResMut<InputRecorder>,
+    world_seed: Option<Res<WorldSeed>>,
+    client: Option<ResMut<RenetClient>>,
+) {
+    if let Some(Ok(ReplayCommand { path })) = replay_command.take() {
+        if !matches!(*recorder, InputRecorder::Idle) {
+            replay_command.reply_failed("Stop recording or replaying first");
+            return;
+        }
+        let recording = match InputRecording::load(&path) {
+            Ok(recording) => recording,
+            Err(err) => {
+                replay_command.reply_failed(format!("Load failed: {}", err));
+                return;
+            }
+        };
+        // 不同的种子地形不同 回放没有意义
+        let seed = world_seed.map(|seed| seed.0);
+        if recording.seed.is_some() && recording.seed != seed {
+            replay_command.reply_failed(format!(
+                "Recorded with seed {:?}, current seed is {:?}",
+                recording.seed, seed
+            ));
+            return;
+        }
+        let Some(mut client) = client else {
+            replay_command.reply_failed("Not connected to server");
+            return;
+        };
+        let message = bincode::serialize(&ServerCommandMessage::Teleport {
+            position: recording.start.into(),
+        })
+        .unwrap();
+        client.send_message(ClientChannel::ServerCommand, message);
+        replay_command.reply_ok(format!(
+            "Replaying {:.1}s of input from {}",
+            recording.duration(),
+            path
+        ));
+        *recorder = InputRecorder::Waiting {
+            recording,
+            elapsed: 0.0,
+        };
+    }
+}
@@ -0,0 +1,44 @@
+use bevy::prelude::{Res, ResMut};
+use bevy_console::ConsoleCommand;
+use bevy_renet::renet::RenetClient;
+use clap::Parser;
+
+use crate::{
+    client::message_def::{chunk_query::ChunkQuery, ClientChannel},
+    common::ClipSpheres,
+    voxel_world::chunk::{get_chunk_key_i3_by_vec3, ChunkKey},
+};
+
+#[derive(Parser, ConsoleCommand)]
+#[command(name = "regen", about = "regenerate the chunks around the player")]
+pub struct RegenCommand {
+    /// chunk radius around the player
+    radius: Option<i32>,
+    /// wipe player edits in the area
+    #[arg(long)]
+    wipe: bool,
+}
+
+pub fn regen_chunks(
+    mut regen_command: ConsoleCommand<RegenCommand>,
+    clip_spheres: Res<ClipSpheres>,
+    client: Option<ResMut<RenetClient>>,
+) {
+    if let Some(Ok(RegenCommand { radius, wipe })) = regen_command.take() {
+        let Some(mut client) = client else {
+            regen_command.reply_failed("Not connected to server");
+            return;
+        };
+        let mut ive3 = get_chunk_key_i3_by_vec3(clip_spheres.new_sphere.center);
+        ive3.y = 0;
+        let radius = radius.unwrap_or(1);
+        let message = bincode::serialize(&ChunkQuery::Regen {
+            chunk_key: ChunkKey(ive3),
+            radius,
+            keep_edits: !wipe,
+        })
+        .unwrap();
+        client.send_message(ClientChannel::ChunkQuery, message);
+        regen_command.reply_ok(format!("Regen chunks around {:?} radius {}", ive3, radius));
+    }
+}
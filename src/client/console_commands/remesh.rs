@@ -0,0 +1,93 @@
+use std::time::Instant;
+
+use bevy::prelude::{Commands, IVec3, Res, ResMut};
+use bevy_console::ConsoleCommand;
+use clap::Parser;
+
+use crate::{
+    client::mesh_display::{MeshManager, MeshUploadQueue},
+    common::ClipSpheres,
+    voxel_world::{
+        chunk::{get_chunk_key_i3_by_vec3, ChunkKey},
+        chunk_map::ChunkMap,
+    },
+};
+
+// 重新生成网格的最大半径(区块) 范围外的列本来也没有加载
+pub const MAX_REMESH_RADIUS: i32 = 16;
+
+#[derive(Parser, ConsoleCommand)]
+#[command(
+    name = "remesh",
+    about = "rebuild the meshes around the player from the loaded voxels"
+)]
+pub struct RemeshCommand {
+    /// chunk radius around the player
+    radius: Option<i32>,
+}
+
+/**
+ * 删除这一列的网格 gen_mesh_system 会使用已经加载的体素重新生成
+ * 数据还没有准备好的列不处理 返回是否删除了
+ */
+pub fn clear_column_mesh(
+    commands: &mut Commands,
+    mesh_manager: &mut MeshManager,
+    upload_queue: &mut MeshUploadQueue,
+    chunk_map: &ChunkMap,
+    chunk_key: ChunkKey,
+) -> bool {
+    if !chunk_map.chunk_for_mesh_ready(chunk_key) {
+        return false;
+    }
+    if let Some(entity) = mesh_manager.entities.remove(&chunk_key) {
+        commands.entity(entity).despawn();
+    }
+    if let Some(entity) = mesh_manager.water_entities.remove(&chunk_key) {
+        commands.entity(entity).despawn();
+    }
+    mesh_manager.mesh_storge.remove(&chunk_key);
+    mesh_manager.water_mesh_storge.remove(&chunk_key);
+    mesh_manager.fast_key.remove(&chunk_key);
+    mesh_manager.lod_coarse.remove(&chunk_key);
+    upload_queue.ready.retain(|(_, _, key)| *key != chunk_key);
+    // 有数据状态时 gen_mesh_system 不会再向服务端请求
+    mesh_manager
+        .data_status
+        .insert(chunk_key, (true, Instant::now()));
+    true
+}
+
+pub fn remesh_chunks(
+    mut commands: Commands,
+    mut remesh_command: ConsoleCommand<RemeshCommand>,
+    clip_spheres: Res<ClipSpheres>,
+    chunk_map: Res<ChunkMap>,
+    mut mesh_manager: ResMut<MeshManager>,
+    mut upload_queue: ResMut<MeshUploadQueue>,
+) {
+    if let Some(Ok(RemeshCommand { radius })) = remesh_command.take() {
+        let mut center = get_chunk_key_i3_by_vec3(clip_spheres.new_sphere.center);
+        center.y = 0;
+        let radius = radius.unwrap_or(1).clamp(0, MAX_REMESH_RADIUS);
+        let mut count = 0;
+        for x in -radius..=radius {
+            for z in -radius..=radius {
+                let chunk_key = ChunkKey(center + IVec3::new(x, 0, z));
+                if clear_column_mesh(
+                    &mut commands,
+                    mesh_manager.as_mut(),
+                    upload_queue.as_mut(),
+                    &chunk_map,
+                    chunk_key,
+                ) {
+                    count += 1;
+                }
+            }
+        }
+        remesh_command.reply_ok(format!(
+            "Remesh {} columns around {:?} radius {}",
+            count, center, radius
+        ));
+    }
+}
@@ -0,0 +1,25 @@
+use bevy::prelude::ResMut;
+use bevy_console::ConsoleCommand;
+use bevy_renet::renet::RenetClient;
+use clap::Parser;
+
+use crate::client::message_def::{server_command::ServerCommandMessage, ClientChannel};
+
+#[derive(Parser, ConsoleCommand)]
+#[command(name = "save", about = "save the world on the server now (admin)")]
+pub struct SaveCommand;
+
+// 保存完成后服务端返回结果
+pub fn save_world(
+    mut save_command: ConsoleCommand<SaveCommand>,
+    client: Option<ResMut<RenetClient>>,
+) {
+    if let Some(Ok(_)) = save_command.take() {
+        let Some(mut client) = client else {
+            save_command.reply_failed("Not connected to server");
+            return;
+        };
+        let message = bincode::serialize(&ServerCommandMessage::Save).unwrap();
+        client.send_message(ClientChannel::ServerCommand, message);
+    }
+}
@@ -0,0 +1,21 @@
+use bevy::prelude::Res;
+use bevy_console::ConsoleCommand;
+use clap::Parser;
+
+use crate::voxel_world::map_database::WorldSeed;
+
+#[derive(Parser, ConsoleCommand)]
+#[command(name = "seed", about = "print the seed of current world")]
+pub struct SeedCommand;
+
+pub fn print_seed(
+    mut seed_command: ConsoleCommand<SeedCommand>,
+    world_seed: Option<Res<WorldSeed>>,
+) {
+    if let Some(Ok(_)) = seed_command.take() {
+        match world_seed {
+            Some(seed) => seed_command.reply_ok(format!("Seed: {}", seed.0)),
+            None => seed_command.reply_failed("Seed has not been synced from server"),
+        }
+    }
+}
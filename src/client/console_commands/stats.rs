@@ -0,0 +1,117 @@
+// 体素统计
+// /stats 统计玩家周围已加载的区块中每种体素的数量和比例 用来检查矿石的稀有程度
+// 直接读取客户端收到的体素数据 没有加载的区块不统计
+
+use bevy::prelude::{IVec3, Res};
+use bevy_console::ConsoleCommand;
+use clap::Parser;
+
+use crate::{
+    common::ClipSpheres,
+    voxel_world::{
+        chunk::{get_chunk_key_i3_by_vec3, ChunkKey},
+        chunk_map::ChunkMap,
+        gen_config::gen_config,
+    },
+};
+
+use super::chunks::{voxel_histogram, voxel_name};
+
+// 统计的最大半径(区块)
+pub const MAX_STATS_RADIUS: i32 = 16;
+
+#[derive(Parser, ConsoleCommand)]
+#[command(
+    name = "stats",
+    about = "count each voxel type in the loaded chunks around the player"
+)]
+pub struct StatsCommand {
+    /// chunk radius around the player
+    radius: Option<i32>,
+}
+
+/**
+ * 以 center 所在的列为中心 统计半径内所有已加载的区块
+ * 返回统计的区块数和每种体素的数量
+ */
+pub fn region_histogram(
+    chunk_map: &ChunkMap,
+    center: IVec3,
+    radius: i32,
+) -> (usize, Vec<(u8, usize)>) {
+    let height = gen_config().world_height;
+    let keys: Vec<ChunkKey> = (-radius..=radius)
+        .flat_map(|x| (-radius..=radius).map(move |z| (x, z)))
+        .flat_map(|(x, z)| {
+            height
+                .chunk_ys()
+                .map(move |y| ChunkKey(IVec3::new(center.x + x, y, center.z + z)))
+        })
+        .filter(|key| chunk_map.get(*key).is_some())
+        .collect();
+    let histogram = voxel_histogram(keys.iter().filter_map(|key| chunk_map.get(*key)).flatten());
+    (keys.len(), histogram)
+}
+
+pub fn voxel_stats(
+    mut stats_command: ConsoleCommand<StatsCommand>,
+    clip_spheres: Res<ClipSpheres>,
+    chunk_map: Res<ChunkMap>,
+) {
+    if let Some(Ok(StatsCommand { radius })) = stats_command.take() {
+        let radius = radius.unwrap_or(2).clamp(0, MAX_STATS_RADIUS);
+        let center = get_chunk_key_i3_by_vec3(clip_spheres.new_sphere.center);
+        let (chunks, histogram) = region_histogram(&chunk_map, center, radius);
+        let total: usize = histogram.iter().map(|(_, count)| count).sum();
+        if total == 0 {
+            stats_command.reply_failed("No loaded chunks around the player");
+            return;
+        }
+        stats_command.reply(format!(
+            "{} voxels in {} chunks around {:?} radius {}",
+            total, chunks, center, radius
+        ));
+        // 稀有的矿石比例很小 多保留几位小数
+        for (id, count) in histogram {
+            stats_command.reply(format!(
+                "{:>12} {:>10} {:>8.3}%",
+                voxel_name(id),
+                count,
+                count as f64 / total as f64 * 100.0
+            ));
+        }
+        stats_command.ok();
+    }
+}
+
+#[test]
+fn test_region_histogram() {
+    use crate::{
+        voxel_world::voxel::{Stone, Voxel, VoxelMaterial},
+        CHUNK_VOLUME,
+    };
+
+    let mut chunk_map = ChunkMap::new();
+    chunk_map.write_chunk(
+        ChunkKey(IVec3::new(0, 0, 0)),
+        vec![Stone::into_voxel(); CHUNK_VOLUME as usize],
+    );
+    chunk_map.write_chunk(
+        ChunkKey(IVec3::new(1, 2, -1)),
+        vec![Voxel::EMPTY; CHUNK_VOLUME as usize],
+    );
+    // 半径外的区块不统计
+    chunk_map.write_chunk(
+        ChunkKey(IVec3::new(3, 0, 0)),
+        vec![Stone::into_voxel(); CHUNK_VOLUME as usize],
+    );
+    let (chunks, histogram) = region_histogram(&chunk_map, IVec3::new(0, 5, 0), 1);
+    assert_eq!(chunks, 2);
+    assert_eq!(
+        histogram,
+        vec![
+            (Voxel::EMPTY.id, CHUNK_VOLUME as usize),
+            (Stone::ID, CHUNK_VOLUME as usize)
+        ]
+    );
+}
@@ -0,0 +1,95 @@
+// 传送 /tp 后面是玩家的名字或者坐标 /tphere 把玩家传送过来
+// 由服务端检查权限 目标不在线时返回失败
+
+use bevy::prelude::ResMut;
+use bevy_console::ConsoleCommand;
+use bevy_renet::renet::RenetClient;
+use clap::Parser;
+
+use crate::client::message_def::{server_command::ServerCommandMessage, ClientChannel};
+
+#[derive(Parser, ConsoleCommand)]
+#[command(
+    name = "tp",
+    about = "teleport to a player (moderator) or to x y z (admin)",
+    allow_negative_numbers = true
+)]
+pub struct TpCommand {
+    /// player name, or the x coordinate followed by y and z
+    target: String,
+    y: Option<f32>,
+    z: Option<f32>,
+}
+
+#[derive(Parser, ConsoleCommand)]
+#[command(name = "tphere", about = "bring a player to you (moderator)")]
+pub struct TpHereCommand {
+    username: String,
+}
+
+// 只有一个参数时是玩家的名字 三个参数时是坐标
+pub fn tp_message(
+    target: String,
+    y: Option<f32>,
+    z: Option<f32>,
+) -> Result<ServerCommandMessage, String> {
+    match (y, z) {
+        (None, None) => Ok(ServerCommandMessage::TeleportToPlayer { username: target }),
+        (Some(y), Some(z)) => match target.parse::<f32>() {
+            Ok(x) if x.is_finite() && y.is_finite() && z.is_finite() => {
+                Ok(ServerCommandMessage::Teleport {
+                    position: [x, y, z],
+                })
+            }
+            _ => Err(format!("Invalid x coordinate {}", target)),
+        },
+        _ => Err(String::from("Usage: tp <player> or tp <x> <y> <z>")),
+    }
+}
+
+// 传送完成后服务端回复
+pub fn teleport(mut tp_command: ConsoleCommand<TpCommand>, client: Option<ResMut<RenetClient>>) {
+    if let Some(Ok(TpCommand { target, y, z })) = tp_command.take() {
+        let Some(mut client) = client else {
+            tp_command.reply_failed("Not connected to server");
+            return;
+        };
+        let command = match tp_message(target, y, z) {
+            Ok(command) => command,
+            Err(err) => {
+                tp_command.reply_failed(err);
+                return;
+            }
+        };
+        let message = bincode::serialize(&command).unwrap();
+        client.send_message(ClientChannel::ServerCommand, message);
+    }
+}
+
+pub fn teleport_here(
+    mut tphere_command: ConsoleCommand<TpHereCommand>,
+    client: Option<ResMut<RenetClient>>,
+) {
+    if let Some(Ok(TpHereCommand { username })) = tphere_command.take() {
+        let Some(mut client) = client else {
+            tphere_command.reply_failed("Not connected to server");
+            return;
+        };
+        let message = bincode::serialize(&ServerCommandMessage::TeleportHere { username }).unwrap();
+        client.send_message(ClientChannel::ServerCommand, message);
+    }
+}
+
+#[test]
+fn test_tp_message() {
+    assert!(matches!(
+        tp_message(String::from("alice"), None, None),
+        Ok(ServerCommandMessage::TeleportToPlayer { username }) if username == "alice"
+    ));
+    assert!(matches!(
+        tp_message(String::from("-12.5"), Some(70.0), Some(3.0)),
+        Ok(ServerCommandMessage::Teleport { position }) if position == [-12.5, 70.0, 3.0]
+    ));
+    assert!(tp_message(String::from("alice"), Some(70.0), Some(3.0)).is_err());
+    assert!(tp_message(String::from("1"), Some(70.0), None).is_err());
+}
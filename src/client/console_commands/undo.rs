@@ -0,0 +1,31 @@
+use bevy::prelude::ResMut;
+use bevy_console::ConsoleCommand;
+use bevy_renet::renet::RenetClient;
+use clap::Parser;
+
+use crate::client::message_def::{chunk_query::ChunkQuery, ClientChannel};
+
+#[derive(Parser, ConsoleCommand)]
+#[command(name = "undo", about = "undo your most recent block edits")]
+pub struct UndoCommand {
+    /// number of edits to undo
+    count: Option<usize>,
+}
+
+// 撤销的结果由服务端返回
+pub fn undo_edits(
+    mut undo_command: ConsoleCommand<UndoCommand>,
+    client: Option<ResMut<RenetClient>>,
+) {
+    if let Some(Ok(UndoCommand { count })) = undo_command.take() {
+        let Some(mut client) = client else {
+            undo_command.reply_failed("Not connected to server");
+            return;
+        };
+        let message = bincode::serialize(&ChunkQuery::Undo {
+            count: count.unwrap_or(1),
+        })
+        .unwrap();
+        client.send_message(ClientChannel::ChunkQuery, message);
+    }
+}
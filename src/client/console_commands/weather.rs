@@ -0,0 +1,43 @@
+use bevy::prelude::{Res, ResMut};
+use bevy_console::ConsoleCommand;
+use bevy_renet::renet::RenetClient;
+use clap::Parser;
+
+use crate::{
+    client::message_def::{server_command::ServerCommandMessage, ClientChannel},
+    sky::weather::{Weather, WeatherState, WEATHER_PERIOD},
+};
+
+#[derive(Parser, ConsoleCommand)]
+#[command(name = "weather", about = "print or set the weather (admin)")]
+pub struct WeatherCommand {
+    #[arg(value_enum)]
+    weather: Option<Weather>,
+    /// how long the weather lasts in seconds
+    #[arg(default_value_t = WEATHER_PERIOD)]
+    duration: f32,
+}
+
+pub fn set_weather(
+    mut weather_command: ConsoleCommand<WeatherCommand>,
+    state: Res<WeatherState>,
+    client: Option<ResMut<RenetClient>>,
+) {
+    if let Some(Ok(WeatherCommand { weather, duration })) = weather_command.take() {
+        let Some(weather) = weather else {
+            weather_command.reply_ok(format!(
+                "Weather: {:?} ({:.0}%)",
+                state.weather,
+                state.intensity * 100.0
+            ));
+            return;
+        };
+        let Some(mut client) = client else {
+            weather_command.reply_failed("Not connected to server");
+            return;
+        };
+        let message =
+            bincode::serialize(&ServerCommandMessage::SetWeather { weather, duration }).unwrap();
+        client.send_message(ClientChannel::ServerCommand, message);
+    }
+}
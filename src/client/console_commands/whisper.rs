@@ -0,0 +1,71 @@
+use bevy::prelude::{Res, ResMut};
+use bevy_console::ConsoleCommand;
+use bevy_renet::renet::RenetClient;
+use clap::Parser;
+
+use crate::client::{
+    message_def::{server_command::ServerCommandMessage, ClientChannel},
+    player::ClientLobby,
+};
+
+#[derive(Parser, ConsoleCommand)]
+#[command(name = "w", about = "send a private message to a player")]
+pub struct WhisperCommand {
+    username: String,
+    text: Vec<String>,
+}
+
+#[derive(Parser, ConsoleCommand)]
+#[command(name = "r", about = "reply to the last private message")]
+pub struct ReplyCommand {
+    text: Vec<String>,
+}
+
+fn send_whisper(client: &mut RenetClient, username: String, text: Vec<String>) {
+    let message = bincode::serialize(&ServerCommandMessage::Whisper {
+        username,
+        text: text.join(" "),
+    })
+    .unwrap();
+    client.send_message(ClientChannel::ServerCommand, message);
+}
+
+// 消息由服务端转发回来后显示
+pub fn whisper(
+    mut whisper_command: ConsoleCommand<WhisperCommand>,
+    client: Option<ResMut<RenetClient>>,
+) {
+    if let Some(Ok(WhisperCommand { username, text })) = whisper_command.take() {
+        let Some(mut client) = client else {
+            whisper_command.reply_failed("Not connected to server");
+            return;
+        };
+        if text.is_empty() {
+            whisper_command.reply_failed("Message is empty");
+            return;
+        }
+        send_whisper(&mut client, username, text);
+    }
+}
+
+pub fn reply_whisper(
+    mut reply_command: ConsoleCommand<ReplyCommand>,
+    client: Option<ResMut<RenetClient>>,
+    lobby: Res<ClientLobby>,
+) {
+    if let Some(Ok(ReplyCommand { text })) = reply_command.take() {
+        let Some(mut client) = client else {
+            reply_command.reply_failed("Not connected to server");
+            return;
+        };
+        let Some(username) = lobby.last_whisper.clone() else {
+            reply_command.reply_failed("No one to reply to");
+            return;
+        };
+        if text.is_empty() {
+            reply_command.reply_failed("Message is empty");
+            return;
+        }
+        send_whisper(&mut client, username, text);
+    }
+}
@@ -0,0 +1,30 @@
+use bevy::prelude::ResMut;
+use bevy_console::ConsoleCommand;
+use bevy_renet::renet::RenetClient;
+use clap::Parser;
+
+use crate::client::message_def::{server_command::ServerCommandMessage, ClientChannel};
+
+#[derive(Parser, ConsoleCommand)]
+#[command(
+    name = "world",
+    about = "move to another world, without a name list the worlds"
+)]
+pub struct WorldCommand {
+    name: Option<String>,
+}
+
+// 服务端传送到世界的出生点后返回结果
+pub fn change_world(
+    mut world_command: ConsoleCommand<WorldCommand>,
+    client: Option<ResMut<RenetClient>>,
+) {
+    if let Some(Ok(WorldCommand { name })) = world_command.take() {
+        let Some(mut client) = client else {
+            world_command.reply_failed("Not connected to server");
+            return;
+        };
+        let message = bincode::serialize(&ServerCommandMessage::World { name }).unwrap();
+        client.send_message(ClientChannel::ServerCommand, message);
+    }
+}
@@ -1,6 +1,38 @@
-use bevy::prelude::{Color, Gizmos, Plugin, Query, Transform, Update, Vec3};
+use bevy::{
+    pbr::wireframe::{Wireframe, WireframePlugin},
+    prelude::{
+        in_state, Assets, Color, Commands, DetectChanges, Entity, Gizmos, IVec2, IVec3, Input,
+        IntoSystemConfigs, KeyCode, MouseButton, OnExit, Plugin, Query, Res, ResMut, Resource,
+        Time, Transform, Update, Vec3, With, Without,
+    },
+    time::{Timer, TimerMode},
+};
+use bevy_easy_localize::Localize;
+use bevy_egui::{egui, EguiContexts};
 
-use crate::server::player::Player;
+use crate::{
+    client::{
+        mesh_display::{MeshManager, MeshUploadQueue, TerrainMesh},
+        player::controller::CharacterController,
+        ray_cast::choose_cube::ChooseCube,
+        settings::GraphicsSettings,
+        state_manager::{notification::Notification, GameState},
+        voxels::mesh_material::{BindlessMaterial, MaterialStorge},
+    },
+    common::ClipSpheresFrozen,
+    server::{player::Player, tick_rate::ServerTickRate},
+    tools::{all_empty, vec3_to_chunk_key_any_xyz},
+    voxel_world::{
+        biomes::{BiomeHeightSampler, BiomeKind, BiomeMapPalette, BIOME_REGISTRY},
+        chunk::{get_chunk_key_i3_by_vec3, ChunkKey},
+        chunk_map::ChunkMap,
+        gen_config::{gen_config, GenConfig},
+        map_database::WorldSeed,
+        voxel::Voxel,
+        voxel_registry::VOXEL_REGISTRY,
+    },
+    CHUNK_SIZE, PROTOCOL_VERSION,
+};
 
 pub struct ClientDebugPlugin;
 
@@ -27,3 +59,756 @@ fn debug_player_aabb(mut gizmos: Gizmos, query: Query<(&Player, &Transform)>) {
         );
     }
 }
+
+#[derive(Debug, Default, Resource)]
+pub struct DebugHudSetting {
+    pub enabled: bool,
+}
+
+/**
+ * 调试信息面板 显示种子 服务端帧率 协议版本 网格队列 区块加载状态和玩家坐标
+ * F2 切换 默认关闭 不需要打开 CLIENT_DEBUG
+ */
+pub struct DebugHudPlugin;
+
+impl Plugin for DebugHudPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.init_resource::<DebugHudSetting>();
+        app.add_systems(
+            Update,
+            (toggle_debug_hud, debug_hud)
+                .chain()
+                .run_if(in_state(GameState::Game)),
+        );
+        app.add_systems(OnExit(GameState::Game), setdown_debug_hud);
+    }
+}
+
+fn toggle_debug_hud(keys: Res<Input<KeyCode>>, mut setting: ResMut<DebugHudSetting>) {
+    if keys.just_pressed(KeyCode::F2) {
+        setting.enabled = !setting.enabled;
+    }
+}
+
+fn setdown_debug_hud(mut setting: ResMut<DebugHudSetting>) {
+    setting.enabled = false;
+}
+
+fn debug_hud(
+    mut contexts: EguiContexts,
+    setting: Res<DebugHudSetting>,
+    world_seed: Option<Res<WorldSeed>>,
+    tick_rate: Option<Res<ServerTickRate>>,
+    localize: Res<Localize>,
+    mut notification: ResMut<Notification>,
+    player_query: Query<&Transform, With<CharacterController>>,
+    upload_queue: Option<Res<MeshUploadQueue>>,
+    frozen: Option<Res<ClipSpheresFrozen>>,
+) {
+    if !setting.enabled {
+        return;
+    }
+    let position = player_query.get_single().ok().map(|t| t.translation);
+    egui::Window::new("Debug")
+        .anchor(egui::Align2::LEFT_TOP, [10.0, 10.0])
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.horizontal(|ui| match world_seed {
+                Some(seed) => {
+                    ui.label(format!("Seed: {}", seed.0));
+                    if ui.button("Copy").clicked() {
+                        ui.output_mut(|o| o.copied_text = seed.0.to_string());
+                    }
+                }
+                None => {
+                    ui.label("Seed: -");
+                }
+            });
+            match tick_rate {
+                Some(tick_rate) => ui.label(format!(
+                    "Tick rate: {}Hz ({:.1}ms)",
+                    tick_rate.rate,
+                    tick_rate.interval().as_secs_f32() * 1000.0
+                )),
+                None => ui.label("Tick rate: -"),
+            };
+            ui.label(format!("Protocol: {}", PROTOCOL_VERSION));
+            if let Some(upload_queue) = upload_queue {
+                ui.label(format!("Mesh queue: {}", upload_queue.ready.len()));
+            }
+            if let Some(frozen) = frozen {
+                ui.label(if frozen.0 {
+                    "Chunk loading: frozen (F9)"
+                } else {
+                    "Chunk loading: on (F9)"
+                });
+            }
+            if let Some(position) = position {
+                ui.horizontal(|ui| {
+                    ui.label(format!(
+                        "XYZ: {:.1} {:.1} {:.1}",
+                        position.x, position.y, position.z
+                    ));
+                    if ui.button("Copy").clicked() {
+                        let text = position_report(position, world_seed.as_ref().map(|s| s.0));
+                        ui.output_mut(|o| o.copied_text = text);
+                        notify_copied(&mut notification, &localize);
+                    }
+                });
+            }
+        });
+}
+
+/**
+ * 玩家位置的文本 方便粘贴到问题报告里
+ * 例如: 12.50 -3.00 7.25 chunk(1 0 0) seed 42
+ */
+pub fn position_report(position: Vec3, seed: Option<i32>) -> String {
+    let chunk = get_chunk_key_i3_by_vec3(position);
+    let mut text = format!(
+        "{:.2} {:.2} {:.2} chunk({} {} {})",
+        position.x, position.y, position.z, chunk.x, chunk.y, chunk.z
+    );
+    if let Some(seed) = seed {
+        text.push_str(&format!(" seed {}", seed));
+    }
+    text
+}
+
+fn notify_copied(notification: &mut Notification, localize: &Localize) {
+    notification.info(localize.get("已复制坐标"));
+}
+
+/**
+ * F7 复制玩家的坐标到剪贴板
+ * 调试面板没有打开时也可以使用
+ */
+pub struct CopyPositionPlugin;
+
+impl Plugin for CopyPositionPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.add_systems(Update, copy_position.run_if(in_state(GameState::Game)));
+    }
+}
+
+fn copy_position(
+    keys: Res<Input<KeyCode>>,
+    mut contexts: EguiContexts,
+    world_seed: Option<Res<WorldSeed>>,
+    localize: Res<Localize>,
+    mut notification: ResMut<Notification>,
+    player_query: Query<&Transform, With<CharacterController>>,
+) {
+    if !keys.just_pressed(KeyCode::F7) {
+        return;
+    }
+    let Ok(transform) = player_query.get_single() else {
+        return;
+    };
+    let text = position_report(transform.translation, world_seed.map(|s| s.0));
+    contexts.ctx_mut().output_mut(|o| o.copied_text = text);
+    notify_copied(&mut notification, &localize);
+}
+
+/**
+ * F9 冻结或者恢复区块的加载 见 ClipSpheresFrozen
+ * 默认不冻结 离开游戏时恢复
+ */
+pub struct FreezeChunksPlugin;
+
+impl Plugin for FreezeChunksPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.add_systems(
+            Update,
+            toggle_freeze_chunks.run_if(in_state(GameState::Game)),
+        );
+        app.add_systems(OnExit(GameState::Game), setdown_freeze_chunks);
+    }
+}
+
+fn toggle_freeze_chunks(
+    keys: Res<Input<KeyCode>>,
+    mut frozen: ResMut<ClipSpheresFrozen>,
+    mut notification: ResMut<Notification>,
+) {
+    if !keys.just_pressed(KeyCode::F9) {
+        return;
+    }
+    frozen.0 = !frozen.0;
+    if frozen.0 {
+        notification.info("Chunk loading frozen");
+    } else {
+        notification.info("Chunk loading resumed");
+    }
+}
+
+fn setdown_freeze_chunks(mut frozen: ResMut<ClipSpheresFrozen>) {
+    frozen.0 = false;
+}
+
+// 区块边框显示的范围(区块个数)
+const CHUNK_BORDER_RANGE: i32 = 2;
+
+#[derive(Debug, Default, Resource)]
+pub struct ChunkBorderSetting {
+    pub enabled: bool,
+}
+
+/**
+ * 显示区块边框 用来排查区块之间的接缝问题
+ * F3 切换 默认关闭
+ */
+pub struct ChunkBorderPlugin;
+
+impl Plugin for ChunkBorderPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.init_resource::<ChunkBorderSetting>();
+        app.add_systems(
+            Update,
+            (toggle_chunk_borders, draw_chunk_borders).run_if(in_state(GameState::Game)),
+        );
+    }
+}
+
+fn toggle_chunk_borders(keys: Res<Input<KeyCode>>, mut setting: ResMut<ChunkBorderSetting>) {
+    if keys.just_pressed(KeyCode::F3) {
+        setting.enabled = !setting.enabled;
+    }
+}
+
+// 颜色: 绿色 已经生成网格 黄色 有数据没有网格 灰色 空区块
+fn draw_chunk_borders(
+    mut gizmos: Gizmos,
+    setting: Res<ChunkBorderSetting>,
+    chunk_map: Res<ChunkMap>,
+    mesh_manager: Res<MeshManager>,
+    player_query: Query<&Transform, With<CharacterController>>,
+) {
+    if !setting.enabled {
+        return;
+    }
+    let Ok(transform) = player_query.get_single() else {
+        return;
+    };
+    let center = get_chunk_key_i3_by_vec3(transform.translation);
+    for x in -CHUNK_BORDER_RANGE..=CHUNK_BORDER_RANGE {
+        for y in -CHUNK_BORDER_RANGE..=CHUNK_BORDER_RANGE {
+            for z in -CHUNK_BORDER_RANGE..=CHUNK_BORDER_RANGE {
+                let chunk_key = ChunkKey(center + IVec3::new(x, y, z));
+                let Some(voxels) = chunk_map.get(chunk_key) else {
+                    continue;
+                };
+                let color = if all_empty(voxels) {
+                    Color::GRAY
+                } else if mesh_manager.entities.contains_key(&chunk_key.to_y_zore()) {
+                    Color::GREEN
+                } else {
+                    Color::YELLOW
+                };
+                gizmos.cuboid(
+                    Transform {
+                        translation: (chunk_key.0 * CHUNK_SIZE).as_vec3(),
+                        scale: Vec3::splat(CHUNK_SIZE as f32),
+                        ..Default::default()
+                    },
+                    color,
+                );
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default, Resource)]
+pub struct WireframeSetting {
+    pub enabled: bool,
+}
+
+/**
+ * 区块网格显示为线框 用来检查贪心合并的结果和缺失的面
+ * F4 切换 默认关闭 只添加或者删除 Wireframe 组件 不修改原来的材质
+ */
+pub struct MeshWireframePlugin;
+
+impl Plugin for MeshWireframePlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.add_plugins(WireframePlugin);
+        app.init_resource::<WireframeSetting>();
+        app.add_systems(
+            Update,
+            (toggle_wireframe, apply_wireframe)
+                .chain()
+                .run_if(in_state(GameState::Game)),
+        );
+    }
+}
+
+fn toggle_wireframe(keys: Res<Input<KeyCode>>, mut setting: ResMut<WireframeSetting>) {
+    if keys.just_pressed(KeyCode::F4) {
+        setting.enabled = !setting.enabled;
+    }
+}
+
+// 开启时新加载的区块也会显示线框
+fn apply_wireframe(
+    mut commands: Commands,
+    setting: Res<WireframeSetting>,
+    without_wireframe: Query<Entity, (With<TerrainMesh>, Without<Wireframe>)>,
+    with_wireframe: Query<Entity, (With<TerrainMesh>, With<Wireframe>)>,
+) {
+    if setting.enabled {
+        for entity in without_wireframe.iter() {
+            commands.entity(entity).insert(Wireframe);
+        }
+    } else if setting.is_changed() {
+        for entity in with_wireframe.iter() {
+            commands.entity(entity).remove::<Wireframe>();
+        }
+    }
+}
+
+#[derive(Debug, Default, Resource)]
+pub struct FlatColorSetting {
+    pub enabled: bool,
+}
+
+/**
+ * 区块不使用贴图 每种体素显示为一种纯色 只保留光照和阴影
+ * 用来区分几何 光照的问题和贴图 UV 的问题 可以和线框一起使用
+ * F8 切换 默认关闭 修改区块共用的材质 新加载的区块也会生效
+ */
+pub struct FlatColorPlugin;
+
+impl Plugin for FlatColorPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.init_resource::<FlatColorSetting>();
+        app.add_systems(
+            Update,
+            (toggle_flat_color, apply_flat_color)
+                .chain()
+                .run_if(in_state(GameState::Game)),
+        );
+        app.add_systems(OnExit(GameState::Game), setdown_flat_color);
+    }
+}
+
+fn toggle_flat_color(
+    keys: Res<Input<KeyCode>>,
+    mut setting: ResMut<FlatColorSetting>,
+    mut notification: ResMut<Notification>,
+) {
+    if !keys.just_pressed(KeyCode::F8) {
+        return;
+    }
+    setting.enabled = !setting.enabled;
+    if setting.enabled {
+        notification.info("Textures disabled");
+    } else {
+        notification.info("Textures enabled");
+    }
+}
+
+fn apply_flat_color(
+    setting: Res<FlatColorSetting>,
+    storge: Option<Res<MaterialStorge>>,
+    mut materials: ResMut<Assets<BindlessMaterial>>,
+) {
+    let Some(storge) = storge else {
+        return;
+    };
+    // get_mut 会让材质重新准备 只在需要切换时调用
+    let changed = materials
+        .get(&storge.0)
+        .map_or(false, |material| material.flat != setting.enabled);
+    if !changed {
+        return;
+    }
+    if let Some(material) = materials.get_mut(&storge.0) {
+        material.flat = setting.enabled;
+    }
+}
+
+// 重新进入游戏时会重新创建材质
+fn setdown_flat_color(mut setting: ResMut<FlatColorSetting>) {
+    setting.enabled = false;
+}
+
+// 群落覆盖图的格子数 格子之间的距离(方块) 每个格子显示的像素
+const BIOME_OVERLAY_SIZE: i32 = 32;
+const BIOME_OVERLAY_STEP: i32 = 4;
+const BIOME_OVERLAY_CELL: f32 = 4.0;
+
+#[derive(Debug, Resource)]
+pub struct BiomeOverlay {
+    pub enabled: bool,
+    timer: Timer,
+    // 上次采样的中心格子和种子 没有变化时不重新采样
+    sampled: Option<(IVec2, i32)>,
+    // 群落特征值 x 向右 z 向下
+    attrs: Vec<f32>,
+}
+
+impl Default for BiomeOverlay {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            timer: Timer::from_seconds(0.25, TimerMode::Repeating),
+            sampled: None,
+            attrs: Vec::new(),
+        }
+    }
+}
+
+/**
+ * 群落噪声的覆盖图 用来调整群落的阈值
+ * 在右下角显示玩家周围的群落特征值 颜色使用群落的调色板
+ * F6 切换 默认关闭 稀疏采样 并且只在玩家走出当前格子后重新采样
+ */
+pub struct BiomeOverlayPlugin;
+
+impl Plugin for BiomeOverlayPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.init_resource::<BiomeOverlay>();
+        app.add_systems(
+            Update,
+            (
+                toggle_biome_overlay,
+                sample_biome_overlay,
+                draw_biome_overlay,
+            )
+                .chain()
+                .run_if(in_state(GameState::Game)),
+        );
+    }
+}
+
+fn toggle_biome_overlay(keys: Res<Input<KeyCode>>, mut overlay: ResMut<BiomeOverlay>) {
+    if keys.just_pressed(KeyCode::F6) {
+        overlay.enabled = !overlay.enabled;
+    }
+}
+
+fn sample_biome_overlay(
+    time: Res<Time>,
+    world_seed: Option<Res<WorldSeed>>,
+    mut overlay: ResMut<BiomeOverlay>,
+    player_query: Query<&Transform, With<CharacterController>>,
+) {
+    if !overlay.enabled {
+        return;
+    }
+    if !overlay.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let (Some(world_seed), Ok(transform)) = (world_seed, player_query.get_single()) else {
+        return;
+    };
+    let position = transform.translation / BIOME_OVERLAY_STEP as f32;
+    let center = IVec2::new(position.x.floor() as i32, position.z.floor() as i32);
+    if overlay.sampled == Some((center, world_seed.0)) {
+        return;
+    }
+    // 和 biomes_noise 使用相同的噪声
+    let sampler = BiomeHeightSampler::new(world_seed.0);
+    let half = (CHUNK_SIZE / 2) as f64;
+    let mut attrs = Vec::with_capacity((BIOME_OVERLAY_SIZE * BIOME_OVERLAY_SIZE) as usize);
+    for z in 0..BIOME_OVERLAY_SIZE {
+        for x in 0..BIOME_OVERLAY_SIZE {
+            let cell = center + IVec2::new(x, z) - IVec2::splat(BIOME_OVERLAY_SIZE / 2);
+            let world = cell * BIOME_OVERLAY_STEP;
+            attrs.push(sampler.biome_attr_at(world.x as f64 + half, world.y as f64 + half));
+        }
+    }
+    overlay.attrs = attrs;
+    overlay.sampled = Some((center, world_seed.0));
+}
+
+// 设置中的群落地图配色 越接近下一个群落的阈值越暗 可以看出梯度
+fn biome_overlay_color(attr: f32, palette: BiomeMapPalette, config: &GenConfig) -> egui::Color32 {
+    let kind = BiomeKind::from_attr(attr, config);
+    let index = BIOME_REGISTRY
+        .iter()
+        .position(|entry| entry.kind == kind)
+        .unwrap_or(0);
+    let min = if index == 0 {
+        -1.0
+    } else {
+        BIOME_REGISTRY[index - 1].max_attr
+    };
+    let max = BIOME_REGISTRY[index].max_attr.min(1.0);
+    let t = ((attr - min) / (max - min)).clamp(0.0, 1.0);
+    let shade = 1.0 - 0.5 * t;
+    let [r, g, b, _] = kind.map_color(palette).as_rgba_f32();
+    egui::Color32::from_rgb(
+        (r * shade * 255.0) as u8,
+        (g * shade * 255.0) as u8,
+        (b * shade * 255.0) as u8,
+    )
+}
+
+fn draw_biome_overlay(
+    mut contexts: EguiContexts,
+    overlay: Res<BiomeOverlay>,
+    settings: Res<GraphicsSettings>,
+) {
+    if !overlay.enabled || overlay.attrs.is_empty() {
+        return;
+    }
+    egui::Window::new("Biome")
+        .anchor(egui::Align2::RIGHT_BOTTOM, [-10.0, -10.0])
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            let config = gen_config();
+            let size = BIOME_OVERLAY_SIZE as f32 * BIOME_OVERLAY_CELL;
+            let (rect, _) = ui.allocate_exact_size(egui::vec2(size, size), egui::Sense::hover());
+            let painter = ui.painter_at(rect);
+            for (index, attr) in overlay.attrs.iter().enumerate() {
+                let x = index as i32 % BIOME_OVERLAY_SIZE;
+                let z = index as i32 / BIOME_OVERLAY_SIZE;
+                let min = rect.min
+                    + egui::vec2(x as f32 * BIOME_OVERLAY_CELL, z as f32 * BIOME_OVERLAY_CELL);
+                painter.rect_filled(
+                    egui::Rect::from_min_size(min, egui::Vec2::splat(BIOME_OVERLAY_CELL)),
+                    0.0,
+                    biome_overlay_color(*attr, settings.biome_palette, &config),
+                );
+            }
+            // 玩家所在的位置
+            painter.circle_filled(rect.center(), 2.0, egui::Color32::RED);
+            let center = (BIOME_OVERLAY_SIZE / 2) * (BIOME_OVERLAY_SIZE + 1);
+            let attr = overlay.attrs[center as usize];
+            ui.label(format!(
+                "{:?} ({:.3})",
+                BiomeKind::from_attr(attr, &config),
+                attr
+            ));
+        });
+}
+
+#[derive(Debug, Default, Resource)]
+pub struct MeasureTool {
+    pub enabled: bool,
+    // 选中的方块 最多两个
+    pub points: Vec<IVec3>,
+}
+
+/**
+ * 测量两个方块之间的距离 用来检查生成的高度和建筑的大小
+ * /measure 切换 开启后中键选择准星指向的方块
+ * 第三次点击或者 Esc 清除测量
+ */
+pub struct MeasurePlugin;
+
+impl Plugin for MeasurePlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.init_resource::<MeasureTool>();
+        app.add_systems(
+            Update,
+            (pick_measure_point, draw_measure)
+                .chain()
+                .run_if(in_state(GameState::Game)),
+        );
+        app.add_systems(OnExit(GameState::Game), setdown_measure);
+    }
+}
+
+fn pick_measure_point(
+    mouse_button_input: Res<Input<MouseButton>>,
+    keys: Res<Input<KeyCode>>,
+    choose_cube: Res<ChooseCube>,
+    mut measure: ResMut<MeasureTool>,
+) {
+    if !measure.enabled {
+        return;
+    }
+    if keys.just_pressed(KeyCode::Escape) {
+        measure.points.clear();
+        return;
+    }
+    if !mouse_button_input.just_pressed(MouseButton::Middle) {
+        return;
+    }
+    if measure.points.len() >= 2 {
+        measure.points.clear();
+        return;
+    }
+    if let Some(center) = choose_cube.center {
+        measure.points.push(center.floor().as_ivec3());
+    }
+}
+
+/**
+ * 测量结果的文本
+ * 例如: dx 3 dy -2 dz 0 | size 4x3x1 | distance 3.61
+ */
+pub fn measure_report(a: IVec3, b: IVec3) -> String {
+    let delta = b - a;
+    let size = delta.abs() + IVec3::ONE;
+    format!(
+        "dx {} dy {} dz {} | size {}x{}x{} | distance {:.2}",
+        delta.x,
+        delta.y,
+        delta.z,
+        size.x,
+        size.y,
+        size.z,
+        delta.as_vec3().length()
+    )
+}
+
+fn draw_measure(mut gizmos: Gizmos, mut contexts: EguiContexts, measure: Res<MeasureTool>) {
+    if !measure.enabled {
+        return;
+    }
+    let centers: Vec<Vec3> = measure
+        .points
+        .iter()
+        .map(|point| point.as_vec3() + Vec3::splat(0.5))
+        .collect();
+    for center in centers.iter() {
+        gizmos.cuboid(
+            Transform::from_translation(*center).with_scale(Vec3::splat(1.02)),
+            Color::CYAN,
+        );
+    }
+    let text = match measure.points.as_slice() {
+        [a, b] => {
+            gizmos.line(centers[0], centers[1], Color::CYAN);
+            measure_report(*a, *b)
+        }
+        [a] => format!("From {} {} {}", a.x, a.y, a.z),
+        _ => "Middle click a block".to_string(),
+    };
+    egui::Window::new("Measure")
+        .anchor(egui::Align2::CENTER_TOP, [0.0, 10.0])
+        .resizable(false)
+        .title_bar(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label(text);
+        });
+}
+
+fn setdown_measure(mut measure: ResMut<MeasureTool>) {
+    *measure = MeasureTool::default();
+}
+
+#[derive(Debug, Default, Resource)]
+pub struct TargetReadout {
+    pub enabled: bool,
+    // 上次的目标 (方块中心, 法向量对面的方块) 没有变化时不重新查询
+    target: Option<(Vec3, Option<Vec3>)>,
+    text: Option<String>,
+}
+
+/**
+ * 在准星下面显示指向的方块 坐标 体素类型和击中的面
+ * 用来检查射线检测和放置的位置 F10 切换 默认关闭 没有目标时不显示
+ */
+pub struct TargetReadoutPlugin;
+
+impl Plugin for TargetReadoutPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.init_resource::<TargetReadout>();
+        app.add_systems(
+            Update,
+            (
+                toggle_target_readout,
+                update_target_readout,
+                draw_target_readout,
+            )
+                .chain()
+                .run_if(in_state(GameState::Game)),
+        );
+        app.add_systems(OnExit(GameState::Game), setdown_target_readout);
+    }
+}
+
+// 击中的面 由方块中心指向法向量对面的方块
+pub fn face_name(center: Vec3, out_center: Vec3) -> &'static str {
+    let normal = (out_center - center).round();
+    match (normal.x as i32, normal.y as i32, normal.z as i32) {
+        (1, 0, 0) => "+X",
+        (-1, 0, 0) => "-X",
+        (0, 1, 0) => "+Y (top)",
+        (0, -1, 0) => "-Y (bottom)",
+        (0, 0, 1) => "+Z",
+        (0, 0, -1) => "-Z",
+        _ => "-",
+    }
+}
+
+/**
+ * 目标方块的文本
+ * 例如: Block 3 64 -2 | Stone | Face +Y (top)
+ */
+pub fn target_report(center: Vec3, out_center: Option<Vec3>, voxel: Option<Voxel>) -> String {
+    let block = center.floor().as_ivec3();
+    let name = match voxel {
+        Some(voxel) => match VOXEL_REGISTRY.get(voxel.id) {
+            Some(def) => def.name.to_string(),
+            None => format!("#{}", voxel.id),
+        },
+        None => "not loaded".to_string(),
+    };
+    let face = out_center.map_or("-", |out_center| face_name(center, out_center));
+    format!(
+        "Block {} {} {} | {} | Face {}",
+        block.x, block.y, block.z, name, face
+    )
+}
+
+fn toggle_target_readout(
+    keys: Res<Input<KeyCode>>,
+    mut readout: ResMut<TargetReadout>,
+    mut notification: ResMut<Notification>,
+) {
+    if !keys.just_pressed(KeyCode::F10) {
+        return;
+    }
+    readout.enabled = !readout.enabled;
+    readout.target = None;
+    readout.text = None;
+    if readout.enabled {
+        notification.info("Target readout on");
+    } else {
+        notification.info("Target readout off");
+    }
+}
+
+fn update_target_readout(
+    choose_cube: Res<ChooseCube>,
+    chunk_map: Res<ChunkMap>,
+    mut readout: ResMut<TargetReadout>,
+) {
+    if !readout.enabled {
+        return;
+    }
+    let target = choose_cube
+        .center
+        .map(|center| (center, choose_cube.out_center));
+    // 目标不变时方块也可能被修改 区块更新时一起刷新
+    if target == readout.target && !chunk_map.is_changed() {
+        return;
+    }
+    readout.target = target;
+    readout.text = target.map(|(center, out_center)| {
+        let (chunk_key, xyz) = vec3_to_chunk_key_any_xyz(center);
+        target_report(center, out_center, chunk_map.get_block(chunk_key, xyz))
+    });
+}
+
+fn draw_target_readout(mut contexts: EguiContexts, readout: Res<TargetReadout>) {
+    let (true, Some(text)) = (readout.enabled, readout.text.as_ref()) else {
+        return;
+    };
+    egui::Window::new("Target")
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 40.0])
+        .resizable(false)
+        .title_bar(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label(text);
+        });
+}
+
+fn setdown_target_readout(mut readout: ResMut<TargetReadout>) {
+    *readout = TargetReadout::default();
+}
@@ -0,0 +1,462 @@
+// 地表装饰
+// 草丛 花 枯灌木 石头 只是显示用的小网格 不是体素 不影响碰撞 射线和区块数据
+// 每个区块列一个网格 跟随地形网格生成和删除 位置只和种子 区块和群落有关
+
+use bevy::{
+    pbr::NotShadowCaster,
+    prelude::{
+        in_state, AssetEvent, Assets, Color, Commands, DetectChanges, Entity, EventReader, Handle,
+        IVec3, IntoSystemConfigs, Local, Mesh, OnExit, PbrBundle, Plugin, Res, ResMut, Resource,
+        StandardMaterial, Update, Vec3,
+    },
+    render::mesh::{Indices, PrimitiveTopology},
+    utils::{HashMap, HashSet},
+};
+use rand::Rng;
+
+use crate::{
+    common::ClipSpheres,
+    tools::chunk_key_any_xyz_to_vec3,
+    voxel_world::{
+        biomes::{refresh_sampler, BiomeHeightSampler, BiomeKind, DecorationKind},
+        chunk::{chunk_rng, get_chunk_key_i3_by_vec3, ChunkKey},
+        chunk_map::ChunkMap,
+        gen_config::gen_config,
+        map_database::WorldSeed,
+        voxel::Voxel,
+    },
+    CHUNK_SIZE_U32,
+};
+
+use super::{
+    mesh_display::{mesh_upload_priority, MeshManager},
+    settings::{GraphicsSettings, DECORATION_DENSITY_RANGE},
+    state_manager::GameState,
+};
+
+// 装饰的随机数 salt
+const DECORATION_SALT: u64 = 0x6465_636f;
+// 每帧最多生成的区块列
+const DECORATIONS_PER_FRAME: usize = 4;
+
+// 一个装饰 pos 是底部的中心
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Decoration {
+    pub kind: DecorationKind,
+    pub pos: Vec3,
+    pub scale: f32,
+    // 绕 y 轴的旋转
+    pub angle: f32,
+    // 颜色的变化 花的颜色由这个决定
+    pub variant: u32,
+}
+
+#[derive(Debug, Default, Resource)]
+pub struct DecorationManager {
+    // 已经处理过的区块列 没有装饰的区块列是 None
+    pub entities: HashMap<ChunkKey, Option<Entity>>,
+    pub material: Option<Handle<StandardMaterial>>,
+    // 生成时使用的密度 修改后重新生成
+    pub density: f32,
+}
+
+impl DecorationManager {
+    fn remove(&mut self, commands: &mut Commands, chunk_key: &ChunkKey) {
+        if let Some(Some(entity)) = self.entities.remove(chunk_key) {
+            commands.entity(entity).despawn();
+        }
+    }
+
+    fn clear(&mut self, commands: &mut Commands) {
+        for entity in self.entities.drain().filter_map(|(_, entity)| entity) {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+pub struct DecorationPlugin;
+
+impl Plugin for DecorationPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.init_resource::<DecorationManager>();
+        app.add_systems(
+            Update,
+            (refresh_decorations, spawn_decorations)
+                .chain()
+                .run_if(in_state(GameState::Game)),
+        );
+        app.add_systems(OnExit(GameState::Game), setdown_decorations);
+    }
+}
+
+/**
+ * 区块列中每一列最上面的方块上放置装饰
+ * 每一列使用的随机数个数固定 修改一列的方块不会影响其他列的装饰
+ */
+pub fn column_decorations(
+    chunk_map: &ChunkMap,
+    chunk_key: ChunkKey,
+    seed: i32,
+    density: f32,
+    biome_at: impl Fn(Vec3) -> BiomeKind,
+) -> Vec<Decoration> {
+    let mut rng = chunk_rng(chunk_key.to_y_zore(), seed, DECORATION_SALT);
+    let mut result = Vec::new();
+    for x in 0..CHUNK_SIZE_U32 {
+        for z in 0..CHUNK_SIZE_U32 {
+            let roll: f32 = rng.gen();
+            let offset = Vec3::new(rng.gen_range(-0.3..0.3), 0.0, rng.gen_range(-0.3..0.3));
+            let scale = rng.gen_range(0.7..1.2);
+            let angle = rng.gen_range(0.0..std::f32::consts::PI);
+            let variant = rng.gen();
+
+            let Some((key, xyz, voxel)) = column_top(chunk_map, chunk_key, x, z) else {
+                continue;
+            };
+            let center = chunk_key_any_xyz_to_vec3(key, xyz);
+            let mut total = 0.0;
+            for entry in biome_at(center).entry().decorations {
+                total += entry.density * density;
+                if roll < total {
+                    if entry.ground == voxel.id {
+                        result.push(Decoration {
+                            kind: entry.kind,
+                            pos: center + Vec3::Y * 0.5 + offset,
+                            scale,
+                            angle,
+                            variant,
+                        });
+                    }
+                    break;
+                }
+            }
+        }
+    }
+    result
+}
+
+// 一列最上面不是空气的方块 数据不完整时返回 None
+fn column_top(
+    chunk_map: &ChunkMap,
+    chunk_key: ChunkKey,
+    x: u32,
+    z: u32,
+) -> Option<(ChunkKey, [u32; 3], Voxel)> {
+    for chunk_y in gen_config().world_height.chunk_ys().rev() {
+        let key = ChunkKey(IVec3::new(chunk_key.0.x, chunk_y, chunk_key.0.z));
+        for y in (0..CHUNK_SIZE_U32).rev() {
+            let voxel = chunk_map.get_block(key, [x, y, z])?;
+            if voxel.id != Voxel::EMPTY.id {
+                return Some((key, [x, y, z], voxel));
+            }
+        }
+    }
+    None
+}
+
+// 装饰的颜色 (底部, 顶部)
+fn decoration_colors(decoration: &Decoration) -> (Color, Color) {
+    match decoration.kind {
+        DecorationKind::GrassTuft => (Color::rgb(0.22, 0.42, 0.12), Color::rgb(0.45, 0.70, 0.25)),
+        DecorationKind::Flower => {
+            let flowers = [
+                Color::rgb(0.90, 0.20, 0.20),
+                Color::rgb(0.95, 0.85, 0.25),
+                Color::rgb(0.65, 0.40, 0.90),
+                Color::rgb(0.95, 0.95, 0.95),
+            ];
+            (
+                Color::rgb(0.22, 0.42, 0.12),
+                flowers[decoration.variant as usize % flowers.len()],
+            )
+        }
+        DecorationKind::DeadShrub => (Color::rgb(0.35, 0.25, 0.15), Color::rgb(0.55, 0.45, 0.30)),
+        DecorationKind::Rock => {
+            let shade = 0.45 + (decoration.variant % 16) as f32 / 100.0;
+            (
+                Color::rgb(shade, shade, shade),
+                Color::rgb(shade, shade, shade),
+            )
+        }
+    }
+}
+
+#[derive(Default)]
+struct DecorationMeshBuilder {
+    positions: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+    colors: Vec<[f32; 4]>,
+    indices: Vec<u32>,
+}
+
+impl DecorationMeshBuilder {
+    // 四个顶点按照 左下 右下 右上 左上 的顺序
+    fn quad(&mut self, corners: [Vec3; 4], normal: Vec3, bottom: Color, top: Color) {
+        let start = self.positions.len() as u32;
+        for (i, corner) in corners.iter().enumerate() {
+            self.positions.push(corner.to_array());
+            self.normals.push(normal.to_array());
+            let color = if i < 2 { bottom } else { top };
+            self.colors.push(color.as_linear_rgba_f32());
+        }
+        self.indices
+            .extend_from_slice(&[start, start + 1, start + 2, start, start + 2, start + 3]);
+    }
+
+    /**
+     * 交叉的两个面片 法线朝上 光照和地面一致
+     * 材质不剔除背面 两面都可以看到
+     */
+    fn cross(&mut self, decoration: &Decoration, width: f32, height: f32) {
+        let (bottom, top) = decoration_colors(decoration);
+        let up = Vec3::Y * height * decoration.scale;
+        for angle in [
+            decoration.angle,
+            decoration.angle + std::f32::consts::FRAC_PI_2,
+        ] {
+            let half = Vec3::new(angle.cos(), 0.0, angle.sin()) * width * decoration.scale / 2.0;
+            let (a, b) = (decoration.pos - half, decoration.pos + half);
+            self.quad([a, b, b + up, a + up], Vec3::Y, bottom, top);
+        }
+    }
+
+    // 压扁的小方块 底面贴着地面看不到 不生成
+    fn rock(&mut self, decoration: &Decoration) {
+        let (color, _) = decoration_colors(decoration);
+        let half = Vec3::new(0.2, 0.0, 0.15) * decoration.scale;
+        let height = 0.15 * decoration.scale;
+        let (sin, cos) = decoration.angle.sin_cos();
+        let rotate = |v: Vec3| Vec3::new(v.x * cos - v.z * sin, v.y, v.x * sin + v.z * cos);
+        let corner = |x: f32, y: f32, z: f32| {
+            decoration.pos + rotate(Vec3::new(x * half.x, y * height, z * half.z))
+        };
+        let faces = [
+            // 上 前 后 右 左
+            (
+                [
+                    corner(-1., 1., 1.),
+                    corner(1., 1., 1.),
+                    corner(1., 1., -1.),
+                    corner(-1., 1., -1.),
+                ],
+                Vec3::Y,
+            ),
+            (
+                [
+                    corner(-1., 0., 1.),
+                    corner(1., 0., 1.),
+                    corner(1., 1., 1.),
+                    corner(-1., 1., 1.),
+                ],
+                rotate(Vec3::Z),
+            ),
+            (
+                [
+                    corner(1., 0., -1.),
+                    corner(-1., 0., -1.),
+                    corner(-1., 1., -1.),
+                    corner(1., 1., -1.),
+                ],
+                rotate(Vec3::NEG_Z),
+            ),
+            (
+                [
+                    corner(1., 0., 1.),
+                    corner(1., 0., -1.),
+                    corner(1., 1., -1.),
+                    corner(1., 1., 1.),
+                ],
+                rotate(Vec3::X),
+            ),
+            (
+                [
+                    corner(-1., 0., -1.),
+                    corner(-1., 0., 1.),
+                    corner(-1., 1., 1.),
+                    corner(-1., 1., -1.),
+                ],
+                rotate(Vec3::NEG_X),
+            ),
+        ];
+        for (corners, normal) in faces {
+            self.quad(corners, normal, color, color);
+        }
+    }
+
+    fn build(self) -> Option<Mesh> {
+        if self.indices.is_empty() {
+            return None;
+        }
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, self.positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, self.normals);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, self.colors);
+        mesh.set_indices(Some(Indices::U32(self.indices)));
+        Some(mesh)
+    }
+}
+
+// 一个区块列的所有装饰合并成一个网格 使用世界坐标
+pub fn decoration_mesh(decorations: &[Decoration]) -> Option<Mesh> {
+    let mut builder = DecorationMeshBuilder::default();
+    for decoration in decorations {
+        match decoration.kind {
+            DecorationKind::GrassTuft => builder.cross(decoration, 0.8, 0.5),
+            DecorationKind::Flower => builder.cross(decoration, 0.4, 0.6),
+            DecorationKind::DeadShrub => builder.cross(decoration, 0.7, 0.6),
+            DecorationKind::Rock => builder.rock(decoration),
+        }
+    }
+    builder.build()
+}
+
+/**
+ * 地形网格删除或者重新生成时 删除对应的装饰 之后重新生成
+ * 关闭装饰或者修改密度时删除所有的装饰
+ */
+fn refresh_decorations(
+    mut commands: Commands,
+    mut manager: ResMut<DecorationManager>,
+    mesh_manager: Res<MeshManager>,
+    settings: Res<GraphicsSettings>,
+    mut mesh_events: EventReader<AssetEvent<Mesh>>,
+) {
+    let density = settings.decoration_density.clamp(
+        *DECORATION_DENSITY_RANGE.start(),
+        *DECORATION_DENSITY_RANGE.end(),
+    );
+    if settings.is_changed() && (!settings.decorations || manager.density != density) {
+        manager.clear(&mut commands);
+        manager.density = density;
+    }
+    let modified: HashSet<Handle<Mesh>> = mesh_events
+        .iter()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { handle } => Some(handle.clone()),
+            _ => None,
+        })
+        .collect();
+    let removed: Vec<ChunkKey> = manager
+        .entities
+        .keys()
+        .filter(|key| {
+            !mesh_manager.entities.contains_key(key)
+                || mesh_manager
+                    .mesh_storge
+                    .get(key)
+                    .map_or(false, |handle| modified.contains(handle))
+        })
+        .copied()
+        .collect();
+    for key in removed.iter() {
+        manager.remove(&mut commands, key);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_decorations(
+    mut commands: Commands,
+    mut manager: ResMut<DecorationManager>,
+    mesh_manager: Res<MeshManager>,
+    chunk_map: Res<ChunkMap>,
+    settings: Res<GraphicsSettings>,
+    world_seed: Option<Res<WorldSeed>>,
+    clip_spheres: Res<ClipSpheres>,
+    mut mesh_assets: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut sampler: Local<Option<(i32, BiomeHeightSampler)>>,
+) {
+    if !settings.decorations {
+        return;
+    }
+    let Some(world_seed) = world_seed else {
+        return;
+    };
+    let seed = world_seed.0;
+    let sampler = refresh_sampler(&mut sampler, seed);
+    let center = get_chunk_key_i3_by_vec3(clip_spheres.new_sphere.center);
+    let mut keys: Vec<ChunkKey> = mesh_manager
+        .entities
+        .keys()
+        .filter(|key| !manager.entities.contains_key(key))
+        .copied()
+        .collect();
+    keys.sort_by_key(|key| mesh_upload_priority(*key, center));
+    let material = manager
+        .material
+        .get_or_insert_with(|| {
+            materials.add(StandardMaterial {
+                base_color: Color::WHITE,
+                perceptual_roughness: 1.0,
+                double_sided: true,
+                cull_mode: None,
+                ..Default::default()
+            })
+        })
+        .clone();
+    for key in keys.into_iter().take(DECORATIONS_PER_FRAME) {
+        let decorations = column_decorations(&chunk_map, key, seed, manager.density, |pos| {
+            sampler.biome_at(pos.x, pos.z)
+        });
+        let entity = decoration_mesh(&decorations).map(|mesh| {
+            commands
+                .spawn((
+                    PbrBundle {
+                        mesh: mesh_assets.add(mesh),
+                        material: material.clone(),
+                        ..Default::default()
+                    },
+                    NotShadowCaster,
+                ))
+                .id()
+        });
+        manager.entities.insert(key, entity);
+    }
+}
+
+fn setdown_decorations(mut commands: Commands, mut manager: ResMut<DecorationManager>) {
+    manager.clear(&mut commands);
+}
+
+#[test]
+fn test_column_decorations() {
+    use crate::voxel_world::voxel::{Grass, Stone, VoxelMaterial};
+    use ndshape::ConstShape;
+
+    // 左半边是草 右半边是石头 地面在 y = 0 的区块中间
+    let chunk_key = ChunkKey(IVec3::new(3, 0, -2));
+    let mut chunk_map = ChunkMap::new();
+    for chunk_y in gen_config().world_height.chunk_ys() {
+        let key = ChunkKey(IVec3::new(chunk_key.0.x, chunk_y, chunk_key.0.z));
+        let mut voxels = vec![Voxel::EMPTY; crate::CHUNK_VOLUME as usize];
+        if chunk_y == 0 {
+            for x in 0..CHUNK_SIZE_U32 {
+                for z in 0..CHUNK_SIZE_U32 {
+                    let index = crate::ChunkShape::linearize([x, CHUNK_SIZE_U32 / 2, z]);
+                    voxels[index as usize] = if x < CHUNK_SIZE_U32 / 2 {
+                        Grass::into_voxel()
+                    } else {
+                        Stone::into_voxel()
+                    };
+                }
+            }
+        }
+        chunk_map.write_chunk(key, voxels);
+    }
+    let basic = |_| BiomeKind::Basic;
+    let decorations = column_decorations(&chunk_map, chunk_key, 1, 2.0, basic);
+    assert!(!decorations.is_empty());
+    let ground = chunk_key_any_xyz_to_vec3(chunk_key, [0, CHUNK_SIZE_U32 / 2, 0]).y + 0.5;
+    let split = chunk_key_any_xyz_to_vec3(chunk_key, [CHUNK_SIZE_U32 / 2, 0, 0]).x - 0.5;
+    for decoration in decorations.iter() {
+        assert_eq!(decoration.pos.y, ground);
+        assert!(decoration.pos.x < split);
+    }
+    // 相同的数据得到相同的装饰 密度为 0 时没有装饰
+    assert_eq!(
+        column_decorations(&chunk_map, chunk_key, 1, 2.0, basic),
+        decorations
+    );
+    assert!(column_decorations(&chunk_map, chunk_key, 1, 0.0, basic).is_empty());
+    assert!(decoration_mesh(&decorations).is_some());
+}
@@ -0,0 +1,127 @@
+// 远处实体的剔除
+// 离相机超过设置距离的其他玩家和掉落物隐藏 不再平滑位置和旋转 走近后重新显示
+// 自己的角色不会被剔除 重新显示时直接使用最新的服务端位置 不从隐藏前的位置过渡
+
+use bevy::prelude::{
+    in_state, Commands, Component, Entity, GlobalTransform, IntoSystemConfigs, Plugin, Query, Res,
+    Transform, Update, Vec3, Visibility, With, Without,
+};
+
+use super::{
+    filled_object::FilledObjectCommpent,
+    net_smoothing::NetSnapshots,
+    player::controller::{CameraTag, CharacterController},
+    settings::{GraphicsSettings, ENTITY_CULL_DISTANCE_RANGE},
+    state_manager::GameState,
+};
+
+// 被剔除的实体 隐藏并且暂停平滑
+#[derive(Debug, Component)]
+pub struct Culled;
+
+pub struct EntityCullingPlugin;
+
+impl Plugin for EntityCullingPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.add_systems(
+            Update,
+            (cull_remote_players, cull_filled_objects).run_if(in_state(GameState::Game)),
+        );
+    }
+}
+
+pub fn should_cull(camera: Vec3, position: Vec3, distance: f32) -> bool {
+    camera.distance_squared(position) > distance * distance
+}
+
+fn cull_distance(settings: &GraphicsSettings) -> f32 {
+    settings.entity_cull_distance.clamp(
+        *ENTITY_CULL_DISTANCE_RANGE.start(),
+        *ENTITY_CULL_DISTANCE_RANGE.end(),
+    )
+}
+
+/**
+ * 按照最新收到的位置判断 隐藏时位置不再更新
+ * 重新显示时只保留最新的快照 直接出现在现在的位置
+ */
+#[allow(clippy::type_complexity)]
+fn cull_remote_players(
+    mut commands: Commands,
+    settings: Res<GraphicsSettings>,
+    camera_query: Query<&GlobalTransform, With<CameraTag>>,
+    mut query: Query<
+        (
+            Entity,
+            &mut NetSnapshots,
+            &mut Transform,
+            &mut Visibility,
+            Option<&Culled>,
+        ),
+        Without<CharacterController>,
+    >,
+) {
+    let Ok(camera) = camera_query.get_single() else {
+        return;
+    };
+    let camera = camera.translation();
+    let distance = cull_distance(&settings);
+    for (entity, mut snapshots, mut transform, mut visibility, culled) in query.iter_mut() {
+        let position = snapshots.latest().unwrap_or(transform.translation);
+        let cull = should_cull(camera, position, distance);
+        if cull && culled.is_none() {
+            commands.entity(entity).insert(Culled);
+            *visibility = Visibility::Hidden;
+        } else if !cull && culled.is_some() {
+            commands.entity(entity).remove::<Culled>();
+            *visibility = Visibility::Inherited;
+            snapshots.snap();
+            transform.translation = position;
+        }
+    }
+}
+
+// 掉落物的位置一直由服务端更新 隐藏时只停止旋转
+#[allow(clippy::type_complexity)]
+fn cull_filled_objects(
+    mut commands: Commands,
+    settings: Res<GraphicsSettings>,
+    camera_query: Query<&GlobalTransform, With<CameraTag>>,
+    mut query: Query<
+        (Entity, &Transform, &mut Visibility, Option<&Culled>),
+        (With<FilledObjectCommpent>, Without<NetSnapshots>),
+    >,
+) {
+    let Ok(camera) = camera_query.get_single() else {
+        return;
+    };
+    let camera = camera.translation();
+    let distance = cull_distance(&settings);
+    for (entity, transform, mut visibility, culled) in query.iter_mut() {
+        let cull = should_cull(camera, transform.translation, distance);
+        if cull && culled.is_none() {
+            commands.entity(entity).insert(Culled);
+            *visibility = Visibility::Hidden;
+        } else if !cull && culled.is_some() {
+            commands.entity(entity).remove::<Culled>();
+            *visibility = Visibility::Inherited;
+        }
+    }
+}
+
+#[test]
+fn test_entity_culling() {
+    assert!(!should_cull(Vec3::ZERO, Vec3::new(30.0, 0.0, 40.0), 50.0));
+    assert!(should_cull(Vec3::ZERO, Vec3::new(30.0, 0.0, 40.1), 50.0));
+
+    // 隐藏期间收到的快照 重新显示时直接使用最新的位置
+    let mut snapshots = NetSnapshots::default();
+    snapshots.push(1.0, Vec3::ZERO);
+    snapshots.push(2.0, Vec3::X * 100.0);
+    snapshots.push(3.0, Vec3::X * 200.0);
+    assert_eq!(snapshots.latest(), Some(Vec3::X * 200.0));
+    snapshots.snap();
+    assert_eq!(snapshots.interpolate(1.5), Some(Vec3::X * 200.0));
+    snapshots.push(4.0, Vec3::X * 210.0);
+    assert_eq!(snapshots.interpolate(3.5), Some(Vec3::X * 205.0));
+}
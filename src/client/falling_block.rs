@@ -0,0 +1,137 @@
+// 客户端的下落方块显示
+// 服务端发送开始下落的消息后 按照和服务端相同的速度播放下落 到达落点后停住
+// 落地的消息到达后删除 落地的方块由区块的更新显示
+
+use bevy::{
+    prelude::{
+        in_state, Assets, Commands, Component, DespawnRecursiveExt, Entity, Event, EventReader,
+        Handle, IntoSystemConfigs, MaterialMeshBundle, Mesh, OnExit, Plugin, Query, Res, ResMut,
+        Resource, Time, Transform, Update, Vec3,
+    },
+    utils::HashMap,
+};
+
+use crate::{server::falling_block::fall_step, voxel_world::voxel::Voxel};
+
+use super::{
+    state_manager::GameState,
+    voxels::{
+        mesh::gen_one_volex_mesh, mesh_material::MaterialStorge,
+        voxel_materail_config::MaterailConfiguration,
+    },
+};
+
+#[derive(Debug, Clone, Event)]
+pub enum FallingBlockEvent {
+    Spawn {
+        id: u64,
+        voxel: Voxel,
+        translation: [f32; 3],
+        target_y: f32,
+    },
+    Landed {
+        id: u64,
+    },
+}
+
+#[derive(Debug, Component)]
+pub struct ClientFallingBlock {
+    pub speed: f32,
+    pub target_y: f32,
+}
+
+#[derive(Debug, Default, Resource)]
+pub struct ClientFallingBlocks {
+    // 服务端的 id ==> 显示的实体
+    pub blocks: HashMap<u64, Entity>,
+    meshes: HashMap<u8, Handle<Mesh>>,
+}
+
+pub struct ClientFallingBlockPlugin;
+
+impl Plugin for ClientFallingBlockPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.add_event::<FallingBlockEvent>();
+        app.init_resource::<ClientFallingBlocks>();
+        app.add_systems(
+            Update,
+            (deal_falling_block_events, animate_falling_blocks)
+                .chain()
+                .run_if(in_state(GameState::Game)),
+        );
+        app.add_systems(OnExit(GameState::Game), setdown_falling_blocks);
+    }
+}
+
+fn deal_falling_block_events(
+    mut commands: Commands,
+    mut events: EventReader<FallingBlockEvent>,
+    mut falling_blocks: ResMut<ClientFallingBlocks>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    materials: Res<MaterialStorge>,
+    material_config: Res<MaterailConfiguration>,
+) {
+    for event in events.iter() {
+        match event {
+            FallingBlockEvent::Spawn {
+                id,
+                voxel,
+                translation,
+                target_y,
+            } => {
+                let mesh = match falling_blocks.meshes.get(&voxel.id) {
+                    Some(mesh) => mesh.clone(),
+                    None => {
+                        let Some(mesh) = gen_one_volex_mesh(*voxel, material_config.clone()) else {
+                            continue;
+                        };
+                        let mesh = meshes.add(mesh);
+                        falling_blocks.meshes.insert(voxel.id, mesh.clone());
+                        mesh
+                    }
+                };
+                let entity = commands
+                    .spawn(MaterialMeshBundle {
+                        mesh,
+                        material: materials.0.clone(),
+                        transform: Transform::from_translation(Vec3::from(*translation)),
+                        ..Default::default()
+                    })
+                    .insert(ClientFallingBlock {
+                        speed: 0.0,
+                        target_y: *target_y,
+                    })
+                    .id();
+                if let Some(old) = falling_blocks.blocks.insert(*id, entity) {
+                    commands.entity(old).despawn_recursive();
+                }
+            }
+            FallingBlockEvent::Landed { id } => {
+                if let Some(entity) = falling_blocks.blocks.remove(id) {
+                    commands.entity(entity).despawn_recursive();
+                }
+            }
+        }
+    }
+}
+
+// 落地的消息可能晚一点到 先停在落点上
+fn animate_falling_blocks(
+    time: Res<Time>,
+    mut query: Query<(&mut ClientFallingBlock, &mut Transform)>,
+) {
+    for (mut block, mut transform) in query.iter_mut() {
+        if transform.translation.y <= block.target_y {
+            continue;
+        }
+        let (y, speed) = fall_step(transform.translation.y, block.speed, time.delta_seconds());
+        block.speed = speed;
+        transform.translation.y = y.max(block.target_y);
+    }
+}
+
+fn setdown_falling_blocks(mut commands: Commands, mut falling_blocks: ResMut<ClientFallingBlocks>) {
+    for (_, entity) in falling_blocks.blocks.drain() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -3,8 +3,8 @@ use std::f32::consts::TAU;
 use ahash::HashSet;
 use bevy::{
     prelude::{
-        in_state, Color, Commands, Component, Entity, Gizmos, IntoSystemConfigs,
-        MaterialMeshBundle, Plugin, Query, Res, ResMut, Resource, Transform, Update, Vec3,
+        in_state, warn, Color, Commands, Component, Entity, EventWriter, Gizmos, IntoSystemConfigs,
+        MaterialMeshBundle, Plugin, Query, Res, ResMut, Resource, Transform, Update, Vec3, Without,
     },
     time::Time,
     utils::HashMap,
@@ -13,12 +13,14 @@ use bevy_renet::renet::RenetClient;
 use bevy_sprite3d::{Sprite3d, Sprite3dParams};
 
 use crate::{
+    common::net_error::{decode, NetErrorEvent},
     server::message_def::{filled_object_message::FilledObjectMessage, ServerChannel},
     staff::StaffInfoStroge,
     CLIENT_DEBUG,
 };
 
 use super::{
+    entity_culling::Culled,
     state_manager::GameState,
     voxels::{
         mesh::gen_one_volex_mesh, mesh_material::MaterialStorge,
@@ -51,7 +53,7 @@ impl Plugin for ClientFilledObjectnPlugin {
 
 //FIXME: 存在问题 旋转物体的mesh
 fn rotate_filled_objects(
-    mut query: Query<(&FilledObjectCommpent, &mut Transform)>,
+    mut query: Query<(&FilledObjectCommpent, &mut Transform), Without<Culled>>,
     timer: Res<Time>,
     mut gizmos: Gizmos,
 ) {
@@ -74,9 +76,17 @@ fn sync_filled_objects(
     // mut mesh_assets: ResMut<Assets<Mesh>>,
     mut query: Query<(Entity, &FilledObjectCommpent, &mut Transform)>,
     mut sprite_params: Sprite3dParams,
+    mut net_errors: EventWriter<NetErrorEvent>,
 ) {
     while let Some(message) = client.receive_message(ServerChannel::FilledObjectMessage) {
-        let message: FilledObjectMessage = bincode::deserialize(&message).unwrap();
+        let message: FilledObjectMessage = match decode(&message) {
+            Ok(message) => message,
+            Err(err) => {
+                warn!("{}", err);
+                net_errors.send(NetErrorEvent(err));
+                continue;
+            }
+        };
         match message {
             FilledObjectMessage::SyncFilledObject(objs) => {
                 let mut new_set: HashSet<Entity> = HashSet::default();
@@ -0,0 +1,361 @@
+// 输入录制和回放
+// 录制时记录每一帧的键盘和鼠标输入 以及世界种子 开始的位置和视角 停止时保存到文件
+// 回放时先传送到开始的位置 然后按照时间把输入写回事件中 回放期间屏蔽真实的输入 按 Esc 停止
+// 用来复现偶发的物理和生成的问题 比如走到某个位置掉到地下
+
+use std::path::Path;
+
+use bevy::{
+    ecs::event::ManualEventReader,
+    input::{
+        keyboard::KeyboardInput,
+        mouse::{MouseButtonInput, MouseMotion, MouseScrollUnit, MouseWheel},
+        ButtonState, InputSystem,
+    },
+    prelude::{
+        in_state, Entity, EventReader, EventWriter, Events, IntoSystemConfigs, KeyCode, Local,
+        MouseButton, OnExit, Plugin, PreUpdate, Query, Res, ResMut, Resource, Time, Transform,
+        Vec2, Vec3, Window, With,
+    },
+    window::PrimaryWindow,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    player::{
+        controller::{set_cursor_grabbed, CharacterController},
+        look::{MouseSettings, PitchEvent, YawEvent},
+    },
+    state_manager::GameState,
+};
+
+// 回放前等待传送的最长时间(秒)
+pub const REPLAY_WAIT_TIMEOUT: f32 = 5.0;
+// 离开始的位置在这个距离内就开始回放
+pub const REPLAY_START_DISTANCE: f32 = 1.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum RecordedInput {
+    Key {
+        scan_code: u32,
+        key_code: Option<KeyCode>,
+        pressed: bool,
+    },
+    Mouse {
+        button: MouseButton,
+        pressed: bool,
+    },
+    Motion(Vec2),
+    Wheel {
+        unit: MouseScrollUnit,
+        x: f32,
+        y: f32,
+    },
+}
+
+// 同一帧的输入 time 是从开始录制到这一帧的时间
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedFrame {
+    pub time: f32,
+    pub inputs: Vec<RecordedInput>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InputRecording {
+    // 客户端还没有同步种子时为 None
+    pub seed: Option<i32>,
+    pub start: Vec3,
+    pub yaw_pitch_roll: Vec3,
+    pub frames: Vec<RecordedFrame>,
+}
+
+impl InputRecording {
+    pub fn new(seed: Option<i32>, start: Vec3, yaw_pitch_roll: Vec3) -> Self {
+        Self {
+            seed,
+            start,
+            yaw_pitch_roll,
+            frames: Vec::new(),
+        }
+    }
+
+    // 默认的文件名
+    pub fn file_name(&self) -> String {
+        format!(
+            "recording_{}_{:.0}_{:.0}.bin",
+            self.seed.unwrap_or_default(),
+            self.start.x,
+            self.start.z
+        )
+    }
+
+    // 录制的时长(秒)
+    pub fn duration(&self) -> f32 {
+        self.frames.last().map_or(0.0, |frame| frame.time)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        let data = bincode::serialize(self).map_err(|err| err.to_string())?;
+        std::fs::write(path, data).map_err(|err| err.to_string())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let data = std::fs::read(path).map_err(|err| err.to_string())?;
+        bincode::deserialize(&data).map_err(|err| err.to_string())
+    }
+
+    /**
+     * 从 next 开始 到 elapsed 为止需要回放的帧
+     * 返回这些帧和下一次开始的位置
+     */
+    pub fn frames_until(&self, next: usize, elapsed: f32) -> (&[RecordedFrame], usize) {
+        let end = next
+            + self.frames[next..]
+                .iter()
+                .take_while(|frame| frame.time <= elapsed)
+                .count();
+        (&self.frames[next..end], end)
+    }
+}
+
+#[derive(Debug, Default, Resource)]
+pub enum InputRecorder {
+    #[default]
+    Idle,
+    Recording {
+        recording: InputRecording,
+        elapsed: f32,
+    },
+    // 等待角色传送到开始的位置
+    Waiting {
+        recording: InputRecording,
+        elapsed: f32,
+    },
+    // next 是下一个要回放的帧
+    Playing {
+        recording: InputRecording,
+        elapsed: f32,
+        next: usize,
+    },
+}
+
+impl InputRecorder {
+    // 回放期间屏蔽真实的输入
+    pub fn is_replaying(&self) -> bool {
+        matches!(
+            self,
+            InputRecorder::Waiting { .. } | InputRecorder::Playing { .. }
+        )
+    }
+}
+
+pub struct InputRecordPlugin;
+
+impl Plugin for InputRecordPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.init_resource::<InputRecorder>();
+        app.add_systems(
+            PreUpdate,
+            (
+                replay_inputs.before(InputSystem),
+                record_inputs.after(InputSystem),
+            )
+                .run_if(in_state(GameState::Game)),
+        );
+        app.add_systems(OnExit(GameState::Game), setdown_input_record);
+    }
+}
+
+fn pressed(state: ButtonState) -> bool {
+    state == ButtonState::Pressed
+}
+
+fn button_state(pressed: bool) -> ButtonState {
+    if pressed {
+        ButtonState::Pressed
+    } else {
+        ButtonState::Released
+    }
+}
+
+// 没有输入的帧不保存 录制的开销只和输入的数量有关
+fn record_inputs(
+    mut recorder: ResMut<InputRecorder>,
+    time: Res<Time>,
+    mut keyboard: EventReader<KeyboardInput>,
+    mut mouse_buttons: EventReader<MouseButtonInput>,
+    mut mouse_motion: EventReader<MouseMotion>,
+    mut mouse_wheel: EventReader<MouseWheel>,
+) {
+    let InputRecorder::Recording { recording, elapsed } = recorder.as_mut() else {
+        return;
+    };
+    *elapsed += time.delta_seconds();
+    let mut inputs = Vec::new();
+    inputs.extend(keyboard.iter().map(|event| RecordedInput::Key {
+        scan_code: event.scan_code,
+        key_code: event.key_code,
+        pressed: pressed(event.state),
+    }));
+    inputs.extend(mouse_buttons.iter().map(|event| RecordedInput::Mouse {
+        button: event.button,
+        pressed: pressed(event.state),
+    }));
+    inputs.extend(
+        mouse_motion
+            .iter()
+            .map(|event| RecordedInput::Motion(event.delta)),
+    );
+    inputs.extend(mouse_wheel.iter().map(|event| RecordedInput::Wheel {
+        unit: event.unit,
+        x: event.x,
+        y: event.y,
+    }));
+    if !inputs.is_empty() {
+        recording.frames.push(RecordedFrame {
+            time: *elapsed,
+            inputs,
+        });
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn replay_inputs(
+    mut recorder: ResMut<InputRecorder>,
+    time: Res<Time>,
+    mut live_keys: Local<ManualEventReader<KeyboardInput>>,
+    mut keyboard: ResMut<Events<KeyboardInput>>,
+    mut mouse_buttons: ResMut<Events<MouseButtonInput>>,
+    mut mouse_motion: ResMut<Events<MouseMotion>>,
+    mut mouse_wheel: ResMut<Events<MouseWheel>>,
+    mut mouse_settings: ResMut<MouseSettings>,
+    mut yaw_events: EventWriter<YawEvent>,
+    mut pitch_events: EventWriter<PitchEvent>,
+    player_query: Query<&Transform, With<CharacterController>>,
+    mut primary_window: Query<(Entity, &mut Window), With<PrimaryWindow>>,
+) {
+    if !recorder.is_replaying() {
+        live_keys.clear(&keyboard);
+        return;
+    }
+    let stop = live_keys
+        .iter(&keyboard)
+        .any(|event| event.key_code == Some(KeyCode::Escape) && pressed(event.state));
+    keyboard.clear();
+    mouse_buttons.clear();
+    mouse_motion.clear();
+    mouse_wheel.clear();
+    if stop {
+        println!("回放被中断");
+        *recorder = InputRecorder::Idle;
+        return;
+    }
+    let Ok((window_entity, mut window)) = primary_window.get_single_mut() else {
+        return;
+    };
+    match recorder.as_mut() {
+        InputRecorder::Waiting { recording, elapsed } => {
+            *elapsed += time.delta_seconds();
+            let arrived = player_query.get_single().map_or(false, |transform| {
+                transform.translation.distance(recording.start) <= REPLAY_START_DISTANCE
+            });
+            if !arrived && *elapsed < REPLAY_WAIT_TIMEOUT {
+                return;
+            }
+            if !arrived {
+                println!("没有传送到录制开始的位置 从当前位置回放");
+            }
+            mouse_settings.yaw_pitch_roll = recording.yaw_pitch_roll;
+            yaw_events.send(YawEvent::new(recording.yaw_pitch_roll.x));
+            pitch_events.send(PitchEvent::new(recording.yaw_pitch_roll.y));
+            // 鼠标移动只在锁定光标时生效
+            set_cursor_grabbed(&mut window, true);
+            let recording = recording.clone();
+            *recorder = InputRecorder::Playing {
+                recording,
+                elapsed: 0.0,
+                next: 0,
+            };
+        }
+        InputRecorder::Playing {
+            recording,
+            elapsed,
+            next,
+        } => {
+            *elapsed += time.delta_seconds();
+            let (frames, end) = recording.frames_until(*next, *elapsed);
+            for input in frames.iter().flat_map(|frame| frame.inputs.iter()) {
+                match *input {
+                    RecordedInput::Key {
+                        scan_code,
+                        key_code,
+                        pressed,
+                    } => keyboard.send(KeyboardInput {
+                        scan_code,
+                        key_code,
+                        state: button_state(pressed),
+                        window: window_entity,
+                    }),
+                    RecordedInput::Mouse { button, pressed } => {
+                        mouse_buttons.send(MouseButtonInput {
+                            button,
+                            state: button_state(pressed),
+                            window: window_entity,
+                        })
+                    }
+                    RecordedInput::Motion(delta) => mouse_motion.send(MouseMotion { delta }),
+                    RecordedInput::Wheel { unit, x, y } => mouse_wheel.send(MouseWheel {
+                        unit,
+                        x,
+                        y,
+                        window: window_entity,
+                    }),
+                }
+            }
+            *next = end;
+            // 回放的 Esc 不能停止回放
+            live_keys.clear(&keyboard);
+            if *next >= recording.frames.len() {
+                println!("回放结束 共{:.1}秒", recording.duration());
+                *recorder = InputRecorder::Idle;
+            }
+        }
+        _ => {}
+    }
+}
+
+fn setdown_input_record(mut recorder: ResMut<InputRecorder>) {
+    *recorder = InputRecorder::Idle;
+}
+
+#[test]
+fn test_input_recording() {
+    let mut recording = InputRecording::new(Some(42), Vec3::new(1.5, 20.0, -3.5), Vec3::ZERO);
+    for (time, key) in [(0.1, KeyCode::W), (0.5, KeyCode::Space), (0.9, KeyCode::W)] {
+        recording.frames.push(RecordedFrame {
+            time,
+            inputs: vec![
+                RecordedInput::Key {
+                    scan_code: 0,
+                    key_code: Some(key),
+                    pressed: true,
+                },
+                RecordedInput::Motion(Vec2::new(1.0, -2.0)),
+            ],
+        });
+    }
+    assert_eq!(recording.duration(), 0.9);
+
+    // 按照时间分批回放 所有的帧只回放一次
+    let (frames, next) = recording.frames_until(0, 0.05);
+    assert!(frames.is_empty());
+    let (frames, next) = recording.frames_until(next, 0.5);
+    assert_eq!(frames.len(), 2);
+    let (frames, next) = recording.frames_until(next, 10.0);
+    assert_eq!(frames.len(), 1);
+    assert_eq!(next, recording.frames.len());
+
+    let path = std::env::temp_dir().join(recording.file_name());
+    recording.save(&path).unwrap();
+    assert_eq!(InputRecording::load(&path).unwrap(), recording);
+}
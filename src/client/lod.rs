@@ -0,0 +1,100 @@
+// 远处区块的低精度网格(LOD)
+// 把体素按照 2x2x2 合并成一个大方块 再用原来的网格生成逻辑处理 贪心合并后面数会大幅减少
+
+use bevy::prelude::{Res, ResMut, Vec3};
+use ndshape::{ConstShape, ConstShape3u32};
+
+use crate::{
+    common::ClipSpheres,
+    voxel_world::{chunk::ChunkKey, voxel::Voxel},
+    CHUNK_SIZE, CHUNK_SIZE_ADD_2_U32,
+};
+
+use super::mesh_display::{DirtyChunks, MeshManager};
+
+// 超过这个水平距离使用低精度网格
+pub const LOD_DISTANCE: f32 = 64.0;
+// 切换时的缓冲距离 防止在边界上来回切换
+pub const LOD_HYSTERESIS: f32 = 8.0;
+
+type ColumnShape = ConstShape3u32<CHUNK_SIZE_ADD_2_U32, 256, CHUNK_SIZE_ADD_2_U32>;
+
+/**
+ * 降低一半精度
+ * 边缘的一圈是相邻区块的数据 保持不变
+ */
+pub fn downsample_voxels(voxels: Vec<Voxel>) -> Vec<Voxel> {
+    let mut ret = voxels.clone();
+    let size = CHUNK_SIZE as u32;
+    for bx in (1..=size).step_by(2) {
+        for bz in (1..=size).step_by(2) {
+            for by in (0..256u32).step_by(2) {
+                // 从上往下找第一个实心方块 保证地表的材质
+                let mut picked = voxels[ColumnShape::linearize([bx, by, bz]) as usize];
+                'find: for y in [by + 1, by] {
+                    for x in [bx, bx + 1] {
+                        for z in [bz, bz + 1] {
+                            let voxel = voxels[ColumnShape::linearize([x, y, z]) as usize];
+                            if voxel.is_solid() {
+                                picked = voxel;
+                                break 'find;
+                            }
+                        }
+                    }
+                }
+                for y in [by, by + 1] {
+                    for x in [bx, bx + 1] {
+                        for z in [bz, bz + 1] {
+                            ret[ColumnShape::linearize([x, y, z]) as usize] = picked;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    ret
+}
+
+// 区块中心到玩家的水平距离
+pub fn lod_distance(chunk_key: ChunkKey, center: Vec3) -> f32 {
+    let chunk_center = Vec3::new(
+        (chunk_key.0.x * CHUNK_SIZE) as f32,
+        center.y,
+        (chunk_key.0.z * CHUNK_SIZE) as f32,
+    );
+    chunk_center.distance(center)
+}
+
+// 新生成的网格是否使用低精度
+pub fn need_coarse(chunk_key: ChunkKey, center: Vec3) -> bool {
+    lod_distance(chunk_key, center) > LOD_DISTANCE
+}
+
+/**
+ * 根据距离切换精度 使用缓冲距离避免频繁切换
+ */
+pub fn update_lod_system(
+    clip_spheres: Res<ClipSpheres>,
+    mut mesh_manager: ResMut<MeshManager>,
+    mut dirty_chunks: ResMut<DirtyChunks>,
+) {
+    let center = clip_spheres.new_sphere.center;
+    let mut changed = Vec::new();
+    for chunk_key in mesh_manager.entities.keys() {
+        let distance = lod_distance(*chunk_key, center);
+        let coarse = mesh_manager.lod_coarse.contains(chunk_key);
+        if coarse && distance < LOD_DISTANCE - LOD_HYSTERESIS {
+            changed.push((*chunk_key, false));
+        } else if !coarse && distance > LOD_DISTANCE + LOD_HYSTERESIS {
+            changed.push((*chunk_key, true));
+        }
+    }
+    for (chunk_key, coarse) in changed {
+        if coarse {
+            mesh_manager.lod_coarse.insert(chunk_key);
+        } else {
+            mesh_manager.lod_coarse.remove(&chunk_key);
+        }
+        dirty_chunks.mark(chunk_key, 0);
+    }
+}
@@ -5,9 +5,10 @@ use std::{
 
 use bevy::{
     prelude::{
-        AlphaMode, AssetServer, Assets, Color, Commands, Component, Entity, Handle, IVec3,
-        IntoSystemConfigs, Last, MaterialMeshBundle, MaterialPlugin, Mesh, Plugin, PreUpdate, Res,
-        ResMut, Resource, StandardMaterial, Startup, Transform, Update, Vec3,
+        warn, AlphaMode, AssetServer, Assets, Color, Commands, Component, Entity, EventWriter,
+        GlobalTransform, Handle, IVec3, IntoSystemConfigs, Last, MaterialMeshBundle,
+        MaterialPlugin, Mesh, Plugin, PreUpdate, Query, Res, ResMut, Resource, StandardMaterial,
+        Startup, Transform, Update, Vec3, With,
     },
     tasks::{AsyncComputeTaskPool, Task},
     time::{Time, Timer, TimerMode},
@@ -18,24 +19,36 @@ use bevy_renet::renet::RenetClient;
 use ndshape::{ConstShape, ConstShape3u32};
 
 use crate::{
-    common::ClipSpheres,
-    server::message_def::{chunk_result::ChunkResult, ServerChannel},
+    common::{
+        chunk_streaming,
+        net_error::{decode, decode_chunk, NetError, NetErrorEvent},
+        ClipSpheres,
+    },
+    server::{
+        fill::chunk_to_block,
+        message_def::{chunk_result::ChunkResult, ServerChannel},
+    },
     tools::get_all_v_chunk,
     voxel_world::{
         chunk::{
             find_chunk_keys_array_by_sphere_y_0, generate_offset_resource,
-            generate_offset_resource_min_1, ChunkKey, NeighbourOffset,
+            generate_offset_resource_min_1, get_chunk_key_i3_by_vec3, ChunkKey, NeighbourOffset,
         },
         chunk_map::ChunkMap,
-        compress::uncompress,
+        light::{light_affected_columns, LightSeed},
         voxel::Voxel,
+        voxel_registry::VOXEL_REGISTRY,
     },
-    CHUNK_SIZE, CHUNK_SIZE_U32, MATERIAL_RON, VIEW_RADIUS,
+    ChunkShape, CHUNK_SIZE, CHUNK_SIZE_U32, MATERIAL_RON, VIEW_RADIUS,
 };
 
 use super::{
+    block_particles::BlockBrokenEvent,
+    lod::{downsample_voxels, need_coarse},
     message_def::{chunk_query::ChunkQuery, ClientChannel},
+    player::controller::CameraTag,
     ray_cast::MyRaycastSet,
+    settings::{GraphicsSettings, MESH_UPLOADS_RANGE, MESH_UPLOAD_BUDGET_RANGE},
     voxels::{
         mesh::{gen_mesh, gen_mesh_water, pick_water},
         mesh_material::{BindlessMaterial, MaterialStorge},
@@ -51,21 +64,107 @@ pub struct MeshManager {
     pub water_entities: HashMap<ChunkKey, Entity>,
     pub fast_key: HashSet<ChunkKey>,
     pub data_status: HashMap<ChunkKey, (bool, Instant)>,
+    // 使用低精度网格的区块 只有开启 lod feature 时才会有数据
+    pub lod_coarse: HashSet<ChunkKey>,
 }
 
 #[derive(Resource)]
 pub struct MeshTasks {
-    pub tasks: Vec<Task<(Vec<Voxel>, ChunkKey)>>,
+    pub tasks: Vec<Task<(Vec<Voxel>, Vec<LightSeed>, ChunkKey)>>,
+}
+
+/**
+ * 已经准备好数据 等待生成网格上传的区块
+ * 一帧上传太多网格会卡顿 每帧只处理设置中的数量和时间 离玩家近的先处理
+ */
+#[derive(Resource, Default)]
+pub struct MeshUploadQueue {
+    pub ready: Vec<(Vec<Voxel>, Vec<LightSeed>, ChunkKey)>,
 }
 
 #[derive(Resource)]
 pub struct ChunkSyncTask {
-    pub tasks: Vec<Task<(ChunkKey, Vec<Voxel>)>>,
+    pub tasks: Vec<Task<(ChunkKey, Result<Vec<Voxel>, NetError>)>>,
 }
 
-#[derive(Resource)]
-pub struct ChunkUpdateTask {
-    pub tasks: Vec<Task<ChunkKey>>,
+// 每帧最多刷新的网格数量
+const MAX_REMESH_PER_FRAME: usize = 16;
+
+/**
+ * 需要重新生成网格的列 (列, 优先级) 修改所在的列优先
+ * 方块在列的边界上时才刷新相邻的列 同一列多次修改只刷新一次
+ */
+#[derive(Debug, Default, Resource)]
+pub struct DirtyChunks {
+    pub dirty: HashMap<ChunkKey, usize>,
+    // 已经重新生成的网格数量 用来检查刷新的次数
+    pub remeshes: u64,
+}
+
+impl DirtyChunks {
+    pub fn mark(&mut self, chunk_key: ChunkKey, priority: usize) {
+        let entry = self.dirty.entry(chunk_key.to_y_zore()).or_insert(priority);
+        *entry = (*entry).max(priority);
+    }
+
+    // 区块中的一个方块从 old 变成 new
+    pub fn mark_block(&mut self, chunk_key: ChunkKey, pos: [u32; 3], old: Voxel, new: Voxel) {
+        let column = chunk_key.to_y_zore();
+        self.mark(column, 1);
+        let faces = [
+            (pos[0] == 0, IVec3::new(-1, 0, 0)),
+            (pos[0] == CHUNK_SIZE_U32 - 1, IVec3::new(1, 0, 0)),
+            (pos[2] == 0, IVec3::new(0, 0, -1)),
+            (pos[2] == CHUNK_SIZE_U32 - 1, IVec3::new(0, 0, 1)),
+        ];
+        for (on_face, offset) in faces {
+            if on_face {
+                self.mark(column.add_ivec3(offset), 0);
+            }
+        }
+        // 光源的变化会照到更远的区块
+        // 天空光只使用整列数据计算 上面刷新的区块已经包括了受影响的部分
+        let level = VOXEL_REGISTRY
+            .light(old.id)
+            .max(VOXEL_REGISTRY.light(new.id));
+        if level > 0 {
+            for key in light_affected_columns(column, pos, level) {
+                self.mark(key, 0);
+            }
+        }
+    }
+
+    /**
+     * 整个区块的数据被替换 只刷新有变化的方块影响的列
+     * 原来没有数据时刷新这一列和相邻的四列
+     */
+    pub fn mark_chunk(&mut self, chunk_key: ChunkKey, old: Option<&Vec<Voxel>>, new: &[Voxel]) {
+        let Some(old) = old.filter(|old| old.len() == new.len()) else {
+            let column = chunk_key.to_y_zore();
+            self.mark(column, 1);
+            for offset in [IVec3::X, IVec3::NEG_X, IVec3::Z, IVec3::NEG_Z] {
+                self.mark(column.add_ivec3(offset), 0);
+            }
+            return;
+        };
+        for (index, (old_voxel, new_voxel)) in old.iter().zip(new.iter()).enumerate() {
+            if old_voxel != new_voxel {
+                let pos = ChunkShape::delinearize(index as u32);
+                self.mark_block(chunk_key, pos, *old_voxel, *new_voxel);
+            }
+        }
+    }
+
+    // 取出优先级最高的几列
+    pub fn take(&mut self, max: usize) -> Vec<ChunkKey> {
+        let mut keys: Vec<(ChunkKey, usize)> = self.dirty.iter().map(|(k, p)| (*k, *p)).collect();
+        keys.sort_by(|a, b| b.1.cmp(&a.1));
+        keys.truncate(max);
+        for (key, _) in keys.iter() {
+            self.dirty.remove(key);
+        }
+        keys.into_iter().map(|(key, _)| key).collect()
+    }
 }
 pub struct ClientMeshPlugin;
 
@@ -75,9 +174,10 @@ impl Plugin for ClientMeshPlugin {
         app.insert_resource(ChunkMap::new());
         app.insert_resource(MeshManager::default());
         app.insert_resource(MeshTasks { tasks: Vec::new() });
+        app.init_resource::<MeshUploadQueue>();
         app.insert_resource(generate_offset_resource(VIEW_RADIUS));
         app.insert_resource(ChunkSyncTask { tasks: Vec::new() });
-        app.insert_resource(ChunkUpdateTask { tasks: Vec::new() });
+        app.init_resource::<DirtyChunks>();
         app.insert_resource(CycleCheckTimer(Timer::new(
             bevy::utils::Duration::from_millis(1000 * 2),
             TimerMode::Repeating,
@@ -87,7 +187,11 @@ impl Plugin for ClientMeshPlugin {
         // mesh_加载和更新相关
         app.add_systems(
             PreUpdate,
-            (gen_mesh_system, async_chunk_result, cycle_check_mesh)
+            (
+                gen_mesh_system.run_if(chunk_streaming),
+                async_chunk_result,
+                cycle_check_mesh.run_if(chunk_streaming),
+            )
                 .run_if(bevy_renet::transport::client_connected()),
         );
         app.add_systems(
@@ -95,6 +199,8 @@ impl Plugin for ClientMeshPlugin {
             (update_mesh_system, save_chunk_result, update_chunk_mesh),
         );
         app.add_systems(Last, deleter_mesh_system);
+        #[cfg(feature = "lod")]
+        app.add_systems(Update, super::lod::update_lod_system);
     }
 }
 
@@ -115,6 +221,14 @@ fn setup(
     ));
 }
 
+// 相机的朝向 没有相机时返回零 只按距离排序
+fn camera_view(camera_query: &Query<&GlobalTransform, With<CameraTag>>) -> Vec3 {
+    camera_query
+        .get_single()
+        .map_or(Vec3::ZERO, |transform| transform.forward())
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn gen_mesh_system(
     chunk_map: Res<ChunkMap>,
     mut mesh_manager: ResMut<MeshManager>,
@@ -122,12 +236,21 @@ pub fn gen_mesh_system(
     neighbour_offest: Res<NeighbourOffset>,
     mut mesh_task: ResMut<MeshTasks>,
     mut client: ResMut<RenetClient>,
+    settings: Res<GraphicsSettings>,
+    camera_query: Query<&GlobalTransform, With<CameraTag>>,
 ) {
     let pool = AsyncComputeTaskPool::get();
-    for key in
-        find_chunk_keys_array_by_sphere_y_0(clip_spheres.new_sphere, neighbour_offest.0.clone())
-            .drain(..)
-    {
+    let mut keys =
+        find_chunk_keys_array_by_sphere_y_0(clip_spheres.new_sphere, neighbour_offest.0.clone());
+    // 视线方向的区块先请求
+    let center = get_chunk_key_i3_by_vec3(clip_spheres.new_sphere.center);
+    let view = camera_view(&camera_query);
+    let bias = settings.chunk_view_bias;
+    keys.sort_by(|a, b| {
+        chunk_load_priority(*a, center, view, bias)
+            .total_cmp(&chunk_load_priority(*b, center, view, bias))
+    });
+    for key in keys.drain(..) {
         if !mesh_manager.entities.contains_key(&key) && !mesh_manager.fast_key.contains(&key) {
             // FIXME: 这要给数据加上 一个有效时间放置server端丢命令
             if let Some(_state) = mesh_manager.data_status.get(&key) {
@@ -135,7 +258,19 @@ pub fn gen_mesh_system(
                     mesh_manager.fast_key.insert(key);
                     mesh_manager.data_status.insert(key, (true, Instant::now()));
                     let volexs: Vec<Voxel> = chunk_map.get_with_neighbor_full_y(key);
-                    let task = pool.spawn(async move { (volexs.clone(), key) });
+                    let seeds = chunk_map.light_seeds_near(key);
+                    let coarse =
+                        cfg!(feature = "lod") && need_coarse(key, clip_spheres.new_sphere.center);
+                    if coarse {
+                        mesh_manager.lod_coarse.insert(key);
+                    }
+                    let task = pool.spawn(async move {
+                        if coarse {
+                            (downsample_voxels(volexs), seeds, key)
+                        } else {
+                            (volexs, seeds, key)
+                        }
+                    });
                     mesh_task.tasks.push(task);
                 }
             } else if !chunk_map.chunk_for_mesh_ready(key) {
@@ -158,28 +293,43 @@ pub fn async_chunk_result(
     mut client: ResMut<RenetClient>,
     mut chunk_sync_task: ResMut<ChunkSyncTask>,
     mut chunk_map: ResMut<ChunkMap>,
-    mut chunk_update_task: ResMut<ChunkUpdateTask>,
+    mut dirty_chunks: ResMut<DirtyChunks>,
+    mut block_broken: EventWriter<BlockBrokenEvent>,
+    mut net_errors: EventWriter<NetErrorEvent>,
 ) {
     let pool = AsyncComputeTaskPool::get();
-    let mut key_set: HashSet<(usize, ChunkKey)> = HashSet::new();
     while let Some(message) = client.receive_message(ServerChannel::ChunkResult) {
-        let chunk_result: ChunkResult = bincode::deserialize(&message).unwrap();
+        let chunk_result: ChunkResult = match decode(&message) {
+            Ok(chunk_result) => chunk_result,
+            Err(err) => {
+                warn!("{}", err);
+                net_errors.send(NetErrorEvent(err));
+                continue;
+            }
+        };
         match chunk_result {
             ChunkResult::UpdateChunkData { key, data } => {
-                let voxel = uncompress(&data.0, data.1);
+                let voxel = match decode_chunk(&data.0, data.1) {
+                    Ok(voxel) => voxel,
+                    Err(err) => {
+                        warn!("{:?} {}", key, err);
+                        continue;
+                    }
+                };
+                dirty_chunks.mark_chunk(key, chunk_map.get(key), &voxel);
+                chunk_map.write_chunk(key.clone(), voxel);
+            }
+            ChunkResult::UpdateChunkSame((key, voxel)) => {
+                let voxel = get_all_v_chunk(voxel);
+                dirty_chunks.mark_chunk(key, chunk_map.get(key), &voxel);
                 chunk_map.write_chunk(key.clone(), voxel);
-                key_set.insert((1, key.to_y_zore()));
-                key_set.insert((0, key.to_y_zore().add_ivec3(IVec3::new(1, 0, 0))));
-                key_set.insert((0, key.to_y_zore().add_ivec3(IVec3::new(-1, 0, 0))));
-                key_set.insert((0, key.to_y_zore().add_ivec3(IVec3::new(0, 0, 1))));
-                key_set.insert((0, key.to_y_zore().add_ivec3(IVec3::new(0, 0, -1))));
             }
             ChunkResult::ChunkData { key, data } => {
-                let task = pool.spawn(async move { (key, uncompress(&data.0, data.1)) });
+                let task = pool.spawn(async move { (key, decode_chunk(&data.0, data.1)) });
                 chunk_sync_task.tasks.push(task);
             }
             ChunkResult::ChunkSame((key, voxel)) => {
-                let task = pool.spawn(async move { (key, get_all_v_chunk(voxel)) });
+                let task = pool.spawn(async move { (key, Ok(get_all_v_chunk(voxel))) });
                 chunk_sync_task.tasks.push(task);
             }
             ChunkResult::ChunkUpdateOne {
@@ -192,70 +342,44 @@ pub fn async_chunk_result(
                     type SampleShape =
                         ConstShape3u32<CHUNK_SIZE_U32, CHUNK_SIZE_U32, CHUNK_SIZE_U32>;
                     let index = SampleShape::linearize(pos) as usize;
+                    let old_voxel = voxel[index];
                     voxel[index] = voxel_type;
-                    let mut clone_chunk_key = chunk_key;
-                    clone_chunk_key.0.y = 0;
-                    key_set.insert((1, clone_chunk_key));
-                    // 2. 刷新mesh的task 注意是刷新的task
-                    if pos[0] == 0 {
-                        let mut new_chunk_key_i3 = clone_chunk_key.0;
-                        new_chunk_key_i3.x -= 1;
-                        key_set.insert((0, ChunkKey(new_chunk_key_i3)));
-                    }
-                    if pos[0] == CHUNK_SIZE_U32 - 1 {
-                        let mut new_chunk_key_i3 = clone_chunk_key.0;
-                        new_chunk_key_i3.x += 1;
-                        key_set.insert((0, ChunkKey(new_chunk_key_i3)));
-                    }
-                    if pos[2] == 0 {
-                        let mut new_chunk_key_i3 = clone_chunk_key.0;
-                        new_chunk_key_i3.z -= 1;
-                        key_set.insert((0, ChunkKey(new_chunk_key_i3)));
-                    }
-                    if pos[2] == CHUNK_SIZE_U32 - 1 {
-                        let mut new_chunk_key_i3 = clone_chunk_key.0;
-                        new_chunk_key_i3.z += 1;
-                        key_set.insert((0, ChunkKey(new_chunk_key_i3)));
+                    // 实心方块被破坏 显示碎片
+                    if old_voxel.is_solid() && voxel_type.id == Voxel::EMPTY.id {
+                        block_broken.send(BlockBrokenEvent {
+                            center: chunk_to_block(chunk_key, pos).as_vec3() + Vec3::splat(0.5),
+                            voxel: old_voxel,
+                        });
                     }
+                    dirty_chunks.mark_block(chunk_key, pos, old_voxel, voxel_type);
                 }
             }
         }
     }
-    // 这里解决处理顺序
-    let mut sorted_vec: Vec<(usize, ChunkKey)> = key_set.into_iter().collect();
-    sorted_vec.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap().reverse());
-
-    for (_, key) in sorted_vec.iter() {
-        let chunk_key: ChunkKey = *key;
-        if mesh_manager.entities.get(key).is_some() {
-            let task = pool.spawn(async move { chunk_key });
-            chunk_update_task.tasks.push(task);
-        }
-    }
+    // 还没有显示的列生成网格时会使用最新的数据
+    dirty_chunks
+        .dirty
+        .retain(|key, _| mesh_manager.entities.contains_key(key));
 }
 
 pub fn update_chunk_mesh(
     mut commands: Commands,
-    mut chunk_update_task: ResMut<ChunkUpdateTask>,
+    mut dirty_chunks: ResMut<DirtyChunks>,
     material_config: Res<MaterailConfiguration>,
     chunk_map: Res<ChunkMap>,
     mut mesh_manager: ResMut<MeshManager>,
     mut mesh_assets: ResMut<Assets<Mesh>>,
 ) {
-    let l = chunk_update_task.tasks.len().min(16);
-    for ele in chunk_update_task.tasks.drain(..l) {
-        if let Some(chunk_key) =
-            futures_lite::future::block_on(futures_lite::future::poll_once(ele))
-        {
-            update_mesh(
-                &mut commands,
-                chunk_map.as_ref(),
-                chunk_key,
-                material_config.clone(),
-                mesh_manager.as_mut(),
-                mesh_assets.as_mut(),
-            )
-        }
+    for chunk_key in dirty_chunks.take(MAX_REMESH_PER_FRAME) {
+        update_mesh(
+            &mut commands,
+            chunk_map.as_ref(),
+            chunk_key,
+            material_config.clone(),
+            mesh_manager.as_mut(),
+            mesh_assets.as_mut(),
+        );
+        dirty_chunks.remeshes += 1;
     }
 }
 
@@ -267,8 +391,12 @@ pub fn update_mesh(
     mesh_manager: &mut MeshManager,
     mesh_assets: &mut Assets<Mesh>,
 ) {
-    let volexs: Vec<Voxel> = chunk_map.get_with_neighbor_full_y(chunk_key_y0);
-    match gen_mesh(volexs.to_owned(), material_config.clone()) {
+    let mut volexs: Vec<Voxel> = chunk_map.get_with_neighbor_full_y(chunk_key_y0);
+    if mesh_manager.lod_coarse.contains(&chunk_key_y0) {
+        volexs = downsample_voxels(volexs);
+    }
+    let seeds = chunk_map.light_seeds_near(chunk_key_y0);
+    match gen_mesh(volexs.to_owned(), &seeds, material_config.clone()) {
         Some(render_mesh) => {
             if let Some(mesh_handle) = mesh_manager.mesh_storge.get(&chunk_key_y0) {
                 if let Some(mesh) = mesh_assets.get_mut(mesh_handle) {
@@ -318,10 +446,11 @@ pub fn save_chunk_result(
 ) {
     let l = chunk_sync_task.tasks.len().min(16);
     for ele in chunk_sync_task.tasks.drain(..l) {
-        if let Some((chunk_key, data)) =
-            futures_lite::future::block_on(futures_lite::future::poll_once(ele))
-        {
-            chunk_map.write_chunk(chunk_key, data);
+        match futures_lite::future::block_on(futures_lite::future::poll_once(ele)) {
+            Some((chunk_key, Ok(data))) => chunk_map.write_chunk(chunk_key, data),
+            // 解压失败的区块不写入 之后会重新请求
+            Some((chunk_key, Err(err))) => warn!("{:?} {}", chunk_key, err),
+            None => {}
         }
     }
 }
@@ -339,24 +468,108 @@ pub enum HitMeshType {
 #[derive(Debug, Component)]
 pub struct WaterMesh;
 
+// 上传的优先级 和玩家所在区块的水平距离 越小越先上传
+pub fn mesh_upload_priority(chunk_key: ChunkKey, center: IVec3) -> i32 {
+    let d = (chunk_key.0 - center).abs();
+    d.x.max(d.z)
+}
+
+/**
+ * 区块请求和上传的优先级 越小越先处理
+ * 水平距离按照和视线的夹角加权 正前方不变 正后方是 1 + bias 倍
+ * bias 为 0 时只看距离 最多两倍 转身时身后的区块也不会一直排在最后
+ */
+pub fn chunk_load_priority(chunk_key: ChunkKey, center: IVec3, view: Vec3, bias: f32) -> f32 {
+    let distance = mesh_upload_priority(chunk_key, center) as f32;
+    let offset = ((chunk_key.0 - center).as_vec3() * Vec3::new(1.0, 0.0, 1.0)).normalize_or_zero();
+    let view = (view * Vec3::new(1.0, 0.0, 1.0)).normalize_or_zero();
+    if offset == Vec3::ZERO || view == Vec3::ZERO {
+        return distance;
+    }
+    distance * (1.0 + bias.clamp(0.0, 1.0) * (1.0 - offset.dot(view)) / 2.0)
+}
+
+/**
+ * 从队列中取出这一帧要上传的区块 priority 小的先上传
+ * 最多 max_count 个 用完 budget 时停止 至少上传一个 保证队列一直在前进
+ */
+pub fn take_mesh_uploads<T>(
+    ready: &mut Vec<T>,
+    key: impl Fn(&T) -> ChunkKey,
+    priority: impl Fn(ChunkKey) -> f32,
+    max_count: usize,
+    budget: Duration,
+    mut upload: impl FnMut(T),
+) {
+    // 远的在前面 从末尾取出近的
+    ready.sort_by(|a, b| priority(key(b)).total_cmp(&priority(key(a))));
+    let start = Instant::now();
+    let mut count = 0;
+    while count < max_count.max(1) {
+        if count > 0 && start.elapsed() >= budget {
+            break;
+        }
+        let Some(item) = ready.pop() else {
+            break;
+        };
+        upload(item);
+        count += 1;
+    }
+}
+
+// 完成的任务进入上传队列 没有完成的留到下一帧
+fn collect_finished_mesh_tasks(mesh_task: &mut MeshTasks, queue: &mut MeshUploadQueue) {
+    let mut pending = Vec::new();
+    for mut task in mesh_task.tasks.drain(..) {
+        match futures_lite::future::block_on(futures_lite::future::poll_once(&mut task)) {
+            Some(result) => queue.ready.push(result),
+            None => pending.push(task),
+        }
+    }
+    mesh_task.tasks = pending;
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn update_mesh_system(
     mut commands: Commands,
     mut mesh_manager: ResMut<MeshManager>,
     mut mesh_assets: ResMut<Assets<Mesh>>,
     mut mesh_task: ResMut<MeshTasks>,
+    mut upload_queue: ResMut<MeshUploadQueue>,
     materials: Res<MaterialStorge>,
     material_config: Res<MaterailConfiguration>,
     mut materials_assets: ResMut<Assets<StandardMaterial>>,
+    settings: Res<GraphicsSettings>,
+    clip_spheres: Res<ClipSpheres>,
+    camera_query: Query<&GlobalTransform, With<CameraTag>>,
 ) {
-    let l: usize = mesh_task.tasks.len().min(3);
-    for ele in mesh_task.tasks.drain(..l) {
-        if let Some((voxels, chunk_key)) =
-            futures_lite::future::block_on(futures_lite::future::poll_once(ele))
-        {
+    collect_finished_mesh_tasks(mesh_task.as_mut(), upload_queue.as_mut());
+    if upload_queue.ready.is_empty() {
+        return;
+    }
+    let max_count = settings
+        .mesh_uploads_per_frame
+        .clamp(*MESH_UPLOADS_RANGE.start(), *MESH_UPLOADS_RANGE.end());
+    let budget_ms = settings.mesh_upload_budget_ms.clamp(
+        *MESH_UPLOAD_BUDGET_RANGE.start(),
+        *MESH_UPLOAD_BUDGET_RANGE.end(),
+    );
+    let center = get_chunk_key_i3_by_vec3(clip_spheres.new_sphere.center);
+    let view = camera_view(&camera_query);
+    let bias = settings.chunk_view_bias;
+    take_mesh_uploads(
+        &mut upload_queue.ready,
+        |(_, _, chunk_key)| *chunk_key,
+        |chunk_key| chunk_load_priority(chunk_key, center, view, bias),
+        max_count,
+        Duration::from_secs_f32(budget_ms / 1000.0),
+        |(voxels, seeds, chunk_key)| {
             if mesh_manager.entities.contains_key(&chunk_key) {
                 return;
             } else {
-                if let Some(render_mesh) = gen_mesh(voxels.to_owned(), material_config.clone()) {
+                if let Some(render_mesh) =
+                    gen_mesh(voxels.to_owned(), &seeds, material_config.clone())
+                {
                     let mesh_handle = mesh_assets.add(render_mesh);
                     mesh_manager
                         .mesh_storge
@@ -423,8 +636,8 @@ pub fn update_mesh_system(
                     );
                 }
             }
-        }
-    }
+        },
+    );
 }
 
 pub fn deleter_mesh_system(
@@ -458,6 +671,7 @@ pub fn deleter_mesh_system(
             commands.entity(entity).despawn();
         }
         mesh_manager.data_status.remove(&chunk_key);
+        mesh_manager.lod_coarse.remove(&chunk_key);
     }
 }
 
@@ -510,9 +724,11 @@ pub fn mesh_chunk_map_setdown(
     mut mesh_manager: ResMut<MeshManager>,
     mut chunk_sync_task: ResMut<ChunkSyncTask>,
     mut chunk_map: ResMut<ChunkMap>,
-    mut chunk_update_task: ResMut<ChunkUpdateTask>,
+    mut dirty_chunks: ResMut<DirtyChunks>,
+    mut upload_queue: ResMut<MeshUploadQueue>,
 ) {
-    chunk_update_task.tasks.drain(..);
+    *dirty_chunks = DirtyChunks::default();
+    upload_queue.ready.clear();
     chunk_sync_task.tasks.drain(..);
     chunk_map.map_data.clear();
     for (_, entity) in mesh_manager.entities.clone() {
@@ -523,3 +739,85 @@ pub fn mesh_chunk_map_setdown(
     }
     *mesh_manager.as_mut() = MeshManager::default();
 }
+
+#[test]
+fn test_take_mesh_uploads() {
+    let key = |x: i32| ChunkKey(IVec3::new(x, 0, 0));
+    let mut ready = vec![key(3), key(-1), key(0), key(2)];
+    let mut uploaded = Vec::new();
+    take_mesh_uploads(
+        &mut ready,
+        |chunk_key| *chunk_key,
+        |chunk_key| mesh_upload_priority(chunk_key, IVec3::ZERO) as f32,
+        2,
+        Duration::from_secs(1),
+        |chunk_key| uploaded.push(chunk_key),
+    );
+    assert_eq!(uploaded, vec![key(0), key(-1)]);
+    assert_eq!(ready.len(), 2);
+
+    // 时间用完时也至少上传一个
+    let mut uploaded = Vec::new();
+    take_mesh_uploads(
+        &mut ready,
+        |chunk_key| *chunk_key,
+        |chunk_key| mesh_upload_priority(chunk_key, IVec3::ZERO) as f32,
+        2,
+        Duration::ZERO,
+        |chunk_key| uploaded.push(chunk_key),
+    );
+    assert_eq!(uploaded, vec![key(2)]);
+
+    // 看向 +x 时前方的区块先处理 身后的最多排到两倍距离
+    let view = Vec3::X;
+    assert!(
+        chunk_load_priority(key(2), IVec3::ZERO, view, 0.5)
+            < chunk_load_priority(key(-2), IVec3::ZERO, view, 0.5)
+    );
+    assert_eq!(chunk_load_priority(key(-2), IVec3::ZERO, view, 0.0), 2.0);
+    assert_eq!(chunk_load_priority(key(-2), IVec3::ZERO, view, 5.0), 4.0);
+    assert!(
+        chunk_load_priority(key(-1), IVec3::ZERO, view, 1.0)
+            < chunk_load_priority(key(3), IVec3::ZERO, view, 1.0)
+    );
+}
+
+#[test]
+fn test_dirty_chunks() {
+    use crate::{
+        voxel_world::voxel::{Lamp, Stone, VoxelMaterial},
+        CHUNK_VOLUME,
+    };
+
+    let chunk_key = ChunkKey(IVec3::new(2, 1, 3));
+    let column = chunk_key.to_y_zore();
+    let stone = Stone::into_voxel();
+    let mut dirty = DirtyChunks::default();
+    // 内部的方块只刷新自己的列 y 方向的边界不影响相邻的列
+    dirty.mark_block(chunk_key, [5, 0, 5], Voxel::EMPTY, stone);
+    dirty.mark_block(chunk_key, [6, CHUNK_SIZE_U32 - 1, 5], Voxel::EMPTY, stone);
+    assert_eq!(dirty.take(16), vec![column]);
+    // 在角上时刷新两个相邻的列 自己的列优先
+    dirty.mark_block(chunk_key, [0, 3, CHUNK_SIZE_U32 - 1], Voxel::EMPTY, stone);
+    let keys = dirty.take(16);
+    assert_eq!(keys.len(), 3);
+    assert_eq!(keys[0], column);
+    assert!(keys.contains(&column.add_ivec3(IVec3::NEG_X)));
+    assert!(keys.contains(&column.add_ivec3(IVec3::Z)));
+    // 光源会照到更远的列
+    dirty.mark_block(chunk_key, [2, 3, 2], Voxel::EMPTY, Lamp::into_voxel());
+    assert!(dirty.take(16).len() > 1);
+
+    // 整个区块替换时只按照变化的方块刷新
+    let old = vec![Voxel::EMPTY; CHUNK_VOLUME as usize];
+    let mut new = old.clone();
+    dirty.mark_chunk(chunk_key, Some(&old), &new);
+    assert!(dirty.dirty.is_empty());
+    new[ChunkShape::linearize([4, 4, 4]) as usize] = stone;
+    dirty.mark_chunk(chunk_key, Some(&old), &new);
+    assert_eq!(dirty.take(16), vec![column]);
+    dirty.mark_chunk(chunk_key, None, &new);
+    assert_eq!(dirty.dirty.len(), 5);
+    assert_eq!(dirty.take(2).len(), 2);
+    assert_eq!(dirty.dirty.len(), 3);
+}
@@ -1,7 +1,10 @@
 use bevy::prelude::{Component, Vec3};
 use serde::{Deserialize, Serialize};
 
-use crate::voxel_world::{chunk::ChunkKey, voxel::Voxel};
+use crate::{
+    server::brush::{BrushMode, BrushShape},
+    voxel_world::{chunk::ChunkKey, voxel::Voxel},
+};
 
 #[derive(Debug, Serialize, Deserialize, Component)]
 pub enum ChunkQuery {
@@ -15,4 +18,33 @@ pub enum ChunkQuery {
         center: Vec3,
         active_index: Option<usize>,
     },
+    // 重新生成周围的区块
+    Regen {
+        chunk_key: ChunkKey,
+        radius: i32,
+        keep_edits: bool,
+    },
+    // 把两个角之间的方块全部设置成 voxel 方块坐标
+    Fill {
+        from: [i32; 3],
+        to: [i32; 3],
+        voxel: Voxel,
+    },
+    // 一次笔刷 修改中心周围的方块 方块坐标
+    Brush {
+        center: [i32; 3],
+        radius: u32,
+        shape: BrushShape,
+        mode: BrushMode,
+        voxel: Voxel,
+    },
+    // 撤销自己最近的几次修改
+    Undo {
+        count: usize,
+    },
+    // 右键和可以交互的方块交互 比如开关灯
+    Interact {
+        chunk_key: ChunkKey,
+        pos: [u32; 3],
+    },
 }
@@ -1,5 +1,6 @@
 pub mod chunk_query;
 pub mod player_input;
+pub mod server_command;
 pub mod staff_rule_message;
 pub mod user_command;
 
@@ -20,6 +21,8 @@ pub enum ClientChannel {
     ChunkQuery,
     // 合成命令
     StaffRule,
+    // 服务端指令
+    ServerCommand,
 }
 
 impl From<ClientChannel> for u8 {
@@ -29,6 +32,7 @@ impl From<ClientChannel> for u8 {
             ClientChannel::Input => 1,
             ClientChannel::ChunkQuery => 2,
             ClientChannel::StaffRule => 3,
+            ClientChannel::ServerCommand => 4,
         }
     }
 }
@@ -64,6 +68,13 @@ impl ClientChannel {
                     resend_time: Duration::ZERO,
                 },
             },
+            ChannelConfig {
+                channel_id: Self::ServerCommand.into(),
+                max_memory_usage_bytes: 5 * 1024 * 1024,
+                send_type: SendType::ReliableOrdered {
+                    resend_time: Duration::ZERO,
+                },
+            },
         ]
     }
 }
@@ -0,0 +1,165 @@
+use bevy::prelude::Component;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    server::permission::{command_level, PermissionLevel},
+    sky::weather::Weather,
+    voxel_world::biomes::BiomeKind,
+};
+
+/**
+ * 需要服务端执行的指令(由控制台输入)
+ */
+#[derive(Debug, Serialize, Deserialize, Component)]
+pub enum ServerCommandMessage {
+    // 修改物理参数 None 表示不修改
+    SetPhysics {
+        gravity: Option<f32>,
+        jump_speed: Option<f32>,
+    },
+    // 查询在线玩家
+    ListPlayers,
+    // 踢出玩家
+    Kick {
+        username: String,
+        reason: Option<String>,
+    },
+    // 封禁玩家
+    Ban {
+        username: String,
+        reason: Option<String>,
+    },
+    // 解除封禁
+    Unban {
+        username: String,
+    },
+    // 查询封禁列表
+    BanList,
+    // 聊天消息
+    Chat {
+        text: String,
+    },
+    // 私聊消息
+    Whisper {
+        username: String,
+        text: String,
+    },
+    // 设置玩家的权限等级
+    Op {
+        username: String,
+        level: PermissionLevel,
+    },
+    // 查询每个客户端的区块发送带宽
+    Bandwidth,
+    // 设置天气 持续时间(秒) 之后回到按照种子的天气
+    SetWeather {
+        weather: Weather,
+        duration: f32,
+    },
+    // 从玩家的位置向外查找最近的群落 范围(方块) 找到后是否传送过去
+    LocateBiome {
+        biome: BiomeKind,
+        radius: i32,
+        teleport: bool,
+    },
+    // 传送到指定的位置 回放录制的输入前使用
+    Teleport {
+        position: [f32; 3],
+    },
+    // 传送到在线玩家的位置
+    TeleportToPlayer {
+        username: String,
+    },
+    // 把在线玩家传送到自己的位置
+    TeleportHere {
+        username: String,
+    },
+    // 立即保存区块和玩家数据
+    Save,
+    // 自杀 在出生点重生
+    Kill,
+    // 切换到指定的世界 没有名字时查看所在的世界和所有世界
+    World {
+        name: Option<String>,
+    },
+    // 开启或者关闭飞行 创造世界中所有人都可以飞行
+    Fly {
+        enabled: bool,
+    },
+}
+
+impl ServerCommandMessage {
+    // 发送这条消息的控制台指令
+    pub fn command_name(&self) -> &'static str {
+        match self {
+            ServerCommandMessage::SetPhysics { .. } => "gravity",
+            ServerCommandMessage::ListPlayers => "players",
+            ServerCommandMessage::Kick { .. } => "kick",
+            ServerCommandMessage::Ban { .. } => "ban",
+            ServerCommandMessage::Unban { .. } => "unban",
+            ServerCommandMessage::BanList => "banlist",
+            ServerCommandMessage::Chat { .. } => "chat",
+            ServerCommandMessage::Whisper { .. } => "w",
+            ServerCommandMessage::Op { .. } => "op",
+            ServerCommandMessage::Bandwidth => "bandwidth",
+            ServerCommandMessage::SetWeather { .. } => "weather",
+            ServerCommandMessage::LocateBiome { .. } => "locatebiome",
+            ServerCommandMessage::Teleport { .. }
+            | ServerCommandMessage::TeleportToPlayer { .. } => "tp",
+            ServerCommandMessage::TeleportHere { .. } => "tphere",
+            ServerCommandMessage::Save => "save",
+            ServerCommandMessage::Kill => "kill",
+            ServerCommandMessage::World { .. } => "world",
+            ServerCommandMessage::Fly { .. } => "fly",
+        }
+    }
+
+    /**
+     * 执行指令需要的最低权限 和客户端使用同一个 command_level
+     * 传送到任意位置需要 Admin
+     */
+    pub fn required_level(&self) -> PermissionLevel {
+        let level = command_level(self.command_name());
+        match self {
+            ServerCommandMessage::Teleport { .. }
+            | ServerCommandMessage::LocateBiome { teleport: true, .. } => {
+                level.max(PermissionLevel::Admin)
+            }
+            _ => level,
+        }
+    }
+}
+
+#[test]
+fn test_required_level() {
+    // 和客户端的指令等级一致
+    let kick = ServerCommandMessage::Kick {
+        username: String::new(),
+        reason: None,
+    };
+    assert_eq!(kick.required_level(), command_level("kick"));
+    assert_eq!(
+        ServerCommandMessage::TeleportHere {
+            username: String::new()
+        }
+        .required_level(),
+        PermissionLevel::Moderator
+    );
+    assert_eq!(
+        ServerCommandMessage::Teleport { position: [0.0; 3] }.required_level(),
+        PermissionLevel::Admin
+    );
+    assert_eq!(
+        ServerCommandMessage::Fly { enabled: true }.required_level(),
+        PermissionLevel::Guest
+    );
+    assert_eq!(
+        ServerCommandMessage::LocateBiome {
+            biome: BiomeKind::Basic,
+            radius: 100,
+            teleport: false
+        }
+        .required_level(),
+        PermissionLevel::Guest
+    );
+}
@@ -0,0 +1,104 @@
+// 客户端的生物显示
+// 生物由服务端生成和回收 这里只根据消息创建或者删除显示用的实体
+
+use bevy::{
+    prelude::{
+        in_state, shape, Assets, Color, Commands, DespawnRecursiveExt, Entity, Event, EventReader,
+        Handle, IntoSystemConfigs, Mesh, OnExit, PbrBundle, Plugin, ResMut, Resource,
+        StandardMaterial, Transform, Update, Vec3,
+    },
+    utils::HashMap,
+};
+
+use crate::server::mob::MobKind;
+
+use super::state_manager::GameState;
+
+#[derive(Debug, Clone, Event)]
+pub enum MobEvent {
+    Spawn {
+        id: u64,
+        kind: MobKind,
+        translation: [f32; 3],
+    },
+    Despawn {
+        id: u64,
+    },
+}
+
+#[derive(Debug, Default, Resource)]
+pub struct ClientMobs {
+    // 服务端的生物 id ==> 显示的实体
+    pub mobs: HashMap<u64, Entity>,
+    assets: HashMap<MobKind, (Handle<Mesh>, Handle<StandardMaterial>)>,
+}
+
+pub struct ClientMobPlugin;
+
+impl Plugin for ClientMobPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.add_event::<MobEvent>();
+        app.init_resource::<ClientMobs>();
+        app.add_systems(Update, deal_mob_events.run_if(in_state(GameState::Game)));
+        app.add_systems(OnExit(GameState::Game), setdown_mobs);
+    }
+}
+
+// 占位的显示 一个方块
+fn mob_color(kind: MobKind) -> Color {
+    match kind {
+        MobKind::Critter => Color::rgb(0.85, 0.55, 0.35),
+    }
+}
+
+fn deal_mob_events(
+    mut commands: Commands,
+    mut events: EventReader<MobEvent>,
+    mut client_mobs: ResMut<ClientMobs>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for event in events.iter() {
+        match event {
+            MobEvent::Spawn {
+                id,
+                kind,
+                translation,
+            } => {
+                let (mesh, material) = client_mobs
+                    .assets
+                    .entry(*kind)
+                    .or_insert_with(|| {
+                        let size = kind.size();
+                        (
+                            meshes.add(Mesh::from(shape::Box::new(size.x, size.y, size.z))),
+                            materials.add(mob_color(*kind).into()),
+                        )
+                    })
+                    .clone();
+                let entity = commands
+                    .spawn(PbrBundle {
+                        mesh,
+                        material,
+                        transform: Transform::from_translation(Vec3::from(*translation)),
+                        ..Default::default()
+                    })
+                    .id();
+                if let Some(old) = client_mobs.mobs.insert(*id, entity) {
+                    commands.entity(old).despawn_recursive();
+                }
+            }
+            MobEvent::Despawn { id } => {
+                if let Some(entity) = client_mobs.mobs.remove(id) {
+                    commands.entity(entity).despawn_recursive();
+                }
+            }
+        }
+    }
+}
+
+fn setdown_mobs(mut commands: Commands, mut client_mobs: ResMut<ClientMobs>) {
+    for (_, entity) in client_mobs.mobs.drain() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
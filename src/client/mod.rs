@@ -1,39 +1,65 @@
 use bevy::prelude::{
-    Assets, Commands, DespawnRecursiveExt, Entity, Mesh, Quat, Query, Res, ResMut,
-    StandardMaterial, Transform, Without,
+    warn, Assets, Commands, DespawnRecursiveExt, Entity, EventWriter, Mesh, Quat, Query, Res,
+    ResMut, StandardMaterial, Time, Transform, Without,
 };
+use bevy_console::PrintConsoleLine;
 use bevy_renet::renet::{transport::NetcodeClientTransport, RenetClient};
 
 use crate::{
-    client::player::PlayerInfo,
+    client::{
+        chat::{ChatLineKind, ChatLog},
+        falling_block::FallingBlockEvent,
+        mob::MobEvent,
+        net_smoothing::NetSnapshots,
+        player::PlayerInfo,
+    },
+    common::net_error::{decode, NetErrorEvent},
     server::{
         message_def::{
             networked_entities::NetworkedEntities, server_messages::ServerMessages, ServerChannel,
         },
         player::Player,
+        tick_rate::ServerTickRate,
     },
+    voxel_world::{gen_config::set_gen_config, map_database::WorldSeed},
 };
 
 use self::player::{
     client_create_player,
-    controller::{HeadTag, YawTag},
+    controller::{CharacterController, HeadTag, YawTag},
     ClientLobby,
 };
 
+pub mod achievement;
+pub mod block_particles;
+pub mod chat;
+pub mod connection_quality;
 pub mod console_commands;
 pub mod debug;
+pub mod decoration;
+pub mod entity_culling;
+pub mod falling_block;
 pub mod filled_object;
+pub mod input_record;
+pub mod lod;
 pub mod mesh_display;
 pub mod message_def;
+pub mod mob;
+pub mod net_smoothing;
+pub mod play_transition;
 pub mod player;
 pub mod ray_cast;
+pub mod render_scale;
+pub mod settings;
 pub mod state_manager;
 pub mod tool_bar_manager;
 pub mod ui;
+pub mod underwater;
 pub mod voxels;
 pub mod sp_mesh_display;
 
 // 同步创建或者删除角色
+#[allow(clippy::too_many_arguments)]
 pub fn client_sync_players(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
@@ -41,10 +67,19 @@ pub fn client_sync_players(
     mut client: ResMut<RenetClient>,
     transport: Res<NetcodeClientTransport>,
     mut lobby: ResMut<ClientLobby>,
+    mut console_line: EventWriter<PrintConsoleLine>,
+    mut chat_log: ResMut<ChatLog>,
+    mut mob_events: EventWriter<MobEvent>,
+    mut falling_block_events: EventWriter<FallingBlockEvent>,
+    mut controllers: Query<&mut CharacterController>,
 ) {
     let client_id = transport.client_id();
     while let Some(message) = client.receive_message(ServerChannel::ServerMessages) {
-        let server_message: ServerMessages = bincode::deserialize(&message).unwrap();
+        // 协议版本不一致时可能无法解析 等待服务端断开连接
+        let Ok(server_message) = bincode::deserialize::<ServerMessages>(&message) else {
+            warn!("无法解析服务端消息 协议版本可能不一致");
+            continue;
+        };
         match server_message {
             ServerMessages::PlayerCreate {
                 entity,
@@ -61,7 +96,7 @@ pub fn client_sync_players(
                     client_id,
                     materials.as_mut(),
                     meshes.as_mut(),
-                    username,
+                    username.clone(),
                     client_id == id,
                 );
 
@@ -70,6 +105,7 @@ pub fn client_sync_players(
                     client_entity,
                 };
                 lobby.players.insert(id, player_info);
+                lobby.names.insert(id, username);
                 // 这记录脖子和头部的对应关系
                 lobby.yaws.insert(id, yaw);
                 lobby.pitch.insert(id, head);
@@ -82,6 +118,7 @@ pub fn client_sync_players(
                 println!("Player {} disconnected.", id);
                 lobby.yaws.remove(&id);
                 lobby.pitch.remove(&id);
+                lobby.names.remove(&id);
                 if let Some(PlayerInfo {
                     server_entity: _,
                     client_entity,
@@ -90,21 +127,110 @@ pub fn client_sync_players(
                     commands.entity(client_entity).despawn_recursive();
                 }
             }
+            ServerMessages::WorldSeed { seed } => {
+                println!("World seed {}.", seed);
+                commands.insert_resource(WorldSeed(seed));
+            }
+            ServerMessages::PhysicsConfig(physics_config) => {
+                commands.insert_resource(physics_config);
+            }
+            ServerMessages::GenConfig(config) => {
+                set_gen_config(config);
+            }
+            ServerMessages::TickRate { rate } => {
+                println!("Server tick rate {}Hz.", rate);
+                commands.insert_resource(ServerTickRate { rate });
+            }
+            ServerMessages::Disconnect { reason } => {
+                println!("Disconnect by server: {:?}.", reason);
+                commands.insert_resource(reason);
+            }
+            ServerMessages::CommandReply { ok, text } => {
+                let line = if ok { text } else { format!("[error] {}", text) };
+                console_line.send(PrintConsoleLine::new(line.into()));
+            }
+            ServerMessages::Chat { username, text } => {
+                chat_log.push(ChatLineKind::Chat, format!("<{}> {}", username, text));
+            }
+            ServerMessages::Whisper { from, to, text } => {
+                let outgoing = lobby.names.get(&client_id) == Some(&from);
+                let line = if outgoing {
+                    format!("[You -> {}] {}", to, text)
+                } else {
+                    format!("[{} -> you] {}", from, text)
+                };
+                lobby.last_whisper = Some(if outgoing { to } else { from });
+                chat_log.push(ChatLineKind::Whisper, line);
+            }
+            ServerMessages::Permission { level } => {
+                println!("Permission level {:?}.", level);
+                commands.insert_resource(level);
+            }
+            ServerMessages::MobSpawn {
+                id,
+                kind,
+                translation,
+            } => {
+                mob_events.send(MobEvent::Spawn {
+                    id,
+                    kind,
+                    translation,
+                });
+            }
+            ServerMessages::MobDespawn { id } => {
+                mob_events.send(MobEvent::Despawn { id });
+            }
+            ServerMessages::FallingBlockSpawn {
+                id,
+                voxel,
+                translation,
+                target_y,
+            } => {
+                falling_block_events.send(FallingBlockEvent::Spawn {
+                    id,
+                    voxel,
+                    translation,
+                    target_y,
+                });
+            }
+            ServerMessages::FallingBlockLanded { id } => {
+                falling_block_events.send(FallingBlockEvent::Landed { id });
+            }
+            // 飞行由服务端确认后才切换
+            ServerMessages::FlyMode { enabled } => {
+                for mut controller in controllers.iter_mut() {
+                    controller.fly = enabled;
+                }
+                let text = if enabled { "Flying" } else { "Stopped flying" };
+                console_line.send(PrintConsoleLine::new(text.into()));
+            }
         }
     }
 }
 
 // 同步角色移动或者头部移动
+// 其他玩家的位置先记录下来 由 net_smoothing 平滑后显示
+#[allow(clippy::too_many_arguments)]
 pub fn client_sync_players_state(
     mut commands: Commands,
+    time: Res<Time>,
     players: Query<Entity, &Player>,
+    mut snapshots: Query<&mut NetSnapshots>,
     mut yaw_query: Query<(&YawTag, &mut Transform)>,
     mut patch_query: Query<(&HeadTag, &mut Transform), Without<YawTag>>,
     mut client: ResMut<RenetClient>,
     lobby: Res<ClientLobby>,
+    mut net_errors: EventWriter<NetErrorEvent>,
 ) {
     while let Some(message) = client.receive_message(ServerChannel::NetworkedEntities) {
-        let server_message: NetworkedEntities = bincode::deserialize(&message).unwrap();
+        let server_message: NetworkedEntities = match decode(&message) {
+            Ok(message) => message,
+            Err(err) => {
+                warn!("{}", err);
+                net_errors.send(NetErrorEvent(err));
+                continue;
+            }
+        };
         let NetworkedEntities {
             client_ids,
             translations,
@@ -120,11 +246,13 @@ pub fn client_sync_players_state(
             }) = lobby.players.get(&client_id)
             {
                 let translation = translations[i].into();
-                let transform = Transform {
-                    translation,
-                    ..Default::default()
-                };
-                if let Ok(entity) = players.get(*client_entity) {
+                if let Ok(mut snapshots) = snapshots.get_mut(*client_entity) {
+                    snapshots.push(time.elapsed_seconds_f64(), translation);
+                } else if let Ok(entity) = players.get(*client_entity) {
+                    let transform = Transform {
+                        translation,
+                        ..Default::default()
+                    };
                     commands.entity(entity).insert(transform);
                 }
             }
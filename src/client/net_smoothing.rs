@@ -0,0 +1,174 @@
+// 其他玩家的位置平滑
+// 服务端每个 tick 发送一次位置 直接使用会一跳一跳的
+// 插值: 显示稍早之前的位置 在两次快照之间过渡 平滑但是有延迟
+// 外推: 按照最近的速度预测现在的位置 延迟低但是转向时会冲过头
+
+use std::collections::VecDeque;
+
+use bevy::{
+    prelude::{
+        in_state, Component, IntoSystemConfigs, Plugin, Query, Res, Transform, Update, Vec3,
+        Without,
+    },
+    time::Time,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{entity_culling::Culled, settings::GraphicsSettings, state_manager::GameState};
+use crate::server::tick_rate::ServerTickRate;
+
+// 插值延迟几个 tick 的间隔
+const INTERPOLATION_TICKS: f64 = 2.0;
+// 外推的最长时间(秒) 包来得太晚时停在这里 不会一直往前走
+pub const MAX_EXTRAPOLATION: f64 = 0.25;
+// 每个玩家保留的快照数量
+const SNAPSHOT_LIMIT: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NetSmoothing {
+    Interpolate,
+    Extrapolate,
+    Blend,
+}
+
+impl NetSmoothing {
+    pub const ALL: [NetSmoothing; 3] = [
+        NetSmoothing::Interpolate,
+        NetSmoothing::Extrapolate,
+        NetSmoothing::Blend,
+    ];
+
+    // 翻译的关键字
+    pub fn name(&self) -> &'static str {
+        match self {
+            NetSmoothing::Interpolate => "插值",
+            NetSmoothing::Extrapolate => "外推",
+            NetSmoothing::Blend => "混合",
+        }
+    }
+
+    // 延迟和准确度的取舍
+    pub fn tooltip(&self) -> &'static str {
+        match self {
+            NetSmoothing::Interpolate => "插值说明",
+            NetSmoothing::Extrapolate => "外推说明",
+            NetSmoothing::Blend => "混合说明",
+        }
+    }
+}
+
+/**
+ * 收到的位置快照 (收到的时间, 位置)
+ * 只有其他玩家有 自己的位置直接使用
+ */
+#[derive(Debug, Default, Component)]
+pub struct NetSnapshots {
+    snapshots: VecDeque<(f64, Vec3)>,
+}
+
+impl NetSnapshots {
+    pub fn push(&mut self, time: f64, translation: Vec3) {
+        self.snapshots.push_back((time, translation));
+        while self.snapshots.len() > SNAPSHOT_LIMIT {
+            self.snapshots.pop_front();
+        }
+    }
+
+    // 最新收到的位置
+    pub fn latest(&self) -> Option<Vec3> {
+        self.snapshots.back().map(|(_, p)| *p)
+    }
+
+    // 只保留最新的快照 之后从这里开始平滑 不从旧的位置过渡
+    pub fn snap(&mut self) {
+        while self.snapshots.len() > 1 {
+            self.snapshots.pop_front();
+        }
+    }
+
+    /**
+     * render_time 时的位置 在前后两个快照之间线性插值
+     * 比所有快照都晚时停在最新的位置
+     */
+    pub fn interpolate(&self, render_time: f64) -> Option<Vec3> {
+        let (first_time, first) = *self.snapshots.front()?;
+        if render_time <= first_time {
+            return Some(first);
+        }
+        for ((t0, p0), (t1, p1)) in self.snapshots.iter().zip(self.snapshots.iter().skip(1)) {
+            if render_time <= *t1 {
+                let t = ((render_time - t0) / (t1 - t0).max(f64::EPSILON)) as f32;
+                return Some(p0.lerp(*p1, t));
+            }
+        }
+        self.snapshots.back().map(|(_, p)| *p)
+    }
+
+    /**
+     * 按照最近两个快照的速度预测 now 时的位置
+     * 预测的时间不超过 max_ahead
+     */
+    pub fn extrapolate(&self, now: f64, max_ahead: f64) -> Option<Vec3> {
+        let len = self.snapshots.len();
+        let (t1, p1) = *self.snapshots.back()?;
+        if len < 2 {
+            return Some(p1);
+        }
+        let (t0, p0) = self.snapshots[len - 2];
+        let velocity = (p1 - p0) / (t1 - t0).max(f64::EPSILON) as f32;
+        let ahead = (now - t1).clamp(0.0, max_ahead) as f32;
+        Some(p1 + velocity * ahead)
+    }
+}
+
+pub struct NetSmoothingPlugin;
+
+impl Plugin for NetSmoothingPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.add_systems(
+            Update,
+            smooth_remote_players.run_if(in_state(GameState::Game)),
+        );
+    }
+}
+
+fn smooth_remote_players(
+    time: Res<Time>,
+    settings: Res<GraphicsSettings>,
+    tick_rate: Option<Res<ServerTickRate>>,
+    mut query: Query<(&NetSnapshots, &mut Transform), Without<Culled>>,
+) {
+    let now = time.elapsed_seconds_f64();
+    let interval = tick_rate
+        .map(|tick_rate| tick_rate.interval().as_secs_f64())
+        .unwrap_or(1.0 / 60.0);
+    let render_time = now - interval * INTERPOLATION_TICKS;
+    for (snapshots, mut transform) in query.iter_mut() {
+        let translation = match settings.net_smoothing {
+            NetSmoothing::Interpolate => snapshots.interpolate(render_time),
+            NetSmoothing::Extrapolate => snapshots.extrapolate(now, MAX_EXTRAPOLATION),
+            NetSmoothing::Blend => snapshots
+                .interpolate(render_time)
+                .zip(snapshots.extrapolate(now, MAX_EXTRAPOLATION))
+                .map(|(a, b)| a.lerp(b, settings.net_blend.clamp(0.0, 1.0))),
+        };
+        if let Some(translation) = translation {
+            transform.translation = translation;
+        }
+    }
+}
+
+#[test]
+fn test_net_snapshots() {
+    let mut snapshots = NetSnapshots::default();
+    assert_eq!(snapshots.interpolate(0.0), None);
+    snapshots.push(1.0, Vec3::ZERO);
+    snapshots.push(2.0, Vec3::X * 10.0);
+    assert_eq!(snapshots.interpolate(0.5), Some(Vec3::ZERO));
+    assert_eq!(snapshots.interpolate(1.5), Some(Vec3::X * 5.0));
+    assert_eq!(snapshots.interpolate(3.0), Some(Vec3::X * 10.0));
+    let predicted = snapshots.extrapolate(2.1, 0.25).unwrap();
+    assert!(predicted.distance(Vec3::X * 11.0) < 1e-4);
+    // 包来得很晚时不会无限外推
+    assert_eq!(snapshots.extrapolate(10.0, 0.25), Some(Vec3::X * 12.5));
+}
@@ -0,0 +1,140 @@
+// 切换 PlayState 时的过渡
+// 打开合成列表等界面时游戏画面逐渐变暗 界面从下方滑入 回到游戏时变暗逐渐消失
+// 光标仍然立即切换 过渡时长在设置中修改 关闭后立即切换
+
+use bevy::prelude::{
+    in_state, IntoSystemConfigs, OnExit, Plugin, Res, ResMut, Resource, State, Time, Update,
+};
+use bevy_egui::{egui, EguiContexts};
+
+use super::{
+    settings::{GraphicsSettings, PLAY_TRANSITION_RANGE},
+    state_manager::{game::PlayState, GameState},
+};
+
+// 完全变暗时遮罩的不透明度
+const MENU_DIM_ALPHA: f32 = 0.55;
+// 界面滑入的距离(像素)
+pub const MENU_SLIDE_DISTANCE: f32 = 40.0;
+
+#[derive(Debug, Default, Resource)]
+pub struct PlayTransition {
+    // 0 是游戏画面 1 是完全进入界面
+    pub progress: f32,
+}
+
+impl PlayTransition {
+    /**
+     * 向目标前进一帧 duration 秒走完全程
+     * 关闭过渡时直接到达目标
+     */
+    pub fn step(&mut self, target: f32, delta: f32, duration: Option<f32>) {
+        match duration {
+            Some(duration) if duration > 0.0 => {
+                let step = delta / duration;
+                self.progress = if self.progress < target {
+                    (self.progress + step).min(target)
+                } else {
+                    (self.progress - step).max(target)
+                };
+            }
+            _ => self.progress = target,
+        }
+    }
+
+    // 缓入缓出
+    pub fn eased(&self) -> f32 {
+        let t = self.progress.clamp(0.0, 1.0);
+        t * t * (3.0 - 2.0 * t)
+    }
+
+    // 界面在最终位置下方的距离
+    pub fn slide_offset(&self) -> f32 {
+        (1.0 - self.eased()) * MENU_SLIDE_DISTANCE
+    }
+}
+
+// 需要使用鼠标的界面状态
+pub fn is_menu_state(state: PlayState) -> bool {
+    matches!(state, PlayState::StaffRules | PlayState::State)
+}
+
+pub struct PlayTransitionPlugin;
+
+impl Plugin for PlayTransitionPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.init_resource::<PlayTransition>();
+        app.add_systems(
+            Update,
+            (update_play_transition, draw_menu_dim)
+                .chain()
+                .run_if(in_state(GameState::Game)),
+        );
+        app.add_systems(OnExit(GameState::Game), setdown_play_transition);
+    }
+}
+
+fn update_play_transition(
+    time: Res<Time>,
+    settings: Res<GraphicsSettings>,
+    state: Res<State<PlayState>>,
+    mut transition: ResMut<PlayTransition>,
+) {
+    let target = if is_menu_state(*state.get()) {
+        1.0
+    } else {
+        0.0
+    };
+    let duration = settings.play_transitions.then(|| {
+        settings
+            .play_transition_duration
+            .clamp(*PLAY_TRANSITION_RANGE.start(), *PLAY_TRANSITION_RANGE.end())
+    });
+    transition.step(target, time.delta_seconds(), duration);
+}
+
+// 背景的遮罩 画在界面的下面
+fn draw_menu_dim(
+    mut contexts: EguiContexts,
+    settings: Res<GraphicsSettings>,
+    transition: Res<PlayTransition>,
+) {
+    let alpha = transition.eased() * MENU_DIM_ALPHA;
+    if !settings.menu_dim || alpha <= 0.0 {
+        return;
+    }
+    let ctx = contexts.ctx_mut();
+    let screen = ctx.screen_rect();
+    egui::Area::new("menu_dim")
+        .order(egui::Order::Background)
+        .fixed_pos(screen.min)
+        .interactable(false)
+        .show(ctx, |ui| {
+            ui.painter().rect_filled(
+                screen,
+                0.0,
+                egui::Color32::from_black_alpha((alpha * 255.0) as u8),
+            );
+        });
+}
+
+fn setdown_play_transition(mut transition: ResMut<PlayTransition>) {
+    *transition = PlayTransition::default();
+}
+
+#[test]
+fn test_play_transition() {
+    let mut transition = PlayTransition::default();
+    transition.step(1.0, 0.1, Some(0.2));
+    assert_eq!(transition.progress, 0.5);
+    assert!(transition.slide_offset() > 0.0 && transition.slide_offset() < MENU_SLIDE_DISTANCE);
+    transition.step(1.0, 0.5, Some(0.2));
+    assert_eq!(transition.progress, 1.0);
+    assert_eq!(transition.slide_offset(), 0.0);
+    // 关闭过渡时立即切换
+    transition.step(0.0, 0.01, None);
+    assert_eq!((transition.progress, transition.eased()), (0.0, 0.0));
+
+    assert!(is_menu_state(PlayState::StaffRules));
+    assert!(!is_menu_state(PlayState::Main));
+}
@@ -1,17 +1,25 @@
 use bevy::{
     prelude::{
         in_state, warn, Component, Entity, EventReader, Input, IntoSystemConfigs,
-        IntoSystemSetConfigs, KeyCode, Mat4, OnEnter, OnExit, Plugin, PreUpdate, Query, Res,
-        ResMut, Resource, SystemSet, Transform, Update, Vec3, Visibility, With,
+        IntoSystemSetConfigs, KeyCode, Local, Mat4, OnEnter, OnExit, Plugin, PreUpdate, Query, Res,
+        ResMut, Resource, SystemSet, Time, Transform, Vec3, Visibility, With,
     },
-    window::{CursorGrabMode, PrimaryWindow, Window},
+    window::{CursorGrabMode, PrimaryWindow, Window, WindowFocused},
 };
 use bevy_egui::EguiSet;
 use bevy_renet::renet::RenetClient;
 
-use crate::client::{
-    message_def::{player_input::PlayerInput, ClientChannel},
-    state_manager::GameState,
+use crate::{
+    client::{
+        message_def::{
+            player_input::PlayerInput, server_command::ServerCommandMessage, ClientChannel,
+        },
+        settings::{GraphicsSettings, INPUT_SEND_RATE_RANGE},
+        state_manager::GameState,
+    },
+    server::physics_config::PhysicsConfig,
+    tools::vec3_to_chunk_key_any_xyz,
+    voxel_world::{chunk_map::ChunkMap, voxel::VoxelProperties},
 };
 
 use super::{
@@ -47,9 +55,15 @@ pub struct CharacterController {
     pub fly: bool,
     pub walk_speed: f32,
     pub run_speed: f32,
-    pub jump_speed: f32,
     pub velocity: Vec3,
     pub jumping: bool,
+    // 落地前多久按下的跳跃仍然有效(秒)
+    pub jump_buffer_time: f32,
+    // 离开地面后多久内仍然可以跳跃(秒)
+    pub coyote_time: f32,
+    pub jump_timer: JumpTimer,
+    // 双击跳跃切换飞行
+    pub jump_tap: DoubleTap,
     pub input_state: InputState,
 }
 
@@ -60,14 +74,110 @@ impl Default for CharacterController {
             fly: false,
             walk_speed: 5.0,
             run_speed: 8.0,
-            jump_speed: 5.0,
             velocity: Vec3::ZERO,
             jumping: false,
+            jump_buffer_time: 0.1,
+            coyote_time: 0.1,
+            jump_timer: JumpTimer::default(),
+            jump_tap: DoubleTap::default(),
             input_state: InputState::default(),
         }
     }
 }
 
+/**
+ * 跳跃的缓冲和土狼时间
+ * 落地前稍早按下的跳跃会在落地时执行 走出边缘后的一小段时间内仍然可以跳
+ */
+#[derive(Debug, Default, Clone, Copy)]
+pub struct JumpTimer {
+    // 距离上次按下跳跃的时间 None 表示没有等待执行的跳跃
+    pub since_jump_pressed: Option<f32>,
+    // 距离上次站在地面的时间 None 表示跳起后还没有落地
+    pub since_grounded: Option<f32>,
+}
+
+impl JumpTimer {
+    pub fn tick(&mut self, delta: f32, grounded: bool, jump_pressed: bool) {
+        self.since_jump_pressed = if jump_pressed {
+            Some(0.0)
+        } else {
+            self.since_jump_pressed.map(|t| t + delta)
+        };
+        self.since_grounded = if grounded {
+            Some(0.0)
+        } else {
+            self.since_grounded.map(|t| t + delta)
+        };
+    }
+
+    // 满足条件时消耗掉这次跳跃 防止在空中连跳
+    pub fn try_jump(&mut self, buffer_time: f32, coyote_time: f32) -> bool {
+        let buffered = matches!(self.since_jump_pressed, Some(t) if t <= buffer_time);
+        let can_jump = matches!(self.since_grounded, Some(t) if t <= coyote_time);
+        if buffered && can_jump {
+            self.since_jump_pressed = None;
+            self.since_grounded = None;
+            return true;
+        }
+        false
+    }
+}
+
+/**
+ * 双击按键 两次按下的间隔不超过 window 秒
+ */
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DoubleTap {
+    // 距离上次按下的时间 None 表示没有等待第二次按下
+    pub since_pressed: Option<f32>,
+}
+
+impl DoubleTap {
+    // 返回这一次按下是否完成了双击 完成后重新计算 连按三次只算一次
+    pub fn tick(&mut self, delta: f32, pressed: bool, window: f32) -> bool {
+        self.since_pressed = self
+            .since_pressed
+            .map(|t| t + delta)
+            .filter(|t| *t <= window);
+        if !pressed {
+            return false;
+        }
+        if self.since_pressed.take().is_some() {
+            return true;
+        }
+        self.since_pressed = Some(0.0);
+        false
+    }
+}
+
+/**
+ * 发送给服务端的移动和视角 按照设置中的频率发送 和帧率无关
+ * 高刷新率的客户端不会每帧都发送 开始 停止移动和跳跃仍然立即发送
+ */
+#[derive(Debug, Default, Resource)]
+pub struct InputSender {
+    elapsed: f32,
+    // 上一次发送的移动
+    sent_move: Option<Vec3>,
+    // 等待发送的最新的值
+    next_move: Option<Vec3>,
+    yaw: Option<f32>,
+    pitch: Option<f32>,
+}
+
+/**
+ * 需要立即发送的移动 不等下一次定时发送
+ * 开始或者停止移动 还有跳跃(向上的速度从无到有)
+ */
+pub fn urgent_move(sent: Option<Vec3>, next: Vec3) -> bool {
+    let Some(sent) = sent else {
+        return true;
+    };
+    let moving = |v: Vec3| v.length_squared() > 1E-6;
+    moving(sent) != moving(next) || (next.y > 0.0 && sent.y <= 0.0)
+}
+
 #[derive(Debug, Component)]
 pub struct BodyTag;
 // 首摇
@@ -93,6 +203,9 @@ impl Plugin for CharacterControllerPlugin {
         app.add_event::<PitchEvent>()
             .add_event::<YawEvent>()
             .init_resource::<MouseSettings>()
+            .init_resource::<InputSender>()
+            // 连接后会被服务端的参数覆盖
+            .init_resource::<PhysicsConfig>()
             .add_systems(OnEnter(GameState::Game), initial_grab_cursor)
             .insert_resource(ControllerFlag { flag: true })
             .configure_sets(
@@ -109,6 +222,7 @@ impl Plugin for CharacterControllerPlugin {
                 PreUpdate,
                 (
                     cursor_grab.after(EguiSet::InitContexts),
+                    release_cursor_on_focus,
                     toggle_third_person,
                     (input_to_send)
                         .in_set(ControllerSet::InputToEvent)
@@ -121,11 +235,18 @@ impl Plugin for CharacterControllerPlugin {
                 )
                     .run_if(in_state(GameState::Game)),
             );
-        app.add_systems(OnExit(GameState::Game), back_grab_cursor);
-        // 发送message系统
         app.add_systems(
-            Update,
-            (controller_to_yaw, controller_to_pitch)
+            OnExit(GameState::Game),
+            (back_grab_cursor, setdown_input_sender),
+        );
+        // 发送message系统 和产生输入的系统在同一个调度中 这一帧的输入在这一帧发送
+        app.add_systems(
+            PreUpdate,
+            (
+                (controller_to_yaw, controller_to_pitch).after(ControllerSet::InputToLook),
+                flush_input.after(input_to_send),
+            )
+                .chain()
                 .run_if(bevy_renet::transport::client_connected()),
         );
     }
@@ -142,25 +263,67 @@ fn initial_grab_cursor(mut primary_window: Query<&mut Window, With<PrimaryWindow
 
 pub fn back_grab_cursor(mut primary_window: Query<&mut Window, With<PrimaryWindow>>) {
     if let Ok(mut window) = primary_window.get_single_mut() {
-        {
-            window.cursor.grab_mode = CursorGrabMode::None;
-            window.cursor.visible = true;
-        }
+        set_cursor_grabbed(&mut window, false);
     } else {
         warn!("Primary window not found for `initial_grab_cursor`!");
     }
 }
 
+/**
+ * 锁定或者释放光标 锁定时隐藏 释放时显示
+ * 所有修改光标的地方都使用这个函数 保证锁定和显示的状态一致
+ */
+pub fn set_cursor_grabbed(window: &mut Window, grabbed: bool) {
+    let (grab_mode, visible) = cursor_state(grabbed);
+    window.cursor.grab_mode = grab_mode;
+    window.cursor.visible = visible;
+}
+
+// 返回 (锁定模式, 是否显示)
+pub fn cursor_state(grabbed: bool) -> (CursorGrabMode, bool) {
+    if grabbed {
+        (CursorGrabMode::Confined, false)
+    } else {
+        (CursorGrabMode::None, true)
+    }
+}
+
+pub fn cursor_grabbed(window: &Window) -> bool {
+    window.cursor.grab_mode != CursorGrabMode::None
+}
+
 /// Grabs/ungrabs mouse cursor
 fn toggle_grab_cursor(window: &mut Window) {
-    match window.cursor.grab_mode {
-        CursorGrabMode::None => {
-            window.cursor.grab_mode = CursorGrabMode::Confined;
-            window.cursor.visible = false;
+    let grabbed = cursor_grabbed(window);
+    set_cursor_grabbed(window, !grabbed);
+}
+
+/**
+ * 窗口失去焦点时释放光标 重新获得焦点时恢复
+ * 避免切换窗口后光标仍然被锁定在游戏里
+ */
+fn release_cursor_on_focus(
+    mut focus_events: EventReader<WindowFocused>,
+    settings: Res<GraphicsSettings>,
+    mut primary_window: Query<(Entity, &mut Window), With<PrimaryWindow>>,
+    mut regrab: Local<bool>,
+) {
+    let Ok((window_entity, mut window)) = primary_window.get_single_mut() else {
+        return;
+    };
+    for event in focus_events.iter() {
+        if event.window != window_entity {
+            continue;
         }
-        _ => {
-            window.cursor.grab_mode = CursorGrabMode::None;
-            window.cursor.visible = true;
+        if event.focused {
+            // 只恢复由失去焦点释放的光标
+            if *regrab {
+                set_cursor_grabbed(&mut window, true);
+                *regrab = false;
+            }
+        } else if settings.release_cursor_unfocused && cursor_grabbed(&window) {
+            set_cursor_grabbed(&mut window, false);
+            *regrab = true;
         }
     }
 }
@@ -188,20 +351,54 @@ fn cursor_grab(
     }
 }
 
-pub fn controller_to_yaw(mut yaws: EventReader<YawEvent>, mut client: ResMut<RenetClient>) {
-    if let Some(yaw) = yaws.iter().next() {
-        // transform.rotation = Quat::from_rotation_y(**yaw);
-        let message = bincode::serialize(&PlayerInput::YAW(yaw.yaw)).unwrap();
-        client.send_message(ClientChannel::Input, message);
+// 只记录最新的视角 由 flush_input 发送
+pub fn controller_to_yaw(mut yaws: EventReader<YawEvent>, mut sender: ResMut<InputSender>) {
+    if let Some(yaw) = yaws.iter().last() {
+        sender.yaw = Some(yaw.yaw);
     }
 }
 
-pub fn controller_to_pitch(mut pitches: EventReader<PitchEvent>, mut client: ResMut<RenetClient>) {
-    if let Some(pitch) = pitches.iter().next() {
-        // transform.rotation = Quat::from_rotation_x(**pitch);
-        let message = bincode::serialize(&PlayerInput::PITCH(pitch.pitch)).unwrap();
+pub fn controller_to_pitch(mut pitches: EventReader<PitchEvent>, mut sender: ResMut<InputSender>) {
+    if let Some(pitch) = pitches.iter().last() {
+        sender.pitch = Some(pitch.pitch);
+    }
+}
+
+// 到了发送的时间或者有需要立即发送的移动时 发送所有等待的值
+fn flush_input(
+    time: Res<Time>,
+    settings: Res<GraphicsSettings>,
+    mut sender: ResMut<InputSender>,
+    mut client: ResMut<RenetClient>,
+) {
+    let rate = settings
+        .input_send_rate
+        .clamp(*INPUT_SEND_RATE_RANGE.start(), *INPUT_SEND_RATE_RANGE.end());
+    sender.elapsed += time.delta_seconds();
+    let urgent = sender
+        .next_move
+        .map_or(false, |next| urgent_move(sender.sent_move, next));
+    if !urgent && sender.elapsed < 1.0 / rate as f32 {
+        return;
+    }
+    sender.elapsed = 0.0;
+    if let Some(next) = sender.next_move.take() {
+        let message = bincode::serialize(&PlayerInput::MOVE(next)).unwrap();
+        client.send_message(ClientChannel::Input, message);
+        sender.sent_move = Some(next);
+    }
+    if let Some(yaw) = sender.yaw.take() {
+        let message = bincode::serialize(&PlayerInput::YAW(yaw)).unwrap();
         client.send_message(ClientChannel::Input, message);
     }
+    if let Some(pitch) = sender.pitch.take() {
+        let message = bincode::serialize(&PlayerInput::PITCH(pitch)).unwrap();
+        client.send_message(ClientChannel::Input, message);
+    }
+}
+
+fn setdown_input_sender(mut sender: ResMut<InputSender>) {
+    *sender = InputSender::default();
 }
 
 #[derive(Debug, Component)]
@@ -249,18 +446,47 @@ fn toggle_third_person(
 
 pub fn input_to_send(
     keyboard_input: Res<Input<KeyCode>>,
-    mut controller_query: Query<(&LookEntity, &mut CharacterController)>,
+    mut controller_query: Query<(&LookEntity, &mut CharacterController, &Transform)>,
     look_direction_query: Query<&LookDirection>,
     controller_flag: Res<ControllerFlag>,
+    chunk_map: Res<ChunkMap>,
+    physics_config: Res<PhysicsConfig>,
+    time: Res<Time>,
+    settings: Res<GraphicsSettings>,
+    primary_window: Query<&Window, With<PrimaryWindow>>,
+    mut sender: ResMut<InputSender>,
     mut client: ResMut<RenetClient>,
 ) {
     let xz = Vec3::new(1.0, 0.0, 1.0);
     if !controller_flag.flag {
         return;
     }
-    for (look_entity, mut controller) in controller_query.iter_mut() {
-        if keyboard_input.just_pressed(controller.input_map.key_fly) {
-            controller.fly = !controller.fly;
+    // 失去焦点时按键不再生效 仍然发送速度让角色停下来
+    let focused = primary_window
+        .get_single()
+        .map_or(true, |window| window.focused);
+    let idle = Input::<KeyCode>::default();
+    let keyboard_input = if focused || !settings.pause_movement_unfocused {
+        keyboard_input.as_ref()
+    } else {
+        &idle
+    };
+    for (look_entity, mut controller, transform) in controller_query.iter_mut() {
+        // 由服务端检查是否可以飞行 确认后才切换
+        let jump_pressed = keyboard_input.just_pressed(controller.input_map.key_jump);
+        let delta = time.delta_seconds();
+        let double_tap = controller
+            .jump_tap
+            .tick(delta, jump_pressed, FLY_DOUBLE_TAP_TIME)
+            && settings.fly_double_tap;
+        if keyboard_input.just_pressed(controller.input_map.key_fly) || double_tap {
+            let command = ServerCommandMessage::Fly {
+                enabled: !controller.fly,
+            };
+            client.send_message(
+                ClientChannel::ServerCommand,
+                bincode::serialize(&command).unwrap(),
+            );
         }
         if keyboard_input.pressed(controller.input_map.key_forward) {
             controller.input_state.forward = true;
@@ -286,21 +512,25 @@ pub fn input_to_send(
         if keyboard_input.pressed(controller.input_map.key_fly_down) {
             controller.input_state.down = true;
         }
+        // 飞行时按住跳跃上升 按住潜行下降
+        if controller.fly && keyboard_input.pressed(controller.input_map.key_jump) {
+            controller.input_state.up = true;
+        }
+        if controller.fly && keyboard_input.pressed(controller.input_map.key_crouch) {
+            controller.input_state.down = true;
+        }
 
         let look = look_direction_query
             .get_component::<LookDirection>(look_entity.0)
             .expect("Failed to get LookDirection from Entity");
 
         // Calculate forward / right / up vectors
-        let (forward, right, up) = if controller.fly {
-            (look.forward, look.right, look.up)
-        } else {
-            (
-                (look.forward * xz).normalize(),
-                (look.right * xz).normalize(),
-                Vec3::Y,
-            )
-        };
+        // 飞行时也在水平面上移动 竖直方向只由上升下降控制
+        let (forward, right, up) = (
+            (look.forward * xz).normalize(),
+            (look.right * xz).normalize(),
+            Vec3::Y,
+        );
 
         // Calculate the desired velocity based on input
         let mut desired_velocity = Vec3::ZERO;
@@ -324,11 +554,17 @@ pub fn input_to_send(
         }
 
         // Limit x/z velocity to walk/run speed
-        let speed = if controller.input_state.run {
-            controller.run_speed
-        } else {
-            controller.walk_speed
+        let mut speed = match (controller.fly, controller.input_state.run) {
+            (true, true) => settings.fly_speed() * FLY_SPRINT_FACTOR,
+            (true, false) => settings.fly_speed(),
+            (false, true) => controller.run_speed,
+            (false, false) => controller.walk_speed,
         };
+        let in_liquid = properties_at(&chunk_map, transform.translation).is_liquid;
+        // 在液体中移动变慢
+        if !controller.fly && in_liquid {
+            speed *= LIQUID_SPEED_FACTOR;
+        }
         desired_velocity = if desired_velocity.length_squared() > 1E-6 {
             desired_velocity.normalize() * speed
         } else {
@@ -336,20 +572,173 @@ pub fn input_to_send(
             controller.velocity * 0.5 * xz
         };
 
-        if !controller.fly {
-            desired_velocity.y = if controller.input_state.jump {
+        if !controller.fly
+            && settings.auto_jump
+            && !controller.input_state.jump
+            && need_auto_jump(&chunk_map, transform.translation, desired_velocity * xz)
+        {
+            controller.input_state.jump = true;
+        }
+
+        let climbing = !controller.fly && chunk_map.climbable_at(transform.translation);
+        if climbing {
+            // 攀爬时由上升下降控制竖直速度 松开时停在原地
+            let up_pressed =
+                controller.input_state.up || keyboard_input.pressed(controller.input_map.key_jump);
+            let down_pressed = controller.input_state.down
+                || keyboard_input.pressed(controller.input_map.key_crouch);
+            desired_velocity.y = match (up_pressed, down_pressed) {
+                (true, false) => CLIMB_SPEED,
+                (false, true) => -CLIMB_SPEED,
+                _ => 0.0,
+            };
+        } else if !controller.fly {
+            // 在液体中可以一直向上游
+            let grounded = in_liquid || is_grounded(&chunk_map, transform.translation);
+            let jump_pressed = controller.input_state.jump;
+            let (buffer_time, coyote_time) = (controller.jump_buffer_time, controller.coyote_time);
+            controller
+                .jump_timer
+                .tick(time.delta_seconds(), grounded, jump_pressed);
+            desired_velocity.y = if controller.jump_timer.try_jump(buffer_time, coyote_time) {
                 controller.jumping = true;
-                controller.jump_speed
+                physics_config.jump_speed
             } else {
                 0.0
             };
         }
         //TODO Handle jumping
         // let was_jumping = controller.jumping;
-        let message = bincode::serialize(&PlayerInput::MOVE(desired_velocity)).unwrap();
-        client.send_message(ClientChannel::Input, message);
+        sender.next_move = Some(desired_velocity);
         controller.input_state = InputState::default();
     }
 }
 
-// todo 个人的fly模式
+// 玩家脚底相对于身体中心的偏移 和服务端的胶囊体保持一致
+const PLAYER_FOOT_OFFSET: f32 = 0.5 * 1.7 + 0.3;
+// 向前探测台阶的距离
+const AUTO_JUMP_PROBE: f32 = 0.6;
+
+// 液体中移动速度的系数
+const LIQUID_SPEED_FACTOR: f32 = 0.5;
+
+// 攀爬时竖直方向的速度
+const CLIMB_SPEED: f32 = 3.0;
+
+// 双击跳跃的最长间隔(秒)
+const FLY_DOUBLE_TAP_TIME: f32 = 0.3;
+// 飞行时疾跑的速度系数
+const FLY_SPRINT_FACTOR: f32 = 2.0;
+
+// 获取该位置的方块属性 没有加载的区块当做空气
+pub fn properties_at(chunk_map: &ChunkMap, pos: Vec3) -> VoxelProperties {
+    let (chunk_key, xyz) = vec3_to_chunk_key_any_xyz(pos);
+    match chunk_map.get_block(chunk_key, xyz) {
+        Some(voxel) => voxel.properties(),
+        None => VoxelProperties::AIR,
+    }
+}
+
+// 判断该位置的方块是否会阻挡玩家
+fn is_solid_at(chunk_map: &ChunkMap, pos: Vec3) -> bool {
+    properties_at(chunk_map, pos).is_solid
+}
+
+// 是否站在地面上
+fn is_grounded(chunk_map: &ChunkMap, body_pos: Vec3) -> bool {
+    let mut foot = body_pos - Vec3::Y * PLAYER_FOOT_OFFSET;
+    // 脚底离方块表面太远说明在空中
+    if (foot.y - foot.y.round()).abs() > 0.05 {
+        return false;
+    }
+    foot.y = foot.y.round();
+    is_solid_at(chunk_map, foot - Vec3::Y * 0.5)
+}
+
+/**
+ * 是否需要自动跳跃
+ * 站在地面上 前方脚下有一格方块 并且方块上面有两格的空间
+ */
+fn need_auto_jump(chunk_map: &ChunkMap, body_pos: Vec3, horizontal_velocity: Vec3) -> bool {
+    if horizontal_velocity.length_squared() < 1E-6 {
+        return false;
+    }
+    if !is_grounded(chunk_map, body_pos) {
+        return false;
+    }
+    let mut foot = body_pos - Vec3::Y * PLAYER_FOOT_OFFSET;
+    foot.y = foot.y.round();
+    let step = foot + horizontal_velocity.normalize() * AUTO_JUMP_PROBE + Vec3::Y * 0.5;
+    is_solid_at(chunk_map, step)
+        && !is_solid_at(chunk_map, step + Vec3::Y)
+        && !is_solid_at(chunk_map, step + Vec3::Y * 2.0)
+        // 头顶也需要有空间
+        && !is_solid_at(chunk_map, foot + Vec3::Y * 2.5)
+}
+
+#[test]
+fn test_jump_buffer() {
+    let mut timer = JumpTimer::default();
+    // 空中按下跳跃 还没有落地
+    timer.tick(0.016, false, true);
+    assert!(!timer.try_jump(0.1, 0.1));
+    // 0.05秒后落地 缓冲的跳跃执行
+    timer.tick(0.05, true, false);
+    assert!(timer.try_jump(0.1, 0.1));
+    // 跳跃已经被消耗
+    timer.tick(0.016, true, false);
+    assert!(!timer.try_jump(0.1, 0.1));
+
+    // 按下太早 超过缓冲时间
+    let mut timer = JumpTimer::default();
+    timer.tick(0.016, false, true);
+    timer.tick(0.2, true, false);
+    assert!(!timer.try_jump(0.1, 0.1));
+}
+
+#[test]
+fn test_coyote_time() {
+    let mut timer = JumpTimer::default();
+    timer.tick(0.016, true, false);
+    // 走出边缘 0.05秒后按下跳跃
+    timer.tick(0.05, false, true);
+    assert!(timer.try_jump(0.1, 0.1));
+    // 不能在空中再跳一次
+    timer.tick(0.016, false, true);
+    assert!(!timer.try_jump(0.1, 0.1));
+
+    // 离开地面太久
+    let mut timer = JumpTimer::default();
+    timer.tick(0.016, true, false);
+    timer.tick(0.2, false, true);
+    assert!(!timer.try_jump(0.1, 0.1));
+}
+
+#[test]
+fn test_urgent_move() {
+    let walk = Vec3::new(5.0, 0.0, 0.0);
+    assert!(urgent_move(None, Vec3::ZERO));
+    // 一直走或者一直停着 等定时发送
+    assert!(!urgent_move(Some(walk), Vec3::new(0.0, 0.0, 5.0)));
+    assert!(!urgent_move(Some(Vec3::ZERO), Vec3::ZERO));
+    // 开始 停止和跳跃立即发送
+    assert!(urgent_move(Some(Vec3::ZERO), walk));
+    assert!(urgent_move(Some(walk), Vec3::ZERO));
+    assert!(urgent_move(Some(walk), walk + Vec3::Y * 8.0));
+}
+
+#[test]
+fn test_double_tap() {
+    let mut tap = DoubleTap::default();
+    assert!(!tap.tick(0.016, true, 0.3));
+    assert!(!tap.tick(0.1, false, 0.3));
+    assert!(tap.tick(0.1, true, 0.3));
+    // 第三次按下重新开始计算
+    assert!(!tap.tick(0.1, true, 0.3));
+
+    // 两次按下间隔太长
+    let mut tap = DoubleTap::default();
+    assert!(!tap.tick(0.016, true, 0.3));
+    assert!(!tap.tick(0.4, false, 0.3));
+    assert!(!tap.tick(0.016, true, 0.3));
+}
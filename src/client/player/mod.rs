@@ -11,7 +11,7 @@ use bevy::{
 use bevy_atmosphere::prelude::AtmosphereCamera;
 use bevy_mod_billboard::BillboardTextBundle;
 
-use crate::server::player::Player;
+use crate::{client::net_smoothing::NetSnapshots, server::player::Player};
 
 use self::{
     controller::{BodyTag, CameraTag, CharacterController, HeadTag, ThirdPerson, YawTag},
@@ -38,6 +38,10 @@ pub struct ClientLobby {
     pub players: HashMap<u64, PlayerInfo>,
     pub yaws: HashMap<u64, Entity>,
     pub pitch: HashMap<u64, Entity>,
+    // 玩家的名字
+    pub names: HashMap<u64, String>,
+    // 最近一次私聊的对象 /r 使用
+    pub last_whisper: Option<String>,
 }
 
 pub fn client_create_player(
@@ -63,6 +67,8 @@ pub fn client_create_player(
         .insert((Visibility::Inherited, ComputedVisibility::HIDDEN));
     if is_current {
         body_entry.insert(CharacterController::default());
+    } else {
+        body_entry.insert(NetSnapshots::default());
     }
     let body = body_entry.id();
     let yaw = commands
@@ -9,18 +9,52 @@ use bevy_renet::renet::RenetClient;
 
 use crate::{
     client::{
+        achievement::AchievementEvent,
         message_def::{chunk_query::ChunkQuery, ClientChannel},
         ray_cast::choose_cube::ChooseCube,
         state_manager::GameState,
         ui::tool_bar::ToolBar,
     },
-    server::player::Player,
+    server::{
+        brush::{BrushMode, BrushShape},
+        player::Player,
+    },
     tools::{vec3_to_chunk_key_any_xyz, zone::check_player_put_object_available},
-    voxel_world::{chunk::ChunkKey, chunk_map::ChunkMap, voxel::Voxel},
+    voxel_world::{
+        chunk::ChunkKey,
+        chunk_map::ChunkMap,
+        voxel::Voxel,
+        voxel_registry::{Tool, VoxelRegistry},
+    },
 };
 
 use super::controller::ControllerFlag;
 
+// 当前的笔刷 默认是普通的单个方块放置 用 /brush 修改
+#[derive(Debug, Clone, Copy, Resource)]
+pub struct ActiveBrush {
+    pub mode: BrushMode,
+    pub shape: BrushShape,
+    pub radius: u32,
+}
+
+impl Default for ActiveBrush {
+    fn default() -> Self {
+        Self {
+            mode: BrushMode::Normal,
+            shape: BrushShape::Sphere,
+            radius: 0,
+        }
+    }
+}
+
+impl ActiveBrush {
+    // 普通的单个方块放置 走原来的放置流程 消耗物品
+    pub fn is_single(&self) -> bool {
+        self.mode == BrushMode::Normal && self.radius == 0
+    }
+}
+
 // 破坏方块的计时器
 
 #[derive(Debug, Resource, Clone)]
@@ -62,17 +96,25 @@ pub fn deal_attack_time(
 pub fn deal_broken_cube_event(
     mut broke_cube_event: EventReader<BrokeCubeEvent>,
     mut client: ResMut<RenetClient>,
+    mut achievement_events: EventWriter<AchievementEvent>,
+    tool_bar_data: Res<ToolBar>,
 ) {
     for event in broke_cube_event.iter() {
+        // 手里拿着工具时发送位置 服务端扣除耐久
+        let active_index = tool_bar_data
+            .active_staff()
+            .filter(|(_, staff)| staff.is_tool())
+            .map(|(index, _)| index);
         let message = bincode::serialize(&ChunkQuery::Change {
             chunk_key: event.chunk_key,
             pos: event.xyz,
             voxel_type: Voxel::EMPTY,
             center: event.center,
-            active_index: None,
+            active_index,
         })
         .unwrap();
         client.send_message(ClientChannel::ChunkQuery, message);
+        achievement_events.send(AchievementEvent::BlockBroken);
     }
 }
 
@@ -87,6 +129,9 @@ pub fn mouse_button_system(
     mut attack_timer: ResMut<AttackTimer>,
     player_query: Query<(&Player, &Transform)>,
     chunk_map: Res<ChunkMap>,
+    voxel_registry: Res<VoxelRegistry>,
+    mut achievement_events: EventWriter<AchievementEvent>,
+    brush: Res<ActiveBrush>,
 ) {
     if !controller_flag.flag {
         // println!("3:{}", controller_flag.flag);
@@ -130,10 +175,11 @@ pub fn mouse_button_system(
                 if test_chunk_key == chunk_key && test_xyz == xyz {
                     // 和原来位置一样不处理
                 } else {
-                    // FIXME: 后续根据体素块 和 当前物体来判断 新的 timer
-                    attack_timer.timer = Some(Timer::new(
-                        bevy::utils::Duration::from_millis(1000 * 2),
-                        TimerMode::Once,
+                    attack_timer.timer = Some(break_timer(
+                        &chunk_map,
+                        &voxel_registry,
+                        pos,
+                        tool_bar_data.active_tool(),
                     ));
                     attack_timer.chunk_key = chunk_key;
                     attack_timer.xyz = xyz;
@@ -143,10 +189,11 @@ pub fn mouse_button_system(
                 attack_timer.chunk_key = chunk_key;
                 attack_timer.xyz = xyz;
                 attack_timer.center = pos;
-                // FIXME: 后续根据体素块 和 当前物体来判断
-                attack_timer.timer = Some(Timer::new(
-                    bevy::utils::Duration::from_millis(1000 * 2),
-                    TimerMode::Once,
+                attack_timer.timer = Some(break_timer(
+                    &chunk_map,
+                    &voxel_registry,
+                    pos,
+                    tool_bar_data.active_tool(),
                 ));
             }
         } else {
@@ -161,10 +208,47 @@ pub fn mouse_button_system(
     }
 
     if mouse_button_input.just_pressed(MouseButton::Right) {
+        // 对着可以交互的方块时交互 按住 Shift 时照常放置
+        if !keyboard_input.pressed(KeyCode::ShiftLeft) {
+            if let Some(pos) = choose_cube.center {
+                let (chunk_key, xyz) = vec3_to_chunk_key_any_xyz(pos);
+                let interactable = chunk_map
+                    .get_block(chunk_key, xyz)
+                    .map_or(false, |voxel| voxel_registry.interactable(voxel.id));
+                if interactable {
+                    let message = bincode::serialize(&ChunkQuery::Interact {
+                        chunk_key,
+                        pos: xyz,
+                    })
+                    .unwrap();
+                    client.send_message(ClientChannel::ChunkQuery, message);
+                    return;
+                }
+            }
+        }
         // Note: 这里放置时尝试转换成体素再传递
         if let Some(crate::staff::StaffType::Voxel(voxel_type)) =
             tool_bar_data.staff_type_try_to_voxel()
         {
+            // 笔刷一次修改一组方块 替换模式以选中的方块为中心
+            if !brush.is_single() {
+                let target = match brush.mode {
+                    BrushMode::Normal => choose_cube.out_center,
+                    _ => choose_cube.center,
+                };
+                if let Some(pos) = target {
+                    let message = bincode::serialize(&ChunkQuery::Brush {
+                        center: pos.floor().as_ivec3().to_array(),
+                        radius: brush.radius,
+                        shape: brush.shape,
+                        mode: brush.mode,
+                        voxel: voxel_type,
+                    })
+                    .unwrap();
+                    client.send_message(ClientChannel::ChunkQuery, message);
+                }
+                return;
+            }
             if let Some(pos) = choose_cube.out_center {
                 println!("放置物品{:?}", voxel_type);
                 // 判断当前这里是否和 其他的player的位置冲突
@@ -180,6 +264,7 @@ pub fn mouse_button_system(
                     })
                     .unwrap();
                     client.send_message(ClientChannel::ChunkQuery, message);
+                    achievement_events.send(AchievementEvent::BlockPlaced);
                 } else {
                     warn!("放置物体时有其他的玩家");
                 }
@@ -190,11 +275,27 @@ pub fn mouse_button_system(
     }
 }
 
+// 根据方块的硬度和手里的工具生成破坏计时器
+fn break_timer(
+    chunk_map: &ChunkMap,
+    voxel_registry: &VoxelRegistry,
+    pos: Vec3,
+    tool: Tool,
+) -> Timer {
+    let (chunk_key, xyz) = vec3_to_chunk_key_any_xyz(pos);
+    let id = match chunk_map.get_block(chunk_key, xyz) {
+        Some(voxel) => voxel.id,
+        None => Voxel::FILLED.id,
+    };
+    Timer::from_seconds(voxel_registry.break_time(id, tool), TimerMode::Once)
+}
+
 pub struct MouseControlPlugin;
 
 impl Plugin for MouseControlPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
         app.add_event::<BrokeCubeEvent>();
+        app.init_resource::<ActiveBrush>();
         app.insert_resource(AttackTimer {
             pressed: false,
             timer: None,
@@ -1,8 +1,8 @@
 use bevy::{
     prelude::{
-        AlphaMode, Assets, Color, Commands, Entity, Gizmos, GlobalTransform, Mesh, PbrBundle,
-        Plugin, Quat, Query, Res, ResMut, StandardMaterial, Startup, Transform, Update, Vec3,
-        Visibility, With, Without,
+        AlphaMode, Assets, Color, Commands, Entity, Gizmos, GlobalTransform, IVec3, Mesh,
+        PbrBundle, Plugin, Quat, Query, Res, ResMut, StandardMaterial, Startup, Transform, Update,
+        Vec3, Visibility, With, Without,
     },
     reflect::Reflect,
     render::render_resource::PrimitiveTopology,
@@ -12,17 +12,104 @@ use bevy_mod_raycast::{
     DefaultRaycastingPlugin, Ray3d,
 };
 
-use crate::{CLIENT_DEBUG, TOUCH_RADIUS};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    tools::vec3_to_chunk_key_any_xyz,
+    voxel_world::{chunk_map::ChunkMap, voxel::Voxel, voxel_registry::VOXEL_REGISTRY},
+    CLIENT_DEBUG, TOUCH_RADIUS,
+};
 
 use self::choose_cube::{ChooseCube, HelpCube};
 
 use super::{
     mesh_display::TerrainMesh,
     player::{controller::CameraTag, mouse_control::AttackTimer},
+    settings::GraphicsSettings,
 };
 
 pub mod choose_cube;
 
+/**
+ * 瞄准透明方块(水 树叶)的方式
+ * 穿过时选中后面第一个不透明的方块 后面没有方块时仍然选中透明的方块
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TargetMode {
+    FirstBlock,
+    ThroughTransparent,
+}
+
+impl TargetMode {
+    pub const ALL: [TargetMode; 2] = [TargetMode::FirstBlock, TargetMode::ThroughTransparent];
+
+    // 翻译的关键字
+    pub fn name(&self) -> &'static str {
+        match self {
+            TargetMode::FirstBlock => "选中透明方块",
+            TargetMode::ThroughTransparent => "穿过透明方块",
+        }
+    }
+}
+
+// 视线可以穿过的体素
+fn passable(voxel: Voxel) -> bool {
+    voxel.id == Voxel::EMPTY.id || VOXEL_REGISTRY.transparent(voxel.id)
+}
+
+/**
+ * 沿着射线逐个方块前进 返回第一个不透明的方块和射线进入的面的法向量
+ * 方块 p 占据 p 到 p + 1 的范围 超出距离或者区块没有加载时返回 None
+ */
+pub fn first_opaque_block(
+    origin: Vec3,
+    direction: Vec3,
+    max_distance: f32,
+    voxel_at: impl Fn(IVec3) -> Option<Voxel>,
+) -> Option<(IVec3, IVec3)> {
+    let direction = direction.normalize_or_zero();
+    if direction == Vec3::ZERO {
+        return None;
+    }
+    let mut block = origin.floor().as_ivec3();
+    let mut step = IVec3::ZERO;
+    // 到下一个边界的距离 和穿过一个方块的距离
+    let mut t_max = Vec3::splat(f32::INFINITY);
+    let mut t_delta = Vec3::splat(f32::INFINITY);
+    for axis in 0..3 {
+        let d = direction[axis];
+        if d > 0.0 {
+            step[axis] = 1;
+            t_max[axis] = (block[axis] as f32 + 1.0 - origin[axis]) / d;
+            t_delta[axis] = 1.0 / d;
+        } else if d < 0.0 {
+            step[axis] = -1;
+            t_max[axis] = (origin[axis] - block[axis] as f32) / -d;
+            t_delta[axis] = 1.0 / -d;
+        }
+    }
+    loop {
+        let axis = if t_max.x <= t_max.y && t_max.x <= t_max.z {
+            0
+        } else if t_max.y <= t_max.z {
+            1
+        } else {
+            2
+        };
+        if t_max[axis] > max_distance {
+            return None;
+        }
+        block[axis] += step[axis];
+        t_max[axis] += t_delta[axis];
+        let voxel = voxel_at(block)?;
+        if !passable(voxel) {
+            let mut normal = IVec3::ZERO;
+            normal[axis] = -step[axis];
+            return Some((block, normal));
+        }
+    }
+}
+
 fn get_pos_chunk_center(vec3: Vec3, normal: Vec3) -> Vec3 {
     // 应该是命中点所在的面的中点
     let mid_pos = Vec3::new(
@@ -124,6 +211,8 @@ pub fn touth_mesh_ray_cast(
         (With<HelpCube>, Without<CameraTag>),
     >,
     attack_timer: Res<AttackTimer>,
+    settings: Res<GraphicsSettings>,
+    chunk_map: Res<ChunkMap>,
 ) {
     let Ok((mut chue_pos, mut visibility)) = query_help_cube.get_single_mut() else {
         println!("not found Cube.");
@@ -161,11 +250,28 @@ pub fn touth_mesh_ray_cast(
                 gizmos.circle(hit_point, normal, 0.1 * rate, Color::BLUE);
             }
 
-            let center_point: Vec3;
+            let mut center_point: Vec3;
             match mesh_data.0 {
                 super::mesh_display::HitMeshType::Common => {
                     center_point = get_pos_chunk_center(hit_point, normal);
-                    let out_center_point = get_pos_chunk_center(hit_point, -normal);
+                    let mut out_center_point = get_pos_chunk_center(hit_point, -normal);
+                    if settings.target_mode == TargetMode::ThroughTransparent {
+                        let voxel_at = |pos: IVec3| {
+                            let (chunk_key, xyz) =
+                                vec3_to_chunk_key_any_xyz(pos.as_vec3() + Vec3::splat(0.5));
+                            chunk_map.get_block(chunk_key, xyz)
+                        };
+                        let hit_transparent = voxel_at(center_point.floor().as_ivec3())
+                            .map_or(false, |voxel| VOXEL_REGISTRY.transparent(voxel.id));
+                        if hit_transparent {
+                            if let Some((block, face)) =
+                                first_opaque_block(ray_pos, ray_dir, TOUCH_RADIUS, voxel_at)
+                            {
+                                center_point = block.as_vec3() + Vec3::splat(0.5);
+                                out_center_point = center_point + face.as_vec3();
+                            }
+                        }
+                    }
                     gizmos.sphere(out_center_point, Quat::IDENTITY, 0.5, Color::GREEN);
                     choose_cube.out_center = Some(out_center_point);
                 }
@@ -195,3 +301,40 @@ fn hidden_help_cube(choose_cube: &mut ChooseCube, visibility: &mut Visibility) {
     choose_cube.out_center = None;
     *visibility = Visibility::Hidden
 }
+
+#[test]
+fn test_first_opaque_block() {
+    use crate::voxel_world::voxel::{AppleLeaf, Stone, VoxelMaterial};
+
+    // x = 2 是树叶 x = 4 是石头
+    let voxel_at = |pos: IVec3| {
+        Some(match pos.x {
+            2 => AppleLeaf::into_voxel(),
+            4 => Stone::into_voxel(),
+            _ => Voxel::EMPTY,
+        })
+    };
+    let origin = Vec3::new(0.5, 0.5, 0.5);
+    assert_eq!(
+        first_opaque_block(origin, Vec3::X, 10.0, voxel_at),
+        Some((IVec3::new(4, 0, 0), IVec3::new(-1, 0, 0)))
+    );
+    // 超出距离
+    assert_eq!(first_opaque_block(origin, Vec3::X, 3.0, voxel_at), None);
+    assert_eq!(first_opaque_block(origin, -Vec3::X, 10.0, voxel_at), None);
+    // 斜着进入时返回进入的面
+    let (block, normal) = first_opaque_block(
+        Vec3::new(3.5, 3.0, 0.5),
+        Vec3::new(0.2, -1.0, 0.0),
+        10.0,
+        |pos| {
+            Some(if pos.y < 0 {
+                Stone::into_voxel()
+            } else {
+                Voxel::EMPTY
+            })
+        },
+    )
+    .unwrap();
+    assert_eq!((block.y, normal), (-1, IVec3::Y));
+}
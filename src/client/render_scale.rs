@@ -0,0 +1,172 @@
+// 渲染缩放
+// 3D 相机先渲染到一张 窗口大小 * 缩放 的贴图上 再用一个 2D 相机把贴图拉伸到整个窗口
+// 小于 1 时降低分辨率提高性能 大于 1 时是超采样
+// egui 直接画在窗口上 所以文字始终是原生分辨率
+
+use bevy::{
+    core_pipeline::tonemapping::Tonemapping,
+    prelude::{
+        in_state, Assets, Camera, Camera2dBundle, Commands, Component, DespawnRecursiveExt, Entity,
+        Handle, Image, IntoSystemConfigs, OnExit, Plugin, Query, Res, ResMut, Resource, Sprite,
+        SpriteBundle, UVec2, UiCameraConfig, Update, Vec2, With,
+    },
+    render::{
+        camera::RenderTarget,
+        render_resource::{
+            Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+        },
+        view::RenderLayers,
+    },
+    window::{PrimaryWindow, Window, WindowRef},
+};
+
+use super::{
+    player::controller::CameraTag,
+    settings::{GraphicsSettings, RENDER_SCALE_RANGE},
+    state_manager::GameState,
+};
+
+// 显示缩放后画面的渲染层 不和其他物体混在一起
+const RENDER_SCALE_LAYER: u8 = 31;
+
+#[derive(Debug, Default, Resource)]
+pub struct RenderScaleTarget {
+    image: Option<Handle<Image>>,
+    size: UVec2,
+}
+
+// 显示缩放后画面的 2D 相机和精灵
+#[derive(Debug, Component)]
+pub struct RenderScaleView;
+
+pub struct RenderScalePlugin;
+
+impl Plugin for RenderScalePlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.init_resource::<RenderScaleTarget>();
+        app.add_systems(
+            Update,
+            update_render_scale.run_if(in_state(GameState::Game)),
+        );
+        app.add_systems(OnExit(GameState::Game), clear_render_scale);
+    }
+}
+
+fn render_image(size: UVec2) -> Image {
+    let extent = Extent3d {
+        width: size.x,
+        height: size.y,
+        depth_or_array_layers: 1,
+    };
+    let mut image = Image {
+        texture_descriptor: TextureDescriptor {
+            label: None,
+            size: extent,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Bgra8UnormSrgb,
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_DST
+                | TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        },
+        ..Default::default()
+    };
+    image.resize(extent);
+    image
+}
+
+/**
+ * 缩放或者窗口大小变化时重建贴图
+ * 缩放为 1 时直接渲染到窗口
+ */
+fn update_render_scale(
+    mut commands: Commands,
+    settings: Res<GraphicsSettings>,
+    mut target: ResMut<RenderScaleTarget>,
+    mut images: ResMut<Assets<Image>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut cameras: Query<(Entity, &mut Camera), With<CameraTag>>,
+    views: Query<Entity, With<RenderScaleView>>,
+) {
+    let (Ok(window), Ok((camera_entity, mut camera))) =
+        (windows.get_single(), cameras.get_single_mut())
+    else {
+        return;
+    };
+    let scale = settings
+        .render_scale
+        .clamp(*RENDER_SCALE_RANGE.start(), *RENDER_SCALE_RANGE.end());
+    // 玩家重新生成时相机也是新的
+    let scaled = matches!(camera.target, RenderTarget::Image(_));
+    if (scale - 1.0).abs() < 0.01 {
+        if scaled || target.image.is_some() {
+            camera.target = RenderTarget::Window(WindowRef::Primary);
+            commands
+                .entity(camera_entity)
+                .insert(UiCameraConfig { show_ui: true });
+            for entity in views.iter() {
+                commands.entity(entity).despawn_recursive();
+            }
+            *target = RenderScaleTarget::default();
+        }
+        return;
+    }
+    let physical = Vec2::new(
+        window.physical_width() as f32,
+        window.physical_height() as f32,
+    );
+    let size = (physical * scale).as_uvec2().max(UVec2::ONE);
+    if scaled && target.size == size {
+        return;
+    }
+    let image = images.add(render_image(size));
+    camera.target = RenderTarget::Image(image.clone());
+    // 界面画在窗口的相机上
+    commands
+        .entity(camera_entity)
+        .insert(UiCameraConfig { show_ui: false });
+    for entity in views.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let layer = RenderLayers::layer(RENDER_SCALE_LAYER);
+    commands.spawn((
+        Camera2dBundle {
+            camera: Camera {
+                order: 1,
+                ..Default::default()
+            },
+            // 3D 相机已经做过色调映射
+            tonemapping: Tonemapping::None,
+            ..Default::default()
+        },
+        layer,
+        RenderScaleView,
+    ));
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                custom_size: Some(Vec2::new(window.width(), window.height())),
+                ..Default::default()
+            },
+            texture: image.clone(),
+            ..Default::default()
+        },
+        layer,
+        RenderScaleView,
+    ));
+    target.image = Some(image);
+    target.size = size;
+}
+
+fn clear_render_scale(
+    mut commands: Commands,
+    mut target: ResMut<RenderScaleTarget>,
+    views: Query<Entity, With<RenderScaleView>>,
+) {
+    for entity in views.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    *target = RenderScaleTarget::default();
+}
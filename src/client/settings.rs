@@ -0,0 +1,415 @@
+// 客户端的图像设置 保存在文件中 在设置菜单中修改
+
+use std::{io::Write, ops::RangeInclusive, time::Duration};
+
+use bevy::{
+    core_pipeline::tonemapping::ColorGrading,
+    prelude::{DetectChanges, Msaa, Plugin, Query, Ref, Res, ResMut, Resource, Update},
+    winit::{UpdateMode, WinitSettings},
+};
+use bevy_egui::EguiSettings;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    client::{
+        net_smoothing::NetSmoothing,
+        player::controller::CameraTag,
+        ray_cast::TargetMode,
+        state_manager::notification::{ToastAnchor, DEFAULT_TOAST_DURATION},
+    },
+    voxel_world::biomes::BiomeMapPalette,
+    CLIENT_SETTINGS_PATH,
+};
+
+// 亮度和伽马的调节范围 1.0 表示不修改画面
+pub const BRIGHTNESS_RANGE: RangeInclusive<f32> = 0.5..=2.0;
+pub const GAMMA_RANGE: RangeInclusive<f32> = 0.5..=2.0;
+// 渲染缩放的范围 小于 1 降低分辨率 大于 1 超采样
+pub const RENDER_SCALE_RANGE: RangeInclusive<f32> = 0.5..=2.0;
+// 界面缩放的范围 和系统的缩放相乘
+pub const UI_SCALE_RANGE: RangeInclusive<f32> = 0.75..=2.5;
+// 窗口失去焦点时的帧率范围
+pub const UNFOCUSED_FPS_RANGE: RangeInclusive<u32> = 1..=30;
+// 通知默认时长的范围(秒)
+pub const TOAST_DURATION_RANGE: RangeInclusive<f32> = 1.0..=10.0;
+// 每帧上传区块网格的数量和时间(毫秒)
+pub const MESH_UPLOADS_RANGE: RangeInclusive<usize> = 1..=32;
+pub const MESH_UPLOAD_BUDGET_RANGE: RangeInclusive<f32> = 1.0..=16.0;
+// 地表装饰的密度倍数
+pub const DECORATION_DENSITY_RANGE: RangeInclusive<f32> = 0.0..=2.0;
+// 水下色调的强度 也就是覆盖层的不透明度
+pub const UNDERWATER_TINT_RANGE: RangeInclusive<f32> = 0.0..=0.8;
+// 区块加载偏向视线方向的程度
+pub const CHUNK_VIEW_BIAS_RANGE: RangeInclusive<f32> = 0.0..=1.0;
+// 每秒发送移动和视角的次数
+pub const INPUT_SEND_RATE_RANGE: RangeInclusive<u32> = 10..=60;
+// 破坏方块时碎片的数量和存在的时间(秒)
+pub const BREAK_PARTICLES_RANGE: RangeInclusive<usize> = 1..=32;
+pub const BREAK_PARTICLE_LIFETIME_RANGE: RangeInclusive<f32> = 0.2..=2.0;
+// 连接不稳定警告的延迟(毫秒)和丢包率阈值
+pub const CONNECTION_WARN_RTT_RANGE: RangeInclusive<f32> = 50.0..=1000.0;
+pub const CONNECTION_WARN_LOSS_RANGE: RangeInclusive<f32> = 0.01..=0.5;
+// 打开和关闭界面的过渡时间(秒)
+pub const PLAY_TRANSITION_RANGE: RangeInclusive<f32> = 0.05..=1.0;
+// 其他玩家和掉落物的显示距离(格)
+pub const ENTITY_CULL_DISTANCE_RANGE: RangeInclusive<f32> = 16.0..=512.0;
+// 飞行的速度(格/秒) 疾跑时加倍
+pub const FLY_SPEED_RANGE: RangeInclusive<f32> = 2.0..=30.0;
+
+// 阴影质量
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ShadowQuality {
+    Low,
+    Medium,
+    High,
+}
+
+impl ShadowQuality {
+    pub const ALL: [ShadowQuality; 3] = [
+        ShadowQuality::Low,
+        ShadowQuality::Medium,
+        ShadowQuality::High,
+    ];
+
+    // 阴影贴图的分辨率
+    pub fn map_size(&self) -> usize {
+        match self {
+            ShadowQuality::Low => 1024,
+            ShadowQuality::Medium => 2048,
+            ShadowQuality::High => 4096,
+        }
+    }
+
+    // 级联阴影的层数
+    pub fn cascades(&self) -> usize {
+        match self {
+            ShadowQuality::Low => 1,
+            ShadowQuality::Medium => 2,
+            ShadowQuality::High => 4,
+        }
+    }
+
+    // 翻译的关键字
+    pub fn name(&self) -> &'static str {
+        match self {
+            ShadowQuality::Low => "低",
+            ShadowQuality::Medium => "中",
+            ShadowQuality::High => "高",
+        }
+    }
+}
+
+// 日出日落时地平线的大气散射 关闭可以节省低端机器的性能
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ScatteringQuality {
+    Off,
+    Low,
+    High,
+}
+
+impl ScatteringQuality {
+    pub const ALL: [ScatteringQuality; 3] = [
+        ScatteringQuality::Off,
+        ScatteringQuality::Low,
+        ScatteringQuality::High,
+    ];
+
+    // 散射光的强度 0 表示关闭
+    pub fn strength(&self) -> f32 {
+        match self {
+            ScatteringQuality::Off => 0.0,
+            ScatteringQuality::Low => 0.5,
+            ScatteringQuality::High => 1.0,
+        }
+    }
+
+    // 光晕的集中程度 越大光晕越小
+    pub fn exponent(&self) -> f32 {
+        match self {
+            ScatteringQuality::Off | ScatteringQuality::Low => 30.0,
+            ScatteringQuality::High => 10.0,
+        }
+    }
+
+    // 翻译的关键字
+    pub fn name(&self) -> &'static str {
+        match self {
+            ScatteringQuality::Off => "关",
+            ScatteringQuality::Low => "低",
+            ScatteringQuality::High => "高",
+        }
+    }
+}
+
+// 多重采样抗锯齿
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MsaaLevel {
+    Off,
+    X2,
+    X4,
+}
+
+impl MsaaLevel {
+    pub const ALL: [MsaaLevel; 3] = [MsaaLevel::Off, MsaaLevel::X2, MsaaLevel::X4];
+
+    pub fn msaa(&self) -> Msaa {
+        match self {
+            MsaaLevel::Off => Msaa::Off,
+            MsaaLevel::X2 => Msaa::Sample2,
+            MsaaLevel::X4 => Msaa::Sample4,
+        }
+    }
+
+    // 翻译的关键字
+    pub fn name(&self) -> &'static str {
+        match self {
+            MsaaLevel::Off => "关",
+            MsaaLevel::X2 => "2x",
+            MsaaLevel::X4 => "4x",
+        }
+    }
+}
+
+/**
+ * 图像设置
+ * 新增的字段使用默认值 旧的设置文件可以继续读取
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, Resource)]
+#[serde(default)]
+pub struct GraphicsSettings {
+    pub shadows_enabled: bool,
+    pub shadow_quality: ShadowQuality,
+    // 画面亮度的倍数
+    pub brightness: f32,
+    pub gamma: f32,
+    pub msaa: MsaaLevel,
+    // 3D 画面的渲染缩放 界面始终使用原生分辨率
+    pub render_scale: f32,
+    // egui 界面的缩放 和 3D 画面的渲染缩放无关
+    pub ui_scale: f32,
+    // 窗口失去焦点时限制帧率 网络连接照常更新
+    pub limit_unfocused: bool,
+    pub unfocused_fps: u32,
+    // 窗口失去焦点时不再读取移动按键 角色停下来
+    pub pause_movement_unfocused: bool,
+    // 窗口失去焦点时释放光标 重新获得焦点后恢复
+    pub release_cursor_unfocused: bool,
+    // 通知显示的位置和默认时长(秒)
+    pub toast_anchor: ToastAnchor,
+    pub toast_duration: f32,
+    // 其他玩家位置的平滑方式 混合时 0 为插值 1 为外推
+    pub net_smoothing: NetSmoothing,
+    pub net_blend: f32,
+    // 每帧最多上传的区块网格 数量和时间任意一个用完就留到下一帧
+    pub mesh_uploads_per_frame: usize,
+    pub mesh_upload_budget_ms: f32,
+    // 地表装饰(草 花 石头) 关闭后可以提高性能
+    pub decorations: bool,
+    pub decoration_density: f32,
+    // 头部在水中时画面的色调(rgb)和强度
+    pub underwater_tint: [f32; 3],
+    pub underwater_tint_strength: f32,
+    // 每秒发送移动和视角的次数 和帧率无关 跳跃和开始停止移动立即发送
+    pub input_send_rate: u32,
+    // 日出日落时地平线的光晕
+    pub scattering: ScatteringQuality,
+    // 视线方向的区块先加载 0 只按距离 1 时身后的区块按两倍距离排序
+    pub chunk_view_bias: f32,
+    // 破坏方块时的碎片 关闭后可以提高性能
+    pub break_particles: bool,
+    pub break_particle_count: usize,
+    pub break_particle_lifetime: f32,
+    // 延迟或丢包持续超过阈值时警告连接不稳定 一半时指示器变黄
+    pub connection_warn_rtt: f32,
+    pub connection_warn_loss: f32,
+    // 瞄准透明方块(水 树叶)时选中它 还是选中后面的方块
+    pub target_mode: TargetMode,
+    // 打开合成列表等界面时的过渡 关闭后立即切换
+    pub play_transitions: bool,
+    pub play_transition_duration: f32,
+    // 界面后面的游戏画面变暗
+    pub menu_dim: bool,
+    // 超过这个距离的其他玩家和掉落物不显示
+    pub entity_cull_distance: f32,
+    // 群落覆盖图等地图的配色
+    pub biome_palette: BiomeMapPalette,
+    // 飞行的速度 双击跳跃切换飞行
+    pub fly_speed: f32,
+    pub fly_double_tap: bool,
+    // 自动跳跃 遇到一格高的台阶自动跳上去
+    pub auto_jump: bool,
+}
+
+impl Default for GraphicsSettings {
+    fn default() -> Self {
+        Self {
+            shadows_enabled: true,
+            shadow_quality: ShadowQuality::Medium,
+            brightness: 1.0,
+            gamma: 1.0,
+            msaa: MsaaLevel::X4,
+            render_scale: 1.0,
+            ui_scale: 1.0,
+            limit_unfocused: true,
+            unfocused_fps: 10,
+            pause_movement_unfocused: true,
+            release_cursor_unfocused: true,
+            toast_anchor: ToastAnchor::TopRight,
+            toast_duration: DEFAULT_TOAST_DURATION,
+            net_smoothing: NetSmoothing::Interpolate,
+            net_blend: 0.5,
+            mesh_uploads_per_frame: 3,
+            mesh_upload_budget_ms: 4.0,
+            decorations: true,
+            decoration_density: 1.0,
+            underwater_tint: [0.05, 0.25, 0.6],
+            underwater_tint_strength: 0.35,
+            input_send_rate: 20,
+            scattering: ScatteringQuality::Low,
+            chunk_view_bias: 0.3,
+            break_particles: true,
+            break_particle_count: 12,
+            break_particle_lifetime: 0.6,
+            connection_warn_rtt: 250.0,
+            connection_warn_loss: 0.05,
+            target_mode: TargetMode::FirstBlock,
+            play_transitions: true,
+            play_transition_duration: 0.2,
+            menu_dim: true,
+            entity_cull_distance: 96.0,
+            biome_palette: BiomeMapPalette::Default,
+            fly_speed: 10.0,
+            fly_double_tap: true,
+            auto_jump: false,
+        }
+    }
+}
+
+impl GraphicsSettings {
+    // 没有文件时使用默认设置
+    pub fn load() -> Self {
+        match std::fs::File::open(CLIENT_SETTINGS_PATH) {
+            Ok(file) => ron::de::from_reader(file).unwrap_or_else(|err| {
+                println!("客户端设置读取失败: {}", err);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    // 亮度和伽马恢复默认值
+    pub fn reset_brightness(&mut self) {
+        let default = Self::default();
+        self.brightness = default.brightness;
+        self.gamma = default.gamma;
+    }
+
+    pub fn fly_speed(&self) -> f32 {
+        self.fly_speed
+            .clamp(*FLY_SPEED_RANGE.start(), *FLY_SPEED_RANGE.end())
+    }
+
+    pub fn reset_ui_scale(&mut self) {
+        self.ui_scale = Self::default().ui_scale;
+    }
+
+    // 界面缩放的值 超出范围时限制在范围内
+    pub fn ui_scale(&self) -> f32 {
+        self.ui_scale
+            .clamp(*UI_SCALE_RANGE.start(), *UI_SCALE_RANGE.end())
+    }
+
+    pub fn save(&self) {
+        let res = ron::to_string(self).unwrap();
+        match std::fs::File::create(CLIENT_SETTINGS_PATH) {
+            Ok(mut file) => {
+                if let Err(err) = file.write_all(res.as_bytes()) {
+                    println!("客户端设置保存失败: {}", err);
+                }
+            }
+            Err(err) => println!("客户端设置保存失败: {}", err),
+        }
+    }
+}
+
+pub struct ClientSettingsPlugin;
+
+impl Plugin for ClientSettingsPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.insert_resource(GraphicsSettings::load());
+        app.add_systems(
+            Update,
+            (
+                save_settings,
+                apply_color_grading,
+                apply_unfocused_mode,
+                apply_msaa,
+                apply_ui_scale,
+            ),
+        );
+    }
+}
+
+// 修改后立即保存
+fn save_settings(settings: Res<GraphicsSettings>) {
+    if settings.is_changed() && !settings.is_added() {
+        settings.save();
+    }
+}
+
+/**
+ * 亮度和伽马作为最后画面的后处理 通过相机的 ColorGrading 实现
+ * 不修改环境光 所以昼夜变化照常生效 只是整体变亮或者变暗
+ */
+fn apply_color_grading(
+    settings: Res<GraphicsSettings>,
+    mut query: Query<(&mut ColorGrading, Ref<CameraTag>)>,
+) {
+    for (mut color_grading, camera) in query.iter_mut() {
+        if settings.is_changed() || camera.is_added() {
+            let brightness = settings
+                .brightness
+                .clamp(*BRIGHTNESS_RANGE.start(), *BRIGHTNESS_RANGE.end());
+            // exposure 的单位是档位 每增加 1 亮度翻倍
+            color_grading.exposure = brightness.log2();
+            color_grading.gamma = settings
+                .gamma
+                .clamp(*GAMMA_RANGE.start(), *GAMMA_RANGE.end());
+        }
+    }
+}
+
+// 失去焦点时降低更新频率 节省电量和显卡
+fn apply_unfocused_mode(settings: Res<GraphicsSettings>, mut winit: ResMut<WinitSettings>) {
+    if !settings.is_changed() {
+        return;
+    }
+    winit.unfocused_mode = if settings.limit_unfocused {
+        let fps = settings
+            .unfocused_fps
+            .clamp(*UNFOCUSED_FPS_RANGE.start(), *UNFOCUSED_FPS_RANGE.end());
+        // 等待的时间到了仍然会更新一帧 renet 的心跳不会中断
+        UpdateMode::ReactiveLowPower {
+            max_wait: Duration::from_secs_f64(1.0 / fps as f64),
+        }
+    } else {
+        UpdateMode::Continuous
+    };
+}
+
+fn apply_msaa(settings: Res<GraphicsSettings>, mut msaa: ResMut<Msaa>) {
+    if settings.is_changed() && *msaa != settings.msaa.msaa() {
+        *msaa = settings.msaa.msaa();
+    }
+}
+
+/**
+ * 所有的 egui 界面共用 EguiSettings 的缩放
+ * 最后的 pixels_per_point 是窗口的缩放乘以这个值
+ */
+fn apply_ui_scale(settings: Res<GraphicsSettings>, mut egui_settings: ResMut<EguiSettings>) {
+    let scale = settings.ui_scale() as f64;
+    if settings.is_changed() && egui_settings.scale_factor != scale {
+        egui_settings.scale_factor = scale;
+    }
+}
@@ -1,12 +1,12 @@
-use std::{marker::PhantomData, time::Duration};
+use std::marker::PhantomData;
 
 use bevy::{
     app::AppExit,
     input::mouse::MouseWheel,
     prelude::{
-        in_state, AmbientLight, Commands, DespawnRecursiveExt, Entity, EventReader, EventWriter,
-        Input, IntoSystemConfigs, KeyCode, Local, NextState, OnEnter, OnExit, Plugin, Query, Res,
-        ResMut, Resource, State, States, Update, Vec2, With,
+        in_state, warn, AmbientLight, Commands, DespawnRecursiveExt, Entity, EventReader,
+        EventWriter, Input, IntoSystemConfigs, KeyCode, Local, NextState, OnEnter, OnExit, Plugin,
+        Query, Res, ResMut, State, States, Update, With,
     },
     window::{CursorGrabMode, PrimaryWindow, Window, WindowCloseRequested},
 };
@@ -20,12 +20,30 @@ use renet_visualizer::{RenetClientVisualizer, RenetVisualizerStyle};
 
 use crate::{
     client::{
+        achievement::AchievementPlugin,
+        block_particles::BlockParticlePlugin,
+        chat::{chat_input_closed, ChatPlugin},
         client_sync_players, client_sync_players_state,
+        connection_quality::ConnectionQualityPlugin,
         console_commands::ConsoleCommandPlugins,
+        debug::{
+            BiomeOverlayPlugin, ChunkBorderPlugin, CopyPositionPlugin, DebugHudPlugin,
+            FlatColorPlugin, FreezeChunksPlugin, MeasurePlugin, MeshWireframePlugin,
+            TargetReadoutPlugin,
+        },
+        decoration::DecorationPlugin,
+        entity_culling::EntityCullingPlugin,
+        falling_block::ClientFallingBlockPlugin,
         filled_object::{setdown_filled_object, ClientFilledObjectnPlugin},
+        input_record::InputRecordPlugin,
         mesh_display::{mesh_chunk_map_setdown, ClientMeshPlugin},
+        mob::ClientMobPlugin,
+        net_smoothing::NetSmoothingPlugin,
+        play_transition::PlayTransitionPlugin,
         player::{
-            controller::{CharacterController, CharacterControllerPlugin, ControllerFlag},
+            controller::{
+                cursor_state, CharacterController, CharacterControllerPlugin, ControllerFlag,
+            },
             mouse_control::MouseControlPlugin,
             throw_system::deal_with_throw,
             ClientLobby,
@@ -38,18 +56,22 @@ use crate::{
             tool_bar::{tool_bar, ToolBar},
             UiPicResourceManager,
         },
+        underwater::UnderwaterPlugin,
+    },
+    common::{
+        net_error::{NetError, NetErrorEvent},
+        ClientClipSpheresPlugin,
     },
-    common::ClientClipSpheresPlugin,
-    sky::ClientSkyPlugins,
+    server::{
+        message_def::server_messages::ServerDisconnectReason, permission::PermissionLevel,
+        tick_rate::ServerTickRate,
+    },
+    sky::{ClientSkyPlugins, AMBIENT_BRIGHTNESS},
+    voxel_world::map_database::WorldSeed,
 };
 
 use super::{new_renet_client, notification::Notification, ConnectionAddr, GameState};
 
-#[derive(Default, Resource)]
-pub struct TextEditDemo {
-    pub input: String,
-}
-
 #[derive(Clone, Copy, Default, Eq, PartialEq, Debug, Hash, States)]
 pub enum PlayState {
     Main,
@@ -65,16 +87,20 @@ pub struct GamePlugin;
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
         app.add_state::<PlayState>();
+        app.add_event::<NetErrorEvent>();
         app.add_systems(OnEnter(GameState::Game), setup);
 
         // app.insert_resource();
-        app.insert_resource(TextEditDemo::default());
         app.insert_resource(RenetClientVisualizer::<200>::new(
             RenetVisualizerStyle::default(),
         ));
         app.add_systems(
             Update,
-            (toggle_play_staff_rules, update_visulizer_system).run_if(in_state(GameState::Game)),
+            (
+                toggle_play_staff_rules.run_if(chat_input_closed),
+                update_visulizer_system,
+            )
+                .run_if(in_state(GameState::Game)),
         );
         app.add_systems(
             Update,
@@ -85,8 +111,7 @@ impl Plugin for GamePlugin {
             (
                 egui_center_cursor_system,
                 mian_ui,
-                controller_tool_bar,
-                chat_window,
+                controller_tool_bar.run_if(chat_input_closed),
             )
                 .run_if(in_state(PlayState::Main))
                 .after(EguiSet::InitContexts),
@@ -103,20 +128,43 @@ impl Plugin for GamePlugin {
             ClientFilledObjectnPlugin,
             ToolBarSyncPlugin,
             SpMeshManagerPlugin,
+            ChunkBorderPlugin,
+            MeshWireframePlugin,
+            BiomeOverlayPlugin,
+            CopyPositionPlugin,
+            ChatPlugin,
+        ));
+        app.add_plugins((
+            ClientMobPlugin,
+            NetSmoothingPlugin,
+            AchievementPlugin,
+            DecorationPlugin,
+            UnderwaterPlugin,
+            InputRecordPlugin,
+            ClientFallingBlockPlugin,
+            MeasurePlugin,
+            FreezeChunksPlugin,
+            FlatColorPlugin,
+            TargetReadoutPlugin,
+            BlockParticlePlugin,
+            ConnectionQualityPlugin,
+            PlayTransitionPlugin,
+            EntityCullingPlugin,
         ));
+        app.add_plugins(DebugHudPlugin);
 
         app.add_systems(
             Update,
             (
                 client_sync_players,
                 client_sync_players_state,
-                panic_on_error_system,
                 deal_with_throw,
             )
                 .chain()
                 .run_if(bevy_renet::transport::client_connected())
                 .run_if(in_state(GameState::Game)),
         );
+        app.add_systems(Update, handle_net_errors.run_if(in_state(GameState::Game)));
         app.add_systems(
             Update,
             disconnect_on_close
@@ -142,19 +190,32 @@ fn setdown(mut commands: Commands, mut client_lobby: ResMut<ClientLobby>) {
     }
     // 清空数据
     *client_lobby.as_mut() = ClientLobby::default();
+    commands.remove_resource::<WorldSeed>();
+    commands.remove_resource::<ServerTickRate>();
+    commands.remove_resource::<PermissionLevel>();
 }
 
 fn setup(
     mut commands: Commands,
     connection_addr: Res<ConnectionAddr>,
     mut play_state: ResMut<NextState<PlayState>>,
+    mut game_state: ResMut<NextState<GameState>>,
+    mut notification: ResMut<Notification>,
     mut flags: ResMut<ControllerFlag>,
 ) {
-    let (client, transport) = new_renet_client(connection_addr.clone());
+    let (client, transport) = match new_renet_client(connection_addr.clone()) {
+        Ok(client) => client,
+        Err(err) => {
+            warn!("无法连接服务器: {}", err);
+            notification.error(err.to_string());
+            game_state.set(GameState::Menu);
+            return;
+        }
+    };
     commands.insert_resource(client);
     commands.insert_resource(transport);
     commands.insert_resource(AmbientLight {
-        brightness: 1.06,
+        brightness: AMBIENT_BRIGHTNESS,
         ..Default::default()
     });
     commands.insert_resource(ClientLobby::default());
@@ -163,7 +224,16 @@ fn setup(
     flags.flag = true;
 }
 
+/**
+ * 不同游戏状态下的光标 返回 (锁定模式, 是否显示)
+ * 只有在主界面中锁定光标 其他界面需要使用鼠标
+ */
+pub fn play_state_cursor(state: PlayState) -> (CursorGrabMode, bool) {
+    cursor_state(state == PlayState::Main)
+}
+
 // 切换合成公式
+// 光标只由切换后的状态决定 不读取当前的光标 连续快速切换也不会错乱
 fn toggle_play_staff_rules(
     state: Res<State<PlayState>>,
     mut play_state: ResMut<NextState<PlayState>>,
@@ -171,33 +241,19 @@ fn toggle_play_staff_rules(
     mut primary_window: Query<&mut Window, With<PrimaryWindow>>,
     mut flags: ResMut<ControllerFlag>,
 ) {
+    if !keyboard_input.just_pressed(KeyCode::E) {
+        return;
+    }
+    let target = match state.get() {
+        PlayState::StaffRules => PlayState::Main,
+        _ => PlayState::StaffRules,
+    };
+    flags.flag = target == PlayState::Main;
+    play_state.set(target);
     if let Ok(mut window) = primary_window.get_single_mut() {
-        if keyboard_input.just_pressed(KeyCode::E) {
-            match state.get() {
-                PlayState::StaffRules => {
-                    flags.flag = true;
-                    play_state.set(PlayState::Main);
-                    match window.cursor.grab_mode {
-                        CursorGrabMode::None => {
-                            window.cursor.grab_mode = CursorGrabMode::Confined;
-                            window.cursor.visible = false;
-                        }
-                        _ => {}
-                    }
-                }
-                _ => {
-                    flags.flag = false;
-                    play_state.set(PlayState::StaffRules);
-                    match window.cursor.grab_mode {
-                        CursorGrabMode::None => {}
-                        _ => {
-                            window.cursor.grab_mode = CursorGrabMode::None;
-                            window.cursor.visible = true;
-                        }
-                    }
-                }
-            }
-        }
+        let (grab_mode, visible) = play_state_cursor(target);
+        window.cursor.grab_mode = grab_mode;
+        window.cursor.visible = visible;
     }
 }
 
@@ -217,46 +273,102 @@ fn update_visulizer_system(
     }
 }
 
-// If any error is found we just panic
-fn panic_on_error_system(mut renet_error: EventReader<NetcodeTransportError>) {
-    for e in renet_error.iter() {
-        panic!("{}", e);
+/**
+ * 可以恢复的错误(超时 传输错误)只提示 断开连接时由 client_do_disconnected 回到菜单
+ * 不能恢复的错误(消息无法解析 协议不一致)继续下去只会和服务端不同步 主动断开并回到菜单
+ */
+fn handle_net_errors(
+    mut renet_error: EventReader<NetcodeTransportError>,
+    mut net_errors: EventReader<NetErrorEvent>,
+    client: Option<ResMut<RenetClient>>,
+    mut notification: ResMut<Notification>,
+    mut play_state: ResMut<NextState<PlayState>>,
+    mut game_state: ResMut<NextState<GameState>>,
+) {
+    let errors = renet_error
+        .iter()
+        .map(NetError::from)
+        .chain(net_errors.iter().map(|event| event.0.clone()));
+    let mut fatal = None;
+    for err in errors {
+        warn!("网络错误: {}", err);
+        if err.is_recoverable() {
+            notification.warning(err.to_string());
+        } else if fatal.is_none() {
+            fatal = Some(err);
+        }
+    }
+    let Some(err) = fatal else {
+        return;
+    };
+    if let Some(mut client) = client {
+        client.disconnect();
     }
+    notification.error(err.to_string());
+    play_state.set(PlayState::Disabled);
+    game_state.set(GameState::Menu);
 }
 
 fn client_do_disconnected(
+    mut commands: Commands,
     localize: Res<Localize>,
     client: Res<RenetClient>,
+    disconnect_reason: Option<Res<ServerDisconnectReason>>,
     mut play_state: ResMut<NextState<PlayState>>,
     mut game_state: ResMut<NextState<GameState>>,
     // mut menu_state: ResMut<NextState<MenuState>>,
     mut notification: ResMut<Notification>,
 ) {
-    let mut message = "连接异常";
+    let mut message = String::from(localize.get("连接异常"));
     if let Some(bevy_renet::renet::DisconnectReason::DisconnectedByServer) =
         client.disconnect_reason()
     {
-        message = "用户名已经存在";
+        // 服务端断开前会先发送原因
+        message = match disconnect_reason.as_deref() {
+            Some(ServerDisconnectReason::ServerFull) => localize.get("服务器已满").into(),
+            Some(ServerDisconnectReason::Kicked(reason)) => {
+                with_reason(localize.get("你被踢出了服务器"), reason)
+            }
+            Some(ServerDisconnectReason::Banned(reason)) => {
+                with_reason(localize.get("你已被服务器封禁"), reason)
+            }
+            Some(ServerDisconnectReason::VersionMismatch { server, client }) => format!(
+                "{} ({})",
+                localize.get("版本不一致"),
+                NetError::ProtocolMismatch {
+                    server: *server,
+                    client: *client
+                }
+            ),
+            Some(ServerDisconnectReason::AdminNotLocal) => {
+                localize.get("管理员只能从本机登录").into()
+            }
+            Some(ServerDisconnectReason::UsernameTaken) | None => {
+                localize.get("用户名已存在").into()
+            }
+        };
     }
-    notification
-        .toasts
-        .error(localize.get(message))
-        .set_duration(Some(Duration::from_secs(5)));
+    commands.remove_resource::<ServerDisconnectReason>();
+    notification.error(message);
     play_state.set(PlayState::Disabled);
     game_state.set(GameState::Menu);
 }
 
+fn with_reason(message: &str, reason: &Option<String>) -> String {
+    match reason {
+        Some(reason) => format!("{}: {}", message, reason),
+        None => String::from(message),
+    }
+}
+
 // 中心十字
 
 // 添加中心十字
-pub fn egui_center_cursor_system(
-    mut contexts: EguiContexts,
-    window_qurey: Query<&mut Window, With<PrimaryWindow>>,
-) {
+pub fn egui_center_cursor_system(mut contexts: EguiContexts) {
     let ctx = contexts.ctx_mut();
 
-    let Ok(window) = window_qurey.get_single() else{return;};
-    let size = Vec2::new(window.width(), window.height());
+    // 使用 egui 的坐标 界面缩放后仍然在屏幕中心
+    let size = ctx.screen_rect().size();
     // 透明的屏幕！
 
     egui::CentralPanel::default()
@@ -424,34 +536,24 @@ fn disconnect_on_close(
     }
 }
 
-fn chat_window(mut contexts: EguiContexts, mut input: ResMut<TextEditDemo>) {
-    let ctx = contexts.ctx_mut();
-    egui::Window::new("Chat")
-        .title_bar(false)
-        .vscroll(true)
-        .resizable(false)
-        .frame(egui::Frame::none().fill(egui::Color32::BLACK.gamma_multiply(0.8)))
-        .default_height(200.0)
-        .default_width(360.0)
-        .anchor(egui::Align2::LEFT_BOTTOM, [0.0, 0.0])
-        .collapsible(false)
-        .show(ctx, |ui| {
-            egui::CentralPanel::default().show_inside(ui, |ui| {
-                ui.horizontal(|ui| {
-                    ui.label("Player");
-                    ui.label("time");
-                    ui.colored_label(egui::Color32::RED, "text");
-                });
-            });
-
-            egui::TopBottomPanel::bottom("bottom").show_inside(ui, |ui| {
-                ui.horizontal(|ui| {
-                    ui.text_edit_singleline(&mut input.input);
-
-                    if ui.button("Send").clicked() {
-                        //todo
-                    };
-                });
-            })
-        });
+#[test]
+fn test_play_state_cursor() {
+    assert_eq!(
+        play_state_cursor(PlayState::Main),
+        (CursorGrabMode::Confined, false)
+    );
+    assert_eq!(
+        play_state_cursor(PlayState::StaffRules),
+        (CursorGrabMode::None, true)
+    );
+    // 锁定时一定隐藏 释放时一定显示
+    for state in [
+        PlayState::Main,
+        PlayState::StaffRules,
+        PlayState::State,
+        PlayState::Disabled,
+    ] {
+        let (grab_mode, visible) = play_state_cursor(state);
+        assert_eq!(grab_mode == CursorGrabMode::None, visible);
+    }
 }
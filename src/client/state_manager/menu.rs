@@ -8,13 +8,26 @@ use bevy::{
 };
 use bevy_easy_localize::Localize;
 use bevy_egui::{egui, EguiContext, EguiContexts, EguiUserTextures};
-use std::time::Duration;
 
-use super::{notification::Notification, ConnectionAddr, GameState};
+use super::{
+    notification::{Notification, ToastAnchor},
+    ConnectionAddr, GameState,
+};
 use super::{CHINESE, ENGLISH};
 use crate::{
     client::{
+        net_smoothing::NetSmoothing,
         player::controller::back_grab_cursor,
+        ray_cast::TargetMode,
+        settings::{
+            GraphicsSettings, MsaaLevel, ScatteringQuality, ShadowQuality, BREAK_PARTICLES_RANGE,
+            BREAK_PARTICLE_LIFETIME_RANGE, BRIGHTNESS_RANGE, CHUNK_VIEW_BIAS_RANGE,
+            CONNECTION_WARN_LOSS_RANGE, CONNECTION_WARN_RTT_RANGE, DECORATION_DENSITY_RANGE,
+            ENTITY_CULL_DISTANCE_RANGE, FLY_SPEED_RANGE, GAMMA_RANGE, INPUT_SEND_RATE_RANGE,
+            MESH_UPLOADS_RANGE, MESH_UPLOAD_BUDGET_RANGE, PLAY_TRANSITION_RANGE,
+            RENDER_SCALE_RANGE, TOAST_DURATION_RANGE, UI_SCALE_RANGE, UNDERWATER_TINT_RANGE,
+            UNFOCUSED_FPS_RANGE,
+        },
         ui::{
             test::toggle_ui,
             tool_bar::{tool_bar, ToolBar},
@@ -24,6 +37,7 @@ use crate::{
     },
     staff::StaffInfoStroge,
     tools::string::{is_port, is_valid_server_address},
+    voxel_world::biomes::BiomeMapPalette,
     CLIENT_DEBUG,
 };
 
@@ -64,6 +78,7 @@ fn menu_settings(
     mut localize: ResMut<Localize>,
     mut contexts: EguiContexts,
     mut menu_state: ResMut<NextState<MenuState>>,
+    mut settings: ResMut<GraphicsSettings>,
 ) {
     egui::CentralPanel::default().show(contexts.ctx_mut(), |ui| {
         ui.heading(localize.get("设置"));
@@ -73,6 +88,366 @@ fn menu_settings(
         if ui.button(localize.get("切换中文")).clicked() {
             localize.set_language(CHINESE);
         }
+        // 只在修改时写入 避免每帧触发保存
+        let mut shadows_enabled = settings.shadows_enabled;
+        if ui
+            .checkbox(&mut shadows_enabled, localize.get("阴影"))
+            .changed()
+        {
+            settings.shadows_enabled = shadows_enabled;
+        }
+        let mut shadow_quality = settings.shadow_quality;
+        ui.horizontal(|ui| {
+            ui.label(localize.get("阴影质量"));
+            for quality in ShadowQuality::ALL {
+                ui.selectable_value(&mut shadow_quality, quality, localize.get(quality.name()));
+            }
+        });
+        if shadow_quality != settings.shadow_quality {
+            settings.shadow_quality = shadow_quality;
+        }
+        let mut scattering = settings.scattering;
+        ui.horizontal(|ui| {
+            ui.label(localize.get("大气散射"));
+            for quality in ScatteringQuality::ALL {
+                ui.selectable_value(&mut scattering, quality, localize.get(quality.name()));
+            }
+        });
+        if scattering != settings.scattering {
+            settings.scattering = scattering;
+        }
+        let mut msaa = settings.msaa;
+        ui.horizontal(|ui| {
+            ui.label(localize.get("抗锯齿"));
+            for level in MsaaLevel::ALL {
+                ui.selectable_value(&mut msaa, level, localize.get(level.name()));
+            }
+        });
+        if msaa != settings.msaa {
+            settings.msaa = msaa;
+        }
+        let mut render_scale = settings.render_scale;
+        if ui
+            .add(
+                egui::Slider::new(&mut render_scale, RENDER_SCALE_RANGE)
+                    .step_by(0.05)
+                    .text(localize.get("渲染缩放")),
+            )
+            .changed()
+        {
+            settings.render_scale = render_scale;
+        }
+        let mut ui_scale = settings.ui_scale();
+        if ui
+            .add(
+                egui::Slider::new(&mut ui_scale, UI_SCALE_RANGE)
+                    .step_by(0.05)
+                    .text(localize.get("界面缩放")),
+            )
+            .changed()
+        {
+            settings.ui_scale = ui_scale;
+        }
+        if ui.button(localize.get("恢复默认界面缩放")).clicked() {
+            settings.reset_ui_scale();
+        }
+        let mut brightness = settings.brightness;
+        if ui
+            .add(egui::Slider::new(&mut brightness, BRIGHTNESS_RANGE).text(localize.get("亮度")))
+            .changed()
+        {
+            settings.brightness = brightness;
+        }
+        let mut gamma = settings.gamma;
+        if ui
+            .add(egui::Slider::new(&mut gamma, GAMMA_RANGE).text(localize.get("伽马")))
+            .changed()
+        {
+            settings.gamma = gamma;
+        }
+        if ui.button(localize.get("恢复默认亮度")).clicked() {
+            settings.reset_brightness();
+        }
+        let mut limit_unfocused = settings.limit_unfocused;
+        if ui
+            .checkbox(&mut limit_unfocused, localize.get("后台限制帧率"))
+            .changed()
+        {
+            settings.limit_unfocused = limit_unfocused;
+        }
+        let mut unfocused_fps = settings.unfocused_fps;
+        if ui
+            .add_enabled(
+                settings.limit_unfocused,
+                egui::Slider::new(&mut unfocused_fps, UNFOCUSED_FPS_RANGE)
+                    .text(localize.get("后台帧率")),
+            )
+            .changed()
+        {
+            settings.unfocused_fps = unfocused_fps;
+        }
+        let mut pause_movement = settings.pause_movement_unfocused;
+        if ui
+            .checkbox(&mut pause_movement, localize.get("后台停止移动"))
+            .changed()
+        {
+            settings.pause_movement_unfocused = pause_movement;
+        }
+        let mut release_cursor = settings.release_cursor_unfocused;
+        if ui
+            .checkbox(&mut release_cursor, localize.get("后台释放光标"))
+            .changed()
+        {
+            settings.release_cursor_unfocused = release_cursor;
+        }
+        let mut toast_anchor = settings.toast_anchor;
+        ui.horizontal(|ui| {
+            ui.label(localize.get("通知位置"));
+            for anchor in ToastAnchor::ALL {
+                ui.selectable_value(&mut toast_anchor, anchor, localize.get(anchor.name()));
+            }
+        });
+        if toast_anchor != settings.toast_anchor {
+            settings.toast_anchor = toast_anchor;
+        }
+        let mut toast_duration = settings.toast_duration;
+        if ui
+            .add(
+                egui::Slider::new(&mut toast_duration, TOAST_DURATION_RANGE)
+                    .text(localize.get("通知时长")),
+            )
+            .changed()
+        {
+            settings.toast_duration = toast_duration;
+        }
+        let mut net_smoothing = settings.net_smoothing;
+        ui.horizontal(|ui| {
+            ui.label(localize.get("位置平滑"));
+            for mode in NetSmoothing::ALL {
+                ui.selectable_value(&mut net_smoothing, mode, localize.get(mode.name()))
+                    .on_hover_text(localize.get(mode.tooltip()));
+            }
+        });
+        if net_smoothing != settings.net_smoothing {
+            settings.net_smoothing = net_smoothing;
+        }
+        let mut net_blend = settings.net_blend;
+        if ui
+            .add_enabled(
+                settings.net_smoothing == NetSmoothing::Blend,
+                egui::Slider::new(&mut net_blend, 0.0..=1.0).text(localize.get("外推比例")),
+            )
+            .changed()
+        {
+            settings.net_blend = net_blend;
+        }
+        let mut input_send_rate = settings.input_send_rate;
+        if ui
+            .add(
+                egui::Slider::new(&mut input_send_rate, INPUT_SEND_RATE_RANGE)
+                    .text(localize.get("发送频率")),
+            )
+            .changed()
+        {
+            settings.input_send_rate = input_send_rate;
+        }
+        let mut fly_speed = settings.fly_speed;
+        if ui
+            .add(egui::Slider::new(&mut fly_speed, FLY_SPEED_RANGE).text(localize.get("飞行速度")))
+            .changed()
+        {
+            settings.fly_speed = fly_speed;
+        }
+        let mut fly_double_tap = settings.fly_double_tap;
+        if ui
+            .checkbox(&mut fly_double_tap, localize.get("双击跳跃飞行"))
+            .changed()
+        {
+            settings.fly_double_tap = fly_double_tap;
+        }
+        let mut auto_jump = settings.auto_jump;
+        if ui
+            .checkbox(&mut auto_jump, localize.get("自动跳跃"))
+            .changed()
+        {
+            settings.auto_jump = auto_jump;
+        }
+        let mut connection_warn_rtt = settings.connection_warn_rtt;
+        if ui
+            .add(
+                egui::Slider::new(&mut connection_warn_rtt, CONNECTION_WARN_RTT_RANGE)
+                    .step_by(10.0)
+                    .text(localize.get("延迟警告")),
+            )
+            .changed()
+        {
+            settings.connection_warn_rtt = connection_warn_rtt;
+        }
+        let mut connection_warn_loss = settings.connection_warn_loss;
+        if ui
+            .add(
+                egui::Slider::new(&mut connection_warn_loss, CONNECTION_WARN_LOSS_RANGE)
+                    .step_by(0.01)
+                    .text(localize.get("丢包警告")),
+            )
+            .changed()
+        {
+            settings.connection_warn_loss = connection_warn_loss;
+        }
+        let mut target_mode = settings.target_mode;
+        ui.horizontal(|ui| {
+            ui.label(localize.get("瞄准方式"));
+            for mode in TargetMode::ALL {
+                ui.selectable_value(&mut target_mode, mode, localize.get(mode.name()));
+            }
+        });
+        if target_mode != settings.target_mode {
+            settings.target_mode = target_mode;
+        }
+        let mut chunk_view_bias = settings.chunk_view_bias;
+        if ui
+            .add(
+                egui::Slider::new(&mut chunk_view_bias, CHUNK_VIEW_BIAS_RANGE)
+                    .text(localize.get("视线优先加载")),
+            )
+            .changed()
+        {
+            settings.chunk_view_bias = chunk_view_bias;
+        }
+        let mut mesh_uploads = settings.mesh_uploads_per_frame;
+        if ui
+            .add(
+                egui::Slider::new(&mut mesh_uploads, MESH_UPLOADS_RANGE)
+                    .text(localize.get("每帧网格上传")),
+            )
+            .changed()
+        {
+            settings.mesh_uploads_per_frame = mesh_uploads;
+        }
+        let mut mesh_upload_budget = settings.mesh_upload_budget_ms;
+        if ui
+            .add(
+                egui::Slider::new(&mut mesh_upload_budget, MESH_UPLOAD_BUDGET_RANGE)
+                    .text(localize.get("网格上传时间")),
+            )
+            .changed()
+        {
+            settings.mesh_upload_budget_ms = mesh_upload_budget;
+        }
+        let mut decorations = settings.decorations;
+        if ui
+            .checkbox(&mut decorations, localize.get("地表装饰"))
+            .changed()
+        {
+            settings.decorations = decorations;
+        }
+        let mut decoration_density = settings.decoration_density;
+        if ui
+            .add_enabled(
+                settings.decorations,
+                egui::Slider::new(&mut decoration_density, DECORATION_DENSITY_RANGE)
+                    .step_by(0.1)
+                    .text(localize.get("装饰密度")),
+            )
+            .changed()
+        {
+            settings.decoration_density = decoration_density;
+        }
+        let mut break_particles = settings.break_particles;
+        if ui
+            .checkbox(&mut break_particles, localize.get("破坏碎片"))
+            .changed()
+        {
+            settings.break_particles = break_particles;
+        }
+        let mut break_particle_count = settings.break_particle_count;
+        if ui
+            .add_enabled(
+                settings.break_particles,
+                egui::Slider::new(&mut break_particle_count, BREAK_PARTICLES_RANGE)
+                    .text(localize.get("碎片数量")),
+            )
+            .changed()
+        {
+            settings.break_particle_count = break_particle_count;
+        }
+        let mut break_particle_lifetime = settings.break_particle_lifetime;
+        if ui
+            .add_enabled(
+                settings.break_particles,
+                egui::Slider::new(&mut break_particle_lifetime, BREAK_PARTICLE_LIFETIME_RANGE)
+                    .step_by(0.1)
+                    .text(localize.get("碎片时间")),
+            )
+            .changed()
+        {
+            settings.break_particle_lifetime = break_particle_lifetime;
+        }
+        let mut play_transitions = settings.play_transitions;
+        if ui
+            .checkbox(&mut play_transitions, localize.get("界面过渡"))
+            .changed()
+        {
+            settings.play_transitions = play_transitions;
+        }
+        let mut play_transition_duration = settings.play_transition_duration;
+        if ui
+            .add_enabled(
+                settings.play_transitions,
+                egui::Slider::new(&mut play_transition_duration, PLAY_TRANSITION_RANGE)
+                    .step_by(0.05)
+                    .text(localize.get("过渡时间")),
+            )
+            .changed()
+        {
+            settings.play_transition_duration = play_transition_duration;
+        }
+        let mut menu_dim = settings.menu_dim;
+        if ui
+            .checkbox(&mut menu_dim, localize.get("界面背景变暗"))
+            .changed()
+        {
+            settings.menu_dim = menu_dim;
+        }
+        let mut entity_cull_distance = settings.entity_cull_distance;
+        if ui
+            .add(
+                egui::Slider::new(&mut entity_cull_distance, ENTITY_CULL_DISTANCE_RANGE)
+                    .step_by(8.0)
+                    .text(localize.get("实体显示距离")),
+            )
+            .changed()
+        {
+            settings.entity_cull_distance = entity_cull_distance;
+        }
+        let mut biome_palette = settings.biome_palette;
+        ui.horizontal(|ui| {
+            ui.label(localize.get("群落地图配色"));
+            for palette in BiomeMapPalette::ALL {
+                ui.selectable_value(&mut biome_palette, palette, localize.get(palette.name()));
+            }
+        });
+        if biome_palette != settings.biome_palette {
+            settings.biome_palette = biome_palette;
+        }
+        let mut underwater_tint = settings.underwater_tint;
+        ui.horizontal(|ui| {
+            ui.label(localize.get("水下色调"));
+            ui.color_edit_button_rgb(&mut underwater_tint);
+        });
+        if underwater_tint != settings.underwater_tint {
+            settings.underwater_tint = underwater_tint;
+        }
+        let mut underwater_tint_strength = settings.underwater_tint_strength;
+        if ui
+            .add(
+                egui::Slider::new(&mut underwater_tint_strength, UNDERWATER_TINT_RANGE)
+                    .text(localize.get("水下色调强度")),
+            )
+            .changed()
+        {
+            settings.underwater_tint_strength = underwater_tint_strength;
+        }
         if ui.button(localize.get("返回")).clicked() {
             // 状态转移到 多人游戏的设置
             menu_state.set(MenuState::Main);
@@ -102,31 +477,16 @@ fn menu_multiplayer(
         if ui.button(localize.get("开始")).clicked() {
             // 这开始游戏相关数据
             if connection_addr.server.is_empty() {
-                notification
-                    .toasts
-                    .error("Server 不是为空")
-                    .set_duration(Some(Duration::from_secs(5)));
+                notification.error("Server 不是为空");
             } else if !is_valid_server_address(connection_addr.server.as_str()) {
                 // 判断数据是否合法
-                notification
-                    .toasts
-                    .error("Server 不是ip地址")
-                    .set_duration(Some(Duration::from_secs(5)));
+                notification.error("Server 不是ip地址");
             } else if !is_port(connection_addr.port.as_str()) {
-                notification
-                    .toasts
-                    .error("Port 不是数字")
-                    .set_duration(Some(Duration::from_secs(5)));
+                notification.error("Port 不是数字");
             } else if connection_addr.port.is_empty() {
-                notification
-                    .toasts
-                    .error("Port 为空")
-                    .set_duration(Some(Duration::from_secs(5)));
+                notification.error("Port 为空");
             } else {
-                notification
-                    .toasts
-                    .info(localize.get("进入服务器"))
-                    .set_duration(Some(Duration::from_secs(5)));
+                notification.info(localize.get("进入服务器"));
                 menu_state.set(MenuState::Disabled);
                 game_state.set(GameState::Game);
             }
@@ -208,7 +568,7 @@ fn test(
                 let mut num: usize = 999;
                 // test start
                 toggle_ui(ui, &mut test_resource.flag);
-                tool_box(ui, &mut test_resource.flag, &mut num, text_id, bod_id);
+                tool_box(ui, &mut test_resource.flag, &mut num, None, text_id, bod_id);
                 tool_bar(
                     ui,
                     &mut tool_bar_data,
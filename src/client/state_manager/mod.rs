@@ -8,7 +8,13 @@ use bevy_renet::renet::{
     RenetClient,
 };
 
-use crate::{connection_config, users::Username, PROTOCOL_ID};
+use crate::{
+    common::net_error::NetError,
+    connection_config,
+    tools::string::{is_port, is_valid_server_address},
+    users::{write_protocol_version, Username},
+    PROTOCOL_ID, PROTOCOL_VERSION,
+};
 
 pub mod game;
 pub mod menu;
@@ -55,6 +61,49 @@ impl Default for ConnectionAddr {
     }
 }
 
+impl ConnectionAddr {
+    /**
+     * 使用命令行的参数 addr 是 ip:端口 没有端口时使用默认端口
+     * 参数不合法时返回错误信息
+     */
+    pub fn from_args(addr: &str, nickname: Option<String>) -> Result<Self, String> {
+        let default = Self::default();
+        let (server, port) = match addr.rsplit_once(':') {
+            // ipv6 的地址需要用中括号和端口分开
+            Some((server, port)) if !server.contains(':') || server.ends_with(']') => {
+                (server, port)
+            }
+            _ => (addr, default.port.as_str()),
+        };
+        let server = server.trim_start_matches('[').trim_end_matches(']');
+        if !is_valid_server_address(server) {
+            return Err(format!("{} 不是ip地址", server));
+        }
+        if !is_port(port) {
+            return Err(format!("{} 不是端口", port));
+        }
+        let nickname = nickname.unwrap_or(default.nickname.clone());
+        if nickname.trim().is_empty() {
+            return Err(String::from("昵称为空"));
+        }
+        Ok(Self {
+            server: String::from(server),
+            port: String::from(port),
+            nickname,
+        })
+    }
+
+    // 只修改昵称 进入菜单后仍然可以修改
+    pub fn with_nickname(mut self, nickname: String) -> Self {
+        self.nickname = nickname;
+        self
+    }
+}
+
+// 启动参数中指定了服务器 跳过菜单直接连接
+#[derive(Debug, Resource)]
+pub struct QuickJoin;
+
 // Generic system that takes a component as a parameter, and will despawn all entities with that component
 pub fn despawn_screen<T: Component>(to_despawn: Query<Entity, With<T>>, mut commands: Commands) {
     for entity in &to_despawn {
@@ -62,29 +111,56 @@ pub fn despawn_screen<T: Component>(to_despawn: Query<Entity, With<T>>, mut comm
     }
 }
 
-// 创建连接
-pub fn new_renet_client(connection_addr: ConnectionAddr) -> (RenetClient, NetcodeClientTransport) {
+/**
+ * 创建连接 地址不正确或者无法创建 socket 时返回错误
+ */
+pub fn new_renet_client(
+    connection_addr: ConnectionAddr,
+) -> Result<(RenetClient, NetcodeClientTransport), NetError> {
     let client = RenetClient::new(connection_config());
     let addr = format!("{}:{}", connection_addr.server, connection_addr.port);
-    print!("客户端正在连接:{}", addr);
-    let server_addr = addr.parse().unwrap();
-    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    println!("客户端正在连接:{}", addr);
+    let server_addr = addr
+        .parse()
+        .map_err(|_| NetError::Transport(format!("invalid server address {}", addr)))?;
+    let socket = UdpSocket::bind("127.0.0.1:0")?;
     // 这里为了生成唯一的id
     let current_time = SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)
         .unwrap();
     let client_id = current_time.as_millis() as u64;
+    let mut user_data = Username(connection_addr.nickname).to_netcode_user_data();
+    write_protocol_version(&mut user_data, PROTOCOL_VERSION);
     let authentication = ClientAuthentication::Unsecure {
         client_id,
         protocol_id: PROTOCOL_ID,
         server_addr,
-        user_data: Some(Username(connection_addr.nickname).to_netcode_user_data()),
+        user_data: Some(user_data),
     };
 
-    let transport = NetcodeClientTransport::new(current_time, authentication, socket).unwrap();
+    let transport = NetcodeClientTransport::new(current_time, authentication, socket)?;
 
-    (client, transport)
+    Ok((client, transport))
 }
 
 pub const CHINESE: &str = "Chinese";
 pub const ENGLISH: &str = "English";
+
+#[test]
+fn test_connection_addr_from_args() {
+    let addr = ConnectionAddr::from_args("192.168.1.2:6000", Some(String::from("steve"))).unwrap();
+    assert_eq!(addr.server, "192.168.1.2");
+    assert_eq!(addr.port, "6000");
+    assert_eq!(addr.nickname, "steve");
+    // 没有端口时使用默认端口
+    let addr = ConnectionAddr::from_args("127.0.0.1", None).unwrap();
+    assert_eq!(addr, ConnectionAddr::default());
+    let addr = ConnectionAddr::from_args("[::1]:5001", None).unwrap();
+    assert_eq!((addr.server.as_str(), addr.port.as_str()), ("::1", "5001"));
+    let addr = ConnectionAddr::from_args("::1", None).unwrap();
+    assert_eq!((addr.server.as_str(), addr.port.as_str()), ("::1", "5000"));
+
+    assert!(ConnectionAddr::from_args("localhost:5000", None).is_err());
+    assert!(ConnectionAddr::from_args("127.0.0.1:abc", None).is_err());
+    assert!(ConnectionAddr::from_args("127.0.0.1", Some(String::from(" "))).is_err());
+}
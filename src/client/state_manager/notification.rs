@@ -1,10 +1,167 @@
-use bevy::prelude::{Plugin, ResMut, Resource, Update};
-use bevy_egui::EguiContexts;
-use egui_notify::Toasts;
+// 弹出通知
+// 显示的位置和时长在设置中配置 错误比普通消息显示得更久 点击通知可以关闭
 
-#[derive(Resource, Default)]
+use bevy::prelude::{DetectChanges, Plugin, Res, ResMut, Resource, Time, Update};
+use bevy_egui::{
+    egui::{self, Align2, Color32, Id, Order, RichText, Sense},
+    EguiContexts,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::client::settings::GraphicsSettings;
+
+// 默认的显示时长(秒)
+pub const DEFAULT_TOAST_DURATION: f32 = 3.0;
+// 不同等级相对默认时长的倍数
+const SUCCESS_SCALE: f32 = 1.0;
+const INFO_SCALE: f32 = 1.0;
+const WARNING_SCALE: f32 = 1.5;
+const ERROR_SCALE: f32 = 2.5;
+// 距离屏幕边缘和通知之间的间距
+const TOAST_MARGIN: f32 = 8.0;
+const TOAST_SPACING: f32 = 4.0;
+
+// 通知显示的角落
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ToastAnchor {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl ToastAnchor {
+    pub const ALL: [ToastAnchor; 4] = [
+        ToastAnchor::TopLeft,
+        ToastAnchor::TopRight,
+        ToastAnchor::BottomLeft,
+        ToastAnchor::BottomRight,
+    ];
+
+    pub fn align(&self) -> Align2 {
+        match self {
+            ToastAnchor::TopLeft => Align2::LEFT_TOP,
+            ToastAnchor::TopRight => Align2::RIGHT_TOP,
+            ToastAnchor::BottomLeft => Align2::LEFT_BOTTOM,
+            ToastAnchor::BottomRight => Align2::RIGHT_BOTTOM,
+        }
+    }
+
+    // 从角落出发的偏移 后面的通知沿着 y 方向排开
+    fn offset(&self, y: f32) -> egui::Vec2 {
+        match self {
+            ToastAnchor::TopLeft => egui::vec2(TOAST_MARGIN, TOAST_MARGIN + y),
+            ToastAnchor::TopRight => egui::vec2(-TOAST_MARGIN, TOAST_MARGIN + y),
+            ToastAnchor::BottomLeft => egui::vec2(TOAST_MARGIN, -TOAST_MARGIN - y),
+            ToastAnchor::BottomRight => egui::vec2(-TOAST_MARGIN, -TOAST_MARGIN - y),
+        }
+    }
+
+    // 翻译的关键字
+    pub fn name(&self) -> &'static str {
+        match self {
+            ToastAnchor::TopLeft => "左上",
+            ToastAnchor::TopRight => "右上",
+            ToastAnchor::BottomLeft => "左下",
+            ToastAnchor::BottomRight => "右下",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToastLevel {
+    Success,
+    Info,
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone)]
+pub struct Toast {
+    id: u64,
+    pub text: String,
+    pub level: ToastLevel,
+    // 剩余的显示时长(秒)
+    pub remaining: f32,
+    // 上一帧显示的高度 用来排开下一条通知
+    height: f32,
+}
+
+impl Toast {
+    pub fn set_duration(&mut self, duration: f32) -> &mut Self {
+        self.remaining = duration;
+        self
+    }
+}
+
+#[derive(Resource)]
 pub struct Notification {
-    pub toasts: Toasts,
+    // 最早的在前面
+    toasts: Vec<Toast>,
+    next_id: u64,
+    anchor: ToastAnchor,
+    // 默认的显示时长(秒)
+    duration: f32,
+}
+
+impl Default for Notification {
+    fn default() -> Self {
+        Self {
+            toasts: Vec::new(),
+            next_id: 0,
+            anchor: ToastAnchor::TopRight,
+            duration: DEFAULT_TOAST_DURATION,
+        }
+    }
+}
+
+impl Notification {
+    pub fn success(&mut self, text: impl Into<String>) -> &mut Toast {
+        self.push(text.into(), ToastLevel::Success, SUCCESS_SCALE)
+    }
+
+    pub fn info(&mut self, text: impl Into<String>) -> &mut Toast {
+        self.push(text.into(), ToastLevel::Info, INFO_SCALE)
+    }
+
+    pub fn warning(&mut self, text: impl Into<String>) -> &mut Toast {
+        self.push(text.into(), ToastLevel::Warning, WARNING_SCALE)
+    }
+
+    pub fn error(&mut self, text: impl Into<String>) -> &mut Toast {
+        self.push(text.into(), ToastLevel::Error, ERROR_SCALE)
+    }
+
+    fn push(&mut self, text: String, level: ToastLevel, scale: f32) -> &mut Toast {
+        self.next_id += 1;
+        self.toasts.push(Toast {
+            id: self.next_id,
+            text,
+            level,
+            remaining: self.duration * scale,
+            height: 0.0,
+        });
+        self.toasts.last_mut().unwrap()
+    }
+
+    pub fn toasts(&self) -> &[Toast] {
+        &self.toasts
+    }
+
+    // 点击关闭
+    pub fn dismiss(&mut self, index: usize) {
+        if index < self.toasts.len() {
+            self.toasts.remove(index);
+        }
+    }
+
+    // 扣掉经过的时间 去掉到时的通知
+    pub fn tick(&mut self, delta: f32) {
+        for toast in self.toasts.iter_mut() {
+            toast.remaining -= delta;
+        }
+        self.toasts.retain(|toast| toast.remaining > 0.0);
+    }
 }
 
 pub struct NotificationPlugin;
@@ -12,11 +169,72 @@ pub struct NotificationPlugin;
 impl Plugin for NotificationPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
         app.insert_resource(Notification::default());
-        app.add_systems(Update, update_notifications);
+        app.add_systems(Update, (apply_notification_settings, update_notifications));
     }
 }
 
-fn update_notifications(mut contexts: EguiContexts, mut notification: ResMut<Notification>) {
+// 设置修改后更新位置和时长 已经显示的通知保留
+fn apply_notification_settings(
+    settings: Res<GraphicsSettings>,
+    mut notification: ResMut<Notification>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    notification.duration = settings.toast_duration;
+    notification.anchor = settings.toast_anchor;
+}
+
+// 最新的通知靠近角落 点击通知的任意位置就关闭它
+fn update_notifications(
+    mut contexts: EguiContexts,
+    time: Res<Time>,
+    mut notification: ResMut<Notification>,
+) {
+    notification.tick(time.delta_seconds());
     let ctx = contexts.ctx_mut();
-    notification.toasts.show(ctx);
+    let anchor = notification.anchor;
+    let mut y = 0.0;
+    let mut clicked = None;
+    for (index, toast) in notification.toasts.iter_mut().enumerate().rev() {
+        let response = egui::Area::new(Id::new(("toast", toast.id)))
+            .order(Order::Foreground)
+            .anchor(anchor.align(), anchor.offset(y))
+            .interactable(true)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style())
+                    .show(ui, |ui| {
+                        let color = match toast.level {
+                            ToastLevel::Success => Color32::LIGHT_GREEN,
+                            ToastLevel::Info => ui.visuals().text_color(),
+                            ToastLevel::Warning => ui.visuals().warn_fg_color,
+                            ToastLevel::Error => ui.visuals().error_fg_color,
+                        };
+                        ui.label(RichText::new(&toast.text).color(color));
+                    })
+                    .response
+                    .interact(Sense::click())
+            });
+        toast.height = response.response.rect.height();
+        y += toast.height + TOAST_SPACING;
+        if response.inner.clicked() {
+            clicked = Some(index);
+        }
+    }
+    if let Some(index) = clicked {
+        notification.dismiss(index);
+    }
+}
+
+#[test]
+fn test_notification_tick_and_dismiss() {
+    let mut notification = Notification::default();
+    notification.info("a");
+    notification.error("b");
+    // 错误显示得更久
+    notification.tick(DEFAULT_TOAST_DURATION * INFO_SCALE + 0.1);
+    assert_eq!(notification.toasts().len(), 1);
+    assert_eq!(notification.toasts()[0].level, ToastLevel::Error);
+    notification.dismiss(0);
+    assert!(notification.toasts().is_empty());
 }
@@ -1,13 +1,13 @@
 use bevy::prelude::{
-    in_state, App, AssetServer, Input, IntoSystemConfigs, MouseButton, NextState, OnEnter, Plugin,
-    Res, ResMut, Update,
+    in_state, App, AssetServer, Commands, Input, IntoSystemConfigs, MouseButton, NextState,
+    OnEnter, Plugin, Res, ResMut, Update,
 };
 use bevy_egui::{
     egui::{self, FontDefinitions, FontId, RichText, TextStyle},
     EguiContexts,
 };
 
-use super::GameState;
+use super::{GameState, QuickJoin};
 pub struct SplashPlugin;
 
 impl Plugin for SplashPlugin {
@@ -101,11 +101,17 @@ fn show(mut contexts: EguiContexts, asset_server: Res<AssetServer>) {
     });
 }
 // Tick the timer, and change state when finished
+// 启动参数指定了服务器时直接进入游戏 只使用一次
 fn countdown(
+    mut commands: Commands,
     mut game_state: ResMut<NextState<GameState>>,
     mouse_button_input: Res<Input<MouseButton>>,
+    quick_join: Option<Res<QuickJoin>>,
 ) {
-    if mouse_button_input.just_pressed(MouseButton::Left) {
+    if quick_join.is_some() {
+        commands.remove_resource::<QuickJoin>();
+        game_state.set(GameState::Game);
+    } else if mouse_button_input.just_pressed(MouseButton::Left) {
         game_state.set(GameState::Menu);
     }
 }
@@ -1,12 +1,17 @@
-use bevy::prelude::{in_state, IntoSystemConfigs, Plugin, Res, ResMut, Update};
+use bevy::prelude::{in_state, warn, EventWriter, IntoSystemConfigs, Plugin, Res, ResMut, Update};
+use bevy_easy_localize::Localize;
 use bevy_renet::renet::RenetClient;
 
 use crate::{
+    common::net_error::{decode, NetErrorEvent},
     server::message_def::{tool_bar_message::ToolBarMessage, ServerChannel},
     staff::StaffInfoStroge,
 };
 
-use super::{state_manager::GameState, ui::tool_bar::ToolBar};
+use super::{
+    state_manager::{notification::Notification, GameState},
+    ui::tool_bar::ToolBar,
+};
 
 // 同步 toolbar信息 相关插件
 pub struct ToolBarSyncPlugin;
@@ -26,10 +31,20 @@ fn sync_toolbar_message(
     mut client: ResMut<RenetClient>,
     mut tool_bar_data: ResMut<ToolBar>,
     staff_infos: Res<StaffInfoStroge>,
+    localize: Res<Localize>,
+    mut notification: ResMut<Notification>,
+    mut net_errors: EventWriter<NetErrorEvent>,
 ) {
     let active = tool_bar_data.active_index.clone();
     while let Some(message) = client.receive_message(ServerChannel::ToolBarMessage) {
-        let tool_bar_message: ToolBarMessage = bincode::deserialize(&message).unwrap();
+        let tool_bar_message: ToolBarMessage = match decode(&message) {
+            Ok(message) => message,
+            Err(err) => {
+                warn!("{}", err);
+                net_errors.send(NetErrorEvent(err));
+                continue;
+            }
+        };
         match tool_bar_message {
             ToolBarMessage::SyncToolbar {
                 index,
@@ -45,6 +60,14 @@ fn sync_toolbar_message(
                     tool_bar_data.empty_staff(index);
                 }
             }
+            ToolBarMessage::ToolBroken { index, staff_id } => {
+                // 位置已经由 SyncToolbar 清空
+                let name = staff_infos
+                    .get(staff_id)
+                    .map_or(String::new(), |staff| staff.name);
+                notification.info(format!("{} {}", localize.get("工具损坏了"), name));
+                tool_bar_data.empty_staff(index);
+            }
         }
         //重新激活方块
         tool_bar_data.active(active);
@@ -12,7 +12,10 @@ use bevy_renet::renet::RenetClient;
 use egui_extras::{Column, Size, StripBuilder, TableBuilder};
 
 use crate::{
-    client::message_def::{staff_rule_message::StaffRuleMessage, ClientChannel},
+    client::{
+        message_def::{staff_rule_message::StaffRuleMessage, ClientChannel},
+        play_transition::PlayTransition,
+    },
     staff::{
         rule::{StaffRule, StaffRules},
         StaffInfoStroge,
@@ -24,6 +27,7 @@ use super::tool_bar::ToolBar;
 #[derive(Debug, Resource)]
 pub struct MyMemory(pub egui::Memory);
 
+#[allow(clippy::too_many_arguments)]
 pub fn staff_rules_ui(
     mut q: Query<
         (
@@ -41,6 +45,7 @@ pub fn staff_rules_ui(
     mut client: ResMut<RenetClient>,
     localize: Res<Localize>,
     mut memory: ResMut<MyMemory>,
+    transition: Res<PlayTransition>,
 ) {
     // 这里显示合成列表
     if let Ok((_, ctx, _)) = q.get_single_mut() {
@@ -52,7 +57,11 @@ pub fn staff_rules_ui(
             .collapsible(false)
             .title_bar(true)
             .scroll2([false, false])
-            .anchor(Align2::CENTER_CENTER, Vec2::ZERO);
+            // 打开时从下方滑入
+            .anchor(
+                Align2::CENTER_CENTER,
+                Vec2::new(0.0, transition.slide_offset()),
+            );
         windows.show(ctx, |ui| {
             ui.vertical(|ui| {
                 // TODO 先展示 物品栏和合计的物品个数？
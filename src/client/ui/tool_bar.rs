@@ -3,7 +3,7 @@ use bevy_egui::egui;
 
 use crate::{
     staff::{Staff, StaffType},
-    voxel_world::voxel::Voxel,
+    voxel_world::{voxel::Voxel, voxel_registry::Tool},
 };
 
 use super::tool_box::tool_box;
@@ -74,6 +74,14 @@ impl ToolBar {
         }
     }
 
+    // 破坏方块时手里的工具 没有拿工具时是空手
+    pub fn active_tool(&self) -> Tool {
+        self.tools[self.active_index]
+            .staff
+            .as_ref()
+            .map_or(Tool::HAND, |staff| staff.break_tool())
+    }
+
     // 当前激活中的物品
     pub fn staff_type(&self) -> Option<StaffType> {
         self.tools[self.active_index]
@@ -139,6 +147,10 @@ pub fn tool_bar(
                     ui,
                     &mut tool_box_data.active,
                     &mut tool_box_data.num,
+                    tool_box_data
+                        .staff
+                        .as_ref()
+                        .and_then(|staff| staff.durability),
                     if let Some(data) = tool_box_data.staff.clone() {
                         get_texture_egui(&data.icon.clone())
                     } else {
@@ -6,6 +6,8 @@ pub fn tool_box(
     ui: &mut egui::Ui,
     on: &mut bool,
     num: &mut usize,
+    // 工具的最大耐久 有耐久时显示耐久条 不显示数量
+    durability: Option<usize>,
     texture_id: Option<egui::TextureId>,
     tool_box_border: Option<egui::TextureId>,
 ) -> egui::Response {
@@ -62,16 +64,30 @@ pub fn tool_box(
                     Color32::WHITE,
                 );
             }
-            let mut pos = rect.max;
-            pos.x -= 32.;
-            pos.y -= 4.;
-            ui.painter().text(
-                pos,
-                Align2::CENTER_CENTER,
-                format!("x{}", num),
-                FontId::new(10.0, egui::FontFamily::Monospace),
-                Color32::WHITE,
-            );
+            if let Some(durability) = durability {
+                let rate = (*num as f32 / durability.max(1) as f32).clamp(0.0, 1.0);
+                let mut bar = rect.shrink2(egui::vec2(10.0, 0.0));
+                bar.set_top(rect.bottom() - 8.0);
+                bar.set_bottom(rect.bottom() - 5.0);
+                ui.painter().rect_filled(bar, 0.0, Color32::BLACK);
+                bar.set_right(bar.left() + bar.width() * rate);
+                ui.painter().rect_filled(
+                    bar,
+                    0.0,
+                    Color32::from_rgb(((1.0 - rate) * 255.0) as u8, (rate * 255.0) as u8, 0),
+                );
+            } else {
+                let mut pos = rect.max;
+                pos.x -= 32.;
+                pos.y -= 4.;
+                ui.painter().text(
+                    pos,
+                    Align2::CENTER_CENTER,
+                    format!("x{}", num),
+                    FontId::new(10.0, egui::FontFamily::Monospace),
+                    Color32::WHITE,
+                );
+            }
         }
     }
     if *on {
@@ -0,0 +1,117 @@
+// 水下效果
+// 相机所在的方块是液体时 画面覆盖一层色调 雾变近变成水的颜色 浮出水面后恢复
+// 现在还没有声音 以后的声音系统可以读取 Underwater 降低高频
+
+use bevy::{
+    pbr::{FogFalloff, FogSettings},
+    prelude::{
+        in_state, Color, DetectChanges, GlobalTransform, IntoSystemConfigs, OnExit, Plugin, Query,
+        Res, ResMut, Resource, Update, With,
+    },
+};
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{
+    sky::{biome_atmosphere, default_fog_falloff},
+    voxel_world::chunk_map::ChunkMap,
+};
+
+use super::{
+    player::controller::{properties_at, CameraTag},
+    settings::{GraphicsSettings, UNDERWATER_TINT_RANGE},
+    state_manager::GameState,
+};
+
+// 水下能看到的距离
+pub const UNDERWATER_FOG_START: f32 = 2.0;
+pub const UNDERWATER_FOG_END: f32 = 24.0;
+
+// 相机是否在水中
+#[derive(Debug, Default, Resource)]
+pub struct Underwater(pub bool);
+
+pub struct UnderwaterPlugin;
+
+impl Plugin for UnderwaterPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.init_resource::<Underwater>();
+        app.add_systems(
+            Update,
+            (
+                detect_underwater,
+                underwater_fog.after(biome_atmosphere),
+                underwater_tint,
+            )
+                .chain()
+                .run_if(in_state(GameState::Game)),
+        );
+        app.add_systems(OnExit(GameState::Game), setdown_underwater);
+    }
+}
+
+fn detect_underwater(
+    chunk_map: Res<ChunkMap>,
+    camera: Query<&GlobalTransform, With<CameraTag>>,
+    mut underwater: ResMut<Underwater>,
+) {
+    let Ok(camera) = camera.get_single() else {
+        return;
+    };
+    let submerged = properties_at(&chunk_map, camera.translation()).is_liquid;
+    // 没有变化时不触发 is_changed
+    if underwater.0 != submerged {
+        underwater.0 = submerged;
+    }
+}
+
+fn tint_color(settings: &GraphicsSettings) -> Color {
+    let [r, g, b] = settings.underwater_tint;
+    let strength = settings
+        .underwater_tint_strength
+        .clamp(*UNDERWATER_TINT_RANGE.start(), *UNDERWATER_TINT_RANGE.end());
+    Color::rgba(r, g, b, strength)
+}
+
+/**
+ * 水下的雾 群落的雾每帧向群落的颜色过渡 这里在它之后覆盖
+ * 浮出水面时恢复距离 颜色由群落的雾慢慢过渡回去
+ */
+fn underwater_fog(
+    underwater: Res<Underwater>,
+    settings: Res<GraphicsSettings>,
+    mut fog_query: Query<&mut FogSettings, With<CameraTag>>,
+) {
+    for mut fog in fog_query.iter_mut() {
+        if underwater.0 {
+            fog.color = tint_color(&settings).with_a(1.0);
+            fog.falloff = FogFalloff::Linear {
+                start: UNDERWATER_FOG_START,
+                end: UNDERWATER_FOG_END,
+            };
+        } else if underwater.is_changed() {
+            fog.falloff = default_fog_falloff();
+        }
+    }
+}
+
+// 覆盖整个画面的色调 画在界面的最底层
+fn underwater_tint(
+    mut contexts: EguiContexts,
+    underwater: Res<Underwater>,
+    settings: Res<GraphicsSettings>,
+) {
+    if !underwater.0 {
+        return;
+    }
+    let [r, g, b, a] = tint_color(&settings).as_rgba_f32();
+    let ctx = contexts.ctx_mut();
+    ctx.layer_painter(egui::LayerId::background()).rect_filled(
+        ctx.screen_rect(),
+        0.0,
+        egui::Rgba::from_rgba_unmultiplied(r, g, b, a),
+    );
+}
+
+fn setdown_underwater(mut underwater: ResMut<Underwater>) {
+    underwater.0 = false;
+}
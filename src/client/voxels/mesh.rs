@@ -5,21 +5,105 @@ use bevy::{
         render_resource::PrimitiveTopology,
     },
 };
-use block_mesh::{greedy_quads, GreedyQuadsBuffer, RIGHT_HANDED_Y_UP_CONFIG};
+use block_mesh::{
+    greedy_quads, GreedyQuadsBuffer, MergeVoxel, Voxel as MeshVoxel, VoxelVisibility,
+    RIGHT_HANDED_Y_UP_CONFIG,
+};
 use ndshape::{ConstShape, ConstShape3u32, Shape};
 
 use crate::{
     client::voxels::mesh_material::ATTRIBUTE_DATA,
-    voxel_world::voxel::{Voxel, VoxelDirection, VoxelMaterial, Water},
-    CHUNK_SIZE, CHUNK_SIZE_ADD_2_U32,
+    voxel_world::{
+        light::{column_light, max_light, pack_light, LightSeed, MAX_LIGHT},
+        voxel::{Voxel, VoxelDirection, VoxelMaterial, Water},
+    },
+    ChunkColumnShape, CHUNK_SIZE, CHUNK_SIZE_ADD_2_U32,
 };
 
 use super::voxel_materail_config::MaterailConfiguration;
 
+// 和 RIGHT_HANDED_Y_UP_CONFIG.faces 的顺序一致
+const FACE_OFFSETS: [[i32; 3]; 6] = [
+    [-1, 0, 0],
+    [0, -1, 0],
+    [0, 0, -1],
+    [1, 0, 0],
+    [0, 1, 0],
+    [0, 0, 1],
+];
+
+/**
+ * 带有每个面光照的体素
+ * 光照不同的面不会被贪心合并到一起
+ */
+#[derive(Debug, Clone, Copy)]
+struct LitVoxel {
+    voxel: Voxel,
+    face_light: [u8; 6],
+}
+
+impl MeshVoxel for LitVoxel {
+    fn get_visibility(&self) -> VoxelVisibility {
+        self.voxel.get_visibility()
+    }
+}
+
+impl MergeVoxel for LitVoxel {
+    type MergeValue = (u8, [u8; 6]);
+
+    fn merge_value(&self) -> Self::MergeValue {
+        (self.voxel.merge_value(), self.face_light)
+    }
+}
+
+// 面的光照是面朝向的体素的光照 光源自己的面使用光源的亮度
+// 没有光照数据时按照露天处理
+fn lit_voxels<S>(voxels: &[Voxel], light: &[u8], voxels_shape: &S) -> Vec<LitVoxel>
+where
+    S: Shape<3, Coord = u32>,
+{
+    let size = voxels_shape.as_array();
+    voxels
+        .iter()
+        .enumerate()
+        .map(|(index, voxel)| {
+            let mut face_light = [pack_light(MAX_LIGHT, 0); 6];
+            if !light.is_empty() {
+                let pos = voxels_shape.delinearize(index as u32);
+                for (face, offset) in FACE_OFFSETS.iter().enumerate() {
+                    let mut next = [0; 3];
+                    let mut inside = true;
+                    for axis in 0..3 {
+                        let v = pos[axis] as i32 + offset[axis];
+                        inside &= v >= 0 && v < size[axis] as i32;
+                        next[axis] = v.max(0) as u32;
+                    }
+                    let around = if inside {
+                        light[voxels_shape.linearize(next) as usize]
+                    } else {
+                        0
+                    };
+                    face_light[face] = max_light(around, light[index]);
+                }
+            }
+            LitVoxel {
+                voxel: *voxel,
+                face_light,
+            }
+        })
+        .collect()
+}
+
+/**
+ * light 是每个体素的光照 见 column_light 为空时不计算光照
+ * 顶点数据: 贴图索引[0-7] 法向量[8-10] 方块光照[12-15] 天空光照[16-19]
+ */
 pub fn gen_mesh_volex<S>(
     voxels: Vec<Voxel>,
+    light: &[u8],
     material_config: MaterailConfiguration,
     voxels_shape: &S,
+    min: [u32; 3],
     max: [u32; 3],
     mut deal_vec: impl FnMut(Vec<[f32; 3]>) -> Vec<[f32; 3]>,
 ) -> Option<Mesh>
@@ -28,7 +112,8 @@ where
 {
     let mut buffer = GreedyQuadsBuffer::new(S::SIZE as usize);
     let faces: [block_mesh::OrientedBlockFace; 6] = RIGHT_HANDED_Y_UP_CONFIG.faces;
-    greedy_quads(&voxels, voxels_shape, [0; 3], max, &faces, &mut buffer);
+    let lit = lit_voxels(&voxels, light, voxels_shape);
+    greedy_quads(&lit, voxels_shape, min, max, &faces, &mut buffer);
     let num_indices = buffer.quads.num_quads() * 6;
     let num_vertices = buffer.quads.num_quads() * 4;
     if num_indices == 0 {
@@ -109,8 +194,12 @@ where
                 voxels[index as usize].direction.clone(),
             );
 
+            // 方块光照
+            let light_num =
+                (lit[index as usize].face_light[block_face_normal_index] as u32) << 12u32;
+
             //  这里后面要知道是那个面的方便渲染
-            data.extend_from_slice(&[normol_num | (txt_index); 4]);
+            data.extend_from_slice(&[light_num | normol_num | (txt_index); 4]);
         }
     }
 
@@ -139,19 +228,62 @@ pub fn gen_one_volex_mesh(voxel: Voxel, material_config: MaterailConfiguration)
             }
         }
     }
-    return gen_mesh_volex::<Tmp>(voxels, material_config, &Tmp {}, [2, 2, 2], |list| {
-        list.iter()
-            .map(|a| [a[0] - 1.5, a[1] - 1.5, a[2] - 1.5])
-            .collect()
-    });
-}
-
-pub fn gen_mesh(voxels: Vec<Voxel>, material_config: MaterailConfiguration) -> Option<Mesh> {
-    type Tmp = ConstShape3u32<CHUNK_SIZE_ADD_2_U32, 256, CHUNK_SIZE_ADD_2_U32>;
     return gen_mesh_volex::<Tmp>(
         voxels,
+        &[],
         material_config,
         &Tmp {},
+        [0; 3],
+        [2, 2, 2],
+        |list| {
+            list.iter()
+                .map(|a| [a[0] - 1.5, a[1] - 1.5, a[2] - 1.5])
+                .collect()
+        },
+    );
+}
+
+type ColumnShape = ChunkColumnShape;
+
+/**
+ * 从底部开始 完全被实心方块填满的层(包括相邻区块的边缘)里不可能有可见的面
+ * 返回网格生成时可以使用的最小y 最后一层实心层作为边界保留
+ * 地下大片的石头不再参与贪心合并的遍历
+ */
+pub fn occluded_min_y(voxels: &Vec<Voxel>) -> u32 {
+    let layer = CHUNK_SIZE_ADD_2_U32;
+    let mut solid_layers = 0;
+    'layer: for y in 0..256 {
+        for x in 0..layer {
+            for z in 0..layer {
+                let index = ColumnShape::linearize([x, y, z]) as usize;
+                if voxels[index].get_visibility() != VoxelVisibility::Opaque {
+                    break 'layer;
+                }
+            }
+        }
+        solid_layers += 1;
+    }
+    // 至少要留一层给贪心合并 全部实心时也不会生成面
+    (solid_layers as u32).saturating_sub(1).min(254)
+}
+
+/**
+ * seeds 是相邻区块列中的光源 见 ChunkMap::light_seeds_near
+ */
+pub fn gen_mesh(
+    voxels: Vec<Voxel>,
+    seeds: &[LightSeed],
+    material_config: MaterailConfiguration,
+) -> Option<Mesh> {
+    let min_y = occluded_min_y(&voxels);
+    let light = column_light(&voxels, seeds);
+    return gen_mesh_volex::<ColumnShape>(
+        voxels,
+        &light,
+        material_config,
+        &ColumnShape {},
+        [0, min_y, 0],
         [(CHUNK_SIZE + 1) as u32, 255, (CHUNK_SIZE + 1) as u32],
         |a| a,
     );
@@ -226,7 +358,7 @@ pub fn gen_mesh_water(voxels: Vec<Voxel>, material_config: MaterailConfiguration
 pub fn pick_water(voxels: Vec<Voxel>) -> Vec<Voxel> {
     let mut ret = Vec::new();
     for v in voxels {
-        if v.id == Water::ID {
+        if v.is_liquid() {
             ret.push(Voxel::FILLED);
         } else {
             ret.push(Voxel::EMPTY);
@@ -234,3 +366,105 @@ pub fn pick_water(voxels: Vec<Voxel>) -> Vec<Voxel> {
     }
     ret
 }
+
+#[cfg(test)]
+fn count_quads(voxels: &Vec<Voxel>, min_y: u32) -> usize {
+    let mut buffer = GreedyQuadsBuffer::new(ColumnShape::SIZE as usize);
+    greedy_quads(
+        voxels,
+        &ColumnShape {},
+        [0, min_y, 0],
+        [(CHUNK_SIZE + 1) as u32, 255, (CHUNK_SIZE + 1) as u32],
+        &RIGHT_HANDED_Y_UP_CONFIG.faces,
+        &mut buffer,
+    );
+    buffer.quads.num_quads()
+}
+
+#[test]
+fn test_solid_cube_only_shell() {
+    use crate::voxel_world::voxel::Stone;
+    let mut voxels = vec![Voxel::EMPTY; ColumnShape::SIZE as usize];
+    for x in 1..=CHUNK_SIZE as u32 {
+        for y in 1..=CHUNK_SIZE as u32 {
+            for z in 1..=CHUNK_SIZE as u32 {
+                voxels[ColumnShape::linearize([x, y, z]) as usize] = Stone::into_voxel();
+            }
+        }
+    }
+    assert_eq!(occluded_min_y(&voxels), 0);
+    // 同一种材质 每个面合并成一个
+    assert_eq!(count_quads(&voxels, 0), 6);
+}
+
+#[test]
+fn test_occluded_layers_same_faces() {
+    use crate::voxel_world::voxel::Stone;
+    let mut voxels = vec![Voxel::EMPTY; ColumnShape::SIZE as usize];
+    for x in 0..CHUNK_SIZE_ADD_2_U32 {
+        for y in 0..100 {
+            for z in 0..CHUNK_SIZE_ADD_2_U32 {
+                voxels[ColumnShape::linearize([x, y, z]) as usize] = Stone::into_voxel();
+            }
+        }
+    }
+    // 地表上的一个方块
+    voxels[ColumnShape::linearize([5, 100, 5]) as usize] = Stone::into_voxel();
+    let min_y = occluded_min_y(&voxels);
+    assert_eq!(min_y, 99);
+    assert_eq!(count_quads(&voxels, min_y), count_quads(&voxels, 0));
+}
+
+#[cfg(test)]
+fn count_visible_faces(voxels: &Vec<Voxel>) -> usize {
+    let mut buffer = block_mesh::UnitQuadBuffer::new();
+    block_mesh::visible_block_faces(
+        voxels,
+        &ColumnShape {},
+        [0; 3],
+        [(CHUNK_SIZE + 1) as u32, 255, (CHUNK_SIZE + 1) as u32],
+        &RIGHT_HANDED_Y_UP_CONFIG.faces,
+        &mut buffer,
+    );
+    buffer.num_quads()
+}
+
+/**
+ * 顶点数的对比 每个面 4 个顶点
+ * 实心的 16x16x16 方块: 每个方块 6 个面 98304 个顶点 剔除相邻面后 6144 个 合并后 24 个
+ * 地下 99 层实心加一个凸起: 剔除后 1040 个顶点 合并后更少 遍历从 y = 99 开始
+ */
+#[test]
+fn test_vertex_reduction() {
+    use crate::voxel_world::voxel::Stone;
+    let mut voxels = vec![Voxel::EMPTY; ColumnShape::SIZE as usize];
+    for x in 1..=CHUNK_SIZE as u32 {
+        for y in 1..=CHUNK_SIZE as u32 {
+            for z in 1..=CHUNK_SIZE as u32 {
+                voxels[ColumnShape::linearize([x, y, z]) as usize] = Stone::into_voxel();
+            }
+        }
+    }
+    let solid = voxels
+        .iter()
+        .filter(|voxel| voxel.get_visibility() == VoxelVisibility::Opaque)
+        .count();
+    assert_eq!(solid * 6 * 4, 98304);
+    assert_eq!(count_visible_faces(&voxels) * 4, 6144);
+    assert_eq!(count_quads(&voxels, occluded_min_y(&voxels)) * 4, 24);
+
+    let mut voxels = vec![Voxel::EMPTY; ColumnShape::SIZE as usize];
+    for x in 0..CHUNK_SIZE_ADD_2_U32 {
+        for y in 0..100 {
+            for z in 0..CHUNK_SIZE_ADD_2_U32 {
+                voxels[ColumnShape::linearize([x, y, z]) as usize] = Stone::into_voxel();
+            }
+        }
+    }
+    voxels[ColumnShape::linearize([5, 100, 5]) as usize] = Stone::into_voxel();
+    // 顶层 255 个面 凸起 5 个面
+    let culled = count_visible_faces(&voxels);
+    assert_eq!(culled * 4, 1040);
+    let greedy = count_quads(&voxels, occluded_min_y(&voxels));
+    assert!(greedy < culled, "合并后{}个面", greedy);
+}
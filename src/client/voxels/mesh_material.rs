@@ -25,10 +25,13 @@ use crate::MAX_TEXTURE_COUNT;
 #[uuid = "8dd2b424-45a2-4a53-ac29-7ce356b2d5fe"]
 pub struct BindlessMaterial {
     textures: Vec<Handle<Image>>,
+    // 不使用贴图 每种体素显示为一种纯色 用来检查光照和 AO
+    pub flat: bool,
 }
 
 impl AsBindGroup for BindlessMaterial {
-    type Data = ();
+    // 是否使用纯色 作为管线的 key 切换时重新编译着色器
+    type Data = bool;
 
     fn as_bind_group(
         &self,
@@ -85,7 +88,7 @@ impl AsBindGroup for BindlessMaterial {
         Ok(PreparedBindGroup {
             bindings: vec![],
             bind_group,
-            data: (),
+            data: self.flat,
         })
     }
 
@@ -135,8 +138,13 @@ impl Material for BindlessMaterial {
         _pipeline: &bevy::pbr::MaterialPipeline<Self>,
         descriptor: &mut bevy::render::render_resource::RenderPipelineDescriptor,
         layout: &bevy::render::mesh::MeshVertexBufferLayout,
-        _key: bevy::pbr::MaterialPipelineKey<Self>,
+        key: bevy::pbr::MaterialPipelineKey<Self>,
     ) -> Result<(), bevy::render::render_resource::SpecializedMeshPipelineError> {
+        if key.bind_group_data {
+            if let Some(fragment) = descriptor.fragment.as_mut() {
+                fragment.shader_defs.push("VOXEL_FLAT_COLOR".into());
+            }
+        }
         let vertex_layout = layout.get_layout(&[
             Mesh::ATTRIBUTE_POSITION.at_shader_location(0),
             Mesh::ATTRIBUTE_UV_0.at_shader_location(1),
@@ -185,7 +193,10 @@ impl MaterialStorge {
             })
             .collect();
         // 这个东西 可以后续的处理！
-        let mat = materials.add(BindlessMaterial { textures });
+        let mat = materials.add(BindlessMaterial {
+            textures,
+            flat: false,
+        });
         Self(mat)
     }
 }
@@ -5,10 +5,14 @@ use bevy_inspector_egui::prelude::*;
 use serde::{Deserialize, Serialize};
 use walkdir::WalkDir;
 
-use crate::voxel_world::voxel::{Grass, Soli, Stone, VoxelDirection, VoxelMaterial};
+use crate::{
+    voxel_world::{voxel::VoxelDirection, voxel_registry::VOXEL_REGISTRY},
+    MATERIAL_RON,
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default, Reflect, InspectorOptions)]
 #[reflect(InspectorOptions)]
+#[serde(default)]
 pub struct VoxelConfig {
     pub index: u32,
     pub path: String,
@@ -16,6 +20,7 @@ pub struct VoxelConfig {
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default, InspectorOptions, Reflect)]
 #[reflect(InspectorOptions)]
+#[serde(default)]
 pub struct VoxelTypeConfig {
     pub type_name: String,
     pub type_ch_name: String,
@@ -25,7 +30,9 @@ pub struct VoxelTypeConfig {
     pub normal: HashMap<u8, VoxelConfig>,
 }
 
+// 体素的面贴图在 VoxelRegistry 中注册 volex.ron 中只需要写覆盖的部分
 #[derive(Debug, Clone, Serialize, Deserialize, Resource, InspectorOptions, Default, Reflect)]
+#[serde(default)]
 pub struct MaterailConfiguration {
     // 体素类型列表
     pub voxels: HashMap<u8, VoxelTypeConfig>,
@@ -33,33 +40,6 @@ pub struct MaterailConfiguration {
     pub files: Vec<String>,
 }
 
-#[macro_export]
-macro_rules! add_volex {
-    ($types: ident,$class: expr) => {
-        if (!$class.voxels.contains_key(&$types::ID)) {
-            $class.voxels.insert(
-                $types::ID,
-                VoxelTypeConfig {
-                    type_name: String::from($types::NAME),
-                    type_ch_name: String::from($types::CN_NAME),
-                    ..Default::default()
-                },
-            );
-            println!(
-                "* 加载体素[{}][{}]",
-                String::from($types::NAME),
-                String::from($types::CN_NAME),
-            );
-        } else {
-            println!(
-                "加载体素[{}][{}]",
-                String::from($types::NAME),
-                String::from($types::CN_NAME),
-            );
-        }
-    };
-}
-
 impl MaterailConfiguration {
     // 初始化
     pub fn new() -> Self {
@@ -89,15 +69,65 @@ impl MaterailConfiguration {
         self
     }
 
+    /**
+     * 按照 VoxelRegistry 生成体素类型和面贴图
+     * volex.ron 中写了路径的面覆盖注册表中的贴图 贴图的索引按照路径在 files 中查找
+     */
     pub fn load_all_voxels(mut self) -> Self {
-        // 初始化全部的数据
-        add_volex!(Stone, self);
-        add_volex!(Soli, self);
-        add_volex!(Grass, self);
-        // todo 加载其他的类型
+        let overrides = std::mem::take(&mut self.voxels);
+        for def in VOXEL_REGISTRY.iter() {
+            let mut config = VoxelTypeConfig {
+                type_name: String::from(def.name),
+                type_ch_name: String::from(def.cn_name),
+                ..Default::default()
+            };
+            if let Some(path) = def.textures.default {
+                config.default = self.texture(path);
+            }
+            for (face, path) in def.textures.faces.iter() {
+                config.normal.insert(*face, self.texture(path));
+            }
+            if let Some(custom) = overrides.get(&def.id) {
+                if !custom.default.path.is_empty() {
+                    config.default = self.texture(&custom.default.path);
+                }
+                for (face, custom) in custom.normal.iter() {
+                    if !custom.path.is_empty() {
+                        config.normal.insert(*face, self.texture(&custom.path));
+                    }
+                }
+                println!(
+                    "加载体素[{}][{}] 使用 {} 中的贴图",
+                    def.name, def.cn_name, MATERIAL_RON
+                );
+            } else {
+                println!("加载体素[{}][{}]", def.name, def.cn_name);
+            }
+            self.voxels.insert(def.id, config);
+        }
+        for id in overrides.keys() {
+            if !VOXEL_REGISTRY.contains(*id) {
+                println!("{} 中的体素[{}]没有注册 忽略", MATERIAL_RON, id);
+            }
+        }
         self
     }
 
+    // 贴图在 files 中的位置 没有的话加到最后
+    fn texture(&mut self, path: &str) -> VoxelConfig {
+        let index = match self.files.iter().position(|file| file == path) {
+            Some(index) => index,
+            None => {
+                self.files.push(String::from(path));
+                self.files.len() - 1
+            }
+        };
+        VoxelConfig {
+            index: index as u32,
+            path: String::from(path),
+        }
+    }
+
     pub fn read_file(self, path: String) -> Result<Self, ron::Error> {
         let reader = std::fs::File::open(path);
         match reader {
@@ -163,3 +193,37 @@ fn rotate_half_pi(normal: u8) -> u8 {
         _ => normal,
     };
 }
+
+#[test]
+fn test_load_all_voxels() {
+    use crate::voxel_world::{
+        voxel::{Grass, Stone, VoxelMaterial},
+        voxel_registry::FACE_TOP,
+    };
+
+    // 没有覆盖时全部使用注册表中的贴图
+    let config = MaterailConfiguration::default().load_all_voxels();
+    let grass = &config.voxels[&Grass::ID];
+    assert_eq!(grass.normal[&FACE_TOP].path, "textures/草坪.png");
+    assert_eq!(
+        config.files[grass.default.index as usize],
+        "textures/grass_a.png"
+    );
+    // 相同的贴图只加载一次
+    let mut files = config.files.clone();
+    files.sort();
+    files.dedup();
+    assert_eq!(files.len(), config.files.len());
+
+    // volex.ron 中只写需要覆盖的面
+    let config: MaterailConfiguration =
+        ron::from_str(r#"(voxels:{1:(normal:{4:(path:"textures/001.png")})})"#).unwrap();
+    let config = config.load_all_voxels();
+    let stone = &config.voxels[&Stone::ID];
+    assert_eq!(stone.default.path, "textures/002.png");
+    assert_eq!(
+        config.files[stone.normal[&FACE_TOP].index as usize],
+        "textures/001.png"
+    );
+    assert_eq!(stone.type_name, Stone::NAME);
+}
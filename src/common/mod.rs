@@ -1,13 +1,18 @@
 use std::{collections::HashSet, marker::PhantomData};
 
 use bevy::{
-    prelude::{Component, Plugin, PreUpdate, Query, ResMut, Resource, Transform, Vec3, With},
+    prelude::{Component, Plugin, PreUpdate, Query, Res, ResMut, Resource, Transform, Vec3, With},
     reflect::Reflect,
     utils::HashMap,
 };
 use bevy_inspector_egui::InspectorOptions;
 
-use crate::{server::player::Player, VIEW_RADIUS};
+use crate::{
+    server::{config::ServerConfig, player::Player},
+    VIEW_RADIUS,
+};
+
+pub mod net_error;
 
 #[derive(Debug, Clone, Copy, Reflect, InspectorOptions)]
 pub struct Sphere3 {
@@ -21,13 +26,32 @@ pub struct ClipSpheres {
     pub new_sphere: Sphere3,
 }
 
+/**
+ * 冻结区块的加载 用来测试渲染的性能
+ * 冻结时视野球不再跟随玩家 不请求新的区块 也不删除已经显示的区块
+ * 解冻后按照玩家当前的位置补上冻结期间的加载和删除
+ */
+#[derive(Debug, Default, Resource)]
+pub struct ClipSpheresFrozen(pub bool);
+
+// 区块加载相关系统的运行条件
+pub fn chunk_streaming(frozen: Res<ClipSpheresFrozen>) -> bool {
+    !frozen.0
+}
+
 // 适用于单个数据
 pub fn update_clip_shpere_system<T>(
     mut clip_spheres: ResMut<ClipSpheres>,
+    frozen: Res<ClipSpheresFrozen>,
     query: Query<&Transform, With<T>>,
 ) where
     T: Component,
 {
+    if frozen.0 {
+        // 新旧相同 冻结期间不会删除区块
+        clip_spheres.old_sphere = clip_spheres.new_sphere;
+        return;
+    }
     let position = if let Some(trf) = query.iter().next() {
         trf.translation
     } else {
@@ -59,6 +83,7 @@ impl<T: Component> Plugin for ClientClipSpheresPlugin<T> {
             new_sphere: init_shpere,
         };
         app.insert_resource(clip_spheres);
+        app.init_resource::<ClipSpheresFrozen>();
         app.add_systems(PreUpdate, update_clip_shpere_system::<T>);
     }
 }
@@ -83,13 +108,16 @@ impl Plugin for ServerClipSpheresPlugin {
 pub fn update_all_clip_shpere_system(
     mut server_clip_spheres: ResMut<ServerClipSpheres>,
     query: Query<(&Player, &Transform)>,
+    config: Option<Res<ServerConfig>>,
 ) {
+    // 配置的加载半径不超过客户端的可视半径
+    let radius = config.map_or(VIEW_RADIUS, |config| config.view_radius);
     let mut old_keys: HashSet<u64> = server_clip_spheres.clip_spheres.keys().cloned().collect();
     for (player, transform) in query.iter() {
         let client_id = player.id;
         let sphere = Sphere3 {
             center: transform.translation,
-            radius: VIEW_RADIUS,
+            radius,
         };
         old_keys.remove(&client_id);
         if let Some(clip_sphere) = server_clip_spheres.clip_spheres.get_mut(&client_id) {
@@ -0,0 +1,138 @@
+// 网络层的错误
+// 连接 区块解码 消息解析 出错时返回 NetError 调用方按照错误的类型处理
+// 超时和传输层的错误可以重新连接 解析错误和协议不一致重连也没有用
+
+use std::fmt;
+
+use bevy::prelude::Event;
+use bevy_renet::renet::transport::{NetcodeDisconnectReason, NetcodeError, NetcodeTransportError};
+use bit_vec::BitVec;
+use huffman_compress::Tree;
+use serde::de::DeserializeOwned;
+
+use crate::{
+    voxel_world::{compress::uncompress, voxel::Voxel},
+    CHUNK_VOLUME,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NetError {
+    // 创建连接失败 或者连接过程中传输层出错
+    Transport(String),
+    // 消息或者区块数据无法解析
+    Decode(String),
+    // 客户端和服务端的协议版本不一致
+    ProtocolMismatch { server: u32, client: u32 },
+    // 连接或者等待服务端响应超时
+    Timeout,
+}
+
+// 收到的消息出错时发送 由 handle_net_errors 按照是否可以恢复处理
+#[derive(Debug, Clone, Event)]
+pub struct NetErrorEvent(pub NetError);
+
+impl NetError {
+    // 可以通过重新连接恢复的错误
+    pub fn is_recoverable(&self) -> bool {
+        matches!(self, NetError::Transport(_) | NetError::Timeout)
+    }
+}
+
+impl fmt::Display for NetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetError::Transport(err) => write!(f, "transport error: {}", err),
+            NetError::Decode(err) => write!(f, "decode error: {}", err),
+            NetError::ProtocolMismatch { server, client } => {
+                write!(f, "protocol mismatch: server {}, client {}", server, client)
+            }
+            NetError::Timeout => write!(f, "connection timed out"),
+        }
+    }
+}
+
+impl std::error::Error for NetError {}
+
+impl From<bincode::Error> for NetError {
+    fn from(err: bincode::Error) -> Self {
+        NetError::Decode(err.to_string())
+    }
+}
+
+impl From<std::io::Error> for NetError {
+    fn from(err: std::io::Error) -> Self {
+        NetError::Transport(err.to_string())
+    }
+}
+
+// 连接和等待响应的超时
+fn is_timeout(err: &NetcodeError) -> bool {
+    matches!(
+        err,
+        NetcodeError::Disconnected(
+            NetcodeDisconnectReason::ConnectionTimedOut
+                | NetcodeDisconnectReason::ConnectionRequestTimedOut
+                | NetcodeDisconnectReason::ConnectionResponseTimedOut
+        )
+    )
+}
+
+impl From<NetcodeError> for NetError {
+    fn from(err: NetcodeError) -> Self {
+        if is_timeout(&err) {
+            return NetError::Timeout;
+        }
+        NetError::Transport(err.to_string())
+    }
+}
+
+impl From<&NetcodeTransportError> for NetError {
+    fn from(err: &NetcodeTransportError) -> Self {
+        match err {
+            NetcodeTransportError::Netcode(err) if is_timeout(err) => NetError::Timeout,
+            err => NetError::Transport(err.to_string()),
+        }
+    }
+}
+
+// 解析收到的消息
+pub fn decode<T: DeserializeOwned>(message: &[u8]) -> Result<T, NetError> {
+    Ok(bincode::deserialize(message)?)
+}
+
+/**
+ * 解压收到的区块数据 体素的数量不正确时返回错误
+ */
+pub fn decode_chunk(buffer: &BitVec, tree: Tree<Voxel>) -> Result<Vec<Voxel>, NetError> {
+    let voxels = uncompress(buffer, tree);
+    if voxels.len() != CHUNK_VOLUME as usize {
+        return Err(NetError::Decode(format!(
+            "chunk has {} voxels, expected {}",
+            voxels.len(),
+            CHUNK_VOLUME
+        )));
+    }
+    Ok(voxels)
+}
+
+#[test]
+fn test_net_error() {
+    use crate::voxel_world::compress::compress;
+
+    // 只有一种体素时压缩后是空的 使用 ChunkSame 发送
+    let mut voxels = vec![Voxel::FILLED; CHUNK_VOLUME as usize];
+    voxels[0] = Voxel::EMPTY;
+    let (buffer, tree) = compress(voxels.clone());
+    assert_eq!(decode_chunk(&buffer, tree), Ok(voxels));
+    let (buffer, tree) = compress(voxels[..10].to_vec());
+    assert!(matches!(
+        decode_chunk(&buffer, tree),
+        Err(NetError::Decode(_))
+    ));
+
+    assert_eq!(decode::<u32>(&bincode::serialize(&7u32).unwrap()), Ok(7));
+    let err = decode::<String>(&[1, 2]).unwrap_err();
+    assert!(matches!(err, NetError::Decode(_)));
+    assert!(!err.is_recoverable());
+    assert!(NetError::Timeout.is_recoverable());
+}
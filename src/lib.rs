@@ -1,5 +1,6 @@
 use bevy_renet::renet::{transport::NETCODE_KEY_BYTES, ConnectionConfig};
 use client::message_def::ClientChannel;
+use ndshape::{ConstShape, ConstShape2u32, ConstShape3u32};
 use server::message_def::ServerChannel;
 
 pub mod client;
@@ -13,8 +14,43 @@ pub mod voxel_world;
 
 pub const PRIVATE_KEY: &[u8; NETCODE_KEY_BYTES] = b"an example very very secret key."; // 32-bytes
 pub const WORD_PATH: &str = "world_test";
+// 封禁列表文件
+pub const BANLIST_PATH: &str = "banlist.ron";
+// 服务端配置文件
+pub const SERVER_CONFIG_PATH: &str = "server_config.toml";
+// 服务端脚本的目录
+pub const SCRIPTS_PATH: &str = "scripts";
+// 地形生成的配置文件 服务端运行时修改会重新加载
+pub const GEN_CONFIG_PATH: &str = "gen_config.ron";
 pub const MATERIAL_RON: &str = "volex.ron";
+// 客户端设置文件
+pub const CLIENT_SETTINGS_PATH: &str = "client_settings.ron";
+// 客户端已解锁的成就
+pub const ACHIEVEMENTS_PATH: &str = "achievements.ron";
 pub const PROTOCOL_ID: u64 = 7;
+// 网络消息的版本 修改任何消息的结构时都需要加 1 并更新 message_def 中的布局测试
+// 连接时客户端通过用户数据发送 和服务端不一致时会被断开
+pub const PROTOCOL_VERSION: u32 = 4;
+// 新世界默认使用的种子
+pub const DEFAULT_SEED: i32 = 1512354854;
+// 服务端默认的 tick 频率(每秒)
+pub const DEFAULT_TICK_RATE: u32 = 60;
+// 服务端默认的最大玩家数
+pub const DEFAULT_MAX_PLAYERS: usize = 32;
+// 服务端默认每个客户端每个 tick 发送的区块数据(字节)
+pub const DEFAULT_CHUNK_BUDGET: usize = 64 * 1024;
+// 服务端默认的自动保存间隔(秒)
+pub const DEFAULT_AUTOSAVE_INTERVAL: f32 = 300.0;
+// 服务端默认的世界边界(格) 多个世界并排放置 不能为 0
+pub const DEFAULT_WORLD_BORDER: u32 = 3072;
+// 服务端默认一次 /fill 最多修改的方块数
+pub const DEFAULT_MAX_FILL_VOLUME: u64 = 32 * 32 * 32;
+// 出生时脚下和地表的距离 留出角色半身的高度
+pub const SPAWN_HEIGHT_OFFSET: f32 = 1.0;
+// 服务端物理的固定步长(秒) 和 tick 频率无关 玩家的位置在两步之间插值
+pub const PHYSICS_TIMESTEP: f32 = 1.0 / 60.0;
+// 聊天消息的最大长度(字符)
+pub const MAX_CHAT_LENGTH: usize = 256;
 
 pub type SmallKeyHashMap<K, V> = ahash::AHashMap<K, V>;
 
@@ -23,9 +59,47 @@ pub const VIEW_RADIUS: f32 = 128.00;
 // 物理引擎半径 这里如果计算的慢可能 跟不上？
 pub const PY_DISTANCE: i32 = 1;
 // CHUNK大小
+// 区块的数据是 CHUNK_SIZE^3 个体素 按照 ChunkShape 排列(x 变化最快 然后是 y 最后是 z)
+// 区块的平面数据(噪声 高度)是 CHUNK_SIZE^2 个 按照 ChunkPanelShape 排列
+// 体素 [x, y, z] 所在的列是平面上的 [x, z] 见 biomes::column_index
+// 网格生成需要额外的一圈邻居数据 所以使用 CHUNK_SIZE_ADD_2_U32
+// 修改 CHUNK_SIZE 时 下面的断言会在编译期检查这些关系
 pub const CHUNK_SIZE: i32 = 16;
 pub const CHUNK_SIZE_U32: u32 = CHUNK_SIZE as u32;
 pub const CHUNK_SIZE_ADD_2_U32: u32 = CHUNK_SIZE_U32 + 2;
+// 一个区块的体素个数
+pub const CHUNK_VOLUME: u32 = CHUNK_SIZE_U32 * CHUNK_SIZE_U32 * CHUNK_SIZE_U32;
+// 一个区块平面的列数
+pub const CHUNK_AREA: u32 = CHUNK_SIZE_U32 * CHUNK_SIZE_U32;
+// 世界的高度(-128..128) 按照整列区块生成网格
+pub const WORLD_HEIGHT: u32 = 256;
+
+pub type ChunkShape = ConstShape3u32<CHUNK_SIZE_U32, CHUNK_SIZE_U32, CHUNK_SIZE_U32>;
+pub type ChunkPanelShape = ConstShape2u32<CHUNK_SIZE_U32, CHUNK_SIZE_U32>;
+// 网格生成使用的整列数据 带一圈邻居 见 ChunkMap::get_with_neighbor_full_y
+pub type ChunkColumnShape =
+    ConstShape3u32<CHUNK_SIZE_ADD_2_U32, WORLD_HEIGHT, CHUNK_SIZE_ADD_2_U32>;
+
+const _: () = {
+    // 区块的中心在 CHUNK_SIZE / 2 的位置
+    assert!(
+        CHUNK_SIZE > 0 && CHUNK_SIZE % 2 == 0,
+        "CHUNK_SIZE 必须是正偶数"
+    );
+    assert!(
+        WORLD_HEIGHT % CHUNK_SIZE_U32 == 0,
+        "世界高度必须是 CHUNK_SIZE 的整数倍"
+    );
+    assert!(
+        ChunkShape::SIZE == CHUNK_VOLUME,
+        "ChunkShape 和 CHUNK_SIZE 不一致"
+    );
+    assert!(
+        ChunkPanelShape::SIZE == CHUNK_AREA,
+        "ChunkPanelShape 和 CHUNK_SIZE 不一致"
+    );
+    assert!(CHUNK_VOLUME == CHUNK_AREA * CHUNK_SIZE_U32);
+};
 // 贴图个数
 pub const MAX_TEXTURE_COUNT: usize = 22;
 // 物体选择半径
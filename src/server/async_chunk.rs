@@ -1,12 +1,17 @@
 use bevy::{
-    prelude::{warn, EventWriter, Plugin, Query, Res, ResMut, Resource, Update},
+    prelude::{
+        warn, EventWriter, IVec3, IntoSystemConfigs, Plugin, Query, Res, ResMut, Resource,
+        Transform, Update, Vec3,
+    },
     tasks::{AsyncComputeTaskPool, Task},
+    utils::{HashMap, HashSet},
 };
 use bevy_renet::renet::RenetServer;
 use ndshape::{ConstShape, ConstShape3u32};
 
 use crate::{
     client::message_def::{chunk_query::ChunkQuery, ClientChannel},
+    common::net_error::decode,
     server::{message_def::ServerChannel, object_filing::put_object::put_object},
     staff::StaffInfoStroge,
     voxel_world::{
@@ -14,25 +19,90 @@ use crate::{
         chunk::ChunkKey,
         chunk_map::ChunkMap,
         compress::compress,
+        gen_config::{gen_config, WorldHeight},
         map_database::{DbSaveTasks, MapDataBase},
         player_state::PlayerOnTimeState,
+        spawn::SpawnPoint,
         voxel::{BasicStone, Voxel, VoxelMaterial},
         voxel_mesh::VOXEL_MESH_MAP,
+        voxel_registry::{Tool, VOXEL_REGISTRY},
+        world::{is_creative, WorldId},
     },
-    CHUNK_SIZE, CHUNK_SIZE_U32,
+    ChunkShape, CHUNK_SIZE_U32, DEFAULT_MAX_FILL_VOLUME,
 };
 
 use super::{
+    brush::{brush_blocks, brush_region, MAX_BRUSH_RADIUS},
+    chunk::collect_generated_chunks,
+    chunk_budget::ChunkSendQueue,
+    config::ServerConfig,
+    edit_history::{
+        edit_xyz, group_by_chunk, undo_record, BlockEdit, EditHistory, EditRecord,
+        EDIT_HISTORY_SIZE,
+    },
+    falling_block::FallingBlocks,
+    fill::{
+        check_block_edit, check_fill_corners, check_player_edit, check_reach, chunk_to_block,
+        fill_bounds, fill_chunk_keys, fill_region, fill_volume, BuildLimit, FillLimit,
+        SpawnProtection, WorldBorder,
+    },
+    gen_pool::{GenPool, GenResult},
+    light_query::LightCache,
     message_def::chunk_result::ChunkResult,
     object_filing::ObjectFillEvent,
-    player::ServerLobby,
+    permission::{command_level, Permissions},
+    player::{send_to_world, ServerLobby},
+    scripting::ScriptEvent,
+    server_command::reply,
     sp_physics::DespawnSpEvent,
     terrain_physics::{ColliderManager, ColliderTasksManager, ColliderUpdateTasksManager},
+    tool_bar_sync::wear_tool,
 };
 
+// 重新生成区块的最大半径
+pub const MAX_REGEN_RADIUS: i32 = 4;
+
 #[derive(Debug, Resource)]
 pub struct ChunkResultTasks {
-    pub tasks: Vec<Task<(u64, Vec<u8>)>>,
+    // (客户端 0 表示广播, 区块, 消息)
+    pub tasks: Vec<Task<(u64, ChunkKey, Vec<u8>)>>,
+}
+
+/**
+ * 客户端请求的区块还没有生成 交给生成线程池
+ * 生成完成后回复等待这个区块的客户端
+ */
+#[derive(Debug, Default, Resource)]
+pub struct PendingChunkReplies {
+    pub waiting: HashMap<ChunkKey, Vec<u64>>,
+}
+
+/**
+ * 等待重新生成的区块 和是否保留修改
+ * 同一个区块只生成一次 后面的请求覆盖是否保留修改
+ */
+#[derive(Default, Resource)]
+pub struct PendingRegens {
+    // 还没有交给生成线程池的区块
+    pub queued: HashMap<ChunkKey, bool>,
+    // 已经交给生成线程池 等待结果的区块
+    pub generating: HashMap<ChunkKey, bool>,
+    // collect_generated_chunks 取回的结果 还没有覆盖到世界中
+    pub generated: Vec<(GenResult, bool)>,
+}
+
+impl PendingRegens {
+    // 中心区块周围半径内 世界高度的全部区块
+    pub fn push(&mut self, center: ChunkKey, radius: i32, keep_edits: bool) {
+        for x in -radius..=radius {
+            for z in -radius..=radius {
+                for y in gen_config().world_height.chunk_ys() {
+                    let key = ChunkKey(IVec3::new(center.0.x + x, y, center.0.z + z));
+                    self.queued.insert(key, keep_edits);
+                }
+            }
+        }
+    }
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -52,41 +122,85 @@ pub fn deal_chunk_query_system(
     server_lobby: Res<ServerLobby>,
     mut other_tree_tasks_map: ResMut<OtherTreeTasksMap>,
     mut event_writer: EventWriter<DespawnSpEvent>,
+    permissions: Permissions,
+    (
+        mut pending_regens,
+        fill_limit,
+        build_limit,
+        mut edit_history,
+        mut falling_blocks,
+        spawn_protection,
+        spawn_point,
+        mut script_events,
+        mut gen_pool,
+        mut pending_replies,
+        player_transforms,
+        mut light_cache,
+        world_border,
+    ): (
+        ResMut<PendingRegens>,
+        Res<FillLimit>,
+        Res<BuildLimit>,
+        ResMut<EditHistory>,
+        ResMut<FallingBlocks>,
+        Res<SpawnProtection>,
+        Res<SpawnPoint>,
+        EventWriter<ScriptEvent>,
+        ResMut<GenPool>,
+        ResMut<PendingChunkReplies>,
+        Query<&Transform>,
+        ResMut<LightCache>,
+        Res<WorldBorder>,
+    ),
 ) {
     let pool = AsyncComputeTaskPool::get();
     for client_id in server.clients_id() {
         while let Some(message) = server.receive_message(client_id, ClientChannel::ChunkQuery) {
-            let chunk_query: ChunkQuery = bincode::deserialize(&message).unwrap();
+            let chunk_query: ChunkQuery = match decode(&message) {
+                Ok(chunk_query) => chunk_query,
+                Err(err) => {
+                    warn!("{}|{}", client_id, err);
+                    continue;
+                }
+            };
             match chunk_query {
                 ChunkQuery::GetFullY(chunk_key) => {
-                    // 获取全部的值 然后返回
-                    let last_inex = -128 / CHUNK_SIZE + 1;
-                    for y_offset in last_inex..=128 / CHUNK_SIZE {
+                    // 只能请求自己所在世界的区块
+                    let world = player_translation(client_id, &server_lobby, &player_transforms)
+                        .ok()
+                        .map(WorldId::of_position);
+                    if world != Some(WorldId::of_chunk(chunk_key)) {
+                        warn!("{}|请求了其他世界的区块 {:?}", client_id, chunk_key);
+                        continue;
+                    }
+                    // 获取世界高度内全部的值 然后返回 高度之外的区块客户端当做空气
+                    for y_offset in gen_config().world_height.chunk_ys() {
                         let mut new_key = chunk_key;
                         new_key.0.y = y_offset;
                         let voxels;
                         if let Some(data) = chunk_map.map_data.get(&new_key) {
                             voxels = data.clone();
                         } else {
-                            voxels = db.find_by_chunk_key(
-                                new_key,
-                                db_save_task.as_mut(),
-                                other_tree_tasks_map.as_mut(),
-                            );
+                            match db.load_saved(new_key) {
+                                Ok(Some(data)) => voxels = data,
+                                // 没有保存过的区块交给生成线程池 生成后再回复
+                                Ok(None) => {
+                                    gen_pool.try_request(db.seed, new_key);
+                                    pending_replies
+                                        .waiting
+                                        .entry(new_key)
+                                        .or_default()
+                                        .push(client_id);
+                                    continue;
+                                }
+                                Err(e) => {
+                                    println!("wrong, to get Map {:?}", e);
+                                    continue;
+                                }
+                            }
                         }
-                        let (buffer, tree) = compress(voxels.clone());
-                        let message = if buffer.len() == 0 {
-                            bincode::serialize(&ChunkResult::ChunkSame((new_key, voxels[0])))
-                                .unwrap()
-                        } else {
-                            bincode::serialize(&ChunkResult::ChunkData {
-                                key: new_key,
-                                data: (buffer, tree),
-                            })
-                            .unwrap()
-                        };
-
-                        let task = pool.spawn(async move { (client_id, message) });
+                        let message = full_chunk_message(new_key, voxels);
+                        let task = pool.spawn(async move { (client_id, new_key, message) });
                         tasks.tasks.push(task);
                     }
                 }
@@ -97,6 +211,25 @@ pub fn deal_chunk_query_system(
                     center,
                     active_index,
                 } => {
+                    if !pos_in_chunk(pos) {
+                        continue;
+                    }
+                    let block = chunk_to_block(chunk_key, pos);
+                    // 距离 高度限制和出生点保护
+                    if let Err(text) = check_client_edit(
+                        client_id,
+                        block,
+                        &permissions,
+                        &db,
+                        &server_lobby,
+                        &player_transforms,
+                        &build_limit,
+                        &spawn_protection,
+                        &spawn_point,
+                    ) {
+                        reply(&mut server, client_id, false, text);
+                        continue;
+                    }
                     if let Some(voxel) = chunk_map.map_data.get_mut(&chunk_key) {
                         // 1. 更新 chunk_map 数据
                         type SampleShape =
@@ -150,6 +283,30 @@ pub fn deal_chunk_query_system(
                             }
                         }
                         voxel[index] = voxel_type;
+                        // 记录玩家的修改 重新生成时可以保留
+                        db.record_edit(chunk_key, index, voxel_type);
+                        light_cache.refresh_region(block, block);
+                        // 破坏后检查上面的方块 放置会下落的方块时检查它自己
+                        if voxel_type.id == Voxel::EMPTY.id {
+                            falling_blocks.checks.push(block + IVec3::Y);
+                        } else if VOXEL_REGISTRY.falls(voxel_type.id) {
+                            falling_blocks.checks.push(block);
+                        }
+                        // 放置和转动可以撤销 破坏已经产生了掉落 不能撤销
+                        if voxel_type.id != Voxel::EMPTY.id {
+                            edit_history.push(
+                                client_id,
+                                EditRecord {
+                                    edits: vec![BlockEdit {
+                                        chunk_key,
+                                        index,
+                                        old: old_voxel,
+                                        new: voxel_type,
+                                    }],
+                                    refund: active_index.map(|_| center),
+                                },
+                            );
+                        }
                         // 2. 更新 db 数据
                         let new_voxels_clone = voxel.clone();
                         let task =
@@ -162,11 +319,60 @@ pub fn deal_chunk_query_system(
                             voxel_type,
                         })
                         .unwrap();
-                        server.broadcast_message(ServerChannel::ChunkResult, message);
+                        send_to_world(
+                            &mut server,
+                            lobby_positions(&server_lobby, &player_transforms),
+                            WorldId::of_chunk(chunk_key),
+                            ServerChannel::ChunkResult,
+                            message,
+                        );
+                        // 通知脚本 转动方向不算放置
+                        if let Some(username) = permissions.username_of(client_id) {
+                            if old_voxel.id == Voxel::EMPTY.id && voxel_type.id != Voxel::EMPTY.id {
+                                script_events.send(ScriptEvent::Place {
+                                    username,
+                                    block,
+                                    voxel: voxel_type,
+                                });
+                            } else if old_voxel.id != Voxel::EMPTY.id
+                                && voxel_type.id == Voxel::EMPTY.id
+                            {
+                                script_events.send(ScriptEvent::Break {
+                                    username,
+                                    block,
+                                    voxel: old_voxel,
+                                });
+                            }
+                        }
                         // FIXME: 这里要考虑把代码格式简化 一下
                         // 发送物体被打下来的消息 old_voxel  chunk_key, pos, 还原物体的位置!
                         if old_voxel.id != Voxel::EMPTY.id && voxel_type.id == Voxel::EMPTY.id {
                             println!("cube被打下来了: {:?}", old_voxel);
+                            // 掉落按照损坏前手里的工具判断
+                            let mut tool = Tool::HAND;
+                            // 破坏方块时使用一次手里的工具
+                            if let Some(active_index) = active_index {
+                                if let Some(Ok(mut player_state)) = server_lobby
+                                    .players
+                                    .get(&client_id)
+                                    .map(|entity| query_state.get_mut(*entity))
+                                {
+                                    if let Some((Some(staff_id), _)) =
+                                        player_state.0.toolbar.get(active_index)
+                                    {
+                                        tool = staff_info_stroge
+                                            .get(*staff_id)
+                                            .map_or(Tool::HAND, |staff| staff.break_tool());
+                                    }
+                                    wear_tool(
+                                        client_id,
+                                        &mut server,
+                                        &mut player_state.0,
+                                        active_index,
+                                        &staff_info_stroge,
+                                    );
+                                }
+                            }
                             if VOXEL_MESH_MAP.contains_key(&old_voxel.id) {
                                 // 如果是特殊物体被破坏要处理物理地形
                                 event_writer.send(DespawnSpEvent {
@@ -177,7 +383,7 @@ pub fn deal_chunk_query_system(
 
                             // 物体时被打下来了 这里通过配置掉落
                             if let Some(staff_list) =
-                                staff_info_stroge.voxel_to_staff_list(old_voxel)
+                                staff_info_stroge.voxel_to_staff_list(old_voxel, tool)
                             {
                                 println!("staff下落: {:?}", staff_list);
                                 for staff in staff_list.into_iter() {
@@ -266,11 +472,672 @@ pub fn deal_chunk_query_system(
                         }
                     }
                 }
+                ChunkQuery::Regen {
+                    chunk_key,
+                    radius,
+                    keep_edits,
+                } => {
+                    // 重新生成会覆盖其他玩家的修改 只有管理员可以使用
+                    if permissions.level_of(client_id, &db) < command_level("regen") {
+                        reply(
+                            &mut server,
+                            client_id,
+                            false,
+                            format!(
+                                "Insufficient permission: requires {:?}",
+                                command_level("regen")
+                            ),
+                        );
+                        continue;
+                    }
+                    let radius = radius.clamp(0, MAX_REGEN_RADIUS);
+                    println!(
+                        "{}|重新生成区块 {:?} 半径 {} 保留修改 {}",
+                        client_id, chunk_key, radius, keep_edits
+                    );
+                    pending_regens.push(chunk_key, radius, keep_edits);
+                }
+                ChunkQuery::Fill { from, to, voxel } => {
+                    if permissions.level_of(client_id, &db) < command_level("fill") {
+                        reply(
+                            &mut server,
+                            client_id,
+                            false,
+                            format!(
+                                "Insufficient permission: requires {:?}",
+                                command_level("fill")
+                            ),
+                        );
+                        continue;
+                    }
+                    // 和笔刷一样 不能填充没有注册的体素
+                    if !VOXEL_REGISTRY.contains(voxel.id) {
+                        reply(
+                            &mut server,
+                            client_id,
+                            false,
+                            format!("Unknown voxel {}", voxel.id),
+                        );
+                        continue;
+                    }
+                    let (min, max) = fill_bounds(IVec3::from(from), IVec3::from(to));
+                    if let Err(err) = check_fill_corners(min, max, *world_border) {
+                        reply(&mut server, client_id, false, err);
+                        continue;
+                    }
+                    // 和笔刷一样 只能修改自己所在的世界
+                    let world = player_translation(client_id, &server_lobby, &player_transforms)
+                        .ok()
+                        .map(WorldId::of_position);
+                    if [min, max]
+                        .iter()
+                        .any(|corner| Some(WorldId::of_position(corner.as_vec3())) != world)
+                    {
+                        reply(
+                            &mut server,
+                            client_id,
+                            false,
+                            String::from("The fill must stay in your world"),
+                        );
+                        continue;
+                    }
+                    let volume = fill_volume(min, max);
+                    if volume > fill_limit.0 {
+                        reply(
+                            &mut server,
+                            client_id,
+                            false,
+                            format!("Region has {} blocks, limit is {}", volume, fill_limit.0),
+                        );
+                        continue;
+                    }
+                    let keys = match fill_chunk_keys(min, max) {
+                        Ok(keys) => keys,
+                        Err(err) => {
+                            reply(&mut server, client_id, false, err);
+                            continue;
+                        }
+                    };
+                    load_chunks(
+                        keys.into_iter(),
+                        &mut chunk_map,
+                        &mut db,
+                        &mut db_save_task,
+                        &mut other_tree_tasks_map,
+                    );
+                    let report = fill_region(&mut chunk_map, min, max, voxel);
+                    println!(
+                        "{}|填充 {} 到 {} 为 {:?} 修改了{}个方块",
+                        client_id,
+                        min,
+                        max,
+                        voxel,
+                        report.changed()
+                    );
+                    // 特殊物体被覆盖时处理物理地形 填充不产生掉落
+                    for edit in report.changes.iter() {
+                        if VOXEL_MESH_MAP.contains_key(&edit.old.id) {
+                            event_writer.send(DespawnSpEvent {
+                                chunk_key: edit.chunk_key,
+                                index: edit.index,
+                            });
+                        }
+                    }
+                    let chunks = commit_edits(
+                        group_by_chunk(
+                            report
+                                .changes
+                                .iter()
+                                .map(|edit| (edit.chunk_key, edit.index, edit.new)),
+                        ),
+                        &chunk_map,
+                        &mut db,
+                        &mut db_save_task,
+                        &mut tasks,
+                        &collider_manager,
+                        &mut collider_update_tasks_manager,
+                        &mut collider_tasks,
+                        &mut light_cache,
+                    );
+                    let mut text =
+                        format!("Filled {} blocks in {} chunks", report.changed(), chunks);
+                    if report.skipped > 0 {
+                        text.push_str(&format!(", skipped {} bedrock", report.skipped));
+                    }
+                    edit_history.push(
+                        client_id,
+                        EditRecord {
+                            edits: report.changes,
+                            refund: None,
+                        },
+                    );
+                    reply(&mut server, client_id, true, text);
+                }
+                ChunkQuery::Brush {
+                    center,
+                    radius,
+                    shape,
+                    mode,
+                    voxel,
+                } => {
+                    let center = IVec3::from(center);
+                    // 笔刷的中心和单个方块一样要够得到 之后的坐标计算不会溢出
+                    let player =
+                        match player_translation(client_id, &server_lobby, &player_transforms) {
+                            Ok(player) => player,
+                            Err(text) => {
+                                reply(&mut server, client_id, false, text);
+                                continue;
+                            }
+                        };
+                    if let Err(text) = check_reach(player, center) {
+                        reply(&mut server, client_id, false, text);
+                        continue;
+                    }
+                    let world = WorldId::of_position(player);
+                    if WorldId::of_position(center.as_vec3()) != world {
+                        reply(
+                            &mut server,
+                            client_id,
+                            false,
+                            String::from("The brush must stay in your world"),
+                        );
+                        continue;
+                    }
+                    // 创造世界中所有人都可以使用笔刷 按照玩家所在的世界判断
+                    let creative = is_creative(player);
+                    if !creative && permissions.level_of(client_id, &db) < command_level("brush") {
+                        reply(
+                            &mut server,
+                            client_id,
+                            false,
+                            format!(
+                                "Insufficient permission: requires {:?}",
+                                command_level("brush")
+                            ),
+                        );
+                        continue;
+                    }
+                    if radius > MAX_BRUSH_RADIUS {
+                        reply(
+                            &mut server,
+                            client_id,
+                            false,
+                            format!("Brush radius must be at most {}", MAX_BRUSH_RADIUS),
+                        );
+                        continue;
+                    }
+                    if !VOXEL_REGISTRY.contains(voxel.id) {
+                        reply(
+                            &mut server,
+                            client_id,
+                            false,
+                            format!("Unknown voxel {}", voxel.id),
+                        );
+                        continue;
+                    }
+                    // 世界高度之外的部分忽略
+                    let height = gen_config().world_height;
+                    let blocks: Vec<IVec3> = brush_blocks(center, radius, shape)
+                        .into_iter()
+                        .filter(|pos| {
+                            (height.min_block_y()..=height.max_block_y()).contains(&pos.y)
+                        })
+                        .collect();
+                    // 笔刷的任何一部分超出高度限制或者在出生点保护范围内都不修改 管理员不受限制
+                    let level = permissions.level_of(client_id, &db);
+                    if let Some(Err(text)) = blocks
+                        .iter()
+                        .map(|pos| {
+                            check_block_edit(
+                                *pos,
+                                level,
+                                creative,
+                                &build_limit,
+                                &spawn_protection,
+                                spawn_point.0,
+                            )
+                        })
+                        .find(Result::is_err)
+                    {
+                        reply(&mut server, client_id, false, text);
+                        continue;
+                    }
+                    let r = IVec3::splat(radius as i32);
+                    let min =
+                        (center - r).max(IVec3::new(i32::MIN, height.min_block_y(), i32::MIN));
+                    let max =
+                        (center + r).min(IVec3::new(i32::MAX, height.max_block_y(), i32::MAX));
+                    let Ok(keys) = fill_chunk_keys(min, max) else {
+                        continue;
+                    };
+                    load_chunks(
+                        keys.into_iter(),
+                        &mut chunk_map,
+                        &mut db,
+                        &mut db_save_task,
+                        &mut other_tree_tasks_map,
+                    );
+                    let report = brush_region(&mut chunk_map, &blocks, mode, voxel);
+                    println!(
+                        "{}|笔刷 {} 半径 {} {:?} {:?} 为 {:?} 修改了{}个方块",
+                        client_id,
+                        center,
+                        radius,
+                        shape,
+                        mode,
+                        voxel,
+                        report.changed()
+                    );
+                    if report.changes.is_empty() {
+                        continue;
+                    }
+                    for edit in report.changes.iter() {
+                        if VOXEL_MESH_MAP.contains_key(&edit.old.id) {
+                            event_writer.send(DespawnSpEvent {
+                                chunk_key: edit.chunk_key,
+                                index: edit.index,
+                            });
+                        }
+                    }
+                    commit_edits(
+                        group_by_chunk(
+                            report
+                                .changes
+                                .iter()
+                                .map(|edit| (edit.chunk_key, edit.index, edit.new)),
+                        ),
+                        &chunk_map,
+                        &mut db,
+                        &mut db_save_task,
+                        &mut tasks,
+                        &collider_manager,
+                        &mut collider_update_tasks_manager,
+                        &mut collider_tasks,
+                        &mut light_cache,
+                    );
+                    // 一次笔刷作为一次修改 可以整体撤销
+                    edit_history.push(
+                        client_id,
+                        EditRecord {
+                            edits: report.changes,
+                            refund: None,
+                        },
+                    );
+                }
+                ChunkQuery::Undo { count } => {
+                    let mut undone = 0;
+                    let mut restored_blocks = 0;
+                    let mut skipped = 0;
+                    for _ in 0..count.clamp(1, EDIT_HISTORY_SIZE) {
+                        let Some(record) = edit_history.pop(client_id) else {
+                            break;
+                        };
+                        // 区块可能已经卸载了 先读取
+                        let keys: HashSet<ChunkKey> =
+                            record.edits.iter().map(|edit| edit.chunk_key).collect();
+                        load_chunks(
+                            keys.into_iter(),
+                            &mut chunk_map,
+                            &mut db,
+                            &mut db_save_task,
+                            &mut other_tree_tasks_map,
+                        );
+                        let restored = undo_record(&mut chunk_map, &record);
+                        for edit in restored.iter() {
+                            if VOXEL_MESH_MAP.contains_key(&edit.new.id) {
+                                event_writer.send(DespawnSpEvent {
+                                    chunk_key: edit.chunk_key,
+                                    index: edit.index,
+                                });
+                            }
+                        }
+                        // 撤销放置 返还消耗的物品
+                        if let (Some(center), [edit]) = (record.refund, restored.as_slice()) {
+                            if let Some(staff) = staff_info_stroge.voxel_to_staff(edit.new) {
+                                fill_event.send(ObjectFillEvent {
+                                    chunk_key: edit.chunk_key,
+                                    xyz: edit_xyz(edit),
+                                    center,
+                                    staff: staff.clone(),
+                                });
+                            }
+                        }
+                        undone += 1;
+                        restored_blocks += restored.len();
+                        skipped += record.edits.len() - restored.len();
+                        commit_edits(
+                            group_by_chunk(
+                                restored
+                                    .iter()
+                                    .map(|edit| (edit.chunk_key, edit.index, edit.old)),
+                            ),
+                            &chunk_map,
+                            &mut db,
+                            &mut db_save_task,
+                            &mut tasks,
+                            &collider_manager,
+                            &mut collider_update_tasks_manager,
+                            &mut collider_tasks,
+                            &mut light_cache,
+                        );
+                    }
+                    if undone == 0 {
+                        reply(
+                            &mut server,
+                            client_id,
+                            false,
+                            String::from("Nothing to undo"),
+                        );
+                        continue;
+                    }
+                    println!("{}|撤销了{}次操作", client_id, undone);
+                    let mut text = format!("Undid {} edits ({} blocks)", undone, restored_blocks);
+                    if skipped > 0 {
+                        text.push_str(&format!(", kept {} blocks changed by others", skipped));
+                    }
+                    reply(&mut server, client_id, true, text);
+                }
+                ChunkQuery::Interact { chunk_key, pos } => {
+                    if !pos_in_chunk(pos) {
+                        continue;
+                    }
+                    // 和修改方块一样的检查
+                    if let Err(text) = check_client_edit(
+                        client_id,
+                        chunk_to_block(chunk_key, pos),
+                        &permissions,
+                        &db,
+                        &server_lobby,
+                        &player_transforms,
+                        &build_limit,
+                        &spawn_protection,
+                        &spawn_point,
+                    ) {
+                        reply(&mut server, client_id, false, text);
+                        continue;
+                    }
+                    let Some(voxels) = chunk_map.map_data.get_mut(&chunk_key) else {
+                        continue;
+                    };
+                    let index = ChunkShape::linearize(pos) as usize;
+                    // 以服务端的方块为准 客户端看到的可能已经被修改了
+                    let Some(voxel_type) = VOXEL_REGISTRY.interact(voxels[index]) else {
+                        continue;
+                    };
+                    voxels[index] = voxel_type;
+                    db.record_edit(chunk_key, index, voxel_type);
+                    let block = chunk_to_block(chunk_key, pos);
+                    light_cache.refresh_region(block, block);
+                    let save_voxels = voxels.clone();
+                    let task = pool.spawn(async move { (chunk_key.as_u8_array(), save_voxels) });
+                    db_save_task.tasks.push(task);
+                    // 客户端按照单个方块的修改更新网格和光照
+                    let message = bincode::serialize(&ChunkResult::ChunkUpdateOne {
+                        chunk_key,
+                        pos,
+                        voxel_type,
+                    })
+                    .unwrap();
+                    send_to_world(
+                        &mut server,
+                        lobby_positions(&server_lobby, &player_transforms),
+                        WorldId::of_chunk(chunk_key),
+                        ServerChannel::ChunkResult,
+                        message,
+                    );
+                    send_codiller_task(
+                        chunk_key,
+                        &collider_manager,
+                        &chunk_map,
+                        &mut collider_update_tasks_manager,
+                        &mut collider_tasks,
+                    );
+                }
             }
         }
     }
 }
 
+/**
+ * 重新生成的区块 包括生成配置修改后自动重新生成的
+ * 交给生成线程池 队列满了下一帧继续 取回的结果覆盖已经加载的数据 再通知全部客户端
+ */
+#[allow(clippy::too_many_arguments)]
+pub fn regen_chunks_system(
+    mut pending_regens: ResMut<PendingRegens>,
+    mut gen_pool: ResMut<GenPool>,
+    mut chunk_map: ResMut<ChunkMap>,
+    mut db: ResMut<MapDataBase>,
+    mut db_save_task: ResMut<DbSaveTasks>,
+    mut other_tree_tasks_map: ResMut<OtherTreeTasksMap>,
+    mut tasks: ResMut<ChunkResultTasks>,
+    mut light_cache: ResMut<LightCache>,
+    collider_manager: Res<ColliderManager>,
+    mut collider_update_tasks_manager: ResMut<ColliderUpdateTasksManager>,
+    mut collider_tasks: ResMut<ColliderTasksManager>,
+) {
+    let queued: Vec<(ChunkKey, bool)> = pending_regens.queued.drain().collect();
+    let mut queue_full = false;
+    for (key, keep_edits) in queued {
+        // 正在生成的区块可能还在使用修改之前的生成配置 取回之后再重新生成一次
+        if pending_regens.generating.contains_key(&key) {
+            pending_regens.queued.insert(key, keep_edits);
+            continue;
+        }
+        if !queue_full && gen_pool.try_request(db.seed, key) {
+            pending_regens.generating.insert(key, keep_edits);
+        } else {
+            queue_full = true;
+            pending_regens.queued.insert(key, keep_edits);
+        }
+    }
+    let pool = AsyncComputeTaskPool::get();
+    for ((key, voxels, other_trees), keep_edits) in std::mem::take(&mut pending_regens.generated) {
+        let voxels = db.regenerate_chunk(
+            key,
+            voxels,
+            other_trees,
+            keep_edits,
+            db_save_task.as_mut(),
+            other_tree_tasks_map.as_mut(),
+        );
+        chunk_map.write_chunk(key, voxels.clone());
+        light_cache.refresh_chunk(key);
+        // 通知全部客户端
+        let task = pool.spawn(async move { (0, key, update_chunk_message(key, voxels)) });
+        tasks.tasks.push(task);
+        send_codiller_task(
+            key,
+            &collider_manager,
+            &chunk_map,
+            &mut collider_update_tasks_manager,
+            &mut collider_tasks,
+        );
+    }
+}
+
+// 没有加载的区块先读取或者生成
+fn load_chunks(
+    keys: impl Iterator<Item = ChunkKey>,
+    chunk_map: &mut ChunkMap,
+    db: &mut MapDataBase,
+    db_save_task: &mut DbSaveTasks,
+    other_tree_tasks_map: &mut OtherTreeTasksMap,
+) {
+    for key in keys {
+        if !chunk_map.map_data.contains_key(&key) {
+            let voxels = db.find_by_chunk_key(key, db_save_task, other_tree_tasks_map);
+            chunk_map.write_chunk(key, voxels);
+        }
+    }
+}
+
+/**
+ * 一次修改多个方块之后 记录修改 保存区块 把整个区块发送给所有客户端 更新碰撞
+ * 返回修改的区块数
+ */
+#[allow(clippy::too_many_arguments)]
+pub fn commit_edits(
+    edits: Vec<(ChunkKey, Vec<(usize, Voxel)>)>,
+    chunk_map: &ChunkMap,
+    db: &mut MapDataBase,
+    db_save_task: &mut DbSaveTasks,
+    tasks: &mut ChunkResultTasks,
+    collider_manager: &ColliderManager,
+    collider_update_tasks_manager: &mut ColliderUpdateTasksManager,
+    collider_tasks: &mut ColliderTasksManager,
+    light_cache: &mut LightCache,
+) -> usize {
+    let pool = AsyncComputeTaskPool::get();
+    let mut collider_keys = HashSet::new();
+    for (chunk_key, edits) in edits.iter() {
+        let chunk_key = *chunk_key;
+        db.record_edits(chunk_key, edits);
+        light_cache.refresh_chunk(chunk_key);
+        let voxels = chunk_map.map_data[&chunk_key].clone();
+        let save_voxels = voxels.clone();
+        let task = pool.spawn(async move { (chunk_key.as_u8_array(), save_voxels) });
+        db_save_task.tasks.push(task);
+        let task =
+            pool.spawn(async move { (0, chunk_key, update_chunk_message(chunk_key, voxels)) });
+        tasks.tasks.push(task);
+        // 边界上的方块会影响相邻区块的碰撞
+        collider_keys.insert(chunk_key);
+        for offset in [
+            IVec3::X,
+            IVec3::NEG_X,
+            IVec3::Y,
+            IVec3::NEG_Y,
+            IVec3::Z,
+            IVec3::NEG_Z,
+        ] {
+            collider_keys.insert(chunk_key.add_ivec3(offset));
+        }
+    }
+    for key in collider_keys {
+        send_codiller_task(
+            key,
+            collider_manager,
+            chunk_map,
+            collider_update_tasks_manager,
+            collider_tasks,
+        );
+    }
+    edits.len()
+}
+
+// 玩家当前的位置 修改方块和笔刷按照它检查距离
+fn player_translation(
+    client_id: u64,
+    lobby: &ServerLobby,
+    player_transforms: &Query<&Transform>,
+) -> Result<Vec3, String> {
+    lobby
+        .players
+        .get(&client_id)
+        .and_then(|entity| player_transforms.get(*entity).ok())
+        .map(|transform| transform.translation)
+        .ok_or_else(|| String::from("Player is not spawned"))
+}
+
+// 在线玩家的 client_id 和位置
+fn lobby_positions(lobby: &ServerLobby, player_transforms: &Query<&Transform>) -> Vec<(u64, Vec3)> {
+    lobby
+        .players
+        .iter()
+        .filter_map(|(client_id, entity)| {
+            let transform = player_transforms.get(*entity).ok()?;
+            Some((*client_id, transform.translation))
+        })
+        .collect()
+}
+
+/**
+ * 玩家修改或者交互一个方块前的检查 按照服务端记录的玩家位置和权限
+ * 创造世界中不受高度限制
+ */
+#[allow(clippy::too_many_arguments)]
+fn check_client_edit(
+    client_id: u64,
+    block: IVec3,
+    permissions: &Permissions,
+    db: &MapDataBase,
+    lobby: &ServerLobby,
+    player_transforms: &Query<&Transform>,
+    build_limit: &BuildLimit,
+    spawn_protection: &SpawnProtection,
+    spawn_point: &SpawnPoint,
+) -> Result<(), String> {
+    let player = player_translation(client_id, lobby, player_transforms)?;
+    check_player_edit(
+        player,
+        block,
+        permissions.level_of(client_id, db),
+        is_creative(player),
+        build_limit,
+        spawn_protection,
+        spawn_point.0,
+    )
+}
+
+/**
+ * 回复等待生成的区块 生成结果由 collect_generated_chunks 写入 ChunkMap
+ * 请求时队列已满的区块在这里重新请求
+ */
+pub fn reply_generated_chunks(
+    mut pending_replies: ResMut<PendingChunkReplies>,
+    chunk_map: Res<ChunkMap>,
+    mut gen_pool: ResMut<GenPool>,
+    db: Res<MapDataBase>,
+    mut tasks: ResMut<ChunkResultTasks>,
+) {
+    let pool = AsyncComputeTaskPool::get();
+    pending_replies.waiting.retain(|key, clients| {
+        let Some(voxels) = chunk_map.map_data.get(key) else {
+            gen_pool.try_request(db.seed, *key);
+            return true;
+        };
+        let key = *key;
+        let message = full_chunk_message(key, voxels.clone());
+        for client_id in clients.drain(..) {
+            let message = message.clone();
+            tasks
+                .tasks
+                .push(pool.spawn(async move { (client_id, key, message) }));
+        }
+        false
+    });
+}
+
+// 客户端请求的整个区块 只有一种体素时不需要压缩
+pub fn full_chunk_message(key: ChunkKey, voxels: Vec<Voxel>) -> Vec<u8> {
+    let (buffer, tree) = compress(voxels.clone());
+    if buffer.len() == 0 {
+        bincode::serialize(&ChunkResult::ChunkSame((key, voxels[0]))).unwrap()
+    } else {
+        bincode::serialize(&ChunkResult::ChunkData {
+            key,
+            data: (buffer, tree),
+        })
+        .unwrap()
+    }
+}
+
+// 更新整个区块的消息 只有一种体素时不需要压缩
+fn update_chunk_message(key: ChunkKey, voxels: Vec<Voxel>) -> Vec<u8> {
+    let (buffer, tree) = compress(voxels.clone());
+    if buffer.len() == 0 {
+        bincode::serialize(&ChunkResult::UpdateChunkSame((key, voxels[0]))).unwrap()
+    } else {
+        bincode::serialize(&ChunkResult::UpdateChunkData {
+            key,
+            data: (buffer, tree),
+        })
+        .unwrap()
+    }
+}
+
 fn send_codiller_task(
     chunk_key: ChunkKey,
     collider_manager: &ColliderManager,
@@ -290,16 +1157,29 @@ fn send_codiller_task(
     }
 }
 
-pub fn send_message(mut tasks: ResMut<ChunkResultTasks>, mut server: ResMut<RenetServer>) {
+// 广播的更新直接发送给区块所在世界的玩家 单个客户端请求的区块按照预算排队发送
+pub fn send_message(
+    mut tasks: ResMut<ChunkResultTasks>,
+    mut server: ResMut<RenetServer>,
+    mut send_queue: ResMut<ChunkSendQueue>,
+    server_lobby: Res<ServerLobby>,
+    player_transforms: Query<&Transform>,
+) {
     let l = tasks.tasks.len().min(16);
     for ele in tasks.tasks.drain(..l) {
-        if let Some((client_id, message)) =
+        if let Some((client_id, chunk_key, message)) =
             futures_lite::future::block_on(futures_lite::future::poll_once(ele))
         {
             if client_id == 0 {
-                server.broadcast_message(ServerChannel::ChunkResult, message);
+                send_to_world(
+                    &mut server,
+                    lobby_positions(&server_lobby, &player_transforms),
+                    WorldId::of_chunk(chunk_key),
+                    ServerChannel::ChunkResult,
+                    message,
+                );
             } else {
-                server.send_message(client_id, ServerChannel::ChunkResult, message);
+                send_queue.push(client_id, chunk_key, message);
             }
         }
     }
@@ -309,7 +1189,45 @@ pub struct ChunkDataPlugin;
 
 impl Plugin for ChunkDataPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
+        let max_fill_volume = app
+            .world
+            .get_resource::<ServerConfig>()
+            .map_or(DEFAULT_MAX_FILL_VOLUME, |config| config.max_fill_volume);
+        let build_limit = app.world.get_resource::<ServerConfig>().map_or(
+            BuildLimit {
+                min_y: WorldHeight::default().min_block_y(),
+                max_y: WorldHeight::default().max_block_y(),
+            },
+            |config| BuildLimit {
+                min_y: config.build_min_y,
+                max_y: config.build_max_y,
+            },
+        );
         app.insert_resource(ChunkResultTasks { tasks: Vec::new() });
-        app.add_systems(Update, (deal_chunk_query_system, send_message));
+        app.insert_resource(FillLimit(max_fill_volume));
+        app.insert_resource(build_limit);
+        app.insert_resource(WorldBorder(
+            app.world
+                .get_resource::<ServerConfig>()
+                .map_or(0, |config| config.world_border),
+        ));
+        app.insert_resource(SpawnProtection {
+            radius: app
+                .world
+                .get_resource::<ServerConfig>()
+                .map_or(0, |config| config.spawn_protection),
+        });
+        app.init_resource::<PendingRegens>();
+        app.init_resource::<EditHistory>();
+        app.init_resource::<PendingChunkReplies>();
+        app.add_systems(
+            Update,
+            (
+                deal_chunk_query_system,
+                regen_chunks_system.after(collect_generated_chunks),
+                reply_generated_chunks.after(collect_generated_chunks),
+                send_message,
+            ),
+        );
     }
 }
@@ -0,0 +1,226 @@
+// 自动保存
+// 按照配置的间隔 把还没有写入的区块和在线玩家的数据写入数据库并刷新到磁盘
+// 在主线程中只复制一份需要保存的数据 写入和刷新在后台线程中执行 不会卡住模拟
+// 后台写入期间暂停 save_db_task_system 之后的修改等快照写完再写入 不会被快照覆盖
+// 保存成功后 快照之前的修改日志已经没有用了 压缩掉
+
+use std::time::{Duration, Instant};
+
+use bevy::{
+    prelude::{Plugin, Query, Res, ResMut, Resource, Time, Transform, Update},
+    tasks::{IoTaskPool, Task},
+    utils::HashMap,
+};
+use bevy_renet::renet::RenetServer;
+
+use crate::voxel_world::{
+    map_database::{DbSaveTasks, MapDataBase},
+    player_state::{player_state_key, PlayerOnTimeState, PlayerState},
+    voxel::Voxel,
+};
+
+use super::{config::ServerConfig, player::Player, server_command::reply};
+
+// 一次保存的结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct AutosaveReport {
+    pub chunks: usize,
+    pub players: usize,
+    pub duration: Duration,
+    pub error: Option<String>,
+}
+
+#[derive(Resource)]
+pub struct Autosave {
+    // 0 表示不自动保存 只能通过 /save 保存
+    pub interval: f32,
+    pub elapsed: f32,
+    // 请求立即保存的客户端 保存完成后回复
+    pub requested: Vec<u64>,
+    // 保存任务 等待回复的客户端 开始时修改日志的位置
+    running: Option<(Task<AutosaveReport>, Vec<u64>, Option<u64>)>,
+}
+
+impl Autosave {
+    pub fn new(interval: f32) -> Self {
+        Self {
+            interval,
+            elapsed: 0.0,
+            requested: Vec::new(),
+            running: None,
+        }
+    }
+}
+
+pub struct AutosavePlugin;
+
+impl Plugin for AutosavePlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        let interval = app
+            .world
+            .get_resource::<ServerConfig>()
+            .map_or(0.0, |config| config.autosave_interval);
+        if interval > 0.0 {
+            println!("自动保存间隔: {}秒", interval);
+        }
+        app.insert_resource(Autosave::new(interval));
+        app.add_systems(Update, autosave_system);
+    }
+}
+
+/**
+ * 写入区块和玩家数据 然后刷新到磁盘
+ * 在后台线程中执行
+ */
+pub fn write_snapshot(
+    db: &sled::Db,
+    chunks: &HashMap<[u8; 8], Vec<Voxel>>,
+    players: &[(String, PlayerState)],
+) -> AutosaveReport {
+    let start = Instant::now();
+    let write = || -> sled::Result<()> {
+        for (key, voxels) in chunks.iter() {
+            db.insert(key, bincode::serialize(voxels).unwrap())?;
+        }
+        for (username, state) in players.iter() {
+            db.insert(
+                player_state_key(username),
+                bincode::serialize(state).unwrap(),
+            )?;
+        }
+        db.flush()?;
+        Ok(())
+    };
+    let error = write().err().map(|err| err.to_string());
+    AutosaveReport {
+        chunks: chunks.len(),
+        players: players.len(),
+        duration: start.elapsed(),
+        error,
+    }
+}
+
+// 取出已经完成的保存任务 没有完成的留给 save_db_task_system
+fn take_dirty_chunks(db_save_tasks: &mut DbSaveTasks) -> HashMap<[u8; 8], Vec<Voxel>> {
+    let mut chunks = HashMap::new();
+    let mut unfinished = Vec::new();
+    for mut task in db_save_tasks.tasks.drain(..) {
+        match futures_lite::future::block_on(futures_lite::future::poll_once(&mut task)) {
+            // 同一个区块只保留最后一次的数据
+            Some((key, voxels)) => {
+                chunks.insert(key, voxels);
+            }
+            None => unfinished.push(task),
+        }
+    }
+    db_save_tasks.tasks = unfinished;
+    chunks
+}
+
+fn autosave_system(
+    time: Res<Time>,
+    mut autosave: ResMut<Autosave>,
+    mut db_save_tasks: ResMut<DbSaveTasks>,
+    mut map_database: ResMut<MapDataBase>,
+    players: Query<(&Player, &Transform, &PlayerOnTimeState)>,
+    mut server: ResMut<RenetServer>,
+) {
+    if let Some((task, clients, journal_mark)) = autosave.running.as_mut() {
+        let Some(report) = futures_lite::future::block_on(futures_lite::future::poll_once(task))
+        else {
+            return;
+        };
+        let (ok, text) = match &report.error {
+            None => (
+                true,
+                format!(
+                    "Saved {} chunks and {} players in {} ms",
+                    report.chunks,
+                    report.players,
+                    report.duration.as_millis()
+                ),
+            ),
+            Some(err) => (false, format!("Save failed: {}", err)),
+        };
+        println!(
+            "自动保存: {}个区块 {}个玩家 用时{}毫秒 {:?}",
+            report.chunks,
+            report.players,
+            report.duration.as_millis(),
+            report.error
+        );
+        for client_id in clients.drain(..) {
+            reply(&mut server, client_id, ok, text.clone());
+        }
+        if let (None, Some(mark), Some(journal)) =
+            (&report.error, *journal_mark, map_database.journal.as_mut())
+        {
+            if let Err(err) = journal.compact(mark) {
+                println!("压缩修改日志失败{:?}", err);
+            }
+        }
+        autosave.running = None;
+        db_save_tasks.paused = false;
+    }
+
+    autosave.elapsed += time.delta_seconds();
+    let due = autosave.interval > 0.0 && autosave.elapsed >= autosave.interval;
+    if !due && autosave.requested.is_empty() {
+        return;
+    }
+    autosave.elapsed = 0.0;
+
+    let chunks = take_dirty_chunks(&mut db_save_tasks);
+    // 还有没完成的保存任务时 日志中的修改不一定都在快照中 不压缩
+    let journal_mark = map_database
+        .journal
+        .as_ref()
+        .filter(|_| db_save_tasks.tasks.is_empty())
+        .map(|journal| journal.position());
+    let player_states: Vec<(String, PlayerState)> = players
+        .iter()
+        .map(|(player, transform, state)| {
+            let mut state = state.0.clone();
+            state.position = transform.translation.into();
+            (player.username.clone(), state)
+        })
+        .collect();
+    // sled::Db 内部是共享的 复制一份给后台线程
+    let db = map_database.db.clone();
+    let task = IoTaskPool::get().spawn(async move { write_snapshot(&db, &chunks, &player_states) });
+    let clients = std::mem::take(&mut autosave.requested);
+    autosave.running = Some((task, clients, journal_mark));
+    db_save_tasks.paused = true;
+}
+
+#[test]
+fn test_write_snapshot() {
+    use crate::voxel_world::{chunk::ChunkKey, player_state::StoragePlayerState};
+    use bevy::prelude::IVec3;
+
+    let db = sled::Config::new().temporary(true).open().unwrap();
+    let key = ChunkKey(IVec3::new(1, 2, 3)).as_u8_array();
+    let mut chunks = HashMap::new();
+    chunks.insert(key, vec![Voxel::EMPTY; crate::CHUNK_VOLUME as usize]);
+    let state = PlayerState {
+        position: [1.0, 2.0, 3.0],
+        toolbar: Default::default(),
+    };
+    let report = write_snapshot(&db, &chunks, &[(String::from("steve"), state.clone())]);
+    assert_eq!((report.chunks, report.players), (1, 1));
+    assert_eq!(report.error, None);
+
+    let saved: Vec<Voxel> = bincode::deserialize(&db.get(key).unwrap().unwrap()).unwrap();
+    assert_eq!(saved, chunks[&key]);
+    let map_database = MapDataBase {
+        db,
+        seed: 0,
+        journal: None,
+    };
+    assert_eq!(
+        map_database
+            .get_player_state(String::from("steve"))
+            .map(|state| state.position),
+        Some(state.position)
+    );
+}
@@ -0,0 +1,175 @@
+// 封禁列表 保存在文件中 连接时检查
+// 可以按照用户名或者 IP 封禁 封禁在线的玩家时同时记录他的 IP
+// 本机地址和未指定的地址永远不会被封禁 否则本机的管理员也无法连接
+
+use std::{io::Write, net::IpAddr};
+
+use bevy::prelude::Resource;
+use serde::{Deserialize, Serialize};
+
+use crate::BANLIST_PATH;
+
+use super::player::ServerAdmins;
+
+// 可以按照 IP 封禁的地址
+pub fn is_bannable_ip(ip: IpAddr) -> bool {
+    !ip.is_loopback() && !ip.is_unspecified()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BanEntry {
+    // 直接封禁 IP 时是 IP 的文本
+    pub username: String,
+    #[serde(default)]
+    pub ip: Option<IpAddr>,
+    pub reason: Option<String>,
+}
+
+impl BanEntry {
+    pub fn matches(&self, username: &str, ip: Option<IpAddr>) -> bool {
+        self.username == username || (self.ip.is_some_and(is_bannable_ip) && self.ip == ip)
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Resource)]
+pub struct BanList {
+    pub entries: Vec<BanEntry>,
+}
+
+impl BanList {
+    // 没有文件时返回空的列表
+    pub fn load() -> Self {
+        match std::fs::File::open(BANLIST_PATH) {
+            Ok(file) => ron::de::from_reader(file).unwrap_or_else(|err| {
+                println!("封禁列表读取失败: {}", err);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    // 保存失败时只打印 内存中的封禁仍然有效
+    pub fn save(&self) {
+        let res = match ron::to_string(self) {
+            Ok(res) => res,
+            Err(err) => {
+                println!("封禁列表保存失败: {}", err);
+                return;
+            }
+        };
+        if let Err(err) =
+            std::fs::File::create(BANLIST_PATH).and_then(|mut file| file.write_all(res.as_bytes()))
+        {
+            println!("封禁列表保存失败: {}", err);
+        }
+    }
+
+    // 用户名或者 IP 被封禁 配置的管理员只按用户名检查
+    pub fn get(
+        &self,
+        username: &str,
+        ip: Option<IpAddr>,
+        admins: &ServerAdmins,
+    ) -> Option<&BanEntry> {
+        let ip = ip.filter(|_| !admins.0.contains(username));
+        self.entries
+            .iter()
+            .find(|entry| entry.matches(username, ip))
+    }
+
+    /**
+     * 封禁用户名 ip 是玩家当前的地址
+     * target 是 IP 地址时直接封禁这个 IP
+     * 已经封禁过的只更新原因和地址
+     */
+    pub fn ban(&mut self, target: String, ip: Option<IpAddr>, reason: Option<String>) {
+        self.insert(target, ip, reason);
+        self.save();
+    }
+
+    // 只修改内存中的列表 本机地址不记录
+    fn insert(&mut self, target: String, ip: Option<IpAddr>, reason: Option<String>) {
+        let ip = target
+            .parse::<IpAddr>()
+            .ok()
+            .or(ip)
+            .filter(|ip| is_bannable_ip(*ip));
+        match self
+            .entries
+            .iter_mut()
+            .find(|entry| entry.username == target)
+        {
+            Some(entry) => {
+                entry.reason = reason;
+                entry.ip = ip.or(entry.ip);
+            }
+            None => self.entries.push(BanEntry {
+                username: target,
+                ip,
+                reason,
+            }),
+        }
+    }
+
+    // 按照用户名或者 IP 解除
+    pub fn unban(&mut self, target: &str) -> bool {
+        let len = self.entries.len();
+        let ip = target.parse::<IpAddr>().ok();
+        self.entries
+            .retain(|entry| entry.username != target && (ip.is_none() || entry.ip != ip));
+        if self.entries.len() != len {
+            self.save();
+            return true;
+        }
+        false
+    }
+}
+
+#[test]
+fn test_ban_by_ip() {
+    let mut ban_list = BanList::default();
+    let ip: IpAddr = "10.0.0.7".parse().unwrap();
+    // 不保存到文件
+    ban_list.entries.push(BanEntry {
+        username: String::from("alice"),
+        ip: Some(ip),
+        reason: None,
+    });
+    let admins = ServerAdmins::default();
+    assert!(ban_list.get("alice", None, &admins).is_some());
+    // 换了用户名 同一个地址仍然被封禁
+    assert!(ban_list.get("bob", Some(ip), &admins).is_some());
+    assert!(ban_list
+        .get("bob", Some("10.0.0.8".parse().unwrap()), &admins)
+        .is_none());
+    assert!(ban_list.get("bob", None, &admins).is_none());
+}
+
+#[test]
+fn test_ban_local_player() {
+    let mut ban_list = BanList::default();
+    let local: IpAddr = "127.0.0.1".parse().unwrap();
+    let admins = ServerAdmins(["admin".to_string()].into_iter().collect());
+    // 封禁本机连接的玩家只记录用户名
+    ban_list.insert(String::from("alice"), Some(local), None);
+    assert_eq!(ban_list.entries[0].ip, None);
+    assert!(ban_list.get("alice", Some(local), &admins).is_some());
+    assert!(ban_list.get("bob", Some(local), &admins).is_none());
+    assert!(ban_list.get("admin", Some(local), &admins).is_none());
+    // 直接封禁本机地址和未指定的地址不起作用
+    ban_list.insert(String::from("127.0.0.1"), None, None);
+    ban_list.insert(String::from("::"), None, None);
+    assert!(ban_list.get("admin", Some(local), &admins).is_none());
+    assert!(ban_list.get("bob", Some(local), &admins).is_none());
+    // 文件中旧的本机地址也不匹配 配置的管理员不按 IP 检查
+    let remote: IpAddr = "10.0.0.7".parse().unwrap();
+    ban_list.entries.push(BanEntry {
+        username: String::from("carol"),
+        ip: Some(local),
+        reason: None,
+    });
+    ban_list.insert(String::from("dave"), Some(remote), None);
+    assert!(ban_list.get("bob", Some(local), &admins).is_none());
+    assert!(ban_list.get("bob", Some(remote), &admins).is_some());
+    assert!(ban_list.get("admin", Some(remote), &admins).is_none());
+}
@@ -0,0 +1,173 @@
+// 笔刷
+// 右键放置时按照笔刷修改球体或者立方体内的方块 一次点击作为一次修改发送 可以整体撤销
+// Normal 只放置到空气中 ReplaceOnly 只替换不是空气的方块 ReplaceType 只替换指定的体素
+// 笔刷不消耗物品 需要管理员权限 见 permission::command_level("brush")
+
+use bevy::prelude::IVec3;
+use ndshape::ConstShape;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    voxel_world::{
+        chunk_map::ChunkMap,
+        voxel::{BasicStone, Voxel, VoxelMaterial},
+    },
+    ChunkShape,
+};
+
+use super::{
+    edit_history::BlockEdit,
+    fill::{block_to_chunk, FillReport},
+};
+
+// 笔刷的最大半径(方块) 半径 8 的立方体是 4913 个方块
+pub const MAX_BRUSH_RADIUS: u32 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BrushMode {
+    Normal,
+    ReplaceOnly,
+    ReplaceType(Voxel),
+}
+
+impl BrushMode {
+    // 这个位置原来的体素是否可以被修改
+    pub fn accepts(&self, old: Voxel) -> bool {
+        match self {
+            BrushMode::Normal => old.id == Voxel::EMPTY.id,
+            BrushMode::ReplaceOnly => old.id != Voxel::EMPTY.id,
+            BrushMode::ReplaceType(target) => old.id == target.id,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BrushShape {
+    Sphere,
+    Cube,
+}
+
+/**
+ * 笔刷覆盖的方块坐标
+ * 球体包括到中心的距离不超过 radius + 0.5 的方块 半径 0 时只有中心
+ */
+pub fn brush_blocks(center: IVec3, radius: u32, shape: BrushShape) -> Vec<IVec3> {
+    let r = radius.min(MAX_BRUSH_RADIUS) as i32;
+    let limit = (r as f32 + 0.5).powi(2);
+    let mut blocks = Vec::new();
+    for x in -r..=r {
+        for y in -r..=r {
+            for z in -r..=r {
+                let offset = IVec3::new(x, y, z);
+                if shape == BrushShape::Sphere && offset.length_squared() as f32 > limit {
+                    continue;
+                }
+                blocks.push(center + offset);
+            }
+        }
+    }
+    blocks
+}
+
+/**
+ * 按照笔刷修改方块 和 fill_region 一样跳过基岩和没有加载的区块
+ * mode 不接受的方块不修改
+ */
+pub fn brush_region(
+    chunk_map: &mut ChunkMap,
+    blocks: &[IVec3],
+    mode: BrushMode,
+    voxel: Voxel,
+) -> FillReport {
+    let mut report = FillReport::default();
+    for pos in blocks {
+        let (chunk_key, xyz) = block_to_chunk(*pos);
+        let Some(voxels) = chunk_map.map_data.get_mut(&chunk_key) else {
+            continue;
+        };
+        let index = ChunkShape::linearize(xyz) as usize;
+        let old_voxel = voxels[index];
+        if old_voxel == voxel || !mode.accepts(old_voxel) {
+            continue;
+        }
+        if old_voxel.id == BasicStone::ID {
+            report.skipped += 1;
+            continue;
+        }
+        voxels[index] = voxel;
+        report.changes.push(BlockEdit {
+            chunk_key,
+            index,
+            old: old_voxel,
+            new: voxel,
+        });
+    }
+    report
+}
+
+#[test]
+fn test_brush_region() {
+    use crate::{
+        server::fill::fill_chunk_keys,
+        voxel_world::voxel::{Sand, Stone},
+        CHUNK_VOLUME,
+    };
+
+    assert_eq!(
+        brush_blocks(IVec3::ZERO, 0, BrushShape::Sphere),
+        vec![IVec3::ZERO]
+    );
+    assert_eq!(brush_blocks(IVec3::ZERO, 1, BrushShape::Cube).len(), 27);
+    // 半径 1 的球体去掉了 8 个角
+    assert_eq!(brush_blocks(IVec3::ZERO, 1, BrushShape::Sphere).len(), 19);
+    assert_eq!(
+        brush_blocks(IVec3::ZERO, 100, BrushShape::Cube).len(),
+        (MAX_BRUSH_RADIUS as usize * 2 + 1).pow(3)
+    );
+
+    // 下半部分是石头 其中一个是沙子
+    let mut chunk_map = ChunkMap::new();
+    for key in fill_chunk_keys(IVec3::splat(-2), IVec3::splat(2)).unwrap() {
+        chunk_map.write_chunk(key, vec![Voxel::EMPTY; CHUNK_VOLUME as usize]);
+    }
+    let blocks = brush_blocks(IVec3::ZERO, 1, BrushShape::Cube);
+    let lower: Vec<IVec3> = blocks.iter().copied().filter(|pos| pos.y < 0).collect();
+    brush_region(
+        &mut chunk_map,
+        &lower,
+        BrushMode::Normal,
+        Stone::into_voxel(),
+    );
+    brush_region(
+        &mut chunk_map,
+        &[IVec3::new(0, -1, 0)],
+        BrushMode::ReplaceOnly,
+        Sand::into_voxel(),
+    );
+
+    // 只放置到空气中
+    let report = brush_region(
+        &mut chunk_map,
+        &blocks,
+        BrushMode::Normal,
+        Stone::into_voxel(),
+    );
+    assert_eq!(report.changed(), 18);
+    assert!(report.changes.iter().all(|edit| edit.old == Voxel::EMPTY));
+    // 只替换沙子
+    let report = brush_region(
+        &mut chunk_map,
+        &blocks,
+        BrushMode::ReplaceType(Sand::into_voxel()),
+        Voxel::EMPTY,
+    );
+    assert_eq!(report.changed(), 1);
+    // 替换所有不是空气的方块
+    let report = brush_region(
+        &mut chunk_map,
+        &blocks,
+        BrushMode::ReplaceOnly,
+        Voxel::EMPTY,
+    );
+    assert_eq!(report.changed(), 26);
+}
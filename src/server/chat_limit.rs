@@ -0,0 +1,231 @@
+// 聊天频率限制
+// 每个玩家在一段时间内最多发送几条聊天和私聊 超过的消息直接拒绝 不会广播
+// 连续被拒绝多次后自动禁言一段时间 按照用户名记录 重新连接不会解除 管理员不受限制
+
+use std::collections::VecDeque;
+
+use bevy::{
+    prelude::{Res, ResMut, Resource, Time},
+    utils::HashMap,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{config::ServerConfig, permission::PermissionLevel};
+
+/**
+ * 聊天频率限制的配置 见 ServerConfig::chat_limit
+ */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChatLimit {
+    // window 秒内最多发送的消息数 0 表示不限制
+    pub messages: u32,
+    pub window: f32,
+    // 连续被拒绝这么多次后自动禁言 0 表示不禁言
+    pub mute_after: u32,
+    // 自动禁言的时间(秒)
+    pub mute_duration: f32,
+}
+
+impl Default for ChatLimit {
+    fn default() -> Self {
+        Self {
+            messages: 5,
+            window: 10.0,
+            mute_after: 3,
+            mute_duration: 60.0,
+        }
+    }
+}
+
+impl ChatLimit {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.window.is_finite() || self.window <= 0.0 {
+            return Err(format!(
+                "chat_limit.window 必须大于 0 当前是 {}",
+                self.window
+            ));
+        }
+        if !self.mute_duration.is_finite() || self.mute_duration < 0.0 {
+            return Err(format!(
+                "chat_limit.mute_duration 不能小于 0 当前是 {}",
+                self.mute_duration
+            ));
+        }
+        Ok(())
+    }
+}
+
+// 检查的结果 时间都是还需要等待的秒数
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChatVerdict {
+    Allowed,
+    Limited { retry_after: f32 },
+    Muted { remaining: f32 },
+}
+
+impl ChatVerdict {
+    // 被拒绝时回复给玩家的提示
+    pub fn reason(&self) -> Option<String> {
+        match self {
+            ChatVerdict::Allowed => None,
+            ChatVerdict::Limited { retry_after } => Some(format!(
+                "You are sending messages too quickly, wait {:.1}s",
+                retry_after
+            )),
+            ChatVerdict::Muted { remaining } => {
+                Some(format!("You are muted for {:.0}s", remaining.ceil()))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct ChatHistory {
+    // 窗口内发送的消息的时间
+    sent: VecDeque<f64>,
+    // 连续被拒绝的次数
+    rejected: u32,
+    muted_until: Option<f64>,
+}
+
+#[derive(Debug, Default, Resource)]
+pub struct ChatLimiter {
+    players: HashMap<String, ChatHistory>,
+}
+
+impl ChatLimiter {
+    /**
+     * 玩家在 now 秒时发送一条消息 允许时记录下来
+     * 有一条消息被允许后连续被拒绝的次数重新计算
+     */
+    pub fn check(&mut self, username: &str, now: f64, limit: &ChatLimit) -> ChatVerdict {
+        if limit.messages == 0 {
+            return ChatVerdict::Allowed;
+        }
+        let history = self.players.entry(username.to_string()).or_default();
+        if let Some(until) = history.muted_until {
+            if now < until {
+                return ChatVerdict::Muted {
+                    remaining: (until - now) as f32,
+                };
+            }
+            history.muted_until = None;
+        }
+        let window = limit.window as f64;
+        while matches!(history.sent.front(), Some(sent) if now - sent >= window) {
+            history.sent.pop_front();
+        }
+        if history.sent.len() < limit.messages as usize {
+            history.sent.push_back(now);
+            history.rejected = 0;
+            return ChatVerdict::Allowed;
+        }
+        history.rejected += 1;
+        if limit.mute_after > 0 && history.rejected >= limit.mute_after {
+            history.rejected = 0;
+            history.sent.clear();
+            history.muted_until = Some(now + limit.mute_duration as f64);
+            return ChatVerdict::Muted {
+                remaining: limit.mute_duration,
+            };
+        }
+        ChatVerdict::Limited {
+            retry_after: (history.sent[0] + window - now) as f32,
+        }
+    }
+
+    // 聊天和私聊共用 管理员不受限制 被拒绝时返回提示给玩家的信息
+    pub fn check_player(
+        &mut self,
+        username: &str,
+        level: PermissionLevel,
+        now: f64,
+        limit: &ChatLimit,
+    ) -> Option<String> {
+        if level >= PermissionLevel::Admin {
+            return None;
+        }
+        self.check(username, now, limit).reason()
+    }
+
+    // 删除窗口内没有消息也没有被禁言的玩家 这些玩家的下一条消息一定被允许
+    pub fn prune(&mut self, now: f64, limit: &ChatLimit) {
+        let window = limit.window as f64;
+        self.players.retain(|_, history| {
+            let muted = history.muted_until.map_or(false, |until| now < until);
+            let recent = history
+                .sent
+                .back()
+                .map_or(false, |sent| now - sent < window);
+            muted || recent
+        });
+    }
+}
+
+pub fn prune_chat_limiter(
+    time: Res<Time>,
+    config: Res<ServerConfig>,
+    mut chat_limiter: ResMut<ChatLimiter>,
+) {
+    chat_limiter.prune(time.elapsed_seconds_f64(), &config.chat_limit);
+}
+
+#[test]
+fn test_chat_limiter() {
+    let limit = ChatLimit {
+        messages: 2,
+        window: 10.0,
+        mute_after: 2,
+        mute_duration: 30.0,
+    };
+    let mut limiter = ChatLimiter::default();
+    assert_eq!(limiter.check("a", 0.0, &limit), ChatVerdict::Allowed);
+    assert_eq!(limiter.check("a", 1.0, &limit), ChatVerdict::Allowed);
+    assert_eq!(
+        limiter.check("a", 2.0, &limit),
+        ChatVerdict::Limited { retry_after: 8.0 }
+    );
+    // 其他玩家不受影响
+    assert_eq!(limiter.check("b", 2.0, &limit), ChatVerdict::Allowed);
+    // 第一条消息离开窗口后可以继续发送
+    assert_eq!(limiter.check("a", 10.0, &limit), ChatVerdict::Allowed);
+
+    // 连续被拒绝两次后禁言
+    assert!(matches!(
+        limiter.check("a", 10.5, &limit),
+        ChatVerdict::Limited { .. }
+    ));
+    assert_eq!(
+        limiter.check("a", 10.5, &limit),
+        ChatVerdict::Muted { remaining: 30.0 }
+    );
+    assert_eq!(
+        limiter.check("a", 20.5, &limit),
+        ChatVerdict::Muted { remaining: 20.0 }
+    );
+    assert_eq!(limiter.check("a", 40.5, &limit), ChatVerdict::Allowed);
+
+    // 窗口和禁言都过期的玩家被删除
+    limiter.prune(45.0, &limit);
+    assert_eq!(limiter.players.len(), 1);
+    limiter.prune(50.5, &limit);
+    assert!(limiter.players.is_empty());
+    assert_eq!(
+        limiter.check_player("a", PermissionLevel::Admin, 50.5, &limit),
+        None
+    );
+    assert!(limiter.players.is_empty());
+
+    // 0 表示不限制
+    let unlimited = ChatLimit {
+        messages: 0,
+        ..limit
+    };
+    for i in 0..100 {
+        assert_eq!(
+            limiter.check("c", i as f64 * 0.01, &unlimited),
+            ChatVerdict::Allowed
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use bevy::prelude::{Last, Plugin, Res, ResMut, Update};
+use bevy::prelude::{IntoSystemConfigs, Last, Plugin, Res, ResMut, Update, Vec3};
 
 use crate::{
     common::ServerClipSpheres,
@@ -8,55 +8,132 @@ use crate::{
             find_chunk_keys_by_sphere_to_full_height, generate_offset_resource, NeighbourOffset,
         },
         chunk_map::ChunkMap,
-        map_database::{save_db_task_system, DbSaveTasks, MapDataBase},
+        journal::{close_journal_system, EditJournal},
+        map_database::{save_db_task_system, DbSaveTasks, MapDataBase, WorldSeed},
+        spawn::{find_safe_spawn, SpawnPoint},
+        world::set_worlds,
     },
-    VIEW_RADIUS, WORD_PATH,
+    WORD_PATH,
+};
+
+use super::{
+    async_chunk::PendingRegens,
+    config::ServerConfig,
+    gen_pool::{GenPool, GEN_QUEUE_CAPACITY},
 };
 
 /**
  * 服务端生成 chunk数据
+ * 保存过的区块直接读取 没有保存过的交给生成线程池 队列满了下一帧再请求
  */
 pub fn server_chunk_generate_system(
     mut chunk_map: ResMut<ChunkMap>,
     neighbour_offest: Res<NeighbourOffset>,
     server_clip_spheres: Res<ServerClipSpheres>,
-    mut db: ResMut<MapDataBase>,
-    mut db_save_tasks: ResMut<DbSaveTasks>,
-    mut other_tree_tasks_map: ResMut<OtherTreeTasksMap>,
+    db: Res<MapDataBase>,
+    mut gen_pool: ResMut<GenPool>,
 ) {
+    let mut queue_full = false;
     for (_client_id, clip_spheres) in server_clip_spheres.clip_spheres.iter() {
         // 通过球体计算 chunkey
         find_chunk_keys_by_sphere_to_full_height(
             clip_spheres.new_sphere,
             neighbour_offest.0.clone(),
             |key| {
-                // 这里要判断一下获取的方法
-                // chunk_map.gen_chunk_data(key);
-                if !chunk_map.map_data.contains_key(&key) {
-                    //  这里可以判断一下是否是 已经加载的数据
-                    let data = db.find_by_chunk_key(
-                        key,
-                        db_save_tasks.as_mut(),
-                        other_tree_tasks_map.as_mut(),
-                    );
-                    chunk_map.write_chunk(key, data);
+                if chunk_map.map_data.contains_key(&key) || gen_pool.pending.contains(&key) {
+                    return;
+                }
+                match db.load_saved(key) {
+                    Ok(Some(data)) => chunk_map.write_chunk(key, data),
+                    Ok(None) => {
+                        if !queue_full && !gen_pool.try_request(db.seed, key) {
+                            queue_full = true;
+                        }
+                    }
+                    Err(e) => println!("wrong, to get Map {:?}", e),
                 }
             },
         );
     }
 }
 
+/**
+ * 取回线程池生成的区块 期间已经由其他途径加载的区块不再覆盖
+ * 重新生成的区块交给 regen_chunks_system 覆盖
+ */
+pub fn collect_generated_chunks(
+    mut chunk_map: ResMut<ChunkMap>,
+    db: Res<MapDataBase>,
+    mut gen_pool: ResMut<GenPool>,
+    mut db_save_tasks: ResMut<DbSaveTasks>,
+    mut other_tree_tasks_map: ResMut<OtherTreeTasksMap>,
+    mut pending_regens: ResMut<PendingRegens>,
+) {
+    for (key, voxels, other_trees) in gen_pool.take_results() {
+        if let Some(keep_edits) = pending_regens.generating.remove(&key) {
+            pending_regens
+                .generated
+                .push(((key, voxels, other_trees), keep_edits));
+            continue;
+        }
+        if chunk_map.map_data.contains_key(&key) {
+            continue;
+        }
+        db.save_generated(
+            key,
+            &voxels,
+            other_trees,
+            db_save_tasks.as_mut(),
+            other_tree_tasks_map.as_mut(),
+        );
+        chunk_map.write_chunk(key, voxels);
+    }
+}
+
 pub struct ServerChunkPlugin;
 
 impl Plugin for ServerChunkPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
+        let config = app
+            .world
+            .get_resource::<ServerConfig>()
+            .cloned()
+            .unwrap_or_default();
+        // 生成线程和出生点都需要按照世界生成
+        set_worlds(config.worlds.clone());
         // init MapData
-        app.insert_resource(MapDataBase::new(WORD_PATH));
-        app.insert_resource(generate_offset_resource(VIEW_RADIUS));
+        let mut db = MapDataBase::new(WORD_PATH, config.seed);
+        println!("世界种子: {}", db.seed);
+        if config.journal {
+            match EditJournal::recover(WORD_PATH, &mut db) {
+                Ok((journal, recovered)) => {
+                    if recovered > 0 {
+                        println!("上次没有正常关闭 从日志恢复了{}个修改", recovered);
+                    }
+                    db.journal = Some(journal);
+                }
+                Err(err) => println!("打开修改日志失败{:?}", err),
+            }
+        }
+        app.insert_resource(WorldSeed(db.seed));
+        let spawn_point = match config.spawn {
+            Some(spawn) => Vec3::from(spawn),
+            None => find_safe_spawn(db.seed, &config.spawn_rules),
+        };
+        println!("出生点: {:?}", spawn_point);
+        app.insert_resource(SpawnPoint(spawn_point));
+        app.insert_resource(db);
+        app.insert_resource(generate_offset_resource(config.view_radius));
         app.insert_resource(ChunkMap::new());
-        app.insert_resource(DbSaveTasks { tasks: Vec::new() });
+        app.insert_resource(DbSaveTasks::default());
+        let threads = config.gen_thread_count();
+        println!("区块生成线程: {}", threads);
+        app.insert_resource(GenPool::new(threads, GEN_QUEUE_CAPACITY));
 
-        app.add_systems(Update, server_chunk_generate_system);
-        app.add_systems(Last, save_db_task_system);
+        app.add_systems(
+            Update,
+            (collect_generated_chunks, server_chunk_generate_system).chain(),
+        );
+        app.add_systems(Last, (save_db_task_system, close_journal_system).chain());
     }
 }
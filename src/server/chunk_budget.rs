@@ -0,0 +1,175 @@
+// 区块数据的发送预算
+// 每个客户端每个 tick 最多发送预算内字节的区块数据 离玩家近的区块先发送 剩下的留到之后的 tick
+// 避免一个视距很大的客户端占满服务端的上行带宽
+// 排队期间区块可能被修改 内存中有的区块在发送时按照最新的数据重新编码 排队时的数据只用来估计大小
+
+use bevy::{
+    prelude::{IVec3, Plugin, Query, Res, ResMut, Resource, Timer, TimerMode, Transform, Update},
+    time::Time,
+    utils::HashMap,
+};
+use bevy_renet::renet::RenetServer;
+
+use crate::voxel_world::{
+    chunk::{get_chunk_key_i3_by_vec3, ChunkKey},
+    chunk_map::ChunkMap,
+};
+
+use super::{async_chunk::full_chunk_message, message_def::ServerChannel, player::ServerLobby};
+
+#[derive(Debug, Clone, Copy, Resource)]
+pub struct ChunkSendBudget {
+    // 每个客户端每个 tick 的字节数
+    pub bytes_per_tick: usize,
+}
+
+// 客户端的带宽统计
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ClientBandwidth {
+    // 最近一秒发送的字节数
+    pub bytes_per_second: usize,
+    pub total_bytes: u64,
+    // 等待发送的区块
+    pub queued: usize,
+    pub queued_bytes: usize,
+    window_bytes: usize,
+}
+
+#[derive(Debug, Resource)]
+pub struct ChunkSendQueue {
+    pending: HashMap<u64, Vec<(ChunkKey, Vec<u8>)>>,
+    pub usage: HashMap<u64, ClientBandwidth>,
+    window: Timer,
+}
+
+impl Default for ChunkSendQueue {
+    fn default() -> Self {
+        Self {
+            pending: HashMap::default(),
+            usage: HashMap::default(),
+            window: Timer::from_seconds(1.0, TimerMode::Repeating),
+        }
+    }
+}
+
+impl ChunkSendQueue {
+    pub fn push(&mut self, client_id: u64, chunk_key: ChunkKey, message: Vec<u8>) {
+        self.pending
+            .entry(client_id)
+            .or_default()
+            .push((chunk_key, message));
+    }
+}
+
+pub struct ChunkBudgetPlugin {
+    pub bytes_per_tick: usize,
+}
+
+impl Plugin for ChunkBudgetPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        println!("区块发送预算: {} 字节/tick", self.bytes_per_tick);
+        app.insert_resource(ChunkSendBudget {
+            bytes_per_tick: self.bytes_per_tick,
+        });
+        app.init_resource::<ChunkSendQueue>();
+        app.add_systems(Update, send_chunks_within_budget);
+    }
+}
+
+// 区块和玩家所在区块的距离 越小越先发送 水平距离优先
+pub fn chunk_priority(chunk_key: ChunkKey, center: IVec3) -> i32 {
+    let d = (chunk_key.0 - center).abs();
+    d.x.max(d.z) * 4 + d.y
+}
+
+/**
+ * 按照优先级取出预算内的区块 剩下的留在 pending 中
+ * 至少发送一个 保证比预算大的区块也能发出去
+ */
+pub fn take_within_budget(
+    pending: &mut Vec<(ChunkKey, Vec<u8>)>,
+    center: IVec3,
+    budget: usize,
+) -> Vec<(ChunkKey, Vec<u8>)> {
+    pending.sort_by_key(|(chunk_key, _)| chunk_priority(*chunk_key, center));
+    let mut used = 0;
+    let mut count = 0;
+    for (_, message) in pending.iter() {
+        if count > 0 && used + message.len() > budget {
+            break;
+        }
+        used += message.len();
+        count += 1;
+    }
+    pending.drain(..count).collect()
+}
+
+fn send_chunks_within_budget(
+    mut server: ResMut<RenetServer>,
+    mut queue: ResMut<ChunkSendQueue>,
+    budget: Res<ChunkSendBudget>,
+    lobby: Res<ServerLobby>,
+    time: Res<Time>,
+    players: Query<&Transform>,
+    chunk_map: Res<ChunkMap>,
+) {
+    let queue = queue.as_mut();
+    // 断开的客户端不再发送
+    let clients = server.clients_id();
+    queue
+        .pending
+        .retain(|client_id, _| clients.contains(client_id));
+    queue
+        .usage
+        .retain(|client_id, _| clients.contains(client_id));
+
+    for (client_id, pending) in queue.pending.iter_mut() {
+        let center = lobby
+            .players
+            .get(client_id)
+            .and_then(|entity| players.get(*entity).ok())
+            .map(|transform| get_chunk_key_i3_by_vec3(transform.translation))
+            .unwrap_or(IVec3::ZERO);
+        let usage = queue.usage.entry(*client_id).or_default();
+        for (chunk_key, message) in take_within_budget(pending, center, budget.bytes_per_tick) {
+            let message = chunk_map
+                .map_data
+                .get(&chunk_key)
+                .map_or(message, |voxels| {
+                    full_chunk_message(chunk_key, voxels.clone())
+                });
+            usage.window_bytes += message.len();
+            usage.total_bytes += message.len() as u64;
+            server.send_message(*client_id, ServerChannel::ChunkResult, message);
+        }
+        usage.queued = pending.len();
+        usage.queued_bytes = pending.iter().map(|(_, message)| message.len()).sum();
+    }
+    queue.pending.retain(|_, pending| !pending.is_empty());
+
+    if queue.window.tick(time.delta()).just_finished() {
+        for usage in queue.usage.values_mut() {
+            usage.bytes_per_second = usage.window_bytes;
+            usage.window_bytes = 0;
+        }
+    }
+}
+
+#[test]
+fn test_take_within_budget() {
+    let key = |x: i32| ChunkKey(IVec3::new(x, 0, 0));
+    let mut pending = vec![
+        (key(3), vec![0u8; 40]),
+        (key(0), vec![0u8; 40]),
+        (key(1), vec![0u8; 40]),
+    ];
+    let sent = take_within_budget(&mut pending, IVec3::ZERO, 100);
+    let sent: Vec<ChunkKey> = sent.into_iter().map(|(key, _)| key).collect();
+    assert_eq!(sent, vec![key(0), key(1)]);
+    assert_eq!(pending.len(), 1);
+
+    // 比预算大的区块也会发送
+    let mut pending = vec![(key(0), vec![0u8; 500])];
+    assert_eq!(take_within_budget(&mut pending, IVec3::ZERO, 100).len(), 1);
+    assert!(pending.is_empty());
+}
@@ -0,0 +1,372 @@
+// 服务端配置
+// 启动时从 server_config.toml 读取 没有文件时生成一份带注释的默认配置
+// 缺少的字段使用默认值 数值不合法时直接退出 不会带着错误的配置运行
+
+use bevy::prelude::{Plugin, Query, Res, Resource, Transform, Update, With};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use crate::{
+    voxel_world::{
+        gen_config::WorldHeight,
+        spawn::SpawnRules,
+        world::{world_origin_x, WorldConfig, MAX_WORLDS, WORLD_SPACING},
+    },
+    DEFAULT_AUTOSAVE_INTERVAL, DEFAULT_CHUNK_BUDGET, DEFAULT_MAX_FILL_VOLUME, DEFAULT_MAX_PLAYERS,
+    DEFAULT_SEED, DEFAULT_TICK_RATE, DEFAULT_WORLD_BORDER, VIEW_RADIUS,
+};
+
+use super::{chat_limit::ChatLimit, gen_pool::default_gen_threads, player::Player};
+
+// 区块生成线程数的上限
+pub const MAX_GEN_THREADS: usize = 64;
+
+// 生成的默认配置文件 修改字段或者默认值时需要同步修改这里
+pub const DEFAULT_SERVER_CONFIG: &str = r#"# 服务端配置 删除这个文件后重新启动会生成默认配置
+# 缺少的字段使用默认值
+
+# 新世界使用的种子 已经存在的世界继续使用保存的种子
+seed = 1512354854
+
+# 每秒的 tick 数 必须大于 0
+tick_rate = 60
+
+# 最大玩家数 必须大于 0
+max_players = 32
+
+# 每个客户端每个 tick 最多发送的区块数据(字节) 必须大于 0
+chunk_budget = 65536
+
+# 服务端加载区块的半径(格) 范围 16 到 128
+view_radius = 128.0
+
+# 世界边界 玩家不能离开所在世界的原点这个距离(格)
+# 加上 view_radius 必须小于相邻世界距离的一半 只有一个世界时可以设置为 0 表示没有边界
+world_border = 3072
+
+# 固定的出生点 不设置时在原点附近自动查找安全的位置
+# spawn = [0.0, 80.0, 0.0]
+
+# 管理员的用户名
+# 用户名由客户端自己填写 无法验证身份 所以只接受本机(回环地址)的连接
+# 其他地址使用这些用户名登录会被拒绝 /op 提升到 Moderator 以上的用户名也一样
+admins = []
+
+# 区块生成的线程数 0 表示使用 CPU 核心数减 2
+gen_threads = 0
+
+# 自动保存区块和玩家数据的间隔(秒) 0 表示不自动保存
+autosave_interval = 300.0
+
+# /fill 一次最多修改的方块数 必须大于 0
+max_fill_volume = 32768
+
+# 修改方块时写入日志 服务端崩溃后重新启动时恢复自动保存之间的修改
+journal = true
+
+# 死亡时保留物品 关闭时物品在死亡的位置掉落
+keep_inventory = false
+
+# 玩家可以放置和破坏方块的高度范围 管理员不受限制
+build_min_y = -120
+build_max_y = 135
+
+# 出生点周围不能放置和破坏方块的范围(水平距离 方块) 0 表示不保护 管理员不受限制
+spawn_protection = 0
+
+# 自动查找出生点的规则 设置了 spawn 时不使用 找不到满足规则的位置时忽略
+[spawn_rules]
+# 不出生在水边
+avoid_water = true
+# 周围 3x3 范围内的高度差上限(格) 越小越平坦
+max_height_variance = 2
+# 不在这些群落出生 例如炎热的群落 ["Dry", "Sand"]
+avoid_biomes = []
+
+# 聊天频率限制 管理员不受限制
+[chat_limit]
+# window 秒内最多发送的聊天和私聊 0 表示不限制
+messages = 5
+window = 10.0
+# 连续被拒绝这么多次后自动禁言 mute_duration 秒 0 表示不禁言
+mute_after = 3
+mute_duration = 60.0
+
+# 世界 第一个是主世界 其他的世界依次放在 x 方向上 使用 /world 名字 切换
+# seed 不设置时使用上面的种子 flat 生成平坦的地形
+[[worlds]]
+name = "overworld"
+flat = false
+
+[[worlds]]
+name = "creative"
+flat = true
+"#;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Resource)]
+#[serde(default)]
+pub struct ServerConfig {
+    pub seed: i32,
+    pub tick_rate: u32,
+    pub max_players: usize,
+    pub chunk_budget: usize,
+    pub view_radius: f32,
+    pub world_border: u32,
+    pub spawn: Option<[f32; 3]>,
+    // 只对本机(回环地址)的连接生效
+    pub admins: Vec<String>,
+    pub gen_threads: usize,
+    pub autosave_interval: f32,
+    pub journal: bool,
+    pub max_fill_volume: u64,
+    pub keep_inventory: bool,
+    pub build_min_y: i32,
+    pub build_max_y: i32,
+    pub spawn_protection: u32,
+    pub spawn_rules: SpawnRules,
+    pub chat_limit: ChatLimit,
+    pub worlds: Vec<WorldConfig>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            seed: DEFAULT_SEED,
+            tick_rate: DEFAULT_TICK_RATE,
+            max_players: DEFAULT_MAX_PLAYERS,
+            chunk_budget: DEFAULT_CHUNK_BUDGET,
+            view_radius: VIEW_RADIUS,
+            world_border: DEFAULT_WORLD_BORDER,
+            spawn: None,
+            admins: Vec::new(),
+            gen_threads: 0,
+            autosave_interval: DEFAULT_AUTOSAVE_INTERVAL,
+            journal: true,
+            max_fill_volume: DEFAULT_MAX_FILL_VOLUME,
+            keep_inventory: false,
+            build_min_y: WorldHeight::default().min_block_y(),
+            build_max_y: WorldHeight::default().max_block_y(),
+            spawn_protection: 0,
+            spawn_rules: SpawnRules::default(),
+            chat_limit: ChatLimit::default(),
+            worlds: WorldConfig::defaults(),
+        }
+    }
+}
+
+impl ServerConfig {
+    /**
+     * 读取配置文件 没有文件时生成默认配置
+     * 文件格式或者数值不正确时返回错误信息
+     */
+    pub fn load_or_create(path: &str) -> Result<Self, String> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                println!("没有找到服务端配置 生成默认配置: {}", path);
+                if let Err(err) = std::fs::write(path, DEFAULT_SERVER_CONFIG) {
+                    println!("默认配置保存失败: {}", err);
+                }
+                String::from(DEFAULT_SERVER_CONFIG)
+            }
+            Err(err) => return Err(format!("{}: {}", path, err)),
+        };
+        Self::parse(&text).map_err(|err| format!("{}: {}", path, err))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let config: Self = toml::from_str(text).map_err(|err| err.to_string())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.tick_rate == 0 {
+            return Err(String::from("tick_rate 必须大于 0"));
+        }
+        if self.max_players == 0 {
+            return Err(String::from("max_players 必须大于 0"));
+        }
+        if self.chunk_budget == 0 {
+            return Err(String::from("chunk_budget 必须大于 0"));
+        }
+        if !(16.0..=VIEW_RADIUS).contains(&self.view_radius) {
+            return Err(format!(
+                "view_radius 必须在 16 到 {} 之间 当前是 {}",
+                VIEW_RADIUS, self.view_radius
+            ));
+        }
+        if self.gen_threads > MAX_GEN_THREADS {
+            return Err(format!(
+                "gen_threads 不能超过 {} 当前是 {}",
+                MAX_GEN_THREADS, self.gen_threads
+            ));
+        }
+        if !self.autosave_interval.is_finite() || self.autosave_interval < 0.0 {
+            return Err(format!(
+                "autosave_interval 不能小于 0 当前是 {}",
+                self.autosave_interval
+            ));
+        }
+        if self.max_fill_volume == 0 {
+            return Err(String::from("max_fill_volume 必须大于 0"));
+        }
+        if self.build_min_y > self.build_max_y {
+            return Err(format!(
+                "build_min_y 不能大于 build_max_y 当前是 {} 和 {}",
+                self.build_min_y, self.build_max_y
+            ));
+        }
+        // 边界加上加载区块的范围不能碰到相邻的世界
+        let max_border = WORLD_SPACING as f32 / 2.0 - self.view_radius;
+        if self.world_border as f32 >= max_border {
+            return Err(format!(
+                "world_border 必须小于 {} 当前是 {}",
+                max_border, self.world_border
+            ));
+        }
+        if self.worlds.is_empty() || self.worlds.len() > MAX_WORLDS {
+            return Err(format!(
+                "worlds 的数量必须在 1 到 {} 之间 当前是 {}",
+                MAX_WORLDS,
+                self.worlds.len()
+            ));
+        }
+        // 没有边界时玩家可以走到其他世界
+        if self.world_border == 0 && self.worlds.len() > 1 {
+            return Err(String::from("有多个世界时 world_border 不能为 0"));
+        }
+        self.chat_limit.validate()?;
+        let mut names = HashSet::new();
+        for world in self.worlds.iter() {
+            if world.name.is_empty() || world.name.contains(char::is_whitespace) {
+                return Err(format!(
+                    "worlds 的名字不能为空或者包含空格: {:?}",
+                    world.name
+                ));
+            }
+            if !names.insert(world.name.to_lowercase()) {
+                return Err(format!("worlds 的名字重复: {}", world.name));
+            }
+        }
+        if let Some(spawn) = self.spawn {
+            if spawn.iter().any(|v| !v.is_finite()) {
+                return Err(format!("spawn 不是有效的位置: {:?}", spawn));
+            }
+            if !self.inside_border(spawn[0], spawn[2]) {
+                return Err(format!("spawn {:?} 在世界边界之外", spawn));
+            }
+        }
+        Ok(())
+    }
+
+    // 生成区块使用的线程数
+    pub fn gen_thread_count(&self) -> usize {
+        if self.gen_threads == 0 {
+            default_gen_threads()
+        } else {
+            self.gen_threads
+        }
+    }
+
+    // 水平位置是否在所在世界的边界内
+    pub fn inside_border(&self, x: f32, z: f32) -> bool {
+        let border = self.world_border as f32;
+        let x = x - world_origin_x(x);
+        self.world_border == 0 || (x.abs() <= border && z.abs() <= border)
+    }
+}
+
+pub struct ServerConfigPlugin {
+    pub config: ServerConfig,
+}
+
+impl Plugin for ServerConfigPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.insert_resource(self.config.clone());
+        app.add_systems(Update, clamp_players_to_world_border);
+    }
+}
+
+/**
+ * 玩家走到世界边界时停在边界上
+ * 有多个世界时 没有设置边界也不能走出所在世界的范围 加载的区块不会碰到相邻的世界
+ */
+fn clamp_players_to_world_border(
+    config: Res<ServerConfig>,
+    mut query: Query<&mut Transform, With<Player>>,
+) {
+    let border = match config.world_border {
+        0 => f32::MAX,
+        border => border as f32,
+    };
+    let slice = if config.worlds.len() > 1 {
+        WORLD_SPACING as f32 / 2.0 - config.view_radius
+    } else {
+        f32::MAX
+    };
+    if border == f32::MAX && slice == f32::MAX {
+        return;
+    }
+    let border_x = border.min(slice);
+    for mut transform in query.iter_mut() {
+        let translation = transform.translation;
+        let origin_x = world_origin_x(translation.x);
+        let x = origin_x + (translation.x - origin_x).clamp(-border_x, border_x);
+        let z = translation.z.clamp(-border, border);
+        if x != translation.x || z != translation.z {
+            transform.translation.x = x;
+            transform.translation.z = z;
+        }
+    }
+}
+
+#[test]
+fn test_server_config() {
+    use crate::voxel_world::biomes::BiomeKind;
+
+    // 生成的默认文件和默认值一致
+    assert_eq!(
+        ServerConfig::parse(DEFAULT_SERVER_CONFIG),
+        Ok(ServerConfig::default())
+    );
+    // 缺少的字段使用默认值
+    let config = ServerConfig::parse("tick_rate = 20").unwrap();
+    assert_eq!(config.tick_rate, 20);
+    assert_eq!(config.max_players, DEFAULT_MAX_PLAYERS);
+
+    assert!(ServerConfig::parse("tick_rate = 0").is_err());
+    assert!(ServerConfig::parse("view_radius = 1000.0").is_err());
+    assert!(ServerConfig::parse("world_border = 64\nspawn = [100.0, 80.0, 0.0]").is_err());
+    assert!(ServerConfig::parse("world_border = 4000").is_err());
+    // 多个世界时必须有边界
+    assert!(ServerConfig::parse("world_border = 0").is_err());
+    assert!(ServerConfig::parse("world_border = 0\n[[worlds]]\nname = \"a\"").is_ok());
+    assert!(ServerConfig::parse("gen_threads = 1000").is_err());
+    assert!(ServerConfig::parse("autosave_interval = -1.0").is_err());
+    assert!(ServerConfig::parse("max_fill_volume = 0").is_err());
+    assert!(ServerConfig::parse("build_min_y = 10\nbuild_max_y = 0").is_err());
+    let config = ServerConfig::parse("[spawn_rules]\navoid_biomes = [\"Dry\", \"Sand\"]").unwrap();
+    assert_eq!(
+        config.spawn_rules.avoid_biomes,
+        vec![BiomeKind::Dry, BiomeKind::Sand]
+    );
+    assert!(config.spawn_rules.avoid_water);
+    assert!(!ServerConfig::parse("journal = false").unwrap().journal);
+    assert!(
+        ServerConfig::parse("keep_inventory = true")
+            .unwrap()
+            .keep_inventory
+    );
+    assert!(ServerConfig::parse("[[worlds]]\nname = \"a\"\n[[worlds]]\nname = \"A\"").is_err());
+    assert!(ServerConfig::parse("worlds = []").is_err());
+    assert_eq!(
+        ServerConfig::parse("[chat_limit]\nmessages = 0")
+            .unwrap()
+            .chat_limit
+            .messages,
+        0
+    );
+    assert!(ServerConfig::parse("[chat_limit]\nwindow = 0.0").is_err());
+    assert!(ServerConfig::default().gen_thread_count() >= 1);
+}
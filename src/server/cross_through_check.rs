@@ -17,8 +17,9 @@ use crate::{
 #[derive(Debug, Clone, Copy, Component)]
 pub struct CossTroughCheck;
 
+// 下一帧把物体移动到这个位置 也用来传送玩家
 #[derive(Debug, Clone, Copy, Component)]
-pub struct CossTroughFixed(Vec3);
+pub struct CossTroughFixed(pub Vec3);
 
 pub struct CrossTroughCheckPlugin;
 
@@ -0,0 +1,296 @@
+// 玩家死亡和重生
+// 掉出世界底部 从高处摔下或者使用 /kill 时死亡 在所在世界的出生点重生
+// keep_inventory 关闭时 toolbar 中的物品在死亡的位置掉落 任何人都可以捡起 一段时间后消失
+
+use bevy::{
+    prelude::{
+        Commands, Component, Entity, Event, EventReader, EventWriter, IntoSystemConfigs, Local,
+        Plugin, Query, Res, ResMut, Transform, Update, Vec3, Without,
+    },
+    time::{Time, Timer, TimerMode},
+    utils::HashMap,
+};
+use bevy_rapier3d::{
+    prelude::{RapierContext, RapierRigidBodyHandle},
+    rapier::prelude::RigidBodyType,
+};
+use bevy_renet::renet::RenetServer;
+
+use crate::{
+    staff::StaffInfoStroge,
+    tools::vec3_to_chunk_key_any_xyz,
+    voxel_world::{
+        chunk_map::ChunkMap,
+        gen_config::{gen_config, WorldHeight},
+        map_database::WorldSeed,
+        player_state::{PlayerOnTimeState, PlayerState},
+        spawn::{world_spawn, SpawnPoint},
+        voxel_registry::VOXEL_REGISTRY,
+        world::WorldId,
+    },
+    CHUNK_SIZE,
+};
+
+use super::{
+    config::ServerConfig,
+    cross_through_check::CossTroughFixed,
+    object_filing::{gen_filled_object, ToolWear},
+    player::{Flying, Player, ServerLobby},
+    server_command::{reply, teleport_player},
+    tool_bar_sync::send_all_tool_bar,
+};
+
+// 低于这个高度时死亡 世界最低的方块再往下一个区块
+pub fn void_death_y(height: WorldHeight) -> f32 {
+    (height.min_block_y() - CHUNK_SIZE) as f32
+}
+// 死亡掉落的物品存在的时间(秒)
+pub const DEATH_DROP_LIFETIME: f32 = 300.0;
+// 落地时的下落速度超过这个值会摔死 重力越小同样的高度落地速度越小
+pub const LETHAL_FALL_SPEED: f32 = 25.0;
+// 竖直速度突然变得比这个值小时算作落地
+const LANDED_SPEED: f32 = 2.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeathCause {
+    Void,
+    Fall,
+    Kill,
+}
+
+#[derive(Debug, Event)]
+pub struct PlayerDeathEvent {
+    pub client_id: u64,
+    pub cause: DeathCause,
+}
+
+// 死亡掉落的物品 到时间后消失
+#[derive(Debug, Component)]
+pub struct DeathDrop(pub Timer);
+
+pub struct PlayerDeathPlugin;
+
+impl Plugin for PlayerDeathPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.add_event::<PlayerDeathEvent>();
+        app.add_systems(
+            Update,
+            (
+                detect_void_deaths,
+                detect_fall_deaths,
+                deal_player_death,
+                expire_death_drops,
+            )
+                .chain(),
+        );
+    }
+}
+
+/**
+ * 死亡时掉落的物品 (物品 id, 工具剩余的耐久)
+ * 普通物品每个一份 工具带着耐久 toolbar 同时被清空
+ */
+pub fn take_death_drops(
+    player_state: &mut PlayerState,
+    staff_info_stroge: &StaffInfoStroge,
+) -> Vec<(usize, Option<usize>)> {
+    let mut drops = Vec::new();
+    for slot in player_state.toolbar.iter_mut() {
+        let (Some(staff_id), num) = std::mem::take(slot) else {
+            continue;
+        };
+        let is_tool = staff_info_stroge
+            .get(staff_id)
+            .map_or(false, |staff| staff.is_tool());
+        if is_tool {
+            drops.push((staff_id, Some(num)));
+        } else {
+            drops.extend(std::iter::repeat((staff_id, None)).take(num));
+        }
+    }
+    drops
+}
+
+// 正在传送的玩家不重复检查
+fn detect_void_deaths(
+    query: Query<(&Player, &Transform), Without<CossTroughFixed>>,
+    mut deaths: EventWriter<PlayerDeathEvent>,
+) {
+    let void_y = void_death_y(gen_config().world_height);
+    for (player, transform) in query.iter() {
+        if transform.translation.y < void_y {
+            deaths.send(PlayerDeathEvent {
+                client_id: player.id,
+                cause: DeathCause::Void,
+            });
+        }
+    }
+}
+
+// 上一次的竖直速度是 last_vy 这一次是 vy 时是否摔死
+pub fn is_lethal_landing(last_vy: f32, vy: f32) -> bool {
+    last_vy < -LETHAL_FALL_SPEED && vy > -LANDED_SPEED
+}
+
+/**
+ * 下落很快时突然停下来就是摔死了 落到液体中不会
+ * 飞行和正在传送(运动学刚体)的玩家不检查
+ */
+fn detect_fall_deaths(
+    context: Res<RapierContext>,
+    chunk_map: Res<ChunkMap>,
+    mut last_speeds: Local<HashMap<Entity, f32>>,
+    query: Query<(Entity, &Player, &Transform, &RapierRigidBodyHandle), Without<Flying>>,
+    mut deaths: EventWriter<PlayerDeathEvent>,
+) {
+    let mut speeds = HashMap::default();
+    for (entity, player, transform, handle) in query.iter() {
+        let Some(body) = context.bodies.get(handle.0) else {
+            continue;
+        };
+        if body.body_type() != RigidBodyType::Dynamic {
+            continue;
+        }
+        let vy = body.linvel().y;
+        speeds.insert(entity, vy);
+        let Some(last_vy) = last_speeds.get(&entity) else {
+            continue;
+        };
+        if !is_lethal_landing(*last_vy, vy) {
+            continue;
+        }
+        let in_liquid = [Vec3::ZERO, Vec3::NEG_Y].iter().any(|offset| {
+            let (chunk_key, xyz) = vec3_to_chunk_key_any_xyz(transform.translation + *offset);
+            chunk_map
+                .get_block(chunk_key, xyz)
+                .map_or(false, |voxel| VOXEL_REGISTRY.properties(voxel.id).is_liquid)
+        });
+        if !in_liquid {
+            deaths.send(PlayerDeathEvent {
+                client_id: player.id,
+                cause: DeathCause::Fall,
+            });
+        }
+    }
+    *last_speeds = speeds;
+}
+
+#[allow(clippy::too_many_arguments)]
+fn deal_player_death(
+    mut commands: Commands,
+    mut deaths: EventReader<PlayerDeathEvent>,
+    mut server: ResMut<RenetServer>,
+    server_lobby: Res<ServerLobby>,
+    config: Res<ServerConfig>,
+    spawn_point: Res<SpawnPoint>,
+    world_seed: Res<WorldSeed>,
+    staff_info_stroge: Res<StaffInfoStroge>,
+    mut context: ResMut<RapierContext>,
+    mut players: Query<(&Transform, &RapierRigidBodyHandle, &mut PlayerOnTimeState)>,
+) {
+    for PlayerDeathEvent { client_id, cause } in deaths.iter() {
+        let Some(entity) = server_lobby.players.get(client_id).copied() else {
+            continue;
+        };
+        let Ok((transform, body_handle, mut player_state)) = players.get_mut(entity) else {
+            continue;
+        };
+        println!("玩家{}死亡: {:?}", client_id, cause);
+        let world = WorldId::of_position(transform.translation).unwrap_or(WorldId::MAIN);
+        let respawn = world_spawn(world, spawn_point.0, world_seed.0);
+        if !config.keep_inventory {
+            // 掉出世界时掉落在出生点 不然物品也会掉进虚空
+            let drop_at = match cause {
+                DeathCause::Void => respawn,
+                DeathCause::Fall | DeathCause::Kill => transform.translation,
+            };
+            let drops = take_death_drops(&mut player_state.0, &staff_info_stroge);
+            spawn_death_drops(&mut commands, &staff_info_stroge, drop_at, drops);
+            send_all_tool_bar(*client_id, &mut server, player_state.0.clone());
+        }
+        teleport_player(&mut commands, &mut context, entity, body_handle, respawn);
+        let text = match cause {
+            DeathCause::Void => "You fell out of the world",
+            DeathCause::Fall => "You hit the ground too hard",
+            DeathCause::Kill => "You died",
+        };
+        reply(&mut server, *client_id, true, text.to_string());
+    }
+}
+
+fn spawn_death_drops(
+    commands: &mut Commands,
+    staff_info_stroge: &StaffInfoStroge,
+    center: Vec3,
+    drops: Vec<(usize, Option<usize>)>,
+) {
+    let (chunk_key, _) = vec3_to_chunk_key_any_xyz(center);
+    for (staff_id, wear) in drops {
+        let Some(staff) = staff_info_stroge.get(staff_id) else {
+            continue;
+        };
+        let entity = gen_filled_object(commands, chunk_key, center, staff);
+        commands
+            .entity(entity)
+            .insert(DeathDrop(Timer::from_seconds(
+                DEATH_DROP_LIFETIME,
+                TimerMode::Once,
+            )));
+        if let Some(wear) = wear {
+            commands.entity(entity).insert(ToolWear(wear));
+        }
+    }
+}
+
+fn expire_death_drops(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut DeathDrop)>,
+) {
+    for (entity, mut death_drop) in query.iter_mut() {
+        if death_drop.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+#[test]
+fn test_take_death_drops() {
+    use crate::staff::{Staff, StaffType};
+    use bevy::{prelude::Handle, utils::HashMap};
+
+    let staff = |id: usize, durability: Option<usize>| Staff {
+        id,
+        name: String::new(),
+        icon: Handle::default(),
+        staff_type: StaffType::Consumable(0),
+        durability,
+        tool: None,
+    };
+    let staff_info_stroge = StaffInfoStroge {
+        data: HashMap::from_iter([(0, staff(0, None)), (15, staff(15, Some(128)))]),
+        voxel_staff: HashMap::default(),
+        filled_map: HashMap::default(),
+    };
+    let mut player_state = PlayerState::default();
+    player_state.toolbar[0] = (Some(0), 3);
+    player_state.toolbar[4] = (Some(15), 20);
+
+    let drops = take_death_drops(&mut player_state, &staff_info_stroge);
+    // 普通物品每个一份 工具保留剩余的耐久
+    assert_eq!(drops, vec![(0, None), (0, None), (0, None), (15, Some(20))]);
+    assert!(player_state.toolbar.iter().all(|slot| *slot == (None, 0)));
+}
+
+#[test]
+fn test_lethal_landing() {
+    // 从 h 格高的地方落下时的落地速度
+    let landing = |gravity: f32, height: f32| (2.0 * gravity * height).sqrt();
+    assert!(is_lethal_landing(-landing(9.81, 40.0), 0.0));
+    assert!(!is_lethal_landing(-landing(9.81, 20.0), 0.0));
+    // 低重力时同样的高度落地更轻
+    assert!(!is_lethal_landing(-landing(1.62, 40.0), 0.0));
+    // 还在下落 或者只是减速
+    assert!(!is_lethal_landing(-40.0, -38.0));
+    assert!(!is_lethal_landing(40.0, 0.0));
+}
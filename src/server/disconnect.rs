@@ -0,0 +1,61 @@
+// 带原因的断开连接
+// renet 断开时不能附带原因 所以先发送原因 延迟一段时间再断开 保证客户端能收到
+
+use std::time::Duration;
+
+use bevy::{
+    prelude::{Plugin, Res, ResMut, Resource, Update},
+    time::{Time, Timer, TimerMode},
+};
+use bevy_renet::renet::RenetServer;
+
+use super::message_def::{
+    server_messages::{ServerDisconnectReason, ServerMessages},
+    ServerChannel,
+};
+
+// 发送原因后等待的时间
+pub const DISCONNECT_DELAY: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Default, Resource)]
+pub struct PendingDisconnects {
+    pub clients: Vec<(u64, Timer)>,
+}
+
+impl PendingDisconnects {
+    pub fn disconnect(
+        &mut self,
+        server: &mut RenetServer,
+        client_id: u64,
+        reason: ServerDisconnectReason,
+    ) {
+        let message = bincode::serialize(&ServerMessages::Disconnect { reason }).unwrap();
+        server.send_message(client_id, ServerChannel::ServerMessages, message);
+        self.clients
+            .push((client_id, Timer::new(DISCONNECT_DELAY, TimerMode::Once)));
+    }
+}
+
+pub struct ServerDisconnectPlugin;
+
+impl Plugin for ServerDisconnectPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.init_resource::<PendingDisconnects>();
+        app.add_systems(Update, deal_pending_disconnects);
+    }
+}
+
+fn deal_pending_disconnects(
+    time: Res<Time>,
+    mut pending: ResMut<PendingDisconnects>,
+    mut server: ResMut<RenetServer>,
+) {
+    pending.clients.retain_mut(|(client_id, timer)| {
+        timer.tick(time.delta());
+        if timer.finished() {
+            server.disconnect(*client_id);
+            return false;
+        }
+        true
+    });
+}
@@ -0,0 +1,169 @@
+// 玩家修改方块的历史 用来撤销
+// 每个玩家保存最近的几次操作 放置和填充各算一次 断开连接时清空
+// 破坏方块已经产生了掉落 撤销会复制物品 所以不记录
+// 撤销放置时把消耗的物品掉落返还 撤销填充不返还
+
+use std::collections::VecDeque;
+
+use bevy::{
+    prelude::{Resource, Vec3},
+    utils::HashMap,
+};
+use ndshape::ConstShape;
+
+use crate::{
+    voxel_world::{chunk::ChunkKey, chunk_map::ChunkMap, voxel::Voxel},
+    ChunkShape,
+};
+
+// 每个玩家保存的操作次数
+pub const EDIT_HISTORY_SIZE: usize = 32;
+// 每个玩家保存的方块总数 超过时丢弃最早的操作
+pub const EDIT_HISTORY_MAX_BLOCKS: usize = 64 * 1024;
+
+// 一个方块的修改
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BlockEdit {
+    pub chunk_key: ChunkKey,
+    pub index: usize,
+    pub old: Voxel,
+    pub new: Voxel,
+}
+
+// 一次操作的全部修改 撤销时一起恢复
+#[derive(Debug, Clone, PartialEq)]
+pub struct EditRecord {
+    pub edits: Vec<BlockEdit>,
+    // 放置时消耗了物品 撤销时在这个位置掉落返还
+    pub refund: Option<Vec3>,
+}
+
+#[derive(Debug, Default, Resource)]
+pub struct EditHistory {
+    histories: HashMap<u64, VecDeque<EditRecord>>,
+}
+
+impl EditHistory {
+    pub fn push(&mut self, client_id: u64, record: EditRecord) {
+        if record.edits.is_empty() {
+            return;
+        }
+        let history = self.histories.entry(client_id).or_default();
+        history.push_back(record);
+        while history.len() > EDIT_HISTORY_SIZE
+            || (history.len() > 1
+                && history.iter().map(|r| r.edits.len()).sum::<usize>() > EDIT_HISTORY_MAX_BLOCKS)
+        {
+            history.pop_front();
+        }
+    }
+
+    // 最近的一次操作
+    pub fn pop(&mut self, client_id: u64) -> Option<EditRecord> {
+        self.histories.get_mut(&client_id)?.pop_back()
+    }
+
+    pub fn len(&self, client_id: u64) -> usize {
+        self.histories
+            .get(&client_id)
+            .map_or(0, |history| history.len())
+    }
+
+    pub fn clear(&mut self, client_id: u64) {
+        self.histories.remove(&client_id);
+    }
+}
+
+/**
+ * 撤销一次操作 返回实际恢复的修改
+ * 之后被其他人修改过的方块保持不变
+ * 需要的区块必须已经在 chunk_map 中 没有加载的区块会被跳过
+ */
+pub fn undo_record(chunk_map: &mut ChunkMap, record: &EditRecord) -> Vec<BlockEdit> {
+    let mut restored = Vec::new();
+    for edit in record.edits.iter().rev() {
+        let Some(voxels) = chunk_map.map_data.get_mut(&edit.chunk_key) else {
+            continue;
+        };
+        if voxels[edit.index] != edit.new {
+            continue;
+        }
+        voxels[edit.index] = edit.old;
+        restored.push(*edit);
+    }
+    restored
+}
+
+// 按照区块分组 (区块, 下标, 体素)
+pub fn group_by_chunk(
+    edits: impl Iterator<Item = (ChunkKey, usize, Voxel)>,
+) -> Vec<(ChunkKey, Vec<(usize, Voxel)>)> {
+    let mut groups: HashMap<ChunkKey, Vec<(usize, Voxel)>> = HashMap::new();
+    for (chunk_key, index, voxel) in edits {
+        groups.entry(chunk_key).or_default().push((index, voxel));
+    }
+    groups.into_iter().collect()
+}
+
+// 修改的方块在区块内的位置
+pub fn edit_xyz(edit: &BlockEdit) -> [u32; 3] {
+    ChunkShape::delinearize(edit.index as u32)
+}
+
+#[test]
+fn test_edit_history() {
+    use crate::{
+        voxel_world::voxel::{Stone, VoxelMaterial},
+        CHUNK_VOLUME,
+    };
+    use bevy::prelude::IVec3;
+
+    let chunk_key = ChunkKey(IVec3::ZERO);
+    let edit = |index: usize| BlockEdit {
+        chunk_key,
+        index,
+        old: Voxel::EMPTY,
+        new: Stone::into_voxel(),
+    };
+    let mut history = EditHistory::default();
+    for index in 0..EDIT_HISTORY_SIZE + 2 {
+        history.push(
+            1,
+            EditRecord {
+                edits: vec![edit(index)],
+                refund: None,
+            },
+        );
+    }
+    assert_eq!(history.len(1), EDIT_HISTORY_SIZE);
+    // 超过方块总数时丢弃最早的操作 最近的一次总是保留
+    history.push(
+        1,
+        EditRecord {
+            edits: vec![edit(0); EDIT_HISTORY_MAX_BLOCKS],
+            refund: None,
+        },
+    );
+    assert_eq!(history.len(1), 1);
+    history.clear(1);
+    assert_eq!(history.pop(1), None);
+
+    let mut chunk_map = ChunkMap::new();
+    let mut voxels = vec![Voxel::EMPTY; CHUNK_VOLUME as usize];
+    voxels[1] = Stone::into_voxel();
+    voxels[2] = Stone::into_voxel();
+    chunk_map.write_chunk(chunk_key, voxels);
+    // 下标 3 之后被其他人挖掉了
+    let record = EditRecord {
+        edits: vec![edit(1), edit(2), edit(3)],
+        refund: None,
+    };
+    let restored = undo_record(&mut chunk_map, &record);
+    assert_eq!(restored, vec![edit(2), edit(1)]);
+    assert_eq!(chunk_map.get(chunk_key).unwrap()[1], Voxel::EMPTY);
+    assert_eq!(
+        group_by_chunk(restored.iter().map(|e| (e.chunk_key, e.index, e.old))),
+        vec![(chunk_key, vec![(2, Voxel::EMPTY), (1, Voxel::EMPTY)])]
+    );
+    assert_eq!(edit_xyz(&edit(17)), [1, 1, 0]);
+}
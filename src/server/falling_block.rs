@@ -0,0 +1,270 @@
+// 受重力影响的方块
+// 注册表中标记了 falls 的方块(沙子) 下面的方块被破坏后失去支撑 变成下落的方块
+// 上面连着的同类方块一起下落 落到第一个有支撑的位置后重新变成方块
+// 服务端计算下落 客户端收到开始和落地的消息 按照相同的速度播放下落的动画
+
+use bevy::prelude::{IVec3, Plugin, Query, Res, ResMut, Resource, Time, Transform, Update};
+use bevy_renet::renet::RenetServer;
+use ndshape::ConstShape;
+
+use crate::{
+    voxel_world::{
+        chunk_map::ChunkMap,
+        gen_config::gen_config,
+        map_database::{DbSaveTasks, MapDataBase},
+        voxel::Voxel,
+        voxel_registry::VOXEL_REGISTRY,
+        world::WorldId,
+    },
+    ChunkShape,
+};
+
+use super::{
+    async_chunk::{commit_edits, ChunkResultTasks},
+    edit_history::group_by_chunk,
+    fill::block_to_chunk,
+    light_query::LightCache,
+    message_def::{server_messages::ServerMessages, ServerChannel},
+    player::{send_to_world, Player},
+    terrain_physics::{ColliderManager, ColliderTasksManager, ColliderUpdateTasksManager},
+};
+
+// 下落的加速度和最大速度(格/秒)
+pub const FALLING_BLOCK_GRAVITY: f32 = 20.0;
+pub const FALLING_BLOCK_MAX_SPEED: f32 = 40.0;
+// 一次最多下落的方块数 更高的部分等下面的落地后再检查
+pub const FALLING_COLUMN_LIMIT: usize = 64;
+
+/**
+ * 下落一帧 返回新的高度和速度
+ * 服务端和客户端使用相同的计算
+ */
+pub fn fall_step(y: f32, speed: f32, delta: f32) -> (f32, f32) {
+    let speed = (speed + FALLING_BLOCK_GRAVITY * delta).min(FALLING_BLOCK_MAX_SPEED);
+    (y - speed * delta, speed)
+}
+
+// 一个下落中的方块 y 是方块底部的高度
+#[derive(Debug, Clone, PartialEq)]
+pub struct FallingBlock {
+    pub id: u64,
+    pub voxel: Voxel,
+    pub x: i32,
+    pub z: i32,
+    pub y: f32,
+    pub speed: f32,
+    pub target_y: i32,
+}
+
+#[derive(Debug, Default, Resource)]
+pub struct FallingBlocks {
+    // 需要检查是否会下落的方块坐标
+    pub checks: Vec<IVec3>,
+    pub falling: Vec<FallingBlock>,
+    next_id: u64,
+}
+
+pub struct FallingBlockPlugin;
+
+impl Plugin for FallingBlockPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.init_resource::<FallingBlocks>();
+        app.add_systems(Update, falling_block_system);
+    }
+}
+
+fn voxel_at(chunk_map: &ChunkMap, pos: IVec3) -> Option<Voxel> {
+    let (chunk_key, xyz) = block_to_chunk(pos);
+    chunk_map.get_block(chunk_key, xyz)
+}
+
+fn set_voxel(chunk_map: &mut ChunkMap, pos: IVec3, voxel: Voxel) -> Option<(usize, Voxel)> {
+    let (chunk_key, xyz) = block_to_chunk(pos);
+    let voxels = chunk_map.map_data.get_mut(&chunk_key)?;
+    let index = ChunkShape::linearize(xyz) as usize;
+    voxels[index] = voxel;
+    Some((index, voxel))
+}
+
+// 没有加载的区块和世界底部当做有支撑 不会一直掉下去
+fn is_support(chunk_map: &ChunkMap, pos: IVec3) -> bool {
+    if pos.y < gen_config().world_height.min_block_y() {
+        return true;
+    }
+    voxel_at(chunk_map, pos).map_or(true, |voxel| voxel.is_solid())
+}
+
+/**
+ * pos 的方块会下落时 把它和上面连着的会下落的方块从地图中移除
+ * 返回移除的方块 从下往上
+ */
+pub fn collapse_column(chunk_map: &mut ChunkMap, pos: IVec3) -> Vec<(IVec3, Voxel)> {
+    let mut column = Vec::new();
+    if is_support(chunk_map, pos - IVec3::Y) {
+        return column;
+    }
+    let mut current = pos;
+    while column.len() < FALLING_COLUMN_LIMIT {
+        match voxel_at(chunk_map, current) {
+            Some(voxel) if VOXEL_REGISTRY.falls(voxel.id) => {
+                set_voxel(chunk_map, current, Voxel::EMPTY);
+                column.push((current, voxel));
+                current += IVec3::Y;
+            }
+            _ => break,
+        }
+    }
+    column
+}
+
+// 从 pos 往下第一个有支撑的位置
+pub fn landing_y(chunk_map: &ChunkMap, pos: IVec3) -> i32 {
+    let mut y = pos.y;
+    while !is_support(chunk_map, IVec3::new(pos.x, y - 1, pos.z)) {
+        y -= 1;
+    }
+    y
+}
+
+#[allow(clippy::too_many_arguments)]
+fn falling_block_system(
+    time: Res<Time>,
+    mut falling_blocks: ResMut<FallingBlocks>,
+    mut chunk_map: ResMut<ChunkMap>,
+    mut db: ResMut<MapDataBase>,
+    mut db_save_task: ResMut<DbSaveTasks>,
+    mut tasks: ResMut<ChunkResultTasks>,
+    collider_manager: Res<ColliderManager>,
+    mut collider_update_tasks_manager: ResMut<ColliderUpdateTasksManager>,
+    mut collider_tasks: ResMut<ColliderTasksManager>,
+    mut server: ResMut<RenetServer>,
+    mut light_cache: ResMut<LightCache>,
+    players: Query<(&Player, &Transform)>,
+) {
+    let falling_blocks = falling_blocks.as_mut();
+    let mut edits = Vec::new();
+
+    // 1. 失去支撑的方块开始下落
+    for pos in std::mem::take(&mut falling_blocks.checks) {
+        let column = collapse_column(&mut chunk_map, pos);
+        let (Some((bottom, _)), Some((top, _))) = (column.first(), column.last()) else {
+            continue;
+        };
+        // 超过一次下落的数量 剩下的部分下一帧再检查
+        if column.len() == FALLING_COLUMN_LIMIT {
+            falling_blocks.checks.push(*top + IVec3::Y);
+        }
+        let land = landing_y(&chunk_map, *bottom);
+        for (offset, (pos, voxel)) in column.into_iter().enumerate() {
+            let (chunk_key, xyz) = block_to_chunk(pos);
+            edits.push((chunk_key, ChunkShape::linearize(xyz) as usize, Voxel::EMPTY));
+            let block = FallingBlock {
+                id: falling_blocks.next_id,
+                voxel,
+                x: pos.x,
+                z: pos.z,
+                y: pos.y as f32,
+                speed: 0.0,
+                target_y: land + offset as i32,
+            };
+            falling_blocks.next_id += 1;
+            let message = bincode::serialize(&ServerMessages::FallingBlockSpawn {
+                id: block.id,
+                voxel,
+                translation: [pos.x as f32 + 0.5, block.y + 0.5, pos.z as f32 + 0.5],
+                target_y: block.target_y as f32 + 0.5,
+            })
+            .unwrap();
+            // 落地的消息发给所有人 中途换了世界的玩家也能移除下落的方块
+            send_to_world(
+                &mut server,
+                players
+                    .iter()
+                    .map(|(player, transform)| (player.id, transform.translation)),
+                WorldId::of_position(pos.as_vec3()),
+                ServerChannel::ServerMessages,
+                message,
+            );
+            falling_blocks.falling.push(block);
+        }
+    }
+
+    // 2. 下落 落地后变回方块
+    let delta = time.delta_seconds();
+    let mut landed = Vec::new();
+    falling_blocks.falling.retain_mut(|block| {
+        (block.y, block.speed) = fall_step(block.y, block.speed, delta);
+        if block.y > block.target_y as f32 {
+            return true;
+        }
+        landed.push(block.clone());
+        false
+    });
+    for block in landed {
+        // 下落期间落点可能被放了方块 往上找空的位置
+        let mut pos = IVec3::new(block.x, block.target_y, block.z);
+        while voxel_at(&chunk_map, pos).map_or(false, |voxel| voxel.is_solid()) {
+            pos += IVec3::Y;
+        }
+        if let Some((index, voxel)) = set_voxel(&mut chunk_map, pos, block.voxel) {
+            let (chunk_key, _) = block_to_chunk(pos);
+            edits.push((chunk_key, index, voxel));
+        }
+        let message =
+            bincode::serialize(&ServerMessages::FallingBlockLanded { id: block.id }).unwrap();
+        server.broadcast_message(ServerChannel::ServerMessages, message);
+    }
+
+    if !edits.is_empty() {
+        commit_edits(
+            group_by_chunk(edits.into_iter()),
+            &chunk_map,
+            &mut db,
+            &mut db_save_task,
+            &mut tasks,
+            &collider_manager,
+            &mut collider_update_tasks_manager,
+            &mut collider_tasks,
+            &mut light_cache,
+        );
+    }
+}
+
+#[test]
+fn test_falling_column() {
+    use crate::{
+        server::fill::chunk_to_block,
+        voxel_world::{
+            chunk::ChunkKey,
+            voxel::{Sand, Stone, VoxelMaterial},
+        },
+        CHUNK_VOLUME,
+    };
+
+    let chunk_key = ChunkKey(IVec3::ZERO);
+    let mut chunk_map = ChunkMap::new();
+    chunk_map.write_chunk(chunk_key, vec![Voxel::EMPTY; CHUNK_VOLUME as usize]);
+    let at = |y: u32| chunk_to_block(chunk_key, [4, y, 4]);
+    set_voxel(&mut chunk_map, at(0), Stone::into_voxel());
+    for y in 5..8 {
+        set_voxel(&mut chunk_map, at(y), Sand::into_voxel());
+    }
+    set_voxel(&mut chunk_map, at(8), Stone::into_voxel());
+    set_voxel(&mut chunk_map, at(9), Sand::into_voxel());
+
+    // 有支撑的沙子不会下落
+    assert!(collapse_column(&mut chunk_map, at(9)).is_empty());
+    // 下面是空的 上面连着的沙子一起下落 石头挡住了更上面的沙子
+    let column = collapse_column(&mut chunk_map, at(5));
+    assert_eq!(column.len(), 3);
+    assert_eq!(voxel_at(&chunk_map, at(6)), Some(Voxel::EMPTY));
+    assert_eq!(landing_y(&chunk_map, column[0].0), at(1).y);
+
+    let (mut y, mut speed) = (at(5).y as f32, 0.0);
+    let mut frames = 0;
+    while y > at(1).y as f32 {
+        (y, speed) = fall_step(y, speed, 1.0 / 60.0);
+        frames += 1;
+    }
+    assert!(frames > 1 && speed <= FALLING_BLOCK_MAX_SPEED);
+}
@@ -0,0 +1,529 @@
+// 区域填充
+// 管理员把一个长方体区域内的方块全部设置成同一种体素 区域可以跨越多个区块
+// 没有加载的区块先读取或者生成 修改后保存 并把整个区块发送给所有客户端
+// 区域的大小有上限 见 ServerConfig::max_fill_volume
+
+use bevy::prelude::{IVec3, Resource, Vec2, Vec3};
+use ndshape::ConstShape;
+
+use crate::{
+    server::{edit_history::BlockEdit, permission::PermissionLevel},
+    voxel_world::{
+        chunk::ChunkKey,
+        chunk_map::ChunkMap,
+        gen_config::gen_config,
+        voxel::{BasicStone, Voxel, VoxelMaterial},
+        world::WorldId,
+    },
+    ChunkShape, CHUNK_SIZE, CHUNK_SIZE_U32, TOUCH_RADIUS,
+};
+
+// 服务端允许的最远修改距离 比客户端的 TOUCH_RADIUS 宽松一些 留出位置同步的延迟
+pub const MAX_REACH: f32 = TOUCH_RADIUS + 2.0;
+
+// 一次填充最多涉及的区块数 没有加载的区块需要在填充前同步读取或者生成
+pub const MAX_FILL_CHUNKS: usize = 512;
+
+// 一次填充的最大方块数
+#[derive(Debug, Clone, Copy, Resource)]
+pub struct FillLimit(pub u64);
+
+// 世界的边界 见 ServerConfig::world_border 0 表示没有边界
+#[derive(Debug, Clone, Copy, Resource)]
+pub struct WorldBorder(pub u32);
+
+// 玩家可以放置和破坏方块的高度范围 管理员不受限制
+#[derive(Debug, Clone, Copy, Resource)]
+pub struct BuildLimit {
+    pub min_y: i32,
+    pub max_y: i32,
+}
+
+impl BuildLimit {
+    // 超出范围时返回提示给玩家的信息
+    pub fn check(&self, y: i32) -> Result<(), String> {
+        if y < self.min_y || y > self.max_y {
+            return Err(format!(
+                "You can only build between y {} and {}",
+                self.min_y, self.max_y
+            ));
+        }
+        Ok(())
+    }
+}
+
+/**
+ * 出生点周围的保护范围 见 ServerConfig::spawn_protection
+ * 按照水平距离计算 radius 为 0 时不保护 管理员不受限制
+ */
+#[derive(Debug, Clone, Copy, Resource)]
+pub struct SpawnProtection {
+    pub radius: u32,
+}
+
+impl SpawnProtection {
+    // 方块所在世界的出生点附近不能修改 其他世界的出生点在世界的原点
+    pub fn check(
+        &self,
+        block: IVec3,
+        spawn_point: Vec3,
+        level: PermissionLevel,
+    ) -> Result<(), String> {
+        if self.radius == 0 || level >= PermissionLevel::Admin {
+            return Ok(());
+        }
+        let center = match WorldId::of_position(block.as_vec3()) {
+            Some(world) if world != WorldId::MAIN => Vec2::new(world.origin_x() + 0.5, 0.5),
+            _ => Vec2::new(spawn_point.x, spawn_point.z),
+        };
+        let block = Vec2::new(block.x as f32 + 0.5, block.z as f32 + 0.5);
+        if block.distance(center) <= self.radius as f32 {
+            return Err(format!("Spawn is protected within {} blocks", self.radius));
+        }
+        Ok(())
+    }
+}
+
+/**
+ * 玩家修改一个方块前的检查 高度限制和出生点保护
+ * 管理员和创造世界中的玩家可以在任何高度修改
+ */
+pub fn check_block_edit(
+    block: IVec3,
+    level: PermissionLevel,
+    creative: bool,
+    build_limit: &BuildLimit,
+    spawn_protection: &SpawnProtection,
+    spawn_point: Vec3,
+) -> Result<(), String> {
+    if level < PermissionLevel::Admin && !creative {
+        build_limit.check(block.y)?;
+    }
+    spawn_protection.check(block, spawn_point, level)
+}
+
+/**
+ * ChunkQuery::Change 和 Interact 的检查 player 是服务端记录的玩家位置
+ * 玩家需要够得到这个方块 再检查高度限制和出生点保护
+ */
+pub fn check_player_edit(
+    player: Vec3,
+    block: IVec3,
+    level: PermissionLevel,
+    creative: bool,
+    build_limit: &BuildLimit,
+    spawn_protection: &SpawnProtection,
+    spawn_point: Vec3,
+) -> Result<(), String> {
+    check_reach(player, block)?;
+    check_block_edit(
+        block,
+        level,
+        creative,
+        build_limit,
+        spawn_protection,
+        spawn_point,
+    )
+}
+
+// 客户端发来的区块内位置 超出区块时不处理
+pub fn pos_in_chunk(pos: [u32; 3]) -> bool {
+    pos.iter().all(|v| *v < CHUNK_SIZE_U32)
+}
+
+// 玩家只能修改和交互够得到的方块 按照到方块中心的距离
+pub fn check_reach(player: Vec3, block: IVec3) -> Result<(), String> {
+    if player.distance(block.as_vec3() + Vec3::splat(0.5)) > MAX_REACH {
+        return Err(String::from("That block is out of reach"));
+    }
+    Ok(())
+}
+
+// 一次填充的结果
+#[derive(Debug, Default)]
+pub struct FillReport {
+    // 修改的方块 包括原来的体素 撤销时使用
+    pub changes: Vec<BlockEdit>,
+    // 基岩不能被覆盖
+    pub skipped: usize,
+}
+
+impl FillReport {
+    pub fn changed(&self) -> usize {
+        self.changes.len()
+    }
+}
+
+/**
+ * 方块坐标所在的区块和区块内的位置
+ * 和 map_generator::column_of 使用相同的换算
+ */
+pub fn block_to_chunk(pos: IVec3) -> (ChunkKey, [u32; 3]) {
+    let shifted = pos + IVec3::splat(CHUNK_SIZE / 2);
+    (
+        ChunkKey(IVec3::new(
+            shifted.x.div_euclid(CHUNK_SIZE),
+            shifted.y.div_euclid(CHUNK_SIZE),
+            shifted.z.div_euclid(CHUNK_SIZE),
+        )),
+        [
+            shifted.x.rem_euclid(CHUNK_SIZE) as u32,
+            shifted.y.rem_euclid(CHUNK_SIZE) as u32,
+            shifted.z.rem_euclid(CHUNK_SIZE) as u32,
+        ],
+    )
+}
+
+// 区块和区块内的位置还原到方块坐标
+pub fn chunk_to_block(chunk_key: ChunkKey, xyz: [u32; 3]) -> IVec3 {
+    chunk_key.0 * CHUNK_SIZE - IVec3::splat(CHUNK_SIZE / 2)
+        + IVec3::new(xyz[0] as i32, xyz[1] as i32, xyz[2] as i32)
+}
+
+// 两个角的坐标可以是任意顺序
+pub fn fill_bounds(a: IVec3, b: IVec3) -> (IVec3, IVec3) {
+    (a.min(b), a.max(b))
+}
+
+// 按 i64 计算 任意两个角都不会溢出
+pub fn fill_volume(min: IVec3, max: IVec3) -> u64 {
+    let size = |min: i32, max: i32| (max as i64 - min as i64 + 1) as u64;
+    size(min.x, max.x)
+        .saturating_mul(size(min.y, max.y))
+        .saturating_mul(size(min.z, max.z))
+}
+
+/**
+ * 区域的两个角必须在同一个世界中 并且在世界的边界内
+ * 检查之后区块坐标的计算不会溢出
+ */
+pub fn check_fill_corners(min: IVec3, max: IVec3, border: WorldBorder) -> Result<(), String> {
+    let world = WorldId::of_position(min.as_vec3());
+    let Some(world) = world.filter(|world| WorldId::of_position(max.as_vec3()) == Some(*world))
+    else {
+        return Err(String::from("The region must be inside one world"));
+    };
+    // 没有边界时也留出换算区块坐标的余量
+    let limit = match border.0 {
+        0 => (i32::MAX - CHUNK_SIZE) as u32,
+        border => border,
+    };
+    let origin_x = world.origin_x() as i32;
+    let inside =
+        |pos: IVec3| (pos.x - origin_x).unsigned_abs() <= limit && pos.z.unsigned_abs() <= limit;
+    if !inside(min) || !inside(max) {
+        return Err(format!("The region must be within {} blocks", limit));
+    }
+    Ok(())
+}
+
+/**
+ * 区域覆盖的区块
+ * 区域在世界的高度之外 或者超过 MAX_FILL_CHUNKS 个区块时返回错误
+ */
+pub fn fill_chunk_keys(min: IVec3, max: IVec3) -> Result<Vec<ChunkKey>, String> {
+    let (min_key, _) = block_to_chunk(min);
+    let (max_key, _) = block_to_chunk(max);
+    let height = gen_config().world_height;
+    if !height.contains(min_key.0.y) || !height.contains(max_key.0.y) {
+        return Err(format!(
+            "y must be between {} and {}",
+            height.min_block_y(),
+            height.max_block_y()
+        ));
+    }
+    let count = fill_volume(min_key.0, max_key.0);
+    if count > MAX_FILL_CHUNKS as u64 {
+        return Err(format!(
+            "Region spans {} chunks, limit is {}",
+            count, MAX_FILL_CHUNKS
+        ));
+    }
+    let mut keys = Vec::new();
+    for x in min_key.0.x..=max_key.0.x {
+        for y in min_key.0.y..=max_key.0.y {
+            for z in min_key.0.z..=max_key.0.z {
+                keys.push(ChunkKey(IVec3::new(x, y, z)));
+            }
+        }
+    }
+    Ok(keys)
+}
+
+/**
+ * 把区域内的方块设置成 voxel
+ * 需要的区块必须已经在 chunk_map 中 没有加载的区块会被跳过
+ * 和原来相同的方块不算修改
+ */
+pub fn fill_region(chunk_map: &mut ChunkMap, min: IVec3, max: IVec3, voxel: Voxel) -> FillReport {
+    let mut report = FillReport::default();
+    for x in min.x..=max.x {
+        for y in min.y..=max.y {
+            for z in min.z..=max.z {
+                let (chunk_key, xyz) = block_to_chunk(IVec3::new(x, y, z));
+                let Some(voxels) = chunk_map.map_data.get_mut(&chunk_key) else {
+                    continue;
+                };
+                let index = ChunkShape::linearize(xyz) as usize;
+                let old_voxel = voxels[index];
+                if old_voxel == voxel {
+                    continue;
+                }
+                if old_voxel.id == BasicStone::ID {
+                    report.skipped += 1;
+                    continue;
+                }
+                voxels[index] = voxel;
+                report.changes.push(BlockEdit {
+                    chunk_key,
+                    index,
+                    old: old_voxel,
+                    new: voxel,
+                });
+            }
+        }
+    }
+    report
+}
+
+#[test]
+fn test_fill_region() {
+    use crate::{voxel_world::voxel::Stone, CHUNK_VOLUME};
+
+    assert_eq!(
+        block_to_chunk(IVec3::new(-8, 7, 8)),
+        (ChunkKey(IVec3::new(0, 0, 1)), [0, 15, 0])
+    );
+    assert_eq!(
+        block_to_chunk(IVec3::new(-9, -8, 0)),
+        (ChunkKey(IVec3::new(-1, 0, 0)), [15, 0, 8])
+    );
+
+    assert_eq!(
+        chunk_to_block(ChunkKey(IVec3::new(-1, 0, 0)), [15, 0, 8]),
+        IVec3::new(-9, -8, 0)
+    );
+
+    let (min, max) = fill_bounds(IVec3::new(4, 2, 0), IVec3::new(-4, 0, 0));
+    assert_eq!((min, max), (IVec3::new(-4, 0, 0), IVec3::new(4, 2, 0)));
+    assert_eq!(fill_volume(min, max), 27);
+    assert!(fill_chunk_keys(IVec3::new(0, 200, 0), IVec3::new(0, 201, 0)).is_err());
+    // 极端的角不会溢出 也不能绕过数量限制
+    assert_eq!(
+        fill_volume(IVec3::splat(i32::MIN), IVec3::splat(i32::MAX)),
+        u64::MAX
+    );
+    assert!(check_fill_corners(
+        IVec3::new(-3, 0, i32::MIN),
+        IVec3::new(3, 0, i32::MAX),
+        WorldBorder(0)
+    )
+    .is_err());
+    assert!(
+        check_fill_corners(IVec3::new(0, 0, 0), IVec3::new(100, 0, 0), WorldBorder(64)).is_err()
+    );
+    assert!(check_fill_corners(
+        IVec3::new(-10, 0, 0),
+        IVec3::new(WorldId(1).origin_x() as i32, 0, 0),
+        WorldBorder(0)
+    )
+    .is_err());
+    assert!(check_fill_corners(
+        IVec3::new(-64, 0, -64),
+        IVec3::new(64, 0, 64),
+        WorldBorder(64)
+    )
+    .is_ok());
+    assert!(fill_chunk_keys(IVec3::new(0, 0, 0), IVec3::new(16 * 600, 0, 0)).is_err());
+
+    // 跨越 x 方向的两个区块
+    let keys = fill_chunk_keys(IVec3::new(4, 0, 0), IVec3::new(10, 1, 0)).unwrap();
+    assert_eq!(keys.len(), 2);
+    let mut chunk_map = ChunkMap::new();
+    for key in keys.iter() {
+        chunk_map.write_chunk(*key, vec![Voxel::EMPTY; CHUNK_VOLUME as usize]);
+    }
+    let (bedrock_key, bedrock_xyz) = block_to_chunk(IVec3::new(4, 0, 0));
+    chunk_map.map_data.get_mut(&bedrock_key).unwrap()
+        [ChunkShape::linearize(bedrock_xyz) as usize] = BasicStone::into_voxel();
+
+    let report = fill_region(
+        &mut chunk_map,
+        IVec3::new(4, 0, 0),
+        IVec3::new(10, 1, 0),
+        Stone::into_voxel(),
+    );
+    assert_eq!(report.changed(), 13);
+    assert_eq!(report.skipped, 1);
+    assert!(report.changes.iter().all(|edit| edit.old == Voxel::EMPTY));
+    assert!(report
+        .changes
+        .iter()
+        .any(|edit| edit.chunk_key != bedrock_key));
+
+    // 清空时记录被覆盖的方块 再次清空没有修改
+    let report = fill_region(
+        &mut chunk_map,
+        IVec3::new(4, 0, 0),
+        IVec3::new(10, 1, 0),
+        Voxel::EMPTY,
+    );
+    assert!(report
+        .changes
+        .iter()
+        .all(|edit| edit.old == Stone::into_voxel()));
+    let report = fill_region(
+        &mut chunk_map,
+        IVec3::new(4, 0, 0),
+        IVec3::new(10, 1, 0),
+        Voxel::EMPTY,
+    );
+    assert_eq!(report.changed(), 0);
+}
+
+#[test]
+fn test_build_limit() {
+    let limit = BuildLimit {
+        min_y: -20,
+        max_y: 64,
+    };
+    assert!(limit.check(-20).is_ok());
+    assert!(limit.check(64).is_ok());
+    // 超出最高和最低高度的修改被拒绝
+    assert!(limit.check(65).is_err());
+    assert!(limit.check(-21).is_err());
+}
+
+#[test]
+fn test_spawn_protection() {
+    let protection = SpawnProtection { radius: 16 };
+    let spawn_point = Vec3::new(100.5, 40.0, -20.5);
+    let near = IVec3::new(105, 10, -25);
+    let far = IVec3::new(130, 40, -20);
+    // 普通玩家不能修改出生点附近的方块 管理员可以
+    assert!(protection
+        .check(near, spawn_point, PermissionLevel::Guest)
+        .is_err());
+    assert!(protection
+        .check(near, spawn_point, PermissionLevel::Moderator)
+        .is_err());
+    assert!(protection
+        .check(near, spawn_point, PermissionLevel::Admin)
+        .is_ok());
+    assert!(protection
+        .check(far, spawn_point, PermissionLevel::Guest)
+        .is_ok());
+    // 其他世界保护世界的原点
+    let origin = WorldId(1).origin_x() as i32;
+    assert!(protection
+        .check(
+            IVec3::new(origin + 3, 0, 2),
+            spawn_point,
+            PermissionLevel::Guest
+        )
+        .is_err());
+    assert!(protection
+        .check(
+            IVec3::new(origin + 100, 0, -20),
+            spawn_point,
+            PermissionLevel::Guest
+        )
+        .is_ok());
+    // 0 表示不保护
+    assert!(SpawnProtection { radius: 0 }
+        .check(near, spawn_point, PermissionLevel::Guest)
+        .is_ok());
+}
+
+#[test]
+fn test_check_block_edit() {
+    let build_limit = BuildLimit {
+        min_y: -20,
+        max_y: 64,
+    };
+    let spawn_protection = SpawnProtection { radius: 0 };
+    // 和 ChunkQuery::Change 一样从区块坐标换算
+    let above = chunk_to_block(ChunkKey(IVec3::new(3, 5, -2)), [1, 5, 1]);
+    assert!(above.y > build_limit.max_y);
+    let check = |level, creative| {
+        check_block_edit(
+            above,
+            level,
+            creative,
+            &build_limit,
+            &spawn_protection,
+            Vec3::ZERO,
+        )
+    };
+    // 普通玩家不能在最高高度之上修改 管理员和创造世界中的玩家可以
+    assert!(check(PermissionLevel::Guest, false).is_err());
+    assert!(check(PermissionLevel::Moderator, false).is_err());
+    assert!(check(PermissionLevel::Admin, false).is_ok());
+    assert!(check(PermissionLevel::Guest, true).is_ok());
+    let inside = chunk_to_block(ChunkKey(IVec3::new(3, 1, -2)), [1, 5, 1]);
+    assert!(check_block_edit(
+        inside,
+        PermissionLevel::Guest,
+        false,
+        &build_limit,
+        &spawn_protection,
+        Vec3::ZERO
+    )
+    .is_ok());
+}
+
+#[test]
+fn test_check_player_edit() {
+    use crate::{
+        client::message_def::chunk_query::ChunkQuery, common::net_error::decode,
+        voxel_world::voxel::Stone,
+    };
+
+    let build_limit = BuildLimit {
+        min_y: -20,
+        max_y: 64,
+    };
+    let spawn_protection = SpawnProtection { radius: 0 };
+    // 客户端发来最高高度之上的修改 按照服务端收到的消息解析
+    let message = bincode::serialize(&ChunkQuery::Change {
+        chunk_key: ChunkKey(IVec3::new(3, 5, -2)),
+        pos: [1, 5, 1],
+        voxel_type: Stone::into_voxel(),
+        center: Vec3::ZERO,
+        active_index: None,
+    })
+    .unwrap();
+    let Ok(ChunkQuery::Change { chunk_key, pos, .. }) = decode::<ChunkQuery>(&message) else {
+        panic!("消息解析失败");
+    };
+    assert!(pos_in_chunk(pos));
+    let block = chunk_to_block(chunk_key, pos);
+    assert!(block.y > build_limit.max_y);
+    // 玩家站在方块旁边 够得到
+    let player = block.as_vec3() + Vec3::new(0.5, 2.0, 2.5);
+    let check = |player, level, creative| {
+        check_player_edit(
+            player,
+            block,
+            level,
+            creative,
+            &build_limit,
+            &spawn_protection,
+            Vec3::ZERO,
+        )
+    };
+    assert!(check(player, PermissionLevel::Player, false).is_err());
+    assert!(check(player, PermissionLevel::Admin, false).is_ok());
+    assert!(check(player, PermissionLevel::Player, true).is_ok());
+    // 够不到的方块 管理员也不能修改
+    assert!(check(Vec3::ZERO, PermissionLevel::Admin, true).is_err());
+}
+
+#[test]
+fn test_check_reach() {
+    let player = Vec3::new(0.5, 10.0, 0.5);
+    assert!(check_reach(player, IVec3::new(0, 8, 3)).is_ok());
+    assert!(check_reach(player, IVec3::new(0, 10, 20)).is_err());
+    assert!(pos_in_chunk([0, 15, 15]));
+    assert!(!pos_in_chunk([0, 16, 0]));
+}
@@ -0,0 +1,132 @@
+// 区块生成的线程池
+// 生成区块比较慢 放在单独的线程中执行 不和网络和物理的系统抢线程
+// 请求队列有上限 队列满了之后 try_request 返回 false 调用方下一帧再请求
+
+use std::{
+    sync::{
+        mpsc::{self, Receiver, SyncSender, TrySendError},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use bevy::{prelude::Resource, utils::HashSet};
+
+use crate::voxel_world::{
+    biomes::TreeGentor, chunk::ChunkKey, voxel::Voxel, world::gen_world_chunk,
+};
+
+// 等待生成的区块的最大数量
+pub const GEN_QUEUE_CAPACITY: usize = 256;
+
+// 生成的结果 (区块, 体素, 需要补到其他区块的树)
+pub type GenResult = (ChunkKey, Vec<Voxel>, Vec<(Vec<ChunkKey>, TreeGentor)>);
+
+/**
+ * 默认的线程数 保留两个核心给网络和模拟
+ */
+pub fn default_gen_threads() -> usize {
+    thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .saturating_sub(2)
+        .max(1)
+}
+
+#[derive(Resource)]
+pub struct GenPool {
+    requests: SyncSender<(i32, ChunkKey)>,
+    results: Mutex<Receiver<GenResult>>,
+    // 已经请求 还没有取回结果的区块
+    pub pending: HashSet<ChunkKey>,
+    pub threads: usize,
+}
+
+impl GenPool {
+    pub fn new(threads: usize, capacity: usize) -> Self {
+        let (request_sender, request_receiver) = mpsc::sync_channel::<(i32, ChunkKey)>(capacity);
+        let (result_sender, result_receiver) = mpsc::channel();
+        let request_receiver = Arc::new(Mutex::new(request_receiver));
+        for index in 0..threads {
+            let requests = request_receiver.clone();
+            let results = result_sender.clone();
+            thread::Builder::new()
+                .name(format!("chunk-gen-{}", index))
+                .spawn(move || loop {
+                    // 只在取请求时持有锁 生成时其他线程可以继续取
+                    let request = requests.lock().unwrap().recv();
+                    // 线程池被删除后退出
+                    let Ok((seed, chunk_key)) = request else {
+                        break;
+                    };
+                    let (voxels, trees) = gen_world_chunk(seed, chunk_key);
+                    if results.send((chunk_key, voxels, trees)).is_err() {
+                        break;
+                    }
+                })
+                .unwrap();
+        }
+        Self {
+            requests: request_sender,
+            results: Mutex::new(result_receiver),
+            pending: HashSet::new(),
+            threads,
+        }
+    }
+
+    /**
+     * 请求生成区块 已经在生成的区块不会重复请求
+     * 队列满了返回 false
+     */
+    pub fn try_request(&mut self, seed: i32, chunk_key: ChunkKey) -> bool {
+        if self.pending.contains(&chunk_key) {
+            return true;
+        }
+        match self.requests.try_send((seed, chunk_key)) {
+            Ok(()) => {
+                self.pending.insert(chunk_key);
+                true
+            }
+            Err(TrySendError::Full(_)) => false,
+            Err(TrySendError::Disconnected(_)) => {
+                println!("区块生成线程已经退出");
+                false
+            }
+        }
+    }
+
+    // 取出所有已经生成完的区块
+    pub fn take_results(&mut self) -> Vec<GenResult> {
+        let results: Vec<GenResult> = self.results.lock().unwrap().try_iter().collect();
+        for (chunk_key, _, _) in results.iter() {
+            self.pending.remove(chunk_key);
+        }
+        results
+    }
+}
+
+#[test]
+fn test_gen_pool() {
+    use bevy::prelude::IVec3;
+
+    // 没有线程时队列满了就拒绝
+    let mut pool = GenPool::new(0, 1);
+    assert!(pool.try_request(1, ChunkKey(IVec3::ZERO)));
+    assert!(pool.try_request(1, ChunkKey(IVec3::ZERO)));
+    assert!(!pool.try_request(1, ChunkKey(IVec3::X)));
+
+    let mut pool = GenPool::new(2, 8);
+    let keys: Vec<ChunkKey> = (0..4).map(|x| ChunkKey(IVec3::new(x, 0, 0))).collect();
+    for key in keys.iter() {
+        assert!(pool.try_request(1, *key));
+    }
+    let mut results = Vec::new();
+    while results.len() < keys.len() {
+        results.extend(pool.take_results());
+        thread::yield_now();
+    }
+    assert!(pool.pending.is_empty());
+    for (key, voxels, _) in results {
+        assert_eq!(voxels, gen_world_chunk(1, key).0);
+    }
+}
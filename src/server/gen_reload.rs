@@ -0,0 +1,150 @@
+// 生成配置的热重载
+// 定时检查配置文件的修改时间 修改后等文件稳定一段时间再读取 避免读到写了一半的文件
+// 检查通过后替换全局的生成配置 并重新生成所有玩家周围的区块 不合法的配置只打印错误 继续使用原来的配置
+// 客户端也按照生成配置查询群落 连接时和重新加载后同步给客户端
+
+use std::time::{Duration, Instant, SystemTime};
+
+use bevy::{
+    prelude::{
+        EventReader, IVec3, Plugin, Query, Res, ResMut, Resource, Timer, TimerMode, Transform,
+        Update,
+    },
+    time::Time,
+    utils::HashSet,
+};
+use bevy_renet::renet::{RenetServer, ServerEvent};
+
+use crate::{
+    voxel_world::{
+        chunk::{get_chunk_key_i3_by_vec3, ChunkKey},
+        gen_config::{gen_config, set_gen_config, GenConfig},
+    },
+    GEN_CONFIG_PATH,
+};
+
+use super::{
+    async_chunk::PendingRegens,
+    message_def::{server_messages::ServerMessages, ServerChannel},
+    player::ServerLobby,
+};
+
+// 检查文件的间隔(秒)
+const POLL_INTERVAL: f32 = 0.5;
+// 文件修改后保持不变这么久才读取
+const DEBOUNCE: Duration = Duration::from_millis(500);
+// 重新加载后 玩家周围重新生成的区块半径
+pub const RELOAD_REGEN_RADIUS: i32 = 2;
+
+#[derive(Debug, Resource)]
+struct GenConfigWatcher {
+    timer: Timer,
+    // 已经处理过的修改时间
+    applied: Option<SystemTime>,
+    // 等待稳定的修改时间 和第一次看到的时间
+    pending: Option<(SystemTime, Instant)>,
+}
+
+pub struct GenConfigPlugin;
+
+impl Plugin for GenConfigPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        // 启动时的配置错误直接退出 和服务端配置一致
+        let config = GenConfig::load_or_create(GEN_CONFIG_PATH).unwrap_or_else(|err| {
+            eprintln!("生成配置错误 {}: {}", GEN_CONFIG_PATH, err);
+            std::process::exit(1);
+        });
+        set_gen_config(config);
+        app.insert_resource(GenConfigWatcher {
+            timer: Timer::from_seconds(POLL_INTERVAL, TimerMode::Repeating),
+            applied: file_modified(GEN_CONFIG_PATH),
+            pending: None,
+        });
+        app.add_systems(Update, (send_gen_config, reload_gen_config));
+    }
+}
+
+fn file_modified(path: &str) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn gen_config_message() -> Vec<u8> {
+    bincode::serialize(&ServerMessages::GenConfig(gen_config())).unwrap()
+}
+
+// 新连接的客户端 同步当前的生成配置
+fn send_gen_config(mut server_events: EventReader<ServerEvent>, mut server: ResMut<RenetServer>) {
+    for event in server_events.iter() {
+        if let ServerEvent::ClientConnected { client_id } = event {
+            server.send_message(
+                *client_id,
+                ServerChannel::ServerMessages,
+                gen_config_message(),
+            );
+        }
+    }
+}
+
+fn reload_gen_config(
+    time: Res<Time>,
+    mut watcher: ResMut<GenConfigWatcher>,
+    mut pending_regens: ResMut<PendingRegens>,
+    lobby: Res<ServerLobby>,
+    players: Query<&Transform>,
+    mut server: ResMut<RenetServer>,
+) {
+    if !watcher.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let Some(modified) = file_modified(GEN_CONFIG_PATH) else {
+        return;
+    };
+    if watcher.applied == Some(modified) {
+        return;
+    }
+    match watcher.pending {
+        Some((pending, since)) if pending == modified => {
+            if since.elapsed() < DEBOUNCE {
+                return;
+            }
+        }
+        _ => {
+            watcher.pending = Some((modified, Instant::now()));
+            return;
+        }
+    }
+    watcher.pending = None;
+    watcher.applied = Some(modified);
+
+    let config = match std::fs::read_to_string(GEN_CONFIG_PATH)
+        .map_err(|err| err.to_string())
+        .and_then(|text| GenConfig::parse(&text))
+    {
+        Ok(config) => config,
+        Err(err) => {
+            println!("生成配置没有重新加载 {}: {}", GEN_CONFIG_PATH, err);
+            return;
+        }
+    };
+    if config == gen_config() {
+        return;
+    }
+    set_gen_config(config);
+    println!("生成配置已重新加载 重新生成玩家周围的区块");
+    server.broadcast_message(ServerChannel::ServerMessages, gen_config_message());
+    // 在同一个区块中的玩家只重新生成一次 重叠的区块由 PendingRegens 合并
+    let centers: HashSet<IVec3> = lobby
+        .players
+        .values()
+        .filter_map(|entity| players.get(*entity).ok())
+        .map(|transform| {
+            let mut center = get_chunk_key_i3_by_vec3(transform.translation);
+            center.y = 0;
+            center
+        })
+        .collect();
+    for center in centers {
+        // 保留玩家的修改
+        pending_regens.push(ChunkKey(center), RELOAD_REGEN_RADIUS, true);
+    }
+}
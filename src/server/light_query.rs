@@ -0,0 +1,214 @@
+// 服务端的光照查询 生物生成 作物生长等玩法使用
+// 和客户端生成网格使用相同的数据和算法(voxel_world::light) 按照整列计算后缓存
+// 缓存的光照超过 LIGHT_CACHE_TTL 秒后查询时重新计算
+// 服务端修改方块时使用 refresh_region 丢弃附近的缓存 见 async_chunk::commit_edits
+
+use bevy::{
+    prelude::{IVec3, Local, Plugin, Res, ResMut, Resource, Update, Vec3},
+    time::Time,
+    utils::HashMap,
+};
+use ndshape::ConstShape;
+
+use crate::{
+    voxel_world::{
+        chunk::ChunkKey,
+        chunk_map::ChunkMap,
+        gen_config::COLUMN_MIN_CHUNK_Y,
+        light::{column_light, unpack_light, MAX_LIGHT},
+    },
+    ChunkColumnShape, CHUNK_SIZE, CHUNK_SIZE_U32, WORLD_HEIGHT,
+};
+
+use super::fill::{block_to_chunk, chunk_to_block};
+
+// 缓存的光照最多使用的时间(秒)
+pub const LIGHT_CACHE_TTL: f64 = 1.0;
+// 每隔这么久丢弃一次这段时间内没有重新计算的列(秒) 比 TTL 长很多 经常查询的列一直保留
+pub const LIGHT_CACHE_EXPIRE_INTERVAL: f64 = 30.0;
+
+#[derive(Debug, Default, Resource)]
+pub struct LightCache {
+    // y = 0 的区块 ==> (计算的时间, 整列的光照)
+    columns: HashMap<ChunkKey, (f64, Vec<u8>)>,
+}
+
+impl LightCache {
+    /**
+     * 方块的光照 返回 (天空光, 方块光)
+     * 天空光不考虑昼夜 没有加载的区块当做空气 世界上方是露天 下方没有光
+     */
+    pub fn light_at(&mut self, chunk_map: &ChunkMap, pos: IVec3, now: f64) -> (u8, u8) {
+        let (chunk_key, xyz) = block_to_chunk(pos);
+        // 和 ChunkMap::get_with_neighbor_full_y 的列坐标一致
+        let y = (chunk_key.0.y - COLUMN_MIN_CHUNK_Y) * CHUNK_SIZE + xyz[1] as i32;
+        if y >= WORLD_HEIGHT as i32 {
+            return (MAX_LIGHT, 0);
+        }
+        if y < 0 {
+            return (0, 0);
+        }
+        let column_key = ChunkKey(IVec3::new(chunk_key.0.x, 0, chunk_key.0.z));
+        let (computed_at, light) = self
+            .columns
+            .entry(column_key)
+            .or_insert_with(|| (now, column_light_of(chunk_map, column_key)));
+        if now - *computed_at >= LIGHT_CACHE_TTL {
+            *computed_at = now;
+            *light = column_light_of(chunk_map, column_key);
+        }
+        let index = ChunkColumnShape::linearize([xyz[0] + 1, y as u32, xyz[2] + 1]) as usize;
+        unpack_light(light[index])
+    }
+
+    /**
+     * 天空光和方块光中较大的一个 0 到 MAX_LIGHT
+     * world_pos 是方块内的任意一点
+     */
+    pub fn light_level_at(&mut self, chunk_map: &ChunkMap, world_pos: Vec3, now: f64) -> u8 {
+        let (sky, block) = self.light_at(chunk_map, world_pos.floor().as_ivec3(), now);
+        sky.max(block)
+    }
+
+    /**
+     * 丢弃包括这个范围的缓存 下一次查询时重新计算
+     * 光照会照到相邻的列 范围向外扩大 MAX_LIGHT 格
+     */
+    pub fn refresh_region(&mut self, min: IVec3, max: IVec3) {
+        let reach = IVec3::splat(MAX_LIGHT as i32);
+        let (min_key, _) = block_to_chunk(min.min(max) - reach);
+        let (max_key, _) = block_to_chunk(min.max(max) + reach);
+        self.columns.retain(|key, _| {
+            key.0.x < min_key.0.x
+                || key.0.x > max_key.0.x
+                || key.0.z < min_key.0.z
+                || key.0.z > max_key.0.z
+        });
+    }
+
+    // 修改了区块中的方块
+    pub fn refresh_chunk(&mut self, chunk_key: ChunkKey) {
+        self.refresh_region(
+            chunk_to_block(chunk_key, [0, 0, 0]),
+            chunk_to_block(chunk_key, [CHUNK_SIZE_U32 - 1; 3]),
+        );
+    }
+
+    // 丢弃很久没有查询的缓存 没有人查询的列不会一直占用内存
+    pub fn expire(&mut self, now: f64) {
+        self.columns
+            .retain(|_, (computed_at, _)| now - *computed_at < LIGHT_CACHE_EXPIRE_INTERVAL);
+    }
+}
+
+// 和网格生成一样包括相邻列中的光源
+fn column_light_of(chunk_map: &ChunkMap, column_key: ChunkKey) -> Vec<u8> {
+    let voxels = chunk_map.get_with_neighbor_full_y(column_key);
+    let seeds = chunk_map.light_seeds_near(column_key);
+    column_light(&voxels, &seeds)
+}
+
+pub struct LightQueryPlugin;
+
+impl Plugin for LightQueryPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.init_resource::<LightCache>();
+        app.add_systems(Update, expire_light_cache);
+    }
+}
+
+fn expire_light_cache(
+    time: Res<Time>,
+    mut last_expire: Local<f64>,
+    mut light_cache: ResMut<LightCache>,
+) {
+    let now = time.elapsed_seconds_f64();
+    if now - *last_expire < LIGHT_CACHE_EXPIRE_INTERVAL {
+        return;
+    }
+    *last_expire = now;
+    light_cache.expire(now);
+}
+
+#[test]
+fn test_light_cache() {
+    use crate::{
+        server::fill::fill_region,
+        voxel_world::{
+            gen_config::COLUMN_MAX_CHUNK_Y,
+            voxel::{Lamp, Stone, Voxel, VoxelMaterial},
+            voxel_registry::VOXEL_REGISTRY,
+        },
+        CHUNK_VOLUME,
+    };
+
+    let mut chunk_map = ChunkMap::new();
+    for x in -1..=1 {
+        for z in -1..=1 {
+            for y in COLUMN_MIN_CHUNK_Y..=COLUMN_MAX_CHUNK_Y {
+                let key = ChunkKey(IVec3::new(x, y, z));
+                chunk_map.write_chunk(key, vec![Voxel::EMPTY; CHUNK_VOLUME as usize]);
+            }
+        }
+    }
+    // 一个封闭的房间 里面 y = 10 到 12 是空气
+    fill_region(
+        &mut chunk_map,
+        IVec3::new(-5, 9, -5),
+        IVec3::new(5, 13, 5),
+        Stone::into_voxel(),
+    );
+    fill_region(
+        &mut chunk_map,
+        IVec3::new(-4, 10, -4),
+        IVec3::new(4, 12, 4),
+        Voxel::EMPTY,
+    );
+    let mut cache = LightCache::default();
+    assert_eq!(
+        cache.light_level_at(&chunk_map, Vec3::new(7.5, 11.5, 0.5), 0.0),
+        MAX_LIGHT
+    );
+    assert_eq!(
+        cache.light_at(&chunk_map, IVec3::new(0, 11, 0), 0.0),
+        (0, 0)
+    );
+
+    // 放置光源后 缓存过期或者刷新前仍然是旧的光照
+    fill_region(
+        &mut chunk_map,
+        IVec3::new(2, 10, 0),
+        IVec3::new(2, 10, 0),
+        Lamp::into_voxel(),
+    );
+    assert_eq!(
+        cache.light_at(&chunk_map, IVec3::new(0, 11, 0), 0.5),
+        (0, 0)
+    );
+    cache.refresh_region(IVec3::new(2, 10, 0), IVec3::new(2, 10, 0));
+    let level = VOXEL_REGISTRY.light(Lamp::ID);
+    assert_eq!(
+        cache.light_at(&chunk_map, IVec3::new(0, 11, 0), 0.5),
+        (0, level - 3)
+    );
+
+    // 过期后自动重新计算
+    fill_region(
+        &mut chunk_map,
+        IVec3::new(2, 10, 0),
+        IVec3::new(2, 10, 0),
+        Voxel::EMPTY,
+    );
+    assert_eq!(
+        cache.light_at(&chunk_map, IVec3::new(0, 11, 0), 1.5),
+        (0, 0)
+    );
+    // 过期的时间比 TTL 长 刚计算过的列保留
+    cache.expire(5.0);
+    assert!(!cache.columns.is_empty());
+    cache.refresh_chunk(ChunkKey(IVec3::new(0, 0, 0)));
+    assert!(cache.columns.is_empty());
+    cache.light_at(&chunk_map, IVec3::new(0, 11, 0), 2.0);
+    cache.expire(2.0 + LIGHT_CACHE_EXPIRE_INTERVAL);
+    assert!(cache.columns.is_empty());
+}
@@ -16,6 +16,8 @@ pub enum ChunkResult {
         data: (BitVec, Tree<Voxel>),
     },
     ChunkSame((ChunkKey, Voxel)),
+    // 更新整个区块为同一种体素
+    UpdateChunkSame((ChunkKey, Voxel)),
     ChunkUpdateOne {
         chunk_key: ChunkKey,
         pos: [u32; 3],
@@ -78,3 +78,81 @@ impl ServerChannel {
         ]
     }
 }
+
+// 固定消息的编码 任何一项不一致说明消息的结构改了 需要增加 PROTOCOL_VERSION 再更新这里
+#[test]
+fn test_protocol_layout() {
+    use bevy::prelude::{IVec3, Vec3};
+
+    use crate::{
+        client::message_def::{chunk_query::ChunkQuery, server_command::ServerCommandMessage},
+        voxel_world::{chunk::ChunkKey, voxel::Voxel},
+    };
+
+    use self::{chunk_result::ChunkResult, server_messages::ServerMessages};
+
+    assert_eq!(crate::PROTOCOL_VERSION, 4);
+    // 变体的序号和编码后的长度
+    fn layout<T: serde::Serialize>(message: &T) -> (u32, usize) {
+        let bytes = bincode::serialize(message).unwrap();
+        (
+            u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            bytes.len(),
+        )
+    }
+    let key = ChunkKey(IVec3::ZERO);
+    // 版本不一致时也要能读取断开的原因
+    assert_eq!(
+        layout(&ServerMessages::Disconnect {
+            reason: server_messages::ServerDisconnectReason::VersionMismatch {
+                server: 2,
+                client: 1,
+            },
+        }),
+        (5, 16)
+    );
+    // 断开原因的最后一个变体
+    assert_eq!(
+        layout(&ServerMessages::Disconnect {
+            reason: server_messages::ServerDisconnectReason::AdminNotLocal,
+        }),
+        (5, 8)
+    );
+    assert_eq!(layout(&ServerMessages::FlyMode { enabled: true }), (14, 5));
+    // 每种消息最后一个变体
+    assert_eq!(
+        layout(&ServerMessages::GenConfig(
+            crate::voxel_world::gen_config::GenConfig::default()
+        )),
+        (15, 73)
+    );
+    assert_eq!(
+        layout(&ChunkResult::ChunkUpdateOne {
+            chunk_key: key,
+            pos: [0, 0, 0],
+            voxel_type: Voxel::EMPTY,
+        }),
+        (4, 33)
+    );
+    assert_eq!(
+        layout(&ChunkQuery::Interact {
+            chunk_key: key,
+            pos: [0, 0, 0],
+        }),
+        (6, 28)
+    );
+    assert_eq!(
+        layout(&ChunkQuery::Change {
+            chunk_key: key,
+            pos: [0, 0, 0],
+            voxel_type: Voxel::EMPTY,
+            center: Vec3::ZERO,
+            active_index: None,
+        }),
+        (1, 46)
+    );
+    assert_eq!(
+        layout(&ServerCommandMessage::Fly { enabled: true }),
+        (18, 5)
+    );
+}
@@ -1,6 +1,11 @@
-use bevy::prelude::{Component, Entity};
+use bevy::prelude::{Component, Entity, Resource};
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    server::{mob::MobKind, permission::PermissionLevel, physics_config::PhysicsConfig},
+    voxel_world::{gen_config::GenConfig, voxel::Voxel},
+};
+
 #[derive(Debug, Serialize, Deserialize, Component)]
 pub enum ServerMessages {
     // 创建角色
@@ -14,4 +19,82 @@ pub enum ServerMessages {
     PlayerRemove {
         id: u64,
     },
+    // 同步世界种子
+    WorldSeed {
+        seed: i32,
+    },
+    // 同步物理参数
+    PhysicsConfig(PhysicsConfig),
+    // 服务端的 tick 频率
+    TickRate {
+        rate: u32,
+    },
+    // 即将断开连接的原因
+    // 协议版本不一致时也要能读取 不要修改这个消息之前的顺序
+    Disconnect {
+        reason: ServerDisconnectReason,
+    },
+    // 服务端指令的执行结果
+    CommandReply {
+        ok: bool,
+        text: String,
+    },
+    // 聊天消息
+    Chat {
+        username: String,
+        text: String,
+    },
+    // 私聊消息 同时发送给发送者和接收者
+    Whisper {
+        from: String,
+        to: String,
+        text: String,
+    },
+    // 当前玩家的权限等级
+    Permission {
+        level: PermissionLevel,
+    },
+    // 生成生物
+    MobSpawn {
+        id: u64,
+        kind: MobKind,
+        translation: [f32; 3],
+    },
+    // 删除生物
+    MobDespawn {
+        id: u64,
+    },
+    // 方块开始下落 translation 是方块的中心 下落到 target_y 停止
+    FallingBlockSpawn {
+        id: u64,
+        voxel: Voxel,
+        translation: [f32; 3],
+        target_y: f32,
+    },
+    // 下落的方块落地 区块的更新另外发送
+    FallingBlockLanded {
+        id: u64,
+    },
+    // 当前玩家是否在飞行
+    FlyMode {
+        enabled: bool,
+    },
+    // 同步地形生成配置 客户端查询群落时和服务端一致
+    GenConfig(GenConfig),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Resource)]
+pub enum ServerDisconnectReason {
+    // 相同用户名重复登录
+    UsernameTaken,
+    // 服务器人数已满
+    ServerFull,
+    // 被管理员踢出
+    Kicked(Option<String>),
+    // 被封禁
+    Banned(Option<String>),
+    // 协议版本不一致
+    VersionMismatch { server: u32, client: u32 },
+    // 管理员和 /op 提升过等级的用户名只能从本机登录
+    AdminNotLocal,
 }
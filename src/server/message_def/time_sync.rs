@@ -1,7 +1,11 @@
 use bevy::prelude::Component;
 use serde::{Deserialize, Serialize};
 
+use crate::sky::weather::WeatherState;
+
 #[derive(Debug, Serialize, Deserialize, Component)]
 pub enum TimeSync {
     SkyBox(f32),
+    // 当前的天气 每秒同步一次
+    Weather(WeatherState),
 }
@@ -8,4 +8,9 @@ pub enum ToolBarMessage {
         staff_id: Option<usize>,
         num: usize,
     },
+    // 工具的耐久用完了
+    ToolBroken {
+        index: usize,
+        staff_id: usize,
+    },
 }
@@ -0,0 +1,316 @@
+// 生物生成
+// 每个群落在 BIOME_REGISTRY 中配置可以生成的生物和权重
+// 服务端定期在玩家附近已经加载的区块中寻找合适的地面生成 然后同步给全部客户端
+// 目前只有生成和回收 没有移动和 AI
+
+use std::{f32::consts::TAU, time::Duration};
+
+use bevy::{
+    prelude::{
+        Commands, Component, Entity, EventReader, IVec3, Local, Plugin, Query, Res, ResMut,
+        Resource, Timer, TimerMode, Transform, Update, Vec3, With,
+    },
+    time::Time,
+    transform::TransformBundle,
+};
+use bevy_renet::renet::{RenetServer, ServerEvent};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    tools::vec3_to_chunk_key_any_xyz,
+    voxel_world::{
+        biomes::{refresh_sampler, BiomeHeightSampler},
+        chunk_map::ChunkMap,
+        map_database::WorldSeed,
+        voxel::Voxel,
+        voxel_registry::VOXEL_REGISTRY,
+    },
+};
+
+use super::{
+    light_query::LightCache,
+    message_def::{server_messages::ServerMessages, ServerChannel},
+    player::{Player, ServerLobby},
+};
+
+// 生成检查的间隔(秒)
+pub const MOB_SPAWN_INTERVAL: f32 = 5.0;
+// 每个玩家附近最多的生物数量
+pub const MOB_CAP_PER_PLAYER: usize = 6;
+// 每次检查 每个玩家尝试的位置数量
+pub const MOB_SPAWN_ATTEMPTS: usize = 4;
+// 生成位置和玩家的水平距离范围
+pub const MOB_SPAWN_MIN_DISTANCE: f32 = 16.0;
+pub const MOB_SPAWN_MAX_DISTANCE: f32 = 40.0;
+// 离所有玩家都超过这个距离后回收
+pub const MOB_DESPAWN_DISTANCE: f32 = 96.0;
+// 寻找地面时 在玩家高度上下搜索的范围
+const MOB_SURFACE_SEARCH: i32 = 24;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MobKind {
+    // 占位用的小动物 只在露天生成
+    Critter,
+}
+
+impl MobKind {
+    // 生成位置需要的最低光照
+    pub fn min_light(&self) -> u8 {
+        match self {
+            MobKind::Critter => 8,
+        }
+    }
+
+    // 需要的空间高度(格)
+    pub fn height(&self) -> i32 {
+        match self {
+            MobKind::Critter => 1,
+        }
+    }
+
+    // 碰撞盒的大小 客户端显示也使用这个大小
+    pub fn size(&self) -> Vec3 {
+        match self {
+            MobKind::Critter => Vec3::new(0.6, 0.6, 0.6),
+        }
+    }
+}
+
+// 群落中可以生成的生物和权重
+#[derive(Debug, Clone, Copy)]
+pub struct MobSpawnEntry {
+    pub kind: MobKind,
+    pub weight: u32,
+}
+
+#[derive(Debug, Component)]
+pub struct Mob {
+    pub id: u64,
+    pub kind: MobKind,
+    // 生成时附近的玩家 用来计算每个玩家的上限
+    pub owner: u64,
+}
+
+#[derive(Debug, Resource)]
+pub struct MobSpawner {
+    timer: Timer,
+    next_id: u64,
+}
+
+impl Default for MobSpawner {
+    fn default() -> Self {
+        Self {
+            timer: Timer::new(
+                Duration::from_secs_f32(MOB_SPAWN_INTERVAL),
+                TimerMode::Repeating,
+            ),
+            next_id: 0,
+        }
+    }
+}
+
+pub struct ServerMobPlugin;
+
+impl Plugin for ServerMobPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.init_resource::<MobSpawner>();
+        app.add_systems(Update, (sync_mobs_on_connect, spawn_mobs, despawn_mobs));
+    }
+}
+
+/**
+ * 按照权重选择生物
+ * roll 的范围是 [0, 总权重)
+ */
+pub fn pick_mob(entries: &[MobSpawnEntry], roll: u32) -> Option<MobKind> {
+    let mut roll = roll;
+    for entry in entries {
+        if roll < entry.weight {
+            return Some(entry.kind);
+        }
+        roll -= entry.weight;
+    }
+    None
+}
+
+// 世界坐标的体素 区块没有加载时返回 None
+fn voxel_at(chunk_map: &ChunkMap, pos: IVec3) -> Option<Voxel> {
+    let (chunk_key, xyz) = vec3_to_chunk_key_any_xyz(pos.as_vec3() + Vec3::splat(0.5));
+    chunk_map.get_block(chunk_key, xyz)
+}
+
+// 可以站立的地面
+fn is_ground(voxel: Voxel) -> bool {
+    let properties = VOXEL_REGISTRY.properties(voxel.id);
+    properties.is_solid && !properties.is_liquid
+}
+
+// 生物可以占用的空间
+fn is_open(voxel: Voxel) -> bool {
+    let properties = VOXEL_REGISTRY.properties(voxel.id);
+    !properties.is_solid && !properties.is_liquid
+}
+
+/**
+ * 在 x z 上从上往下寻找可以生成的地面 返回生物脚下方块上方的位置
+ * 只使用已经加载的区块
+ */
+fn find_surface(chunk_map: &ChunkMap, x: i32, z: i32, center_y: i32, height: i32) -> Option<IVec3> {
+    let mut open = 0;
+    for y in (center_y - MOB_SURFACE_SEARCH..=center_y + MOB_SURFACE_SEARCH).rev() {
+        let voxel = voxel_at(chunk_map, IVec3::new(x, y, z))?;
+        if is_open(voxel) {
+            open += 1;
+            continue;
+        }
+        if is_ground(voxel) && open >= height {
+            return Some(IVec3::new(x, y + 1, z));
+        }
+        open = 0;
+    }
+    None
+}
+
+fn spawn_message(mob: &Mob, translation: Vec3) -> Vec<u8> {
+    bincode::serialize(&ServerMessages::MobSpawn {
+        id: mob.id,
+        kind: mob.kind,
+        translation: translation.into(),
+    })
+    .unwrap()
+}
+
+// 新连接的玩家需要知道已经存在的生物
+fn sync_mobs_on_connect(
+    mut server_events: EventReader<ServerEvent>,
+    mut server: ResMut<RenetServer>,
+    mobs: Query<(&Mob, &Transform)>,
+) {
+    for event in server_events.iter() {
+        if let ServerEvent::ClientConnected { client_id } = event {
+            for (mob, transform) in mobs.iter() {
+                let message = spawn_message(mob, transform.translation);
+                server.send_message(*client_id, ServerChannel::ServerMessages, message);
+            }
+        }
+    }
+}
+
+/**
+ * 定期在每个玩家附近尝试生成生物
+ */
+#[allow(clippy::too_many_arguments)]
+fn spawn_mobs(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut spawner: ResMut<MobSpawner>,
+    mut server: ResMut<RenetServer>,
+    chunk_map: Res<ChunkMap>,
+    world_seed: Option<Res<WorldSeed>>,
+    players: Query<(&Player, &Transform)>,
+    mobs: Query<&Mob>,
+    mut sampler: Local<Option<(i32, BiomeHeightSampler)>>,
+    mut light_cache: ResMut<LightCache>,
+) {
+    if !spawner.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let Some(world_seed) = world_seed else {
+        return;
+    };
+    let sampler = refresh_sampler(&mut sampler, world_seed.0);
+    let mut rng = rand::thread_rng();
+    for (player, transform) in players.iter() {
+        let count = mobs.iter().filter(|mob| mob.owner == player.id).count();
+        let mut room = MOB_CAP_PER_PLAYER.saturating_sub(count);
+        for _ in 0..MOB_SPAWN_ATTEMPTS {
+            if room == 0 {
+                break;
+            }
+            let angle = rng.gen_range(0.0..TAU);
+            let distance = rng.gen_range(MOB_SPAWN_MIN_DISTANCE..MOB_SPAWN_MAX_DISTANCE);
+            let x = (transform.translation.x + angle.cos() * distance).floor();
+            let z = (transform.translation.z + angle.sin() * distance).floor();
+
+            let entries = sampler.biome_at(x, z).entry().mobs;
+            let total: u32 = entries.iter().map(|entry| entry.weight).sum();
+            if total == 0 {
+                continue;
+            }
+            let Some(kind) = pick_mob(entries, rng.gen_range(0..total)) else {
+                continue;
+            };
+            let center_y = transform.translation.y.floor() as i32;
+            let Some(pos) = find_surface(&chunk_map, x as i32, z as i32, center_y, kind.height())
+            else {
+                continue;
+            };
+            // 和客户端网格相同的光照
+            let (sky, block) = light_cache.light_at(&chunk_map, pos, time.elapsed_seconds_f64());
+            if sky.max(block) < kind.min_light() {
+                continue;
+            }
+
+            let mob = Mob {
+                id: spawner.next_id,
+                kind,
+                owner: player.id,
+            };
+            spawner.next_id += 1;
+            // 生物的中心放在方块中间
+            let translation =
+                pos.as_vec3() + Vec3::new(0.5, 0.0, 0.5) + Vec3::Y * kind.size().y / 2.0;
+            server.broadcast_message(
+                ServerChannel::ServerMessages,
+                spawn_message(&mob, translation),
+            );
+            commands.spawn((
+                mob,
+                TransformBundle::from_transform(Transform::from_translation(translation)),
+            ));
+            room -= 1;
+        }
+    }
+}
+
+/**
+ * 回收离开所有玩家太远 或者所属玩家已经离开的生物
+ */
+fn despawn_mobs(
+    mut commands: Commands,
+    mut server: ResMut<RenetServer>,
+    lobby: Res<ServerLobby>,
+    mobs: Query<(Entity, &Mob, &Transform)>,
+    players: Query<&Transform, With<Player>>,
+) {
+    for (entity, mob, transform) in mobs.iter() {
+        let near = players.iter().any(|player| {
+            player.translation.distance(transform.translation) < MOB_DESPAWN_DISTANCE
+        });
+        if near && lobby.players.contains_key(&mob.owner) {
+            continue;
+        }
+        commands.entity(entity).despawn();
+        let message = bincode::serialize(&ServerMessages::MobDespawn { id: mob.id }).unwrap();
+        server.broadcast_message(ServerChannel::ServerMessages, message);
+    }
+}
+
+#[test]
+fn test_pick_mob() {
+    let entries = [
+        MobSpawnEntry {
+            kind: MobKind::Critter,
+            weight: 3,
+        },
+        MobSpawnEntry {
+            kind: MobKind::Critter,
+            weight: 0,
+        },
+    ];
+    assert_eq!(pick_mob(&entries, 0), Some(MobKind::Critter));
+    assert_eq!(pick_mob(&entries, 2), Some(MobKind::Critter));
+    assert_eq!(pick_mob(&entries, 3), None);
+    assert_eq!(pick_mob(&[], 0), None);
+}
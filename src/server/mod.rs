@@ -1,4 +1,7 @@
-use bevy::prelude::{Commands, Entity, EventReader, Query, Res, ResMut, Transform, Vec3, With};
+use bevy::prelude::{
+    warn, Commands, Entity, EventReader, EventWriter, Query, Res, ResMut, Time, Transform, Vec3,
+    With,
+};
 use bevy_rapier3d::{
     prelude::{RapierContext, RapierRigidBodyHandle},
     rapier::prelude::RigidBodyMassProps,
@@ -8,32 +11,64 @@ use renet_visualizer::RenetServerVisualizer;
 
 use crate::{
     client::message_def::{player_input::PlayerInput, ClientChannel},
+    common::net_error::decode,
     server::{
-        message_def::{server_messages::ServerMessages, ServerChannel},
-        player::server_create_player,
+        ban_list::BanList,
+        disconnect::PendingDisconnects,
+        edit_history::EditHistory,
+        message_def::{
+            server_messages::{ServerDisconnectReason, ServerMessages},
+            ServerChannel,
+        },
+        permission::{is_local, permission_of, PermissionLevel},
+        player::{server_create_player, MaxPlayers, ServerAdmins},
+        scripting::ScriptEvent,
         tool_bar_sync::send_all_tool_bar,
     },
-    users::Username,
+    users::{protocol_version_from_user_data, Username},
     voxel_world::{
-        map_database::MapDataBase,
+        chunk_map::ChunkMap,
+        map_database::{MapDataBase, WorldSeed},
         player_state::{PlayerOnTimeState, PlayerState, StoragePlayerState},
+        spawn::SpawnPoint,
     },
+    PROTOCOL_VERSION,
 };
 
 use self::{
     message_def::networked_entities::NetworkedEntities,
-    player::{PitchValue, Player, ServerLobby, YawValue},
+    player::{Flying, MoveTarget, PitchValue, Player, ServerLobby, YawValue, MOVE_HOLD_TIME},
 };
 
 pub mod async_chunk;
+pub mod autosave;
+pub mod ban_list;
+pub mod brush;
+pub mod chat_limit;
 pub mod chunk;
+pub mod chunk_budget;
+pub mod config;
 pub mod cross_through_check;
+pub mod death;
+pub mod disconnect;
+pub mod edit_history;
+pub mod falling_block;
+pub mod fill;
+pub mod gen_pool;
+pub mod gen_reload;
+pub mod light_query;
 pub mod message_def;
+pub mod mob;
 pub mod object_filing;
+pub mod permission;
+pub mod physics_config;
 pub mod player;
+pub mod scripting;
+pub mod server_command;
 pub mod sp_physics;
 pub mod staff_rule_sync;
 pub mod terrain_physics;
+pub mod tick_rate;
 pub mod tool_bar_sync;
 
 /**
@@ -49,6 +84,14 @@ pub fn server_connect_system(
     mut server_lobby: ResMut<ServerLobby>,
     transport: Res<NetcodeServerTransport>,
     mut map_database: ResMut<MapDataBase>,
+    world_seed: Res<WorldSeed>,
+    max_players: Res<MaxPlayers>,
+    mut pending_disconnects: ResMut<PendingDisconnects>,
+    ban_list: Res<BanList>,
+    admins: Res<ServerAdmins>,
+    spawn_point: Res<SpawnPoint>,
+    mut edit_history: ResMut<EditHistory>,
+    mut script_events: EventWriter<ScriptEvent>,
 ) {
     for event in server_events.iter() {
         match event {
@@ -56,14 +99,74 @@ pub fn server_connect_system(
                 let user_data = transport.user_data(*client_id).unwrap();
                 let username = Username::from_user_data(&user_data).0;
                 println!("Player {}|{} connected.", client_id, username);
+                let version = protocol_version_from_user_data(&user_data);
+                if version != PROTOCOL_VERSION {
+                    pending_disconnects.disconnect(
+                        &mut server,
+                        *client_id,
+                        ServerDisconnectReason::VersionMismatch {
+                            server: PROTOCOL_VERSION,
+                            client: version,
+                        },
+                    );
+                    println!(
+                        "Player {}|{} 协议版本不一致: {} != {}.",
+                        client_id, username, version, PROTOCOL_VERSION
+                    );
+                    continue;
+                }
+                let ip = transport.client_addr(*client_id).map(|addr| addr.ip());
+                if let Some(entry) = ban_list.get(&username, ip, &admins) {
+                    pending_disconnects.disconnect(
+                        &mut server,
+                        *client_id,
+                        ServerDisconnectReason::Banned(entry.reason.clone()),
+                    );
+                    println!("Player {}|{} 已经被封禁.", client_id, username);
+                    continue;
+                }
+                // 用户名由客户端自己填写 管理员和 /op 提升过等级的用户名只接受本机的连接
+                let level = permission_of(&username, &admins, &map_database);
+                if level > PermissionLevel::Player && !is_local(&transport, *client_id) {
+                    pending_disconnects.disconnect(
+                        &mut server,
+                        *client_id,
+                        ServerDisconnectReason::AdminNotLocal,
+                    );
+                    println!(
+                        "Player {}|{} 不是本机连接 拒绝使用{:?}的用户名.",
+                        client_id, username, level
+                    );
+                    continue;
+                }
                 if server_lobby.names.contains(&username) {
                     // 相同用户名重复登录
-                    server.remove_connection(*client_id);
+                    pending_disconnects.disconnect(
+                        &mut server,
+                        *client_id,
+                        ServerDisconnectReason::UsernameTaken,
+                    );
                     println!("Player {}|{} 重复登录 已经被阻止.", client_id, username);
                     continue;
                 }
+                if server_lobby.players.len() >= max_players.0 {
+                    pending_disconnects.disconnect(
+                        &mut server,
+                        *client_id,
+                        ServerDisconnectReason::ServerFull,
+                    );
+                    println!("Player {}|{} 服务器已满 已经被阻止.", client_id, username);
+                    continue;
+                }
                 server_lobby.names.insert(username.clone());
                 visualizer.add_client(*client_id);
+                // 0. 同步世界种子
+                let message =
+                    bincode::serialize(&ServerMessages::WorldSeed { seed: world_seed.0 }).unwrap();
+                server.send_message(*client_id, ServerChannel::ServerMessages, message);
+                // 同步权限等级
+                let message = bincode::serialize(&ServerMessages::Permission { level }).unwrap();
+                server.send_message(*client_id, ServerChannel::ServerMessages, message);
                 // 1. 先通知 当前连接 其他的已经存在的用户数据
                 for (entity, player, transform, _) in players.iter() {
                     let translation: [f32; 3] = transform.translation.into();
@@ -77,17 +180,17 @@ pub fn server_connect_system(
                     server.send_message(*client_id, ServerChannel::ServerMessages, message);
                 }
                 // 2. 创建这个用户并(注意这里不用mesh 直接创建 一个物理对象就可以了。因为服务器不关心物体的姿态)
-                let transform = Transform::from_xyz(0., 60., 0.);
                 // -- 获取 到用户的信息
                 let mut player_state;
                 if let Some(state) = map_database.get_player_state(username.clone()) {
                     // 获取历史数据
                     player_state = state;
                 } else {
-                    // 第一次新建数据
+                    // 第一次新建数据 放在出生点
                     player_state = PlayerState::default();
-                    player_state.position = [0., 60., 0.];
+                    player_state.position = spawn_point.0.into();
                 }
+                let transform = Transform::from_translation(player_state.position.into());
 
                 let player_entity = server_create_player(
                     &mut commands,
@@ -109,12 +212,17 @@ pub fn server_connect_system(
                 // 发送物品栏 相关的同步信息
                 send_all_tool_bar(*client_id, &mut server, player_state);
                 server.broadcast_message(ServerChannel::ServerMessages, message);
+                script_events.send(ScriptEvent::Join { username });
             }
             ServerEvent::ClientDisconnected { client_id, reason } => {
+                // 被拒绝的连接没有进入大厅 不需要清理
                 if let bevy_renet::renet::DisconnectReason::DisconnectedByServer = reason {
-                    continue;
+                    if !server_lobby.players.contains_key(client_id) {
+                        continue;
+                    }
                 }
                 visualizer.remove_client(*client_id);
+                edit_history.clear(*client_id);
                 println!("Player {} disconnected: {}", client_id, reason);
                 // 告诉所有人减少了一个用户
                 if let Some(player_entity) = server_lobby.players.remove(client_id) {
@@ -125,6 +233,9 @@ pub fn server_connect_system(
                             [tf.translation.x, tf.translation.y, tf.translation.z];
                         server_lobby.names.remove(&player.username.clone());
                         map_database.save_player_state(player.username.clone(), save_state);
+                        script_events.send(ScriptEvent::Leave {
+                            username: player.username.clone(),
+                        });
                     }
                     commands.entity(player_entity).despawn();
                 }
@@ -142,27 +253,45 @@ pub fn deal_message_system(
     mut server: ResMut<RenetServer>,
     lobby: ResMut<ServerLobby>,
     mut context: ResMut<RapierContext>,
-    query: Query<(Entity, &RapierRigidBodyHandle), With<Player>>,
+    chunk_map: Res<ChunkMap>,
+    query: Query<(Entity, &RapierRigidBodyHandle, &Transform, Option<&Flying>), With<Player>>,
 ) {
     let xz = Vec3::new(1.0, 0.0, 1.0);
     for client_id in server.clients_id() {
         while let Some(message) = server.receive_message(client_id, ClientChannel::Input) {
-            let player_input: PlayerInput = bincode::deserialize(&message).unwrap();
+            let player_input: PlayerInput = match decode(&message) {
+                Ok(player_input) => player_input,
+                Err(err) => {
+                    warn!("{}|{}", client_id, err);
+                    continue;
+                }
+            };
             match player_input {
                 PlayerInput::MOVE(vec3) => {
                     if let Some(player_entity) = lobby.players.get(&client_id) {
-                        if let Ok((_, handle)) = query.get(*player_entity) {
-                            if let Some(body) = context.bodies.get_mut(handle.0) {
-                                let mass_props: &RigidBodyMassProps = body.mass_properties();
-                                let effective_mass = mass_props.effective_mass();
-                                let velocity: Vec3 = (*body.linvel()).into();
-                                // 作用冲量
-                                body.apply_impulse(
-                                    ((vec3 - velocity * xz) * effective_mass.x).into(),
-                                    true,
-                                );
-                            }
+                        let Ok((_, handle, transform, flying)) = query.get(*player_entity) else {
+                            continue;
+                        };
+                        // 飞行和攀爬时竖直方向和水平方向一样由 hold_move_targets 保持
+                        if flying.is_some() || chunk_map.climbable_at(transform.translation) {
+                            commands.entity(*player_entity).insert(MoveTarget {
+                                velocity: vec3,
+                                remaining: MOVE_HOLD_TIME,
+                                vertical: true,
+                            });
+                            continue;
+                        }
+                        if let Some(body) = context.bodies.get_mut(handle.0) {
+                            let mass_props: &RigidBodyMassProps = body.mass_properties();
+                            let effective_mass = mass_props.effective_mass();
+                            // 竖直方向(跳跃)只作用一次 水平方向由 hold_move_targets 每帧修正
+                            body.apply_impulse((Vec3::Y * vec3.y * effective_mass.x).into(), true);
                         }
+                        commands.entity(*player_entity).insert(MoveTarget {
+                            velocity: vec3 * xz,
+                            remaining: MOVE_HOLD_TIME,
+                            vertical: false,
+                        });
                     }
                 }
                 PlayerInput::YAW(yaw) => {
@@ -180,6 +309,35 @@ pub fn deal_message_system(
     }
 }
 
+/**
+ * 两次移动消息之间保持收到的水平速度 飞行和攀爬时也保持竖直速度
+ * 作用冲量让速度等于目标 超过保持时间后不再修正
+ */
+pub fn hold_move_targets(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut context: ResMut<RapierContext>,
+    mut query: Query<(Entity, &RapierRigidBodyHandle, &mut MoveTarget)>,
+) {
+    let xz = Vec3::new(1.0, 0.0, 1.0);
+    for (entity, handle, mut target) in query.iter_mut() {
+        let mask = if target.vertical { Vec3::ONE } else { xz };
+        if let Some(body) = context.bodies.get_mut(handle.0) {
+            let effective_mass = body.mass_properties().effective_mass();
+            let velocity: Vec3 = (*body.linvel()).into();
+            // 作用冲量
+            body.apply_impulse(
+                ((target.velocity - velocity * mask) * effective_mass.x).into(),
+                true,
+            );
+        }
+        target.remaining -= time.delta_seconds();
+        if target.remaining <= 0.0 {
+            commands.entity(entity).remove::<MoveTarget>();
+        }
+    }
+}
+
 // 同步玩家角色的位置 头部
 
 pub fn sync_body_and_head(
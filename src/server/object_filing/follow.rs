@@ -29,7 +29,7 @@ use crate::{
     CLOSE_RANGE, NEAR_RANGE, PICK_SPEED,
 };
 
-use super::{throw_object::ThrowObject, FilledObject};
+use super::{throw_object::ThrowObject, FilledObject, ToolWear};
 
 #[derive(Clone, Component, Reflect)]
 #[component(storage = "SparseSet")]
@@ -213,14 +213,21 @@ fn pick_up_entity(
     // 有状态的角色
     mut palyer_states: Query<(Entity, &Player, &mut PlayerOnTimeState)>,
     // 被捡起的数据
-    pick_query: Query<(Entity, &FilledObject, &Picked)>,
+    pick_query: Query<(Entity, &FilledObject, &Picked, Option<&ToolWear>)>,
     mut server: ResMut<RenetServer>,
 ) {
-    for (pick_entity, filled_object, picked) in pick_query.iter() {
+    for (pick_entity, filled_object, picked, wear) in pick_query.iter() {
         // 1. 获取到pick的目标受体
         if let Ok((_, player, mut player_state)) = palyer_states.get_mut(picked.target) {
-            // 2. 检查可以使用的空位 并修改数据
-            if let Some((index, _, num)) = player_state.0.put_staff(filled_object.staff.id) {
+            // 2. 检查可以使用的空位 并修改数据 工具不堆叠
+            let staff = &filled_object.staff;
+            let put = match staff.durability {
+                Some(durability) => player_state
+                    .0
+                    .put_tool(staff.id, wear.map_or(durability, |wear| wear.0)),
+                None => player_state.0.put_staff(staff.id),
+            };
+            if let Some((index, _, num)) = put {
                 // 找到位置并摆放
                 // 发送消息销毁对象
                 let message = bincode::serialize(&ToolBarMessage::SyncToolbar {
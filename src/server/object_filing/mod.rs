@@ -50,6 +50,10 @@ pub struct FilledObject {
     pub staff: Staff,
 }
 
+// 丢出的工具剩余的耐久 捡起时恢复 没有时使用完整的耐久
+#[derive(Debug, Component, Clone, Copy)]
+pub struct ToolWear(pub usize);
+
 pub trait GetChunkKey {
     fn get_chunk_key(&self) -> ChunkKey;
 }
@@ -63,21 +67,13 @@ impl GetChunkKey for FilledObject {
 // 处理服务端的物体掉落
 fn deal_object_filing(mut commands: Commands, mut fill_event: EventReader<ObjectFillEvent>) {
     for event in fill_event.iter() {
-        // 通过staff 生成不同物体的加载模式
-        match event.staff.staff_type {
-            crate::staff::StaffType::Voxel(_)
-            | crate::staff::StaffType::Consumable(_)
-            | crate::staff::StaffType::Sp(_) => {
-                // 渲染一个正方形的 并且添加物理引擎
-                gen_filled_object(
-                    &mut commands,
-                    event.chunk_key,
-                    event.center,
-                    event.staff.clone(),
-                );
-            }
-            _ => {}
-        }
+        // 所有类型的物品都渲染一个正方形的 并且添加物理引擎
+        gen_filled_object(
+            &mut commands,
+            event.chunk_key,
+            event.center,
+            event.staff.clone(),
+        );
     }
 }
 
@@ -1,6 +1,6 @@
 // 接受处理 物体被丢弃的消息
 use bevy::{
-    prelude::{Commands, Component, Entity, Plugin, Query, Res, ResMut, Transform, Update},
+    prelude::{warn, Commands, Component, Entity, Plugin, Query, Res, ResMut, Transform, Update},
     time::{Time, Timer, TimerMode},
 };
 use bevy_rapier3d::prelude::ExternalImpulse;
@@ -8,13 +8,17 @@ use bevy_renet::renet::RenetServer;
 
 use crate::{
     client::message_def::{user_command::UserCommandMessage, ClientChannel},
-    server::{player::ServerLobby, tool_bar_sync::send_all_tool_bar},
+    common::net_error::decode,
+    server::{
+        player::ServerLobby,
+        tool_bar_sync::{send_all_tool_bar, send_tool_broken},
+    },
     staff::StaffInfoStroge,
     tools::vec3_to_chunk_key_any_xyz,
     voxel_world::player_state::PlayerOnTimeState,
 };
 
-use super::gen_filled_object;
+use super::{gen_filled_object, ToolWear};
 
 #[derive(Debug, Component, Clone)]
 pub struct ThrowObject(pub Timer);
@@ -30,7 +34,13 @@ pub fn deal_with_throw_object(
         while let Some(message) = server.receive_message(client_id, ClientChannel::Command) {
             if let Some(entity) = server_lobby.players.get(&client_id) {
                 if let Ok((_, trf, mut player_state)) = query.get_mut(*entity) {
-                    let message: UserCommandMessage = bincode::deserialize(&message).unwrap();
+                    let message: UserCommandMessage = match decode(&message) {
+                        Ok(message) => message,
+                        Err(err) => {
+                            warn!("{}|{}", client_id, err);
+                            continue;
+                        }
+                    };
                     match message {
                         UserCommandMessage::Throw {
                             index,
@@ -39,13 +49,26 @@ pub fn deal_with_throw_object(
                         } => {
                             if let Some(staff) = staff_info_stroge.get(staff_id) {
                                 // 判断是否可以 丢弃物品？
-                                if player_state.0.use_staff(index, staff_id, 1) != None {
+                                // 工具整个丢出 丢出也算使用一次 剩余的耐久保留在掉落物上
+                                let thrown = if staff.is_tool() {
+                                    player_state
+                                        .0
+                                        .take_staff(index, staff_id)
+                                        .map(|num| Some(num.saturating_sub(1)))
+                                } else {
+                                    player_state.0.use_staff(index, staff_id, 1).map(|_| None)
+                                };
+                                if let Some(wear) = thrown {
                                     // 同步 toolbar
                                     send_all_tool_bar(
                                         client_id,
                                         &mut server,
                                         player_state.0.clone(),
                                     );
+                                    if wear == Some(0) {
+                                        send_tool_broken(client_id, &mut server, index, staff_id);
+                                        continue;
+                                    }
                                     // 生成 丢弃物
                                     let (chunk_key, _) = vec3_to_chunk_key_any_xyz(trf.translation);
                                     let throw_object = gen_filled_object(
@@ -54,6 +77,9 @@ pub fn deal_with_throw_object(
                                         trf.translation,
                                         staff,
                                     );
+                                    if let Some(wear) = wear {
+                                        commands.entity(throw_object).insert(ToolWear(wear));
+                                    }
                                     // 添加throw组件。 和额外冲量
                                     commands
                                         .entity(throw_object)
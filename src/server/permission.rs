@@ -0,0 +1,150 @@
+// 玩家的权限等级
+// 每个指令声明需要的最低等级 等级保存在数据库中 启动时配置的管理员始终是 Admin
+
+use bevy::{
+    ecs::system::SystemParam,
+    prelude::{Query, Res, Resource},
+};
+use bevy_renet::renet::transport::NetcodeServerTransport;
+use serde::{Deserialize, Serialize};
+
+use crate::voxel_world::map_database::MapDataBase;
+
+use super::player::{Player, ServerAdmins};
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    Resource,
+    clap::ValueEnum,
+)]
+pub enum PermissionLevel {
+    Guest,
+    // 新玩家默认的等级
+    #[default]
+    Player,
+    Moderator,
+    Admin,
+}
+
+// 控制台指令需要的最低等级 没有列出的指令所有人都可以使用
+pub const COMMAND_LEVELS: &[(&str, PermissionLevel)] = &[
+    ("kick", PermissionLevel::Moderator),
+    ("ban", PermissionLevel::Moderator),
+    ("banlist", PermissionLevel::Moderator),
+    ("tp", PermissionLevel::Moderator),
+    ("tphere", PermissionLevel::Moderator),
+    ("unban", PermissionLevel::Admin),
+    ("op", PermissionLevel::Admin),
+    ("regen", PermissionLevel::Admin),
+    ("gravity", PermissionLevel::Admin),
+    ("jump", PermissionLevel::Admin),
+    ("bandwidth", PermissionLevel::Admin),
+    ("weather", PermissionLevel::Admin),
+    ("save", PermissionLevel::Admin),
+    ("fill", PermissionLevel::Admin),
+    ("clear", PermissionLevel::Admin),
+    ("brush", PermissionLevel::Admin),
+];
+
+/**
+ * 指令需要的最低等级
+ * 客户端用来隐藏不能使用的指令 服务端的 ServerCommandMessage::required_level 也由这里得到
+ * 同一个指令的部分参数可能需要更高的等级(比如 tp 到坐标)
+ */
+pub fn command_level(command_name: &str) -> PermissionLevel {
+    COMMAND_LEVELS
+        .iter()
+        .find(|(name, _)| *name == command_name)
+        .map_or(PermissionLevel::Guest, |(_, level)| *level)
+}
+
+pub trait StoragePermission {
+    fn save_permission(&mut self, username: &str, level: PermissionLevel);
+    fn get_permission(&self, username: &str) -> Option<PermissionLevel>;
+}
+
+impl StoragePermission for MapDataBase {
+    fn save_permission(&mut self, username: &str, level: PermissionLevel) {
+        let key = format!("P:{}", username);
+        if let Err(err) = self.db.insert(key, bincode::serialize(&level).unwrap()) {
+            println!("保存玩家权限失败{:?}", err);
+        }
+    }
+
+    fn get_permission(&self, username: &str) -> Option<PermissionLevel> {
+        let key = format!("P:{}", username);
+        match self.db.get(key) {
+            Ok(Some(data)) => bincode::deserialize(&data).ok(),
+            _ => None,
+        }
+    }
+}
+
+// 玩家的权限等级 按用户名查找 高于 Player 的用户名在连接时已经限制为本机地址
+pub fn permission_of(username: &str, admins: &ServerAdmins, db: &MapDataBase) -> PermissionLevel {
+    if admins.0.contains(username) {
+        return PermissionLevel::Admin;
+    }
+    db.get_permission(username).unwrap_or_default()
+}
+
+/**
+ * 客户端是否从本机(回环地址)连接
+ * 用户名由客户端自己填写 无法验证身份 高于 Player 的等级只对本机连接生效
+ */
+pub fn is_local(transport: &NetcodeServerTransport, client_id: u64) -> bool {
+    transport
+        .client_addr(client_id)
+        .is_some_and(|addr| addr.ip().is_loopback())
+}
+
+/**
+ * 查询在线玩家的权限
+ */
+#[derive(SystemParam)]
+pub struct Permissions<'w, 's> {
+    admins: Res<'w, ServerAdmins>,
+    players: Query<'w, 's, &'static Player>,
+}
+
+impl<'w, 's> Permissions<'w, 's> {
+    pub fn username_of(&self, client_id: u64) -> Option<String> {
+        self.players
+            .iter()
+            .find(|player| player.id == client_id)
+            .map(|player| player.username.clone())
+    }
+
+    pub fn client_id_of(&self, username: &str) -> Option<u64> {
+        self.players
+            .iter()
+            .find(|player| player.username == username)
+            .map(|player| player.id)
+    }
+
+    pub fn is_admin(&self, username: &str) -> bool {
+        self.admins.0.contains(username)
+    }
+
+    // 只能管理等级比自己低的玩家 不在线的玩家按照保存的等级
+    pub fn outranks(&self, client_id: u64, username: &str, db: &MapDataBase) -> bool {
+        self.level_of(client_id, db) > permission_of(username, &self.admins, db)
+    }
+
+    // 不在线的客户端当做 Guest
+    pub fn level_of(&self, client_id: u64, db: &MapDataBase) -> PermissionLevel {
+        match self.username_of(client_id) {
+            Some(username) => permission_of(&username, &self.admins, db),
+            None => PermissionLevel::Guest,
+        }
+    }
+}
@@ -0,0 +1,219 @@
+// 可以调整的物理参数 低重力模式和测试使用
+// 服务端修改后同步给所有客户端 客户端使用其中的跳跃速度
+// 物理使用固定的步长 跳跃高度和下落速度不受帧率影响 见 PHYSICS_TIMESTEP
+
+use bevy::prelude::{
+    DetectChanges, EventReader, Plugin, PostUpdate, Query, Res, ResMut, Resource, Startup, Update,
+    Vec3, With,
+};
+use bevy_rapier3d::prelude::{
+    RapierConfiguration, RapierContext, RapierRigidBodyHandle, TimestepMode,
+};
+use bevy_renet::renet::{RenetServer, ServerEvent};
+use serde::{Deserialize, Serialize};
+
+use crate::PHYSICS_TIMESTEP;
+
+use super::{
+    message_def::{server_messages::ServerMessages, ServerChannel},
+    player::Player,
+};
+
+// 指令可以设置的最大重力和跳跃速度 太大的值会让物理和摔落伤害失去意义
+pub const MAX_GRAVITY: f32 = 100.0;
+pub const MAX_JUMP_SPEED: f32 = 50.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Resource, Serialize, Deserialize)]
+pub struct PhysicsConfig {
+    // 重力加速度(向下)
+    pub gravity: f32,
+    // 起跳时的竖直速度
+    pub jump_speed: f32,
+    // 最大下落速度 低重力时也不会无限加速
+    pub terminal_velocity: f32,
+}
+
+impl Default for PhysicsConfig {
+    fn default() -> Self {
+        // 和原来的手感保持一致
+        Self {
+            gravity: 9.81,
+            jump_speed: 5.0,
+            terminal_velocity: 55.0,
+        }
+    }
+}
+
+impl PhysicsConfig {
+    /**
+     * 按照指令修改参数 限制在 0 到最大值之间
+     * 任何一个值不是有限的数时都不修改
+     */
+    pub fn set(&mut self, gravity: Option<f32>, jump_speed: Option<f32>) -> Result<(), String> {
+        if gravity
+            .into_iter()
+            .chain(jump_speed)
+            .any(|value| !value.is_finite())
+        {
+            return Err(String::from("Physics values must be finite numbers"));
+        }
+        if let Some(gravity) = gravity {
+            self.gravity = gravity.clamp(0.0, MAX_GRAVITY);
+        }
+        if let Some(jump_speed) = jump_speed {
+            self.jump_speed = jump_speed.clamp(0.0, MAX_JUMP_SPEED);
+        }
+        Ok(())
+    }
+}
+
+pub struct PhysicsConfigPlugin;
+
+impl Plugin for PhysicsConfigPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.init_resource::<PhysicsConfig>();
+        app.add_systems(Startup, use_fixed_timestep);
+        app.add_systems(Update, (apply_gravity, sync_physics_config));
+        app.add_systems(PostUpdate, clamp_fall_speed);
+    }
+}
+
+/**
+ * 每帧按照经过的时间执行若干个固定步长 剩下的时间用来插值位置
+ * 可变步长时 tick 频率不同跳跃的高度也不同
+ */
+fn use_fixed_timestep(mut rapier_config: ResMut<RapierConfiguration>) {
+    rapier_config.timestep_mode = TimestepMode::Interpolated {
+        dt: PHYSICS_TIMESTEP,
+        time_scale: 1.0,
+        substeps: 1,
+    };
+}
+
+fn apply_gravity(
+    physics_config: Res<PhysicsConfig>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+) {
+    if physics_config.is_changed() {
+        rapier_config.gravity = Vec3::NEG_Y * physics_config.gravity;
+    }
+}
+
+/**
+ * 限制玩家的下落速度
+ * 落地的冲击只和落地速度有关 所以重力越小落地越轻 见 death::LETHAL_FALL_SPEED
+ */
+fn clamp_fall_speed(
+    physics_config: Res<PhysicsConfig>,
+    mut context: ResMut<RapierContext>,
+    query: Query<&RapierRigidBodyHandle, With<Player>>,
+) {
+    for handle in query.iter() {
+        if let Some(body) = context.bodies.get_mut(handle.0) {
+            let mut velocity: Vec3 = (*body.linvel()).into();
+            if velocity.y < -physics_config.terminal_velocity {
+                velocity.y = -physics_config.terminal_velocity;
+                body.set_linvel(velocity.into(), true);
+            }
+        }
+    }
+}
+
+// 新连接的客户端 和 参数修改后 同步物理参数
+fn sync_physics_config(
+    mut server_events: EventReader<ServerEvent>,
+    physics_config: Res<PhysicsConfig>,
+    mut server: ResMut<RenetServer>,
+) {
+    let message = bincode::serialize(&ServerMessages::PhysicsConfig(*physics_config)).unwrap();
+    if physics_config.is_changed() {
+        server.broadcast_message(ServerChannel::ServerMessages, message);
+        return;
+    }
+    for event in server_events.iter() {
+        if let ServerEvent::ClientConnected { client_id } = event {
+            server.send_message(*client_id, ServerChannel::ServerMessages, message.clone());
+        }
+    }
+}
+
+#[test]
+fn test_jump_apex_fixed_timestep() {
+    use std::time::Duration;
+
+    use bevy::{
+        prelude::{
+            AddAsset, App, AssetPlugin, HierarchyPlugin, Mesh, MinimalPlugins, TransformBundle,
+            TransformPlugin,
+        },
+        scene::ScenePlugin,
+        time::TimeUpdateStrategy,
+    };
+    use bevy_rapier3d::prelude::{Collider, NoUserData, RapierPhysicsPlugin, RigidBody, Velocity};
+    use bevy_renet::renet::ConnectionConfig;
+
+    // 按照帧率运行插件模拟一次跳跃 读取物理中的位置 不受插值的影响
+    let jump_apex = |fps: f32| {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            TransformPlugin,
+            HierarchyPlugin,
+            AssetPlugin::default(),
+            ScenePlugin,
+        ));
+        app.add_asset::<Mesh>();
+        app.add_plugins((
+            RapierPhysicsPlugin::<NoUserData>::default(),
+            PhysicsConfigPlugin,
+        ));
+        app.insert_resource(RenetServer::new(ConnectionConfig::default()));
+        app.add_event::<ServerEvent>();
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(
+            1.0 / fps,
+        )));
+        let entity = app
+            .world
+            .spawn((
+                RigidBody::Dynamic,
+                Collider::ball(0.4),
+                Velocity::linear(Vec3::Y * PhysicsConfig::default().jump_speed),
+                TransformBundle::default(),
+            ))
+            .id();
+        let mut apex: f32 = 0.0;
+        for _ in 0..(fps * 2.0) as usize {
+            app.update();
+            let Some(handle) = app.world.get::<RapierRigidBodyHandle>(entity) else {
+                continue;
+            };
+            let context = app.world.resource::<RapierContext>();
+            apex = apex.max(context.bodies[handle.0].translation().y);
+        }
+        assert!(matches!(
+            app.world.resource::<RapierConfiguration>().timestep_mode,
+            TimestepMode::Interpolated { .. }
+        ));
+        apex
+    };
+    let (low, high) = (jump_apex(30.0), jump_apex(300.0));
+    assert!(low > 1.0);
+    // 可变步长时两种帧率的高度相差 0.07 左右
+    assert!((low - high).abs() < 0.01, "{} {}", low, high);
+}
+
+#[test]
+fn test_physics_config_set() {
+    let mut config = PhysicsConfig::default();
+    assert!(config.set(Some(1.62), None).is_ok());
+    assert_eq!(config.gravity, 1.62);
+    // 负数和过大的值被限制
+    assert!(config.set(Some(-3.0), Some(1e30)).is_ok());
+    assert_eq!((config.gravity, config.jump_speed), (0.0, MAX_JUMP_SPEED));
+    // 不是有限的数时不修改
+    let before = config;
+    assert!(config.set(Some(f32::INFINITY), None).is_err());
+    assert!(config.set(Some(2.0), Some(f32::NAN)).is_err());
+    assert!(config.set(None, Some(f32::NEG_INFINITY)).is_err());
+    assert_eq!(config, before);
+}
@@ -6,11 +6,16 @@ use bevy::{
 };
 use bevy_rapier3d::prelude::{
     Ccd, Collider, ColliderMassProperties, CollisionGroups, Group, LockedAxes, RigidBody, Sleeping,
+    TransformInterpolation,
 };
+use bevy_renet::renet::RenetServer;
 
-use crate::voxel_world::player_state::{PlayerOnTimeState, PlayerState};
+use crate::voxel_world::{
+    player_state::{PlayerOnTimeState, PlayerState},
+    world::WorldId,
+};
 
-use super::cross_through_check::CossTroughCheck;
+use super::{cross_through_check::CossTroughCheck, message_def::ServerChannel};
 
 #[derive(Debug, Component)]
 pub struct Player {
@@ -25,6 +30,14 @@ pub struct ServerLobby {
     pub names: HashSet<String>,
 }
 
+// 服务器最大玩家数
+#[derive(Debug, Clone, Copy, Resource)]
+pub struct MaxPlayers(pub usize);
+
+// 管理员的用户名 启动时配置
+#[derive(Debug, Default, Clone, Resource)]
+pub struct ServerAdmins(pub HashSet<String>);
+
 pub fn server_create_player(
     commands: &mut Commands,
     player_state: PlayerState,
@@ -40,6 +53,8 @@ pub fn server_create_player(
         })
         .insert(TransformBundle::from(transform))
         .insert(RigidBody::Dynamic)
+        // 固定步长之间插值位置 同步给客户端的位置是平滑的
+        .insert(TransformInterpolation::default())
         .insert(Sleeping::default())
         .insert(ColliderMassProperties::Mass(300.0))
         .insert(LockedAxes::ROTATION_LOCKED)
@@ -65,3 +80,38 @@ pub struct YawValue(pub f32);
 
 #[derive(Debug, Component, Default)]
 pub struct PitchValue(pub f32);
+
+// 收到的移动保持的时间(秒) 比客户端最慢的发送间隔长 客户端不再发送时角色停下来
+pub const MOVE_HOLD_TIME: f32 = 0.25;
+
+// 客户端不是每帧都发送移动 收到的水平速度保持一段时间 期间每帧修正
+#[derive(Debug, Component)]
+pub struct MoveTarget {
+    pub velocity: Vec3,
+    pub remaining: f32,
+    // 飞行或者攀爬时竖直方向也每帧修正 抵消重力
+    pub vertical: bool,
+}
+
+// 飞行中的玩家 不受重力影响 竖直方向的速度也由移动保持
+#[derive(Debug, Component)]
+pub struct Flying;
+
+/**
+ * 只发送给位置在 world 中的玩家
+ * 所有世界共用一个区块空间 其他世界的玩家不需要这个世界的聊天和方块更新
+ */
+pub fn send_to_world(
+    server: &mut RenetServer,
+    players: impl IntoIterator<Item = (u64, Vec3)>,
+    world: Option<WorldId>,
+    channel: ServerChannel,
+    message: Vec<u8>,
+) {
+    let channel: u8 = channel.into();
+    for (client_id, position) in players {
+        if WorldId::of_position(position) == world {
+            server.send_message(client_id, channel, message.clone());
+        }
+    }
+}
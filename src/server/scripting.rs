@@ -0,0 +1,424 @@
+// 服务端脚本
+// 启动时加载 SCRIPTS_PATH 目录中的 .rhai 脚本 脚本中定义同名的函数来响应事件
+// on_join(name) on_leave(name) on_place(name, x, y, z, voxel) on_break(name, x, y, z, voxel) on_chat(name, text)
+// 脚本只能通过注册的函数影响游戏 函数把操作放进队列 由系统在主线程执行
+// send_message(name, text) broadcast(text) teleport(name, x, y, z) give_item(name, staff_id, count)
+// 加载和执行的错误只打印日志 不会让服务端退出
+
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use bevy::prelude::{
+    Entity, Event, EventReader, IVec3, Plugin, Query, Res, ResMut, Resource, Update, Vec3,
+};
+use bevy_renet::renet::RenetServer;
+use rhai::{CallFnOptions, Dynamic, Engine, Scope, AST, FLOAT, INT};
+
+use crate::{
+    staff::StaffInfoStroge,
+    voxel_world::{player_state::PlayerOnTimeState, voxel::Voxel, voxel_registry::VOXEL_REGISTRY},
+    SCRIPTS_PATH,
+};
+
+use super::{
+    message_def::{
+        server_messages::ServerMessages, tool_bar_message::ToolBarMessage, ServerChannel,
+    },
+    player::Player,
+    server_command::{PendingTeleport, PendingTeleports},
+};
+
+// 脚本发送的聊天消息使用的名字
+pub const SCRIPT_CHAT_NAME: &str = "server";
+// 一次执行的最大操作数 防止死循环卡住服务端
+const MAX_SCRIPT_OPERATIONS: u64 = 100_000;
+// 一次最多给予的物品数量
+const MAX_GIVE_COUNT: INT = 64;
+
+#[derive(Debug, Clone, Event)]
+pub enum ScriptEvent {
+    Join {
+        username: String,
+    },
+    Leave {
+        username: String,
+    },
+    Place {
+        username: String,
+        block: IVec3,
+        voxel: Voxel,
+    },
+    Break {
+        username: String,
+        block: IVec3,
+        voxel: Voxel,
+    },
+    Chat {
+        username: String,
+        text: String,
+    },
+}
+
+impl ScriptEvent {
+    // 对应的脚本函数和参数
+    pub fn hook(&self) -> (&'static str, Vec<Dynamic>) {
+        let block_args = |username: &str, block: &IVec3, voxel: &Voxel| {
+            let name = VOXEL_REGISTRY
+                .get(voxel.id)
+                .map_or(format!("#{}", voxel.id), |def| def.name.to_string());
+            vec![
+                Dynamic::from(username.to_string()),
+                Dynamic::from(block.x as INT),
+                Dynamic::from(block.y as INT),
+                Dynamic::from(block.z as INT),
+                Dynamic::from(name),
+            ]
+        };
+        match self {
+            ScriptEvent::Join { username } => ("on_join", vec![Dynamic::from(username.clone())]),
+            ScriptEvent::Leave { username } => ("on_leave", vec![Dynamic::from(username.clone())]),
+            ScriptEvent::Place {
+                username,
+                block,
+                voxel,
+            } => ("on_place", block_args(username, block, voxel)),
+            ScriptEvent::Break {
+                username,
+                block,
+                voxel,
+            } => ("on_break", block_args(username, block, voxel)),
+            ScriptEvent::Chat { username, text } => (
+                "on_chat",
+                vec![Dynamic::from(username.clone()), Dynamic::from(text.clone())],
+            ),
+        }
+    }
+}
+
+// 脚本请求的操作
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScriptAction {
+    SendMessage {
+        username: String,
+        text: String,
+    },
+    Broadcast {
+        text: String,
+    },
+    Teleport {
+        username: String,
+        position: Vec3,
+    },
+    GiveItem {
+        username: String,
+        staff_id: usize,
+        count: usize,
+    },
+}
+
+#[derive(Resource)]
+pub struct ServerScripts {
+    engine: Engine,
+    // (文件名, 编译后的脚本)
+    scripts: Vec<(String, AST)>,
+    actions: Arc<Mutex<Vec<ScriptAction>>>,
+}
+
+impl ServerScripts {
+    pub fn new() -> Self {
+        let actions = Arc::new(Mutex::new(Vec::new()));
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_SCRIPT_OPERATIONS);
+        engine.set_max_call_levels(32);
+        engine.set_max_string_size(4096);
+        engine.set_max_array_size(1024);
+        engine.disable_symbol("eval");
+
+        let queue = actions.clone();
+        engine.register_fn("send_message", move |username: &str, text: &str| {
+            queue.lock().unwrap().push(ScriptAction::SendMessage {
+                username: username.to_string(),
+                text: text.to_string(),
+            });
+        });
+        let queue = actions.clone();
+        engine.register_fn("broadcast", move |text: &str| {
+            queue.lock().unwrap().push(ScriptAction::Broadcast {
+                text: text.to_string(),
+            });
+        });
+        // 坐标使用浮点数
+        let queue = actions.clone();
+        engine.register_fn(
+            "teleport",
+            move |username: &str, x: FLOAT, y: FLOAT, z: FLOAT| {
+                queue.lock().unwrap().push(ScriptAction::Teleport {
+                    username: username.to_string(),
+                    position: Vec3::new(x as f32, y as f32, z as f32),
+                });
+            },
+        );
+        let queue = actions.clone();
+        engine.register_fn(
+            "give_item",
+            move |username: &str, staff_id: INT, count: INT| {
+                if staff_id < 0 {
+                    return;
+                }
+                queue.lock().unwrap().push(ScriptAction::GiveItem {
+                    username: username.to_string(),
+                    staff_id: staff_id as usize,
+                    count: count.clamp(0, MAX_GIVE_COUNT) as usize,
+                });
+            },
+        );
+
+        Self {
+            engine,
+            scripts: Vec::new(),
+            actions,
+        }
+    }
+
+    // 加载目录中的全部脚本 按照文件名的顺序 目录不存在时没有脚本
+    pub fn load(dir: &str) -> Self {
+        let mut scripts = Self::new();
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return scripts;
+        };
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().map_or(false, |ext| ext == "rhai"))
+            .collect();
+        paths.sort();
+        for path in paths {
+            match std::fs::read_to_string(&path) {
+                Ok(source) => {
+                    scripts.add(path.display().to_string(), &source);
+                }
+                Err(err) => println!("读取脚本{}失败 {}", path.display(), err),
+            }
+        }
+        println!("加载了{}个脚本", scripts.scripts.len());
+        scripts
+    }
+
+    // 编译并执行脚本的顶层语句 失败时返回 false
+    pub fn add(&mut self, name: String, source: &str) -> bool {
+        let ast = match self.engine.compile(source) {
+            Ok(ast) => ast,
+            Err(err) => {
+                println!("脚本{}编译失败 {}", name, err);
+                return false;
+            }
+        };
+        if let Err(err) = self.engine.run_ast(&ast) {
+            println!("脚本{}执行失败 {}", name, err);
+            return false;
+        }
+        self.scripts.push((name, ast));
+        true
+    }
+
+    // 调用每个脚本中定义了的函数 没有定义的脚本跳过
+    pub fn dispatch(&self, event: &ScriptEvent) {
+        let (hook, args) = event.hook();
+        for (name, ast) in self.scripts.iter() {
+            if !ast
+                .iter_functions()
+                .any(|f| f.name == hook && f.params.len() == args.len())
+            {
+                continue;
+            }
+            if let Err(err) = self.engine.call_fn_with_options::<Dynamic>(
+                CallFnOptions::new().eval_ast(false),
+                &mut Scope::new(),
+                ast,
+                hook,
+                args.clone(),
+            ) {
+                println!("脚本{}执行{}失败 {}", name, hook, err);
+            }
+        }
+    }
+
+    pub fn take_actions(&self) -> Vec<ScriptAction> {
+        std::mem::take(&mut *self.actions.lock().unwrap())
+    }
+}
+
+impl Default for ServerScripts {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct ScriptingPlugin;
+
+impl Plugin for ScriptingPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.add_event::<ScriptEvent>();
+        app.insert_resource(ServerScripts::load(SCRIPTS_PATH));
+        app.add_systems(Update, run_script_hooks);
+    }
+}
+
+fn run_script_hooks(
+    mut events: EventReader<ScriptEvent>,
+    scripts: Res<ServerScripts>,
+    mut server: ResMut<RenetServer>,
+    mut pending_teleports: ResMut<PendingTeleports>,
+    mut players: Query<(Entity, &Player, &mut PlayerOnTimeState)>,
+    staff_info_stroge: Res<StaffInfoStroge>,
+) {
+    for event in events.iter() {
+        scripts.dispatch(event);
+    }
+    for action in scripts.take_actions() {
+        match action {
+            ScriptAction::SendMessage { username, text } => {
+                let Some((_, player, _)) = players.iter().find(|(_, p, _)| p.username == username)
+                else {
+                    continue;
+                };
+                let message = bincode::serialize(&ServerMessages::Chat {
+                    username: SCRIPT_CHAT_NAME.to_string(),
+                    text,
+                })
+                .unwrap();
+                server.send_message(player.id, ServerChannel::ServerMessages, message);
+            }
+            ScriptAction::Broadcast { text } => {
+                let message = bincode::serialize(&ServerMessages::Chat {
+                    username: SCRIPT_CHAT_NAME.to_string(),
+                    text,
+                })
+                .unwrap();
+                server.broadcast_message(ServerChannel::ServerMessages, message);
+            }
+            ScriptAction::Teleport { username, position } => {
+                if !position.is_finite() {
+                    continue;
+                }
+                // 和 /tp 一样 目标的区块加载之后再传送 可能在另一个世界中
+                if let Some((entity, _, _)) =
+                    players.iter().find(|(_, p, _)| p.username == username)
+                {
+                    pending_teleports.teleports.push(PendingTeleport {
+                        entity,
+                        position,
+                        client_id: None,
+                        text: String::new(),
+                        waited: 0.0,
+                    });
+                }
+            }
+            ScriptAction::GiveItem {
+                username,
+                staff_id,
+                count,
+            } => {
+                let Some(staff) = staff_info_stroge.get(staff_id) else {
+                    println!("脚本给予的物品{}不存在", staff_id);
+                    continue;
+                };
+                let Some((_, player, mut player_state)) =
+                    players.iter_mut().find(|(_, p, _)| p.username == username)
+                else {
+                    continue;
+                };
+                for _ in 0..count {
+                    // 物品栏满了就停止
+                    let put = match staff.durability {
+                        Some(durability) => player_state.0.put_tool(staff.id, durability),
+                        None => player_state.0.put_staff(staff.id),
+                    };
+                    let Some((index, staff_id, num)) = put else {
+                        break;
+                    };
+                    let message = bincode::serialize(&ToolBarMessage::SyncToolbar {
+                        index,
+                        staff_id,
+                        num,
+                    })
+                    .unwrap();
+                    server.send_message(player.id, ServerChannel::ToolBarMessage, message);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_script_hooks() {
+    let mut scripts = ServerScripts::new();
+    assert!(scripts.add(
+        String::from("welcome.rhai"),
+        r#"
+            fn on_join(name) {
+                send_message(name, "welcome " + name);
+            }
+            fn on_chat(name, text) {
+                if text == "!spawn" {
+                    teleport(name, 0.5, 70.0, 0.5);
+                }
+            }
+            fn on_break(name, x, y, z, voxel) {
+                give_item(name, 3, 2);
+            }
+        "#
+    ));
+    // 语法错误和运行错误都不会影响其他脚本
+    assert!(!scripts.add(String::from("broken.rhai"), "fn on_join(name) {"));
+    assert!(scripts.add(
+        String::from("failing.rhai"),
+        r#"
+            fn on_join(name) { throw "oops"; }
+            fn on_leave(name) { loop {} }
+        "#
+    ));
+
+    scripts.dispatch(&ScriptEvent::Join {
+        username: String::from("alice"),
+    });
+    scripts.dispatch(&ScriptEvent::Leave {
+        username: String::from("alice"),
+    });
+    scripts.dispatch(&ScriptEvent::Chat {
+        username: String::from("alice"),
+        text: String::from("!spawn"),
+    });
+    scripts.dispatch(&ScriptEvent::Break {
+        username: String::from("alice"),
+        block: IVec3::new(1, 2, 3),
+        voxel: Voxel::EMPTY,
+    });
+    // 没有定义的函数直接跳过
+    scripts.dispatch(&ScriptEvent::Place {
+        username: String::from("alice"),
+        block: IVec3::ZERO,
+        voxel: Voxel::EMPTY,
+    });
+    assert_eq!(
+        scripts.take_actions(),
+        vec![
+            ScriptAction::SendMessage {
+                username: String::from("alice"),
+                text: String::from("welcome alice"),
+            },
+            ScriptAction::Teleport {
+                username: String::from("alice"),
+                position: Vec3::new(0.5, 70.0, 0.5),
+            },
+            ScriptAction::GiveItem {
+                username: String::from("alice"),
+                staff_id: 3,
+                count: 2,
+            },
+        ]
+    );
+    assert!(scripts.take_actions().is_empty());
+}
@@ -0,0 +1,875 @@
+// 处理客户端发送的服务端指令
+
+use std::net::IpAddr;
+
+use bevy::prelude::{
+    warn, Commands, Entity, EventWriter, IntoSystemConfigs, Plugin, Query, Res, ResMut, Resource,
+    Time, Transform, Update, Vec3, With,
+};
+use bevy_rapier3d::{
+    prelude::{GravityScale, RapierContext, RapierRigidBodyHandle},
+    rapier::prelude::RigidBodyType,
+};
+use bevy_renet::renet::{transport::NetcodeServerTransport, RenetServer};
+
+use crate::{
+    client::message_def::{server_command::ServerCommandMessage, ClientChannel},
+    common::net_error::decode,
+    sky::weather::WeatherSchedule,
+    tools::vec3_to_chunk_key_any_xyz,
+    voxel_world::{
+        chunk::ChunkKey,
+        chunk_map::ChunkMap,
+        map_database::{MapDataBase, WorldSeed},
+        spawn::{locate_biome, standing_position, world_spawn, SpawnPoint},
+        world::{find_world, is_creative, world_config, world_names, WorldId},
+    },
+    MAX_CHAT_LENGTH,
+};
+
+use super::{
+    autosave::Autosave,
+    ban_list::{is_bannable_ip, BanList},
+    chat_limit::{prune_chat_limiter, ChatLimiter},
+    chunk::collect_generated_chunks,
+    chunk_budget::{ChunkSendBudget, ChunkSendQueue},
+    config::ServerConfig,
+    cross_through_check::CossTroughFixed,
+    death::{DeathCause, PlayerDeathEvent},
+    disconnect::PendingDisconnects,
+    gen_pool::GenPool,
+    message_def::{
+        server_messages::{ServerDisconnectReason, ServerMessages},
+        ServerChannel,
+    },
+    permission::{command_level, is_local, PermissionLevel, Permissions, StoragePermission},
+    physics_config::PhysicsConfig,
+    player::{send_to_world, Flying, MaxPlayers, Player, ServerLobby},
+    scripting::ScriptEvent,
+};
+
+// 等待传送目标的区块加载的最长时间(秒)
+pub const TELEPORT_LOAD_TIMEOUT: f32 = 10.0;
+
+#[derive(Debug)]
+pub struct PendingTeleport {
+    pub entity: Entity,
+    pub position: Vec3,
+    // 发出指令的客户端 传送完成后回复 脚本的传送没有
+    pub client_id: Option<u64>,
+    pub text: String,
+    pub waited: f32,
+}
+
+#[derive(Debug, Default, Resource)]
+pub struct PendingTeleports {
+    pub teleports: Vec<PendingTeleport>,
+}
+
+pub struct ServerCommandPlugin;
+
+impl Plugin for ServerCommandPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.insert_resource(BanList::load());
+        app.init_resource::<PendingTeleports>();
+        app.init_resource::<ChatLimiter>();
+        app.add_systems(
+            Update,
+            (deal_server_command, finish_pending_teleports, revoke_flying)
+                .chain()
+                .after(collect_generated_chunks),
+        );
+        app.add_systems(Update, prune_chat_limiter.after(deal_server_command));
+    }
+}
+
+// 把指令的执行结果发送给客户端
+pub fn reply(server: &mut RenetServer, client_id: u64, ok: bool, text: String) {
+    let message = bincode::serialize(&ServerMessages::CommandReply { ok, text }).unwrap();
+    server.send_message(client_id, ServerChannel::ServerMessages, message);
+}
+
+#[allow(clippy::too_many_arguments)]
+fn deal_server_command(
+    mut server: ResMut<RenetServer>,
+    mut physics_config: ResMut<PhysicsConfig>,
+    server_lobby: Res<ServerLobby>,
+    max_players: Res<MaxPlayers>,
+    mut ban_list: ResMut<BanList>,
+    mut pending_disconnects: ResMut<PendingDisconnects>,
+    mut map_database: ResMut<MapDataBase>,
+    permissions: Permissions,
+    (send_queue, budget, mut deaths, spawn_point, mut script_events, mut pending_teleports): (
+        Res<ChunkSendQueue>,
+        Res<ChunkSendBudget>,
+        EventWriter<PlayerDeathEvent>,
+        Res<SpawnPoint>,
+        EventWriter<ScriptEvent>,
+        ResMut<PendingTeleports>,
+    ),
+    (time, config, mut chat_limiter, transport): (
+        Res<Time>,
+        Res<ServerConfig>,
+        ResMut<ChatLimiter>,
+        Res<NetcodeServerTransport>,
+    ),
+    mut weather_schedule: ResMut<WeatherSchedule>,
+    mut commands: Commands,
+    world_seed: Res<WorldSeed>,
+    players: Query<(&Transform, &RapierRigidBodyHandle), With<Player>>,
+    mut autosave: ResMut<Autosave>,
+) {
+    for client_id in server.clients_id() {
+        while let Some(message) = server.receive_message(client_id, ClientChannel::ServerCommand) {
+            let command: ServerCommandMessage = match decode(&message) {
+                Ok(command) => command,
+                Err(err) => {
+                    warn!("{}|{}", client_id, err);
+                    continue;
+                }
+            };
+            let required = command.required_level();
+            if permissions.level_of(client_id, &map_database) < required {
+                reply(
+                    &mut server,
+                    client_id,
+                    false,
+                    format!("Insufficient permission: requires {:?}", required),
+                );
+                continue;
+            }
+            match command {
+                ServerCommandMessage::SetPhysics {
+                    gravity,
+                    jump_speed,
+                } => {
+                    if let Err(text) = physics_config.set(gravity, jump_speed) {
+                        reply(&mut server, client_id, false, text);
+                        continue;
+                    }
+                    println!("玩家{}修改物理参数: {:?}", client_id, *physics_config);
+                }
+                ServerCommandMessage::ListPlayers => {
+                    let mut names: Vec<String> = server_lobby.names.iter().cloned().collect();
+                    names.sort();
+                    let text = format!(
+                        "Players {}/{}: {}",
+                        server_lobby.players.len(),
+                        max_players.0,
+                        names.join(", ")
+                    );
+                    reply(&mut server, client_id, true, text);
+                }
+                ServerCommandMessage::Kick { username, reason } => {
+                    if !permissions.outranks(client_id, &username, &map_database) {
+                        reply(
+                            &mut server,
+                            client_id,
+                            false,
+                            format!("Cannot kick {}: same or higher permission", username),
+                        );
+                        continue;
+                    }
+                    let Some(target) = permissions.client_id_of(&username) else {
+                        reply(
+                            &mut server,
+                            client_id,
+                            false,
+                            format!("{} is not online", username),
+                        );
+                        continue;
+                    };
+                    println!("玩家{}被踢出: {:?}", username, reason);
+                    pending_disconnects.disconnect(
+                        &mut server,
+                        target,
+                        ServerDisconnectReason::Kicked(reason),
+                    );
+                    reply(&mut server, client_id, true, format!("Kicked {}", username));
+                }
+                ServerCommandMessage::Ban { username, reason } => {
+                    // 按 IP 封禁可能影响很多玩家 需要和解除封禁一样的等级
+                    if let Ok(ip) = username.parse::<IpAddr>() {
+                        let text = if !is_bannable_ip(ip) {
+                            Some(format!("Cannot ban {}: local address", username))
+                        } else if permissions.level_of(client_id, &map_database)
+                            < command_level("unban")
+                        {
+                            Some(format!(
+                                "Insufficient permission: banning an IP requires {:?}",
+                                command_level("unban")
+                            ))
+                        } else {
+                            None
+                        };
+                        if let Some(text) = text {
+                            reply(&mut server, client_id, false, text);
+                            continue;
+                        }
+                    } else if !permissions.outranks(client_id, &username, &map_database) {
+                        reply(
+                            &mut server,
+                            client_id,
+                            false,
+                            format!("Cannot ban {}: same or higher permission", username),
+                        );
+                        continue;
+                    }
+                    println!("玩家{}被封禁: {:?}", username, reason);
+                    let target = permissions.client_id_of(&username);
+                    let ip = target
+                        .and_then(|target| transport.client_addr(target))
+                        .map(|addr| addr.ip());
+                    ban_list.ban(username.clone(), ip, reason.clone());
+                    if let Some(target) = target {
+                        pending_disconnects.disconnect(
+                            &mut server,
+                            target,
+                            ServerDisconnectReason::Banned(reason),
+                        );
+                    }
+                    reply(&mut server, client_id, true, format!("Banned {}", username));
+                }
+                ServerCommandMessage::Unban { username } => {
+                    if ban_list.unban(&username) {
+                        reply(
+                            &mut server,
+                            client_id,
+                            true,
+                            format!("Unbanned {}", username),
+                        );
+                    } else {
+                        reply(
+                            &mut server,
+                            client_id,
+                            false,
+                            format!("{} is not banned", username),
+                        );
+                    }
+                }
+                ServerCommandMessage::BanList => {
+                    let text = if ban_list.entries.is_empty() {
+                        String::from("Ban list is empty")
+                    } else {
+                        ban_list
+                            .entries
+                            .iter()
+                            .map(|entry| {
+                                let name = match entry.ip {
+                                    Some(ip) if ip.to_string() != entry.username => {
+                                        format!("{} [{}]", entry.username, ip)
+                                    }
+                                    _ => entry.username.clone(),
+                                };
+                                match &entry.reason {
+                                    Some(reason) => format!("{} ({})", name, reason),
+                                    None => name,
+                                }
+                            })
+                            .collect::<Vec<String>>()
+                            .join(", ")
+                    };
+                    reply(&mut server, client_id, true, text);
+                }
+                ServerCommandMessage::Chat { text } => {
+                    let Some(username) = permissions.username_of(client_id) else {
+                        continue;
+                    };
+                    let text: String = text.trim().chars().take(MAX_CHAT_LENGTH).collect();
+                    if text.is_empty() {
+                        continue;
+                    }
+                    // 管理员不受频率限制
+                    if let Some(reason) = chat_limiter.check_player(
+                        &username,
+                        permissions.level_of(client_id, &map_database),
+                        time.elapsed_seconds_f64(),
+                        &config.chat_limit,
+                    ) {
+                        reply(&mut server, client_id, false, reason);
+                        continue;
+                    }
+                    println!("[{}] {}", username, text);
+                    let message = bincode::serialize(&ServerMessages::Chat {
+                        username: username.clone(),
+                        text: text.clone(),
+                    })
+                    .unwrap();
+                    // 聊天只发给同一个世界的玩家 私聊不受限制
+                    let world = server_lobby
+                        .players
+                        .get(&client_id)
+                        .and_then(|entity| players.get(*entity).ok())
+                        .and_then(|(transform, _)| WorldId::of_position(transform.translation));
+                    send_to_world(
+                        &mut server,
+                        server_lobby.players.iter().filter_map(|(id, entity)| {
+                            let (transform, _) = players.get(*entity).ok()?;
+                            Some((*id, transform.translation))
+                        }),
+                        world,
+                        ServerChannel::ServerMessages,
+                        message,
+                    );
+                    script_events.send(ScriptEvent::Chat { username, text });
+                }
+                ServerCommandMessage::Whisper { username, text } => {
+                    let Some(from) = permissions.username_of(client_id) else {
+                        continue;
+                    };
+                    let Some(target) = permissions.client_id_of(&username) else {
+                        reply(
+                            &mut server,
+                            client_id,
+                            false,
+                            format!("{} is not online", username),
+                        );
+                        continue;
+                    };
+                    if target == client_id {
+                        reply(
+                            &mut server,
+                            client_id,
+                            false,
+                            String::from("You can not whisper to yourself"),
+                        );
+                        continue;
+                    }
+                    let text: String = text.trim().chars().take(MAX_CHAT_LENGTH).collect();
+                    if text.is_empty() {
+                        continue;
+                    }
+                    if let Some(reason) = chat_limiter.check_player(
+                        &from,
+                        permissions.level_of(client_id, &map_database),
+                        time.elapsed_seconds_f64(),
+                        &config.chat_limit,
+                    ) {
+                        reply(&mut server, client_id, false, reason);
+                        continue;
+                    }
+                    let message = bincode::serialize(&ServerMessages::Whisper {
+                        from,
+                        to: username,
+                        text,
+                    })
+                    .unwrap();
+                    server.send_message(client_id, ServerChannel::ServerMessages, message.clone());
+                    server.send_message(target, ServerChannel::ServerMessages, message);
+                }
+                ServerCommandMessage::Op { username, level } => {
+                    // 启动参数配置的管理员不能被降级
+                    if permissions.is_admin(&username) {
+                        reply(
+                            &mut server,
+                            client_id,
+                            false,
+                            format!("{} is a server admin", username),
+                        );
+                        continue;
+                    }
+                    // 只能修改等级比自己低的玩家 也只能授予比自己低的等级
+                    if !permissions.outranks(client_id, &username, &map_database) {
+                        reply(
+                            &mut server,
+                            client_id,
+                            false,
+                            format!("Cannot op {}: same or higher permission", username),
+                        );
+                        continue;
+                    }
+                    if level >= permissions.level_of(client_id, &map_database) {
+                        reply(
+                            &mut server,
+                            client_id,
+                            false,
+                            format!("Cannot grant {:?}: same or higher than yours", level),
+                        );
+                        continue;
+                    }
+                    let target = permissions.client_id_of(&username);
+                    // 和连接时一样 高于 Player 的等级只给本机连接
+                    if level > PermissionLevel::Player
+                        && target.is_some_and(|target| !is_local(&transport, target))
+                    {
+                        reply(
+                            &mut server,
+                            client_id,
+                            false,
+                            format!("{} is not connected from this machine", username),
+                        );
+                        continue;
+                    }
+                    println!("玩家{}的权限修改为{:?}", username, level);
+                    map_database.save_permission(&username, level);
+                    if let Some(target) = target {
+                        let message =
+                            bincode::serialize(&ServerMessages::Permission { level }).unwrap();
+                        server.send_message(target, ServerChannel::ServerMessages, message);
+                    }
+                    reply(
+                        &mut server,
+                        client_id,
+                        true,
+                        format!("Set {} to {:?}", username, level),
+                    );
+                }
+                ServerCommandMessage::Bandwidth => {
+                    let text = bandwidth_report(&send_queue, &budget, &permissions);
+                    reply(&mut server, client_id, true, text);
+                }
+                ServerCommandMessage::SetWeather { weather, duration } => {
+                    println!("玩家{}设置天气: {:?} {}秒", client_id, weather, duration);
+                    weather_schedule.forced = Some((weather, duration.max(0.0)));
+                    reply(
+                        &mut server,
+                        client_id,
+                        true,
+                        format!("Set weather to {:?} for {}s", weather, duration),
+                    );
+                }
+                ServerCommandMessage::LocateBiome {
+                    biome,
+                    radius,
+                    teleport,
+                } => {
+                    let Some(entity) = server_lobby.players.get(&client_id).copied() else {
+                        continue;
+                    };
+                    let Ok((transform, _)) = players.get(entity) else {
+                        continue;
+                    };
+                    let origin = transform.translation.floor();
+                    let Some((x, z)) = locate_biome(
+                        world_seed.0,
+                        origin.x as i32,
+                        origin.z as i32,
+                        biome,
+                        radius,
+                    ) else {
+                        reply(
+                            &mut server,
+                            client_id,
+                            false,
+                            format!("No {:?} biome within {} blocks", biome, radius),
+                        );
+                        continue;
+                    };
+                    let distance =
+                        ((x as f32 - origin.x).powi(2) + (z as f32 - origin.z).powi(2)).sqrt();
+                    if !teleport {
+                        reply(
+                            &mut server,
+                            client_id,
+                            true,
+                            format!(
+                                "Nearest {:?} biome at x={} z={} ({:.0} blocks away)",
+                                biome, x, z, distance
+                            ),
+                        );
+                        continue;
+                    }
+                    let position = standing_position(world_seed.0, x, z);
+                    println!("玩家{}传送到{:?}群落{}", client_id, biome, position);
+                    // 和 /tp 一样 目标的区块加载之后再传送
+                    pending_teleports.teleports.push(PendingTeleport {
+                        entity,
+                        position,
+                        client_id: Some(client_id),
+                        text: format!(
+                            "Teleported to {:?} biome at x={:.0} y={:.0} z={:.0}",
+                            biome, position.x, position.y, position.z
+                        ),
+                        waited: 0.0,
+                    });
+                }
+                ServerCommandMessage::Save => {
+                    // 保存完成后由 autosave_system 回复
+                    println!("玩家{}请求保存", client_id);
+                    autosave.requested.push(client_id);
+                }
+                ServerCommandMessage::Teleport { position } => {
+                    let Some(entity) = server_lobby.players.get(&client_id).copied() else {
+                        continue;
+                    };
+                    let position = Vec3::from(position);
+                    if !position.is_finite() {
+                        reply(
+                            &mut server,
+                            client_id,
+                            false,
+                            String::from("Invalid teleport position"),
+                        );
+                        continue;
+                    }
+                    println!("玩家{}传送到{}", client_id, position);
+                    pending_teleports.teleports.push(PendingTeleport {
+                        entity,
+                        position,
+                        client_id: Some(client_id),
+                        text: format!(
+                            "Teleported to x={:.1} y={:.1} z={:.1}",
+                            position.x, position.y, position.z
+                        ),
+                        waited: 0.0,
+                    });
+                }
+                ServerCommandMessage::TeleportToPlayer { username } => {
+                    let Some(entity) = server_lobby.players.get(&client_id).copied() else {
+                        continue;
+                    };
+                    let Some(target) = online_player(&permissions, &server_lobby, &username) else {
+                        reply(
+                            &mut server,
+                            client_id,
+                            false,
+                            format!("{} is not online", username),
+                        );
+                        continue;
+                    };
+                    if target == entity {
+                        reply(
+                            &mut server,
+                            client_id,
+                            false,
+                            String::from("You can not teleport to yourself"),
+                        );
+                        continue;
+                    }
+                    if !permissions.outranks(client_id, &username, &map_database) {
+                        reply(
+                            &mut server,
+                            client_id,
+                            false,
+                            format!("Cannot teleport to {}: same or higher permission", username),
+                        );
+                        continue;
+                    }
+                    let Ok((transform, _)) = players.get(target) else {
+                        continue;
+                    };
+                    println!("玩家{}传送到{}", client_id, username);
+                    pending_teleports.teleports.push(PendingTeleport {
+                        entity,
+                        position: transform.translation,
+                        client_id: Some(client_id),
+                        text: format!("Teleported to {}", username),
+                        waited: 0.0,
+                    });
+                }
+                ServerCommandMessage::TeleportHere { username } => {
+                    let Some(entity) = server_lobby.players.get(&client_id).copied() else {
+                        continue;
+                    };
+                    let Some(target) = online_player(&permissions, &server_lobby, &username) else {
+                        reply(
+                            &mut server,
+                            client_id,
+                            false,
+                            format!("{} is not online", username),
+                        );
+                        continue;
+                    };
+                    if target == entity {
+                        reply(
+                            &mut server,
+                            client_id,
+                            false,
+                            String::from("You can not teleport yourself to yourself"),
+                        );
+                        continue;
+                    }
+                    if !permissions.outranks(client_id, &username, &map_database) {
+                        reply(
+                            &mut server,
+                            client_id,
+                            false,
+                            format!("Cannot teleport {}: same or higher permission", username),
+                        );
+                        continue;
+                    }
+                    let Ok((transform, _)) = players.get(entity) else {
+                        continue;
+                    };
+                    println!("玩家{}传送到玩家{}的位置", username, client_id);
+                    pending_teleports.teleports.push(PendingTeleport {
+                        entity: target,
+                        position: transform.translation,
+                        client_id: Some(client_id),
+                        text: format!("Brought {} to you", username),
+                        waited: 0.0,
+                    });
+                }
+                ServerCommandMessage::Kill => {
+                    deaths.send(PlayerDeathEvent {
+                        client_id,
+                        cause: DeathCause::Kill,
+                    });
+                }
+                ServerCommandMessage::World { name } => {
+                    let Some(entity) = server_lobby.players.get(&client_id).copied() else {
+                        continue;
+                    };
+                    let Ok((transform, _)) = players.get(entity) else {
+                        continue;
+                    };
+                    let current = WorldId::of_position(transform.translation)
+                        .and_then(world_config)
+                        .map_or(String::from("none"), |world| world.name);
+                    let Some(name) = name else {
+                        reply(
+                            &mut server,
+                            client_id,
+                            true,
+                            format!("World: {}, worlds: {}", current, world_names().join(", ")),
+                        );
+                        continue;
+                    };
+                    let Some((world, config)) = find_world(&name) else {
+                        reply(
+                            &mut server,
+                            client_id,
+                            false,
+                            format!(
+                                "No world named {}, worlds: {}",
+                                name,
+                                world_names().join(", ")
+                            ),
+                        );
+                        continue;
+                    };
+                    if config.name == current {
+                        reply(
+                            &mut server,
+                            client_id,
+                            false,
+                            format!("Already in {}", current),
+                        );
+                        continue;
+                    }
+                    let position = world_spawn(world, spawn_point.0, world_seed.0);
+                    println!("玩家{}进入世界{}", client_id, config.name);
+                    // 另一个世界的出生点一般还没有加载 加载之后再传送
+                    pending_teleports.teleports.push(PendingTeleport {
+                        entity,
+                        position,
+                        client_id: Some(client_id),
+                        text: format!("Moved to world {}", config.name),
+                        waited: 0.0,
+                    });
+                }
+                ServerCommandMessage::Fly { enabled } => {
+                    let Some(entity) = server_lobby.players.get(&client_id).copied() else {
+                        continue;
+                    };
+                    let Ok((transform, _)) = players.get(entity) else {
+                        continue;
+                    };
+                    let level = permissions.level_of(client_id, &map_database);
+                    if enabled && !can_fly(transform.translation, level) {
+                        reply(
+                            &mut server,
+                            client_id,
+                            false,
+                            String::from("Flying requires a creative world or Admin"),
+                        );
+                        continue;
+                    }
+                    set_flying(&mut commands, &mut server, entity, client_id, enabled);
+                }
+            }
+        }
+    }
+}
+
+// 在创造世界之外飞行需要的等级
+pub const FLY_ANYWHERE_LEVEL: PermissionLevel = PermissionLevel::Admin;
+
+// 创造世界中所有人都可以飞行 其他世界需要 Admin
+pub fn can_fly(position: Vec3, level: PermissionLevel) -> bool {
+    is_creative(position) || level >= FLY_ANYWHERE_LEVEL
+}
+
+/**
+ * 正在飞行的玩家不再满足条件时取消飞行
+ * 传送 走出创造世界的边界或者被降级后都在这里检查
+ */
+fn revoke_flying(
+    mut commands: Commands,
+    mut server: ResMut<RenetServer>,
+    map_database: Res<MapDataBase>,
+    permissions: Permissions,
+    flying: Query<(Entity, &Player, &Transform), With<Flying>>,
+) {
+    for (entity, player, transform) in flying.iter() {
+        let level = permissions.level_of(player.id, &map_database);
+        if !can_fly(transform.translation, level) {
+            set_flying(&mut commands, &mut server, entity, player.id, false);
+        }
+    }
+}
+
+// 飞行时关闭玩家的重力 并且通知客户端切换控制方式
+pub fn set_flying(
+    commands: &mut Commands,
+    server: &mut RenetServer,
+    entity: Entity,
+    client_id: u64,
+    enabled: bool,
+) {
+    if enabled {
+        commands.entity(entity).insert((Flying, GravityScale(0.0)));
+    } else {
+        commands
+            .entity(entity)
+            .insert(GravityScale(1.0))
+            .remove::<Flying>();
+    }
+    let message = bincode::serialize(&ServerMessages::FlyMode { enabled }).unwrap();
+    server.send_message(client_id, ServerChannel::ServerMessages, message);
+}
+
+// 在线玩家的实体
+fn online_player(permissions: &Permissions, lobby: &ServerLobby, username: &str) -> Option<Entity> {
+    let client_id = permissions.client_id_of(username)?;
+    lobby.players.get(&client_id).copied()
+}
+
+// 目标位置和脚下的区块
+fn destination_keys(position: Vec3) -> [ChunkKey; 2] {
+    [position, position - Vec3::Y * 2.0].map(|point| vec3_to_chunk_key_any_xyz(point).0)
+}
+
+// 目标位置和脚下的区块都已经加载 传送过去不会掉出世界
+pub fn destination_loaded(chunk_map: &ChunkMap, position: Vec3) -> bool {
+    destination_keys(position)
+        .iter()
+        .all(|key| chunk_map.get(*key).is_some())
+}
+
+/**
+ * 加载目标位置的区块 保存过的直接读取 没有保存过的交给生成线程池
+ * 队列满了或者正在生成时下一帧再检查
+ */
+fn load_destination(
+    chunk_map: &mut ChunkMap,
+    db: &MapDataBase,
+    gen_pool: &mut GenPool,
+    position: Vec3,
+) {
+    for key in destination_keys(position) {
+        if chunk_map.map_data.contains_key(&key) || gen_pool.pending.contains(&key) {
+            continue;
+        }
+        match db.load_saved(key) {
+            Ok(Some(data)) => chunk_map.write_chunk(key, data),
+            Ok(None) => {
+                gen_pool.try_request(db.seed, key);
+            }
+            Err(e) => println!("wrong, to get Map {:?}", e),
+        }
+    }
+}
+
+/**
+ * 先加载目标位置的区块 加载完成后再传送
+ * 超时后放弃
+ */
+#[allow(clippy::too_many_arguments)]
+fn finish_pending_teleports(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut context: ResMut<RapierContext>,
+    mut server: ResMut<RenetServer>,
+    mut pending_teleports: ResMut<PendingTeleports>,
+    mut chunk_map: ResMut<ChunkMap>,
+    map_database: Res<MapDataBase>,
+    mut gen_pool: ResMut<GenPool>,
+    players: Query<&RapierRigidBodyHandle, With<Player>>,
+) {
+    let delta = time.delta_seconds();
+    let mut waiting = Vec::new();
+    for mut teleport in pending_teleports.teleports.drain(..) {
+        // 玩家已经离开
+        let Ok(body_handle) = players.get(teleport.entity) else {
+            continue;
+        };
+        load_destination(
+            &mut chunk_map,
+            &map_database,
+            &mut gen_pool,
+            teleport.position,
+        );
+        if destination_loaded(&chunk_map, teleport.position) {
+            teleport_player(
+                &mut commands,
+                &mut context,
+                teleport.entity,
+                body_handle,
+                teleport.position,
+            );
+            if let Some(client_id) = teleport.client_id {
+                reply(&mut server, client_id, true, teleport.text);
+            }
+            continue;
+        }
+        teleport.waited += delta;
+        if teleport.waited >= TELEPORT_LOAD_TIMEOUT {
+            println!("传送的目标区块没有加载: {}", teleport.position);
+            if let Some(client_id) = teleport.client_id {
+                reply(
+                    &mut server,
+                    client_id,
+                    false,
+                    String::from("Teleport destination did not load"),
+                );
+            }
+            continue;
+        }
+        waiting.push(teleport);
+    }
+    pending_teleports.teleports = waiting;
+}
+
+// 和穿透修复一样 先切换成运动学刚体 下一帧移动过去
+pub fn teleport_player(
+    commands: &mut Commands,
+    context: &mut RapierContext,
+    entity: Entity,
+    body_handle: &RapierRigidBodyHandle,
+    position: Vec3,
+) {
+    if let Some(body) = context.bodies.get_mut(body_handle.0) {
+        body.set_linvel(Default::default(), true);
+        body.set_body_type(RigidBodyType::KinematicPositionBased, true);
+        commands.entity(entity).insert(CossTroughFixed(position));
+    }
+}
+
+// 每个客户端一行 带宽按照 KB 显示
+fn bandwidth_report(
+    send_queue: &ChunkSendQueue,
+    budget: &ChunkSendBudget,
+    permissions: &Permissions,
+) -> String {
+    let mut lines = vec![format!(
+        "Chunk budget: {:.1} KB/tick",
+        budget.bytes_per_tick as f32 / 1024.0
+    )];
+    let mut usage: Vec<_> = send_queue.usage.iter().collect();
+    usage.sort_by_key(|(client_id, _)| **client_id);
+    for (client_id, usage) in usage {
+        let name = permissions
+            .username_of(*client_id)
+            .unwrap_or_else(|| client_id.to_string());
+        lines.push(format!(
+            "{}: {:.1} KB/s, queued {} ({:.1} KB), total {:.1} MB",
+            name,
+            usage.bytes_per_second as f32 / 1024.0,
+            usage.queued,
+            usage.queued_bytes as f32 / 1024.0,
+            usage.total_bytes as f32 / (1024.0 * 1024.0)
+        ));
+    }
+    lines.join("\n")
+}
@@ -1,8 +1,9 @@
-use bevy::prelude::{Entity, EventWriter, Plugin, Query, Res, ResMut, Transform, Update};
+use bevy::prelude::{warn, Entity, EventWriter, Plugin, Query, Res, ResMut, Transform, Update};
 use bevy_renet::renet::RenetServer;
 
 use crate::{
     client::message_def::{staff_rule_message::StaffRuleMessage, ClientChannel},
+    common::net_error::decode,
     staff::{
         rule::{StaffNumPair, StaffRules},
         StaffInfoStroge,
@@ -33,11 +34,18 @@ pub fn deal_with_staff_rule(
 ) {
     for client_id in server.clients_id() {
         while let Some(message) = server.receive_message(client_id, ClientChannel::StaffRule) {
+            let staff_rule: StaffRuleMessage = match decode(&message) {
+                Ok(staff_rule) => staff_rule,
+                Err(err) => {
+                    warn!("{}|{}", client_id, err);
+                    continue;
+                }
+            };
             let StaffRuleMessage {
                 staff_rule_id,
                 need,
                 times,
-            } = bincode::deserialize(&message).unwrap();
+            } = staff_rule;
             if let Some(rule) = staff_rules.rules.get(&staff_rule_id) {
                 if let Some(entity) = lobby.players.get(&client_id) {
                     if let Ok((_, trf, mut player_state)) = query.get_mut(*entity) {
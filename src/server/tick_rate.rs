@@ -0,0 +1,88 @@
+// 服务端的 tick 频率
+// 启动时配置 连接时同步给客户端 客户端的插值可以按照实际的间隔调整
+// 由 bevy 的运行循环控制频率 见 ServerTickRate::runner 这里只检查超时的 tick
+
+use std::time::{Duration, Instant};
+
+use bevy::{
+    app::ScheduleRunnerPlugin,
+    prelude::{warn, EventReader, Last, Local, Plugin, Res, ResMut, Resource, Update},
+    winit::{UpdateMode, WinitSettings},
+};
+use bevy_renet::renet::{RenetServer, ServerEvent};
+
+use super::message_def::{server_messages::ServerMessages, ServerChannel};
+
+#[derive(Debug, Clone, Copy, Resource)]
+pub struct ServerTickRate {
+    // 每秒的 tick 数
+    pub rate: u32,
+}
+
+impl ServerTickRate {
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.rate.max(1) as f64)
+    }
+
+    // 没有窗口时使用的运行循环 每个 tick 至少间隔 interval
+    pub fn runner(&self) -> ScheduleRunnerPlugin {
+        ScheduleRunnerPlugin::run_loop(self.interval())
+    }
+
+    // 带窗口调试时 没有窗口事件的情况下按照 tick 频率更新
+    pub fn winit_settings(&self) -> WinitSettings {
+        let mode = UpdateMode::Reactive {
+            max_wait: self.interval(),
+        };
+        WinitSettings {
+            focused_mode: mode,
+            unfocused_mode: mode,
+            ..Default::default()
+        }
+    }
+}
+
+pub struct ServerTickRatePlugin {
+    pub rate: u32,
+}
+
+impl Plugin for ServerTickRatePlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        println!("服务器 tick 频率: {}Hz", self.rate);
+        app.insert_resource(ServerTickRate { rate: self.rate });
+        app.add_systems(Update, sync_tick_rate);
+        app.add_systems(Last, warn_slow_ticks);
+    }
+}
+
+fn sync_tick_rate(
+    mut server_events: EventReader<ServerEvent>,
+    tick_rate: Res<ServerTickRate>,
+    mut server: ResMut<RenetServer>,
+) {
+    for event in server_events.iter() {
+        if let ServerEvent::ClientConnected { client_id } = event {
+            let message = bincode::serialize(&ServerMessages::TickRate {
+                rate: tick_rate.rate,
+            })
+            .unwrap();
+            server.send_message(*client_id, ServerChannel::ServerMessages, message);
+        }
+    }
+}
+
+/**
+ * 两个 tick 之间超过了间隔时打印警告
+ * 间隔包括运行循环的等待 超过时说明 tick 本身太慢了
+ */
+fn warn_slow_ticks(tick_rate: Res<ServerTickRate>, mut last_tick: Local<Option<Instant>>) {
+    let interval = tick_rate.interval();
+    if let Some(last) = *last_tick {
+        let elapsed = last.elapsed();
+        // 运行循环的计时有误差 超过一半才警告
+        if elapsed > interval + interval / 2 {
+            warn!("服务器 tick 超时: 用时 {:?} 目标 {:?}", elapsed, interval);
+        }
+    }
+    *last_tick = Some(Instant::now());
+}
@@ -1,6 +1,6 @@
 use bevy_renet::renet::RenetServer;
 
-use crate::voxel_world::player_state::PlayerState;
+use crate::{staff::StaffInfoStroge, voxel_world::player_state::PlayerState};
 
 use super::message_def::{tool_bar_message::ToolBarMessage, ServerChannel};
 
@@ -26,3 +26,43 @@ pub fn send_all_tool_bar(client_id: u64, server: &mut RenetServer, player_state:
         }
     }
 }
+
+/**
+ * 使用一次 index 位置的工具 同步这个位置 耐久用完时通知客户端
+ * 位置上不是工具时不处理
+ */
+pub fn wear_tool(
+    client_id: u64,
+    server: &mut RenetServer,
+    player_state: &mut PlayerState,
+    index: usize,
+    staff_info_stroge: &StaffInfoStroge,
+) {
+    let Some((Some(staff_id), _)) = player_state.toolbar.get(index).copied() else {
+        return;
+    };
+    if !staff_info_stroge
+        .get(staff_id)
+        .map_or(false, |staff| staff.is_tool())
+    {
+        return;
+    }
+    let Some((index, data, num)) = player_state.wear_tool(index, staff_id) else {
+        return;
+    };
+    let message = bincode::serialize(&ToolBarMessage::SyncToolbar {
+        index,
+        staff_id: data,
+        num,
+    })
+    .unwrap();
+    server.send_message(client_id, ServerChannel::ToolBarMessage, message);
+    if data.is_none() {
+        send_tool_broken(client_id, server, index, staff_id);
+    }
+}
+
+pub fn send_tool_broken(client_id: u64, server: &mut RenetServer, index: usize, staff_id: usize) {
+    let message = bincode::serialize(&ToolBarMessage::ToolBroken { index, staff_id }).unwrap();
+    server.send_message(client_id, ServerChannel::ToolBarMessage, message);
+}
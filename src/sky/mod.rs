@@ -1,7 +1,9 @@
 use bevy::{
+    pbr::{CascadeShadowConfigBuilder, DirectionalLightShadowMap, FogFalloff, FogSettings},
     prelude::{
-        Commands, Component, DirectionalLight, DirectionalLightBundle, IntoSystemConfigs, Plugin,
-        Quat, Query, Res, ResMut, Resource, Startup, Transform, Update, Vec3, With,
+        warn, AmbientLight, Color, Commands, Component, DetectChanges, DirectionalLight,
+        DirectionalLightBundle, Entity, EventWriter, IntoSystemConfigs, Local, Plugin, Quat, Query,
+        Res, ResMut, Resource, Startup, Transform, Update, Vec3, With,
     },
     time::{Time, Timer, TimerMode},
 };
@@ -11,11 +13,48 @@ use bevy_atmosphere::{
 };
 use bevy_renet::renet::{RenetClient, RenetServer};
 
-use crate::server::message_def::{time_sync::TimeSync, ServerChannel};
+use crate::{
+    client::{
+        player::controller::{CameraTag, CharacterController},
+        settings::GraphicsSettings,
+    },
+    common::net_error::{decode, NetErrorEvent},
+    server::message_def::{time_sync::TimeSync, ServerChannel},
+    voxel_world::{
+        biomes::{refresh_sampler, BiomeHeightSampler, BiomeKind},
+        map_database::WorldSeed,
+    },
+    VIEW_RADIUS,
+};
+
+use self::weather::{ClientWeatherPlugin, ServerWeatherPlugin, WeatherState};
+
+pub mod weather;
 
 #[derive(Component)]
 pub struct Sun;
 
+// 正午时太阳的光照强度
+pub const SUN_ILLUMINANCE: f32 = 100000.0;
+// 环境光的亮度 开启阴影时降低 让洞穴里足够暗
+pub const AMBIENT_BRIGHTNESS: f32 = 1.06;
+pub const AMBIENT_BRIGHTNESS_WITH_SHADOWS: f32 = 0.4;
+
+/**
+ * 一天中的时间 也就是太阳的角度(弧度)
+ * 由服务端同步 0 是日出 PI/2 是正午
+ */
+#[derive(Debug, Default, Resource)]
+pub struct TimeOfDay(pub f32);
+
+pub fn ambient_brightness(settings: &GraphicsSettings) -> f32 {
+    if settings.shadows_enabled {
+        AMBIENT_BRIGHTNESS_WITH_SHADOWS
+    } else {
+        AMBIENT_BRIGHTNESS
+    }
+}
+
 #[derive(Resource)]
 pub struct CycleTimer(Timer);
 
@@ -39,6 +78,7 @@ impl Plugin for ServerSkyPlugins {
             TimerMode::Repeating,
         )));
         app.add_systems(Update, daylight_cycle);
+        app.add_plugins(ServerWeatherPlugin);
     }
 }
 
@@ -48,7 +88,7 @@ fn setup_environment(mut commands: Commands) {
     commands.spawn((
         DirectionalLightBundle {
             directional_light: DirectionalLight {
-                illuminance: 100000.0,
+                illuminance: SUN_ILLUMINANCE,
                 shadows_enabled: true,
                 ..Default::default()
             },
@@ -63,30 +103,206 @@ pub struct ClientSkyPlugins;
 impl Plugin for ClientSkyPlugins {
     fn build(&self, app: &mut bevy::prelude::App) {
         app.insert_resource(AtmosphereModel::new(Nishita::default()));
-        app.add_plugins(AtmospherePlugin);
+        app.init_resource::<TimeOfDay>();
+        app.add_plugins((AtmospherePlugin, ClientWeatherPlugin));
         app.add_systems(Startup, setup_environment);
         app.add_systems(
             Update,
             async_sky.run_if(bevy_renet::transport::client_connected()),
         );
+        app.add_systems(Update, (apply_shadow_settings, update_sun.after(async_sky)));
+        app.add_systems(Update, (biome_atmosphere, apply_scattering).chain());
+    }
+}
+
+// 群落颜色过渡的速度
+const BIOME_COLOR_SPEED: f32 = 0.5;
+
+fn lerp_color(from: Color, to: Color, t: f32) -> Color {
+    let [r1, g1, b1, a1] = from.as_rgba_f32();
+    let [r2, g2, b2, a2] = to.as_rgba_f32();
+    Color::rgba(
+        r1 + (r2 - r1) * t,
+        g1 + (g2 - g1) * t,
+        b1 + (b2 - b1) * t,
+        a1 + (a2 - a1) * t,
+    )
+}
+
+// 地面上雾的范围 视野边缘的区块逐渐隐藏
+pub fn default_fog_falloff() -> FogFalloff {
+    FogFalloff::Linear {
+        start: VIEW_RADIUS * 0.6,
+        end: VIEW_RADIUS,
+    }
+}
+
+/**
+ * 根据玩家所在的群落 调整雾和天空的色调
+ * 颜色随时间平滑过渡 跨越群落边界时不会突变
+ */
+pub fn biome_atmosphere(
+    mut commands: Commands,
+    time: Res<Time>,
+    world_seed: Option<Res<WorldSeed>>,
+    player_query: Query<&Transform, With<CharacterController>>,
+    mut camera_query: Query<(Entity, Option<&mut FogSettings>), With<CameraTag>>,
+    ambient_light: Option<ResMut<AmbientLight>>,
+    mut sampler: Local<Option<(i32, BiomeHeightSampler)>>,
+) {
+    let (Some(world_seed), Ok(transform)) = (world_seed, player_query.get_single()) else {
+        return;
+    };
+    let sampler = refresh_sampler(&mut sampler, world_seed.0);
+    let biome: BiomeKind = sampler.biome_at(transform.translation.x, transform.translation.z);
+    let palette = biome.palette();
+    let t = (time.delta_seconds() * BIOME_COLOR_SPEED).min(1.0);
+
+    for (entity, fog) in camera_query.iter_mut() {
+        match fog {
+            Some(mut fog) => {
+                fog.color = lerp_color(fog.color, palette.fog, t);
+            }
+            None => {
+                commands.entity(entity).insert(FogSettings {
+                    color: palette.fog,
+                    falloff: default_fog_falloff(),
+                    ..Default::default()
+                });
+            }
+        }
+    }
+    if let Some(mut ambient_light) = ambient_light {
+        ambient_light.color = lerp_color(ambient_light.color, palette.sky_tint, t);
+    }
+}
+
+// 日出和日落时散射光的颜色 日出偏金色 日落偏红
+const DAWN_GLOW: Color = Color::rgb(1.0, 0.75, 0.5);
+const DUSK_GLOW: Color = Color::rgb(1.0, 0.45, 0.25);
+
+/**
+ * 地平线光晕的颜色 alpha 是强度
+ * 太阳接近地平线时最强 正午和夜晚没有 颜色和雾的颜色混合
+ */
+pub fn scattering_color(t: f32, fog: Color, strength: f32) -> Color {
+    let height = t.sin();
+    // 太阳在地平线以下一点时仍然有余晖
+    if height < -0.2 || strength <= 0.0 {
+        return Color::NONE;
+    }
+    let horizon = (1.0 - height.abs()).powi(4);
+    let glow = if t.cos() > 0.0 { DAWN_GLOW } else { DUSK_GLOW };
+    let color = lerp_color(fog, glow, 0.7);
+    color.with_a(horizon * strength)
+}
+
+// 通过雾的方向光散射实现 只影响画面
+fn apply_scattering(
+    time_of_day: Res<TimeOfDay>,
+    settings: Res<GraphicsSettings>,
+    mut camera_query: Query<&mut FogSettings, With<CameraTag>>,
+) {
+    for mut fog in camera_query.iter_mut() {
+        let color = scattering_color(time_of_day.0, fog.color, settings.scattering.strength());
+        fog.directional_light_color = color;
+        fog.directional_light_exponent = settings.scattering.exponent();
     }
 }
 
 fn async_sky(
     mut client: ResMut<RenetClient>,
     mut atmosphere: AtmosphereMut<Nishita>,
-    mut query: Query<(&mut Transform, &mut DirectionalLight), With<Sun>>,
+    mut time_of_day: ResMut<TimeOfDay>,
+    mut weather: ResMut<WeatherState>,
+    mut net_errors: EventWriter<NetErrorEvent>,
 ) {
     while let Some(message) = client.receive_message(ServerChannel::TimsSync) {
-        let time_sync: TimeSync = bincode::deserialize(&message).unwrap();
+        let time_sync: TimeSync = match decode(&message) {
+            Ok(time_sync) => time_sync,
+            Err(err) => {
+                warn!("{}", err);
+                net_errors.send(NetErrorEvent(err));
+                continue;
+            }
+        };
         match time_sync {
             TimeSync::SkyBox(t) => {
                 atmosphere.sun_position = Vec3::new(0., t.sin(), t.cos());
-                if let Some((mut light_trans, mut directional)) = query.single_mut().into() {
-                    light_trans.rotation = Quat::from_rotation_x(-t);
-                    directional.illuminance = t.sin().max(0.0).powf(2.0) * 100000.0;
-                }
+                time_of_day.0 = t;
+            }
+            TimeSync::Weather(state) => {
+                *weather = state;
             }
         }
     }
 }
+
+// 太阳的方向跟随时间 在地平线以下时不投射阴影 下雨下雪时变暗
+fn update_sun(
+    time_of_day: Res<TimeOfDay>,
+    settings: Res<GraphicsSettings>,
+    weather: Res<WeatherState>,
+    mut query: Query<(&mut Transform, &mut DirectionalLight), With<Sun>>,
+) {
+    if !time_of_day.is_changed() && !settings.is_changed() && !weather.is_changed() {
+        return;
+    }
+    let t = time_of_day.0;
+    for (mut light_trans, mut directional) in query.iter_mut() {
+        light_trans.rotation = Quat::from_rotation_x(-t);
+        directional.illuminance =
+            t.sin().max(0.0).powf(2.0) * SUN_ILLUMINANCE * weather.light_factor();
+        directional.shadows_enabled = settings.shadows_enabled && t.sin() > 0.0;
+    }
+}
+
+/**
+ * 阴影的质量 分辨率和级联层数
+ * 同时调整环境光 开启阴影时环境光更暗 下雨下雪时也更暗
+ */
+fn apply_shadow_settings(
+    mut commands: Commands,
+    settings: Res<GraphicsSettings>,
+    weather: Res<WeatherState>,
+    sun_query: Query<Entity, With<Sun>>,
+    ambient_light: Option<ResMut<AmbientLight>>,
+) {
+    if let Some(mut ambient_light) = ambient_light {
+        if settings.is_changed() || weather.is_changed() || ambient_light.is_added() {
+            ambient_light.brightness = ambient_brightness(&settings) * weather.light_factor();
+        }
+    }
+    if !settings.is_changed() {
+        return;
+    }
+    commands.insert_resource(DirectionalLightShadowMap {
+        size: settings.shadow_quality.map_size(),
+    });
+    for entity in sun_query.iter() {
+        commands.entity(entity).insert(
+            CascadeShadowConfigBuilder {
+                num_cascades: settings.shadow_quality.cascades(),
+                maximum_distance: VIEW_RADIUS,
+                ..Default::default()
+            }
+            .build(),
+        );
+    }
+}
+
+#[test]
+fn test_scattering_color() {
+    use std::f32::consts::PI;
+
+    let fog = Color::rgb(0.5, 0.6, 0.7);
+    // 正午和深夜没有光晕
+    assert!(scattering_color(PI / 2.0, fog, 1.0).a() < 1E-6);
+    assert_eq!(scattering_color(-PI / 2.0, fog, 1.0), Color::NONE);
+    // 日出和日落最强 颜色不同
+    let dawn = scattering_color(0.05, fog, 1.0);
+    let dusk = scattering_color(PI - 0.05, fog, 1.0);
+    assert!(dawn.a() > 0.5 && dusk.a() > 0.5);
+    assert_ne!(dawn.g(), dusk.g());
+    assert_eq!(scattering_color(0.05, fog, 0.0), Color::NONE);
+}
@@ -0,0 +1,370 @@
+// 天气
+// 服务端按照种子决定每个时段的天气 天气的强度逐渐变化 定时同步给客户端
+// 客户端根据所在的群落显示雨或者雪 下雨下雪时画面变暗
+
+use bevy::{
+    pbr::NotShadowCaster,
+    prelude::{
+        shape, AlphaMode, Assets, Color, Commands, Component, Entity, GlobalTransform, Handle,
+        Local, Mesh, OnExit, PbrBundle, Plugin, Query, Res, ResMut, Resource, StandardMaterial,
+        Transform, Update, Vec3, Visibility, With, Without,
+    },
+    time::{Time, Timer, TimerMode},
+};
+use bevy_renet::renet::RenetServer;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    client::{player::controller::CameraTag, state_manager::GameState},
+    server::message_def::{time_sync::TimeSync, ServerChannel},
+    voxel_world::{
+        biomes::{refresh_sampler, snow_level, BiomeHeightSampler, BiomeKind},
+        map_database::WorldSeed,
+        structure::hash_with_seed,
+    },
+};
+
+// 每个时段的长度(秒) 每个时段的天气由种子决定
+pub const WEATHER_PERIOD: f32 = 300.0;
+// 天气从无到最强需要的时间(秒)
+pub const WEATHER_TRANSITION: f32 = 20.0;
+// 最强的暴风雨时 光照降低的比例
+pub const STORM_DARKEN: f32 = 0.5;
+// 天气的随机数 salt
+const WEATHER_SALT: i32 = 0x7765_6174;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+pub enum Weather {
+    #[default]
+    Clear,
+    Rain,
+    Snow,
+}
+
+// 客户端实际显示的降水
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Precipitation {
+    Rain,
+    Snow,
+}
+
+/**
+ * 当前的天气和强度 [0, 1]
+ * 服务端和客户端都有一份 客户端的由服务端同步
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, Resource)]
+pub struct WeatherState {
+    pub weather: Weather,
+    pub intensity: f32,
+}
+
+impl WeatherState {
+    /**
+     * 向目标天气变化一步
+     * 天气不同时先减弱到 0 再切换 晴天的强度保持 0
+     */
+    pub fn step(&mut self, target: Weather, delta: f32) {
+        let change = delta / WEATHER_TRANSITION;
+        if self.weather != target {
+            self.intensity -= change;
+            if self.intensity <= 0.0 {
+                self.weather = target;
+                self.intensity = 0.0;
+            }
+        } else if target == Weather::Clear {
+            self.intensity = 0.0;
+        } else {
+            self.intensity = (self.intensity + change).min(1.0);
+        }
+    }
+
+    // 光照的倍数 下雨下雪时变暗
+    pub fn light_factor(&self) -> f32 {
+        match self.weather {
+            Weather::Clear => 1.0,
+            Weather::Rain | Weather::Snow => 1.0 - STORM_DARKEN * self.intensity,
+        }
+    }
+
+    /**
+     * 所在位置显示的降水
+     * 干燥的群落不下雨 寒冷的群落和雪线之上下雪
+     */
+    pub fn precipitation(&self, biome: BiomeKind, height: f32) -> Option<Precipitation> {
+        if self.weather == Weather::Clear || self.intensity <= 0.0 {
+            return None;
+        }
+        match biome {
+            BiomeKind::Dry | BiomeKind::Sand => None,
+            BiomeKind::Snow => Some(Precipitation::Snow),
+            _ if height >= snow_level() => Some(Precipitation::Snow),
+            _ => match self.weather {
+                Weather::Snow => Some(Precipitation::Snow),
+                _ => Some(Precipitation::Rain),
+            },
+        }
+    }
+}
+
+// 每个时段的天气 只和种子有关
+pub fn scheduled_weather(seed: i32, period: u32) -> Weather {
+    match hash_with_seed(&[period as i32, WEATHER_SALT], seed) % 100 {
+        0..=59 => Weather::Clear,
+        60..=84 => Weather::Rain,
+        _ => Weather::Snow,
+    }
+}
+
+/**
+ * 服务端的天气进度
+ * forced 是指令设置的天气和剩余时间 到时间后回到按照种子的天气
+ */
+#[derive(Debug, Resource)]
+pub struct WeatherSchedule {
+    pub elapsed: f32,
+    pub forced: Option<(Weather, f32)>,
+    sync_timer: Timer,
+}
+
+impl Default for WeatherSchedule {
+    fn default() -> Self {
+        Self {
+            elapsed: 0.0,
+            forced: None,
+            sync_timer: Timer::from_seconds(1.0, TimerMode::Repeating),
+        }
+    }
+}
+
+impl WeatherSchedule {
+    pub fn target(&self, seed: i32) -> Weather {
+        match self.forced {
+            Some((weather, _)) => weather,
+            None => scheduled_weather(seed, (self.elapsed / WEATHER_PERIOD) as u32),
+        }
+    }
+}
+
+pub struct ServerWeatherPlugin;
+
+impl Plugin for ServerWeatherPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.init_resource::<WeatherState>();
+        app.init_resource::<WeatherSchedule>();
+        app.add_systems(Update, update_weather);
+    }
+}
+
+fn update_weather(
+    time: Res<Time>,
+    world_seed: Option<Res<WorldSeed>>,
+    mut schedule: ResMut<WeatherSchedule>,
+    mut state: ResMut<WeatherState>,
+    mut server: ResMut<RenetServer>,
+) {
+    let Some(world_seed) = world_seed else {
+        return;
+    };
+    let delta = time.delta_seconds();
+    schedule.elapsed += delta;
+    if let Some((_, remaining)) = schedule.forced.as_mut() {
+        *remaining -= delta;
+        if *remaining <= 0.0 {
+            schedule.forced = None;
+        }
+    }
+    let target = schedule.target(world_seed.0);
+    state.step(target, delta);
+    if schedule.sync_timer.tick(time.delta()).just_finished() {
+        let message = bincode::serialize(&TimeSync::Weather(*state)).unwrap();
+        server.broadcast_message(ServerChannel::TimsSync, message);
+    }
+}
+
+// 相机周围降水的范围和数量
+const PARTICLE_RADIUS: f32 = 16.0;
+const PARTICLE_HEIGHT: f32 = 24.0;
+const MAX_PARTICLES: usize = 800;
+// 每帧最多新增的粒子
+const PARTICLES_PER_FRAME: usize = 50;
+
+#[derive(Debug, Component)]
+pub struct WeatherParticle(Precipitation);
+
+struct WeatherAssets {
+    rain_mesh: Handle<Mesh>,
+    rain_material: Handle<StandardMaterial>,
+    snow_mesh: Handle<Mesh>,
+    snow_material: Handle<StandardMaterial>,
+}
+
+pub struct ClientWeatherPlugin;
+
+impl Plugin for ClientWeatherPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.init_resource::<WeatherState>();
+        app.add_systems(Update, update_weather_particles);
+        app.add_systems(OnExit(GameState::Game), setdown_weather);
+    }
+}
+
+fn setdown_weather(
+    mut commands: Commands,
+    mut state: ResMut<WeatherState>,
+    particles: Query<Entity, With<WeatherParticle>>,
+) {
+    for entity in particles.iter() {
+        commands.entity(entity).despawn();
+    }
+    *state = WeatherState::default();
+}
+
+fn particle_velocity(precipitation: Precipitation, rng: &mut impl Rng) -> Vec3 {
+    match precipitation {
+        Precipitation::Rain => Vec3::new(0.0, -rng.gen_range(12.0..16.0), 0.0),
+        Precipitation::Snow => Vec3::new(
+            rng.gen_range(-0.5..0.5),
+            -rng.gen_range(1.0..2.0),
+            rng.gen_range(-0.5..0.5),
+        ),
+    }
+}
+
+// 相机周围随机的位置 top 为真时在范围的顶部
+fn particle_position(center: Vec3, top: bool, rng: &mut impl Rng) -> Vec3 {
+    let y = if top {
+        PARTICLE_HEIGHT / 2.0
+    } else {
+        rng.gen_range(-PARTICLE_HEIGHT / 2.0..PARTICLE_HEIGHT / 2.0)
+    };
+    center
+        + Vec3::new(
+            rng.gen_range(-PARTICLE_RADIUS..PARTICLE_RADIUS),
+            y,
+            rng.gen_range(-PARTICLE_RADIUS..PARTICLE_RADIUS),
+        )
+}
+
+/**
+ * 相机周围的雨滴和雪花
+ * 数量跟随天气的强度 落到范围外后回到顶部重新下落
+ */
+#[allow(clippy::too_many_arguments)]
+fn update_weather_particles(
+    mut commands: Commands,
+    time: Res<Time>,
+    state: Res<WeatherState>,
+    world_seed: Option<Res<WorldSeed>>,
+    camera: Query<&GlobalTransform, With<CameraTag>>,
+    mut particles: Query<(Entity, &mut Transform, &WeatherParticle), Without<CameraTag>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut weather_assets: Local<Option<WeatherAssets>>,
+    mut sampler: Local<Option<(i32, BiomeHeightSampler)>>,
+) {
+    let (Some(world_seed), Ok(camera)) = (world_seed, camera.get_single()) else {
+        return;
+    };
+    let sampler = refresh_sampler(&mut sampler, world_seed.0);
+    let center = camera.translation();
+    let precipitation = state.precipitation(sampler.biome_at(center.x, center.z), center.y);
+    let target = match precipitation {
+        Some(_) => (MAX_PARTICLES as f32 * state.intensity) as usize,
+        None => 0,
+    };
+
+    // 数量多了或者降水变了 删除多余的粒子
+    let mut count = 0;
+    for (entity, _, particle) in particles.iter() {
+        if Some(particle.0) != precipitation || count >= target {
+            commands.entity(entity).despawn();
+        } else {
+            count += 1;
+        }
+    }
+
+    let mut rng = rand::thread_rng();
+    let delta = time.delta_seconds();
+    for (_, mut transform, particle) in particles.iter_mut() {
+        transform.translation += particle_velocity(particle.0, &mut rng) * delta;
+        let offset = transform.translation - center;
+        if offset.y < -PARTICLE_HEIGHT / 2.0
+            || offset.x.abs() > PARTICLE_RADIUS
+            || offset.z.abs() > PARTICLE_RADIUS
+        {
+            transform.translation = particle_position(center, true, &mut rng);
+        }
+    }
+
+    let Some(precipitation) = precipitation else {
+        return;
+    };
+    let assets = weather_assets.get_or_insert_with(|| WeatherAssets {
+        rain_mesh: meshes.add(shape::Box::new(0.02, 0.4, 0.02).into()),
+        rain_material: materials.add(StandardMaterial {
+            base_color: Color::rgba(0.6, 0.7, 0.9, 0.6),
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
+            ..Default::default()
+        }),
+        snow_mesh: meshes.add(shape::Cube::new(0.08).into()),
+        snow_material: materials.add(StandardMaterial {
+            base_color: Color::WHITE,
+            unlit: true,
+            ..Default::default()
+        }),
+    });
+    let (mesh, material) = match precipitation {
+        Precipitation::Rain => (&assets.rain_mesh, &assets.rain_material),
+        Precipitation::Snow => (&assets.snow_mesh, &assets.snow_material),
+    };
+    for _ in count..target.min(count + PARTICLES_PER_FRAME) {
+        commands.spawn((
+            PbrBundle {
+                mesh: mesh.clone(),
+                material: material.clone(),
+                transform: Transform::from_translation(particle_position(center, false, &mut rng)),
+                visibility: Visibility::Visible,
+                ..Default::default()
+            },
+            NotShadowCaster,
+            WeatherParticle(precipitation),
+        ));
+    }
+}
+
+#[test]
+fn test_weather_step() {
+    let mut state = WeatherState::default();
+    // 晴天切换到下雨 强度逐渐增加
+    state.step(Weather::Rain, 1.0);
+    assert_eq!(state.weather, Weather::Rain);
+    assert_eq!(state.intensity, 0.0);
+    state.step(Weather::Rain, WEATHER_TRANSITION / 2.0);
+    assert_eq!(state.intensity, 0.5);
+    state.step(Weather::Rain, WEATHER_TRANSITION);
+    assert_eq!(state.intensity, 1.0);
+    assert_eq!(state.light_factor(), 1.0 - STORM_DARKEN);
+    // 切换到下雪时先减弱
+    state.step(Weather::Snow, WEATHER_TRANSITION / 2.0);
+    assert_eq!((state.weather, state.intensity), (Weather::Rain, 0.5));
+    state.step(Weather::Snow, WEATHER_TRANSITION);
+    assert_eq!((state.weather, state.intensity), (Weather::Snow, 0.0));
+
+    let rain = WeatherState {
+        weather: Weather::Rain,
+        intensity: 1.0,
+    };
+    assert_eq!(
+        rain.precipitation(BiomeKind::Basic, 0.0),
+        Some(Precipitation::Rain)
+    );
+    assert_eq!(
+        rain.precipitation(BiomeKind::Snow, 0.0),
+        Some(Precipitation::Snow)
+    );
+    assert_eq!(rain.precipitation(BiomeKind::Sand, 0.0), None);
+    // 相同的种子得到相同的天气
+    assert_eq!(scheduled_weather(1, 7), scheduled_weather(1, 7));
+}
@@ -8,7 +8,10 @@ use bevy::{
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 
-use crate::voxel_world::voxel::Voxel;
+use crate::voxel_world::{
+    voxel::Voxel,
+    voxel_registry::{Tool, VOXEL_REGISTRY},
+};
 
 use self::rule::StaffRulePlugin;
 
@@ -24,6 +27,22 @@ pub struct Staff {
     pub icon: Handle<Image>,
     // 物品类型
     pub staff_type: StaffType,
+    // 工具的耐久 每次使用减一 到零时损坏 其他物品没有耐久
+    pub durability: Option<usize>,
+    // 破坏方块时的工具种类和等级 None 和空手一样
+    pub tool: Option<Tool>,
+}
+
+impl Staff {
+    // 有耐久的工具不能堆叠 toolbar 中的数量表示剩余的耐久
+    pub fn is_tool(&self) -> bool {
+        self.durability.is_some()
+    }
+
+    // 拿着这个物品破坏方块时使用的工具
+    pub fn break_tool(&self) -> Tool {
+        self.tool.unwrap_or(Tool::HAND)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -71,11 +90,23 @@ impl StaffInfoStroge {
         self.voxel_staff.get(&voxel.id)
     }
 
-    // 通过体素获取掉落物
-    pub fn voxel_to_staff_list(&self, voxel: Voxel) -> Option<Vec<Staff>> {
+    /**
+     * 通过体素获取掉落物
+     * 体素的掉落在 VoxelRegistry 中声明 staff.ron 中的 filled_configs 是额外掉落的物品
+     * 工具不满足体素的要求时什么都不掉落
+     */
+    pub fn voxel_to_staff_list(&self, voxel: Voxel, tool: Tool) -> Option<Vec<Staff>> {
+        if !VOXEL_REGISTRY.can_harvest(voxel.id, tool) {
+            return None;
+        }
         let mut ret: Vec<Staff> = Vec::new();
-        let voxle_id = voxel.id;
-        if let Some(mate) = self.filled_map.get(&(voxle_id as usize)) {
+        let mut rng = rand::thread_rng();
+        for (drop, count) in VOXEL_REGISTRY.roll_drops(voxel.id, &mut rng) {
+            if let Some(staff) = self.voxel_to_staff(drop) {
+                ret.extend(std::iter::repeat(staff.clone()).take(count));
+            }
+        }
+        if let Some(mate) = self.filled_map.get(&(voxel.id as usize)) {
             for FilledPair {
                 possible,
                 staff_id,
@@ -84,17 +115,12 @@ impl StaffInfoStroge {
             {
                 if let Some(staff) = self.get(*staff_id) {
                     for _ in 0..*times {
-                        let mut rng = rand::thread_rng();
                         if rng.gen_bool(*possible as f64) {
                             ret.push(staff.clone());
                         }
                     }
                 }
             }
-        } else {
-            if let Some(staff) = self.voxel_staff.get(&voxel.id) {
-                ret.push(staff.clone());
-            }
         }
         if ret.len() > 0 {
             return Some(ret);
@@ -155,6 +181,10 @@ pub struct StaffMeta {
     name: String,
     icon_string: String,
     staff_type: StaffType,
+    #[serde(default)]
+    durability: Option<usize>,
+    #[serde(default)]
+    tool: Option<Tool>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -194,6 +224,8 @@ fn load_staff_configs(
                         name: mate.name,
                         icon: asset_server.load(mate.icon_string),
                         staff_type: mate.staff_type,
+                        durability: mate.durability,
+                        tool: mate.tool,
                     });
                 } else {
                     staff_info_stroge.register(Staff {
@@ -201,6 +233,8 @@ fn load_staff_configs(
                         name: mate.name,
                         icon: Handle::default(),
                         staff_type: mate.staff_type,
+                        durability: mate.durability,
+                        tool: mate.tool,
                     });
                 }
             }
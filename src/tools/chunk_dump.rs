@@ -0,0 +1,72 @@
+// 区块数据导出
+// 出问题的区块可以导出成文件附在问题报告里 测试中再用 load_chunk_dump 重新加载
+
+use std::path::Path;
+
+use ndshape::ConstShape;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    voxel_world::{chunk::ChunkKey, voxel::Voxel},
+    ChunkShape,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkDump {
+    pub chunk_key: ChunkKey,
+    // 客户端还没有同步种子时为 None
+    pub seed: Option<i32>,
+    pub voxels: Vec<Voxel>,
+}
+
+impl ChunkDump {
+    // 默认的文件名
+    pub fn file_name(&self) -> String {
+        let key = self.chunk_key.0;
+        format!("chunk_{}_{}_{}.bin", key.x, key.y, key.z)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        let data = bincode::serialize(self).map_err(|err| err.to_string())?;
+        std::fs::write(path, data).map_err(|err| err.to_string())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let data = std::fs::read(path).map_err(|err| err.to_string())?;
+        let dump: ChunkDump = bincode::deserialize(&data).map_err(|err| err.to_string())?;
+        if dump.voxels.len() != ChunkShape::SIZE as usize {
+            return Err(format!("体素数量不正确: {}", dump.voxels.len()));
+        }
+        Ok(dump)
+    }
+}
+
+/**
+ * 测试中使用 重新得到导出时的区块数据
+ */
+pub fn load_chunk_dump(path: impl AsRef<Path>) -> (ChunkKey, Vec<Voxel>) {
+    let dump = ChunkDump::load(path).expect("读取区块文件失败");
+    (dump.chunk_key, dump.voxels)
+}
+
+#[test]
+fn test_chunk_dump_round_trip() {
+    use crate::voxel_world::voxel::{Stone, VoxelMaterial};
+    use bevy::prelude::IVec3;
+
+    let mut voxels = vec![Voxel::EMPTY; ChunkShape::SIZE as usize];
+    voxels[7] = Stone::into_voxel();
+    let dump = ChunkDump {
+        chunk_key: ChunkKey(IVec3::new(1, -2, 3)),
+        seed: Some(42),
+        voxels,
+    };
+    let path = std::env::temp_dir().join(dump.file_name());
+    dump.save(&path).unwrap();
+    assert_eq!(ChunkDump::load(&path).unwrap(), dump);
+    assert_eq!(
+        load_chunk_dump(&path),
+        (dump.chunk_key, dump.voxels.clone())
+    );
+    std::fs::remove_file(path).unwrap();
+}
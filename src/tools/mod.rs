@@ -9,6 +9,7 @@ use crate::{
     CHUNK_SIZE, CHUNK_SIZE_U32,
 };
 
+pub mod chunk_dump;
 pub mod inspector_egui;
 pub mod string;
 pub mod zone;
@@ -1,5 +1,8 @@
 use bevy_renet::renet::transport::NETCODE_USER_DATA_BYTES;
 
+// 用户数据的最后 4 个字节保存协议版本 用户名最多使用到倒数第 8 个字节
+const PROTOCOL_VERSION_OFFSET: usize = NETCODE_USER_DATA_BYTES - 4;
+
 pub struct Username(pub String);
 
 impl Username {
@@ -22,3 +25,24 @@ impl Username {
         Self(username)
     }
 }
+
+pub fn write_protocol_version(user_data: &mut [u8; NETCODE_USER_DATA_BYTES], version: u32) {
+    user_data[PROTOCOL_VERSION_OFFSET..].copy_from_slice(&version.to_le_bytes());
+}
+
+// 旧版本的客户端没有写入版本 读取到的是 0
+pub fn protocol_version_from_user_data(user_data: &[u8; NETCODE_USER_DATA_BYTES]) -> u32 {
+    let mut bytes = [0u8; 4];
+    bytes.copy_from_slice(&user_data[PROTOCOL_VERSION_OFFSET..]);
+    u32::from_le_bytes(bytes)
+}
+
+#[test]
+fn test_protocol_version_user_data() {
+    let username = Username(String::from("steve"));
+    let mut user_data = username.to_netcode_user_data();
+    assert_eq!(protocol_version_from_user_data(&user_data), 0);
+    write_protocol_version(&mut user_data, 42);
+    assert_eq!(protocol_version_from_user_data(&user_data), 42);
+    assert_eq!(Username::from_user_data(&user_data).0, "steve");
+}
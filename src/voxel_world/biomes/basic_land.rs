@@ -7,15 +7,16 @@ use rand::Rng;
 use crate::{
     tools::chunk_key_any_xyz_to_vec3,
     voxel_world::{
-        chunk::ChunkKey,
+        chunk::{chunk_rng, ChunkKey},
+        gen_config::GenConfig,
         voxel::{AppleLeaf, AppleWood, Grass, Soli, Sown, Stone, Voxel, VoxelMaterial},
     },
 };
 
-use super::{
-    find_out_chunk_keys, BiomesGenerator, SampleShape, TreeGentor, MOUNTAIN_LEVEL, SEE_LEVEL,
-    SNOW_LEVEL,
-};
+use super::{find_out_chunk_keys, BiomesGenerator, SampleShape, TreeGentor};
+
+// 树的随机数 salt
+const TREE_SALT: u64 = 0x7472_6565;
 
 // 基础大陆
 // 1. 雪顶
@@ -27,6 +28,7 @@ pub struct BasicLandBiomes;
 impl BiomesGenerator for BasicLandBiomes {
     fn gen_land_with_info(
         &self,
+        config: &GenConfig,
         _chunk_key: crate::voxel_world::chunk::ChunkKey,
         voxels: &mut Vec<Voxel>,
         chunk_index: u32,
@@ -35,7 +37,7 @@ impl BiomesGenerator for BasicLandBiomes {
         xyz: [u32; 3],
     ) {
         let [x, y, z] = xyz;
-        if height >= SNOW_LEVEL {
+        if height >= config.snow_level {
             // 雪线之上
             voxels[chunk_index as usize] = Sown::into_voxel();
             if y > 0 {
@@ -43,10 +45,10 @@ impl BiomesGenerator for BasicLandBiomes {
                 let under_sown = SampleShape::linearize([x, y - 1, z]);
                 voxels[under_sown as usize] = Sown::into_voxel();
             }
-        } else if height >= MOUNTAIN_LEVEL {
+        } else if height >= config.mountain_level {
             voxels[chunk_index as usize] = Stone::into_voxel();
             // 一层实体
-        } else if height >= SEE_LEVEL {
+        } else if height >= config.sea_level {
             // 一层 草 5层的土
             voxels[chunk_index as usize] = Grass::into_voxel();
             for y_offset in 1..=5 {
@@ -60,17 +62,19 @@ impl BiomesGenerator for BasicLandBiomes {
 
     fn make_tree_with_info(
         &self,
+        config: &GenConfig,
         chunk_key: crate::voxel_world::chunk::ChunkKey,
         voxels: &mut Vec<Voxel>,
-        _chunk_index: u32,
+        chunk_index: u32,
         _plane_index: u32,
         height: f32,
         xyz: [u32; 3],
     ) -> Option<(Vec<ChunkKey>, TreeGentor)> {
-        if height >= MOUNTAIN_LEVEL {
+        if height >= config.mountain_level {
             return None;
         }
-        let mut rng = rand::thread_rng();
+        // 种树的位置已经由种子决定 树的形状只和位置有关 同一个区块重新生成时保持一致
+        let mut rng = chunk_rng(chunk_key, chunk_index as i32, TREE_SALT);
         let root_pos = chunk_key_any_xyz_to_vec3(chunk_key, xyz);
 
         // 判断 是否需要给其他的模块处理？
@@ -2,15 +2,24 @@
 
 use ndshape::ConstShape;
 
-use crate::voxel_world::voxel::{DryGrass, Soli, Sown, Stone, VoxelMaterial};
+use crate::voxel_world::{
+    gen_config::GenConfig,
+    voxel::{DryGrass, Soli, Sown, Stone, VoxelMaterial},
+};
 
-use super::{BiomesGenerator, SampleShape, MOUNTAIN_LEVEL, SEE_LEVEL, SNOW_LEVEL};
+use super::{BiomesGenerator, SampleShape};
 
 pub struct DryLandBiomes;
 
 impl BiomesGenerator for DryLandBiomes {
+    // 起伏的丘陵
+    fn height_curve(&self, noise: f32, _config: &GenConfig) -> f32 {
+        noise * 5.0
+    }
+
     fn gen_land_with_info(
         &self,
+        config: &GenConfig,
         _chunk_key: crate::voxel_world::chunk::ChunkKey,
         voxels: &mut Vec<crate::voxel_world::voxel::Voxel>,
         chunk_index: u32,
@@ -19,7 +28,7 @@ impl BiomesGenerator for DryLandBiomes {
         xyz: [u32; 3],
     ) {
         let [x, y, z] = xyz;
-        if height >= SNOW_LEVEL {
+        if height >= config.snow_level {
             // 雪线之上
             voxels[chunk_index as usize] = Sown::into_voxel();
             if y > 0 {
@@ -27,10 +36,10 @@ impl BiomesGenerator for DryLandBiomes {
                 let under_sown = SampleShape::linearize([x, y - 1, z]);
                 voxels[under_sown as usize] = Sown::into_voxel();
             }
-        } else if height >= MOUNTAIN_LEVEL + 2.0 {
+        } else if height >= config.mountain_level + 2.0 {
             voxels[chunk_index as usize] = Stone::into_voxel();
             // 一层实体
-        } else if height >= SEE_LEVEL {
+        } else if height >= config.sea_level {
             // 一层 草 5层的土
             voxels[chunk_index as usize] = DryGrass::into_voxel();
             for y_offset in 1..=5 {
@@ -1,19 +1,26 @@
+use std::{cell::RefCell, collections::VecDeque, rc::Rc, sync::Mutex};
+
 use bevy::{
-    prelude::{IVec3, Plugin, ResMut, Resource, Update, Vec3},
+    prelude::{warn, Color, IVec3, Plugin, ResMut, Resource, Update, Vec3},
     tasks::AsyncComputeTaskPool,
     utils::{HashMap, HashSet},
 };
-use ndshape::{ConstShape, ConstShape2u32, ConstShape3u32};
+use lazy_static::lazy_static;
+use ndshape::ConstShape;
 use noise::{
     core::worley::{distance_functions::euclidean, ReturnType},
     utils::NoiseMapBuilder,
-    Worley,
+    Fbm, MultiFractal, NoiseFn, Perlin, Worley,
 };
 
 use crate::{
-    server::{async_chunk::ChunkResultTasks, message_def::chunk_result::ChunkResult},
+    server::{
+        async_chunk::ChunkResultTasks,
+        message_def::chunk_result::ChunkResult,
+        mob::{MobKind, MobSpawnEntry},
+    },
     tools::chunk_key_any_xyz_to_vec3,
-    CHUNK_SIZE, CHUNK_SIZE_U32,
+    ChunkPanelShape, ChunkShape, CHUNK_SIZE, CHUNK_SIZE_U32,
 };
 
 use self::{
@@ -26,8 +33,12 @@ use self::{
 };
 
 use super::{
-    chunk::ChunkKey, chunk_map::ChunkMap, compress::compress, map_database::DbSaveTasks,
-    voxel::Voxel,
+    chunk::ChunkKey,
+    chunk_map::ChunkMap,
+    compress::compress,
+    gen_config::{gen_config, GenConfig},
+    map_database::DbSaveTasks,
+    voxel::{DryGrass, Grass, Sand, Voxel, VoxelMaterial},
 };
 
 pub mod basic_land;
@@ -37,35 +48,56 @@ pub mod sand_land;
 pub mod sdf;
 pub mod snow_land;
 
-pub type SampleShape = ConstShape3u32<CHUNK_SIZE_U32, CHUNK_SIZE_U32, CHUNK_SIZE_U32>;
-pub type PanelShape = ConstShape2u32<CHUNK_SIZE_U32, CHUNK_SIZE_U32>;
+// 区块的形状 和 CHUNK_SIZE 的关系见 lib.rs
+pub type SampleShape = ChunkShape;
+pub type PanelShape = ChunkPanelShape;
+
+/**
+ * 体素下标所在的列(平面下标)
+ */
+pub fn column_index(chunk_index: u32) -> u32 {
+    let [x, _, z] = SampleShape::delinearize(chunk_index);
+    PanelShape::linearize([x, z])
+}
 
-// 处理 生物群落
+/**
+ * 处理 生物群落
+ * config 是生成这个区块时读取的生成配置 整个区块使用同一份 重新加载配置时不会混用新旧的值
+ */
 pub fn biomes_generate(
     chunk_key: ChunkKey,
     seed: i32,
     surface_index: Vec<u32>,
     voxels: &mut Vec<Voxel>,
+    config: &GenConfig,
 ) -> Vec<(Vec<ChunkKey>, TreeGentor)> {
     let mut ret = Vec::new();
     if surface_index.len() == 0 {
         return ret;
     }
     // 生成噪声
-    let noise = biomes_noise(chunk_key, seed);
+    let noise = biomes_noise(chunk_key, seed, config.biome_frequency());
     // 这里产生一个 种树的噪声
     let tree_noise = tree_noise(chunk_key, seed);
+    debug_assert_eq!(noise.len(), PanelShape::SIZE as usize);
+    debug_assert_eq!(tree_noise.len(), PanelShape::SIZE as usize);
 
     for index in surface_index {
+        // 上游传入了错误的下标时 跳过这一列 不让生成线程崩溃
+        if index >= SampleShape::SIZE || index as usize >= voxels.len() {
+            warn!("区块{:?}的表面下标{}超出范围 跳过", chunk_key, index);
+            continue;
+        }
         // 由噪声生产的特征值
-        let [x, _, z] = SampleShape::delinearize(index);
-        let index_2d = PanelShape::linearize([x, z]);
+        let index_2d = column_index(index);
         let attr = noise[index_2d as usize];
-        let generator = get_generator_by_attr(attr);
-        generator.gen_land(chunk_key.clone(), voxels, index, index_2d);
+        let generator = get_generator_by_attr(attr, config);
+        generator.gen_land(config, chunk_key.clone(), voxels, index, index_2d);
         // fixme: 这里要记录对于其他方块的影响
-        if tree_noise[index_2d as usize] > 0.99 {
-            if let Some(rs) = generator.make_tree(chunk_key.clone(), voxels, index, index_2d) {
+        if tree_noise[index_2d as usize] > config.tree_threshold {
+            if let Some(rs) =
+                generator.make_tree(config, chunk_key.clone(), voxels, index, index_2d)
+            {
                 ret.push(rs);
             }
         }
@@ -74,18 +106,351 @@ pub fn biomes_generate(
 }
 
 // 获取不同的生成器
-fn get_generator_by_attr(data: f32) -> Box<dyn BiomesGenerator> {
-    return if data < 0.1 {
-        BasicLandBiomes.into_boxed_generator()
-    } else if data < 0.4 {
-        DryLandBiomes.into_boxed_generator()
-    } else if data < 0.6 {
-        SnowLandBiomes.into_boxed_generator()
-    } else if data < 0.8 {
-        SandLandBiomes.into_boxed_generator()
-    } else {
-        BuleLandBoimes.into_boxed_generator()
-    };
+fn get_generator_by_attr(data: f32, config: &GenConfig) -> Box<dyn BiomesGenerator> {
+    BiomeKind::from_attr(data, config).generator()
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize, clap::ValueEnum,
+)]
+pub enum BiomeKind {
+    Basic,
+    Dry,
+    Snow,
+    Sand,
+    Bule,
+}
+
+// 群落的颜色 雾和天空的色调
+#[derive(Debug, Clone, Copy)]
+pub struct BiomePalette {
+    pub fog: Color,
+    pub sky_tint: Color,
+}
+
+/**
+ * 群落地图的配色 群落的覆盖图等地图显示都使用这个设置
+ * 默认使用群落雾的颜色 红绿色盲时有些群落很难区分
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub enum BiomeMapPalette {
+    #[default]
+    Default,
+    // Okabe-Ito 配色 红色盲和绿色盲都可以区分
+    OkabeIto,
+    // 明度逐渐增加 只看亮度也可以区分
+    Viridis,
+}
+
+impl BiomeMapPalette {
+    pub const ALL: [BiomeMapPalette; 3] = [
+        BiomeMapPalette::Default,
+        BiomeMapPalette::OkabeIto,
+        BiomeMapPalette::Viridis,
+    ];
+
+    // 翻译的关键字
+    pub fn name(&self) -> &'static str {
+        match self {
+            BiomeMapPalette::Default => "默认配色",
+            BiomeMapPalette::OkabeIto => "色盲友好配色",
+            BiomeMapPalette::Viridis => "明度渐变配色",
+        }
+    }
+}
+
+// 地表的装饰 只在客户端显示 不是体素 没有碰撞
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DecorationKind {
+    GrassTuft,
+    Flower,
+    DeadShrub,
+    Rock,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DecorationEntry {
+    pub kind: DecorationKind,
+    // 每一列出现的概率 再乘以设置中的密度
+    pub density: f32,
+    // 只放在这种方块上面
+    pub ground: u8,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BiomeEntry {
+    pub kind: BiomeKind,
+    // 特征值小于这个值时选中 默认值 实际使用 GenConfig::biome_thresholds
+    pub max_attr: f32,
+    pub palette: BiomePalette,
+    // 可以生成的生物和权重
+    pub mobs: &'static [MobSpawnEntry],
+    // 地表的装饰 按顺序判断 每一列最多一个
+    pub decorations: &'static [DecorationEntry],
+}
+
+/**
+ * 群落注册表 按照特征值从小到大排列
+ */
+pub const BIOME_REGISTRY: [BiomeEntry; 5] = [
+    BiomeEntry {
+        kind: BiomeKind::Basic,
+        max_attr: 0.1,
+        palette: BiomePalette {
+            fog: Color::rgb(0.75, 0.82, 0.90),
+            sky_tint: Color::rgb(1.0, 1.0, 1.0),
+        },
+        mobs: &[MobSpawnEntry {
+            kind: MobKind::Critter,
+            weight: 10,
+        }],
+        decorations: &[
+            DecorationEntry {
+                kind: DecorationKind::GrassTuft,
+                density: 0.25,
+                ground: Grass::ID,
+            },
+            DecorationEntry {
+                kind: DecorationKind::Flower,
+                density: 0.03,
+                ground: Grass::ID,
+            },
+        ],
+    },
+    BiomeEntry {
+        kind: BiomeKind::Dry,
+        max_attr: 0.4,
+        palette: BiomePalette {
+            fog: Color::rgb(0.80, 0.78, 0.70),
+            sky_tint: Color::rgb(1.0, 0.97, 0.92),
+        },
+        mobs: &[MobSpawnEntry {
+            kind: MobKind::Critter,
+            weight: 5,
+        }],
+        decorations: &[DecorationEntry {
+            kind: DecorationKind::DeadShrub,
+            density: 0.04,
+            ground: DryGrass::ID,
+        }],
+    },
+    BiomeEntry {
+        kind: BiomeKind::Snow,
+        max_attr: 0.6,
+        palette: BiomePalette {
+            fog: Color::rgb(0.80, 0.86, 0.95),
+            sky_tint: Color::rgb(0.93, 0.96, 1.0),
+        },
+        mobs: &[],
+        decorations: &[],
+    },
+    BiomeEntry {
+        kind: BiomeKind::Sand,
+        max_attr: 0.8,
+        palette: BiomePalette {
+            fog: Color::rgb(0.86, 0.80, 0.67),
+            sky_tint: Color::rgb(1.0, 0.96, 0.88),
+        },
+        mobs: &[],
+        decorations: &[DecorationEntry {
+            kind: DecorationKind::Rock,
+            density: 0.02,
+            ground: Sand::ID,
+        }],
+    },
+    BiomeEntry {
+        kind: BiomeKind::Bule,
+        max_attr: f32::MAX,
+        palette: BiomePalette {
+            fog: Color::rgb(0.72, 0.82, 0.80),
+            sky_tint: Color::rgb(0.96, 1.0, 0.97),
+        },
+        mobs: &[MobSpawnEntry {
+            kind: MobKind::Critter,
+            weight: 10,
+        }],
+        decorations: &[],
+    },
+];
+
+impl BiomeKind {
+    // 按照生成配置中的分界选择群落 循环中使用时先读取一次配置
+    pub fn from_attr(attr: f32, config: &GenConfig) -> Self {
+        for (entry, max_attr) in BIOME_REGISTRY.iter().zip(config.biome_thresholds) {
+            if attr < max_attr {
+                return entry.kind;
+            }
+        }
+        BiomeKind::Bule
+    }
+
+    pub fn entry(&self) -> &'static BiomeEntry {
+        BIOME_REGISTRY
+            .iter()
+            .find(|entry| entry.kind == *self)
+            .unwrap()
+    }
+
+    pub fn palette(&self) -> BiomePalette {
+        self.entry().palette
+    }
+
+    // 群落在地图上的颜色
+    pub fn map_color(&self, palette: BiomeMapPalette) -> Color {
+        match palette {
+            BiomeMapPalette::Default => self.palette().fog,
+            BiomeMapPalette::OkabeIto => match self {
+                BiomeKind::Basic => Color::rgb_u8(0, 158, 115),
+                BiomeKind::Dry => Color::rgb_u8(213, 94, 0),
+                BiomeKind::Snow => Color::rgb_u8(86, 180, 233),
+                BiomeKind::Sand => Color::rgb_u8(240, 228, 66),
+                BiomeKind::Bule => Color::rgb_u8(0, 114, 178),
+            },
+            BiomeMapPalette::Viridis => match self {
+                BiomeKind::Basic => Color::rgb_u8(94, 201, 98),
+                BiomeKind::Dry => Color::rgb_u8(33, 145, 140),
+                BiomeKind::Snow => Color::rgb_u8(253, 231, 37),
+                BiomeKind::Sand => Color::rgb_u8(59, 82, 139),
+                BiomeKind::Bule => Color::rgb_u8(68, 1, 84),
+            },
+        }
+    }
+
+    pub fn generator(&self) -> Box<dyn BiomesGenerator> {
+        match self {
+            BiomeKind::Basic => BasicLandBiomes.into_boxed_generator(),
+            BiomeKind::Dry => DryLandBiomes.into_boxed_generator(),
+            BiomeKind::Snow => SnowLandBiomes.into_boxed_generator(),
+            BiomeKind::Sand => SandLandBiomes.into_boxed_generator(),
+            BiomeKind::Bule => BuleLandBoimes.into_boxed_generator(),
+        }
+    }
+}
+
+/**
+ * 世界坐标所在的群落
+ * 噪声的坐标是 chunk_key * CHUNK_SIZE + x 比世界坐标多了半个区块
+ */
+pub fn biome_at(world_x: f32, world_z: f32, seed: i32) -> BiomeKind {
+    cached_height_sampler(seed).biome_at(world_x, world_z)
+}
+
+// 群落高度融合时 周围采样的距离
+pub const BIOME_BLEND_RADIUS: f64 = 8.0;
+
+/**
+ * 群落高度采样
+ * 坐标和 biomes_noise 保持一致 (chunk_key * CHUNK_SIZE + x)
+ */
+pub struct BiomeHeightSampler {
+    biome: Worley,
+    height: Fbm<Perlin>,
+    // 创建时的生成配置 采样时不再读取全局的配置
+    pub config: GenConfig,
+}
+
+impl BiomeHeightSampler {
+    pub fn new(seed: i32) -> Self {
+        Self::with_config(seed, gen_config())
+    }
+
+    pub fn with_config(seed: i32, config: GenConfig) -> Self {
+        Self {
+            biome: Worley::new(seed as u32)
+                .set_distance_function(euclidean)
+                .set_return_type(ReturnType::Value)
+                .set_frequency(config.biome_frequency()),
+            // 额外的一层噪声 和地形噪声错开种子
+            height: Fbm::<Perlin>::new(seed.wrapping_add(1) as u32)
+                .set_octaves(3)
+                .set_frequency(config.height_frequency),
+            config,
+        }
+    }
+
+    // 群落特征值
+    pub fn biome_attr_at(&self, world_x: f64, world_z: f64) -> f32 {
+        self.biome.get([world_x, world_z]) as f32
+    }
+
+    // 世界坐标所在的群落
+    pub fn biome_at(&self, world_x: f32, world_z: f32) -> BiomeKind {
+        let half = (CHUNK_SIZE / 2) as f64;
+        BiomeKind::from_attr(
+            self.biome_attr_at(world_x as f64 + half, world_z as f64 + half),
+            &self.config,
+        )
+    }
+
+    // 高度噪声 [-1, 1]
+    pub fn height_noise_at(&self, world_x: f64, world_z: f64) -> f32 {
+        (self.height.get([world_x, world_z]) as f32).clamp(-1.0, 1.0)
+    }
+
+    /**
+     * 和周围的群落混合后的高度 避免群落边界出现断崖
+     */
+    pub fn blended_height_at(&self, world_x: f64, world_z: f64) -> f32 {
+        let n = self.height_noise_at(world_x, world_z);
+        let r = BIOME_BLEND_RADIUS;
+        let samples = [
+            (0.0, 0.0, 2.0),
+            (r, 0.0, 1.0),
+            (-r, 0.0, 1.0),
+            (0.0, r, 1.0),
+            (0.0, -r, 1.0),
+        ];
+        let mut total = 0.0;
+        let mut weight = 0.0;
+        for (dx, dz, w) in samples {
+            let attr = self.biome_attr_at(world_x + dx, world_z + dz);
+            total += get_generator_by_attr(attr, &self.config).height_curve(n, &self.config) * w;
+            weight += w;
+        }
+        total / weight
+    }
+}
+
+thread_local! {
+    // Worley 不能在线程之间共享 每个线程缓存一个
+    static HEIGHT_SAMPLER: RefCell<Option<(i32, Rc<BiomeHeightSampler>)>> = RefCell::new(None);
+}
+
+/**
+ * 当前线程缓存的采样器 种子或者生成配置变化后重新创建
+ */
+pub fn cached_height_sampler(seed: i32) -> Rc<BiomeHeightSampler> {
+    let config = gen_config();
+    HEIGHT_SAMPLER.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        match cache.as_ref() {
+            Some((cached_seed, sampler)) if *cached_seed == seed && sampler.config == config => {
+                sampler.clone()
+            }
+            _ => {
+                let sampler = Rc::new(BiomeHeightSampler::with_config(seed, config));
+                *cache = Some((seed, sampler.clone()));
+                sampler
+            }
+        }
+    })
+}
+
+/**
+ * 系统中保存的采样器 种子或者生成配置变化后重新创建
+ * 重新读取或者从服务端同步生成配置后 群落的分界也跟着变化
+ */
+pub fn refresh_sampler(
+    sampler: &mut Option<(i32, BiomeHeightSampler)>,
+    seed: i32,
+) -> &BiomeHeightSampler {
+    let config = gen_config();
+    if !matches!(sampler.as_ref(), Some((s, cached)) if *s == seed && cached.config == config) {
+        *sampler = None;
+    }
+    &sampler
+        .get_or_insert_with(|| (seed, BiomeHeightSampler::with_config(seed, config)))
+        .1
 }
 
 pub fn tree_noise(chunk_key: ChunkKey, seed: i32) -> Vec<f32> {
@@ -107,11 +472,75 @@ pub fn tree_noise(chunk_key: ChunkKey, seed: i32) -> Vec<f32> {
         .collect()
 }
 
-pub fn biomes_noise(chunk_key: ChunkKey, seed: i32) -> Vec<f32> {
+// 群落噪声缓存的列数 每列 CHUNK_SIZE * CHUNK_SIZE 个 f32
+pub const BIOME_NOISE_CACHE_SIZE: usize = 1024;
+
+// 种子 频率 区块的 x z
+pub type NoiseCacheKey = (i32, u64, i32, i32);
+
+/**
+ * 二维噪声平面的缓存 和区块的 y 无关 同一列的区块使用同一份噪声
+ * 超过容量时丢弃最早加入的列
+ */
+#[derive(Debug, Default)]
+pub struct NoiseCache {
+    planes: HashMap<NoiseCacheKey, Vec<f32>>,
+    order: VecDeque<NoiseCacheKey>,
+}
+
+impl NoiseCache {
+    pub fn get(&self, key: &NoiseCacheKey) -> Option<Vec<f32>> {
+        self.planes.get(key).cloned()
+    }
+
+    pub fn insert(&mut self, key: NoiseCacheKey, plane: Vec<f32>, capacity: usize) {
+        if self.planes.insert(key, plane).is_none() {
+            self.order.push_back(key);
+        }
+        while self.order.len() > capacity {
+            if let Some(old) = self.order.pop_front() {
+                self.planes.remove(&old);
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.planes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.planes.is_empty()
+    }
+}
+
+lazy_static! {
+    // 生成线程共用 计算噪声时不持有锁
+    static ref BIOME_NOISE_CACHE: Mutex<NoiseCache> = Mutex::new(NoiseCache::default());
+}
+
+/**
+ * 区块所在列的群落噪声
+ * 按照种子和频率缓存 生成配置修改了群落大小后不会用到旧的噪声
+ */
+pub fn biomes_noise(chunk_key: ChunkKey, seed: i32, frequency: f64) -> Vec<f32> {
+    let key = (seed, frequency.to_bits(), chunk_key.0.x, chunk_key.0.z);
+    if let Some(plane) = BIOME_NOISE_CACHE.lock().unwrap().get(&key) {
+        return plane;
+    }
+    let plane = build_biomes_noise(chunk_key, seed, frequency);
+    BIOME_NOISE_CACHE
+        .lock()
+        .unwrap()
+        .insert(key, plane.clone(), BIOME_NOISE_CACHE_SIZE);
+    plane
+}
+
+// 不使用缓存 每次都重新采样整个平面
+pub fn build_biomes_noise(chunk_key: ChunkKey, seed: i32, frequency: f64) -> Vec<f32> {
     let noise = Worley::new(seed as u32)
         .set_distance_function(euclidean)
         .set_return_type(ReturnType::Value)
-        .set_frequency(0.008);
+        .set_frequency(frequency);
 
     let x_offset = (chunk_key.0.x * CHUNK_SIZE) as f64;
     let z_offset = (chunk_key.0.z * CHUNK_SIZE) as f64;
@@ -127,8 +556,23 @@ pub fn biomes_noise(chunk_key: ChunkKey, seed: i32) -> Vec<f32> {
 }
 
 pub trait BiomesGenerator: 'static + Sync + Send {
+    // 群落的高度曲线 输入高度噪声[-1, 1] 返回叠加在基础地形上的高度
+    fn height_curve(&self, noise: f32, _config: &GenConfig) -> f32 {
+        noise * 3.0
+    }
+
+    // 该位置上 只考虑当前群落的高度
+    fn height_at(&self, world_x: f32, world_z: f32, seed: i32) -> f32 {
+        let sampler = cached_height_sampler(seed);
+        self.height_curve(
+            sampler.height_noise_at(world_x as f64, world_z as f64),
+            &sampler.config,
+        )
+    }
+
     fn gen_land_with_info(
         &self,
+        config: &GenConfig,
         chunk_key: ChunkKey,
         voxels: &mut Vec<Voxel>,
         chunk_index: u32,
@@ -139,6 +583,7 @@ pub trait BiomesGenerator: 'static + Sync + Send {
 
     fn gen_land(
         &self,
+        config: &GenConfig,
         chunk_key: ChunkKey,
         voxels: &mut Vec<Voxel>,
         chunk_index: u32,
@@ -148,6 +593,7 @@ pub trait BiomesGenerator: 'static + Sync + Send {
         let [x, y, z] = SampleShape::delinearize(chunk_index);
         let height = base_y + y as f32;
         self.gen_land_with_info(
+            config,
             chunk_key,
             voxels,
             chunk_index,
@@ -159,6 +605,7 @@ pub trait BiomesGenerator: 'static + Sync + Send {
 
     fn make_tree(
         &self,
+        config: &GenConfig,
         chunk_key: ChunkKey,
         voxels: &mut Vec<Voxel>,
         chunk_index: u32,
@@ -168,6 +615,7 @@ pub trait BiomesGenerator: 'static + Sync + Send {
         let [x, y, z] = SampleShape::delinearize(chunk_index);
         let height = base_y + y as f32;
         self.make_tree_with_info(
+            config,
             chunk_key,
             voxels,
             chunk_index,
@@ -179,6 +627,7 @@ pub trait BiomesGenerator: 'static + Sync + Send {
 
     fn make_tree_with_info(
         &self,
+        _config: &GenConfig,
         _chunk_key: ChunkKey,
         _voxels: &mut Vec<Voxel>,
         _chunk_index: u32,
@@ -200,12 +649,15 @@ impl<T: BiomesGenerator> IntoBoxedTerrainGenerator for T {
         Box::new(self)
     }
 }
-// 海平面
-pub const SEE_LEVEL: f32 = -60. + 76.;
-// 山峰线
-pub const MOUNTAIN_LEVEL: f32 = -60. + 100.;
+// 海平面 可以在生成配置中修改
+pub fn see_level() -> f32 {
+    gen_config().sea_level
+}
+
 // 雪线
-pub const SNOW_LEVEL: f32 = -60. + 110.;
+pub fn snow_level() -> f32 {
+    gen_config().snow_level
+}
 
 #[derive(Debug, Clone)]
 pub struct TreeGentor {
@@ -343,7 +795,7 @@ fn deal_other_tree(
                     .unwrap()
                 };
 
-                let task = pool.spawn(async move { (0, message) });
+                let task = pool.spawn(async move { (0, key, message) });
                 tasks.tasks.push(task);
 
                 let task = pool.spawn(async move { (key.as_u8_array(), voxels.clone()) });
@@ -352,3 +804,181 @@ fn deal_other_tree(
         }
     }
 }
+
+#[test]
+fn test_chunk_shape_bounds() {
+    let max = CHUNK_SIZE_U32 - 1;
+    assert_eq!(SampleShape::delinearize(0), [0, 0, 0]);
+    assert_eq!(SampleShape::linearize([0, 0, 0]), 0);
+    assert_eq!(
+        SampleShape::delinearize(SampleShape::SIZE - 1),
+        [max, max, max]
+    );
+    assert_eq!(
+        SampleShape::linearize([max, max, max]),
+        SampleShape::SIZE - 1
+    );
+    assert_eq!(PanelShape::delinearize(0), [0, 0]);
+    assert_eq!(PanelShape::linearize([0, 0]), 0);
+    assert_eq!(PanelShape::delinearize(PanelShape::SIZE - 1), [max, max]);
+    assert_eq!(PanelShape::linearize([max, max]), PanelShape::SIZE - 1);
+}
+
+#[test]
+fn test_column_index_in_bounds() {
+    assert_eq!(column_index(0), 0);
+    assert_eq!(column_index(SampleShape::SIZE - 1), PanelShape::SIZE - 1);
+    for index in 0..SampleShape::SIZE {
+        assert!(column_index(index) < PanelShape::SIZE);
+    }
+}
+
+#[test]
+fn test_biomes_generate_skip_bad_index() {
+    // 在雪线之上 每种群落都会在表面放置方块
+    let chunk_key = ChunkKey(IVec3::new(0, 4, 0));
+    // 只有错误的下标时什么都不生成
+    let mut voxels = vec![Voxel::EMPTY; SampleShape::SIZE as usize];
+    let config = gen_config();
+    let trees = biomes_generate(
+        chunk_key,
+        1,
+        vec![SampleShape::SIZE, u32::MAX],
+        &mut voxels,
+        &config,
+    );
+    assert!(trees.is_empty());
+    assert!(voxels.iter().all(|voxel| *voxel == Voxel::EMPTY));
+
+    // 跳过错误的下标后 和只传入正确的下标结果一样
+    let mut expected = vec![Voxel::EMPTY; SampleShape::SIZE as usize];
+    let expected_trees = biomes_generate(chunk_key, 1, vec![0], &mut expected, &config);
+    let surface_index = vec![SampleShape::SIZE, u32::MAX, 0];
+    let trees = biomes_generate(chunk_key, 1, surface_index, &mut voxels, &config);
+    assert_eq!(voxels, expected);
+    assert_eq!(trees.len(), expected_trees.len());
+    assert_ne!(voxels[0], Voxel::EMPTY);
+}
+
+#[test]
+fn test_all_biomes_reachable() {
+    // 每个种子在原点周围 4096 x 4096 格的范围内按区块采样
+    let seeds = [1512354854, 0, 42];
+    let radius = 2048;
+    let mut counts = [0_usize; BIOME_REGISTRY.len()];
+    for seed in seeds {
+        let sampler = BiomeHeightSampler::new(seed);
+        for x in (-radius..radius).step_by(CHUNK_SIZE as usize) {
+            for z in (-radius..radius).step_by(CHUNK_SIZE as usize) {
+                let kind = sampler.biome_at(x as f32, z as f32);
+                let index = BIOME_REGISTRY
+                    .iter()
+                    .position(|entry| entry.kind == kind)
+                    .unwrap();
+                counts[index] += 1;
+            }
+        }
+    }
+    let total: usize = counts.iter().sum();
+    // cargo test -- --nocapture 查看分布
+    for (entry, count) in BIOME_REGISTRY.iter().zip(counts) {
+        println!(
+            "{:?}: {:.1}%",
+            entry.kind,
+            count as f32 * 100.0 / total as f32
+        );
+    }
+    for (entry, count) in BIOME_REGISTRY.iter().zip(counts) {
+        assert!(count > 0, "群落 {:?} 没有出现", entry.kind);
+    }
+}
+
+#[test]
+fn test_biome_map_palette() {
+    // 每种配色中的群落颜色都不相同
+    for palette in BiomeMapPalette::ALL {
+        let colors: Vec<[f32; 4]> = BIOME_REGISTRY
+            .iter()
+            .map(|entry| entry.kind.map_color(palette).as_rgba_f32())
+            .collect();
+        for (i, a) in colors.iter().enumerate() {
+            for b in colors[i + 1..].iter() {
+                assert_ne!(a, b, "{:?}", palette);
+            }
+        }
+    }
+    assert_eq!(
+        BiomeKind::Snow.map_color(BiomeMapPalette::Default),
+        BiomeKind::Snow.palette().fog
+    );
+}
+
+#[test]
+fn test_biomes_noise_cache() {
+    let frequency = gen_config().biome_frequency();
+    // 同一列不同高度的区块使用同一份噪声 和不使用缓存的结果一致
+    let low = biomes_noise(ChunkKey(IVec3::new(3, -4, 7)), 42, frequency);
+    let high = biomes_noise(ChunkKey(IVec3::new(3, 5, 7)), 42, frequency);
+    assert_eq!(low, high);
+    assert_eq!(
+        low,
+        build_biomes_noise(ChunkKey(IVec3::new(3, 0, 7)), 42, frequency)
+    );
+    assert_ne!(
+        low,
+        biomes_noise(ChunkKey(IVec3::new(3, 0, 7)), 43, frequency)
+    );
+
+    // 超过容量时丢弃最早加入的列
+    let mut cache = NoiseCache::default();
+    for x in 0..3 {
+        cache.insert((0, 0, x, 0), vec![x as f32], 2);
+    }
+    assert_eq!(cache.len(), 2);
+    assert_eq!(cache.get(&(0, 0, 0, 0)), None);
+    assert_eq!(cache.get(&(0, 0, 2, 0)), Some(vec![2.0]));
+}
+
+#[test]
+fn test_cached_height_sampler() {
+    let sampler = cached_height_sampler(42);
+    assert!(Rc::ptr_eq(&sampler, &cached_height_sampler(42)));
+    assert_eq!(
+        sampler.height_noise_at(100.0, -30.0),
+        BiomeHeightSampler::new(42).height_noise_at(100.0, -30.0)
+    );
+    // 换了种子重新创建
+    assert!(!Rc::ptr_eq(&sampler, &cached_height_sampler(43)));
+    assert_eq!(
+        BasicLandBiomes.height_at(100.0, -30.0, 43),
+        BasicLandBiomes.height_curve(
+            BiomeHeightSampler::new(43).height_noise_at(100.0, -30.0),
+            &gen_config()
+        )
+    );
+}
+
+#[test]
+fn test_sampler_follows_gen_config() {
+    use super::gen_config::set_gen_config;
+
+    let original = gen_config();
+    let seed = 7;
+    let x = (0..64)
+        .map(|i| i as f32 * 37.0)
+        .find(|x| biome_at(*x, 0.0, seed) != BiomeKind::Bule)
+        .unwrap();
+    let mut sampler = None;
+    refresh_sampler(&mut sampler, seed);
+    // 只修改群落分界 噪声频率不变 缓存的采样器也要重新创建
+    set_gen_config(GenConfig {
+        biome_thresholds: [-2.0; 4],
+        ..original
+    });
+    let changed = biome_at(x, 0.0, seed);
+    let refreshed = refresh_sampler(&mut sampler, seed).biome_at(x, 0.0);
+    set_gen_config(original);
+    assert_eq!(changed, BiomeKind::Bule);
+    assert_eq!(refreshed, BiomeKind::Bule);
+    assert_ne!(biome_at(x, 0.0, seed), BiomeKind::Bule);
+}
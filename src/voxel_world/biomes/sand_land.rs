@@ -1,6 +1,9 @@
 use ndshape::ConstShape;
 
-use crate::voxel_world::voxel::{Sand, VoxelMaterial};
+use crate::voxel_world::{
+    gen_config::GenConfig,
+    voxel::{Sand, VoxelMaterial},
+};
 
 // 沙漠大陆
 use super::{BiomesGenerator, SampleShape};
@@ -8,8 +11,14 @@ use super::{BiomesGenerator, SampleShape};
 pub struct SandLandBiomes;
 
 impl BiomesGenerator for SandLandBiomes {
+    // 平坦的沙丘
+    fn height_curve(&self, noise: f32, _config: &GenConfig) -> f32 {
+        noise * 1.5
+    }
+
     fn gen_land_with_info(
         &self,
+        _config: &GenConfig,
         _chunk_key: crate::voxel_world::chunk::ChunkKey,
         voxels: &mut Vec<crate::voxel_world::voxel::Voxel>,
         _chunk_index: u32,
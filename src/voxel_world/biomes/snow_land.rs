@@ -1,6 +1,9 @@
 use ndshape::ConstShape;
 
-use crate::voxel_world::voxel::{Sown, VoxelMaterial};
+use crate::voxel_world::{
+    gen_config::GenConfig,
+    voxel::{Sown, VoxelMaterial},
+};
 
 use super::{BiomesGenerator, SampleShape};
 
@@ -8,8 +11,14 @@ use super::{BiomesGenerator, SampleShape};
 pub struct SnowLandBiomes;
 
 impl BiomesGenerator for SnowLandBiomes {
+    // 雪原整体抬高一些
+    fn height_curve(&self, noise: f32, _config: &GenConfig) -> f32 {
+        (noise * 0.5 + 0.5) * 12.0
+    }
+
     fn gen_land_with_info(
         &self,
+        _config: &GenConfig,
         _chunk_key: crate::voxel_world::chunk::ChunkKey,
         voxels: &mut Vec<crate::voxel_world::voxel::Voxel>,
         _chunk_index: u32,
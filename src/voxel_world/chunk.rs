@@ -7,8 +7,10 @@ use bevy::{
     prelude::{IVec3, Resource, Vec3},
     reflect::Reflect,
 };
+use rand::{Error, Rng, RngCore};
 use serde::{Deserialize, Serialize};
 
+use super::structure::hash_with_seed;
 use crate::{common::Sphere3, CHUNK_SIZE};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Reflect)]
@@ -143,3 +145,61 @@ fn get_chunk_key_axis(x: f32) -> i32 {
     }
     0
 }
+
+/**
+ * 区块的随机数生成器
+ * 相同的 区块坐标 世界种子 salt 总是得到相同的随机数序列
+ * 不依赖平台和 rand 的版本 所以保存过的世界重新生成的结果也一样
+ * 不同的功能(矿物 树 结构)使用不同的 salt 避免互相关联
+ */
+pub fn chunk_rng(chunk_key: ChunkKey, seed: i32, salt: u64) -> impl Rng {
+    let key = chunk_key.0;
+    ChunkRng(hash_with_seed(
+        &[key.x, key.y, key.z, salt as i32, (salt >> 32) as i32],
+        seed,
+    ))
+}
+
+// splitmix64 状态只有一个 u64 很小也足够快
+struct ChunkRng(u64);
+
+impl RngCore for ChunkRng {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+#[test]
+fn test_chunk_rng() {
+    let key = ChunkKey(IVec3::new(3, -2, 7));
+    let sequence = |salt: u64| -> Vec<u64> {
+        let mut rng = chunk_rng(key, 42, salt);
+        (0..16).map(|_| rng.gen()).collect()
+    };
+    assert_eq!(sequence(1), sequence(1));
+    assert_ne!(sequence(1), sequence(2));
+    // 区块坐标不同也要不一样
+    let mut other = chunk_rng(ChunkKey(IVec3::new(3, -2, 8)), 42, 1);
+    let other: Vec<u64> = (0..16).map(|_| other.gen()).collect();
+    assert_ne!(sequence(1), other);
+}
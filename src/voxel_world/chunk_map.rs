@@ -1,13 +1,21 @@
 use bevy::{
-    prelude::{IVec3, Resource},
+    prelude::{IVec3, Resource, Vec3},
     reflect::Reflect,
     utils::HashMap,
 };
 use ndshape::{ConstShape, ConstShape3u32};
 
-use crate::{CHUNK_SIZE, CHUNK_SIZE_ADD_2_U32, CHUNK_SIZE_U32};
+use crate::{
+    tools::vec3_to_chunk_key_any_xyz, ChunkShape, CHUNK_SIZE, CHUNK_SIZE_ADD_2_U32, CHUNK_SIZE_U32,
+};
 
-use super::{chunk::ChunkKey, voxel::Voxel};
+use super::{
+    chunk::ChunkKey,
+    gen_config::{gen_config, COLUMN_MAX_CHUNK_Y, COLUMN_MIN_CHUNK_Y},
+    light::{LightSeed, MAX_LIGHT},
+    voxel::Voxel,
+    voxel_registry::VOXEL_REGISTRY,
+};
 
 #[derive(Debug, Clone, Default, Resource, Reflect)]
 pub struct ChunkMap {
@@ -30,6 +38,15 @@ impl ChunkMap {
         None
     }
 
+    // 身体或者脚所在的方块可以攀爬 客户端和服务端使用同样的判断
+    pub fn climbable_at(&self, body_pos: Vec3) -> bool {
+        [body_pos, body_pos - Vec3::Y].iter().any(|pos| {
+            let (chunk_key, xyz) = vec3_to_chunk_key_any_xyz(*pos);
+            self.get_block(chunk_key, xyz)
+                .map_or(false, |voxel| voxel.is_climbable())
+        })
+    }
+
     // 寻找y轴上最进的数据
     pub fn find_closest_block_y(
         &self,
@@ -38,7 +55,7 @@ impl ChunkMap {
         id: u8,
     ) -> Option<(ChunkKey, [u32; 3])> {
         type DataShape = ConstShape3u32<CHUNK_SIZE_U32, CHUNK_SIZE_U32, CHUNK_SIZE_U32>;
-        for chunk_y in chunk_key.0.y..=128 / CHUNK_SIZE {
+        for chunk_y in chunk_key.0.y..=COLUMN_MAX_CHUNK_Y {
             let start = if chunk_y == chunk_key.0.y { xyz[1] } else { 0 };
             let new_chunk_key = ChunkKey(IVec3 {
                 x: chunk_key.0.x,
@@ -67,9 +84,9 @@ impl ChunkMap {
         let n_self = &IVec3::new(0, 0, 0);
 
         let offsets = [px, nx, pz, nz, n_self];
-        let last_index = -128 / CHUNK_SIZE + 1;
 
-        for y_offset in last_index..=128 / CHUNK_SIZE {
+        // 世界高度之外的区块不会收到 当做空气
+        for y_offset in gen_config().world_height.chunk_ys() {
             for offset in offsets.iter() {
                 let mut new_key = chunk_key;
                 new_key.0.y = y_offset;
@@ -104,9 +121,9 @@ impl ChunkMap {
         type DataShape = ConstShape3u32<CHUNK_SIZE_ADD_2_U32, CHUNK_SIZE_U32, CHUNK_SIZE_ADD_2_U32>;
         let mut map: HashMap<i32, Vec<Voxel>> = HashMap::new();
 
-        let last_inex = -128 / CHUNK_SIZE + 1;
+        let last_inex = COLUMN_MIN_CHUNK_Y;
 
-        for y_offset in last_inex..=128 / CHUNK_SIZE {
+        for y_offset in last_inex..=COLUMN_MAX_CHUNK_Y {
             let mut new_key = chunk_key;
             new_key.0.y = y_offset;
             let layer_data = self.get_layer_neighbors(new_key);
@@ -125,6 +142,50 @@ impl ChunkMap {
         result
     }
 
+    /**
+     * 相邻区块列(包括对角)中 可能照到当前列的光源
+     * 坐标和 get_with_neighbor_full_y 的列坐标一致 已经在列数据中的光源不返回
+     */
+    pub fn light_seeds_near(&self, chunk_key: ChunkKey) -> Vec<LightSeed> {
+        let mut seeds = Vec::new();
+        let last_index = COLUMN_MIN_CHUNK_Y;
+        let column_max = CHUNK_SIZE + 1;
+        // 超过这个距离的光照不到列里面
+        let reach = |v: i32| v > -(MAX_LIGHT as i32) && v < column_max + MAX_LIGHT as i32;
+        for dx in -1..=1 {
+            for dz in -1..=1 {
+                if dx == 0 && dz == 0 {
+                    continue;
+                }
+                for y_offset in last_index..=COLUMN_MAX_CHUNK_Y {
+                    let key =
+                        ChunkKey(IVec3::new(chunk_key.0.x + dx, y_offset, chunk_key.0.z + dz));
+                    let Some(voxels) = self.get(key) else {
+                        continue;
+                    };
+                    for (index, voxel) in voxels.iter().enumerate() {
+                        let level = VOXEL_REGISTRY.light(voxel.id);
+                        if level == 0 {
+                            continue;
+                        }
+                        let [x, y, z] = ChunkShape::delinearize(index as u32);
+                        let pos = IVec3::new(
+                            dx * CHUNK_SIZE + x as i32 + 1,
+                            (y_offset - last_index) * CHUNK_SIZE + y as i32,
+                            dz * CHUNK_SIZE + z as i32 + 1,
+                        );
+                        let inside =
+                            (0..=column_max).contains(&pos.x) && (0..=column_max).contains(&pos.z);
+                        if !inside && reach(pos.x) && reach(pos.z) {
+                            seeds.push(LightSeed { pos, level });
+                        }
+                    }
+                }
+            }
+        }
+        seeds
+    }
+
     pub fn get_neighbors(&self, chunk_key: ChunkKey) -> Vec<Voxel> {
         let voxels = self.get(chunk_key);
 
@@ -0,0 +1,318 @@
+// 地形生成的参数
+// 生成任务在异步线程中执行 所以保存一份全局的 服务端修改配置文件后重新加载 见 server::gen_reload
+
+use std::sync::RwLock;
+
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+use crate::CHUNK_SIZE;
+
+use super::biomes::BIOME_REGISTRY;
+
+// 客户端网格使用的整列区块的 y 范围 世界高度只能在这个范围内
+pub const COLUMN_MIN_CHUNK_Y: i32 = -128 / CHUNK_SIZE + 1;
+pub const COLUMN_MAX_CHUNK_Y: i32 = 128 / CHUNK_SIZE;
+
+// 群落大小的范围(格)
+pub const BIOME_SIZE_RANGE: std::ops::RangeInclusive<f64> = 16.0..=4096.0;
+
+/**
+ * 平坦世界 所有的列都是同样的高度 没有群落 树和结构
+ * water 开启时海平面以下都是水 关闭时是干燥的平地
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FlatWorld {
+    // 地面的高度 和 sea_level 使用相同的坐标
+    pub height: f32,
+    pub water: bool,
+}
+
+impl Default for FlatWorld {
+    fn default() -> Self {
+        Self {
+            height: -60. + 70.,
+            water: false,
+        }
+    }
+}
+
+/**
+ * 世界的高度范围(区块的 y) 范围之外的区块不生成 都是空气
+ * 最低一层区块的底部是基岩 客户端按照整列区块生成网格 只能在整列的范围内缩小
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorldHeight {
+    pub min_chunk_y: i32,
+    pub max_chunk_y: i32,
+}
+
+impl Default for WorldHeight {
+    // 整列的高度 包括基岩 海平面 山峰和雪线
+    fn default() -> Self {
+        Self {
+            min_chunk_y: COLUMN_MIN_CHUNK_Y,
+            max_chunk_y: COLUMN_MAX_CHUNK_Y,
+        }
+    }
+}
+
+impl WorldHeight {
+    pub fn contains(&self, chunk_y: i32) -> bool {
+        self.chunk_ys().contains(&chunk_y)
+    }
+
+    pub fn chunk_ys(&self) -> std::ops::RangeInclusive<i32> {
+        self.min_chunk_y..=self.max_chunk_y
+    }
+
+    // 世界最低和最高的方块
+    pub fn min_block_y(&self) -> i32 {
+        self.min_chunk_y * CHUNK_SIZE - CHUNK_SIZE / 2
+    }
+
+    pub fn max_block_y(&self) -> i32 {
+        self.max_chunk_y * CHUNK_SIZE + CHUNK_SIZE / 2 - 1
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.min_chunk_y > self.max_chunk_y {
+            return Err(String::from(
+                "world_height 的 min_chunk_y 不能大于 max_chunk_y",
+            ));
+        }
+        if self.min_chunk_y < COLUMN_MIN_CHUNK_Y || self.max_chunk_y > COLUMN_MAX_CHUNK_Y {
+            return Err(format!(
+                "world_height 必须在 {} 到 {} 之间",
+                COLUMN_MIN_CHUNK_Y, COLUMN_MAX_CHUNK_Y
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GenConfig {
+    // 群落特征值的分界 依次是 Basic Dry Snow Sand 的上限 更大的是 Bule
+    pub biome_thresholds: [f32; 4],
+    // 群落的大小(格) 大约是相邻两个群落中心的距离 越大群落越大
+    // 群落噪声的频率是 1 / biome_size 默认的 125 对应原来的频率 0.008
+    pub biome_size: f64,
+    // 群落高度噪声的频率
+    pub height_frequency: f64,
+    // 地形的基础高度
+    pub base_height: f32,
+    // 山脊噪声的强度
+    pub ridge_scale: f32,
+    // 种树噪声的阈值 越接近 1 树越少
+    pub tree_threshold: f32,
+    // 海平面 山峰线 雪线
+    pub sea_level: f32,
+    pub mountain_level: f32,
+    pub snow_level: f32,
+    // 这个高度之下都是基岩
+    pub bedrock_level: f32,
+    // 生成区块的高度范围
+    pub world_height: WorldHeight,
+    // 设置时生成平坦世界 例如 flat: Some((height: 10.0, water: true))
+    pub flat: Option<FlatWorld>,
+}
+
+impl Default for GenConfig {
+    fn default() -> Self {
+        Self {
+            biome_thresholds: [
+                BIOME_REGISTRY[0].max_attr,
+                BIOME_REGISTRY[1].max_attr,
+                BIOME_REGISTRY[2].max_attr,
+                BIOME_REGISTRY[3].max_attr,
+            ],
+            biome_size: 125.0,
+            height_frequency: 0.01,
+            base_height: -60.,
+            ridge_scale: 5.0,
+            tree_threshold: 0.99,
+            sea_level: -60. + 76.,
+            mountain_level: -60. + 100.,
+            snow_level: -60. + 110.,
+            bedrock_level: -110.,
+            world_height: WorldHeight::default(),
+            flat: None,
+        }
+    }
+}
+
+impl GenConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        let flat_height = self.flat.map_or(0.0, |flat| flat.height);
+        let values = [
+            flat_height,
+            self.base_height,
+            self.ridge_scale,
+            self.tree_threshold,
+            self.sea_level,
+            self.mountain_level,
+            self.snow_level,
+            self.bedrock_level,
+        ];
+        if values
+            .iter()
+            .chain(&self.biome_thresholds)
+            .any(|v| !v.is_finite())
+        {
+            return Err(String::from("存在无效的数值"));
+        }
+        if self.biome_thresholds.windows(2).any(|w| w[0] > w[1]) {
+            return Err(format!(
+                "biome_thresholds 需要从小到大排列: {:?}",
+                self.biome_thresholds
+            ));
+        }
+        if !BIOME_SIZE_RANGE.contains(&self.biome_size) {
+            return Err(format!(
+                "biome_size 必须在 {} 到 {} 之间 当前是 {}",
+                BIOME_SIZE_RANGE.start(),
+                BIOME_SIZE_RANGE.end(),
+                self.biome_size
+            ));
+        }
+        if !self.height_frequency.is_finite() || self.height_frequency <= 0.0 {
+            return Err(String::from("height_frequency 必须大于 0"));
+        }
+        if !(0.0..=1.0).contains(&self.tree_threshold) {
+            return Err(String::from("tree_threshold 必须在 0 到 1 之间"));
+        }
+        if !(self.bedrock_level < self.sea_level
+            && self.sea_level < self.mountain_level
+            && self.mountain_level < self.snow_level)
+        {
+            return Err(String::from(
+                "高度需要满足 bedrock_level < sea_level < mountain_level < snow_level",
+            ));
+        }
+        if self.flat.is_some() && flat_height <= self.bedrock_level {
+            return Err(String::from("flat 的 height 需要高于 bedrock_level"));
+        }
+        self.world_height.validate()
+    }
+
+    /**
+     * 群落噪声的频率
+     * Worley 噪声的单元平均间隔 1 / frequency 格 每个单元是一个群落
+     */
+    pub fn biome_frequency(&self) -> f64 {
+        1.0 / self.biome_size
+    }
+
+    // 解析并检查 不合法的配置不会被使用
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let config: Self = ron::from_str(text).map_err(|err| err.to_string())?;
+        check_removed_keys(text)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /**
+     * 读取配置文件 没有文件时生成默认配置
+     */
+    pub fn load_or_create(path: &str) -> Result<Self, String> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                let config = Self::default();
+                let text =
+                    ron::ser::to_string_pretty(&config, ron::ser::PrettyConfig::default()).unwrap();
+                if let Err(err) = std::fs::write(path, text) {
+                    println!("生成配置保存失败: {}", err);
+                }
+                Ok(config)
+            }
+            Err(err) => Err(err.to_string()),
+        }
+    }
+}
+
+// 已经移除的字段 未知的字段会被忽略 这些字段需要提示换成新的写法
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct RemovedKeys {
+    biome_frequency: Option<f64>,
+}
+
+fn check_removed_keys(text: &str) -> Result<(), String> {
+    let removed: RemovedKeys = ron::from_str(text).map_err(|err| err.to_string())?;
+    if let Some(frequency) = removed.biome_frequency {
+        return Err(format!(
+            "biome_frequency 已经移除 请使用 biome_size (格) 例如 biome_size: {}",
+            1.0 / frequency
+        ));
+    }
+    Ok(())
+}
+
+lazy_static! {
+    static ref GEN_CONFIG: RwLock<GenConfig> = RwLock::new(GenConfig::default());
+}
+
+// 当前使用的生成配置
+pub fn gen_config() -> GenConfig {
+    *GEN_CONFIG.read().unwrap()
+}
+
+// 之后生成的区块使用新的配置 已经生成的区块需要重新生成
+pub fn set_gen_config(config: GenConfig) {
+    *GEN_CONFIG.write().unwrap() = config;
+}
+
+#[test]
+fn test_gen_config() {
+    let config = GenConfig::default();
+    assert_eq!(config.validate(), Ok(()));
+    let text = ron::to_string(&config).unwrap();
+    assert_eq!(GenConfig::parse(&text), Ok(config));
+    // 缺少的字段使用默认值
+    assert_eq!(
+        GenConfig::parse("(ridge_scale: 2.0)").unwrap().ridge_scale,
+        2.0
+    );
+    // 写了一半的文件不会被使用
+    assert!(GenConfig::parse("(ridge_scale: 2.").is_err());
+    assert!(GenConfig::parse("(sea_level: 100.0)").is_err());
+    assert!(GenConfig::parse("(world_height: (min_chunk_y: 2, max_chunk_y: 1))").is_err());
+    assert!(GenConfig::parse("(world_height: (max_chunk_y: 100))").is_err());
+    assert!(GenConfig::parse("(world_height: (min_chunk_y: -2, max_chunk_y: 4))").is_ok());
+    // 方块的范围正好覆盖范围内的区块
+    let height = WorldHeight {
+        min_chunk_y: 0,
+        max_chunk_y: 1,
+    };
+    assert_eq!(height.min_block_y(), -CHUNK_SIZE / 2);
+    assert_eq!(height.max_block_y(), CHUNK_SIZE * 3 / 2 - 1);
+    assert!(GenConfig::parse("(biome_thresholds: (0.5, 0.4, 0.6, 0.8))").is_err());
+    assert_eq!(
+        GenConfig::parse("(flat: Some((water: true)))")
+            .unwrap()
+            .flat,
+        Some(FlatWorld {
+            water: true,
+            ..Default::default()
+        })
+    );
+    assert!(GenConfig::parse("(flat: Some((height: -200.0)))").is_err());
+    // 默认的群落大小和原来的频率一致
+    assert_eq!(config.biome_frequency(), 0.008);
+    assert!(GenConfig::parse("(biome_size: 0.0)").is_err());
+    // 原来的频率字段给出提示 不会被忽略
+    assert!(GenConfig::parse("(biome_frequency: 0.02)")
+        .unwrap_err()
+        .contains("biome_size"));
+    assert_eq!(
+        GenConfig::parse("(biome_size: 1000.0)")
+            .unwrap()
+            .biome_frequency(),
+        0.001
+    );
+}
@@ -0,0 +1,298 @@
+// 方块修改的日志
+// 自动保存之间的修改先追加到日志文件 服务端崩溃后重新启动时从日志恢复
+// 启动时创建标记文件 正常关闭时把修改写入区块的存档 清空日志并删除标记文件
+// 启动时标记文件还在 说明上次没有正常关闭 重放日志中的修改
+// 每条记录追加后同步到磁盘 自动保存成功后去掉已经写入存档的部分 日志不会一直增长
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+use bevy::{
+    app::AppExit,
+    prelude::{EventReader, ResMut},
+    utils::HashMap,
+};
+
+use super::{
+    chunk::ChunkKey,
+    map_database::{DbSaveTasks, MapDataBase},
+    voxel::Voxel,
+    world::gen_world_chunk,
+};
+
+// 日志中的一条记录 同一个区块的一组修改
+pub type JournalEntry = (ChunkKey, Vec<(usize, Voxel)>);
+
+pub struct EditJournal {
+    path: PathBuf,
+    lock_path: PathBuf,
+    file: File,
+    // 已经写入的字节数
+    written: u64,
+}
+
+impl EditJournal {
+    // 日志和标记文件放在世界存档的旁边
+    pub fn paths(world_path: &str) -> (PathBuf, PathBuf) {
+        (
+            PathBuf::from(format!("{}.journal", world_path)),
+            PathBuf::from(format!("{}.lock", world_path)),
+        )
+    }
+
+    /**
+     * 打开日志 上次没有正常关闭时先把日志中的修改写入 db
+     * 返回日志和恢复的修改数量
+     */
+    pub fn recover(world_path: &str, db: &mut MapDataBase) -> std::io::Result<(Self, usize)> {
+        let (journal_path, lock_path) = Self::paths(world_path);
+        let mut recovered = 0;
+        // 正常关闭后日志是空的
+        if lock_path.exists() {
+            let entries = read_journal(&journal_path)?;
+            recovered = replay_journal(db, &entries);
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&journal_path)?;
+        // 恢复的修改已经写入存档 不需要再保留
+        file.set_len(0)?;
+        File::create(&lock_path)?;
+        Ok((
+            Self {
+                path: journal_path,
+                lock_path,
+                file,
+                written: 0,
+            },
+            recovered,
+        ))
+    }
+
+    // 长度 + 数据 最后一条写了一半时读取会忽略
+    pub fn append(&mut self, chunk_key: ChunkKey, edits: &[(usize, Voxel)]) {
+        let data = bincode::serialize(&(chunk_key, edits)).unwrap();
+        let mut record = (data.len() as u32).to_le_bytes().to_vec();
+        record.extend_from_slice(&data);
+        let write = self
+            .file
+            .write_all(&record)
+            .and_then(|_| self.file.sync_data());
+        match write {
+            Ok(_) => self.written += record.len() as u64,
+            Err(err) => println!("写入修改日志失败{:?}", err),
+        }
+    }
+
+    // 当前写到的位置 自动保存开始时记录 保存成功后去掉这之前的记录
+    pub fn position(&self) -> u64 {
+        self.written
+    }
+
+    // 去掉 upto 之前已经写入存档的记录 之后追加的记录保留
+    pub fn compact(&mut self, upto: u64) -> std::io::Result<()> {
+        let mut data = Vec::new();
+        File::open(&self.path)?.read_to_end(&mut data)?;
+        let rest = data.get(upto as usize..).unwrap_or_default().to_vec();
+        self.file.set_len(0)?;
+        self.file.write_all(&rest)?;
+        self.file.sync_data()?;
+        self.written = rest.len() as u64;
+        Ok(())
+    }
+
+    // 修改已经写入存档 清空日志并删除标记文件
+    pub fn close(&mut self) -> std::io::Result<()> {
+        self.file.set_len(0)?;
+        self.written = 0;
+        if self.lock_path.exists() {
+            std::fs::remove_file(&self.lock_path)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn read_journal(path: &Path) -> std::io::Result<Vec<JournalEntry>> {
+    let mut data = Vec::new();
+    match File::open(path) {
+        Ok(mut file) => {
+            file.read_to_end(&mut data)?;
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    }
+    let mut entries = Vec::new();
+    let mut offset = 0;
+    while offset + 4 <= data.len() {
+        let len = u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap()) as usize;
+        let Some(record) = data.get(offset + 4..offset + 4 + len) else {
+            break;
+        };
+        let Ok(entry) = bincode::deserialize::<JournalEntry>(record) else {
+            break;
+        };
+        entries.push(entry);
+        offset += 4 + len;
+    }
+    Ok(entries)
+}
+
+/**
+ * 把日志中的修改按顺序写入区块的存档
+ * 没有保存过的区块使用种子重新生成 返回恢复的修改数量
+ */
+pub fn replay_journal(db: &mut MapDataBase, entries: &[JournalEntry]) -> usize {
+    let mut chunks: HashMap<ChunkKey, Vec<Voxel>> = HashMap::new();
+    // 同一个区块的修改合并之后只记录一次
+    let mut chunk_edits: HashMap<ChunkKey, Vec<(usize, Voxel)>> = HashMap::new();
+    let mut count = 0;
+    for (chunk_key, edits) in entries.iter() {
+        let voxels = chunks
+            .entry(*chunk_key)
+            .or_insert_with(|| match db.load_saved(*chunk_key) {
+                Ok(Some(voxels)) => voxels,
+                _ => gen_world_chunk(db.seed, *chunk_key).0,
+            });
+        for (index, voxel) in edits.iter() {
+            voxels[*index] = *voxel;
+        }
+        chunk_edits
+            .entry(*chunk_key)
+            .or_default()
+            .extend_from_slice(edits);
+        count += edits.len();
+    }
+    for (chunk_key, edits) in chunk_edits.iter() {
+        db.record_edits(*chunk_key, edits);
+    }
+    for (chunk_key, voxels) in chunks.iter() {
+        let key = chunk_key.as_u8_array();
+        if let Err(err) = db.db.insert(key, bincode::serialize(voxels).unwrap()) {
+            println!("恢复区块失败{:?}", err);
+        }
+    }
+    if let Err(err) = db.db.flush() {
+        println!("恢复区块失败{:?}", err);
+    }
+    count
+}
+
+/**
+ * 正常关闭 等待还没有完成的保存 刷新到磁盘后关闭日志
+ * 保存失败时保留日志 下次启动时恢复
+ */
+pub fn close_journal_system(
+    mut exit: EventReader<AppExit>,
+    mut db: ResMut<MapDataBase>,
+    mut db_save_tasks: ResMut<DbSaveTasks>,
+) {
+    if exit.iter().last().is_none() {
+        return;
+    }
+    let mut failed = false;
+    for task in db_save_tasks.tasks.drain(..) {
+        let (key, voxels) = futures_lite::future::block_on(task);
+        if let Err(err) = db.db.insert(key, bincode::serialize(&voxels).unwrap()) {
+            println!("关闭时保存区块失败{:?}", err);
+            failed = true;
+        }
+    }
+    if let Err(err) = db.db.flush() {
+        println!("关闭时保存区块失败{:?}", err);
+        failed = true;
+    }
+    if failed {
+        return;
+    }
+    if let Some(journal) = db.journal.as_mut() {
+        match journal.close() {
+            Ok(_) => println!("修改日志已经写入存档"),
+            Err(err) => println!("关闭修改日志失败{:?}", err),
+        }
+    }
+}
+
+#[test]
+fn test_journal_recover() {
+    use crate::{
+        voxel_world::voxel::{Stone, VoxelMaterial},
+        CHUNK_VOLUME,
+    };
+    use bevy::prelude::IVec3;
+
+    let world_path = std::env::temp_dir()
+        .join(format!("just_join_journal_{}", std::process::id()))
+        .to_string_lossy()
+        .to_string();
+    let open_db = || MapDataBase {
+        db: sled::Config::new().temporary(true).open().unwrap(),
+        seed: 0,
+        journal: None,
+    };
+    let chunk_key = ChunkKey(IVec3::new(1, 0, 1));
+    let mut db = open_db();
+    db.db
+        .insert(
+            chunk_key.as_u8_array(),
+            bincode::serialize(&vec![Voxel::EMPTY; CHUNK_VOLUME as usize]).unwrap(),
+        )
+        .unwrap();
+
+    // 修改写入日志后崩溃 标记文件还在
+    let (mut journal, recovered) = EditJournal::recover(&world_path, &mut db).unwrap();
+    assert_eq!(recovered, 0);
+    journal.append(chunk_key, &[(3, Stone::into_voxel())]);
+    journal.append(chunk_key, &[(3, Voxel::EMPTY), (7, Stone::into_voxel())]);
+    drop(journal);
+
+    let (mut journal, recovered) = EditJournal::recover(&world_path, &mut db).unwrap();
+    assert_eq!(recovered, 3);
+    let voxels = db.load_saved(chunk_key).unwrap().unwrap();
+    assert_eq!((voxels[3], voxels[7]), (Voxel::EMPTY, Stone::into_voxel()));
+    assert_eq!(db.get_edits(chunk_key).len(), 2);
+
+    // 正常关闭后不再重放
+    journal.append(chunk_key, &[(9, Stone::into_voxel())]);
+    journal.close().unwrap();
+    let mut db = open_db();
+    let (mut journal, recovered) = EditJournal::recover(&world_path, &mut db).unwrap();
+    assert_eq!(recovered, 0);
+    journal.close().unwrap();
+}
+
+#[test]
+fn test_journal_compact() {
+    use crate::voxel_world::voxel::{Stone, VoxelMaterial};
+    use bevy::prelude::IVec3;
+
+    let world_path = std::env::temp_dir()
+        .join(format!("just_join_journal_compact_{}", std::process::id()))
+        .to_string_lossy()
+        .to_string();
+    let mut db = MapDataBase {
+        db: sled::Config::new().temporary(true).open().unwrap(),
+        seed: 0,
+        journal: None,
+    };
+    let chunk_key = ChunkKey(IVec3::new(1, 0, 1));
+    let (mut journal, _) = EditJournal::recover(&world_path, &mut db).unwrap();
+    journal.append(chunk_key, &[(3, Stone::into_voxel())]);
+    journal.append(chunk_key, &[(4, Stone::into_voxel())]);
+    // 自动保存开始后的修改不在快照中 压缩后仍然保留
+    let mark = journal.position();
+    journal.append(chunk_key, &[(5, Voxel::EMPTY)]);
+    journal.compact(mark).unwrap();
+    let (journal_path, _) = EditJournal::paths(&world_path);
+    assert_eq!(
+        read_journal(&journal_path).unwrap(),
+        vec![(chunk_key, vec![(5, Voxel::EMPTY)])]
+    );
+    // 压缩后继续追加
+    journal.append(chunk_key, &[(6, Voxel::EMPTY)]);
+    assert_eq!(read_journal(&journal_path).unwrap().len(), 2);
+    journal.close().unwrap();
+}
@@ -0,0 +1,251 @@
+// 光照传播
+// 方块光: 光源的亮度在 VoxelRegistry 中配置
+// 天空光: 露天的列从顶部一直照到第一个实心体素 然后向旁边(洞口 屋檐下)传播
+// 光照只在非实心的体素中传播 每传播一格减少 1
+// 按照网格生成使用的整列数据计算 结果在生成网格时写入顶点数据
+
+use std::collections::VecDeque;
+
+use bevy::prelude::IVec3;
+use ndshape::ConstShape;
+
+use super::{chunk::ChunkKey, voxel::Voxel, voxel_registry::VOXEL_REGISTRY};
+use crate::{ChunkColumnShape, CHUNK_SIZE, CHUNK_SIZE_ADD_2_U32, WORLD_HEIGHT};
+
+// 最大的光照等级 顶点数据中占 4 位
+pub const MAX_LIGHT: u8 = 15;
+
+const NEIGHBORS: [IVec3; 6] = [
+    IVec3::NEG_X,
+    IVec3::NEG_Y,
+    IVec3::NEG_Z,
+    IVec3::X,
+    IVec3::Y,
+    IVec3::Z,
+];
+
+/**
+ * 列外部的光源
+ * pos 使用列坐标 在列的范围之外
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LightSeed {
+    pub pos: IVec3,
+    pub level: u8,
+}
+
+fn column_max() -> IVec3 {
+    IVec3::new(
+        CHUNK_SIZE_ADD_2_U32 as i32,
+        WORLD_HEIGHT as i32,
+        CHUNK_SIZE_ADD_2_U32 as i32,
+    ) - IVec3::ONE
+}
+
+fn column_index(pos: IVec3) -> usize {
+    ChunkColumnShape::linearize([pos.x as u32, pos.y as u32, pos.z as u32]) as usize
+}
+
+// 光可以进入的体素
+fn transparent(voxel: Voxel) -> bool {
+    !VOXEL_REGISTRY.properties(voxel.id).is_solid
+}
+
+/**
+ * 整列数据的光照 每个体素一个字节 高 4 位是天空光 低 4 位是方块光
+ */
+pub fn column_light(voxels: &[Voxel], seeds: &[LightSeed]) -> Vec<u8> {
+    let block = column_block_light(voxels, seeds);
+    let sky = column_sky_light(voxels);
+    sky.iter()
+        .zip(block)
+        .map(|(s, b)| pack_light(*s, b))
+        .collect()
+}
+
+pub fn pack_light(sky: u8, block: u8) -> u8 {
+    sky << 4 | block
+}
+
+// 返回 (天空光, 方块光)
+pub fn unpack_light(light: u8) -> (u8, u8) {
+    (light >> 4, light & 0xF)
+}
+
+// 天空光和方块光分别取最大值
+pub fn max_light(a: u8, b: u8) -> u8 {
+    let (a_sky, a_block) = unpack_light(a);
+    let (b_sky, b_block) = unpack_light(b);
+    pack_light(a_sky.max(b_sky), a_block.max(b_block))
+}
+
+/**
+ * 计算整列数据中每个体素的天空光照
+ * 只使用列中的数据 相邻区块更远处漏进来的天空光不计算
+ */
+pub fn column_sky_light(voxels: &[Voxel]) -> Vec<u8> {
+    let mut light = vec![0u8; voxels.len()];
+    let mut queue = VecDeque::new();
+    let max = column_max();
+    for x in 0..=max.x {
+        for z in 0..=max.z {
+            for y in (0..=max.y).rev() {
+                let pos = IVec3::new(x, y, z);
+                let index = column_index(pos);
+                if !transparent(voxels[index]) {
+                    break;
+                }
+                light[index] = MAX_LIGHT;
+                queue.push_back(pos);
+            }
+        }
+    }
+    propagate(voxels, &mut light, queue);
+    light
+}
+
+/**
+ * 计算整列数据中每个体素的方块光照
+ * 光源自己保存自己的亮度 其他实心体素为 0
+ * 外部的光源从列中最近的体素进入 不考虑列外面的遮挡
+ */
+pub fn column_block_light(voxels: &[Voxel], seeds: &[LightSeed]) -> Vec<u8> {
+    let mut light = vec![0u8; voxels.len()];
+    let mut queue = VecDeque::new();
+    for (index, voxel) in voxels.iter().enumerate() {
+        let level = VOXEL_REGISTRY.light(voxel.id);
+        if level > 0 {
+            light[index] = level;
+            let [x, y, z] = ChunkColumnShape::delinearize(index as u32);
+            queue.push_back(IVec3::new(x as i32, y as i32, z as i32));
+        }
+    }
+    for seed in seeds {
+        let entry = seed.pos.clamp(IVec3::ZERO, column_max());
+        let distance = (seed.pos - entry).abs();
+        let distance = distance.x + distance.y + distance.z;
+        if distance >= seed.level as i32 {
+            continue;
+        }
+        let level = seed.level - distance as u8;
+        let index = column_index(entry);
+        if transparent(voxels[index]) && light[index] < level {
+            light[index] = level;
+            queue.push_back(entry);
+        }
+    }
+    propagate(voxels, &mut light, queue);
+    light
+}
+
+// 广度优先 每个体素只会被更亮的光覆盖
+fn propagate(voxels: &[Voxel], light: &mut [u8], mut queue: VecDeque<IVec3>) {
+    let max = column_max();
+    while let Some(pos) = queue.pop_front() {
+        let level = light[column_index(pos)];
+        if level <= 1 {
+            continue;
+        }
+        for offset in NEIGHBORS {
+            let next = pos + offset;
+            if next.cmplt(IVec3::ZERO).any() || next.cmpgt(max).any() {
+                continue;
+            }
+            let index = column_index(next);
+            if transparent(voxels[index]) && light[index] < level - 1 {
+                light[index] = level - 1;
+                queue.push_back(next);
+            }
+        }
+    }
+}
+
+/**
+ * 光源变化时 光照可能照到的相邻区块列(包括对角)
+ * pos 是区块内的坐标 level 是变化前后光源亮度的最大值
+ */
+pub fn light_affected_columns(chunk_key: ChunkKey, pos: [u32; 3], level: u8) -> Vec<ChunkKey> {
+    let reach = |v: u32| {
+        let v = v as i32;
+        let level = level as i32;
+        let min = if v < level { -1 } else { 0 };
+        let max = if v + level >= CHUNK_SIZE { 1 } else { 0 };
+        min..=max
+    };
+    let mut keys = Vec::new();
+    for dx in reach(pos[0]) {
+        for dz in reach(pos[2]) {
+            if dx == 0 && dz == 0 {
+                continue;
+            }
+            keys.push(ChunkKey(IVec3::new(
+                chunk_key.0.x + dx,
+                0,
+                chunk_key.0.z + dz,
+            )));
+        }
+    }
+    keys
+}
+
+#[test]
+fn test_column_block_light() {
+    use super::voxel::{Lamp, Stone, VoxelMaterial};
+    let mut voxels = vec![Voxel::EMPTY; ChunkColumnShape::SIZE as usize];
+    let lamp = IVec3::new(5, 100, 5);
+    voxels[column_index(lamp)] = Lamp::into_voxel();
+    // 光源右边两格是一堵墙
+    for y in 0..WORLD_HEIGHT as i32 {
+        for z in 0..CHUNK_SIZE_ADD_2_U32 as i32 {
+            voxels[column_index(IVec3::new(7, y, z))] = Stone::into_voxel();
+        }
+    }
+    let light = column_block_light(&voxels, &[]);
+    let level = VOXEL_REGISTRY.light(Lamp::ID);
+    assert_eq!(light[column_index(lamp)], level);
+    assert_eq!(light[column_index(lamp + IVec3::Y * 3)], level - 3);
+    assert_eq!(light[column_index(lamp + IVec3::new(-2, 1, 0))], level - 3);
+    assert_eq!(light[column_index(lamp + IVec3::X * 2)], 0);
+    assert_eq!(light[column_index(lamp + IVec3::X * 3)], 0);
+
+    // 外部光源从最近的体素进入
+    let voxels = vec![Voxel::EMPTY; ChunkColumnShape::SIZE as usize];
+    let seed = LightSeed {
+        pos: IVec3::new(-3, 50, 4),
+        level,
+    };
+    let light = column_block_light(&voxels, &[seed]);
+    assert_eq!(light[column_index(IVec3::new(0, 50, 4))], level - 3);
+    assert_eq!(light[column_index(IVec3::new(1, 50, 4))], level - 4);
+}
+
+#[test]
+fn test_column_sky_light() {
+    use super::voxel::{Stone, VoxelMaterial};
+    let mut voxels = vec![Voxel::EMPTY; ChunkColumnShape::SIZE as usize];
+    // x < 10 的部分有一个屋顶 y = 50 是一个封闭的洞穴
+    for x in 0..10 {
+        for z in 0..CHUNK_SIZE_ADD_2_U32 as i32 {
+            voxels[column_index(IVec3::new(x, 200, z))] = Stone::into_voxel();
+        }
+    }
+    for x in 0..CHUNK_SIZE_ADD_2_U32 as i32 {
+        for z in 0..CHUNK_SIZE_ADD_2_U32 as i32 {
+            voxels[column_index(IVec3::new(x, 49, z))] = Stone::into_voxel();
+            voxels[column_index(IVec3::new(x, 51, z))] = Stone::into_voxel();
+        }
+    }
+    let light = column_sky_light(&voxels);
+    assert_eq!(light[column_index(IVec3::new(12, 201, 3))], MAX_LIGHT);
+    assert_eq!(light[column_index(IVec3::new(12, 100, 3))], MAX_LIGHT);
+    assert_eq!(light[column_index(IVec3::new(9, 199, 3))], MAX_LIGHT - 1);
+    assert_eq!(light[column_index(IVec3::new(5, 199, 3))], MAX_LIGHT - 5);
+    assert_eq!(light[column_index(IVec3::new(5, 50, 3))], 0);
+
+    let packed = pack_light(MAX_LIGHT, 3);
+    assert_eq!(unpack_light(packed), (MAX_LIGHT, 3));
+    assert_eq!(
+        max_light(packed, pack_light(2, 9)),
+        pack_light(MAX_LIGHT, 9)
+    );
+}
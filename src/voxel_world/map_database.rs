@@ -3,23 +3,54 @@
 use bevy::{
     prelude::{ResMut, Resource},
     tasks::{AsyncComputeTaskPool, Task},
+    utils::HashMap,
 };
 use ndshape::{ConstShape, ConstShape3u32};
 use sled::Db;
 
-use crate::{voxel_world::map_generator::gen_chunk_data_by_seed, CHUNK_SIZE_U32, CLIENT_MAP_GEN};
+use crate::{voxel_world::world::gen_world_chunk, CHUNK_SIZE_U32, CLIENT_MAP_GEN};
 
-use super::{biomes::OtherTreeTasksMap, chunk::ChunkKey, voxel::Voxel};
+use super::{
+    biomes::{OtherTreeTasksMap, TreeGentor},
+    chunk::ChunkKey,
+    journal::EditJournal,
+    voxel::Voxel,
+};
+
+// 世界种子存储的key
+const SEED_KEY: &str = "SEED";
+
+// 当前世界的种子
+#[derive(Debug, Clone, Copy, Resource)]
+pub struct WorldSeed(pub i32);
 
 #[derive(Resource)]
 pub struct MapDataBase {
     pub db: Db,
+    pub seed: i32,
+    // 崩溃恢复的修改日志 没有开启时为 None
+    pub journal: Option<EditJournal>,
 }
 
 impl MapDataBase {
-    pub fn new(path: &str) -> Self {
+    // seed 只在创建新世界时使用
+    pub fn new(path: &str, seed: i32) -> Self {
         let db = sled::open(path).unwrap();
-        Self { db }
+        // 种子跟随世界保存 保证重启后地形一致
+        let seed = match db.get(SEED_KEY) {
+            Ok(Some(data)) => bincode::deserialize(&data).unwrap(),
+            _ => {
+                if let Err(err) = db.insert(SEED_KEY, bincode::serialize(&seed).unwrap()) {
+                    println!("保存种子失败{:?}", err);
+                }
+                seed
+            }
+        };
+        Self {
+            db,
+            seed,
+            journal: None,
+        }
     }
 
     // 通过chunkKey 查找体素数据
@@ -29,40 +60,140 @@ impl MapDataBase {
         db_tasks: &mut DbSaveTasks,
         other_tree_tasks_map: &mut OtherTreeTasksMap,
     ) -> Vec<Voxel> {
-        let pool = AsyncComputeTaskPool::get();
-        let mut voxels = Vec::new();
-        type SampleShape = ConstShape3u32<CHUNK_SIZE_U32, CHUNK_SIZE_U32, CHUNK_SIZE_U32>;
-        for _ in 0..SampleShape::SIZE {
-            voxels.push(Voxel::EMPTY);
-        }
-        let key = chunk_key.as_u8_array();
-        match self.db.get(key) {
-            Ok(rs) => match if CLIENT_MAP_GEN { None } else { rs } {
-                Some(data) => bincode::deserialize(&data).unwrap(),
-                // 这里在没有获取到的情况下使用算法的值
-                None => {
-                    let (new_voxels, other_trees) = gen_chunk_data_by_seed(1512354854, chunk_key);
-                    let new_voxels_clone = new_voxels.clone();
-                    let task = pool.spawn(async move { (key, new_voxels_clone) });
-                    db_tasks.tasks.push(task);
-                    other_tree_tasks_map.insert(other_trees);
-                    new_voxels
-                }
-            },
+        match self.load_saved(chunk_key) {
+            Ok(Some(voxels)) => voxels,
+            // 这里在没有获取到的情况下使用算法的值
+            Ok(None) => {
+                let (new_voxels, other_trees) = gen_world_chunk(self.seed, chunk_key);
+                self.save_generated(
+                    chunk_key,
+                    &new_voxels,
+                    other_trees,
+                    db_tasks,
+                    other_tree_tasks_map,
+                );
+                new_voxels
+            }
             Err(e) => {
                 println!("wrong, to get Map {:?}", e);
+                let mut voxels = Vec::new();
+                type SampleShape = ConstShape3u32<CHUNK_SIZE_U32, CHUNK_SIZE_U32, CHUNK_SIZE_U32>;
+                for _ in 0..SampleShape::SIZE {
+                    voxels.push(Voxel::EMPTY);
+                }
                 voxels
             }
         }
     }
+
+    // 读取保存过的区块 没有保存过时返回 None 需要生成
+    pub fn load_saved(&self, chunk_key: ChunkKey) -> sled::Result<Option<Vec<Voxel>>> {
+        if CLIENT_MAP_GEN {
+            return Ok(None);
+        }
+        Ok(self
+            .db
+            .get(chunk_key.as_u8_array())?
+            .map(|data| bincode::deserialize(&data).unwrap()))
+    }
+
+    // 新生成的区块 异步保存 跨区块的树交给 OtherTreeTasksMap
+    pub fn save_generated(
+        &self,
+        chunk_key: ChunkKey,
+        voxels: &[Voxel],
+        other_trees: Vec<(Vec<ChunkKey>, TreeGentor)>,
+        db_tasks: &mut DbSaveTasks,
+        other_tree_tasks_map: &mut OtherTreeTasksMap,
+    ) {
+        let pool = AsyncComputeTaskPool::get();
+        let key = chunk_key.as_u8_array();
+        let voxels = voxels.to_vec();
+        let task = pool.spawn(async move { (key, voxels) });
+        db_tasks.tasks.push(task);
+        other_tree_tasks_map.insert(other_trees);
+    }
+
+    /**
+     * 使用生成线程池重新生成的区块覆盖保存的数据
+     * keep_edits 为 true 时保留玩家修改过的方块
+     */
+    pub fn regenerate_chunk(
+        &mut self,
+        chunk_key: ChunkKey,
+        mut new_voxels: Vec<Voxel>,
+        other_trees: Vec<(Vec<ChunkKey>, TreeGentor)>,
+        keep_edits: bool,
+        db_tasks: &mut DbSaveTasks,
+        other_tree_tasks_map: &mut OtherTreeTasksMap,
+    ) -> Vec<Voxel> {
+        if keep_edits {
+            for (index, voxel) in self.get_edits(chunk_key) {
+                new_voxels[index] = voxel;
+            }
+        } else {
+            self.clear_edits(chunk_key);
+        }
+        self.save_generated(
+            chunk_key,
+            &new_voxels,
+            other_trees,
+            db_tasks,
+            other_tree_tasks_map,
+        );
+        new_voxels
+    }
+
+    // 记录玩家对方块的修改 同一个位置只保留最后一次
+    pub fn record_edit(&mut self, chunk_key: ChunkKey, index: usize, voxel: Voxel) {
+        self.record_edits(chunk_key, &[(index, voxel)]);
+    }
+
+    /**
+     * 一次记录同一个区块的多个修改 填充区域时使用
+     * 按照下标合并 后面的修改覆盖前面的 调用方每次操作每个区块只调用一次 只写入一次
+     */
+    pub fn record_edits(&mut self, chunk_key: ChunkKey, new_edits: &[(usize, Voxel)]) {
+        let mut merged: HashMap<usize, Voxel> = self.get_edits(chunk_key).into_iter().collect();
+        merged.extend(new_edits.iter().copied());
+        let mut edits: Vec<(usize, Voxel)> = merged.into_iter().collect();
+        edits.sort_unstable_by_key(|(index, _)| *index);
+        if let Some(journal) = self.journal.as_mut() {
+            journal.append(chunk_key, new_edits);
+        }
+        let key = format!("EDIT:{:?}", chunk_key);
+        if let Err(err) = self.db.insert(key, bincode::serialize(&edits).unwrap()) {
+            println!("保存修改记录失败{:?}", err);
+        }
+    }
+
+    pub fn get_edits(&self, chunk_key: ChunkKey) -> Vec<(usize, Voxel)> {
+        let key = format!("EDIT:{:?}", chunk_key);
+        match self.db.get(key) {
+            Ok(Some(data)) => bincode::deserialize(&data).unwrap_or_default(),
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn clear_edits(&mut self, chunk_key: ChunkKey) {
+        let key = format!("EDIT:{:?}", chunk_key);
+        if let Err(err) = self.db.remove(key) {
+            println!("删除修改记录失败{:?}", err);
+        }
+    }
 }
 
-#[derive(Debug, Resource)]
+#[derive(Debug, Default, Resource)]
 pub struct DbSaveTasks {
     pub tasks: Vec<Task<([u8; 8], Vec<Voxel>)>>,
+    // 自动保存在后台写入快照时暂停 避免快照中旧的数据覆盖之后的修改
+    pub paused: bool,
 }
 
 pub fn save_db_task_system(mut db_save_task: ResMut<DbSaveTasks>, db: ResMut<MapDataBase>) {
+    if db_save_task.paused {
+        return;
+    }
     // 一次最多处理6个
     let len = db_save_task.tasks.len().min(6);
     for ele in db_save_task.tasks.drain(..len) {
@@ -80,3 +211,39 @@ pub fn save_db_task_system(mut db_save_task: ResMut<DbSaveTasks>, db: ResMut<Map
         }
     }
 }
+
+#[test]
+fn test_record_edits() {
+    use crate::voxel_world::voxel::{Stone, VoxelMaterial};
+    use bevy::prelude::IVec3;
+
+    let mut db = MapDataBase {
+        db: sled::Config::new().temporary(true).open().unwrap(),
+        seed: 0,
+        journal: None,
+    };
+    let chunk_key = ChunkKey(IVec3::new(0, 1, 0));
+    db.record_edits(
+        chunk_key,
+        &[(7, Stone::into_voxel()), (3, Stone::into_voxel())],
+    );
+    // 同一个下标只保留最后一次 包括同一次记录中的重复下标
+    db.record_edits(
+        chunk_key,
+        &[
+            (7, Voxel::EMPTY),
+            (5, Stone::into_voxel()),
+            (5, Voxel::EMPTY),
+        ],
+    );
+    assert_eq!(
+        db.get_edits(chunk_key),
+        vec![
+            (3, Stone::into_voxel()),
+            (5, Voxel::EMPTY),
+            (7, Voxel::EMPTY)
+        ]
+    );
+    db.clear_edits(chunk_key);
+    assert!(db.get_edits(chunk_key).is_empty());
+}
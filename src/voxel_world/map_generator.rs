@@ -1,4 +1,5 @@
-use ndshape::{ConstShape, ConstShape2u32, ConstShape3u32};
+use bevy::prelude::IVec3;
+use ndshape::ConstShape;
 #[cfg(target_arch = "aarch64")]
 use noise::utils::NoiseMapBuilder;
 #[cfg(target_arch = "x86_64")]
@@ -6,8 +7,13 @@ use simdnoise::NoiseBuilder;
 
 use crate::{
     voxel_world::{
-        biomes::biomes_generate,
-        voxel::{BasicStone, Grass, Sand, Soli, Sown, Stone, VoxelMaterial, Water},
+        biomes::{biomes_generate, column_index, BiomeHeightSampler, PanelShape, SampleShape},
+        gen_config::{gen_config, FlatWorld, GenConfig},
+        structure::make_structures_for_chunk,
+        voxel::{
+            AppleLeaf, AppleWood, BasicStone, BuleGrass, DryGrass, Grass, Sand, Soli, Sown, Stone,
+            VoxelMaterial, Water,
+        },
     },
     CHUNK_SIZE, CHUNK_SIZE_U32,
 };
@@ -21,41 +27,42 @@ pub fn gen_chunk_data_by_seed(
     // let base_x = (chunk_key.0.x * CHUNK_SIZE) as f32;
     let base_y: f32 = (chunk_key.0.y * CHUNK_SIZE) as f32;
     // let base_z = (chunk_key.0.z * CHUNK_SIZE) as f32;
-    type SampleShape = ConstShape3u32<CHUNK_SIZE_U32, CHUNK_SIZE_U32, CHUNK_SIZE_U32>;
-    type PanelShape = ConstShape2u32<CHUNK_SIZE_U32, CHUNK_SIZE_U32>;
     let mut voxels = Vec::new();
+    let config = gen_config();
 
-    let noise = noise2d(chunk_key, seed);
-    let noise2 = noise2d_ridge(chunk_key, seed);
+    // 平坦世界不经过下面的海平面填充 水只由 flat.water 决定
+    if let Some(flat) = config.flat {
+        return (gen_flat_chunk(chunk_key, &config, flat), Vec::new());
+    }
+
+    let tops = terrain_tops(chunk_key, seed, &config);
 
     // 表面 索引
     let mut suface_index: Vec<u32> = Vec::new();
 
     for i in 0..SampleShape::SIZE {
-        let [x, y, z] = SampleShape::delinearize(i);
+        let [_, y, _] = SampleShape::delinearize(i);
         let p_y = base_y + y as f32;
-        let h = -60.;
-        let index = PanelShape::linearize([x, z]);
-        let top = h + fn_height(noise[index as usize]) + noise2[index as usize] * 5.0;
+        let top = tops[column_index(i) as usize];
         if p_y <= top {
             // 必须大于海平面
-            if p_y + 1.0 > top && p_y - 1.0 < top && p_y >= -60. + 76. {
+            if p_y + 1.0 > top && p_y - 1.0 < top && p_y >= config.sea_level {
                 suface_index.push(i);
             }
-            if p_y >= -60. + 110. {
+            if p_y >= config.snow_level {
                 voxels.push(Sown::into_voxel());
                 continue;
             }
-            if p_y <= -110. {
+            if p_y <= config.bedrock_level {
                 voxels.push(BasicStone::into_voxel());
                 continue;
             }
-            if p_y >= -60. + 100. {
+            if p_y >= config.mountain_level {
                 voxels.push(Stone::into_voxel());
                 continue;
             }
             if p_y >= top - 1.0 {
-                if p_y < -60. + 76. {
+                if p_y < config.sea_level {
                     voxels.push(Soli::into_voxel());
                 } else {
                     voxels.push(Grass::into_voxel());
@@ -74,7 +81,7 @@ pub fn gen_chunk_data_by_seed(
     for i in 0..SampleShape::SIZE {
         let [_, y, _] = SampleShape::delinearize(i);
         let p_y: f32 = base_y + y as f32;
-        if p_y <= -60. + 76. && voxels[i as usize].id == Voxel::EMPTY.id {
+        if p_y <= config.sea_level && voxels[i as usize].id == Voxel::EMPTY.id {
             water_flag = true;
             voxels[i as usize] = Water::into_voxel();
         }
@@ -82,7 +89,7 @@ pub fn gen_chunk_data_by_seed(
 
     // 处理不同群落
     let others: Vec<(Vec<ChunkKey>, crate::voxel_world::biomes::TreeGentor)> =
-        biomes_generate(chunk_key, seed, suface_index, &mut voxels);
+        biomes_generate(chunk_key, seed, suface_index, &mut voxels, &config);
 
     //生成 沙子
     if water_flag {
@@ -102,6 +109,9 @@ pub fn gen_chunk_data_by_seed(
         }
     }
 
+    // 结构
+    make_structures_for_chunk(chunk_key, seed, &mut voxels);
+
     //侵蚀 洞穴
     // let noise_3d = noise3d_2(chunk_key, seed);
     // for i in 0..SampleShape::SIZE {
@@ -120,14 +130,121 @@ pub fn gen_chunk_data_by_seed(
     (voxels, others)
 }
 
+/**
+ * 平坦世界中高度 p_y 的体素
+ * 地表是草 在水下时是沙子 下面依次是泥土 石头 基岩
+ */
+pub fn flat_voxel(p_y: f32, config: &GenConfig, flat: FlatWorld) -> Voxel {
+    let underwater = flat.water && flat.height < config.sea_level;
+    if p_y <= config.bedrock_level {
+        BasicStone::into_voxel()
+    } else if p_y <= flat.height {
+        if p_y > flat.height - 1.0 {
+            if underwater {
+                Sand::into_voxel()
+            } else {
+                Grass::into_voxel()
+            }
+        } else if p_y > flat.height - 5.0 {
+            Soli::into_voxel()
+        } else {
+            Stone::into_voxel()
+        }
+    } else if flat.water && p_y <= config.sea_level {
+        Water::into_voxel()
+    } else {
+        Voxel::EMPTY
+    }
+}
+
+// 平坦的区块 没有树
+pub fn gen_flat_chunk(chunk_key: ChunkKey, config: &GenConfig, flat: FlatWorld) -> Vec<Voxel> {
+    let base_y: f32 = (chunk_key.0.y * CHUNK_SIZE) as f32;
+    (0..SampleShape::SIZE)
+        .map(|i| {
+            let [_, y, _] = SampleShape::delinearize(i);
+            flat_voxel(base_y + y as f32, config, flat)
+        })
+        .collect()
+}
+
+/**
+ * 区块平面上每一列的地形高度 按照 PanelShape 排列
+ * 区块内 y 的高度 p_y = chunk_key.y * CHUNK_SIZE + y 小于等于这个值的是实心的
+ */
+pub fn terrain_tops(chunk_key: ChunkKey, seed: i32, config: &GenConfig) -> Vec<f32> {
+    if let Some(flat) = config.flat {
+        return vec![flat.height; PanelShape::SIZE as usize];
+    }
+    let noise = noise2d(chunk_key, seed);
+    let noise2 = noise2d_ridge(chunk_key, seed);
+    // 群落决定的高度
+    let sampler = BiomeHeightSampler::with_config(seed, config.clone());
+    (0..PanelShape::SIZE)
+        .map(|index| {
+            let [x, z] = PanelShape::delinearize(index);
+            let biome_height = sampler.blended_height_at(
+                (chunk_key.0.x * CHUNK_SIZE + x as i32) as f64,
+                (chunk_key.0.z * CHUNK_SIZE + z as i32) as f64,
+            );
+            let h = config.base_height;
+            h + fn_height(noise[index as usize])
+                + noise2[index as usize] * config.ridge_scale
+                + biome_height
+        })
+        .collect()
+}
+
+/**
+ * 方块坐标所在的列 返回区块(y 为 0)和平面下标
+ * 区块 chunk_key 的方块范围是 chunk_key * CHUNK_SIZE - CHUNK_SIZE / 2 开始的 CHUNK_SIZE 个
+ */
+pub fn column_of(world_x: i32, world_z: i32) -> (ChunkKey, u32) {
+    let half = CHUNK_SIZE / 2;
+    let chunk_key = ChunkKey(IVec3::new(
+        (world_x + half).div_euclid(CHUNK_SIZE),
+        0,
+        (world_z + half).div_euclid(CHUNK_SIZE),
+    ));
+    let x = (world_x + half).rem_euclid(CHUNK_SIZE) as u32;
+    let z = (world_z + half).rem_euclid(CHUNK_SIZE) as u32;
+    (chunk_key, PanelShape::linearize([x, z]))
+}
+
+/**
+ * 地表最高的地形方块的 y(方块坐标 不包含树)
+ * 只计算一列的高度 不需要生成整个区块 和 gen_chunk_data_by_seed 的结果一致
+ */
+pub fn surface_height_at(world_x: i32, world_z: i32, seed: i32) -> i32 {
+    let (chunk_key, index) = column_of(world_x, world_z);
+    let top = terrain_tops(chunk_key, seed, &gen_config())[index as usize];
+    // p_y = 方块坐标 + CHUNK_SIZE / 2
+    top.floor() as i32 - CHUNK_SIZE / 2
+}
+
+// 地形生成中会使用到的体素 启动时校验是否注册
+pub const GENERATED_VOXELS: [u8; 11] = [
+    Stone::ID,
+    Soli::ID,
+    Grass::ID,
+    Sown::ID,
+    Water::ID,
+    Sand::ID,
+    BasicStone::ID,
+    DryGrass::ID,
+    BuleGrass::ID,
+    AppleWood::ID,
+    AppleLeaf::ID,
+];
+
 pub fn check_water(voxels: Vec<Voxel>, point: [u32; 3]) -> bool {
-    type SampleShape = ConstShape3u32<CHUNK_SIZE_U32, CHUNK_SIZE_U32, CHUNK_SIZE_U32>;
-    let index = SampleShape::linearize(point);
+    // 先检查范围 超出范围的坐标 linearize 会得到其他位置的下标
     if point[0] >= CHUNK_SIZE_U32 || point[1] >= CHUNK_SIZE_U32 || point[2] >= CHUNK_SIZE_U32 {
         return false;
     }
+    let index = SampleShape::linearize(point);
 
-    voxels[index as usize].id == Water::ID
+    voxels[index as usize].is_liquid()
 }
 
 #[cfg(target_arch = "aarch64")]
@@ -284,3 +401,72 @@ pub fn fn_height(x: f32) -> f32 {
     }
     0.
 }
+
+#[test]
+fn test_surface_height_matches_chunk() {
+    let seed = 1512354854;
+    for (world_x, world_z) in [(0, 0), (-9, 7), (8, -8), (37, 120), (-200, -45)] {
+        let h = surface_height_at(world_x, world_z, seed);
+        let (column_key, index) = column_of(world_x, world_z);
+        let [x, z] = PanelShape::delinearize(index);
+        // 从地表所在的区块向上检查整列
+        let chunk_y = (h + CHUNK_SIZE / 2).div_euclid(CHUNK_SIZE);
+        for key_y in chunk_y..=gen_config().world_height.max_chunk_y {
+            let chunk_key = ChunkKey(IVec3::new(column_key.0.x, key_y, column_key.0.z));
+            let (voxels, _) = gen_chunk_data_by_seed(seed, chunk_key);
+            for y in 0..CHUNK_SIZE_U32 {
+                let world_y = key_y * CHUNK_SIZE - CHUNK_SIZE / 2 + y as i32;
+                let voxel = voxels[SampleShape::linearize([x, y, z]) as usize];
+                if world_y == h {
+                    assert!(voxel.id != Voxel::EMPTY.id && !voxel.is_liquid());
+                } else if world_y > h {
+                    // 地表之上只有空气 水 和树
+                    assert!(
+                        voxel.id == Voxel::EMPTY.id
+                            || voxel.is_liquid()
+                            || voxel.id == AppleWood::ID
+                            || voxel.id == AppleLeaf::ID
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_flat_voxel() {
+    let config = GenConfig::default();
+    let ocean = FlatWorld {
+        height: config.sea_level - 4.0,
+        water: true,
+    };
+    let dry = FlatWorld {
+        water: false,
+        ..ocean
+    };
+    // 地面以上到海平面是水 只填充一次 海平面以上是空气
+    assert_eq!(flat_voxel(ocean.height, &config, ocean), Sand::into_voxel());
+    assert_eq!(
+        flat_voxel(ocean.height + 1.0, &config, ocean),
+        Water::into_voxel()
+    );
+    assert_eq!(
+        flat_voxel(config.sea_level, &config, ocean),
+        Water::into_voxel()
+    );
+    assert_eq!(
+        flat_voxel(config.sea_level + 1.0, &config, ocean),
+        Voxel::EMPTY
+    );
+    // 干燥的平地没有水
+    assert_eq!(flat_voxel(dry.height, &config, dry), Grass::into_voxel());
+    assert_eq!(flat_voxel(dry.height + 1.0, &config, dry), Voxel::EMPTY);
+    assert_eq!(
+        flat_voxel(dry.height - 2.0, &config, dry),
+        Soli::into_voxel()
+    );
+    assert_eq!(
+        flat_voxel(config.bedrock_level, &config, dry),
+        BasicStone::into_voxel()
+    );
+}
@@ -2,8 +2,16 @@ pub mod biomes;
 pub mod chunk;
 pub mod chunk_map;
 pub mod compress;
+pub mod gen_config;
+pub mod journal;
+pub mod light;
 pub mod map_database;
 pub mod map_generator;
 pub mod player_state;
+pub mod region;
+pub mod spawn;
+pub mod structure;
 pub mod voxel;
-pub mod voxel_mesh;
\ No newline at end of file
+pub mod voxel_mesh;
+pub mod voxel_registry;
+pub mod world;
@@ -31,6 +31,39 @@ impl PlayerState {
         None
     }
 
+    /**
+     * 放入一个工具 工具不能堆叠 放在第一个空位 数量表示剩余的耐久
+     * 成功时返回新的 toolbar 数据
+     */
+    pub fn put_tool(
+        &mut self,
+        id: usize,
+        durability: usize,
+    ) -> Option<(usize, Option<usize>, usize)> {
+        let index = self.toolbar.iter().position(|slot| slot.0.is_none())?;
+        self.toolbar[index] = (Some(id), durability);
+        Some((index, Some(id), durability))
+    }
+
+    /**
+     * 使用一次工具 耐久减一 到零时工具损坏 位置被清空
+     * 返回新的 toolbar 数据 位置上不是这个工具时返回 None
+     */
+    pub fn wear_tool(&mut self, index: usize, id: usize) -> Option<(usize, Option<usize>, usize)> {
+        self.use_staff(index, id, 1)
+    }
+
+    // 取出这个位置的全部物品 返回数量
+    pub fn take_staff(&mut self, index: usize, id: usize) -> Option<usize> {
+        match self.toolbar.get(index).copied() {
+            Some((Some(old_id), num)) if old_id == id => {
+                self.toolbar[index] = (None, 0);
+                Some(num)
+            }
+            _ => None,
+        }
+    }
+
     // 尝试放置 如果成功返回 新的放置后的 toolbar的数据 失败返回None
     pub fn put_staff(&mut self, id: usize) -> Option<(usize, Option<usize>, usize)> {
         for i in 0..10 {
@@ -49,6 +82,11 @@ impl PlayerState {
     }
 }
 
+// 玩家数据在数据库中的 key
+pub fn player_state_key(username: &str) -> String {
+    format!("U:{}", username)
+}
+
 pub trait StoragePlayerState {
     fn save_player_state(
         &mut self,
@@ -64,7 +102,7 @@ impl StoragePlayerState for MapDataBase {
         username: String,
         player_state: PlayerState,
     ) -> Option<PlayerState> {
-        let key_str = format!("U:{}", username);
+        let key_str = player_state_key(&username);
         let key = key_str.as_bytes();
         match self
             .db
@@ -78,7 +116,7 @@ impl StoragePlayerState for MapDataBase {
         }
     }
     fn get_player_state(&self, username: String) -> Option<PlayerState> {
-        let key_str = format!("U:{}", username);
+        let key_str = player_state_key(&username);
         let key = key_str.as_bytes();
         match self.db.get(key) {
             Ok(rs) => rs.map(|data| bincode::deserialize(&data).unwrap()),
@@ -92,3 +130,27 @@ impl StoragePlayerState for MapDataBase {
 
 #[derive(Debug, Component, Clone)]
 pub struct PlayerOnTimeState(pub PlayerState);
+
+#[test]
+fn test_tool_durability() {
+    let mut state = PlayerState::default();
+    state.toolbar[0] = (Some(0), 5);
+    // 工具放在空位 不和其他物品堆叠
+    assert_eq!(state.put_tool(15, 2), Some((1, Some(15), 2)));
+    assert_eq!(state.put_tool(15, 3), Some((2, Some(15), 3)));
+    // 不是这个工具时不使用
+    assert_eq!(state.wear_tool(0, 15), None);
+    assert_eq!(state.wear_tool(1, 15), Some((1, Some(15), 1)));
+    // 耐久用完时损坏
+    assert_eq!(state.wear_tool(1, 15), Some((1, None, 0)));
+    assert_eq!(state.toolbar[1], (None, 0));
+    assert_eq!(state.wear_tool(1, 15), None);
+    assert_eq!(state.take_staff(2, 15), Some(3));
+    assert_eq!(state.toolbar[2], (None, 0));
+
+    // 没有空位时放不下
+    for index in 1..10 {
+        state.toolbar[index] = (Some(0), 1);
+    }
+    assert_eq!(state.put_tool(15, 3), None);
+}
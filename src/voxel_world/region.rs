@@ -0,0 +1,98 @@
+// 在内存中生成一片区域 不需要 Bevy 渲染和网络
+// 和服务端的流程一致: 生成区块 然后把跨区块的树补到相邻的区块里
+// 用来测试跨区块的生成特性(树 结构 洞穴)在边界上是否连续
+
+use bevy::{prelude::IVec3, utils::HashMap};
+
+use super::{
+    biomes::TreeGentor,
+    chunk::ChunkKey,
+    gen_config::{COLUMN_MAX_CHUNK_Y, COLUMN_MIN_CHUNK_Y},
+    map_generator::gen_chunk_data_by_seed,
+    voxel::Voxel,
+};
+
+// 区块列的 y 范围 和服务端一致
+pub const REGION_MIN_Y: i32 = COLUMN_MIN_CHUNK_Y;
+pub const REGION_MAX_Y: i32 = COLUMN_MAX_CHUNK_Y;
+
+/**
+ * 生成 center 周围 radius 个区块内的所有区块列(整列的高度)
+ * 外面多生成一圈 保证从区域外面伸进来的树也被补上 返回时去掉
+ * 结果只和参数有关 多次调用得到相同的数据
+ */
+pub fn generate_region(center: ChunkKey, radius: u32, seed: i32) -> HashMap<ChunkKey, Vec<Voxel>> {
+    let radius = radius as i32;
+    let (mut chunks, trees) = generate_columns(center, radius + 1, seed);
+    apply_trees(&mut chunks, &trees);
+    chunks.retain(|key, _| {
+        (key.0.x - center.0.x).abs() <= radius && (key.0.z - center.0.z).abs() <= radius
+    });
+    chunks
+}
+
+// 生成区块 返回区块数据和需要补到其他区块的树
+fn generate_columns(
+    center: ChunkKey,
+    radius: i32,
+    seed: i32,
+) -> (
+    HashMap<ChunkKey, Vec<Voxel>>,
+    Vec<(Vec<ChunkKey>, TreeGentor)>,
+) {
+    let mut chunks = HashMap::new();
+    let mut trees = Vec::new();
+    for x in -radius..=radius {
+        for z in -radius..=radius {
+            for y in REGION_MIN_Y..=REGION_MAX_Y {
+                let key = ChunkKey(IVec3::new(center.0.x + x, y, center.0.z + z));
+                let (voxels, others) = gen_chunk_data_by_seed(seed, key);
+                chunks.insert(key, voxels);
+                trees.extend(others);
+            }
+        }
+    }
+    (chunks, trees)
+}
+
+// 和 deal_other_tree 一样 把树补到已经生成的区块里 不在区域内的跳过
+fn apply_trees(chunks: &mut HashMap<ChunkKey, Vec<Voxel>>, trees: &[(Vec<ChunkKey>, TreeGentor)]) {
+    for (keys, tree) in trees {
+        for key in keys {
+            if let Some(voxels) = chunks.get_mut(key) {
+                tree.clone().make_tree_for_chunk(voxels, *key);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_generate_region() {
+    let center = ChunkKey(IVec3::new(2, 0, -3));
+    let seed = 1512354854;
+    let region = generate_region(center, 1, seed);
+    assert_eq!(region.len(), 9 * (REGION_MAX_Y - REGION_MIN_Y + 1) as usize);
+    assert!(region
+        .values()
+        .all(|voxels| voxels.len() == crate::CHUNK_VOLUME as usize));
+    // 相同的参数得到相同的结果
+    assert_eq!(generate_region(center, 1, seed), region);
+
+    // 伸到相邻区块的树干在相邻区块里也存在 树叶可能被其他的树覆盖 只检查树干
+    let (_, trees) = generate_columns(center, 2, seed);
+    for (keys, tree) in trees.iter() {
+        for key in keys.iter().filter(|key| region.contains_key(key)) {
+            let mut trunk = vec![Voxel::EMPTY; crate::CHUNK_VOLUME as usize];
+            tree.clone().make_tree_for_chunk(&mut trunk, *key);
+            for (index, voxel) in trunk.iter().enumerate() {
+                if voxel.id == tree.tree.id {
+                    assert_eq!(
+                        region[key][index].id, tree.tree.id,
+                        "区块{:?}的树不连续",
+                        key
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,398 @@
+// 出生点
+// 从原点附近开始 按照螺旋向外查找 地表在海平面之上 脚下是实心方块 头顶有空气的位置
+// 另外按照 SpawnRules 避开水边 陡坡和指定的群落 都找不到时忽略这些规则
+
+use bevy::{
+    prelude::{IVec3, Resource, Vec3},
+    utils::HashMap,
+};
+use ndshape::ConstShape;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    biomes::{see_level, BiomeHeightSampler, BiomeKind, SampleShape},
+    chunk::ChunkKey,
+    map_generator::column_of,
+    voxel::Voxel,
+    world::{gen_world_chunk, world_terrain_tops, WorldId},
+};
+use crate::{CHUNK_SIZE, SPAWN_HEIGHT_OFFSET};
+
+// 优先在这个范围内查找(方块)
+pub const SPAWN_SEARCH_RADIUS: i32 = 64;
+// 超出后继续向外查找的最大范围(方块)
+pub const SPAWN_MAX_RADIUS: i32 = 1024;
+// 查找的间隔(方块)
+pub const SPAWN_SEARCH_STEP: i32 = 4;
+// 头顶需要空出来的高度(方块)
+pub const SPAWN_HEADROOM: i32 = 2;
+// 周围 3x3 范围内的高度差上限的默认值(方块)
+pub const SPAWN_MAX_HEIGHT_VARIANCE: u32 = 2;
+// 查找群落的间隔(方块) 群落比较大 不需要逐格查找
+pub const LOCATE_BIOME_STEP: i32 = 16;
+// 查找群落的最大范围(方块)
+pub const LOCATE_BIOME_MAX_RADIUS: i32 = 4096;
+
+#[derive(Debug, Clone, Copy, Resource)]
+pub struct SpawnPoint(pub Vec3);
+
+/**
+ * 自动查找出生点的规则 见 ServerConfig::spawn_rules
+ * 没有岩浆 炎热的群落(Dry Sand)可以加到 avoid_biomes 中避开
+ */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpawnRules {
+    // 周围 3x3 的列都不能在海平面之下 不出生在水边
+    pub avoid_water: bool,
+    // 周围 3x3 范围内的高度差上限 越小越平坦
+    pub max_height_variance: u32,
+    // 不在这些群落出生
+    pub avoid_biomes: Vec<BiomeKind>,
+}
+
+impl Default for SpawnRules {
+    fn default() -> Self {
+        Self {
+            avoid_water: true,
+            max_height_variance: SPAWN_MAX_HEIGHT_VARIANCE,
+            avoid_biomes: Vec::new(),
+        }
+    }
+}
+
+impl SpawnRules {
+    // 只要求脚下安全 找不到满足规则的位置时使用
+    pub fn relaxed() -> Self {
+        Self {
+            avoid_water: false,
+            max_height_variance: u32::MAX,
+            avoid_biomes: Vec::new(),
+        }
+    }
+
+    /**
+     * 这一列是否可以出生
+     * neighbours 是周围的 8 列
+     */
+    pub fn allows(&self, center: &SpawnColumn, neighbours: &[SpawnColumn]) -> bool {
+        if !center.safe || self.avoid_biomes.contains(&center.biome) {
+            return false;
+        }
+        if self.avoid_water && neighbours.iter().any(|column| column.wet) {
+            return false;
+        }
+        let heights = neighbours.iter().map(|column| column.height);
+        let min = heights.clone().fold(center.height, i32::min);
+        let max = heights.fold(center.height, i32::max);
+        (max - min) as u32 <= self.max_height_variance
+    }
+}
+
+// 查找出生点时一列的信息
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpawnColumn {
+    // 地表方块的 y
+    pub height: i32,
+    // 地表在海平面之下
+    pub wet: bool,
+    // 地表是实心的方块 头顶是空气
+    pub safe: bool,
+    pub biome: BiomeKind,
+}
+
+// 查找过程中缓存生成的数据
+struct SpawnSearch {
+    seed: i32,
+    sampler: BiomeHeightSampler,
+    tops: HashMap<ChunkKey, Vec<f32>>,
+    chunks: HashMap<ChunkKey, Vec<Voxel>>,
+}
+
+impl SpawnSearch {
+    fn new(seed: i32) -> Self {
+        Self {
+            seed,
+            sampler: BiomeHeightSampler::new(seed),
+            tops: HashMap::new(),
+            chunks: HashMap::new(),
+        }
+    }
+
+    fn surface_height(&mut self, world_x: i32, world_z: i32) -> i32 {
+        let (chunk_key, index) = column_of(world_x, world_z);
+        let seed = self.seed;
+        let tops = self
+            .tops
+            .entry(chunk_key)
+            .or_insert_with(|| world_terrain_tops(seed, chunk_key));
+        tops[index as usize].floor() as i32 - CHUNK_SIZE / 2
+    }
+
+    fn voxel_at(&mut self, pos: IVec3) -> Voxel {
+        let half = IVec3::splat(CHUNK_SIZE / 2);
+        let chunk_key = ChunkKey((pos + half).div_euclid(IVec3::splat(CHUNK_SIZE)));
+        let local = (pos + half).rem_euclid(IVec3::splat(CHUNK_SIZE));
+        let seed = self.seed;
+        let voxels = self
+            .chunks
+            .entry(chunk_key)
+            .or_insert_with(|| gen_world_chunk(seed, chunk_key).0);
+        voxels[SampleShape::linearize([local.x as u32, local.y as u32, local.z as u32]) as usize]
+    }
+
+    fn column(&mut self, world_x: i32, world_z: i32) -> SpawnColumn {
+        let h = self.surface_height(world_x, world_z);
+        // 在海平面之下会站在水里
+        let wet = h + CHUNK_SIZE / 2 <= see_level() as i32;
+        let safe = !wet
+            && is_safe_ground(
+                self.voxel_at(IVec3::new(world_x, h, world_z)),
+                (1..=SPAWN_HEADROOM).map(|dy| self.voxel_at(IVec3::new(world_x, h + dy, world_z))),
+            );
+        SpawnColumn {
+            height: h,
+            wet,
+            safe,
+            biome: self.sampler.biome_at(world_x as f32, world_z as f32),
+        }
+    }
+
+    // 可以出生的位置 返回脚下方块的 y
+    fn check(&mut self, world_x: i32, world_z: i32) -> Option<i32> {
+        let column = self.column(world_x, world_z);
+        column.safe.then_some(column.height)
+    }
+}
+
+/**
+ * 脚下是实心的方块 头顶是空气
+ */
+pub fn is_safe_ground(ground: Voxel, mut above: impl Iterator<Item = Voxel>) -> bool {
+    ground.is_solid() && !ground.is_liquid() && above.all(|voxel| voxel.id == Voxel::EMPTY.id)
+}
+
+// 以原点为中心的第 ring 圈
+fn ring_positions(ring: i32) -> Vec<(i32, i32)> {
+    if ring == 0 {
+        return vec![(0, 0)];
+    }
+    let mut ret = Vec::new();
+    for i in -ring..ring {
+        ret.push((i, -ring));
+        ret.push((ring, i));
+        ret.push((-i, ring));
+        ret.push((-ring, -i));
+    }
+    ret
+}
+
+/**
+ * 按照螺旋向外查找满足规则的列 返回 (x, 地表的 y, z)
+ * 先在 SPAWN_SEARCH_RADIUS 内查找 找不到时继续向外 直到 SPAWN_MAX_RADIUS
+ */
+pub fn search_spawn(
+    rules: &SpawnRules,
+    mut column: impl FnMut(i32, i32) -> SpawnColumn,
+) -> Option<(i32, i32, i32)> {
+    let max_ring = SPAWN_MAX_RADIUS / SPAWN_SEARCH_STEP;
+    for ring in 0..=max_ring {
+        if ring * SPAWN_SEARCH_STEP == SPAWN_SEARCH_RADIUS + SPAWN_SEARCH_STEP {
+            println!(
+                "原点附近{}格内没有找到出生点 继续向外查找",
+                SPAWN_SEARCH_RADIUS
+            );
+        }
+        for (x, z) in ring_positions(ring) {
+            let (world_x, world_z) = (x * SPAWN_SEARCH_STEP, z * SPAWN_SEARCH_STEP);
+            let center = column(world_x, world_z);
+            if !center.safe {
+                continue;
+            }
+            let neighbours: Vec<SpawnColumn> = ring_positions(1)
+                .into_iter()
+                .map(|(dx, dz)| column(world_x + dx, world_z + dz))
+                .collect();
+            if rules.allows(&center, &neighbours) {
+                return Some((world_x, center.height, world_z));
+            }
+        }
+    }
+    None
+}
+
+/**
+ * 查找安全的出生点 返回角色的位置
+ * 没有满足规则的位置时只要求脚下安全 都找不到时 使用原点的地表
+ */
+pub fn find_safe_spawn(seed: i32, rules: &SpawnRules) -> Vec3 {
+    let mut search = SpawnSearch::new(seed);
+    if let Some((x, h, z)) = search_spawn(rules, |x, z| search.column(x, z)) {
+        return spawn_translation(x, h, z);
+    }
+    println!("没有满足规则的出生点 忽略出生规则");
+    if let Some((x, h, z)) = search_spawn(&SpawnRules::relaxed(), |x, z| search.column(x, z)) {
+        return spawn_translation(x, h, z);
+    }
+    println!("没有找到安全的出生点 使用原点");
+    let h = search.surface_height(0, 0);
+    spawn_translation(0, h, 0)
+}
+
+/**
+ * 从 (origin_x, origin_z) 开始按照螺旋向外 在粗略的网格上查找群落
+ * 只采样群落的噪声 不生成区块 超过 max_radius 返回 None
+ */
+pub fn locate_biome(
+    seed: i32,
+    origin_x: i32,
+    origin_z: i32,
+    biome: BiomeKind,
+    max_radius: i32,
+) -> Option<(i32, i32)> {
+    let sampler = BiomeHeightSampler::new(seed);
+    let max_ring = max_radius.clamp(0, LOCATE_BIOME_MAX_RADIUS) / LOCATE_BIOME_STEP;
+    for ring in 0..=max_ring {
+        for (x, z) in ring_positions(ring) {
+            let (world_x, world_z) = (
+                origin_x + x * LOCATE_BIOME_STEP,
+                origin_z + z * LOCATE_BIOME_STEP,
+            );
+            if sampler.biome_at(world_x as f32, world_z as f32) == biome {
+                return Some((world_x, world_z));
+            }
+        }
+    }
+    None
+}
+
+/**
+ * 传送到这一列时角色的位置
+ * 可以站立时站在地表上 否则(水里 树上)放到地表的上方
+ */
+pub fn standing_position(seed: i32, world_x: i32, world_z: i32) -> Vec3 {
+    let mut search = SpawnSearch::new(seed);
+    match search.check(world_x, world_z) {
+        Some(h) => spawn_translation(world_x, h, world_z),
+        None => {
+            let h = search
+                .surface_height(world_x, world_z)
+                .max(see_level() as i32 - CHUNK_SIZE / 2);
+            spawn_translation(world_x, h + SPAWN_HEADROOM, world_z)
+        }
+    }
+}
+
+/**
+ * 世界的出生点 主世界使用 spawn_point
+ * 其他世界站在世界原点的地表上
+ */
+pub fn world_spawn(world: WorldId, spawn_point: Vec3, seed: i32) -> Vec3 {
+    if world == WorldId::MAIN {
+        return spawn_point;
+    }
+    standing_position(seed, world.origin_x() as i32, 0)
+}
+
+// 站在方块的中心
+fn spawn_translation(world_x: i32, h: i32, world_z: i32) -> Vec3 {
+    Vec3::new(
+        world_x as f32 + 0.5,
+        h as f32 + 1.0 + SPAWN_HEIGHT_OFFSET,
+        world_z as f32 + 0.5,
+    )
+}
+
+#[test]
+fn test_find_safe_spawn() {
+    let seed = 1512354854;
+    let spawn = find_safe_spawn(seed, &SpawnRules::default());
+    let ground = IVec3::new(
+        spawn.x.floor() as i32,
+        (spawn.y - 1.0 - SPAWN_HEIGHT_OFFSET).floor() as i32,
+        spawn.z.floor() as i32,
+    );
+    // 重新生成区块检查
+    let voxel_at = |pos: IVec3| {
+        let half = IVec3::splat(CHUNK_SIZE / 2);
+        let chunk_key = ChunkKey((pos + half).div_euclid(IVec3::splat(CHUNK_SIZE)));
+        let local = (pos + half).rem_euclid(IVec3::splat(CHUNK_SIZE));
+        let (voxels, _) = gen_world_chunk(seed, chunk_key);
+        voxels[SampleShape::linearize([local.x as u32, local.y as u32, local.z as u32]) as usize]
+    };
+    assert!(ground.y + CHUNK_SIZE / 2 > see_level() as i32);
+    let ground_voxel = voxel_at(ground);
+    assert!(ground_voxel.is_solid() && !ground_voxel.is_liquid());
+    for dy in 1..=SPAWN_HEADROOM {
+        assert_eq!(voxel_at(ground + IVec3::new(0, dy, 0)).id, Voxel::EMPTY.id);
+    }
+}
+
+#[test]
+fn test_locate_biome() {
+    let seed = 1512354854;
+    let sampler = BiomeHeightSampler::new(seed);
+    let here = sampler.biome_at(100.0, -50.0);
+    // 当前位置就是要找的群落
+    assert_eq!(
+        locate_biome(seed, 100, -50, here, LOCATE_BIOME_MAX_RADIUS),
+        Some((100, -50))
+    );
+    // 找到的位置一定是要找的群落 范围为 0 时只检查起点
+    for biome in [BiomeKind::Basic, BiomeKind::Dry, BiomeKind::Sand] {
+        if let Some((x, z)) = locate_biome(seed, 100, -50, biome, LOCATE_BIOME_MAX_RADIUS) {
+            assert_eq!(sampler.biome_at(x as f32, z as f32), biome);
+        }
+        if biome != here {
+            assert_eq!(locate_biome(seed, 100, -50, biome, 0), None);
+        }
+    }
+}
+
+#[test]
+fn test_spawn_rules() {
+    // 原点附近是一片海 x >= 24 是陆地 其中 x < 40 是陡坡 z < 0 是沙漠
+    let sea = see_level() as i32 - CHUNK_SIZE / 2;
+    let column = |x: i32, z: i32| {
+        let height = if x < 24 {
+            sea - 4
+        } else if x < 40 {
+            sea + (x - 24) * 2
+        } else {
+            sea + 32
+        };
+        SpawnColumn {
+            height,
+            wet: height <= sea,
+            safe: height > sea,
+            biome: if z < 0 {
+                BiomeKind::Sand
+            } else {
+                BiomeKind::Basic
+            },
+        }
+    };
+    let check = |rules: &SpawnRules| {
+        let (x, h, z) = search_spawn(rules, column).unwrap();
+        assert_eq!(column(x, z).height, h);
+        let heights: Vec<i32> = ring_positions(1)
+            .into_iter()
+            .map(|(dx, dz)| column(x + dx, z + dz).height)
+            .collect();
+        (x, z, heights)
+    };
+
+    // 干燥并且平坦
+    let rules = SpawnRules {
+        avoid_biomes: vec![BiomeKind::Sand],
+        ..Default::default()
+    };
+    let (x, z, heights) = check(&rules);
+    assert!(x >= 40 && z >= 0);
+    assert!(heights.iter().all(|h| *h > sea));
+    assert!(heights.iter().all(|h| (h - column(x, z).height).abs() <= 2));
+
+    // 忽略规则时停在海边的坡上
+    let (x, _, _) = check(&SpawnRules::relaxed());
+    assert!((24..40).contains(&x));
+}
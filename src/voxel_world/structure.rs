@@ -0,0 +1,198 @@
+// 结构生成(地牢 村庄等)
+// 世界按照 STRUCTURE_REGION x STRUCTURE_REGION 个区块划分成区域 每个区域最多一个结构
+// 结构完全位于自己的区域中 区域本身就是占用网格 所以结构之间不会互相覆盖
+// 结构的位置只由 区域坐标 和 种子 决定 每个区块生成时各自写入自己范围内的部分
+
+use bevy::prelude::{IVec2, IVec3};
+use ndshape::ConstShape;
+
+use super::{
+    biomes::SampleShape,
+    chunk::ChunkKey,
+    voxel::{AppleWood, Stone, Voxel, VoxelMaterial},
+};
+use crate::CHUNK_SIZE;
+
+// 区域的大小(区块个数)
+pub const STRUCTURE_REGION: i32 = 8;
+// 平均多少个区域出现一个结构
+pub const STRUCTURE_RARITY: u64 = 3;
+
+// 稳定的哈希 不依赖标准库的哈希实现 保证不同版本下的结果一致
+pub fn hash_with_seed(values: &[i32], seed: i32) -> u64 {
+    let mut h = seed as u32 as u64 ^ 0x9E37_79B9_7F4A_7C15;
+    for v in values {
+        h ^= *v as u32 as u64;
+        // splitmix64
+        h = h.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        h = (h ^ (h >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        h = (h ^ (h >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        h ^= h >> 31;
+    }
+    h
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StructureKind {
+    // 地下的小房间
+    Dungeon,
+}
+
+impl StructureKind {
+    pub fn size(&self) -> IVec3 {
+        match self {
+            StructureKind::Dungeon => IVec3::new(12, 6, 12),
+        }
+    }
+
+    // 模板 None 表示不修改原来的体素
+    pub fn voxel_at(&self, local: IVec3) -> Option<Voxel> {
+        match self {
+            StructureKind::Dungeon => {
+                let size = self.size();
+                if local.y == 0 || local.y == size.y - 1 {
+                    return Some(Stone::into_voxel());
+                }
+                if local.x == 0 || local.x == size.x - 1 || local.z == 0 || local.z == size.z - 1 {
+                    return Some(AppleWood::into_voxel());
+                }
+                Some(Voxel::EMPTY)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Structure {
+    pub kind: StructureKind,
+    // 世界坐标下的最小角(方块坐标)
+    pub origin: IVec3,
+}
+
+impl Structure {
+    pub fn contains(&self, pos: IVec3) -> bool {
+        let local = pos - self.origin;
+        let size = self.kind.size();
+        local.cmpge(IVec3::ZERO).all() && local.cmplt(size).all()
+    }
+}
+
+// 区块所在的区域
+pub fn region_of(chunk_key: ChunkKey) -> IVec2 {
+    IVec2::new(
+        chunk_key.0.x.div_euclid(STRUCTURE_REGION),
+        chunk_key.0.z.div_euclid(STRUCTURE_REGION),
+    )
+}
+
+/**
+ * 计算区域中的结构
+ */
+pub fn structure_in_region(region: IVec2, seed: i32) -> Option<Structure> {
+    let h = hash_with_seed(&[region.x, region.y], seed);
+    if h % STRUCTURE_RARITY != 0 {
+        return None;
+    }
+    let kind = StructureKind::Dungeon;
+    // 结构最多跨越两个区块 起点不放在区域最后一个区块上 保证结构在区域内部
+    let chunk_x = region.x * STRUCTURE_REGION + ((h >> 8) % (STRUCTURE_REGION as u64 - 1)) as i32;
+    let chunk_z = region.y * STRUCTURE_REGION + ((h >> 16) % (STRUCTURE_REGION as u64 - 1)) as i32;
+    let offset_x = ((h >> 24) % CHUNK_SIZE as u64) as i32;
+    let offset_z = ((h >> 32) % CHUNK_SIZE as u64) as i32;
+    // 地下的高度
+    let y = -50 + ((h >> 40) % 10) as i32;
+    Some(Structure {
+        kind,
+        origin: IVec3::new(
+            chunk_x * CHUNK_SIZE - CHUNK_SIZE / 2 + offset_x,
+            y,
+            chunk_z * CHUNK_SIZE - CHUNK_SIZE / 2 + offset_z,
+        ),
+    })
+}
+
+/**
+ * 把区域中的结构写入当前区块
+ */
+pub fn make_structures_for_chunk(chunk_key: ChunkKey, seed: i32, voxels: &mut Vec<Voxel>) {
+    let Some(structure) = structure_in_region(region_of(chunk_key), seed) else {
+        return;
+    };
+    let chunk_min = chunk_key.0 * CHUNK_SIZE - IVec3::splat(CHUNK_SIZE / 2);
+    let structure_max = structure.origin + structure.kind.size();
+    let chunk_max = chunk_min + IVec3::splat(CHUNK_SIZE);
+    // 没有交集
+    if structure_max.cmple(chunk_min).any() || structure.origin.cmpge(chunk_max).any() {
+        return;
+    }
+    for index in 0..SampleShape::SIZE {
+        let [x, y, z] = SampleShape::delinearize(index);
+        let pos = chunk_min + IVec3::new(x as i32, y as i32, z as i32);
+        if !structure.contains(pos) {
+            continue;
+        }
+        if let Some(voxel) = structure.kind.voxel_at(pos - structure.origin) {
+            voxels[index as usize] = voxel;
+        }
+    }
+}
+
+#[test]
+fn test_structure_deterministic() {
+    use crate::CHUNK_VOLUME;
+
+    let seed = 1234;
+    let regions: Vec<IVec2> = (-4..4)
+        .flat_map(|x| (-4..4).map(move |z| IVec2::new(x, z)))
+        .collect();
+    let placed: Vec<_> = regions
+        .iter()
+        .map(|region| structure_in_region(*region, seed))
+        .collect();
+    // 相同的种子结果一致
+    for (region, structure) in regions.iter().zip(placed.iter()) {
+        assert_eq!(structure_in_region(*region, seed), *structure);
+    }
+    assert!(placed.iter().any(|structure| structure.is_some()));
+    // 不同的种子位置不同
+    let other: Vec<_> = regions
+        .iter()
+        .map(|region| structure_in_region(*region, seed + 1))
+        .collect();
+    assert_ne!(placed, other);
+
+    // 写入区块的体素也一致
+    let structure = placed.iter().flatten().next().unwrap();
+    let chunk_key = ChunkKey(IVec3::new(
+        (structure.origin.x + CHUNK_SIZE / 2).div_euclid(CHUNK_SIZE),
+        (structure.origin.y + CHUNK_SIZE / 2).div_euclid(CHUNK_SIZE),
+        (structure.origin.z + CHUNK_SIZE / 2).div_euclid(CHUNK_SIZE),
+    ));
+    let mut first = vec![Voxel::FILLED; CHUNK_VOLUME as usize];
+    let mut second = first.clone();
+    make_structures_for_chunk(chunk_key, seed, &mut first);
+    make_structures_for_chunk(chunk_key, seed, &mut second);
+    assert_eq!(first, second);
+    assert!(first.iter().any(|voxel| *voxel != Voxel::FILLED));
+}
+
+#[test]
+fn test_structure_no_overlap() {
+    for seed in [0, 7, 99] {
+        for x in -6..6 {
+            for z in -6..6 {
+                let region = IVec2::new(x, z);
+                let Some(structure) = structure_in_region(region, seed) else {
+                    continue;
+                };
+                // 结构完全在自己的区域内 相邻区域的结构不会重叠
+                let region_min = IVec3::new(region.x, 0, region.y) * STRUCTURE_REGION * CHUNK_SIZE
+                    - IVec3::splat(CHUNK_SIZE / 2);
+                let region_max = region_min + IVec3::splat(STRUCTURE_REGION * CHUNK_SIZE);
+                let structure_max = structure.origin + structure.kind.size();
+                assert!(structure.origin.x >= region_min.x && structure.origin.z >= region_min.z);
+                assert!(structure_max.x <= region_max.x && structure_max.z <= region_max.z);
+            }
+        }
+    }
+}
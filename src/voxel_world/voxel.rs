@@ -4,7 +4,7 @@ use bevy::{prelude::Quat, reflect::Reflect};
 use block_mesh::{MergeVoxel, Voxel as MeshVoxel, VoxelVisibility};
 use serde::{Deserialize, Serialize};
 
-use super::voxel_mesh::VOXEL_MESH_MAP;
+use super::{voxel_mesh::VOXEL_MESH_MAP, voxel_registry::VOXEL_REGISTRY};
 
 /**
  * 体素类型
@@ -122,14 +122,70 @@ impl Voxel {
     }
 }
 
+/**
+ * 体素的物理属性
+ * 生成 网格 碰撞 都通过这里判断方块是否是实心的
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VoxelProperties {
+    // 是否阻挡玩家
+    pub is_solid: bool,
+    // 是否是液体 液体不阻挡但是会减慢移动
+    pub is_liquid: bool,
+    // 是否可以攀爬 比如梯子藤蔓
+    pub is_climbable: bool,
+}
+
+impl VoxelProperties {
+    pub const AIR: Self = Self {
+        is_solid: false,
+        is_liquid: false,
+        is_climbable: false,
+    };
+    pub const SOLID: Self = Self {
+        is_solid: true,
+        is_liquid: false,
+        is_climbable: false,
+    };
+    pub const LIQUID: Self = Self {
+        is_solid: false,
+        is_liquid: true,
+        is_climbable: false,
+    };
+    pub const CLIMBABLE: Self = Self {
+        is_solid: false,
+        is_liquid: false,
+        is_climbable: true,
+    };
+}
+
+impl Voxel {
+    // 体素属性 从注册表中读取
+    pub fn properties(&self) -> VoxelProperties {
+        VOXEL_REGISTRY.properties(self.id)
+    }
+
+    pub fn is_solid(&self) -> bool {
+        self.properties().is_solid
+    }
+
+    pub fn is_liquid(&self) -> bool {
+        self.properties().is_liquid
+    }
+
+    pub fn is_climbable(&self) -> bool {
+        self.properties().is_climbable
+    }
+}
+
 impl MeshVoxel for Voxel {
     fn get_visibility(&self) -> VoxelVisibility {
         // 这里控制显示问题
         if VOXEL_MESH_MAP.contains_key(&self.id) {
             return VoxelVisibility::Empty;
         }
-        // 这里过滤掉水
-        if self.id > 0 && self.id != 5 {
+        // 这里过滤掉水等非实心的方块
+        if self.is_solid() {
             return VoxelVisibility::Opaque;
         }
         VoxelVisibility::Empty
@@ -193,3 +249,5 @@ voxel_material!(AppleWood, 苹果树原木, 10);
 voxel_material!(AppleLeaf, 苹果树叶子, 11);
 voxel_material!(TestCube, 测试方块, 12);
 voxel_material!(WorkCube, 工作方块, 13);
+voxel_material!(Lamp, 灯, 14);
+voxel_material!(LampOff, 熄灭的灯, 15);
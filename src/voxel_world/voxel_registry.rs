@@ -0,0 +1,642 @@
+// 体素注册表
+// 生成 网格 碰撞 交互 统一从这里读取体素的定义
+// 添加新的方块只需要在 default_registry 中注册一条
+
+use bevy::prelude::{Plugin, Resource};
+use lazy_static::lazy_static;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    light::MAX_LIGHT,
+    map_generator::GENERATED_VOXELS,
+    voxel::{
+        AppleLeaf, AppleWood, BasicStone, BuleGrass, DryGrass, Empty, Grass, Lamp, LampOff, Sand,
+        Soli, Sown, Stone, TestCube, Voxel, VoxelMaterial, VoxelProperties, Water, WorkCube,
+    },
+};
+
+// 默认的破坏时间(秒)
+pub const DEFAULT_HARDNESS: f32 = 2.0;
+
+// 材质的种类 决定哪种工具破坏得快
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaterialClass {
+    Stone,
+    Dirt,
+    Wood,
+    Leaves,
+    Other,
+}
+
+impl MaterialClass {
+    pub const COUNT: usize = 5;
+}
+
+// 工具的种类 没有拿工具时是 Hand
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ToolClass {
+    Hand,
+    Pickaxe,
+    Shovel,
+    Axe,
+}
+
+impl ToolClass {
+    pub const COUNT: usize = 4;
+}
+
+/**
+ * 破坏方块时使用的工具 在 staff.ron 中配置
+ * tier 是工具的等级 空手是 0 石头工具是 1
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Tool {
+    pub class: ToolClass,
+    pub tier: u8,
+}
+
+impl Tool {
+    pub const HAND: Self = Self {
+        class: ToolClass::Hand,
+        tier: 0,
+    };
+
+    pub fn new(class: ToolClass, tier: u8) -> Self {
+        Self { class, tier }
+    }
+}
+
+/**
+ * 破坏后的掉落
+ * 最多掉落 count 个 每一个按照 chance 的概率掉落
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VoxelDrop {
+    pub voxel: Voxel,
+    pub count: usize,
+    pub chance: f32,
+}
+
+impl VoxelDrop {
+    // 一定会掉落
+    pub fn new(voxel: Voxel, count: usize) -> Self {
+        Self {
+            voxel,
+            count,
+            chance: 1.0,
+        }
+    }
+
+    pub fn chance(mut self, chance: f32) -> Self {
+        self.chance = chance.clamp(0.0, 1.0);
+        self
+    }
+}
+
+// 面的序号 和 RIGHT_HANDED_Y_UP_CONFIG.faces 的顺序一致 -X -Y -Z +X +Y +Z
+pub const FACE_BOTTOM: u8 = 1;
+pub const FACE_TOP: u8 = 4;
+
+/**
+ * 方块的面贴图 路径相对于 assets
+ * 客户端按照这里的路径生成贴图数组 volex.ron 中只配置需要覆盖的部分
+ */
+#[derive(Debug, Clone, Default)]
+pub struct VoxelTextures {
+    // 没有单独配置的面使用的贴图 None 表示没有贴图
+    pub default: Option<&'static str>,
+    // 单独配置的面 (面的序号, 贴图)
+    pub faces: Vec<(u8, &'static str)>,
+}
+
+/**
+ * 体素定义
+ */
+#[derive(Debug, Clone)]
+pub struct VoxelDef {
+    pub id: u8,
+    pub name: &'static str,
+    pub cn_name: &'static str,
+    // 是否阻挡玩家
+    pub solid: bool,
+    // 是否是液体
+    pub liquid: bool,
+    // 是否可以攀爬
+    pub climbable: bool,
+    // 是否透明 瞄准时可以设置穿过透明的方块
+    pub transparent: bool,
+    // 硬度 空手破坏其他材质需要的时间(秒)
+    pub hardness: f32,
+    // 材质的种类 破坏速度还要乘以工具对这种材质的倍率
+    pub material: MaterialClass,
+    // 掉落需要的最低工具 None 表示空手也会掉落
+    pub requires_tool: Option<Tool>,
+    // 发光的亮度 0 表示不是光源
+    pub light: u8,
+    // 面贴图
+    pub textures: VoxelTextures,
+    // 破坏后掉落的体素 物品(比如苹果)的掉落在 staff.ron 中配置
+    pub drops: Vec<VoxelDrop>,
+    // 下面没有支撑时是否会掉落 比如沙子
+    pub falls: bool,
+    // 右键交互后切换成的体素 None 表示不能交互 右键时放置方块
+    pub toggle: Option<u8>,
+}
+
+impl VoxelDef {
+    // 默认是实心方块 掉落自己
+    pub fn new(id: u8, name: &'static str, cn_name: &'static str) -> Self {
+        Self {
+            id,
+            name,
+            cn_name,
+            solid: true,
+            liquid: false,
+            climbable: false,
+            transparent: false,
+            hardness: DEFAULT_HARDNESS,
+            material: MaterialClass::Other,
+            requires_tool: None,
+            light: 0,
+            textures: VoxelTextures::default(),
+            drops: vec![VoxelDrop::new(
+                Voxel {
+                    id,
+                    ..Default::default()
+                },
+                1,
+            )],
+            falls: false,
+            toggle: None,
+        }
+    }
+
+    pub fn air(mut self) -> Self {
+        self.solid = false;
+        self.drops.clear();
+        self
+    }
+
+    pub fn liquid(mut self) -> Self {
+        self.solid = false;
+        self.liquid = true;
+        self.transparent = true;
+        self.drops.clear();
+        self
+    }
+
+    pub fn climbable(mut self) -> Self {
+        self.solid = false;
+        self.climbable = true;
+        self
+    }
+
+    pub fn falls(mut self) -> Self {
+        self.falls = true;
+        self
+    }
+
+    pub fn transparent(mut self) -> Self {
+        self.transparent = true;
+        self
+    }
+
+    pub fn toggles(mut self, id: u8) -> Self {
+        self.toggle = Some(id);
+        self
+    }
+
+    pub fn hardness(mut self, hardness: f32) -> Self {
+        self.hardness = hardness;
+        self
+    }
+
+    pub fn material(mut self, material: MaterialClass) -> Self {
+        self.material = material;
+        self
+    }
+
+    // 需要这种工具并且不低于这个等级才会掉落
+    pub fn requires_tool(mut self, class: ToolClass, tier: u8) -> Self {
+        self.requires_tool = Some(Tool::new(class, tier));
+        self
+    }
+
+    pub fn light(mut self, level: u8) -> Self {
+        self.light = level.min(MAX_LIGHT);
+        self
+    }
+
+    // 所有面使用的贴图
+    pub fn texture(mut self, path: &'static str) -> Self {
+        self.textures.default = Some(path);
+        self
+    }
+
+    // 单独配置一个面的贴图
+    pub fn face_texture(mut self, face: u8, path: &'static str) -> Self {
+        self.textures.faces.push((face, path));
+        self
+    }
+
+    pub fn drops(mut self, drops: Vec<VoxelDrop>) -> Self {
+        self.drops = drops;
+        self
+    }
+
+    pub fn properties(&self) -> VoxelProperties {
+        VoxelProperties {
+            is_solid: self.solid,
+            is_liquid: self.liquid,
+            is_climbable: self.climbable,
+        }
+    }
+}
+
+// 通过材质类型创建定义
+#[macro_export]
+macro_rules! voxel_def {
+    ($types: ident) => {
+        $crate::voxel_world::voxel_registry::VoxelDef::new(
+            <$types as $crate::voxel_world::voxel::VoxelMaterial>::ID,
+            $types::NAME,
+            $types::CN_NAME,
+        )
+    };
+}
+
+#[derive(Debug, Clone, Resource)]
+pub struct VoxelRegistry {
+    // 使用id作为下标
+    defs: Vec<Option<VoxelDef>>,
+    // 工具对材质的破坏速度倍率 [工具][材质]
+    tool_speeds: [[f32; MaterialClass::COUNT]; ToolClass::COUNT],
+}
+
+impl VoxelRegistry {
+    pub fn builder() -> VoxelRegistryBuilder {
+        VoxelRegistryBuilder {
+            defs: vec![None; u8::MAX as usize + 1],
+            tool_speeds: [[1.0; MaterialClass::COUNT]; ToolClass::COUNT],
+        }
+    }
+
+    pub fn get(&self, id: u8) -> Option<&VoxelDef> {
+        self.defs[id as usize].as_ref()
+    }
+
+    pub fn contains(&self, id: u8) -> bool {
+        self.defs[id as usize].is_some()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &VoxelDef> {
+        self.defs.iter().flatten()
+    }
+
+    // 按照英文名或者中文名查找 英文名不区分大小写
+    pub fn find_by_name(&self, name: &str) -> Option<&VoxelDef> {
+        self.iter()
+            .find(|def| def.name.eq_ignore_ascii_case(name) || def.cn_name == name)
+    }
+
+    // 没有注册的体素 当做实心方块处理
+    pub fn properties(&self, id: u8) -> VoxelProperties {
+        match self.get(id) {
+            Some(def) => def.properties(),
+            None => VoxelProperties::SOLID,
+        }
+    }
+
+    pub fn hardness(&self, id: u8) -> f32 {
+        match self.get(id) {
+            Some(def) => def.hardness,
+            None => DEFAULT_HARDNESS,
+        }
+    }
+
+    // 工具破坏这种材质的速度倍率 没有配置的组合是 1
+    pub fn tool_speed(&self, class: ToolClass, material: MaterialClass) -> f32 {
+        self.tool_speeds[class as usize][material as usize]
+    }
+
+    // 使用工具破坏体素需要的时间(秒)
+    pub fn break_time(&self, id: u8, tool: Tool) -> f32 {
+        let material = self
+            .get(id)
+            .map_or(MaterialClass::Other, |def| def.material);
+        self.hardness(id) / self.tool_speed(tool.class, material)
+    }
+
+    /**
+     * 使用工具破坏后是否掉落
+     * 需要工具的体素 工具的种类要一致并且等级不能更低
+     */
+    pub fn can_harvest(&self, id: u8, tool: Tool) -> bool {
+        match self.get(id).and_then(|def| def.requires_tool) {
+            Some(required) => tool.class == required.class && tool.tier >= required.tier,
+            None => true,
+        }
+    }
+
+    pub fn light(&self, id: u8) -> u8 {
+        match self.get(id) {
+            Some(def) => def.light,
+            None => 0,
+        }
+    }
+
+    // 下面没有支撑时是否会掉落
+    pub fn falls(&self, id: u8) -> bool {
+        self.get(id).map_or(false, |def| def.falls)
+    }
+
+    // 没有注册的体素不透明
+    pub fn transparent(&self, id: u8) -> bool {
+        self.get(id).map_or(false, |def| def.transparent)
+    }
+
+    // 右键时是否交互
+    pub fn interactable(&self, id: u8) -> bool {
+        self.get(id).map_or(false, |def| def.toggle.is_some())
+    }
+
+    /**
+     * 交互后的体素 保留原来的方向
+     * 不能交互时返回 None
+     */
+    pub fn interact(&self, voxel: Voxel) -> Option<Voxel> {
+        let id = self.get(voxel.id)?.toggle?;
+        Some(Voxel { id, ..voxel })
+    }
+
+    /**
+     * 破坏体素时实际掉落的体素和数量
+     * 没有注册的体素不掉落
+     */
+    pub fn roll_drops(&self, id: u8, rng: &mut impl Rng) -> Vec<(Voxel, usize)> {
+        let Some(def) = self.get(id) else {
+            return Vec::new();
+        };
+        def.drops
+            .iter()
+            .filter_map(|drop| {
+                let count = (0..drop.count)
+                    .filter(|_| rng.gen_bool(drop.chance as f64))
+                    .count();
+                (count > 0).then_some((drop.voxel, count))
+            })
+            .collect()
+    }
+
+    /**
+     * 校验所有使用的体素都已经注册了
+     */
+    pub fn validate(&self, ids: &[u8]) {
+        for id in ids {
+            if !self.contains(*id) {
+                panic!("体素[{}]没有在 VoxelRegistry 中注册", id);
+            }
+        }
+    }
+
+    pub fn default_registry() -> Self {
+        VoxelRegistry::builder()
+            // 空手和用错工具时破坏石头和木头很慢 对应的工具快很多
+            .tool_speed(ToolClass::Hand, MaterialClass::Stone, 0.5)
+            .tool_speed(ToolClass::Hand, MaterialClass::Wood, 0.5)
+            .tool_speed(ToolClass::Shovel, MaterialClass::Stone, 0.5)
+            .tool_speed(ToolClass::Axe, MaterialClass::Stone, 0.5)
+            .tool_speed(ToolClass::Pickaxe, MaterialClass::Wood, 0.5)
+            .tool_speed(ToolClass::Shovel, MaterialClass::Wood, 0.5)
+            .tool_speed(ToolClass::Pickaxe, MaterialClass::Stone, 4.0)
+            .tool_speed(ToolClass::Shovel, MaterialClass::Dirt, 4.0)
+            .tool_speed(ToolClass::Axe, MaterialClass::Wood, 4.0)
+            .tool_speed(ToolClass::Axe, MaterialClass::Leaves, 2.0)
+            .register(voxel_def!(Empty).air())
+            .register(
+                voxel_def!(Stone)
+                    .material(MaterialClass::Stone)
+                    .texture("textures/002.png"),
+            )
+            .register(
+                voxel_def!(Soli)
+                    .material(MaterialClass::Dirt)
+                    .texture("textures/003.png"),
+            )
+            // 草地被破坏后变成泥土
+            .register(
+                voxel_def!(Grass)
+                    .material(MaterialClass::Dirt)
+                    .texture("textures/grass_a.png")
+                    .face_texture(FACE_TOP, "textures/草坪.png")
+                    .face_texture(FACE_BOTTOM, "textures/003.png")
+                    .drops(vec![VoxelDrop::new(Soli::into_voxel(), 1)]),
+            )
+            // 雪需要铲子才会掉落
+            .register(
+                voxel_def!(Sown)
+                    .material(MaterialClass::Dirt)
+                    .texture("textures/雪.png")
+                    .requires_tool(ToolClass::Shovel, 1),
+            )
+            .register(voxel_def!(Water).liquid().texture("textures/水.png"))
+            .register(
+                voxel_def!(Sand)
+                    .material(MaterialClass::Dirt)
+                    .texture("textures/沙子.png")
+                    .falls(),
+            )
+            .register(
+                voxel_def!(BasicStone)
+                    .material(MaterialClass::Stone)
+                    .texture("textures/基岩.png"),
+            )
+            .register(
+                voxel_def!(DryGrass)
+                    .material(MaterialClass::Dirt)
+                    .texture("textures/干草侧面.png")
+                    .face_texture(FACE_TOP, "textures/干草地.png")
+                    .face_texture(FACE_BOTTOM, "textures/003.png"),
+            )
+            .register(
+                voxel_def!(BuleGrass)
+                    .material(MaterialClass::Dirt)
+                    .texture("textures/苍翠侧面.png")
+                    .face_texture(FACE_TOP, "textures/苍翠地.png")
+                    .face_texture(FACE_BOTTOM, "textures/003.png"),
+            )
+            .register(
+                voxel_def!(AppleWood)
+                    .material(MaterialClass::Wood)
+                    .texture("textures/苹果树A面.png")
+                    .face_texture(FACE_TOP, "textures/苹果树B面.png")
+                    .face_texture(FACE_BOTTOM, "textures/苹果树B面.png"),
+            )
+            // 树叶很少掉落自己 苹果和树枝在 staff.ron 中配置
+            .register(
+                voxel_def!(AppleLeaf)
+                    .material(MaterialClass::Leaves)
+                    .transparent()
+                    .texture("textures/苹果叶子.png")
+                    .drops(vec![VoxelDrop::new(AppleLeaf::into_voxel(), 1).chance(0.05)]),
+            )
+            // 每个面不同的贴图 用来检查方向
+            .register(
+                voxel_def!(TestCube)
+                    .texture("textures/测试6.png")
+                    .face_texture(1, "textures/测试1.png")
+                    .face_texture(2, "textures/测试2.png")
+                    .face_texture(3, "textures/测试3.png")
+                    .face_texture(4, "textures/测试4.png")
+                    .face_texture(5, "textures/测试5.png"),
+            )
+            .register(voxel_def!(WorkCube))
+            // 右键开关灯 熄灭的灯被破坏后掉落灯
+            .register(
+                voxel_def!(Lamp)
+                    .light(14)
+                    .texture("textures/001.png")
+                    .toggles(LampOff::ID),
+            )
+            .register(
+                voxel_def!(LampOff)
+                    .toggles(Lamp::ID)
+                    .drops(vec![VoxelDrop::new(Lamp::into_voxel(), 1)]),
+            )
+            .build()
+    }
+}
+
+pub struct VoxelRegistryBuilder {
+    defs: Vec<Option<VoxelDef>>,
+    tool_speeds: [[f32; MaterialClass::COUNT]; ToolClass::COUNT],
+}
+
+impl VoxelRegistryBuilder {
+    pub fn tool_speed(mut self, class: ToolClass, material: MaterialClass, speed: f32) -> Self {
+        if !speed.is_finite() || speed <= 0.0 {
+            panic!("{:?}对{:?}的破坏速度必须大于0: {}", class, material, speed);
+        }
+        self.tool_speeds[class as usize][material as usize] = speed;
+        self
+    }
+
+    pub fn register(mut self, def: VoxelDef) -> Self {
+        if let Some(old) = &self.defs[def.id as usize] {
+            panic!("体素id[{}]重复注册: {} 和 {}", def.id, old.name, def.name);
+        }
+        let index = def.id as usize;
+        self.defs[index] = Some(def);
+        self
+    }
+
+    pub fn build(self) -> VoxelRegistry {
+        VoxelRegistry {
+            defs: self.defs,
+            tool_speeds: self.tool_speeds,
+        }
+    }
+}
+
+lazy_static! {
+    // 异步任务中也需要读取 所以保留一份全局的
+    pub static ref VOXEL_REGISTRY: VoxelRegistry = VoxelRegistry::default_registry();
+}
+
+pub struct VoxelRegistryPlugin;
+
+impl Plugin for VoxelRegistryPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        // 启动时校验生成器使用到的体素
+        VOXEL_REGISTRY.validate(&GENERATED_VOXELS);
+        app.insert_resource(VOXEL_REGISTRY.clone());
+    }
+}
+
+#[test]
+fn test_roll_drops() {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    let registry = VoxelRegistry::default_registry();
+    let mut rng = StdRng::seed_from_u64(7);
+    // 默认掉落自己
+    assert_eq!(
+        registry.roll_drops(Stone::ID, &mut rng),
+        vec![(Stone::into_voxel(), 1)]
+    );
+    assert_eq!(
+        registry.roll_drops(Grass::ID, &mut rng),
+        vec![(Soli::into_voxel(), 1)]
+    );
+    assert!(registry.roll_drops(Water::ID, &mut rng).is_empty());
+    assert!(registry.roll_drops(Empty::ID, &mut rng).is_empty());
+
+    // 树叶按照概率掉落
+    let leaves: usize = (0..1000)
+        .map(|_| registry.roll_drops(AppleLeaf::ID, &mut rng).len())
+        .sum();
+    assert!(leaves > 0 && leaves < 200, "树叶掉落了{}次", leaves);
+}
+
+#[test]
+fn test_interact() {
+    use super::voxel::VoxelDirection;
+
+    let registry = VoxelRegistry::default_registry();
+    assert!(registry.interactable(Lamp::ID));
+    assert!(!registry.interactable(Stone::ID));
+    assert_eq!(registry.interact(Stone::into_voxel()), None);
+    // 开关后保留方向 再次交互切换回来
+    let lamp = Lamp::into_voxel_with_dir(VoxelDirection::X);
+    let off = registry.interact(lamp).unwrap();
+    assert_eq!(off, LampOff::into_voxel_with_dir(VoxelDirection::X));
+    assert_eq!(registry.light(off.id), 0);
+    assert_eq!(registry.interact(off), Some(lamp));
+}
+
+#[test]
+fn test_tool_speed() {
+    let registry = VoxelRegistry::default_registry();
+    let pickaxe = Tool::new(ToolClass::Pickaxe, 1);
+    let shovel = Tool::new(ToolClass::Shovel, 1);
+    assert_eq!(
+        registry.tool_speed(ToolClass::Pickaxe, MaterialClass::Stone),
+        4.0
+    );
+    assert_eq!(
+        registry.tool_speed(ToolClass::Hand, MaterialClass::Stone),
+        0.5
+    );
+    // 没有配置的组合不影响速度
+    assert_eq!(
+        registry.tool_speed(ToolClass::Hand, MaterialClass::Other),
+        1.0
+    );
+
+    // 对应的工具比空手和用错工具快
+    let hand_time = registry.break_time(Stone::ID, Tool::HAND);
+    assert_eq!(hand_time, DEFAULT_HARDNESS * 2.0);
+    assert_eq!(
+        registry.break_time(Stone::ID, pickaxe),
+        DEFAULT_HARDNESS / 4.0
+    );
+    assert_eq!(registry.break_time(Stone::ID, shovel), hand_time);
+    assert!(registry.break_time(Soli::ID, shovel) < registry.break_time(Soli::ID, pickaxe));
+    // 没有注册的体素按照默认硬度和 Other 材质
+    assert_eq!(registry.break_time(200, pickaxe), DEFAULT_HARDNESS);
+}
+
+#[test]
+fn test_can_harvest() {
+    let registry = VoxelRegistry::default_registry();
+    // 不需要工具的体素空手也会掉落
+    assert!(registry.can_harvest(Stone::ID, Tool::HAND));
+    assert!(registry.can_harvest(200, Tool::HAND));
+    // 需要工具的体素 种类和等级都要满足
+    assert!(!registry.can_harvest(Sown::ID, Tool::HAND));
+    assert!(!registry.can_harvest(Sown::ID, Tool::new(ToolClass::Pickaxe, 3)));
+    assert!(!registry.can_harvest(Sown::ID, Tool::new(ToolClass::Shovel, 0)));
+    assert!(registry.can_harvest(Sown::ID, Tool::new(ToolClass::Shovel, 1)));
+    assert!(registry.can_harvest(Sown::ID, Tool::new(ToolClass::Shovel, 2)));
+}
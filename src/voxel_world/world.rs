@@ -0,0 +1,255 @@
+// 多个世界
+// 所有世界共用一个区块空间 第 n 个世界的原点在 x = n * WORLD_SPACING 格 第 0 个世界在原点
+// 区块的请求 修改 碰撞和存档都按照位置自然地分开 玩家所在的世界由位置决定
+// 服务端只回复玩家所在世界的区块请求 区块的更新和聊天只发给同一个世界的玩家
+// 每个世界可以使用自己的种子 或者生成平坦的地形
+
+use std::sync::RwLock;
+
+use bevy::prelude::Vec3;
+use lazy_static::lazy_static;
+use ndshape::ConstShape;
+use serde::{Deserialize, Serialize};
+
+use crate::{ChunkShape, CHUNK_SIZE, CHUNK_SIZE_U32, CHUNK_VOLUME};
+
+use super::{
+    biomes::{PanelShape, TreeGentor},
+    chunk::ChunkKey,
+    gen_config::{gen_config, WorldHeight},
+    map_generator::{gen_chunk_data_by_seed, gen_flat_chunk, terrain_tops},
+    voxel::{BasicStone, Voxel, VoxelMaterial},
+};
+
+// 相邻两个世界原点的距离(区块) 不能太大 浮点数的精度会影响物理
+// 最远的世界在 x = 15 * 8192 附近 f32 的精度还有 1/128 格
+pub const WORLD_SPACING_CHUNKS: i32 = 512;
+pub const WORLD_SPACING: i32 = WORLD_SPACING_CHUNKS * CHUNK_SIZE;
+// 最多的世界数
+pub const MAX_WORLDS: usize = 16;
+
+// 世界的编号 也就是在配置中的顺序
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct WorldId(pub u8);
+
+impl WorldId {
+    pub const MAIN: Self = Self(0);
+
+    // 区块所在的世界 在所有世界的范围之外时返回 None
+    pub fn of_chunk(chunk_key: ChunkKey) -> Option<Self> {
+        let index = (chunk_key.0.x + WORLD_SPACING_CHUNKS / 2).div_euclid(WORLD_SPACING_CHUNKS);
+        (0..MAX_WORLDS as i32)
+            .contains(&index)
+            .then_some(Self(index as u8))
+    }
+
+    pub fn of_position(position: Vec3) -> Option<Self> {
+        let x = position.x.floor() as i32;
+        let index = (x + WORLD_SPACING / 2).div_euclid(WORLD_SPACING);
+        (0..MAX_WORLDS as i32)
+            .contains(&index)
+            .then_some(Self(index as u8))
+    }
+
+    // 世界原点的 x 坐标
+    pub fn origin_x(&self) -> f32 {
+        (self.0 as i32 * WORLD_SPACING) as f32
+    }
+}
+
+// 位置所在世界的原点 x 在所有世界之外时使用主世界
+pub fn world_origin_x(x: f32) -> f32 {
+    WorldId::of_position(Vec3::new(x, 0.0, 0.0)).map_or(0.0, |world| world.origin_x())
+}
+
+/**
+ * 一个世界的配置
+ * 没有设置 seed 时使用服务端的种子
+ */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorldConfig {
+    pub name: String,
+    pub seed: Option<i32>,
+    // 平坦的地形 高度和水使用生成配置中的 flat
+    pub flat: bool,
+}
+
+impl Default for WorldConfig {
+    fn default() -> Self {
+        Self {
+            name: String::from("overworld"),
+            seed: None,
+            flat: false,
+        }
+    }
+}
+
+impl WorldConfig {
+    // 默认的两个世界 普通的世界和平坦的创造世界
+    pub fn defaults() -> Vec<Self> {
+        vec![
+            Self::default(),
+            Self {
+                name: String::from("creative"),
+                seed: None,
+                flat: true,
+            },
+        ]
+    }
+}
+
+lazy_static! {
+    // 生成线程中也需要读取
+    static ref WORLDS: RwLock<Vec<WorldConfig>> = RwLock::new(Vec::new());
+}
+
+pub fn set_worlds(worlds: Vec<WorldConfig>) {
+    *WORLDS.write().unwrap() = worlds;
+}
+
+pub fn world_config(world: WorldId) -> Option<WorldConfig> {
+    WORLDS.read().unwrap().get(world.0 as usize).cloned()
+}
+
+/**
+ * 位置是否在创造世界(平坦的世界)中
+ * 创造世界中所有人都可以飞行和使用笔刷 修改方块不受高度限制
+ */
+pub fn is_creative(position: Vec3) -> bool {
+    WorldId::of_position(position)
+        .and_then(world_config)
+        .map_or(false, |world| world.flat)
+}
+
+// 按照名字查找 不区分大小写
+pub fn find_world(name: &str) -> Option<(WorldId, WorldConfig)> {
+    WORLDS
+        .read()
+        .unwrap()
+        .iter()
+        .enumerate()
+        .find(|(_, world)| world.name.eq_ignore_ascii_case(name))
+        .map(|(index, world)| (WorldId(index as u8), world.clone()))
+}
+
+pub fn world_names() -> Vec<String> {
+    WORLDS
+        .read()
+        .unwrap()
+        .iter()
+        .map(|world| world.name.clone())
+        .collect()
+}
+
+/**
+ * 生成区块 按照区块所在的世界选择种子和生成方式
+ * 没有配置的位置使用 seed 生成 世界高度之外的区块直接返回空气
+ */
+pub fn gen_world_chunk(
+    seed: i32,
+    chunk_key: ChunkKey,
+) -> (Vec<Voxel>, Vec<(Vec<ChunkKey>, TreeGentor)>) {
+    let config = gen_config();
+    if !config.world_height.contains(chunk_key.0.y) {
+        return (vec![Voxel::EMPTY; CHUNK_VOLUME as usize], Vec::new());
+    }
+    let (mut voxels, mut trees) = match WorldId::of_chunk(chunk_key).and_then(world_config) {
+        Some(world) if world.flat => {
+            let flat = config.flat.unwrap_or_default();
+            (gen_flat_chunk(chunk_key, &config, flat), Vec::new())
+        }
+        Some(world) => gen_chunk_data_by_seed(world.seed.unwrap_or(seed), chunk_key),
+        None => gen_chunk_data_by_seed(seed, chunk_key),
+    };
+    bound_world_height(chunk_key, config.world_height, &mut voxels, &mut trees);
+    (voxels, trees)
+}
+
+/**
+ * 最低一层区块的底部铺满基岩
+ * 树不会长到世界高度之外的区块
+ */
+pub fn bound_world_height(
+    chunk_key: ChunkKey,
+    height: WorldHeight,
+    voxels: &mut [Voxel],
+    trees: &mut [(Vec<ChunkKey>, TreeGentor)],
+) {
+    if chunk_key.0.y == height.min_chunk_y {
+        for x in 0..CHUNK_SIZE_U32 {
+            for z in 0..CHUNK_SIZE_U32 {
+                voxels[ChunkShape::linearize([x, 0, z]) as usize] = BasicStone::into_voxel();
+            }
+        }
+    }
+    for (keys, _) in trees.iter_mut() {
+        keys.retain(|key| height.contains(key.0.y));
+    }
+}
+
+// 和 gen_world_chunk 一致的地形高度 用来寻找出生点
+pub fn world_terrain_tops(seed: i32, chunk_key: ChunkKey) -> Vec<f32> {
+    let config = gen_config();
+    match WorldId::of_chunk(chunk_key).and_then(world_config) {
+        Some(world) if world.flat => {
+            let flat = config.flat.unwrap_or_default();
+            vec![flat.height; PanelShape::SIZE as usize]
+        }
+        Some(world) => terrain_tops(chunk_key, world.seed.unwrap_or(seed), &config),
+        None => terrain_tops(chunk_key, seed, &config),
+    }
+}
+
+#[test]
+fn test_world_layout() {
+    use bevy::prelude::IVec3;
+
+    assert_eq!(
+        WorldId::of_chunk(ChunkKey(IVec3::new(-10, 0, 5))),
+        Some(WorldId::MAIN)
+    );
+    assert_eq!(
+        WorldId::of_chunk(ChunkKey(IVec3::new(WORLD_SPACING_CHUNKS + 3, 0, 0))),
+        Some(WorldId(1))
+    );
+    assert_eq!(
+        WorldId::of_chunk(ChunkKey(IVec3::new(-WORLD_SPACING_CHUNKS, 0, 0))),
+        None
+    );
+    assert_eq!(
+        WorldId::of_position(Vec3::new(WorldId(1).origin_x() - 20.0, 0.0, 0.0)),
+        Some(WorldId(1))
+    );
+    // 区块和位置的换算一致
+    let chunk_key = ChunkKey(IVec3::new(WORLD_SPACING_CHUNKS / 2, 0, 0));
+    let block_x = (chunk_key.0.x * CHUNK_SIZE) as f32;
+    assert_eq!(
+        WorldId::of_chunk(chunk_key),
+        WorldId::of_position(Vec3::new(block_x, 0.0, 0.0))
+    );
+}
+
+#[test]
+fn test_bound_world_height() {
+    use bevy::prelude::IVec3;
+
+    let height = WorldHeight {
+        min_chunk_y: -2,
+        max_chunk_y: 3,
+    };
+    assert!(height.contains(-2) && height.contains(3));
+    assert!(!height.contains(-3) && !height.contains(4));
+
+    // 最低一层的底部是基岩 上面不变
+    let bottom = ChunkKey(IVec3::new(0, -2, 0));
+    let mut voxels = vec![Voxel::EMPTY; CHUNK_VOLUME as usize];
+    bound_world_height(bottom, height, &mut voxels, &mut []);
+    for (i, voxel) in voxels.iter().enumerate() {
+        let [_, y, _] = ChunkShape::delinearize(i as u32);
+        assert_eq!(voxel.id == BasicStone::ID, y == 0);
+    }
+    let mut voxels = vec![Voxel::EMPTY; CHUNK_VOLUME as usize];
+    bound_world_height(ChunkKey(IVec3::new(0, 0, 0)), height, &mut voxels, &mut []);
+    assert!(voxels.iter().all(|voxel| *voxel == Voxel::EMPTY));
+}